@@ -0,0 +1,95 @@
+//! Benchmarks for deserialising big tables with Serde.
+//!
+//! Run with `cargo bench -p serde_luaq --bench de`.
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use serde::Deserialize;
+use serde_luaq::{from_value, lua_value, LuaValue};
+use std::{collections::BTreeMap, fmt::Write as _, hint::black_box};
+
+const ENTRIES: usize = 10_000;
+const MAX_DEPTH: u16 = 16;
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct Item<'a> {
+    name: &'a str,
+    count: i64,
+    price: f64,
+    enabled: bool,
+}
+
+/// Parses `lua`, leaking it so that values can borrow from it.
+fn parse(lua: String) -> LuaValue<'static> {
+    lua_value(lua.leak().as_bytes(), MAX_DEPTH).unwrap()
+}
+
+fn numbers() -> LuaValue<'static> {
+    let mut lua = String::from("{");
+    for i in 0..ENTRIES {
+        write!(lua, "{i}, ").unwrap();
+    }
+    lua.push('}');
+    parse(lua)
+}
+
+fn strings() -> LuaValue<'static> {
+    let mut lua = String::from("{");
+    for i in 0..ENTRIES {
+        write!(lua, "\"item{i}\", ").unwrap();
+    }
+    lua.push('}');
+    parse(lua)
+}
+
+fn map() -> LuaValue<'static> {
+    let mut lua = String::from("{");
+    for i in 0..ENTRIES {
+        write!(lua, "item{i} = {i}, [\"item {i}\"] = {i}, ").unwrap();
+    }
+    lua.push('}');
+    parse(lua)
+}
+
+fn structs() -> LuaValue<'static> {
+    let mut lua = String::from("{");
+    for i in 0..ENTRIES {
+        write!(
+            lua,
+            "{{name = \"item{i}\", count = {i}, price = {i}.5, enabled = true}}, "
+        )
+        .unwrap();
+    }
+    lua.push('}');
+    parse(lua)
+}
+
+/// Benchmarks deserialising `$v` into `$ty`, both with [`from_value()`] (which copies the value
+/// first) and by consuming an owned value (which only measures the deserialiser).
+macro_rules! bench {
+    ($c:expr, $v:expr, $name:literal, $ty:ty) => {{
+        let v = $v;
+        let mut group = $c.benchmark_group($name);
+        group.throughput(Throughput::Elements(ENTRIES as u64));
+        group.bench_function("from_value", |b| {
+            b.iter(|| black_box(from_value::<$ty>(&v).unwrap()))
+        });
+        group.bench_function("deserialize", |b| {
+            b.iter_batched(
+                || v.clone(),
+                |v| black_box(<$ty>::deserialize(v).unwrap()),
+                BatchSize::LargeInput,
+            )
+        });
+        group.finish();
+    }};
+}
+
+fn bench_de(c: &mut Criterion) {
+    bench!(c, numbers(), "Vec<i64>", Vec<i64>);
+    bench!(c, strings(), "Vec<&str>", Vec<&str>);
+    bench!(c, map(), "BTreeMap<String, i64>", BTreeMap<String, i64>);
+    bench!(c, structs(), "Vec<struct>", Vec<Item>);
+}
+
+criterion_group!(benches, bench_de);
+criterion_main!(benches);
@@ -9,7 +9,7 @@
 //! statement.
 use clap::Parser;
 use flate2::bufread::DeflateDecoder;
-use serde_luaq::{return_statement, to_json_value};
+use serde_luaq::{return_statement, to_json_value, JsonConversionOptions};
 use std::{
     fs::File,
     io::{stdout, BufReader, BufWriter, Read, Write},
@@ -53,7 +53,10 @@ fn main() -> Result {
     let mut buf = Vec::with_capacity(SIZE_LIMIT);
     f.read_to_end(&mut buf)?;
 
-    let map = to_json_value(return_statement(&buf, 16)?, &Default::default())?;
+    let map = to_json_value(
+        return_statement(&buf, 16)?,
+        JsonConversionOptions::default(),
+    )?;
 
     let mut f: Box<dyn Write> = if let Some(output) = args.output {
         Box::new(BufWriter::new(
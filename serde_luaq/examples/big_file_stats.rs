@@ -0,0 +1,233 @@
+//! Prints statistics about the values in a large Lua script, like a game's data dump.
+//!
+//! Statements are parsed one at a time with [`Parser::script_iter()`], so only one global
+//! variable's value is held in memory at once (as well as the input file).
+//!
+//! With `--timeout`, this gives up on files which take too long to parse.
+use clap::Parser as _;
+use serde_luaq::{
+    CancellationToken, LuaNumber, LuaTableEntry, LuaValue, LuaVisitor, Parser, VisitAction,
+};
+use std::{fmt::Write as _, fs, path::PathBuf, time::Duration};
+
+/// Default maximum table depth.
+const DEFAULT_MAX_DEPTH: u16 = 64;
+
+/// Number of largest global variables to list.
+const LARGEST: usize = 5;
+
+type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Prints statistics about the values in a large Lua script.
+#[derive(clap::Parser, Debug)]
+#[command(name = "big_file_stats", version, about, long_about = None, verbatim_doc_comment, rename_all = "snake_case")]
+struct Args {
+    /// Input Lua script filename, will be loaded entirely into memory.
+    #[arg()]
+    input: PathBuf,
+
+    /// Maximum table depth.
+    #[arg(short = 'd', long, default_value_t = DEFAULT_MAX_DEPTH)]
+    max_depth: u16,
+
+    /// Give up after this many seconds.
+    #[arg(short, long)]
+    timeout: Option<u64>,
+}
+
+/// Counts of values, collected by walking them.
+#[derive(Debug, Default, PartialEq)]
+struct Stats {
+    globals: usize,
+    tables: usize,
+    entries: usize,
+    strings: usize,
+    string_bytes: usize,
+    longest_string: usize,
+    integers: usize,
+    floats: usize,
+    booleans: usize,
+    nils: usize,
+    max_depth: usize,
+
+    /// Current table depth while walking.
+    depth: usize,
+
+    /// Global variables with the largest values, as `(heap size, name)`.
+    largest: Vec<(usize, String)>,
+}
+
+impl<'a> LuaVisitor<'a> for Stats {
+    fn enter_value(&mut self, value: &mut LuaValue<'a>) -> VisitAction {
+        match value {
+            LuaValue::Nil => self.nils += 1,
+            LuaValue::Boolean(_) => self.booleans += 1,
+            LuaValue::Number(LuaNumber::Integer(_)) => self.integers += 1,
+            LuaValue::Number(LuaNumber::Float(_)) => self.floats += 1,
+            LuaValue::String(s) => {
+                self.strings += 1;
+                self.string_bytes += s.len();
+                self.longest_string = self.longest_string.max(s.len());
+            }
+            LuaValue::Table(_) => {
+                self.tables += 1;
+                self.depth += 1;
+                self.max_depth = self.max_depth.max(self.depth);
+            }
+        }
+        VisitAction::Continue
+    }
+
+    fn exit_value(&mut self, value: &mut LuaValue<'a>) -> VisitAction {
+        if let LuaValue::Table(_) = value {
+            self.depth -= 1;
+        }
+        VisitAction::Continue
+    }
+
+    fn enter_entry(&mut self, _: &mut LuaTableEntry<'a>) -> VisitAction {
+        self.entries += 1;
+        VisitAction::Continue
+    }
+}
+
+impl Stats {
+    /// Collects statistics for every statement in `b`.
+    fn collect(b: &[u8], parser: &Parser) -> Result<Self> {
+        let mut stats = Self::default();
+        for statement in parser.script_iter(b) {
+            let mut statement = statement?;
+            stats.globals += 1;
+            stats
+                .largest
+                .push((statement.value.heap_size(), statement.name.to_string()));
+            stats
+                .largest
+                .sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+            stats.largest.truncate(LARGEST);
+
+            statement.value.walk(&mut stats);
+        }
+        Ok(stats)
+    }
+
+    fn report(&self) -> Result<String> {
+        let mut out = String::new();
+        writeln!(out, "globals: {}", self.globals)?;
+        writeln!(out, "tables: {} ({} entries)", self.tables, self.entries)?;
+        writeln!(
+            out,
+            "strings: {} ({} bytes, longest {})",
+            self.strings, self.string_bytes, self.longest_string
+        )?;
+        writeln!(out, "integers: {}", self.integers)?;
+        writeln!(out, "floats: {}", self.floats)?;
+        writeln!(out, "booleans: {}", self.booleans)?;
+        writeln!(out, "nils: {}", self.nils)?;
+        writeln!(out, "max depth: {}", self.max_depth)?;
+        writeln!(out, "largest globals:")?;
+        for (size, name) in &self.largest {
+            writeln!(out, "  {name}: {size} bytes")?;
+        }
+        Ok(out)
+    }
+}
+
+fn main() -> Result {
+    let args = Args::parse();
+    let b = fs::read(args.input)?;
+
+    let token = args
+        .timeout
+        .map(|t| CancellationToken::with_timeout(Duration::from_secs(t)));
+    let parser = Parser::builder()
+        .max_depth(args.max_depth)
+        .cancellation(token)
+        .build();
+
+    print!("{}", Stats::collect(&b, &parser)?.report()?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_luaq::Error;
+
+    #[test]
+    fn stats() {
+        let b = br#"
+            version = 3
+            ratio = 0.5
+            enabled = true
+            items = {"sword", "shield", {name = "potion", count = 2, extra = nil}}
+            matrix = {{1, 2}, {3, {4}}}
+        "#;
+        let stats = Stats::collect(b, &Parser::default()).unwrap();
+
+        assert_eq!(5, stats.globals);
+        assert_eq!(6, stats.tables);
+        assert_eq!(13, stats.entries);
+        assert_eq!(3, stats.strings);
+        assert_eq!(17, stats.string_bytes);
+        assert_eq!(6, stats.longest_string);
+        assert_eq!(6, stats.integers);
+        assert_eq!(1, stats.floats);
+        assert_eq!(1, stats.booleans);
+        assert_eq!(1, stats.nils);
+        assert_eq!(3, stats.max_depth);
+        assert_eq!(0, stats.depth);
+        assert_eq!(
+            vec!["items", "matrix", "enabled", "ratio", "version"],
+            stats
+                .largest
+                .iter()
+                .map(|(_, n)| n.as_str())
+                .collect::<Vec<_>>(),
+        );
+
+        let report = stats.report().unwrap();
+        assert!(
+            report.starts_with("globals: 5\ntables: 6 (13 entries)\n"),
+            "{report}"
+        );
+    }
+
+    #[test]
+    fn big_file() {
+        const N: usize = 10_000;
+        let mut b = String::new();
+        for i in 0..N {
+            writeln!(
+                b,
+                "g{i} = {{id = {i}, name = \"item {i}\", tags = {{\"a\", \"b\"}}}}"
+            )
+            .unwrap();
+        }
+        let b = b.into_bytes();
+
+        let stats = Stats::collect(&b, &Parser::default()).unwrap();
+        assert_eq!(N, stats.globals);
+        assert_eq!(N * 2, stats.tables);
+        assert_eq!(N * 5, stats.entries);
+        assert_eq!(N * 3, stats.strings);
+        assert_eq!(N, stats.integers);
+        assert_eq!(2, stats.max_depth);
+        assert_eq!(LARGEST, stats.largest.len());
+
+        // A cancelled token stops parsing.
+        let token = CancellationToken::new();
+        token.cancel();
+        let parser = Parser::builder().cancellation(Some(token)).build();
+        let e = *Stats::collect(&b, &parser)
+            .unwrap_err()
+            .downcast::<Error>()
+            .unwrap();
+        assert_eq!(Error::Cancelled, e);
+    }
+
+    #[test]
+    fn syntax_error() {
+        assert!(Stats::collect(b"a = 1\nb = {", &Parser::default()).is_err());
+    }
+}
@@ -0,0 +1,304 @@
+//! Converts a Factorio-style blueprint, stored as a Lua `return` statement, into JSON.
+//!
+//! Blueprints describe a set of entities (like belts and inserters) and their positions. This
+//! deserialises them into Rust types, and tidies up some fields which are awkward to use outside
+//! of the game while converting:
+//!
+//! * `version` is a 64-bit integer which packs four 16-bit version numbers; this converts it to a
+//!   string like `"1.1.110.0"`.
+//! * entity `direction`s are numbers from `0` to `7`; this converts them to compass directions.
+//!
+//! With `--salvage`, this recovers as much as possible from a blueprint which was truncated
+//! part-way through (like from a copy and paste which was cut off).
+use clap::Parser as _;
+use serde::{Deserialize, Serialize};
+use serde_json::{to_writer, to_writer_pretty};
+use serde_luaq::{
+    from_value_with_options, DeserializeOptions, LuaFormat, LuaNumber, LuaTableEntry, LuaTableExt,
+    LuaValue, LuaVisitor, Parser, Transforms, VisitAction,
+};
+use std::{
+    fs::{self, File},
+    io::{stdout, BufWriter, Write},
+    path::PathBuf,
+};
+
+/// Maximum table depth; blueprint books may contain blueprint books.
+const MAX_DEPTH: u16 = 32;
+
+type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Converts a Factorio-style blueprint, stored as a Lua `return` statement, into JSON.
+#[derive(clap::Parser, Debug)]
+#[command(name = "blueprint_to_json", version, about, long_about = None, verbatim_doc_comment, rename_all = "snake_case")]
+struct Args {
+    /// Input Lua filename, will be loaded entirely into memory.
+    #[arg()]
+    input: PathBuf,
+
+    /// Output JSON filename. If not specified, writes to stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Pretty-print JSON output.
+    #[arg(short, long)]
+    pretty: bool,
+
+    /// Recover as much as possible from a truncated blueprint.
+    #[arg(short, long)]
+    salvage: bool,
+}
+
+/// A blueprint export.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct Export {
+    blueprint: Blueprint,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct Blueprint {
+    item: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(default)]
+    icons: Vec<Icon>,
+    #[serde(default)]
+    entities: Vec<Entity>,
+    /// Game version, converted by [`version()`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct Icon {
+    index: u32,
+    signal: Signal,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct Signal {
+    #[serde(rename = "type")]
+    kind: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct Entity {
+    entity_number: u32,
+    name: String,
+    position: Position,
+    /// Compass direction, converted by [`direction()`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    direction: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct Position {
+    x: f64,
+    y: f64,
+}
+
+/// Compass directions, indexed by Factorio's direction numbers.
+const DIRECTIONS: [&str; 8] = [
+    "north",
+    "northeast",
+    "east",
+    "southeast",
+    "south",
+    "southwest",
+    "west",
+    "northwest",
+];
+
+/// Converts a packed version number into a string like `"1.1.110.0"`.
+fn version(v: LuaValue<'_>) -> std::result::Result<LuaValue<'_>, String> {
+    let LuaValue::Number(LuaNumber::Integer(packed)) = v else {
+        return Err(format!("expected a packed version number, got {v:?}"));
+    };
+    let packed = packed as u64;
+    let parts: Vec<String> = (0..4)
+        .rev()
+        .map(|i| ((packed >> (i * 16)) & 0xffff).to_string())
+        .collect();
+    Ok(LuaValue::from(parts.join(".")))
+}
+
+/// Converts a direction number into a compass direction.
+fn direction(v: LuaValue<'_>) -> std::result::Result<LuaValue<'_>, String> {
+    match v {
+        LuaValue::Number(LuaNumber::Integer(d)) => usize::try_from(d)
+            .ok()
+            .and_then(|d| DIRECTIONS.get(d))
+            .map(|d| LuaValue::from(*d))
+            .ok_or_else(|| format!("unknown direction {d}")),
+        v => Ok(v),
+    }
+}
+
+/// Removes entities which are missing required fields, because they were cut off part-way
+/// through.
+struct DropIncompleteEntities;
+
+impl<'a> LuaVisitor<'a> for DropIncompleteEntities {
+    fn enter_entry(&mut self, entry: &mut LuaTableEntry<'a>) -> VisitAction {
+        let LuaTableEntry::NameValue(b) = entry else {
+            return VisitAction::Continue;
+        };
+        let (name, LuaValue::Table(entities)) = &mut **b else {
+            return VisitAction::Continue;
+        };
+        if name != "entities" {
+            return VisitAction::Continue;
+        }
+
+        entities.retain(|entity| match entity {
+            LuaTableEntry::Value(v) => match &**v {
+                LuaValue::Table(fields) => ["entity_number", "name", "position"]
+                    .into_iter()
+                    .all(|k| fields.position_by_key(&LuaValue::from(k)).is_some()),
+                _ => false,
+            },
+            _ => false,
+        });
+        VisitAction::SkipChildren
+    }
+}
+
+/// Reads a blueprint.
+///
+/// If `salvage` is set, truncated input is repaired, and this also returns the offset where the
+/// input was truncated.
+fn read(b: &[u8], salvage: bool) -> Result<(Export, Option<usize>)> {
+    let parser = Parser::builder().max_depth(MAX_DEPTH).build();
+    let (value, truncated) = if salvage {
+        let mut s = parser.salvage(b, LuaFormat::Return)?;
+        if s.is_truncated() {
+            s.value.walk(&mut DropIncompleteEntities);
+        }
+        (s.value, s.truncated)
+    } else {
+        (parser.ret(b)?, None)
+    };
+
+    let opts = DeserializeOptions::new().transforms(
+        Transforms::new()
+            .field("version", version)
+            .field("direction", direction),
+    );
+    Ok((from_value_with_options(&value, &opts)?, truncated))
+}
+
+fn main() -> Result {
+    let args = Args::parse();
+    let b = fs::read(args.input)?;
+    let (export, truncated) = read(&b, args.salvage)?;
+    if let Some(truncated) = truncated {
+        eprintln!("warning: input was truncated at byte {truncated}");
+    }
+
+    let mut f: Box<dyn Write> = if let Some(output) = args.output {
+        Box::new(BufWriter::new(
+            File::options().create_new(true).write(true).open(output)?,
+        ))
+    } else {
+        Box::new(stdout())
+    };
+
+    if args.pretty {
+        to_writer_pretty(&mut f, &export)?;
+    } else {
+        to_writer(&mut f, &export)?;
+    }
+
+    f.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const BLUEPRINT: &[u8] = br#"return {
+  blueprint = {
+    item = "blueprint",
+    label = "Belt loop",
+    icons = {
+      {index = 1, signal = {type = "item", name = "transport-belt"}},
+    },
+    entities = {
+      {entity_number = 1, name = "transport-belt", position = {x = 0.5, y = 0.5}, direction = 2},
+      {entity_number = 2, name = "transport-belt", position = {x = 1.5, y = 0.5}, direction = 4},
+      {entity_number = 3, name = "inserter", position = {x = 1.5, y = 1.5}},
+    },
+    version = 281479278886912,
+  },
+}
+"#;
+
+    #[test]
+    fn convert_blueprint() {
+        let (export, truncated) = read(BLUEPRINT, false).unwrap();
+        assert_eq!(None, truncated);
+        let json = serde_json::to_value(&export).unwrap();
+        assert_eq!(
+            json!({
+                "blueprint": {
+                    "item": "blueprint",
+                    "label": "Belt loop",
+                    "icons": [
+                        {"index": 1, "signal": {"type": "item", "name": "transport-belt"}},
+                    ],
+                    "entities": [
+                        {
+                            "entity_number": 1,
+                            "name": "transport-belt",
+                            "position": {"x": 0.5, "y": 0.5},
+                            "direction": "east",
+                        },
+                        {
+                            "entity_number": 2,
+                            "name": "transport-belt",
+                            "position": {"x": 1.5, "y": 0.5},
+                            "direction": "south",
+                        },
+                        {
+                            "entity_number": 3,
+                            "name": "inserter",
+                            "position": {"x": 1.5, "y": 1.5},
+                        },
+                    ],
+                    "version": "1.1.110.0",
+                },
+            }),
+            json,
+        );
+    }
+
+    #[test]
+    fn salvage_truncated() {
+        // Cut off part-way through the third entity
+        let b = &BLUEPRINT[..BLUEPRINT.iter().position(|&c| c == b'3').unwrap() + 10];
+        assert!(read(b, false).is_err());
+
+        let (export, truncated) = read(b, true).unwrap();
+        assert!(truncated.is_some());
+        let entities = &export.blueprint.entities;
+        assert_eq!(2, entities.len());
+        assert_eq!(Some("south"), entities[1].direction.as_deref());
+        assert_eq!(None, export.blueprint.version);
+    }
+
+    #[test]
+    fn bad_direction() {
+        let e = read(
+            b"return {blueprint = {item = 'blueprint', entities = {{direction = 9}}}}",
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(
+            "blueprint.entities[1].direction: serde deserialize error: unknown direction 9",
+            e.to_string(),
+        );
+    }
+}
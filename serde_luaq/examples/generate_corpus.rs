@@ -0,0 +1,36 @@
+use clap::Parser;
+use serde_luaq::corpus;
+use std::{fs, path::PathBuf};
+
+type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Writes a generated corpus of Lua scripts to a directory, for seeding fuzzers or as regression
+/// test fixtures.
+#[derive(Parser, Debug)]
+#[command(name = "generate_corpus", version, about, long_about = None, verbatim_doc_comment, rename_all = "snake_case")]
+struct Args {
+    /// Output directory, which will be created if it does not exist.
+    output: PathBuf,
+
+    /// Only write scripts which are expected to parse successfully.
+    #[arg(long)]
+    valid_only: bool,
+}
+
+fn main() -> Result {
+    let args = Args::parse();
+    fs::create_dir_all(&args.output)?;
+
+    let mut written = 0;
+    for entry in corpus() {
+        if args.valid_only && !entry.valid {
+            continue;
+        }
+
+        fs::write(args.output.join(entry.name + ".lua"), entry.data)?;
+        written += 1;
+    }
+
+    eprintln!("wrote {written} files to {}", args.output.display());
+    Ok(())
+}
@@ -1,7 +1,8 @@
 use clap::{Parser, ValueEnum};
 use serde_json::{to_writer, to_writer_pretty};
 use serde_luaq::{
-    lua_value, return_statement, script, to_json_value, JsonConversionOptions, LuaValue,
+    json_schema, lua_value, return_statement, script, to_json_value, JsonConversionOptions,
+    LuaValue,
 };
 use std::{
     alloc::{GlobalAlloc, Layout, System},
@@ -69,6 +70,11 @@ struct Args {
     #[arg(long)]
     lossy_string: bool,
 
+    /// Output a JSON Schema describing the structure of the Lua value, rather than the value
+    /// itself.
+    #[arg(long)]
+    schema: bool,
+
     /// Stop once the Lua value has been loaded.
     #[arg(long)]
     no_json: bool,
@@ -116,10 +122,7 @@ fn main() -> Result {
         eprintln!("Initial memory usage: {start_bytes} bytes");
     }
 
-    let opts = JsonConversionOptions {
-        lossy_string: args.lossy_string,
-        ..Default::default()
-    };
+    let opts = JsonConversionOptions::new().lossy_string(args.lossy_string);
 
     let mut f = File::open(args.input)?;
     let size = f.metadata()?.len() as usize;
@@ -158,7 +161,11 @@ fn main() -> Result {
         return Ok(());
     }
 
-    let json_value = to_json_value(lua_value, &opts)?;
+    let json_value = if args.schema {
+        json_schema(&lua_value, &opts)?
+    } else {
+        to_json_value(lua_value, &opts)?
+    };
 
     let json_bytes = ALLOCATED.load(Relaxed) - parse_lua_bytes;
     if args.memory_stats {
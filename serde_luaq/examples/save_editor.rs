@@ -0,0 +1,392 @@
+//! Edits values in a game save file, which is a Lua `return` statement followed by a checksum.
+//!
+//! Save files look like this:
+//!
+//! ```lua
+//! return {
+//!   player = {
+//!     name = "Alice",
+//!     gold = 150,
+//!   },
+//! }
+//! -- checksum: 1a2b
+//! ```
+//!
+//! The checksum is the sum of all of the bytes before the `--`, as a 16-bit hexadecimal number.
+//! The game refuses to load saves where the checksum doesn't match, so this checks it when
+//! reading the save, and updates it after editing.
+//!
+//! Values are addressed by paths like `player.gold` or `inventory[2]`. Entries which aren't
+//! edited are written back as-is, but comments and formatting aren't preserved.
+use clap::Parser as _;
+use serde::Serialize;
+use serde_luaq::{
+    lua_value, to_string, FooterCheck, Formatter, LuaFormat, LuaTableEntry, LuaTableExt, LuaValue,
+    Parser, Serializer,
+};
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+/// Maximum table depth.
+const MAX_DEPTH: u16 = 32;
+
+type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Edits values in a game save file.
+///
+/// All `--set` edits are applied before all `--delete` edits.
+#[derive(clap::Parser, Debug)]
+#[command(name = "save_editor", version, about, long_about = None, verbatim_doc_comment, rename_all = "snake_case")]
+struct Args {
+    /// Input save filename, will be loaded entirely into memory.
+    #[arg()]
+    input: PathBuf,
+
+    /// Output save filename. If not specified, prints the values at the `--get` paths.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Prints the value at a path, like `player.gold`.
+    #[arg(short, long)]
+    get: Vec<String>,
+
+    /// Sets the value at a path to a Lua expression, like `player.gold=1000`.
+    #[arg(short, long)]
+    set: Vec<String>,
+
+    /// Deletes the value at a path.
+    #[arg(short, long)]
+    delete: Vec<String>,
+
+    /// Don't check the input file's checksum. It must still have a checksum line.
+    #[arg(long)]
+    ignore_checksum: bool,
+}
+
+/// Calculates the checksum of a save's body.
+fn checksum(body: &[u8]) -> String {
+    let sum = body.iter().fold(0u16, |s, &c| s.wrapping_add(c.into()));
+    format!("{sum:04x}")
+}
+
+/// Writes tables with one entry per line, indented with two spaces, like the game does.
+#[derive(Default)]
+struct Indented {
+    /// Whether each open table has any entries.
+    tables: Vec<bool>,
+}
+
+impl Indented {
+    fn newline<W: ?Sized + Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(b"\n")?;
+        for _ in &self.tables {
+            w.write_all(b"  ")?;
+        }
+        Ok(())
+    }
+}
+
+impl Formatter for Indented {
+    fn begin_table<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        self.tables.push(false);
+        w.write_all(b"{")
+    }
+
+    fn end_table<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        if self.tables.pop() == Some(true) {
+            w.write_all(b",")?;
+            self.newline(w)?;
+        }
+        w.write_all(b"}")
+    }
+
+    fn begin_entry<W: ?Sized + Write>(&mut self, w: &mut W, first: bool) -> io::Result<()> {
+        if !first {
+            w.write_all(b",")?;
+        }
+        if let Some(has_entries) = self.tables.last_mut() {
+            *has_entries = true;
+        }
+        self.newline(w)
+    }
+}
+
+/// Parses a path like `player.inventory[2]` or `flags["seen intro"]` into a list of table keys.
+fn parse_path(path: &str) -> Result<Vec<LuaValue<'static>>> {
+    let mut keys = Vec::new();
+    let mut rest = path;
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix('[') {
+            let (key, r) = r.split_once(']').ok_or("unclosed `[` in path")?;
+            let quoted = [('"', '"'), ('\'', '\'')]
+                .into_iter()
+                .find_map(|(open, close)| key.strip_prefix(open)?.strip_suffix(close));
+            keys.push(match quoted {
+                Some(name) => LuaValue::from(name.to_string()),
+                None => LuaValue::integer(key.parse()?),
+            });
+            rest = r;
+        } else {
+            let r = if keys.is_empty() {
+                rest
+            } else {
+                rest.strip_prefix('.')
+                    .ok_or("expected `.` or `[` in path")?
+            };
+            let end = r.find(['.', '[']).unwrap_or(r.len());
+            if end == 0 {
+                return Err(format!("empty name in path {path:?}").into());
+            }
+            keys.push(LuaValue::from(r[..end].to_string()));
+            rest = &r[end..];
+        }
+    }
+
+    if keys.is_empty() {
+        return Err("empty path".into());
+    }
+    Ok(keys)
+}
+
+/// Returns a mutable reference to the value of a table entry, if it can contain a table.
+fn entry_value_mut<'e, 'a>(entry: &'e mut LuaTableEntry<'a>) -> Option<&'e mut LuaValue<'a>> {
+    match entry {
+        LuaTableEntry::Value(v) => Some(v),
+        LuaTableEntry::NameValue(b) => Some(&mut b.1),
+        LuaTableEntry::KeyValue(b) => Some(&mut b.1),
+        _ => None,
+    }
+}
+
+/// Finds the table at `keys`.
+fn table_mut<'v, 'a>(
+    mut v: &'v mut LuaValue<'a>,
+    keys: &[LuaValue<'_>],
+) -> Result<&'v mut Vec<LuaTableEntry<'a>>> {
+    for key in keys {
+        let LuaValue::Table(t) = v else {
+            return Err(format!("not a table at key {key:?}").into());
+        };
+        let i = t
+            .position_by_key(key)
+            .ok_or_else(|| format!("no entry with key {key:?}"))?;
+        v = entry_value_mut(&mut t[i]).ok_or_else(|| format!("not a table: {key:?}"))?;
+    }
+
+    match v {
+        LuaValue::Table(t) => Ok(t),
+        _ => Err("not a table".into()),
+    }
+}
+
+/// Sets the value at `path` to `value`, adding a new entry if needed.
+fn set(root: &mut LuaValue<'_>, path: &str, value: LuaValue<'static>) -> Result {
+    let keys = parse_path(path)?;
+    let (key, parents) = keys.split_last().unwrap();
+    let t = table_mut(root, parents)?;
+
+    match t.position_by_key(key) {
+        Some(i) => {
+            t[i] = match &t[i] {
+                LuaTableEntry::NameValue(b) => {
+                    LuaTableEntry::NameValue(Box::new((b.0.clone(), value)))
+                }
+                LuaTableEntry::KeyValue(b) => {
+                    LuaTableEntry::KeyValue(Box::new((b.0.clone(), value)))
+                }
+                _ => LuaTableEntry::from(value),
+            };
+        }
+        None => t.push(match key {
+            LuaValue::String(k) => LuaTableEntry::from((k.clone(), value)),
+            k => LuaTableEntry::KeyValue(Box::new((k.clone(), value))),
+        }),
+    }
+    Ok(())
+}
+
+/// Deletes the value at `path`.
+fn delete(root: &mut LuaValue<'_>, path: &str) -> Result {
+    let keys = parse_path(path)?;
+    let (key, parents) = keys.split_last().unwrap();
+    table_mut(root, parents)?
+        .remove_by_key(key)
+        .ok_or_else(|| format!("no entry at {path}"))?;
+    Ok(())
+}
+
+/// Reads a save file, checking its checksum if `verify` is set.
+fn read(b: &[u8], verify: bool) -> Result<LuaValue<'_>> {
+    let check = FooterCheck::new(move |body, footer| {
+        let expected = checksum(body);
+        match footer.strip_prefix(b"checksum: ") {
+            Some(c) if !verify || c == expected.as_bytes() => Ok(()),
+            _ => Err(format!("expected checksum {expected}")),
+        }
+    });
+    let parser = Parser::builder().max_depth(MAX_DEPTH).footer(check).build();
+    Ok(parser.ret(b)?)
+}
+
+/// Writes a save file, with a checksum.
+fn write(value: &LuaValue<'_>) -> Result<Vec<u8>> {
+    let mut ser = Serializer::with_formatter(Vec::new(), LuaFormat::Return, Indented::default());
+    value.serialize(&mut ser)?;
+    let mut out = ser.into_inner();
+    out.push(b'\n');
+
+    let sum = checksum(&out);
+    writeln!(out, "-- checksum: {sum}")?;
+    Ok(out)
+}
+
+/// Applies edits to a save file.
+fn edit(b: &[u8], verify: bool, sets: &[String], deletes: &[String]) -> Result<Vec<u8>> {
+    let mut value = read(b, verify)?;
+    for s in sets {
+        let (path, v) = s.split_once('=').ok_or("expected `path=value`")?;
+        set(
+            &mut value,
+            path.trim(),
+            lua_value(v.as_bytes(), MAX_DEPTH)?.into_owned(),
+        )?;
+    }
+    for path in deletes {
+        delete(&mut value, path)?;
+    }
+    write(&value)
+}
+
+/// Gets the value at `path`, as Lua source.
+fn get(b: &[u8], verify: bool, path: &str) -> Result<String> {
+    let mut value = read(b, verify)?;
+    let keys = parse_path(path)?;
+    let (key, parents) = keys.split_last().unwrap();
+    let t = table_mut(&mut value, parents)?;
+    let i = t
+        .position_by_key(key)
+        .ok_or_else(|| format!("no entry at {path}"))?;
+    Ok(to_string(&t.remove(i).move_value(), LuaFormat::Value)?)
+}
+
+fn main() -> Result {
+    let args = Args::parse();
+    let b = fs::read(&args.input)?;
+    let verify = !args.ignore_checksum;
+
+    for path in &args.get {
+        println!("{path} = {}", get(&b, verify, path)?);
+    }
+
+    if let Some(output) = args.output {
+        fs::write(output, edit(&b, verify, &args.set, &args.delete)?)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_luaq::Error;
+
+    const BODY: &[u8] = br#"return {
+  player = {name = "Alice", gold = 150},
+  inventory = {"sword", "shield", "potion"},
+  flags = {tutorial = true, ["seen intro"] = true},
+}
+"#;
+
+    fn save() -> Vec<u8> {
+        [BODY, b"-- checksum: ", checksum(BODY).as_bytes(), b"\n"].concat()
+    }
+
+    #[test]
+    fn edit_save() {
+        let sets = [
+            "player.gold = 1000".to_string(),
+            "player.title = 'Hero'".to_string(),
+            "inventory[2] = {name = 'tower shield', defence = 5}".to_string(),
+            "flags['seen intro'] = false".to_string(),
+        ];
+        let out = edit(&save(), true, &sets, &["flags.tutorial".to_string()]).unwrap();
+
+        let body: &[u8] = br#"return {
+  player = {
+    name = "Alice",
+    gold = 1000,
+    title = "Hero",
+  },
+  inventory = {
+    "sword",
+    {
+      name = "tower shield",
+      defence = 5,
+    },
+    "potion",
+  },
+  flags = {
+    ["seen intro"] = false,
+  },
+}
+"#;
+        let expected = [body, b"-- checksum: ", checksum(body).as_bytes(), b"\n"].concat();
+        assert_eq!(
+            String::from_utf8(expected).unwrap(),
+            String::from_utf8(out.clone()).unwrap(),
+        );
+
+        // The output can be read back, and edited again
+        assert_eq!("1000", get(&out, true, "player.gold").unwrap());
+        assert_eq!(
+            r#""tower shield""#,
+            get(&out, true, "inventory[2].name").unwrap()
+        );
+        assert!(edit(&out, true, &[], &[]).is_ok());
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let mut b = save();
+        // Change the player's gold without updating the checksum.
+        let i = b.windows(3).position(|w| w == b"150").unwrap();
+        b[i] = b'9';
+
+        let e = *read(&b, true).unwrap_err().downcast::<Error>().unwrap();
+        assert!(matches!(e, Error::InvalidFooter { .. }), "{e:?}");
+        assert_eq!("950", get(&b, false, "player.gold").unwrap());
+
+        let e = *read(BODY, true).unwrap_err().downcast::<Error>().unwrap();
+        assert_eq!(Error::MissingFooter, e);
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            vec![
+                LuaValue::from("a".to_string()),
+                LuaValue::integer(2),
+                LuaValue::from("b".to_string()),
+            ],
+            parse_path("a[2].b").unwrap(),
+        );
+        assert_eq!(vec![LuaValue::integer(1)], parse_path("[1]").unwrap());
+        assert_eq!(
+            vec![
+                LuaValue::from("a b".to_string()),
+                LuaValue::from("c".to_string())
+            ],
+            parse_path("['a b'].c").unwrap(),
+        );
+        for bad in ["", "a..b", "a[1", "a[x]", "a[1]b", ".a", "a['b]"] {
+            assert!(parse_path(bad).is_err(), "{bad}");
+        }
+
+        let b = save();
+        assert!(get(&b, true, "player.level").is_err());
+        assert!(get(&b, true, "player.name.first").is_err());
+        assert!(edit(&b, true, &[], &["nothing".to_string()]).is_err());
+    }
+}
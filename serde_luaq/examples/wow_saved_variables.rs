@@ -0,0 +1,187 @@
+//! Summarises the [AceDB][] profiles in a World of Warcraft addon's SavedVariables file.
+//!
+//! WoW stores addon settings in:
+//!
+//! * `WTF/Account/<ACCOUNT>/SavedVariables/<Addon>.lua` (account-wide)
+//! * `WTF/Account/<ACCOUNT>/<Realm>/<Character>/SavedVariables/<Addon>.lua` (per-character)
+//!
+//! Each file is a Lua 5.1 script which assigns the addon's global variables. Most addons use
+//! AceDB-3.0 to manage these, which stores settings in named profiles, and records which profile
+//! each character uses.
+//!
+//! WoW annotates implicitly-keyed table entries with comments (like `"a", -- [1]`), which are
+//! skipped like whitespace.
+//!
+//! [AceDB]: https://www.wowace.com/projects/ace3/pages/api/ace-db-3-0
+use clap::Parser as _;
+use serde::Deserialize;
+use serde_luaq::{from_value, LuaDialect, LuaValue, Parser, UnknownFields};
+use std::{collections::BTreeMap, fmt::Write as _, fs, path::PathBuf};
+
+/// WoW doesn't limit table depth, but settings are rarely nested this deeply.
+const MAX_DEPTH: u16 = 64;
+
+type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Summarises the AceDB profiles in a World of Warcraft addon's SavedVariables file.
+#[derive(clap::Parser, Debug)]
+#[command(name = "wow_saved_variables", version, about, long_about = None, verbatim_doc_comment, rename_all = "snake_case")]
+struct Args {
+    /// Input SavedVariables filename, will be loaded entirely into memory.
+    #[arg()]
+    input: PathBuf,
+}
+
+/// A database created by AceDB-3.0.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct AceDb {
+    /// Which profile each character uses, keyed by `"Character - Realm"`.
+    #[serde(default)]
+    profile_keys: BTreeMap<String, String>,
+
+    /// Settings for each profile, keyed by the profile's name.
+    #[serde(default)]
+    profiles: BTreeMap<String, UnknownFields>,
+
+    /// Everything else, like per-character and account-wide settings.
+    #[serde(flatten)]
+    other: UnknownFields,
+}
+
+/// Describes the type of a value which isn't an AceDB database.
+fn describe(v: &LuaValue) -> String {
+    match v {
+        LuaValue::Nil => "nil".to_string(),
+        LuaValue::Boolean(b) => b.to_string(),
+        LuaValue::Number(n) => n.to_string(),
+        LuaValue::String(s) => format!("string of {} bytes", s.len()),
+        LuaValue::Table(t) => format!("table with {} entries", t.len()),
+    }
+}
+
+/// Summarises all of the global variables in a SavedVariables file.
+fn summarise(b: &[u8]) -> Result<String> {
+    let parser = Parser::builder()
+        .dialect(LuaDialect::Lua51)
+        .max_depth(MAX_DEPTH)
+        .build();
+
+    let mut out = String::new();
+    // Statements are parsed one at a time, so only one global's value is in memory at once.
+    for statement in parser.script_iter(b) {
+        let statement = statement?;
+        let name = statement.name;
+
+        let db = match &statement.value {
+            LuaValue::Table(_) => from_value::<AceDb>(&statement.value).ok(),
+            _ => None,
+        };
+        let Some(db) = db.filter(|db| !db.profiles.is_empty()) else {
+            writeln!(out, "{name}: {}", describe(&statement.value))?;
+            continue;
+        };
+
+        writeln!(out, "{name}: AceDB with {} profile(s)", db.profiles.len())?;
+        for (profile, settings) in &db.profiles {
+            let users: Vec<&str> = db
+                .profile_keys
+                .iter()
+                .filter(|(_, p)| *p == profile)
+                .map(|(character, _)| character.as_str())
+                .collect();
+            let users = if users.is_empty() {
+                "nobody".to_string()
+            } else {
+                users.join(", ")
+            };
+            writeln!(
+                out,
+                "  {profile}: {} setting(s), used by {users}",
+                settings.0.len()
+            )?;
+        }
+
+        for entry in &db.other.0 {
+            let key = entry.key().map_or_else(String::new, |k| describe_key(&k));
+            writeln!(
+                out,
+                "  [{key}]: {}",
+                entry.value().map_or_else(String::new, describe)
+            )?;
+        }
+    }
+
+    Ok(out)
+}
+
+/// Describes the key of a table entry.
+fn describe_key(k: &LuaValue) -> String {
+    k.as_str_lossy()
+        .map_or_else(|| describe(k), |s| s.into_owned())
+}
+
+fn main() -> Result {
+    let args = Args::parse();
+    let b = fs::read(args.input)?;
+    print!("{}", summarise(&b)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAVED_VARIABLES: &[u8] = br#"
+MyAddonDB = {
+	["profileKeys"] = {
+		["Alice - Frostmourne"] = "Default",
+		["Bob - Frostmourne"] = "Healer",
+		["Carol - Barthilas"] = "Default",
+	},
+	["global"] = {
+		["version"] = "1.2.3",
+	},
+	["profiles"] = {
+		["Default"] = {
+			["minimap"] = {
+				["hide"] = false,
+				["minimapPos"] = 220.5,
+			},
+			["scale"] = 1,
+		},
+		["Healer"] = {
+			["trackedSpells"] = {
+				774,
+				33763,
+			},
+		},
+		["Unused"] = {
+		},
+	},
+}
+MyAddonLastSeen = 1718000000
+MyAddonCharDB = nil
+"#;
+
+    #[test]
+    fn summary() {
+        assert_eq!(
+            concat!(
+                "MyAddonDB: AceDB with 3 profile(s)\n",
+                "  Default: 2 setting(s), used by Alice - Frostmourne, Carol - Barthilas\n",
+                "  Healer: 1 setting(s), used by Bob - Frostmourne\n",
+                "  Unused: 0 setting(s), used by nobody\n",
+                "  [global]: table with 1 entries\n",
+                "MyAddonLastSeen: 1718000000\n",
+                "MyAddonCharDB: nil\n",
+            ),
+            summarise(SAVED_VARIABLES).unwrap(),
+        );
+    }
+
+    #[test]
+    fn syntax_error() {
+        assert!(summarise(b"MyAddonDB = {\n\t[\"profiles\"] = {\n").is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! [`Arbitrary`] implementations for property testing and fuzzing.
+use crate::{valid_lua_identifier, LuaNumber, LuaTableEntry, LuaValue};
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+use std::borrow::Cow;
+
+/// Maximum depth of nested tables in an arbitrary [`LuaValue`].
+const MAX_DEPTH: u16 = 8;
+
+impl<'a> Arbitrary<'a> for LuaNumber {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(if u.arbitrary()? {
+            LuaNumber::Float(u.arbitrary()?)
+        } else {
+            LuaNumber::Integer(u.arbitrary()?)
+        })
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        let _ = depth;
+        (1, Some(9))
+    }
+}
+
+/// Generates any [`LuaValue`], including NaN floats and non-UTF-8 strings.
+///
+/// Tables are nested at most 8 deep, and contain entries in the same form the `peg` parser
+/// produces:
+///
+/// * Implicitly-keyed `nil`, boolean and number entries use the compact
+///   [`LuaTableEntry`] variants.
+/// * [`NameValue`][LuaTableEntry::NameValue] entries always have a valid Lua identifier as their
+///   key.
+/// * [`KeyValue`][LuaTableEntry::KeyValue] entries never have a `nil`, NaN or table key.
+///
+/// Tables may contain duplicate keys.
+impl<'a> Arbitrary<'a> for LuaValue<'a> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_value(u, 0)
+    }
+}
+
+/// Generates any [`LuaTableEntry`], following the same rules as [`LuaValue`]'s implementation.
+impl<'a> Arbitrary<'a> for LuaTableEntry<'a> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_entry(u, 1)
+    }
+}
+
+fn arbitrary_value<'a>(u: &mut Unstructured<'a>, depth: u16) -> Result<LuaValue<'a>> {
+    let max = if depth >= MAX_DEPTH { 3 } else { 4 };
+    Ok(match u.int_in_range(0..=max)? {
+        0 => LuaValue::Nil,
+        1 => LuaValue::Boolean(u.arbitrary()?),
+        2 => LuaValue::Number(u.arbitrary()?),
+        3 => LuaValue::String(Cow::Borrowed(u.arbitrary()?)),
+        _ => {
+            let mut t = Vec::new();
+            while u.arbitrary()? {
+                t.push(arbitrary_entry(u, depth + 1)?);
+            }
+            LuaValue::Table(t)
+        }
+    })
+}
+
+fn arbitrary_entry<'a>(u: &mut Unstructured<'a>, depth: u16) -> Result<LuaTableEntry<'a>> {
+    Ok(match u.int_in_range(0..=2)? {
+        0 => {
+            let key = arbitrary_key(u)?;
+            LuaTableEntry::KeyValue(Box::new((key, arbitrary_value(u, depth)?)))
+        }
+        1 => {
+            let key: &str = u.arbitrary()?;
+            let value = arbitrary_value(u, depth)?;
+            if valid_lua_identifier(key.as_bytes()) {
+                LuaTableEntry::NameValue(Box::new((Cow::Borrowed(key), value)))
+            } else {
+                LuaTableEntry::KeyValue(Box::new((key.into(), value)))
+            }
+        }
+        _ => match arbitrary_value(u, depth)? {
+            LuaValue::Nil => LuaTableEntry::NilValue,
+            LuaValue::Boolean(b) => LuaTableEntry::BooleanValue(b),
+            LuaValue::Number(n) => LuaTableEntry::NumberValue(n),
+            v => LuaTableEntry::Value(Box::new(v)),
+        },
+    })
+}
+
+/// Generates a table key which Lua allows.
+fn arbitrary_key<'a>(u: &mut Unstructured<'a>) -> Result<LuaValue<'a>> {
+    Ok(match u.int_in_range(0..=2)? {
+        0 => LuaValue::Boolean(u.arbitrary()?),
+        1 => match u.arbitrary()? {
+            LuaNumber::Float(f) if f.is_nan() => LuaValue::float(0.),
+            n => LuaValue::Number(n),
+        },
+        _ => LuaValue::String(Cow::Borrowed(u.arbitrary()?)),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Checks that a value follows the rules documented on [`LuaValue`]'s implementation.
+    fn check(v: &LuaValue<'_>, depth: u16) {
+        let LuaValue::Table(t) = v else {
+            return;
+        };
+
+        assert!(depth < MAX_DEPTH, "too deep: {v:?}");
+        for entry in t {
+            match entry {
+                LuaTableEntry::KeyValue(b) => {
+                    assert!(
+                        !matches!(b.0, LuaValue::Nil | LuaValue::Table(_)) && !b.0.is_nan(),
+                        "invalid key: {entry:?}"
+                    );
+                    check(&b.1, depth + 1);
+                }
+                LuaTableEntry::NameValue(b) => {
+                    assert!(valid_lua_identifier(b.0.as_bytes()), "{entry:?}");
+                    check(&b.1, depth + 1);
+                }
+                LuaTableEntry::Value(v) => {
+                    assert!(
+                        !matches!(
+                            **v,
+                            LuaValue::Nil | LuaValue::Boolean(_) | LuaValue::Number(_)
+                        ),
+                        "not compact: {entry:?}"
+                    );
+                    check(v, depth + 1);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    #[test]
+    fn arbitrary_values() {
+        // Simple deterministic pseudo-random input
+        let mut state = 0x2545f4914f6cdd1du64;
+        let data: Vec<u8> = (0..0x10000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+
+        let mut tables = 0;
+        for chunk in data.chunks(0x400) {
+            let mut u = Unstructured::new(chunk);
+            while !u.is_empty() {
+                let v = LuaValue::arbitrary(&mut u).unwrap();
+                check(&v, 0);
+                if matches!(v, LuaValue::Table(_)) {
+                    tables += 1;
+                }
+            }
+        }
+
+        assert!(tables > 0);
+        assert_eq!(
+            LuaValue::Nil,
+            LuaValue::arbitrary(&mut Unstructured::new(&[])).unwrap()
+        );
+    }
+}
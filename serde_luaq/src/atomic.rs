@@ -0,0 +1,156 @@
+//! Atomically replacing a file with serialised Lua.
+use crate::{to_writer, Error, LuaFormat};
+use serde::Serialize;
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// When [`write_atomic()`] flushes data to disk with `fsync`.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Fsync {
+    /// Never `fsync`.
+    ///
+    /// Other processes never see a partially-written file, but after a crash or power loss, the
+    /// file could be empty or missing data.
+    None,
+
+    /// `fsync` the temporary file before renaming it over the target.
+    ///
+    /// The new file is complete once it is in place, but after a crash or power loss, the rename
+    /// itself could be lost, leaving the old file.
+    File,
+
+    /// `fsync` the temporary file before renaming it, and then `fsync` the directory containing
+    /// it, so the rename is also on disk when [`write_atomic()`] returns (default).
+    ///
+    /// Directories can only be synced on Unix-like platforms; elsewhere, this is the same as
+    /// [`File`][Self::File].
+    #[default]
+    FileAndDirectory,
+}
+
+/// Options for [`write_atomic()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WriteAtomicOptions {
+    /// Format to [serialise][to_writer] the value in. Defaults to [`LuaFormat::Return`].
+    pub format: LuaFormat,
+
+    /// When to flush data to disk. Defaults to [`Fsync::FileAndDirectory`].
+    pub fsync: Fsync,
+}
+
+impl WriteAtomicOptions {
+    /// Creates options which write a `return` statement, and `fsync` the file and its directory.
+    pub const fn new() -> Self {
+        Self {
+            format: LuaFormat::Return,
+            fsync: Fsync::FileAndDirectory,
+        }
+    }
+
+    /// Sets the [`format`][Self::format] to write.
+    pub const fn format(mut self, format: LuaFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets when to [`fsync`][Self::fsync].
+    pub const fn fsync(mut self, fsync: Fsync) -> Self {
+        self.fsync = fsync;
+        self
+    }
+}
+
+impl Default for WriteAtomicOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Serialises `value` as Lua source code into `path`, replacing it atomically.
+///
+/// The output is written to a temporary file in the same directory as `path`, which is then
+/// renamed over `path`. Other processes (and the same program after a crash) see either the old
+/// file or the new one, never a partially-written file.
+///
+/// If serialising or writing fails, `path` is left as it was and the temporary file is removed.
+///
+/// If `path` already exists, the new file gets its permissions. Otherwise, it is created with
+/// the temporary file's permissions, which are only readable by the current user on Unix-like
+/// platforms.
+///
+/// How long the output takes to reach the disk is set by [`WriteAtomicOptions::fsync`].
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{return_statement, write_atomic, lua_value, WriteAtomicOptions};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("save.lua");
+/// let value = lua_value(b"{level = 3, name = 'alice'}", 16).unwrap();
+///
+/// write_atomic(&path, &value, &WriteAtomicOptions::new()).unwrap();
+/// assert_eq!(
+///     b"return {level = 3, name = \"alice\"}",
+///     std::fs::read(&path).unwrap().as_slice(),
+/// );
+/// ```
+pub fn write_atomic<P, T>(path: P, value: &T, options: &WriteAtomicOptions) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    T: Serialize + ?Sized,
+{
+    let path = path.as_ref();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    // Hidden, and named after the target, in case it is left behind by a crash.
+    let mut prefix = OsString::from(".");
+    prefix.push(path.file_name().unwrap_or_default());
+    prefix.push(".");
+    let mut file = tempfile::Builder::new()
+        .prefix(&prefix)
+        .suffix(".tmp")
+        .tempfile_in(dir)?;
+
+    let mut writer = BufWriter::new(file.as_file_mut());
+    to_writer(&mut writer, value, options.format)?;
+    writer.flush()?;
+    drop(writer);
+
+    if let Ok(metadata) = path.metadata() {
+        file.as_file().set_permissions(metadata.permissions())?;
+    }
+
+    if options.fsync != Fsync::None {
+        file.as_file().sync_all()?;
+    }
+
+    file.persist(path).map_err(|e| e.error)?;
+
+    if options.fsync == Fsync::FileAndDirectory {
+        sync_dir(dir)?;
+    }
+
+    Ok(())
+}
+
+/// Flushes a directory's entries to disk.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+/// Directories can't be opened as files on this platform.
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> std::io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,376 @@
+//! Compact binary encoding of parsed [`LuaValue`] trees, for caching.
+use crate::{error::BinaryError, LuaNumber, LuaTableEntry, LuaValue};
+use std::{borrow::Cow, io::Write, str::from_utf8};
+
+/// Magic bytes at the start of the encoding.
+const MAGIC: &[u8; 4] = b"LUAQ";
+
+/// Current version of the format.
+const VERSION: u8 = 1;
+
+const VALUE_NIL: u8 = 0;
+const VALUE_FALSE: u8 = 1;
+const VALUE_TRUE: u8 = 2;
+const VALUE_INTEGER: u8 = 3;
+const VALUE_FLOAT: u8 = 4;
+const VALUE_STRING: u8 = 5;
+const VALUE_TABLE: u8 = 6;
+
+const ENTRY_KEY_VALUE: u8 = 0;
+const ENTRY_NAME_VALUE: u8 = 1;
+const ENTRY_VALUE: u8 = 2;
+const ENTRY_NUMBER_VALUE: u8 = 3;
+const ENTRY_BOOLEAN_VALUE: u8 = 4;
+const ENTRY_NIL_VALUE: u8 = 5;
+
+/// Encodes a [`LuaValue`] in `serde_luaq`'s compact binary format.
+///
+/// This can be used to cache a parsed [`LuaValue`] between runs, and then load it again with
+/// [`from_binary()`], which is much faster than re-parsing the Lua source. Unlike
+/// `to_bincode()`, this doesn't need any optional features.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{from_binary, lua_value, to_binary};
+///
+/// let value = lua_value(br#"{hello = "world", 1, 2.5, {true}}"#, 16).unwrap();
+/// let cache = to_binary(&value);
+///
+/// // Strings borrow from `cache`.
+/// assert_eq!(value, from_binary(&cache, 16).unwrap());
+/// ```
+///
+/// ## Format
+///
+/// The encoding starts with a 5 byte header: the magic bytes `LUAQ`, followed by the format
+/// version (currently `1`). This is followed by a single encoded value.
+///
+/// Lengths and integers are written as variable-length unsigned integers ("varints"): 7 bits at a
+/// time, least significant group first, with the high bit set on every byte except the last (like
+/// LEB128 or Protocol Buffers). Signed integers are zig-zag encoded first, so small negative
+/// numbers stay short.
+///
+/// Each value starts with a tag byte:
+///
+/// | Tag | Value     | Followed by                                    |
+/// | --- | --------- | ---------------------------------------------- |
+/// | `0` | `nil`     |                                                |
+/// | `1` | `false`   |                                                |
+/// | `2` | `true`    |                                                |
+/// | `3` | integer   | zig-zag varint                                 |
+/// | `4` | float     | 8 bytes, IEEE 754 little-endian                |
+/// | `5` | string    | varint length, then that many bytes            |
+/// | `6` | table     | varint number of entries, then each entry      |
+///
+/// Each table entry starts with a tag byte, which matches the [`LuaTableEntry`] variant:
+///
+/// | Tag | Entry                                         | Followed by                           |
+/// | --- | --------------------------------------------- | ------------------------------------- |
+/// | `0` | [`KeyValue`][LuaTableEntry::KeyValue]         | key value, then value                 |
+/// | `1` | [`NameValue`][LuaTableEntry::NameValue]       | varint length, UTF-8 name, then value |
+/// | `2` | [`Value`][LuaTableEntry::Value]               | value                                 |
+/// | `3` | [`NumberValue`][LuaTableEntry::NumberValue]   | value (which must be a number)        |
+/// | `4` | [`BooleanValue`][LuaTableEntry::BooleanValue] | value (which must be a boolean)       |
+/// | `5` | [`NilValue`][LuaTableEntry::NilValue]         |                                       |
+pub fn to_binary(value: &LuaValue<'_>) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_binary(value, &mut out).expect("writing to a Vec can't fail");
+    out
+}
+
+/// Encodes a [`LuaValue`] like [`to_binary()`], writing it to `w`.
+///
+/// This makes many small writes, so `w` should be buffered (for example, with
+/// [`BufWriter`][std::io::BufWriter]).
+pub fn write_binary<W: Write>(value: &LuaValue<'_>, mut w: W) -> std::io::Result<()> {
+    w.write_all(MAGIC)?;
+    w.write_all(&[VERSION])?;
+    write_value(value, &mut w)
+}
+
+/// Decodes a [`LuaValue`] encoded by [`to_binary()`] or [`write_binary()`].
+///
+/// All strings borrow from `b`, which makes this suitable for use with memory-mapped files.
+///
+/// `max_depth` limits table nesting in the same way as [`lua_value()`][crate::lua_value]. This
+/// returns an error if `b` contains anything after the encoded value.
+pub fn from_binary(b: &[u8], max_depth: u16) -> Result<LuaValue<'_>, BinaryError> {
+    let mut r = Reader { b, pos: 0 };
+    if r.bytes(MAGIC.len())? != MAGIC {
+        return Err(BinaryError::InvalidHeader);
+    }
+
+    match r.byte()? {
+        VERSION => (),
+        v => return Err(BinaryError::UnsupportedVersion(v)),
+    }
+
+    let value = r.value(max_depth)?;
+    if r.pos != b.len() {
+        return Err(BinaryError::TrailingBytes { position: r.pos });
+    }
+
+    Ok(value)
+}
+
+fn write_varint<W: Write>(mut v: u64, w: &mut W) -> std::io::Result<()> {
+    // A u64 needs at most 10 groups of 7 bits.
+    let mut buf = [0; 10];
+    let mut len = 0;
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            buf[len] = byte;
+            len += 1;
+            break;
+        }
+        buf[len] = byte | 0x80;
+        len += 1;
+    }
+    w.write_all(&buf[..len])
+}
+
+fn write_bytes<W: Write>(b: &[u8], w: &mut W) -> std::io::Result<()> {
+    write_varint(b.len() as u64, w)?;
+    w.write_all(b)
+}
+
+fn write_value<W: Write>(value: &LuaValue<'_>, w: &mut W) -> std::io::Result<()> {
+    match value {
+        LuaValue::Nil => w.write_all(&[VALUE_NIL]),
+        LuaValue::Boolean(false) => w.write_all(&[VALUE_FALSE]),
+        LuaValue::Boolean(true) => w.write_all(&[VALUE_TRUE]),
+        LuaValue::Number(LuaNumber::Integer(i)) => {
+            w.write_all(&[VALUE_INTEGER])?;
+            write_varint(((i << 1) ^ (i >> 63)) as u64, w)
+        }
+        LuaValue::Number(LuaNumber::Float(f)) => {
+            w.write_all(&[VALUE_FLOAT])?;
+            w.write_all(&f.to_le_bytes())
+        }
+        LuaValue::String(s) => {
+            w.write_all(&[VALUE_STRING])?;
+            write_bytes(s, w)
+        }
+        LuaValue::Table(t) => {
+            w.write_all(&[VALUE_TABLE])?;
+            write_varint(t.len() as u64, w)?;
+            for entry in t {
+                write_entry(entry, w)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn write_entry<W: Write>(entry: &LuaTableEntry<'_>, w: &mut W) -> std::io::Result<()> {
+    match entry {
+        LuaTableEntry::KeyValue(b) => {
+            w.write_all(&[ENTRY_KEY_VALUE])?;
+            write_value(&b.0, w)?;
+            write_value(&b.1, w)
+        }
+        LuaTableEntry::NameValue(b) => {
+            w.write_all(&[ENTRY_NAME_VALUE])?;
+            write_bytes(b.0.as_bytes(), w)?;
+            write_value(&b.1, w)
+        }
+        LuaTableEntry::Value(v) => {
+            w.write_all(&[ENTRY_VALUE])?;
+            write_value(v, w)
+        }
+        LuaTableEntry::NumberValue(n) => {
+            w.write_all(&[ENTRY_NUMBER_VALUE])?;
+            write_value(&LuaValue::Number(*n), w)
+        }
+        LuaTableEntry::BooleanValue(b) => {
+            w.write_all(&[ENTRY_BOOLEAN_VALUE])?;
+            write_value(&LuaValue::Boolean(*b), w)
+        }
+        LuaTableEntry::NilValue => w.write_all(&[ENTRY_NIL_VALUE]),
+    }
+}
+
+/// Reads values from an encoded buffer.
+struct Reader<'a> {
+    b: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, BinaryError> {
+        let v = *self.b.get(self.pos).ok_or(BinaryError::UnexpectedEof)?;
+        self.pos += 1;
+        Ok(v)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], BinaryError> {
+        let v = self
+            .b
+            .get(self.pos..self.pos.saturating_add(len))
+            .ok_or(BinaryError::UnexpectedEof)?;
+        self.pos += len;
+        Ok(v)
+    }
+
+    fn varint(&mut self) -> Result<u64, BinaryError> {
+        let position = self.pos;
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            let bits = u64::from(byte & 0x7f);
+            if (bits << shift) >> shift != bits {
+                return Err(BinaryError::InvalidVarint { position });
+            }
+            v |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(BinaryError::InvalidVarint { position })
+    }
+
+    /// Reads a length, which can't be longer than the rest of the input.
+    fn len(&mut self) -> Result<usize, BinaryError> {
+        let position = self.pos;
+        let len = self.varint()?;
+        usize::try_from(len)
+            .ok()
+            .filter(|len| *len <= self.b.len() - self.pos)
+            .ok_or(BinaryError::InvalidLength { position })
+    }
+
+    fn value(&mut self, max_depth: u16) -> Result<LuaValue<'a>, BinaryError> {
+        let position = self.pos;
+        Ok(match self.byte()? {
+            VALUE_NIL => LuaValue::Nil,
+            VALUE_FALSE => LuaValue::Boolean(false),
+            VALUE_TRUE => LuaValue::Boolean(true),
+            VALUE_INTEGER => {
+                let v = self.varint()?;
+                LuaValue::integer(((v >> 1) as i64) ^ -((v & 1) as i64))
+            }
+            VALUE_FLOAT => {
+                let b = self.bytes(8)?;
+                LuaValue::float(f64::from_le_bytes(b.try_into().unwrap()))
+            }
+            VALUE_STRING => {
+                let len = self.len()?;
+                LuaValue::String(Cow::Borrowed(self.bytes(len)?))
+            }
+            VALUE_TABLE => {
+                if max_depth == 0 {
+                    return Err(BinaryError::TooDeeplyNested { position });
+                }
+
+                // Every entry is at least 1 byte, so this can't allocate more than the input
+                // could fill.
+                let len = self.len()?;
+                let mut entries = Vec::with_capacity(len);
+                for _ in 0..len {
+                    entries.push(self.entry(max_depth - 1)?);
+                }
+                LuaValue::Table(entries)
+            }
+            tag => return Err(BinaryError::InvalidTag { tag, position }),
+        })
+    }
+
+    fn entry(&mut self, max_depth: u16) -> Result<LuaTableEntry<'a>, BinaryError> {
+        let position = self.pos;
+        Ok(match self.byte()? {
+            ENTRY_KEY_VALUE => {
+                let key = self.value(max_depth)?;
+                let value = self.value(max_depth)?;
+                LuaTableEntry::KeyValue(Box::new((key, value)))
+            }
+            ENTRY_NAME_VALUE => {
+                let len = self.len()?;
+                let name_position = self.pos;
+                let name = from_utf8(self.bytes(len)?).map_err(|_| BinaryError::InvalidUtf8 {
+                    position: name_position,
+                })?;
+                let value = self.value(max_depth)?;
+                LuaTableEntry::NameValue(Box::new((Cow::Borrowed(name), value)))
+            }
+            ENTRY_VALUE => LuaTableEntry::Value(Box::new(self.value(max_depth)?)),
+            ENTRY_NUMBER_VALUE => match self.value(max_depth)? {
+                LuaValue::Number(n) => LuaTableEntry::NumberValue(n),
+                _ => {
+                    return Err(BinaryError::InvalidTag {
+                        tag: ENTRY_NUMBER_VALUE,
+                        position,
+                    })
+                }
+            },
+            ENTRY_BOOLEAN_VALUE => match self.value(max_depth)? {
+                LuaValue::Boolean(b) => LuaTableEntry::BooleanValue(b),
+                _ => {
+                    return Err(BinaryError::InvalidTag {
+                        tag: ENTRY_BOOLEAN_VALUE,
+                        position,
+                    })
+                }
+            },
+            ENTRY_NIL_VALUE => LuaTableEntry::NilValue,
+            tag => return Err(BinaryError::InvalidTag { tag, position }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn varints() {
+        for v in [
+            0,
+            1,
+            0x7f,
+            0x80,
+            0x3fff,
+            0x4000,
+            u64::from(u32::MAX),
+            u64::MAX,
+        ] {
+            let mut b = Vec::new();
+            write_varint(v, &mut b).unwrap();
+            let mut r = Reader { b: &b, pos: 0 };
+            assert_eq!(v, r.varint().unwrap(), "{v:#x}");
+            assert_eq!(b.len(), r.pos);
+        }
+
+        // Too many bits
+        let b = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02];
+        let mut r = Reader { b: &b, pos: 0 };
+        assert_eq!(
+            BinaryError::InvalidVarint { position: 0 },
+            r.varint().unwrap_err()
+        );
+
+        // Too many bytes
+        let b = [0x80; 11];
+        let mut r = Reader { b: &b, pos: 0 };
+        assert_eq!(
+            BinaryError::InvalidVarint { position: 0 },
+            r.varint().unwrap_err()
+        );
+    }
+
+    #[test]
+    fn integers() {
+        for i in [0, 1, -1, 63, -64, 64, i64::MAX, i64::MIN] {
+            let b = to_binary(&LuaValue::integer(i));
+            assert_eq!(LuaValue::integer(i), from_binary(&b, 0).unwrap(), "{i}");
+        }
+
+        // Small integers are short
+        assert_eq!(
+            b"LUAQ\x01\x03\x01",
+            to_binary(&LuaValue::integer(-1)).as_slice()
+        );
+    }
+}
@@ -0,0 +1,397 @@
+//! `bincode` encoding of parsed [`LuaValue`] trees.
+use crate::{LuaNumber, LuaTableEntry, LuaValue};
+use ::bincode::{
+    config::standard,
+    de::{BorrowDecoder, Decoder},
+    enc::Encoder,
+    error::{AllowedEnumVariants, DecodeError, EncodeError},
+    BorrowDecode, Decode, Encode,
+};
+use std::borrow::Cow;
+
+const VALUE_NIL: u8 = 0;
+const VALUE_BOOLEAN: u8 = 1;
+const VALUE_NUMBER: u8 = 2;
+const VALUE_STRING: u8 = 3;
+const VALUE_TABLE: u8 = 4;
+
+const NUMBER_INTEGER: u8 = 0;
+const NUMBER_FLOAT: u8 = 1;
+
+const ENTRY_KEY_VALUE: u8 = 0;
+const ENTRY_NAME_VALUE: u8 = 1;
+const ENTRY_VALUE: u8 = 2;
+const ENTRY_NUMBER_VALUE: u8 = 3;
+const ENTRY_BOOLEAN_VALUE: u8 = 4;
+const ENTRY_NIL_VALUE: u8 = 5;
+
+/// Upper bound for pre-allocating tables, so that a corrupt length can't allocate a huge buffer
+/// before running out of input.
+const MAX_PREALLOCATE: usize = 1024;
+
+impl Encode for LuaNumber {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        match self {
+            LuaNumber::Integer(i) => {
+                NUMBER_INTEGER.encode(encoder)?;
+                i.encode(encoder)
+            }
+            LuaNumber::Float(f) => {
+                NUMBER_FLOAT.encode(encoder)?;
+                f.encode(encoder)
+            }
+        }
+    }
+}
+
+impl<Context> Decode<Context> for LuaNumber {
+    fn decode<D: Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        match u8::decode(decoder)? {
+            NUMBER_INTEGER => Ok(LuaNumber::Integer(i64::decode(decoder)?)),
+            NUMBER_FLOAT => Ok(LuaNumber::Float(f64::decode(decoder)?)),
+            found => Err(unexpected_variant::<Self>(found, NUMBER_FLOAT)),
+        }
+    }
+}
+
+impl<'de, Context> BorrowDecode<'de, Context> for LuaNumber {
+    fn borrow_decode<D: BorrowDecoder<'de, Context = Context>>(
+        decoder: &mut D,
+    ) -> Result<Self, DecodeError> {
+        Self::decode(decoder)
+    }
+}
+
+impl Encode for LuaValue<'_> {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        match self {
+            LuaValue::Nil => VALUE_NIL.encode(encoder),
+            LuaValue::Boolean(b) => {
+                VALUE_BOOLEAN.encode(encoder)?;
+                b.encode(encoder)
+            }
+            LuaValue::Number(n) => {
+                VALUE_NUMBER.encode(encoder)?;
+                n.encode(encoder)
+            }
+            LuaValue::String(s) => {
+                VALUE_STRING.encode(encoder)?;
+                s.as_ref().encode(encoder)
+            }
+            LuaValue::Table(t) => {
+                VALUE_TABLE.encode(encoder)?;
+                t.len().encode(encoder)?;
+                for entry in t {
+                    entry.encode(encoder)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Encode for LuaTableEntry<'_> {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        match self {
+            LuaTableEntry::KeyValue(b) => {
+                ENTRY_KEY_VALUE.encode(encoder)?;
+                b.0.encode(encoder)?;
+                b.1.encode(encoder)
+            }
+            LuaTableEntry::NameValue(b) => {
+                ENTRY_NAME_VALUE.encode(encoder)?;
+                b.0.as_ref().encode(encoder)?;
+                b.1.encode(encoder)
+            }
+            LuaTableEntry::Value(v) => {
+                ENTRY_VALUE.encode(encoder)?;
+                v.encode(encoder)
+            }
+            LuaTableEntry::NumberValue(n) => {
+                ENTRY_NUMBER_VALUE.encode(encoder)?;
+                n.encode(encoder)
+            }
+            LuaTableEntry::BooleanValue(b) => {
+                ENTRY_BOOLEAN_VALUE.encode(encoder)?;
+                b.encode(encoder)
+            }
+            LuaTableEntry::NilValue => ENTRY_NIL_VALUE.encode(encoder),
+        }
+    }
+}
+
+impl<Context> Decode<Context> for LuaValue<'static> {
+    /// Decodes an owned [`LuaValue`], copying all strings.
+    ///
+    /// **Warning:** this has no table depth limit. Use [`from_bincode()`] for untrusted inputs.
+    fn decode<D: Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        decode_value(&mut Copying(decoder), u16::MAX)
+    }
+}
+
+impl<'de, Context> BorrowDecode<'de, Context> for LuaValue<'de> {
+    /// Decodes a [`LuaValue`] which borrows strings from the input.
+    ///
+    /// **Warning:** this has no table depth limit. Use [`from_bincode()`] for untrusted inputs.
+    fn borrow_decode<D: BorrowDecoder<'de, Context = Context>>(
+        decoder: &mut D,
+    ) -> Result<Self, DecodeError> {
+        decode_value(&mut Borrowing(decoder), u16::MAX)
+    }
+}
+
+impl<Context> Decode<Context> for LuaTableEntry<'static> {
+    fn decode<D: Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        decode_entry(&mut Copying(decoder), u16::MAX)
+    }
+}
+
+impl<'de, Context> BorrowDecode<'de, Context> for LuaTableEntry<'de> {
+    fn borrow_decode<D: BorrowDecoder<'de, Context = Context>>(
+        decoder: &mut D,
+    ) -> Result<Self, DecodeError> {
+        decode_entry(&mut Borrowing(decoder), u16::MAX)
+    }
+}
+
+/// Decodes strings from a [`Decoder`], either by copying or borrowing them.
+trait StringDecoder<'de> {
+    type D: Decoder;
+
+    fn decoder(&mut self) -> &mut Self::D;
+    fn decode_bytes(&mut self) -> Result<Cow<'de, [u8]>, DecodeError>;
+    fn decode_str(&mut self) -> Result<Cow<'de, str>, DecodeError>;
+}
+
+/// Copies strings into owned buffers.
+struct Copying<'a, D>(&'a mut D);
+
+impl<'de, D: Decoder> StringDecoder<'de> for Copying<'_, D> {
+    type D = D;
+
+    #[inline]
+    fn decoder(&mut self) -> &mut D {
+        self.0
+    }
+
+    fn decode_bytes(&mut self) -> Result<Cow<'de, [u8]>, DecodeError> {
+        Vec::<u8>::decode(self.0).map(Cow::Owned)
+    }
+
+    fn decode_str(&mut self) -> Result<Cow<'de, str>, DecodeError> {
+        String::decode(self.0).map(Cow::Owned)
+    }
+}
+
+/// Borrows strings from the input.
+struct Borrowing<'a, D>(&'a mut D);
+
+impl<'de, D: BorrowDecoder<'de>> StringDecoder<'de> for Borrowing<'_, D> {
+    type D = D;
+
+    #[inline]
+    fn decoder(&mut self) -> &mut D {
+        self.0
+    }
+
+    fn decode_bytes(&mut self) -> Result<Cow<'de, [u8]>, DecodeError> {
+        <&'de [u8]>::borrow_decode(self.0).map(Cow::Borrowed)
+    }
+
+    fn decode_str(&mut self) -> Result<Cow<'de, str>, DecodeError> {
+        <&'de str>::borrow_decode(self.0).map(Cow::Borrowed)
+    }
+}
+
+#[cold]
+fn unexpected_variant<T>(found: u8, max: u8) -> DecodeError {
+    // AllowedEnumVariants must be 'static
+    static ALLOWED: [AllowedEnumVariants; 6] = [
+        AllowedEnumVariants::Range { min: 0, max: 0 },
+        AllowedEnumVariants::Range { min: 0, max: 1 },
+        AllowedEnumVariants::Range { min: 0, max: 2 },
+        AllowedEnumVariants::Range { min: 0, max: 3 },
+        AllowedEnumVariants::Range { min: 0, max: 4 },
+        AllowedEnumVariants::Range { min: 0, max: 5 },
+    ];
+
+    DecodeError::UnexpectedVariant {
+        type_name: std::any::type_name::<T>(),
+        allowed: &ALLOWED[usize::from(max)],
+        found: found.into(),
+    }
+}
+
+fn decode_value<'de, S: StringDecoder<'de>>(
+    s: &mut S,
+    max_depth: u16,
+) -> Result<LuaValue<'de>, DecodeError> {
+    Ok(match u8::decode(s.decoder())? {
+        VALUE_NIL => LuaValue::Nil,
+        VALUE_BOOLEAN => LuaValue::Boolean(bool::decode(s.decoder())?),
+        VALUE_NUMBER => LuaValue::Number(LuaNumber::decode(s.decoder())?),
+        VALUE_STRING => LuaValue::String(s.decode_bytes()?),
+        VALUE_TABLE => {
+            if max_depth == 0 {
+                return Err(DecodeError::Other("too deeply nested"));
+            }
+
+            let len = usize::decode(s.decoder())?;
+            s.decoder().claim_container_read::<LuaTableEntry>(len)?;
+            let mut entries = Vec::with_capacity(len.min(MAX_PREALLOCATE));
+            for _ in 0..len {
+                entries.push(decode_entry(s, max_depth - 1)?);
+            }
+            LuaValue::Table(entries)
+        }
+        found => return Err(unexpected_variant::<LuaValue>(found, VALUE_TABLE)),
+    })
+}
+
+fn decode_entry<'de, S: StringDecoder<'de>>(
+    s: &mut S,
+    max_depth: u16,
+) -> Result<LuaTableEntry<'de>, DecodeError> {
+    Ok(match u8::decode(s.decoder())? {
+        ENTRY_KEY_VALUE => {
+            let key = decode_value(s, max_depth)?;
+            let value = decode_value(s, max_depth)?;
+            LuaTableEntry::KeyValue(Box::new((key, value)))
+        }
+        ENTRY_NAME_VALUE => {
+            let name = s.decode_str()?;
+            let value = decode_value(s, max_depth)?;
+            LuaTableEntry::NameValue(Box::new((name, value)))
+        }
+        ENTRY_VALUE => LuaTableEntry::Value(Box::new(decode_value(s, max_depth)?)),
+        ENTRY_NUMBER_VALUE => LuaTableEntry::NumberValue(LuaNumber::decode(s.decoder())?),
+        ENTRY_BOOLEAN_VALUE => LuaTableEntry::BooleanValue(bool::decode(s.decoder())?),
+        ENTRY_NIL_VALUE => LuaTableEntry::NilValue,
+        found => return Err(unexpected_variant::<LuaTableEntry>(found, ENTRY_NIL_VALUE)),
+    })
+}
+
+/// [`LuaValue`] with a table depth limit, passed as the decoder's context.
+struct Limited<'de>(LuaValue<'de>);
+
+impl<'de> BorrowDecode<'de, u16> for Limited<'de> {
+    fn borrow_decode<D: BorrowDecoder<'de, Context = u16>>(
+        decoder: &mut D,
+    ) -> Result<Self, DecodeError> {
+        let max_depth = *decoder.context();
+        decode_value(&mut Borrowing(decoder), max_depth).map(Limited)
+    }
+}
+
+/// Encodes a [`LuaValue`] with [`bincode`][::bincode]'s [standard configuration][standard].
+///
+/// This can be used to cache a parsed [`LuaValue`], and then load it again with
+/// [`from_bincode()`] without re-parsing the Lua source.
+///
+/// [`LuaValue`], [`LuaTableEntry`] and [`LuaNumber`] also implement `bincode`'s [`Encode`],
+/// [`Decode`] and [`BorrowDecode`] traits, so they can be used in your own `bincode` types.
+///
+/// Requires the `bincode` feature.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{from_bincode, lua_value, to_bincode};
+///
+/// let value = lua_value(br#"{hello = "world", 1, 2, 3}"#, 16).unwrap();
+/// let cache = to_bincode(&value).unwrap();
+///
+/// // Strings borrow from `cache`, if they can.
+/// assert_eq!(value, from_bincode(&cache, 16).unwrap());
+/// ```
+pub fn to_bincode(value: &LuaValue<'_>) -> Result<Vec<u8>, EncodeError> {
+    ::bincode::encode_to_vec(value, standard())
+}
+
+/// Decodes a [`LuaValue`] encoded by [`to_bincode()`].
+///
+/// All strings borrow from `b`, which makes this suitable for use with memory-mapped files.
+///
+/// `max_depth` limits table nesting in the same way as [`lua_value()`][crate::lua_value]. This
+/// returns an error if `b` contains anything after the encoded value.
+///
+/// Requires the `bincode` feature.
+pub fn from_bincode(b: &[u8], max_depth: u16) -> Result<LuaValue<'_>, DecodeError> {
+    let (Limited(value), len) =
+        ::bincode::borrow_decode_from_slice_with_context(b, standard(), max_depth)?;
+
+    if len != b.len() {
+        return Err(DecodeError::Other("trailing bytes after value"));
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lua_value;
+
+    #[test]
+    fn round_trip() {
+        let value = lua_value(
+            br#"{
+                nil, true, false, 1, 1.5, -0x7fffffffffffffff, (0/0), 1e9999, "hello",
+                "\0\xff", {}, {{{}}}, a = 1, [true] = false, [{}] = "x", [1.5] = nil,
+                ["\u{65E5}"] = "\xe6\x97\xa5",
+            }"#,
+            16,
+        )
+        .unwrap();
+
+        let b = to_bincode(&value).unwrap();
+        let decoded = from_bincode(&b, 16).unwrap();
+        // (0/0) != (0/0), so compare the encoding
+        assert_eq!(b, to_bincode(&decoded).unwrap());
+
+        let LuaValue::Table(t) = &decoded else {
+            panic!("expected table");
+        };
+        assert!(matches!(t[6], LuaTableEntry::NumberValue(n) if n.is_nan()));
+        assert!(matches!(
+            t[8].value(),
+            Some(LuaValue::String(Cow::Borrowed(_)))
+        ));
+
+        // Owned decoding
+        let (owned, len): (LuaValue<'static>, usize) =
+            ::bincode::decode_from_slice(&b, standard()).unwrap();
+        assert_eq!(b.len(), len);
+        assert_eq!(b, to_bincode(&owned).unwrap());
+    }
+
+    #[test]
+    fn depth_limit() {
+        let value = lua_value(b"{{{1}}}", 16).unwrap();
+        let b = to_bincode(&value).unwrap();
+        assert_eq!(value, from_bincode(&b, 3).unwrap());
+        assert!(from_bincode(&b, 2).is_err());
+        assert!(from_bincode(&b, 0).is_err());
+
+        // Scalars don't need any depth
+        let b = to_bincode(&LuaValue::integer(1)).unwrap();
+        assert_eq!(LuaValue::integer(1), from_bincode(&b, 0).unwrap());
+    }
+
+    #[test]
+    fn invalid() {
+        // Empty
+        assert!(from_bincode(b"", 16).is_err());
+        // Bad tag
+        assert!(matches!(
+            from_bincode(b"\x05", 16),
+            Err(DecodeError::UnexpectedVariant { found: 5, .. })
+        ));
+        // Trailing bytes
+        assert!(from_bincode(b"\x00\x00", 16).is_err());
+        // Truncated table with a huge length
+        assert!(from_bincode(b"\x04\xfb\xff\xff\xff\xff", 16).is_err());
+        // Invalid UTF-8 in a name
+        assert!(from_bincode(b"\x04\x01\x01\x01\xff\x00", 16).is_err());
+    }
+}
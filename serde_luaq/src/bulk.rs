@@ -0,0 +1,80 @@
+//! Parallel deserialisation of many inputs, using `rayon`.
+use crate::{from_slice_with_options, BulkError, DeserializeOptions, Error, LuaFormat};
+use rayon::prelude::*;
+use serde::de::DeserializeOwned;
+use std::{fs, path::Path};
+
+/// Deserialises many byte slices in parallel, like [`from_slice_with_options()`].
+///
+/// Results are returned in the same order as `inputs`, and an error in one input doesn't stop
+/// the others from being deserialised.
+///
+/// This uses `rayon`'s global thread pool. To limit the number of threads, call this inside
+/// [`ThreadPool::install()`](https://docs.rs/rayon/1/rayon/struct.ThreadPool.html#method.install).
+///
+/// ## Example
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use serde_luaq::{par_from_slices, DeserializeOptions, LuaFormat};
+///
+/// #[derive(Deserialize, Debug, PartialEq)]
+/// struct Save {
+///     level: i64,
+/// }
+///
+/// let inputs: [&[u8]; 3] = [b"level = 1", b"level = 2", b"level = 'three'"];
+/// let saves = par_from_slices::<_, Save>(
+///     &inputs,
+///     LuaFormat::Script,
+///     16,
+///     &DeserializeOptions::new(),
+/// );
+///
+/// assert_eq!(&Save { level: 1 }, saves[0].as_ref().unwrap());
+/// assert_eq!(&Save { level: 2 }, saves[1].as_ref().unwrap());
+/// assert!(saves[2].is_err());
+/// ```
+pub fn par_from_slices<I, T>(
+    inputs: &[I],
+    format: LuaFormat,
+    max_depth: u16,
+    opts: &DeserializeOptions,
+) -> Vec<Result<T, Error>>
+where
+    I: AsRef<[u8]> + Sync,
+    T: DeserializeOwned + Send,
+{
+    inputs
+        .par_iter()
+        .map(|b| from_slice_with_options(b.as_ref(), format, max_depth, opts))
+        .collect()
+}
+
+/// Reads and deserialises many files in parallel, like [`from_slice_with_options()`].
+///
+/// Results are returned in the same order as `paths`, and an error in one file doesn't stop the
+/// others from being deserialised.
+///
+/// Each file is only kept in memory while it is being deserialised, so at most one file per
+/// thread is in memory at a time (plus the deserialised results). This uses `rayon`'s global
+/// thread pool. To limit the number of threads, call this inside
+/// [`ThreadPool::install()`](https://docs.rs/rayon/1/rayon/struct.ThreadPool.html#method.install).
+pub fn par_from_files<P, T>(
+    paths: &[P],
+    format: LuaFormat,
+    max_depth: u16,
+    opts: &DeserializeOptions,
+) -> Vec<Result<T, BulkError>>
+where
+    P: AsRef<Path> + Sync,
+    T: DeserializeOwned + Send,
+{
+    paths
+        .par_iter()
+        .map(|path| {
+            let b = fs::read(path)?;
+            Ok(from_slice_with_options(&b, format, max_depth, opts)?)
+        })
+        .collect()
+}
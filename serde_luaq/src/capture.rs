@@ -0,0 +1,197 @@
+//! Capturing unknown struct fields and other values as [`LuaValue`].
+use crate::{valid_lua_identifier, LuaNumber, LuaTableEntry, LuaValue};
+use serde::{
+    de::{Error as _, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use std::{borrow::Cow, fmt::Formatter};
+
+/// Captures table entries which a struct does not otherwise model, so they can be preserved.
+///
+/// Use this with [`#[serde(flatten)]`][flatten] on a struct field. Any table entries which don't
+/// match another field of the struct are collected, in order.
+///
+/// This is the complement of [`#[serde(deny_unknown_fields)]`][deny], and is useful for save
+/// editors which need to preserve data they don't understand.
+///
+/// Like with other [flattened fields][crate#flattening], values are passed through Serde's
+/// "any" type, which means:
+///
+/// * string keys which are valid Lua identifiers are captured as
+///   [`LuaTableEntry::NameValue`], and all other keys as [`LuaTableEntry::KeyValue`]
+///
+/// * nested tables which look like [sequences][crate#tables-as-lists-in-serde-vec] are captured
+///   with [implicit keys][LuaTableEntry::Value]
+///
+/// * all strings are copied
+///
+/// ## Example
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use serde_luaq::{from_slice, LuaFormat, LuaTableEntry, LuaValue, UnknownFields};
+///
+/// #[derive(Deserialize, Debug, PartialEq)]
+/// struct Player {
+///     name: String,
+///     #[serde(flatten)]
+///     unknown: UnknownFields,
+/// }
+///
+/// let player: Player = from_slice(
+///     b"{name = 'Alice', level = 3, ['inventory'] = {'sword', 'shield'}}",
+///     LuaFormat::Value,
+///     16,
+/// ).unwrap();
+///
+/// assert_eq!("Alice", player.name);
+/// assert_eq!(
+///     LuaValue::from_iter([
+///         ("level", LuaValue::integer(3)),
+///         ("inventory", LuaValue::Table(vec![
+///             LuaTableEntry::from(LuaValue::from("sword")),
+///             LuaTableEntry::from(LuaValue::from("shield")),
+///         ])),
+///     ]),
+///     LuaValue::from(player.unknown),
+/// );
+/// ```
+///
+/// [deny]: https://serde.rs/container-attrs.html#deny_unknown_fields
+/// [flatten]: https://serde.rs/attr-flatten.html
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct UnknownFields(pub Vec<LuaTableEntry<'static>>);
+
+impl UnknownFields {
+    /// Returns `true` if there were no unknown fields.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<UnknownFields> for LuaValue<'static> {
+    fn from(value: UnknownFields) -> Self {
+        LuaValue::Table(value.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for UnknownFields {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match deserializer.deserialize_map(CaptureVisitor)? {
+            LuaValue::Table(t) => Ok(UnknownFields(t)),
+            _ => Err(D::Error::custom("expected table")),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LuaValue<'_> {
+    /// Deserialises any value into an owned [`LuaValue`].
+    ///
+    /// See [the type-level docs][LuaValue#serde] for limitations.
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(CaptureVisitor)
+    }
+}
+
+struct CaptureVisitor;
+
+impl<'de> Visitor<'de> for CaptureVisitor {
+    type Value = LuaValue<'static>;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("any Lua value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(LuaValue::Boolean(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(LuaValue::integer(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(LuaValue::Number(
+            LuaNumber::try_from(v).unwrap_or(LuaNumber::Float(v as f64)),
+        ))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(LuaValue::float(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(LuaValue::String(Cow::Owned(v.as_bytes().to_vec())))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E> {
+        Ok(LuaValue::String(Cow::Owned(v.into_bytes())))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(LuaValue::String(Cow::Owned(v.to_vec())))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(LuaValue::String(Cow::Owned(v)))
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E> {
+        Ok(LuaValue::Nil)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(LuaValue::Nil)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut entries = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        while let Some(v) = seq.next_element::<LuaValue>()? {
+            entries.push(LuaTableEntry::from(v));
+        }
+
+        Ok(LuaValue::Table(entries))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or_default());
+        while let Some((k, v)) = map.next_entry::<LuaValue, LuaValue>()? {
+            entries.push(match k {
+                LuaValue::String(Cow::Owned(k)) if valid_lua_identifier(&k) => {
+                    // Identifiers are always ASCII
+                    let k = String::from_utf8(k).map_err(A::Error::custom)?;
+                    LuaTableEntry::NameValue(Box::new((Cow::Owned(k), v)))
+                }
+                k => LuaTableEntry::KeyValue(Box::new((k, v))),
+            });
+        }
+
+        Ok(LuaValue::Table(entries))
+    }
+}
@@ -0,0 +1,474 @@
+//! Generated corpus of Lua scripts, for seeding fuzzers and regression tests.
+use std::fmt::Write;
+
+/// A generated Lua script, from [`corpus()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CorpusEntry {
+    /// Unique, filename-safe name for the entry, without an extension.
+    pub name: String,
+
+    /// Lua script source.
+    pub data: Vec<u8>,
+
+    /// `true` if the script should parse with [`script()`][crate::script()] with a maximum table
+    /// depth of [`CORPUS_MAX_DEPTH`].
+    pub valid: bool,
+}
+
+/// Maximum table depth that [`corpus()`] entries are generated for.
+///
+/// This is the same as the `fuzz-target` crate.
+pub const CORPUS_MAX_DEPTH: u16 = 200;
+
+/// Every escape sequence form, as `(name, escape)`.
+const ESCAPES: &[(&str, &str)] = &[
+    ("bell", r"\a"),
+    ("backspace", r"\b"),
+    ("form_feed", r"\f"),
+    ("newline", r"\n"),
+    ("carriage_return", r"\r"),
+    ("tab", r"\t"),
+    ("vertical_tab", r"\v"),
+    ("backslash", r"\\"),
+    ("double_quote", r#"\""#),
+    ("single_quote", r"\'"),
+    ("escaped_lf", "\\\n"),
+    ("escaped_cr", "\\\r"),
+    ("escaped_crlf", "\\\r\n"),
+    ("escaped_lfcr", "\\\n\r"),
+    ("z_empty", r"\z"),
+    ("z_whitespace", "\\z \t\r\n\x0B\x0C "),
+    ("hex_00", r"\x00"),
+    ("hex_7f", r"\x7F"),
+    ("hex_ff", r"\xff"),
+    ("dec_0", r"\0"),
+    ("dec_09", r"\09"),
+    ("dec_255", r"\255"),
+    ("dec_0255", r"\0255"),
+    ("unicode_0", r"\u{0}"),
+    ("unicode_7f", r"\u{7F}"),
+    ("unicode_80", r"\u{80}"),
+    ("unicode_7ff", r"\u{7ff}"),
+    ("unicode_800", r"\u{800}"),
+    ("unicode_ffff", r"\u{FFFF}"),
+    ("unicode_10000", r"\u{10000}"),
+    ("unicode_10ffff", r"\u{10FFFF}"),
+    ("unicode_padded", r"\u{00000000000041}"),
+    ("unicode_surrogate", r"\u{D800}"),
+    ("unicode_110000", r"\u{110000}"),
+    ("unicode_7fffffff", r"\u{7FFFFFFF}"),
+];
+
+/// Invalid escape sequences.
+const BAD_ESCAPES: &[(&str, &str)] = &[
+    ("unknown", r"\q"),
+    ("hex_short", r"\x1"),
+    ("hex_invalid", r"\xZZ"),
+    ("dec_256", r"\256"),
+    ("unicode_empty", r"\u{}"),
+    ("unicode_unterminated", r"\u{41"),
+    ("unicode_80000000", r"\u{80000000}"),
+    ("trailing", "\\"),
+];
+
+/// Every number literal form, as `(name, literal)`.
+const NUMBERS: &[(&str, &str)] = &[
+    ("zero", "0"),
+    ("negative_zero", "-0"),
+    ("integer", "1234"),
+    ("negative", "-1234"),
+    ("leading_zeros", "000123"),
+    ("i64_max", "9223372036854775807"),
+    ("i64_min", "-9223372036854775808"),
+    ("i64_overflow", "9223372036854775808"),
+    ("huge_integer", "123456789012345678901234567890"),
+    ("hex", "0xff"),
+    ("hex_upper", "0XFF"),
+    ("negative_hex", "-0x10"),
+    ("hex_wrapping", "0xffffffffffffffffff"),
+    ("float", "3.14"),
+    ("float_trailing_dot", "3."),
+    ("float_leading_dot", ".5"),
+    ("negative_float", "-2.5"),
+    ("exponent", "1e10"),
+    ("exponent_upper", "1E10"),
+    ("exponent_negative", "1e-10"),
+    ("exponent_positive", "1.5e+3"),
+    ("float_exponent", ".5e2"),
+    ("hex_float", "0x1.8"),
+    ("hex_float_exponent", "0x1.8p4"),
+    ("hex_float_leading_dot", "0x.8p1"),
+    ("hex_float_upper", "0XA.8P-2"),
+    ("hex_exponent", "0x1p-1074"),
+    ("infinity", "1e9999"),
+    ("negative_infinity", "-1e9999"),
+    ("nan", "(0/0)"),
+    ("denormal", "4.9e-324"),
+    ("f64_max", "1.7976931348623157e308"),
+];
+
+/// Invalid number literals.
+const BAD_NUMBERS: &[(&str, &str)] = &[
+    ("double_negative", "--1"),
+    ("plus", "+1"),
+    ("hex_empty", "0x"),
+    ("exponent_empty", "1e"),
+    ("hex_float_exponent_empty", "0x1p"),
+    ("dot", "."),
+    ("nan_unbracketed", "0/0"),
+];
+
+/// Whitespace characters which may appear between tokens.
+const WHITESPACE: &[(&str, &str)] = &[
+    ("none", ""),
+    ("space", " "),
+    ("tab", "\t"),
+    ("lf", "\n"),
+    ("crlf", "\r\n"),
+    ("vertical_tab", "\x0B"),
+    ("form_feed", "\x0C"),
+    ("mixed", " \t\r\n\x0B\x0C "),
+];
+
+/// Generates a corpus of structurally diverse Lua scripts.
+///
+/// This covers all escape sequence forms, number literal forms, string delimiters, table entry
+/// types and nesting patterns that the parser supports, as well as some common mistakes. Entries
+/// are generated in a stable order, and the output does not depend on anything else.
+///
+/// All entries are scripts, to match the `fuzz-target` crate, and have a `name` which is unique
+/// and safe to use as a filename.
+///
+/// This is available with the `corpus` feature. The `generate_corpus` example writes the corpus to
+/// a directory.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{corpus, script, CORPUS_MAX_DEPTH};
+///
+/// for entry in corpus() {
+///     assert_eq!(
+///         entry.valid,
+///         script(&entry.data, CORPUS_MAX_DEPTH).is_ok(),
+///         "{}", entry.name,
+///     );
+/// }
+/// ```
+pub fn corpus() -> Vec<CorpusEntry> {
+    let mut c = Corpus::default();
+    c.strings();
+    c.numbers();
+    c.tables();
+    c.nesting();
+    c.scripts();
+    c.0
+}
+
+#[derive(Default)]
+struct Corpus(Vec<CorpusEntry>);
+
+impl Corpus {
+    fn push(&mut self, name: String, data: impl Into<Vec<u8>>, valid: bool) {
+        debug_assert!(!self.0.iter().any(|e| e.name == name), "{name}");
+        self.0.push(CorpusEntry {
+            name,
+            data: data.into(),
+            valid,
+        });
+    }
+
+    /// Pushes a script assigning a single value.
+    fn value(&mut self, name: String, value: &str, valid: bool) {
+        self.push(name, format!("v = {value}\n"), valid);
+    }
+
+    fn strings(&mut self) {
+        for (quote_name, quote) in [("single", '\''), ("double", '"')] {
+            for (name, escape) in ESCAPES {
+                self.value(
+                    format!("escape_{quote_name}_{name}"),
+                    &format!("{quote}a{escape}b{quote}"),
+                    true,
+                );
+            }
+
+            for (name, escape) in BAD_ESCAPES {
+                self.value(
+                    format!("bad_escape_{quote_name}_{name}"),
+                    &format!("{quote}a{escape}b"),
+                    false,
+                );
+            }
+
+            // All escapes in one string
+            let all: String = ESCAPES.iter().map(|(_, e)| *e).collect();
+            self.value(
+                format!("escape_{quote_name}_all"),
+                &format!("{quote}{all}{quote}"),
+                true,
+            );
+
+            // The other kind of quote doesn't need escaping
+            let other = if quote == '"' { '\'' } else { '"' };
+            self.value(
+                format!("string_{quote_name}_other_quote"),
+                &format!("{quote}{other}{quote}"),
+                true,
+            );
+            self.value(
+                format!("string_{quote_name}_empty"),
+                &format!("{quote}{quote}"),
+                true,
+            );
+            self.value(
+                format!("string_{quote_name}_unescaped"),
+                &format!("{quote}\u{0}\u{7f}\u{80}\u{ff}\u{fffd}{quote}"),
+                true,
+            );
+            self.value(
+                format!("bad_string_{quote_name}_newline"),
+                &format!("{quote}a\nb{quote}"),
+                false,
+            );
+            self.value(
+                format!("bad_string_{quote_name}_unterminated"),
+                &format!("{quote}abc"),
+                false,
+            );
+        }
+
+        for level in 0..=5 {
+            let eq = "=".repeat(level);
+            for (name, body) in [
+                ("empty", ""),
+                ("simple", "hello"),
+                ("leading_lf", "\nhello"),
+                ("leading_crlf", "\r\nhello"),
+                ("only_lf", "\n"),
+                ("escapes", r"\n\x00\u{41}"),
+                ("brackets", "]] ]=] [[ [=["),
+                ("multiline", "a\nb\r\nc\n\r"),
+            ] {
+                if level < 2 && name == "brackets" {
+                    // Would close the string early
+                    continue;
+                }
+                self.value(
+                    format!("long_string_{level}_{name}"),
+                    &format!("[{eq}[{body}]{eq}]"),
+                    true,
+                );
+            }
+
+            self.value(
+                format!("bad_long_string_{level}_unterminated"),
+                &format!("[{eq}[hello]{eq}"),
+                false,
+            );
+            self.value(
+                format!("bad_long_string_{level}_mismatched"),
+                &format!("[{eq}[hello]{eq}=]"),
+                false,
+            );
+        }
+    }
+
+    fn numbers(&mut self) {
+        for (name, n) in NUMBERS {
+            self.value(format!("number_{name}"), n, true);
+        }
+
+        for (name, n) in BAD_NUMBERS {
+            self.value(format!("bad_number_{name}"), n, false);
+        }
+
+        let all: Vec<&str> = NUMBERS.iter().map(|(_, n)| *n).collect();
+        self.value(
+            "number_all".to_string(),
+            &format!("{{{}}}", all.join(",")),
+            true,
+        );
+    }
+
+    fn tables(&mut self) {
+        for (name, t) in [
+            ("empty", "{}"),
+            ("implicit", "{1, 2, 3}"),
+            ("implicit_types", "{nil, true, false, 1, 1.5, 'a', {}}"),
+            ("names", "{a = 1, b = 2, _c = 3, d1 = 4}"),
+            (
+                "keyword_like_names",
+                "{nil_ = 1, truer = 2, ends = 3, _ENV = 4}",
+            ),
+            (
+                "string_keys",
+                r#"{["a"] = 1, ["b c"] = 2, [""] = 3, ["\0"] = 4}"#,
+            ),
+            (
+                "integer_keys",
+                "{[1] = 'a', [2] = 'b', [-1] = 'c', [0] = 'd'}",
+            ),
+            ("float_keys", "{[1.5] = 'a', [2.0] = 'b', [1e9999] = 'c'}"),
+            ("boolean_keys", "{[true] = 1, [false] = 0}"),
+            ("table_keys", "{[{}] = 1, [{1}] = 2}"),
+            ("duplicate_keys", "{a = 1, a = 2, [1] = 3, 4}"),
+            ("mixed", "{1, a = 2, [3] = 4, 'b', ['c'] = {5}}"),
+            ("trailing_comma", "{1, 2,}"),
+            ("trailing_semicolon", "{1, 2;}"),
+            ("semicolons", "{1; 2; a = 3}"),
+            ("mixed_separators", "{1, 2; 3, 4}"),
+            ("sparse", "{[1] = 1, [3] = 3, [1000000] = 4}"),
+        ] {
+            self.value(format!("table_{name}"), t, true);
+        }
+
+        for (name, t) in [
+            ("unterminated", "{1, 2"),
+            ("double_comma", "{1,, 2}"),
+            ("leading_comma", "{, 1}"),
+            ("double_trailing", "{1,,}"),
+            ("keyword_name", "{end = 1}"),
+            ("nil_name", "{nil = 1}"),
+            ("unterminated_key", "{[1 = 2}"),
+            ("missing_value", "{a = }"),
+        ] {
+            self.value(format!("bad_table_{name}"), t, false);
+        }
+
+        for (name, ws) in WHITESPACE {
+            self.value(
+                format!("whitespace_{name}"),
+                &format!("{ws}{{{ws}1{ws},{ws}a{ws}={ws}2{ws},{ws}[{ws}3{ws}]{ws}={ws}4{ws}}}{ws}"),
+                true,
+            );
+        }
+    }
+
+    fn nesting(&mut self) {
+        // The script assignment doesn't count towards the depth limit.
+        let limit = usize::from(CORPUS_MAX_DEPTH);
+        for depth in [1, 2, 16, 17, 100, limit, limit + 1, 8192] {
+            let valid = depth <= limit;
+            self.value(
+                format!("nesting_implicit_{depth}"),
+                &format!("{}{}", "{".repeat(depth), "}".repeat(depth)),
+                valid,
+            );
+
+            let mut named = String::new();
+            for _ in 0..depth {
+                named.push_str("{a = ");
+            }
+            named.push('1');
+            named.push_str(&"}".repeat(depth));
+            self.value(format!("nesting_named_{depth}"), &named, valid);
+
+            let mut keyed = String::new();
+            for _ in 0..depth {
+                keyed.push_str("{[");
+            }
+            keyed.push('1');
+            keyed.push_str(&"] = 1}".repeat(depth));
+            self.value(format!("nesting_keyed_{depth}"), &keyed, valid);
+        }
+
+        // Unbalanced
+        self.value("bad_nesting_unbalanced".to_string(), "{{{}}", false);
+
+        // Wide and deep
+        let mut wide = String::from("{");
+        for i in 0..256 {
+            write!(wide, "{{{i}, k{i} = {{'{i}'}}}},").unwrap();
+        }
+        wide.push('}');
+        self.value("nesting_wide".to_string(), &wide, true);
+
+        // A binary tree
+        fn tree(depth: u32, o: &mut String) {
+            if depth == 0 {
+                o.push_str("true");
+            } else {
+                o.push_str("{l = ");
+                tree(depth - 1, o);
+                o.push_str(", r = ");
+                tree(depth - 1, o);
+                o.push('}');
+            }
+        }
+        let mut t = String::new();
+        tree(10, &mut t);
+        self.value("nesting_tree".to_string(), &t, true);
+    }
+
+    fn scripts(&mut self) {
+        for (name, s) in [
+            ("empty", ""),
+            ("only_whitespace", " \t\r\n"),
+            ("multiple", "a = 1\nb = 2\nc = 3\n"),
+            ("one_line", "a = 1 b = 2 c = 3"),
+            ("semicolons", "a = 1; b = 2;; c = 3;"),
+            ("reassign", "a = 1\na = 2\n"),
+            ("crlf", "a = 1\r\nb = {\r\n1\r\n}\r\n"),
+            ("no_spaces", "a=1;b={1,2}"),
+            ("long_names", "abcdefghijklmnopqrstuvwxyz_0123456789 = true"),
+            ("underscore", "_ = 1\n__ = 2\n_G_ = 3"),
+            (
+                "comments",
+                "-- comment\na = 1 --[[ long ]] b = 2 --[==[\nlonger\n]==]",
+            ),
+        ] {
+            self.push(format!("script_{name}"), s, true);
+        }
+
+        let mut many = String::new();
+        for i in 0..1000 {
+            writeln!(many, "v{i} = {i}").unwrap();
+        }
+        self.push("script_many".to_string(), many, true);
+
+        for (name, s) in [
+            ("keyword", "end = 1"),
+            ("digit_name", "1a = 1"),
+            ("missing_value", "a = "),
+            ("missing_equals", "a 1"),
+            ("return", "return 1"),
+            ("bare_value", "{}"),
+            ("local", "local a = 1"),
+            ("dotted", "a.b = 1"),
+            ("multiple_assignment", "a, b = 1, 2"),
+            ("trailing_garbage", "a = 1 }"),
+        ] {
+            self.push(format!("bad_script_{name}"), s, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::script;
+
+    #[test]
+    fn valid() {
+        let corpus = corpus();
+        assert!(corpus.len() > 200);
+
+        for entry in corpus {
+            assert!(!entry.name.is_empty());
+            assert!(entry
+                .name
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'_'));
+
+            let r = script(&entry.data, CORPUS_MAX_DEPTH);
+            assert_eq!(entry.valid, r.is_ok(), "{}: {r:?}", entry.name);
+        }
+    }
+
+    #[test]
+    fn stable() {
+        assert_eq!(corpus(), corpus());
+    }
+}
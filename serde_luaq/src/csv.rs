@@ -0,0 +1,286 @@
+//! CSV export of tables of records.
+use crate::{CsvExportError, LuaTableEntry, LuaValue};
+use std::{borrow::Cow, io::Write};
+
+/// Options for [`to_csv()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CsvOptions {
+    /// Columns to export, in order.
+    ///
+    /// If [`None`] (the default), columns are [detected][csv_columns] from the first row, and
+    /// every row must have exactly the same columns.
+    ///
+    /// If set, only these columns are exported, and fields which aren't listed are ignored. Rows
+    /// which are missing a column have an empty value.
+    pub columns: Option<Vec<String>>,
+
+    /// Write a header row containing the column names, if there are any columns. Defaults to
+    /// `true`.
+    pub header: bool,
+}
+
+impl CsvOptions {
+    /// Creates options which detect columns and write a header row.
+    pub const fn new() -> Self {
+        Self {
+            columns: None,
+            header: true,
+        }
+    }
+
+    /// Sets the [`columns`][Self::columns] to export, in order.
+    pub fn columns<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.columns = Some(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Sets [`header`][Self::header].
+    pub const fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A row of a rectangular table: its string keys and values, in order.
+type Row<'v, 'a> = Vec<(Cow<'v, str>, &'v LuaValue<'a>)>;
+
+/// Detects the columns of a table of records, like `{{a = 1, b = 2}, {a = 3, b = 4}}`.
+///
+/// `value` must be a table where every entry's value is itself a table (a row), and every row has
+/// the same string keys (columns) with non-table values. Keys of the outer table are ignored.
+///
+/// Columns are returned in the order they appear in the first row. An empty table has no columns.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{csv_columns, lua_value};
+///
+/// let v = lua_value(b"{{name = 'alice', hp = 10}, {hp = 7, name = 'bob'}}", 16).unwrap();
+/// assert_eq!(vec!["name", "hp"], csv_columns(&v).unwrap());
+///
+/// let v = lua_value(b"{{name = 'alice', hp = 10}, {name = 'bob'}}", 16).unwrap();
+/// assert!(csv_columns(&v).is_err());
+/// ```
+pub fn csv_columns(value: &LuaValue) -> Result<Vec<String>, CsvExportError> {
+    let rows = rows(value)?;
+    let Some(first) = rows.first() else {
+        return Ok(Vec::new());
+    };
+
+    let mut columns: Vec<String> = Vec::with_capacity(first.len());
+    for (k, _) in first {
+        if !columns.iter().any(|c| c == k) {
+            columns.push(k.to_string());
+        }
+    }
+
+    for (i, row) in rows.iter().enumerate() {
+        let row_num = i + 1;
+        if let Some((k, _)) = row.iter().find(|(k, _)| !columns.iter().any(|c| c == k)) {
+            return Err(CsvExportError::UnexpectedColumn {
+                row: row_num,
+                column: k.to_string(),
+            });
+        }
+
+        if let Some(c) = columns.iter().find(|c| !row.iter().any(|(k, _)| k == *c)) {
+            return Err(CsvExportError::MissingColumn {
+                row: row_num,
+                column: c.clone(),
+            });
+        }
+    }
+
+    Ok(columns)
+}
+
+/// Exports a table of records, like `{{a = 1, b = 2}, {a = 3, b = 4}}`, to CSV.
+///
+/// Each entry of `value` is written as a row, in order. See [`csv_columns()`] for the
+/// requirements on `value`, and [`CsvOptions`] for how columns are selected.
+///
+/// Values are written as:
+///
+/// * `nil`: an empty string
+/// * booleans: `true` or `false`
+/// * numbers: decimal notation, using Rust's formatting for floats (`inf`, `NaN`)
+/// * strings: as-is, without any [encoding conversion][LuaValue::as_str]
+///
+/// If a key is assigned more than once in a row, the last value is used, like Lua.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{lua_value, to_csv, CsvOptions};
+///
+/// let v = lua_value(b"{{name = 'alice', hp = 10, x = 1}, {name = 'bob', hp = 7.5}}", 16).unwrap();
+/// let opts = CsvOptions::new().columns(["hp", "name"]);
+///
+/// let mut out = Vec::new();
+/// to_csv(&v, &mut out, &opts).unwrap();
+/// assert_eq!(b"hp,name\n10,alice\n7.5,bob\n", out.as_slice());
+/// ```
+pub fn to_csv<W: Write>(
+    value: &LuaValue,
+    writer: W,
+    opts: &CsvOptions,
+) -> Result<(), CsvExportError> {
+    let columns = match &opts.columns {
+        Some(columns) => columns.clone(),
+        None => csv_columns(value)?,
+    };
+
+    let mut writer = ::csv::Writer::from_writer(writer);
+    if opts.header && !columns.is_empty() {
+        writer.write_record(&columns)?;
+    }
+
+    let mut record: Vec<Cow<[u8]>> = Vec::with_capacity(columns.len());
+    for (i, row) in rows(value)?.iter().enumerate() {
+        record.clear();
+        for column in &columns {
+            let cell = match row.iter().rfind(|(k, _)| k == column) {
+                None | Some((_, LuaValue::Nil)) => Cow::Borrowed(b"".as_slice()),
+                Some((_, LuaValue::Boolean(b))) => Cow::Owned(b.to_string().into_bytes()),
+                Some((_, LuaValue::Number(n))) => Cow::Owned(n.to_string().into_bytes()),
+                Some((_, LuaValue::String(s))) => Cow::Borrowed(s.as_ref()),
+                Some((_, LuaValue::Table(_))) => {
+                    return Err(CsvExportError::NestedTable {
+                        row: i + 1,
+                        column: column.clone(),
+                    })
+                }
+            };
+            record.push(cell);
+        }
+        writer.write_record(&record)?;
+    }
+
+    writer.flush().map_err(::csv::Error::from)?;
+    Ok(())
+}
+
+/// Splits a table of records into rows.
+fn rows<'v, 'a>(value: &'v LuaValue<'a>) -> Result<Vec<Row<'v, 'a>>, CsvExportError> {
+    let LuaValue::Table(entries) = value else {
+        return Err(CsvExportError::NotATable);
+    };
+
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let row = i + 1;
+            let fields = match entry {
+                LuaTableEntry::KeyValue(b) => &b.1,
+                LuaTableEntry::NameValue(b) => &b.1,
+                LuaTableEntry::Value(v) => &**v,
+                _ => return Err(CsvExportError::RowNotATable { row }),
+            };
+            let LuaValue::Table(fields) = fields else {
+                return Err(CsvExportError::RowNotATable { row });
+            };
+
+            fields
+                .iter()
+                .map(|field| match field {
+                    LuaTableEntry::NameValue(b) => Ok((Cow::Borrowed(b.0.as_ref()), &b.1)),
+                    LuaTableEntry::KeyValue(b) => match &b.0 {
+                        LuaValue::String(k) => Ok((String::from_utf8_lossy(k), &b.1)),
+                        _ => Err(CsvExportError::InvalidKey { row }),
+                    },
+                    _ => Err(CsvExportError::InvalidKey { row }),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lua_value;
+
+    fn export(lua: &[u8], opts: &CsvOptions) -> Result<String, CsvExportError> {
+        let v = lua_value(lua, 16).unwrap();
+        let mut out = Vec::new();
+        to_csv(&v, &mut out, opts)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn detected_columns() {
+        let opts = CsvOptions::default();
+        assert_eq!(
+            "a,b c\n1,\"x,y\"\ntrue,\n,2.5\n",
+            export(
+                br#"{
+                    {a = 1, ["b c"] = "x,y"},
+                    {["b c"] = nil, a = true},
+                    second = {a = nil, ["b c"] = 2.5},
+                }"#,
+                &opts
+            )
+            .unwrap()
+        );
+
+        // Empty table
+        assert_eq!("", export(b"{}", &opts).unwrap());
+
+        // Last duplicate wins
+        assert_eq!("a\n2\n", export(b"{{a = 1, a = 2}}", &opts).unwrap());
+
+        let opts = CsvOptions::new().header(false);
+        assert_eq!("1\n2\n", export(b"{{a = 1}, {a = 2}}", &opts).unwrap());
+    }
+
+    #[test]
+    fn selected_columns() {
+        let opts = CsvOptions::new().columns(["c", "a"]);
+        assert_eq!(
+            "c,a\n,1\n3,\n",
+            export(b"{{a = 1, b = {}}, {b = 2, c = 3}}", &opts).unwrap()
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let opts = CsvOptions::default();
+        assert!(matches!(
+            export(b"1", &opts),
+            Err(CsvExportError::NotATable)
+        ));
+        assert!(matches!(
+            export(b"{{a = 1}, 2}", &opts),
+            Err(CsvExportError::RowNotATable { row: 2 })
+        ));
+        assert!(matches!(
+            export(b"{{a = 1}, {a = 1, 2}}", &opts),
+            Err(CsvExportError::InvalidKey { row: 2 })
+        ));
+        assert!(matches!(
+            export(b"{{a = 1}, {a = 1, b = 2}}", &opts),
+            Err(CsvExportError::UnexpectedColumn { row: 2, column }) if column == "b"
+        ));
+        assert!(matches!(
+            export(b"{{a = 1, b = 2}, {b = 2}}", &opts),
+            Err(CsvExportError::MissingColumn { row: 2, column }) if column == "a"
+        ));
+        assert!(matches!(
+            export(b"{{a = 1}, {a = {}}}", &opts),
+            Err(CsvExportError::NestedTable { row: 2, column }) if column == "a"
+        ));
+    }
+}
@@ -1,9 +1,13 @@
 //! Deserializes a [`LuaValue`] using Serde.
 
 use crate::{
-    lua_value, return_statement, script,
+    error::PathKey,
+    peg_parser::lua,
+    progress::{CancellationToken, Hooks},
+    script::{script_return_with_hooks, script_with_hooks},
+    transform::Transforms,
     value::{from_utf8_cow, to_utf8_cow},
-    Error, LuaNumber, LuaTableEntry, LuaValue,
+    Error, LuaNumber, LuaTableEntry, LuaValue, ParseOptions,
 };
 use serde::{
     de::{
@@ -12,19 +16,88 @@ use serde::{
     },
     forward_to_deserialize_any, Deserialize, Deserializer,
 };
-use std::{borrow::Cow, collections::BTreeMap, vec};
+use std::{borrow::Cow, collections::BTreeMap, ops::Deref, vec};
+
+/// Options used by [`from_slice()`], and when using [`LuaValue`] as a `Deserializer` directly.
+static DEFAULT_OPTIONS: DeserializeOptions = DeserializeOptions::new();
+
+/// Internal state which is carried through nested deserialisers.
+#[derive(Clone, Copy)]
+struct DeContext<'o> {
+    opts: &'o DeserializeOptions,
+    /// Number of tables which have been entered.
+    depth: u16,
+}
+
+impl<'o> DeContext<'o> {
+    #[inline]
+    fn new(opts: &'o DeserializeOptions) -> Self {
+        Self { opts, depth: 0 }
+    }
+
+    /// Returns [`Error::Cancelled`] if [`DeserializeOptions::cancellation`] has been cancelled.
+    #[inline]
+    fn check_cancelled(&self) -> Result<(), Error> {
+        if self
+            .opts
+            .cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::check)
+        {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Enters a nested table, checking [`DeserializeOptions::recursion_limit`] and
+    /// [`DeserializeOptions::cancellation`].
+    fn enter_table(self) -> Result<Self, Error> {
+        self.check_cancelled()?;
+        let depth = self.depth.saturating_add(1);
+        if let Some(limit) = self.opts.recursion_limit.filter(|&limit| depth > limit) {
+            return Err(Error::RecursionLimit { limit });
+        }
+
+        Ok(Self { depth, ..self })
+    }
+
+    /// Normalises a table key, if [`DeserializeOptions::integral_float_keys`] is set.
+    #[inline]
+    fn key<'a>(&self, key: LuaValue<'a>) -> LuaValue<'a> {
+        match key {
+            LuaValue::Number(n) if self.opts.integral_float_keys => {
+                LuaValue::Number(n.normalise_key())
+            }
+            key => key,
+        }
+    }
+}
+
+impl Deref for DeContext<'_> {
+    type Target = DeserializeOptions;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.opts
+    }
+}
 
 fn utf8_str<E: serde::de::Error>(v: Cow<'_, [u8]>) -> Result<Cow<'_, str>, E> {
     from_utf8_cow(v)
         .map_err(|(_, b)| serde::de::Error::invalid_value(Unexpected::Bytes(&b), &"UTF8 string"))
 }
 
-fn visit_array<'de, V>(array: Vec<LuaTableEntry<'de>>, visitor: V) -> Result<V::Value, Error>
+fn visit_array<'de, V>(
+    array: Vec<LuaTableEntry<'de>>,
+    opts: DeContext<'_>,
+    visitor: V,
+) -> Result<V::Value, Error>
 where
     V: Visitor<'de>,
 {
     let len = array.len();
-    let mut deserializer = SeqDeserializer::new(array)?;
+    let mut deserializer = SeqDeserializer::new(array, opts.enter_table()?)?;
     let seq = visitor.visit_seq(&mut deserializer)?;
     let remaining = deserializer.len();
     if remaining == 0 {
@@ -43,15 +116,47 @@ macro_rules! deserialize_value_number {
         where
             V: Visitor<'de>,
         {
-            match self {
+            match self.value {
                 LuaValue::Number(n) => n.$method(visitor),
-                _ => Err(self.invalid_type(&visitor)),
+                _ => Err(self.value.invalid_type(&visitor)),
             }
         }
     };
 }
 
-impl<'de> serde::Deserializer<'de> for LuaValue<'de> {
+/// Like `deserialize_value_number!`, but reinterprets negative integers as [`u64`] with
+/// [`DeserializeOptions::unsigned_integers`].
+macro_rules! deserialize_value_unsigned {
+    ($method:ident) => {
+        fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+        where
+            V: Visitor<'de>,
+        {
+            match self.value {
+                LuaValue::Number(LuaNumber::Integer(n)) if n < 0 && self.opts.unsigned_integers => {
+                    visitor.visit_u64(n as u64)
+                }
+                LuaValue::Number(n) => n.$method(visitor),
+                _ => Err(self.value.invalid_type(&visitor)),
+            }
+        }
+    };
+}
+
+/// Internal wrapper for [`LuaValue`] which carries [`DeserializeOptions`] through nested values.
+struct ValueDeserializer<'de, 'o> {
+    value: LuaValue<'de>,
+    opts: DeContext<'o>,
+}
+
+impl<'de, 'o> ValueDeserializer<'de, 'o> {
+    #[inline]
+    fn new(value: LuaValue<'de>, opts: DeContext<'o>) -> Self {
+        Self { value, opts }
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for ValueDeserializer<'de, '_> {
     type Error = Error;
 
     #[inline]
@@ -59,7 +164,7 @@ impl<'de> serde::Deserializer<'de> for LuaValue<'de> {
     where
         V: Visitor<'de>,
     {
-        match self {
+        match self.value {
             LuaValue::Nil => visitor.visit_none(),
             LuaValue::Boolean(v) => visitor.visit_bool(v),
             LuaValue::Number(v) => v.deserialize_any(visitor),
@@ -67,7 +172,7 @@ impl<'de> serde::Deserializer<'de> for LuaValue<'de> {
                 Cow::Borrowed(b) => visitor.visit_borrowed_bytes(b),
                 Cow::Owned(b) => visitor.visit_byte_buf(b),
             },
-            LuaValue::Table(v) => LuaTableWrapper(v).deserialize_any(visitor),
+            LuaValue::Table(v) => LuaTableWrapper::new(v, self.opts)?.deserialize_any(visitor),
         }
     }
 
@@ -79,8 +184,8 @@ impl<'de> serde::Deserializer<'de> for LuaValue<'de> {
     deserialize_value_number!(deserialize_u8);
     deserialize_value_number!(deserialize_u16);
     deserialize_value_number!(deserialize_u32);
-    deserialize_value_number!(deserialize_u64);
-    deserialize_value_number!(deserialize_u128);
+    deserialize_value_unsigned!(deserialize_u64);
+    deserialize_value_unsigned!(deserialize_u128);
     deserialize_value_number!(deserialize_f32);
     deserialize_value_number!(deserialize_f64);
 
@@ -89,7 +194,7 @@ impl<'de> serde::Deserializer<'de> for LuaValue<'de> {
     where
         V: Visitor<'de>,
     {
-        match self {
+        match self.value {
             LuaValue::Nil => visitor.visit_none(),
             _ => visitor.visit_some(self),
         }
@@ -105,13 +210,14 @@ impl<'de> serde::Deserializer<'de> for LuaValue<'de> {
     where
         V: Visitor<'de>,
     {
-        match self {
+        match self.value {
             LuaValue::Table(value) => {
-                LuaTableWrapper(value).deserialize_enum(name, variants, visitor)
+                LuaTableWrapper::new(value, self.opts)?.deserialize_enum(name, variants, visitor)
             }
             LuaValue::String(variant) => visitor.visit_enum(EnumDeserializer {
                 variant,
                 value: None,
+                opts: self.opts,
             }),
             other => Err(serde::de::Error::invalid_type(
                 other.unexpected(),
@@ -137,9 +243,20 @@ impl<'de> serde::Deserializer<'de> for LuaValue<'de> {
     where
         V: Visitor<'de>,
     {
-        match self {
+        match self.value {
             LuaValue::Boolean(v) => visitor.visit_bool(v),
-            _ => Err(self.invalid_type(&visitor)),
+            LuaValue::Number(LuaNumber::Integer(v @ (0 | 1))) if self.opts.lenient_bool => {
+                visitor.visit_bool(v == 1)
+            }
+            LuaValue::String(v) if self.opts.lenient_bool_strings => match v.as_ref() {
+                b"true" => visitor.visit_bool(true),
+                b"false" => visitor.visit_bool(false),
+                _ => Err(serde::de::Error::invalid_value(
+                    Unexpected::Bytes(&v),
+                    &"\"true\" or \"false\"",
+                )),
+            },
+            _ => Err(self.value.invalid_type(&visitor)),
         }
     }
 
@@ -161,13 +278,13 @@ impl<'de> serde::Deserializer<'de> for LuaValue<'de> {
     where
         V: Visitor<'de>,
     {
-        match self {
+        match self.value {
             // #[cfg(any(feature = "std", feature = "alloc"))]
             LuaValue::String(v) => match utf8_str::<Error>(v)? {
                 Cow::Borrowed(v) => visitor.visit_borrowed_str(v),
                 Cow::Owned(v) => visitor.visit_string(v),
             },
-            _ => Err(self.invalid_type(&visitor)),
+            _ => Err(self.value.invalid_type(&visitor)),
         }
     }
 
@@ -182,11 +299,11 @@ impl<'de> serde::Deserializer<'de> for LuaValue<'de> {
     where
         V: Visitor<'de>,
     {
-        match self {
+        match self.value {
             // #[cfg(any(feature = "std", feature = "alloc"))]
             LuaValue::String(v) => visitor.visit_bytes(&v),
-            LuaValue::Table(v) => visit_array(v, visitor),
-            _ => Err(self.invalid_type(&visitor)),
+            LuaValue::Table(v) => visit_array(v, self.opts, visitor),
+            _ => Err(self.value.invalid_type(&visitor)),
         }
     }
 
@@ -194,9 +311,9 @@ impl<'de> serde::Deserializer<'de> for LuaValue<'de> {
     where
         V: Visitor<'de>,
     {
-        match self {
+        match self.value {
             LuaValue::Table(t) if t.is_empty() => visitor.visit_unit(),
-            _ => Err(self.invalid_type(&visitor)),
+            _ => Err(self.value.invalid_type(&visitor)),
         }
     }
 
@@ -204,9 +321,9 @@ impl<'de> serde::Deserializer<'de> for LuaValue<'de> {
     where
         V: Visitor<'de>,
     {
-        match self {
+        match self.value {
             LuaValue::Table(t) if t.is_empty() => visitor.visit_unit(),
-            _ => Err(self.invalid_type(&visitor)),
+            _ => Err(self.value.invalid_type(&visitor)),
         }
     }
 
@@ -214,9 +331,9 @@ impl<'de> serde::Deserializer<'de> for LuaValue<'de> {
     where
         V: Visitor<'de>,
     {
-        match self {
-            LuaValue::Table(v) => visit_array(v, visitor),
-            _ => Err(self.invalid_type(&visitor)),
+        match self.value {
+            LuaValue::Table(v) => visit_array(v, self.opts, visitor),
+            _ => Err(self.value.invalid_type(&visitor)),
         }
     }
 
@@ -243,9 +360,217 @@ impl<'de> serde::Deserializer<'de> for LuaValue<'de> {
     where
         V: Visitor<'de>,
     {
-        match self {
-            LuaValue::Table(v) => LuaTableWrapper(v).deserialize_map(visitor),
-            _ => Err(self.invalid_type(&visitor)),
+        match self.value {
+            LuaValue::Table(v) => LuaTableWrapper::new(v, self.opts)?.deserialize_map(visitor),
+            _ => Err(self.value.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            LuaValue::Table(v) => {
+                LuaTableWrapper::new(v, self.opts)?.deserialize_struct(name, fields, visitor)
+            }
+            _ => Err(self.value.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        drop(self);
+        visitor.visit_unit()
+    }
+}
+
+/// Fast path for deserialising a borrowed [`LuaValue`] without copying it first.
+///
+/// Sequences without explicit keys, maps and structs are walked by reference, which is how most
+/// big tables are deserialised. Everything else falls back to a [`ValueDeserializer`] with a copy
+/// of the value, so it behaves exactly the same.
+struct BorrowedDeserializer<'de, 'o> {
+    value: &'de LuaValue<'de>,
+    opts: DeContext<'o>,
+}
+
+impl<'de, 'o> BorrowedDeserializer<'de, 'o> {
+    #[inline]
+    fn new(value: &'de LuaValue<'de>, opts: DeContext<'o>) -> Self {
+        Self { value, opts }
+    }
+
+    /// Copies the value, for everything without a fast path.
+    ///
+    /// This is cheap for everything except tables, because strings are borrowed.
+    #[inline]
+    fn copied(self) -> ValueDeserializer<'de, 'o> {
+        ValueDeserializer::new(self.value.as_borrowed(), self.opts)
+    }
+}
+
+/// Deserialises the value of a table `entry` with `seed`, borrowing it if possible.
+fn deserialize_entry_value<'de, S>(
+    seed: S,
+    entry: &'de LuaTableEntry<'de>,
+    opts: DeContext<'_>,
+) -> Result<S::Value, Error>
+where
+    S: DeserializeSeed<'de>,
+{
+    match entry.value_cow() {
+        Cow::Borrowed(value) => seed.deserialize(BorrowedDeserializer::new(value, opts)),
+        Cow::Owned(value) => seed.deserialize(ValueDeserializer::new(value, opts)),
+    }
+}
+
+macro_rules! forward_to_copied {
+    ($($method:ident)*) => {$(
+        #[inline]
+        fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.copied().$method(visitor)
+        }
+    )*};
+}
+
+impl<'de> serde::Deserializer<'de> for BorrowedDeserializer<'de, '_> {
+    type Error = Error;
+
+    forward_to_copied! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32
+        deserialize_i64 deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32
+        deserialize_u64 deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char
+        deserialize_str deserialize_string deserialize_bytes deserialize_byte_buf
+        deserialize_unit deserialize_identifier
+    }
+
+    #[inline]
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            LuaValue::Nil => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.copied().deserialize_unit_struct(name, visitor)
+    }
+
+    #[inline]
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let LuaValue::Table(entries) = self.value else {
+            return self.copied().deserialize_seq(visitor);
+        };
+
+        // Tables with explicit keys need to be renumbered first.
+        if !matches!(
+            SeqDeserializer::is_seq(entries),
+            SeqType::OnlyValues | SeqType::OnlyNumberValues | SeqType::Empty
+        ) {
+            return self.copied().deserialize_seq(visitor);
+        }
+
+        let mut deserializer = BorrowedSeqDeserializer {
+            iter: entries.iter(),
+            next_index: 1,
+            opts: self.opts.enter_table()?,
+        };
+        let seq = visitor.visit_seq(&mut deserializer)?;
+        if deserializer.iter.len() == 0 {
+            Ok(seq)
+        } else {
+            Err(serde::de::Error::invalid_length(
+                entries.len(),
+                &"fewer elements in array",
+            ))
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let LuaValue::Table(entries) = self.value else {
+            return self.copied().deserialize_map(visitor);
+        };
+
+        // Keys need to be copied to normalise them.
+        if self.opts.integral_float_keys {
+            return self.copied().deserialize_map(visitor);
+        }
+
+        let mut deserializer = BorrowedMapDeserializer {
+            iter: entries.iter(),
+            value: None,
+            path_key: PathKey::Integer(0),
+            next_numeric_index: 1,
+            opts: self.opts.enter_table()?,
+        };
+        let map = visitor.visit_map(&mut deserializer)?;
+        if deserializer.iter.len() == 0 {
+            Ok(map)
+        } else {
+            Err(serde::de::Error::invalid_length(
+                entries.len(),
+                &"fewer elements in map",
+            ))
         }
     }
 
@@ -256,45 +581,342 @@ impl<'de> serde::Deserializer<'de> for LuaValue<'de> {
         visitor: V,
     ) -> Result<V::Value, Error>
     where
-        V: Visitor<'de>,
+        V: Visitor<'de>,
+    {
+        // Keys need to be copied to rename them.
+        if self.opts.fuzzy_fields() {
+            return self.copied().deserialize_struct(name, fields, visitor);
+        }
+
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.copied().deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+}
+
+/// Like [`SeqDeserializer`], for a table with only implicit keys, borrowing its values.
+struct BorrowedSeqDeserializer<'de, 'o> {
+    iter: std::slice::Iter<'de, LuaTableEntry<'de>>,
+    /// Lua index of the next element, for error paths.
+    next_index: i64,
+    opts: DeContext<'o>,
+}
+
+impl<'de> SeqAccess<'de> for BorrowedSeqDeserializer<'de, '_> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.opts.check_cancelled()?;
+        let Some(entry) = self.iter.next() else {
+            return Ok(None);
+        };
+
+        let index = self.next_index;
+        self.next_index += 1;
+        deserialize_entry_value(seed, entry, self.opts)
+            .map(Some)
+            .map_err(|e| e.prepend_path(PathKey::Integer(index)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// Like [`MapDeserializer`], borrowing the table's keys and values.
+struct BorrowedMapDeserializer<'de, 'o> {
+    iter: std::slice::Iter<'de, LuaTableEntry<'de>>,
+    /// Entry whose key was deserialised last.
+    value: Option<&'de LuaTableEntry<'de>>,
+    /// Key of `value`, for error paths.
+    path_key: PathKey<'de>,
+    next_numeric_index: i64,
+    opts: DeContext<'o>,
+}
+
+impl<'de, 'o> BorrowedMapDeserializer<'de, 'o> {
+    /// Takes the next entry of the table, and deserialises its key with `seed`.
+    fn next_key<S>(&mut self, seed: S) -> Result<Option<(S::Value, &'de LuaTableEntry<'de>)>, Error>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        self.opts.check_cancelled()?;
+        let Some(entry) = self.iter.next() else {
+            return Ok(None);
+        };
+
+        let key = match entry {
+            LuaTableEntry::KeyValue(b) => {
+                self.path_key = PathKey::from(&b.0);
+                MapKey::KeyValue(b.0.as_borrowed())
+            }
+            LuaTableEntry::NameValue(b) => {
+                self.path_key = PathKey::Name(Cow::Borrowed(&b.0));
+                MapKey::NameValue(Cow::Borrowed(&b.0))
+            }
+            _ => {
+                let i = self.next_numeric_index;
+                self.next_numeric_index += 1;
+                self.path_key = PathKey::Integer(i);
+                MapKey::Value(i)
+            }
+        };
+
+        let key = seed.deserialize(MapKeyDeserializer {
+            key,
+            opts: self.opts,
+        })?;
+        Ok(Some((key, entry)))
+    }
+
+    /// Deserialises the value of `entry` with `seed`.
+    fn value<S>(&self, seed: S, entry: &'de LuaTableEntry<'de>) -> Result<S::Value, Error>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        deserialize_entry_value(seed, entry, self.opts).map_err(|e| e.prepend_path(&self.path_key))
+    }
+}
+
+impl<'de> MapAccess<'de> for BorrowedMapDeserializer<'de, '_> {
+    type Error = Error;
+
+    fn next_key_seed<S>(&mut self, seed: S) -> Result<Option<S::Value>, Error>
+    where
+        S: DeserializeSeed<'de>,
     {
-        match self {
-            LuaValue::Table(v) => LuaTableWrapper(v).deserialize_struct(name, fields, visitor),
-            _ => Err(self.invalid_type(&visitor)),
-        }
+        let Some((key, entry)) = self.next_key(seed)? else {
+            return Ok(None);
+        };
+
+        self.value = Some(entry);
+        Ok(Some(key))
     }
 
-    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Error>
+    fn next_value_seed<S>(&mut self, seed: S) -> Result<S::Value, Error>
     where
-        V: Visitor<'de>,
+        S: DeserializeSeed<'de>,
     {
-        self.deserialize_string(visitor)
+        match self.value.take() {
+            Some(entry) => self.value(seed, entry),
+            None => Err(serde::de::Error::custom("value is missing")),
+        }
     }
 
-    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    fn next_entry_seed<K, V>(
+        &mut self,
+        kseed: K,
+        vseed: V,
+    ) -> Result<Option<(K::Value, V::Value)>, Error>
     where
-        V: Visitor<'de>,
+        K: DeserializeSeed<'de>,
+        V: DeserializeSeed<'de>,
     {
-        drop(self);
-        visitor.visit_unit()
+        let Some((key, entry)) = self.next_key(kseed)? else {
+            return Ok(None);
+        };
+
+        Ok(Some((key, self.value(vseed, entry)?)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
     }
 }
 
-struct EnumDeserializer<'a> {
+macro_rules! forward_to_value_deserializer {
+    ($deserializer:ident; $($method:ident)*) => {$(
+        #[inline]
+        fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+        where
+            V: Visitor<'de>,
+        {
+            $deserializer::new(self, DeContext::new(&DEFAULT_OPTIONS)).$method(visitor)
+        }
+    )*};
+}
+
+/// Implements [`serde::Deserializer`] for a type by wrapping it in `$deserializer`, with the default
+/// [`DeserializeOptions`].
+macro_rules! impl_value_deserializer {
+    ($(#[$attr:meta])* $ty:ty, $deserializer:ident) => {
+        $(#[$attr])*
+        impl<'de> serde::Deserializer<'de> for $ty {
+            type Error = Error;
+
+            forward_to_value_deserializer! {
+                $deserializer;
+                deserialize_any deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32
+                deserialize_i64 deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32
+                deserialize_u64 deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char
+                deserialize_str deserialize_string deserialize_bytes deserialize_byte_buf
+                deserialize_option deserialize_unit deserialize_seq deserialize_map
+                deserialize_identifier deserialize_ignored_any
+            }
+
+            #[inline]
+            fn deserialize_unit_struct<V>(
+                self,
+                name: &'static str,
+                visitor: V,
+            ) -> Result<V::Value, Error>
+            where
+                V: Visitor<'de>,
+            {
+                $deserializer::new(self, DeContext::new(&DEFAULT_OPTIONS))
+                    .deserialize_unit_struct(name, visitor)
+            }
+
+            #[inline]
+            fn deserialize_newtype_struct<V>(
+                self,
+                name: &'static str,
+                visitor: V,
+            ) -> Result<V::Value, Error>
+            where
+                V: Visitor<'de>,
+            {
+                $deserializer::new(self, DeContext::new(&DEFAULT_OPTIONS))
+                    .deserialize_newtype_struct(name, visitor)
+            }
+
+            #[inline]
+            fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Error>
+            where
+                V: Visitor<'de>,
+            {
+                $deserializer::new(self, DeContext::new(&DEFAULT_OPTIONS))
+                    .deserialize_tuple(len, visitor)
+            }
+
+            #[inline]
+            fn deserialize_tuple_struct<V>(
+                self,
+                name: &'static str,
+                len: usize,
+                visitor: V,
+            ) -> Result<V::Value, Error>
+            where
+                V: Visitor<'de>,
+            {
+                $deserializer::new(self, DeContext::new(&DEFAULT_OPTIONS))
+                    .deserialize_tuple_struct(name, len, visitor)
+            }
+
+            #[inline]
+            fn deserialize_struct<V>(
+                self,
+                name: &'static str,
+                fields: &'static [&'static str],
+                visitor: V,
+            ) -> Result<V::Value, Error>
+            where
+                V: Visitor<'de>,
+            {
+                $deserializer::new(self, DeContext::new(&DEFAULT_OPTIONS))
+                    .deserialize_struct(name, fields, visitor)
+            }
+
+            #[inline]
+            fn deserialize_enum<V>(
+                self,
+                name: &'static str,
+                variants: &'static [&'static str],
+                visitor: V,
+            ) -> Result<V::Value, Error>
+            where
+                V: Visitor<'de>,
+            {
+                $deserializer::new(self, DeContext::new(&DEFAULT_OPTIONS))
+                    .deserialize_enum(name, variants, visitor)
+            }
+        }
+    };
+}
+
+impl_value_deserializer! {
+    /// Deserialise from a [`LuaValue`] with the default [`DeserializeOptions`].
+    LuaValue<'de>, ValueDeserializer
+}
+
+impl_value_deserializer! {
+    /// Deserialise from a borrowed [`LuaValue`] with the default [`DeserializeOptions`].
+    ///
+    /// This doesn't consume the [`LuaValue`], so it can be deserialised many times, into different
+    /// types, without parsing it again. Strings, sequences, maps and structs are borrowed from the
+    /// [`LuaValue`] rather than copied.
+    ///
+    /// ```rust
+    /// use serde::Deserialize;
+    /// use serde_luaq::lua_value;
+    /// use std::collections::BTreeMap;
+    ///
+    /// #[derive(Deserialize, Debug, PartialEq)]
+    /// struct Window<'a> {
+    ///     title: &'a str,
+    /// }
+    ///
+    /// let v = lua_value(b"{window = {title = 'hello'}, volume = {music = 5, sfx = 7}}", 16)
+    ///     .unwrap();
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Ui<'a> {
+    ///     #[serde(borrow)]
+    ///     window: Window<'a>,
+    /// }
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Audio {
+    ///     volume: BTreeMap<String, i64>,
+    /// }
+    ///
+    /// let ui = Ui::deserialize(&v).unwrap();
+    /// let audio = Audio::deserialize(&v).unwrap();
+    /// assert_eq!(Window { title: "hello" }, ui.window);
+    /// assert_eq!(Some(&7), audio.volume.get("sfx"));
+    /// ```
+    &'de LuaValue<'_>, BorrowedDeserializer
+}
+
+struct EnumDeserializer<'a, 'o> {
     variant: Cow<'a, [u8]>,
     value: Option<LuaValue<'a>>,
+    opts: DeContext<'o>,
 }
 
-impl<'de> EnumAccess<'de> for EnumDeserializer<'de> {
+impl<'de, 'o> EnumAccess<'de> for EnumDeserializer<'de, 'o> {
     type Error = Error;
-    type Variant = VariantDeserializer<'de>;
+    type Variant = VariantDeserializer<'de, 'o>;
 
     fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Error>
     where
         V: DeserializeSeed<'de>,
     {
         let variant = self.variant.into_deserializer();
-        let visitor = VariantDeserializer { value: self.value };
+        let visitor = VariantDeserializer {
+            value: self.value,
+            opts: self.opts,
+        };
         seed.deserialize(variant).map(|v| (v, visitor))
     }
 }
@@ -307,24 +929,25 @@ impl<'de> IntoDeserializer<'de, Error> for LuaValue<'de> {
     }
 }
 
-// impl<'de> IntoDeserializer<'de, Error> for &'de LuaValue<'de> {
-//     type Deserializer = Self;
+impl<'de> IntoDeserializer<'de, Error> for &'de LuaValue<'_> {
+    type Deserializer = Self;
 
-//     fn into_deserializer(self) -> Self::Deserializer {
-//         self
-//     }
-// }
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
 
-struct VariantDeserializer<'a> {
+struct VariantDeserializer<'a, 'o> {
     value: Option<LuaValue<'a>>,
+    opts: DeContext<'o>,
 }
 
-impl<'de> VariantAccess<'de> for VariantDeserializer<'de> {
+impl<'de> VariantAccess<'de> for VariantDeserializer<'de, '_> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<(), Error> {
         match self.value {
-            Some(value) => Deserialize::deserialize(value),
+            Some(value) => Deserialize::deserialize(ValueDeserializer::new(value, self.opts)),
             None => Ok(()),
         }
     }
@@ -334,7 +957,7 @@ impl<'de> VariantAccess<'de> for VariantDeserializer<'de> {
         T: DeserializeSeed<'de>,
     {
         match self.value {
-            Some(value) => seed.deserialize(value),
+            Some(value) => seed.deserialize(ValueDeserializer::new(value, self.opts)),
             None => Err(serde::de::Error::invalid_type(
                 Unexpected::UnitVariant,
                 &"newtype variant",
@@ -351,7 +974,7 @@ impl<'de> VariantAccess<'de> for VariantDeserializer<'de> {
                 if v.is_empty() {
                     visitor.visit_unit()
                 } else {
-                    visit_array(v, visitor)
+                    visit_array(v, self.opts, visitor)
                 }
             }
             Some(other) => Err(serde::de::Error::invalid_type(
@@ -367,14 +990,18 @@ impl<'de> VariantAccess<'de> for VariantDeserializer<'de> {
 
     fn struct_variant<V>(
         self,
-        _fields: &'static [&'static str],
+        fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Error>
     where
         V: Visitor<'de>,
     {
         match self.value {
-            Some(LuaValue::Table(v)) => LuaTableWrapper(v).deserialize_any(visitor),
+            Some(LuaValue::Table(v)) => {
+                let mut table = LuaTableWrapper::new(v, self.opts)?;
+                table.match_field_names(fields);
+                table.deserialize_any(visitor)
+            }
             Some(other) => Err(serde::de::Error::invalid_type(
                 other.unexpected(),
                 &"struct variant",
@@ -415,7 +1042,7 @@ impl LuaValue<'_> {
     }
 }
 
-impl MapKeyDeserializer<'_> {
+impl MapKey<'_> {
     #[cold]
     fn unexpected_key(&self) -> Unexpected<'_> {
         match self {
@@ -426,12 +1053,68 @@ impl MapKeyDeserializer<'_> {
     }
 }
 
-enum SeqDeserializer<'a> {
+struct SeqDeserializer<'a, 'o> {
+    iter: SeqIter<'a>,
+    /// Lua index of the next element, for error paths.
+    next_index: i64,
+    opts: DeContext<'o>,
+}
+
+enum SeqIter<'a> {
     LuaValue(vec::IntoIter<LuaValue<'a>>),
     LuaNumber(vec::IntoIter<LuaNumber>),
+    /// Entries which all have implicit keys, which are already in sequence order.
+    Entries(vec::IntoIter<LuaTableEntry<'a>>),
+    /// Entries of a table which isn't a sequence, as `(key, value)` pairs.
+    Pairs {
+        iter: vec::IntoIter<LuaTableEntry<'a>>,
+        next_numeric_index: i64,
+    },
     Empty,
 }
 
+impl<'a> SeqIter<'a> {
+    /// Pads the sequence with `nil` to `len` values, checking that it has no more than `len`, and
+    /// that it needs no more than `max_padding` `nil`s.
+    fn resize(self, len: usize, max_padding: Option<usize>) -> Result<Self, Error> {
+        let mut vec: Vec<LuaValue<'a>> = match self {
+            Self::LuaValue(i) => i.collect(),
+            Self::LuaNumber(i) => i.map(LuaValue::Number).collect(),
+            Self::Entries(i) => i.map(LuaTableEntry::move_value).collect(),
+            Self::Pairs { .. } => {
+                return Err(serde::de::Error::invalid_type(
+                    Unexpected::Map,
+                    &"table with only positive integer or implicit keys",
+                ))
+            }
+            Self::Empty => Vec::new(),
+        };
+
+        if vec.len() > len {
+            return Err(serde::de::Error::invalid_length(
+                vec.len(),
+                &"no more elements than `n`",
+            ));
+        }
+
+        check_padding(len - vec.len(), max_padding)?;
+        vec.resize(len, LuaValue::Nil);
+        Ok(Self::LuaValue(vec.into_iter()))
+    }
+}
+
+/// Checks that filling a sequence with `padding` `nil`s is within
+/// [`DeserializeOptions::max_sequence_padding`], before allocating anything.
+fn check_padding(padding: usize, max_padding: Option<usize>) -> Result<(), Error> {
+    if max_padding.is_some_and(|limit| padding > limit) {
+        return Err(serde::de::Error::invalid_length(
+            padding,
+            &"no more `nil` padding than `max_sequence_padding`",
+        ));
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
 enum SeqType {
     Map,
@@ -441,7 +1124,7 @@ enum SeqType {
     Empty,
 }
 
-impl<'a> SeqDeserializer<'a> {
+impl<'a, 'o> SeqDeserializer<'a, 'o> {
     /// Find what sort of sequence a table is.
     fn is_seq(vec: &[LuaTableEntry<'a>]) -> SeqType {
         if vec.is_empty() {
@@ -459,14 +1142,16 @@ impl<'a> SeqDeserializer<'a> {
                 if !matches!(
                     entry,
                     LuaTableEntry::KeyValue(b)
-                    if matches!(b.0, LuaValue::Number(LuaNumber::Integer(_)))
+                    if matches!(b.0, LuaValue::Number(LuaNumber::Integer(k)) if k >= 1)
                 ) {
+                    // Non-integer keys, or integer keys which can't be a sequence index (zero
+                    // or negative).
                     return SeqType::Map;
                 }
 
-                // At least one entry with an explicit integer key.
+                // At least one entry with an explicit positive integer key. Keep going to check the
+                // remaining keys.
                 has_keys = true;
-                break;
             }
         }
 
@@ -480,34 +1165,84 @@ impl<'a> SeqDeserializer<'a> {
     }
 
     /// Create a new sequence deserializer.
-    fn new(vec: Vec<LuaTableEntry<'a>>) -> Result<Self, Error> {
+    fn new(mut vec: Vec<LuaTableEntry<'a>>, opts: DeContext<'o>) -> Result<Self, Error> {
+        if opts.integral_float_keys {
+            for e in vec.iter_mut() {
+                if let LuaTableEntry::KeyValue(b) = e {
+                    if let LuaValue::Number(n) = &mut b.0 {
+                        *n = n.normalise_key();
+                    }
+                }
+            }
+        }
+
+        let n = if opts.sequence_n_field {
+            Self::take_n(&mut vec)?
+        } else {
+            None
+        };
+
+        let mut iter = Self::renumber(vec, opts.max_sequence_padding)?;
+        if let Some(n) = n {
+            iter = iter
+                .resize(n, opts.max_sequence_padding)
+                .map_err(|e| e.prepend_path(PathKey::Name("n".into())))?;
+        }
+
+        Ok(Self {
+            iter,
+            next_index: 1,
+            opts,
+        })
+    }
+
+    /// Removes a [`table.pack()`-style `n` field][DeserializeOptions::sequence_n_field] from a
+    /// table, and returns its value.
+    fn take_n(vec: &mut Vec<LuaTableEntry<'a>>) -> Result<Option<usize>, Error> {
+        let Some(i) = vec.iter().position(|e| e.key_bytes() == Some(b"n")) else {
+            return Ok(None);
+        };
+
+        let n = match vec.remove(i).move_value() {
+            LuaValue::Number(LuaNumber::Integer(n)) => usize::try_from(n).map_err(|_| {
+                serde::de::Error::invalid_value(Unexpected::Signed(n), &"non-negative length")
+            }),
+            v => Err(v.invalid_type(&"integer length")),
+        };
+
+        n.map(Some)
+            .map_err(|e: Error| e.prepend_path(PathKey::Name("n".into())))
+    }
+
+    /// Converts a table into a 1-indexed sequence of values without gaps.
+    fn renumber(
+        vec: Vec<LuaTableEntry<'a>>,
+        max_padding: Option<usize>,
+    ) -> Result<SeqIter<'a>, Error> {
         // Check to see if we need to re-number things
         match Self::is_seq(&vec) {
             SeqType::Map => {
-                return Err(serde::de::Error::invalid_type(
-                    Unexpected::Map,
-                    &"table with only integer or implicit keys",
-                ))
+                return Ok(SeqIter::Pairs {
+                    iter: vec.into_iter(),
+                    next_numeric_index: 1,
+                })
             }
             SeqType::OnlyNumberValues => {
                 let vec: Vec<LuaNumber> = vec
                     .into_iter()
                     .filter_map(|e| e.move_number_value())
                     .collect();
-                return Ok(SeqDeserializer::LuaNumber(vec.into_iter()));
-            }
-            SeqType::OnlyValues => {
-                let vec: Vec<LuaValue<'a>> = vec.into_iter().map(|e| e.move_value()).collect();
-                return Ok(SeqDeserializer::LuaValue(vec.into_iter()));
+                return Ok(SeqIter::LuaNumber(vec.into_iter()));
             }
-            SeqType::Empty => return Ok(SeqDeserializer::Empty),
+            // Already in order, so values can be taken from the entries as they're needed.
+            SeqType::OnlyValues => return Ok(SeqIter::Entries(vec.into_iter())),
+            SeqType::Empty => return Ok(SeqIter::Empty),
             SeqType::HasExplicitNumericKeys => (),
         }
 
         // Scan over the entire Vec, and overwrite entries.
         let mut h = BTreeMap::new();
         let mut i = 1;
-        let mut highest_key = 0;
         for entry in vec {
             match entry {
                 // This would be much cleaner with box_patterns:
@@ -519,34 +1254,35 @@ impl<'a> SeqDeserializer<'a> {
                         unreachable!();
                     };
                     h.insert(key, value);
-                    highest_key = highest_key.max(key);
                 }
                 LuaTableEntry::Value(value) => {
                     h.insert(i, *value);
                     i += 1;
-                    highest_key = highest_key.max(i);
                 }
                 LuaTableEntry::NumberValue(value) => {
                     h.insert(i, LuaValue::Number(value));
                     i += 1;
-                    highest_key = highest_key.max(i);
                 }
                 LuaTableEntry::BooleanValue(value) => {
                     h.insert(i, LuaValue::Boolean(value));
                     i += 1;
-                    highest_key = highest_key.max(i);
                 }
                 LuaTableEntry::NilValue => {
                     h.insert(i, LuaValue::Nil);
                     i += 1;
-                    highest_key = highest_key.max(i);
                 }
                 _ => unreachable!(),
             }
         }
 
+        // Keys are all positive, so the gaps are the highest key less the number of entries.
+        let len = h
+            .last_key_value()
+            .map_or(0, |(&k, _)| usize::try_from(k).unwrap_or(usize::MAX));
+        check_padding(len.saturating_sub(h.len()), max_padding)?;
+
         // Convert to a Vec with no gaps, with keys starting at 1.
-        let mut vec = Vec::with_capacity((highest_key + 1) as usize);
+        let mut vec = Vec::with_capacity(len);
         let mut next_key = 1;
         for (k, v) in h {
             if k > next_key {
@@ -560,81 +1296,333 @@ impl<'a> SeqDeserializer<'a> {
             next_key = k + 1;
         }
 
-        Ok(SeqDeserializer::LuaValue(vec.into_iter()))
+        Ok(SeqIter::LuaValue(vec.into_iter()))
     }
 
     fn len(&self) -> usize {
-        match self {
-            Self::LuaNumber(i) => i.len(),
-            Self::LuaValue(i) => i.len(),
-            Self::Empty => 0,
+        match &self.iter {
+            SeqIter::LuaNumber(i) => i.len(),
+            SeqIter::LuaValue(i) => i.len(),
+            SeqIter::Entries(i) => i.len(),
+            SeqIter::Pairs { iter, .. } => iter.len(),
+            SeqIter::Empty => 0,
         }
     }
 }
 
-impl<'de> SeqAccess<'de> for SeqDeserializer<'de> {
+impl<'de> SeqAccess<'de> for SeqDeserializer<'de, '_> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
     where
         T: DeserializeSeed<'de>,
     {
-        match self {
-            Self::LuaNumber(i) => match i.next() {
-                Some(value) => seed.deserialize(value).map(Some),
-                None => Ok(None),
-            },
+        self.opts.check_cancelled()?;
+        let value = match &mut self.iter {
+            SeqIter::LuaNumber(i) => i.next().map(LuaValue::Number),
+            SeqIter::LuaValue(i) => i.next(),
+            SeqIter::Entries(i) => i.next().map(LuaTableEntry::move_value),
+            SeqIter::Pairs {
+                iter,
+                next_numeric_index,
+            } => {
+                let Some(entry) = iter.next() else {
+                    return Ok(None);
+                };
 
-            Self::LuaValue(i) => match i.next() {
-                Some(value) => seed.deserialize(value).map(Some),
-                None => Ok(None),
-            },
+                let (key, path_key, value) = match entry {
+                    LuaTableEntry::KeyValue(b) => {
+                        let (key, value) = *b;
+                        let key = self.opts.key(key);
+                        let path_key = PathKey::from(&key);
+                        (MapKey::KeyValue(key), path_key, value)
+                    }
+                    LuaTableEntry::NameValue(b) => {
+                        let (key, value) = *b;
+                        let path_key = PathKey::Name(key.clone());
+                        (MapKey::NameValue(key), path_key, value)
+                    }
+                    entry => {
+                        let i = *next_numeric_index;
+                        *next_numeric_index += 1;
+                        (MapKey::Value(i), PathKey::Integer(i), entry.move_value())
+                    }
+                };
+
+                return seed
+                    .deserialize(PairDeserializer {
+                        key: Some(key),
+                        value: Some(value),
+                        opts: self.opts,
+                    })
+                    .map(Some)
+                    .map_err(|e| e.prepend_path(path_key));
+            }
+            SeqIter::Empty => None,
+        };
 
-            Self::Empty => Ok(None),
+        match value {
+            Some(value) => {
+                let index = self.next_index;
+                self.next_index += 1;
+                seed.deserialize(ValueDeserializer::new(value, self.opts))
+                    .map(Some)
+                    .map_err(|e| e.prepend_path(PathKey::Integer(index)))
+            }
+            None => Ok(None),
         }
     }
 
     fn size_hint(&self) -> Option<usize> {
-        match self {
-            Self::LuaNumber(i) => match i.size_hint() {
+        match &self.iter {
+            SeqIter::LuaNumber(i) => match i.size_hint() {
+                (lower, Some(upper)) if lower == upper => Some(upper),
+                _ => None,
+            },
+
+            SeqIter::LuaValue(i) => match i.size_hint() {
+                (lower, Some(upper)) if lower == upper => Some(upper),
+                _ => None,
+            },
+
+            SeqIter::Entries(i) => match i.size_hint() {
                 (lower, Some(upper)) if lower == upper => Some(upper),
                 _ => None,
             },
 
-            Self::LuaValue(i) => match i.size_hint() {
+            SeqIter::Pairs { iter, .. } => match iter.size_hint() {
                 (lower, Some(upper)) if lower == upper => Some(upper),
                 _ => None,
             },
 
-            Self::Empty => Some(0),
+            SeqIter::Empty => Some(0),
+        }
+    }
+}
+
+/// A `(key, value)` pair from a table which isn't a sequence, as an element of a
+/// [`SeqDeserializer`].
+///
+/// This deserialises as a sequence of two elements, like a tuple.
+struct PairDeserializer<'de, 'o> {
+    key: Option<MapKey<'de>>,
+    value: Option<LuaValue<'de>>,
+    opts: DeContext<'o>,
+}
+
+macro_rules! deserialize_pair_invalid_type {
+    ($($method:ident)*) => {$(
+        fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+        where
+            V: Visitor<'de>,
+        {
+            Err(serde::de::Error::invalid_type(
+                Unexpected::Other("(key, value) pair of a table which isn't a sequence"),
+                &visitor,
+            ))
+        }
+    )*};
+}
+
+impl<'de> serde::Deserializer<'de> for PairDeserializer<'de, '_> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_seq<V>(mut self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let pair = visitor.visit_seq(&mut self)?;
+        if self.value.is_none() {
+            Ok(pair)
+        } else {
+            Err(serde::de::Error::invalid_length(
+                2,
+                &"fewer elements in (key, value) pair",
+            ))
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    #[inline]
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    #[inline]
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    deserialize_pair_invalid_type! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char deserialize_str
+        deserialize_string deserialize_bytes deserialize_byte_buf deserialize_unit
+        deserialize_map deserialize_identifier
+    }
+
+    forward_to_deserialize_any! {
+        unit_struct enum
+    }
+}
+
+impl<'de> SeqAccess<'de> for PairDeserializer<'de, '_> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if let Some(key) = self.key.take() {
+            return seed
+                .deserialize(MapKeyDeserializer {
+                    key,
+                    opts: self.opts,
+                })
+                .map(Some);
+        }
+
+        match self.value.take() {
+            Some(value) => seed
+                .deserialize(ValueDeserializer::new(value, self.opts))
+                .map(Some),
+            None => Ok(None),
         }
     }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(usize::from(self.key.is_some()) + usize::from(self.value.is_some()))
+    }
 }
 
-struct MapDeserializer<'a, T>
+struct MapDeserializer<'a, 'o, T>
 where
     T: Iterator<Item = LuaTableEntry<'a>>,
 {
     // iter: <Vec<LuaTableEntry<'a>> as IntoIterator>::IntoIter,
     iter: T,
     value: Option<LuaValue<'a>>,
+    /// Key of `value`, for error paths.
+    path_key: PathKey<'a>,
     next_numeric_index: i64,
+    opts: DeContext<'o>,
 }
 
-impl<'a, T> MapDeserializer<'a, T>
+impl<'a, 'o, T> MapDeserializer<'a, 'o, T>
 where
     T: Iterator<Item = LuaTableEntry<'a>>,
 {
-    fn new(iter: T) -> Self {
+    fn new(iter: T, opts: DeContext<'o>) -> Self {
         MapDeserializer {
             iter,
             value: None,
+            path_key: PathKey::Integer(0),
             next_numeric_index: 1,
+            opts,
+        }
+    }
+
+    #[inline]
+    fn key(&self, key: MapKey<'a>) -> MapKeyDeserializer<'a, 'o> {
+        MapKeyDeserializer {
+            key,
+            opts: self.opts,
         }
     }
+
+    /// Takes the next entry of the table, splitting it into its key and value.
+    fn next_entry(&mut self) -> Result<Option<(MapKey<'a>, LuaValue<'a>)>, Error> {
+        self.opts.check_cancelled()?;
+
+        let entry = match self.iter.next() {
+            Some(LuaTableEntry::KeyValue(b)) => {
+                let (key, value) = *b;
+                let key = self.opts.key(key);
+                self.path_key = PathKey::from(&key);
+                (MapKey::KeyValue(key), value)
+            }
+            Some(LuaTableEntry::NameValue(b)) => {
+                let (key, value) = *b;
+                self.path_key = PathKey::Name(key.clone());
+                (MapKey::NameValue(key), value)
+            }
+            Some(entry) => {
+                let i = self.next_numeric_index;
+                self.next_numeric_index += 1;
+                self.path_key = PathKey::Integer(i);
+                (MapKey::Value(i), entry.move_value())
+            }
+            None => return Ok(None),
+        };
+
+        Ok(Some(entry))
+    }
+
+    /// Deserialises the `value` of the current entry.
+    fn value<S>(&self, seed: S, value: LuaValue<'a>) -> Result<S::Value, Error>
+    where
+        S: DeserializeSeed<'a>,
+    {
+        seed.deserialize(ValueDeserializer::new(value, self.opts))
+            .map_err(|e| e.prepend_path(&self.path_key))
+    }
 }
 
-impl<'de, T> MapAccess<'de> for MapDeserializer<'de, T>
+impl<'de, T> MapAccess<'de> for MapDeserializer<'de, '_, T>
 where
     T: Iterator<Item = LuaTableEntry<'de>>,
 {
@@ -644,48 +1632,12 @@ where
     where
         S: DeserializeSeed<'de>,
     {
-        // Copy the entry without a value and pass to MapKeyDeserializer
-        match self.iter.next() {
-            Some(LuaTableEntry::KeyValue(b)) => {
-                let (key, value) = *b;
-                self.value = Some(value);
-
-                let key_de = MapKeyDeserializer::KeyValue(key);
-                seed.deserialize(key_de).map(Some)
-            }
-            Some(LuaTableEntry::NameValue(b)) => {
-                let (key, value) = *b;
-                self.value = Some(value);
-                let key_de = MapKeyDeserializer::NameValue(key);
-                seed.deserialize(key_de).map(Some)
-            }
-            Some(LuaTableEntry::Value(value)) => {
-                self.value = Some(*value);
-                let key_de = MapKeyDeserializer::Value(self.next_numeric_index);
-                self.next_numeric_index += 1;
-                seed.deserialize(key_de).map(Some)
-            }
-            Some(LuaTableEntry::NumberValue(value)) => {
-                self.value = Some(LuaValue::Number(value));
-                let key_de = MapKeyDeserializer::Value(self.next_numeric_index);
-                self.next_numeric_index += 1;
-                seed.deserialize(key_de).map(Some)
-            }
-            Some(LuaTableEntry::BooleanValue(value)) => {
-                self.value = Some(LuaValue::Boolean(value));
-                let key_de = MapKeyDeserializer::Value(self.next_numeric_index);
-                self.next_numeric_index += 1;
-                seed.deserialize(key_de).map(Some)
-            }
-            Some(LuaTableEntry::NilValue) => {
-                self.value = Some(LuaValue::Nil);
-                let key_de = MapKeyDeserializer::Value(self.next_numeric_index);
-                self.next_numeric_index += 1;
-                seed.deserialize(key_de).map(Some)
-            }
+        let Some((key, value)) = self.next_entry()? else {
+            return Ok(None);
+        };
 
-            None => Ok(None),
-        }
+        self.value = Some(value);
+        seed.deserialize(self.key(key)).map(Some)
     }
 
     fn next_value_seed<S>(&mut self, seed: S) -> Result<S::Value, Error>
@@ -693,11 +1645,30 @@ where
         S: DeserializeSeed<'de>,
     {
         match self.value.take() {
-            Some(value) => seed.deserialize(value),
+            Some(value) => self.value(seed, value),
             None => Err(serde::de::Error::custom("value is missing")),
         }
     }
 
+    fn next_entry_seed<K, V>(
+        &mut self,
+        kseed: K,
+        vseed: V,
+    ) -> Result<Option<(K::Value, V::Value)>, Error>
+    where
+        K: DeserializeSeed<'de>,
+        V: DeserializeSeed<'de>,
+    {
+        // Most maps are deserialised this way, so skip holding on to the value between calls.
+        let Some((key, value)) = self.next_entry()? else {
+            return Ok(None);
+        };
+
+        let key = kseed.deserialize(self.key(key))?;
+        let value = self.value(vseed, value)?;
+        Ok(Some((key, value)))
+    }
+
     fn size_hint(&self) -> Option<usize> {
         match self.iter.size_hint() {
             (lower, Some(upper)) if lower == upper => Some(upper),
@@ -706,12 +1677,18 @@ where
     }
 }
 
-enum MapKeyDeserializer<'de> {
+/// Key of a table entry, as seen by a [`MapDeserializer`].
+enum MapKey<'de> {
     KeyValue(LuaValue<'de>),
     NameValue(Cow<'de, str>),
     Value(i64),
 }
 
+struct MapKeyDeserializer<'de, 'o> {
+    key: MapKey<'de>,
+    opts: DeContext<'o>,
+}
+
 macro_rules! deserialize_numeric_key {
     ($method:ident) => {
         deserialize_numeric_key!($method, deserialize_number);
@@ -722,9 +1699,12 @@ macro_rules! deserialize_numeric_key {
         where
             V: Visitor<'de>,
         {
-            match self {
-                Self::KeyValue(LuaValue::Number(key)) => key.$method(visitor),
-                Self::Value(key) => visitor.visit_i64(key),
+            match self.key {
+                // Goes through `ValueDeserializer` for `DeserializeOptions::unsigned_integers`.
+                MapKey::KeyValue(key @ LuaValue::Number(_)) => {
+                    ValueDeserializer::new(key, self.opts).$method(visitor)
+                }
+                MapKey::Value(key) => visitor.visit_i64(key),
                 key => Err(serde::de::Error::invalid_type(
                     key.unexpected_key(),
                     &visitor,
@@ -734,20 +1714,22 @@ macro_rules! deserialize_numeric_key {
     };
 }
 
-impl<'de> serde::Deserializer<'de> for MapKeyDeserializer<'de> {
+impl<'de> serde::Deserializer<'de> for MapKeyDeserializer<'de, '_> {
     type Error = Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
     where
         V: Visitor<'de>,
     {
-        match self {
-            Self::KeyValue(key) => key.deserialize_any(visitor),
-            Self::NameValue(key) => match key {
+        match self.key {
+            MapKey::KeyValue(key) => {
+                ValueDeserializer::new(key, self.opts).deserialize_any(visitor)
+            }
+            MapKey::NameValue(key) => match key {
                 Cow::Borrowed(key) => visitor.visit_borrowed_str(key),
                 Cow::Owned(key) => visitor.visit_string(key),
             },
-            Self::Value(key) => visitor.visit_i64(key),
+            MapKey::Value(key) => visitor.visit_i64(key),
         }
     }
 
@@ -771,8 +1753,10 @@ impl<'de> serde::Deserializer<'de> for MapKeyDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        match self {
-            Self::KeyValue(LuaValue::Boolean(key)) => visitor.visit_bool(key),
+        match self.key {
+            MapKey::KeyValue(key) => {
+                ValueDeserializer::new(key, self.opts).deserialize_bool(visitor)
+            }
             key => Err(serde::de::Error::invalid_type(
                 key.unexpected_key(),
                 &visitor,
@@ -780,6 +1764,36 @@ impl<'de> serde::Deserializer<'de> for MapKeyDeserializer<'de> {
         }
     }
 
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        // String keys are the most common, so visit them directly rather than going through
+        // deserialize_any.
+        match self.key {
+            MapKey::NameValue(Cow::Borrowed(key)) => visitor.visit_borrowed_str(key),
+            MapKey::NameValue(Cow::Owned(key)) => visitor.visit_string(key),
+            MapKey::KeyValue(key @ LuaValue::String(_)) => {
+                ValueDeserializer::new(key, self.opts).deserialize_string(visitor)
+            }
+            key => Self { key, ..self }.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
     #[inline]
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
     where
@@ -810,39 +1824,84 @@ impl<'de> serde::Deserializer<'de> for MapKeyDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        match self {
-            Self::KeyValue(key) => key
-                .into_deserializer()
-                .deserialize_enum(name, variants, visitor),
-            Self::NameValue(variant) => visitor.visit_enum(EnumDeserializer {
+        match self.key {
+            MapKey::KeyValue(key) => {
+                ValueDeserializer::new(key, self.opts).deserialize_enum(name, variants, visitor)
+            }
+            MapKey::NameValue(variant) => visitor.visit_enum(EnumDeserializer {
                 variant: to_utf8_cow(variant),
                 value: None,
+                opts: self.opts,
             }),
-            Self::Value(key) => visitor.visit_enum(EnumDeserializer {
+            MapKey::Value(key) => visitor.visit_enum(EnumDeserializer {
                 variant: key.to_string().into_bytes().into(),
                 value: None,
+                opts: self.opts,
             }),
         }
     }
 
     forward_to_deserialize_any! {
-        char str string bytes byte_buf unit unit_struct seq tuple tuple_struct
-        map struct identifier ignored_any
+        char bytes byte_buf unit unit_struct seq tuple tuple_struct map struct ignored_any
     }
 }
 
 /// Internal wrapper for [`Vec<LuaTableEntry>`] that we can implement
 /// [`serde::Deserializer`] on.
-struct LuaTableWrapper<'a>(Vec<LuaTableEntry<'a>>);
+struct LuaTableWrapper<'a, 'o> {
+    entries: Vec<LuaTableEntry<'a>>,
+    opts: DeContext<'o>,
+}
+
+impl<'a, 'o> LuaTableWrapper<'a, 'o> {
+    #[inline]
+    fn new(entries: Vec<LuaTableEntry<'a>>, opts: DeContext<'o>) -> Result<Self, Error> {
+        Ok(Self {
+            entries,
+            opts: opts.enter_table()?,
+        })
+    }
+
+    /// Renames string keys which match one of a struct's `fields` ignoring ASCII case, with
+    /// [`DeserializeOptions::case_insensitive_fields`], or after Unicode normalisation, with
+    /// `DeserializeOptions::unicode_fields`.
+    fn match_field_names(&mut self, fields: &'static [&'static str]) {
+        if !self.opts.fuzzy_fields() {
+            return;
+        }
+
+        for entry in &mut self.entries {
+            let Some(key) = entry.key_bytes() else {
+                continue;
+            };
+
+            if fields.iter().any(|f| f.as_bytes() == key) {
+                continue;
+            }
+
+            let Some(field) = fields.iter().find(|f| self.opts.field_matches(f, key)) else {
+                continue;
+            };
+
+            match entry {
+                LuaTableEntry::NameValue(b) => b.0 = Cow::Borrowed(field),
+                LuaTableEntry::KeyValue(b) => {
+                    b.0 = LuaValue::String(Cow::Borrowed(field.as_bytes()))
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+}
 
-impl<'de> serde::Deserializer<'de> for LuaTableWrapper<'de> {
+impl<'de> serde::Deserializer<'de> for LuaTableWrapper<'de, '_> {
     type Error = Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        if matches!(SeqDeserializer::is_seq(&self.0), SeqType::Map) {
+        if matches!(SeqDeserializer::is_seq(&self.entries), SeqType::Map) {
             self.deserialize_map(visitor)
         } else {
             self.deserialize_seq(visitor)
@@ -850,14 +1909,15 @@ impl<'de> serde::Deserializer<'de> for LuaTableWrapper<'de> {
     }
 
     fn deserialize_struct<V>(
-        self,
+        mut self,
         _name: &'static str,
-        _fields: &'static [&'static str],
+        fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
+        self.match_field_names(fields);
         self.deserialize_map(visitor)
     }
 
@@ -865,8 +1925,8 @@ impl<'de> serde::Deserializer<'de> for LuaTableWrapper<'de> {
     where
         V: Visitor<'de>,
     {
-        let len = self.0.len();
-        let mut deserializer = MapDeserializer::new(self.0.into_iter());
+        let len = self.entries.len();
+        let mut deserializer = MapDeserializer::new(self.entries.into_iter(), self.opts);
         let map = visitor.visit_map(&mut deserializer)?;
         let remaining = deserializer.iter.len();
         if remaining == 0 {
@@ -883,8 +1943,8 @@ impl<'de> serde::Deserializer<'de> for LuaTableWrapper<'de> {
     where
         V: Visitor<'de>,
     {
-        let len = self.0.len();
-        let mut deserializer = SeqDeserializer::new(self.0)?;
+        let len = self.entries.len();
+        let mut deserializer = SeqDeserializer::new(self.entries, self.opts)?;
         let map = visitor.visit_seq(&mut deserializer)?;
         let remaining = deserializer.len();
         if remaining == 0 {
@@ -906,14 +1966,14 @@ impl<'de> serde::Deserializer<'de> for LuaTableWrapper<'de> {
     where
         V: Visitor<'de>,
     {
-        if self.0.len() != 1 {
+        if self.entries.len() != 1 {
             return Err(serde::de::Error::invalid_value(
                 Unexpected::Map,
                 &"table with a single entry",
             ));
         }
 
-        let (variant, value) = match self.0.remove(0) {
+        let (variant, value) = match self.entries.remove(0) {
             LuaTableEntry::KeyValue(b) if matches!(&b.0, LuaValue::String(_)) => {
                 let (k, v) = *b;
                 let LuaValue::String(k) = k else {
@@ -933,6 +1993,7 @@ impl<'de> serde::Deserializer<'de> for LuaTableWrapper<'de> {
         visitor.visit_enum(EnumDeserializer {
             variant,
             value: Some(value),
+            opts: self.opts,
         })
     }
 
@@ -952,7 +2013,30 @@ impl<'de> serde::Deserializer<'de> for LuaTableWrapper<'de> {
 }
 
 /// The format of the input Lua buffer.
+///
+/// ## Empty input
+///
+/// Empty input, or input containing only whitespace, is:
+///
+/// * an error for [`Value`][Self::Value] and [`Return`][Self::Return], unless
+///   [`empty_as_nil`][DeserializeOptions::empty_as_nil] is set, where it is `nil`
+/// * a script with no statements for [`Script`][Self::Script], which deserialises like an empty
+///   table
+///
+/// ```rust
+/// use serde_luaq::{from_slice, from_slice_with_options, DeserializeOptions, LuaFormat};
+/// use std::collections::BTreeMap;
+///
+/// assert!(from_slice::<Option<i64>>(b"", LuaFormat::Value, 16).is_err());
+/// assert!(from_slice::<Option<i64>>(b"", LuaFormat::Return, 16).is_err());
+/// assert!(from_slice::<BTreeMap<String, i64>>(b" ", LuaFormat::Script, 16).unwrap().is_empty());
+///
+/// let opts = DeserializeOptions::new().empty_as_nil(true);
+/// let v: Option<i64> = from_slice_with_options(b"\n", LuaFormat::Value, 16, &opts).unwrap();
+/// assert_eq!(None, v);
+/// ```
 #[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
 pub enum LuaFormat {
     /// A bare Lua expression:
     ///
@@ -974,7 +2058,370 @@ pub enum LuaFormat {
     /// ```lua
     /// return {hello = "world"}
     /// ```
+    ///
+    /// A statement with more than one value (`return 1, 2`) is deserialised like a sequence, so
+    /// it can be read as a tuple. This is the same as returning a sequence table
+    /// (`return {1, 2}`); use [`return_values()`][crate::return_values] to tell them apart.
     Return,
+
+    /// A Lua script containing variable assignments, followed by an optional `return`
+    /// statement:
+    ///
+    /// ```lua
+    /// seen = {"a", "b"}
+    /// return seen
+    /// ```
+    ///
+    /// This deserialises the returned value, or if there is no `return` statement, all variables
+    /// like [`Script`][Self::Script]. To get both, use
+    /// [`script_return_with_options()`][crate::script_return_with_options].
+    ScriptReturn,
+}
+
+/// [Serde deserialisation][from_slice_with_options] options.
+///
+/// The defaults follow Lua's type system strictly.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct DeserializeOptions {
+    /// By default, [`bool`] fields only accept Lua `true` and `false`.
+    ///
+    /// When this option is set to `true`, [`bool`] fields will also accept the integers `0`
+    /// (`false`) and `1` (`true`), which some software uses to serialise booleans. Other integers
+    /// and floats (even `0.0` and `1.0`) are still rejected.
+    ///
+    /// This does not apply to fields which Serde deserialises as an "any" type (such as
+    /// [flattened fields][crate#flattening]).
+    pub lenient_bool: bool,
+
+    /// By default, [`bool`] fields only accept Lua `true` and `false`.
+    ///
+    /// When this option is set to `true`, [`bool`] fields will also accept the _strings_ `"true"`
+    /// and `"false"` (case-sensitive). Any other string is an error.
+    ///
+    /// This does not apply to fields which Serde deserialises as an "any" type (such as
+    /// [flattened fields][crate#flattening]).
+    pub lenient_bool_strings: bool,
+
+    /// Maximum number of nested tables to deserialise, or [`None`] for no limit (default).
+    ///
+    /// The [`peg` parser's `max_depth`][crate::lua_value] limits how deeply nested a parsed
+    /// [`LuaValue`] can be, but Serde's recursion through deeply-nested values (especially
+    /// combined with recursive types) uses a lot more stack for each level, and can still
+    /// overflow the stack.
+    ///
+    /// When set, deserialising a table nested deeper than this limit returns an error. The root
+    /// table is at depth `1`.
+    ///
+    /// This is similar to `serde_json`'s recursion limit, which defaults to `128`.
+    pub recursion_limit: Option<u16>,
+
+    /// By default, a table with a field named `n` can't be deserialised as a sequence.
+    ///
+    /// When this option is set to `true`, sequences (like [`Vec`]) accept a table with an `n`
+    /// field, following the convention of Lua's `table.pack()`: `n` is the length of the sequence,
+    /// and missing values up to `n` are filled with `nil`:
+    ///
+    /// ```rust
+    /// use serde_luaq::{from_slice_with_options, DeserializeOptions, LuaFormat};
+    ///
+    /// let opts = DeserializeOptions::new().sequence_n_field(true);
+    /// let a: Vec<Option<&str>> =
+    ///     from_slice_with_options(br#"{n = 4, "a", nil, "c"}"#, LuaFormat::Value, 16, &opts)
+    ///         .unwrap();
+    /// assert_eq!(vec![Some("a"), None, Some("c"), None], a);
+    /// ```
+    ///
+    /// `n` must be a non-negative integer. Having values past `n` is an error.
+    ///
+    /// This does not apply to fields which Serde deserialises as an "any" type (such as
+    /// [flattened fields][crate#flattening]).
+    pub sequence_n_field: bool,
+
+    /// Maximum number of `nil` values to add when deserialising a table as a sequence, or
+    /// [`None`] for no limit (default).
+    ///
+    /// Sequences are padded with `nil` for gaps between integer keys (`{[1] = 1, [1000] = 2}`),
+    /// and up to the length given by an [`n` field][Self::sequence_n_field]. Without a limit, a
+    /// small input like `{[100000000000] = 1}` or `{n = 100000000000}` could make the deserialiser
+    /// try to allocate hundreds of gigabytes, so set this when deserialising untrusted input.
+    ///
+    /// When set, a table which needs more padding than this returns an error, before allocating
+    /// anything.
+    pub max_sequence_padding: Option<usize>,
+
+    /// By default, table keys which are floats are always treated as floats, so `{[2.0] = "b"}`
+    /// can't be deserialised as a sequence, or as a map with integer keys.
+    ///
+    /// When this option is set to `true`, float keys with an exact integer value are converted to
+    /// integers, like Lua does (so `t[2.0]` and `t[2]` are the same entry). This is needed for
+    /// files written by Lua 5.2 and earlier, which only have floats:
+    ///
+    /// ```rust
+    /// use serde_luaq::{from_slice_with_options, DeserializeOptions, LuaFormat};
+    ///
+    /// let opts = DeserializeOptions::new().integral_float_keys(true);
+    /// let a: Vec<Option<&str>> =
+    ///     from_slice_with_options(br#"{[1.0] = "a", [3.0] = "c"}"#, LuaFormat::Value, 16, &opts)
+    ///         .unwrap();
+    /// assert_eq!(vec![Some("a"), None, Some("c")], a);
+    /// ```
+    ///
+    /// This applies to sequences and maps, and does not apply to fields which Serde deserialises as
+    /// an "any" type (such as [flattened fields][crate#flattening]). See also
+    /// [`LuaNumber::normalise_key()`][crate::LuaNumber::normalise_key].
+    pub integral_float_keys: bool,
+
+    /// Token to cancel parsing and deserialisation, either from another thread or after a
+    /// deadline.
+    ///
+    /// When the token is cancelled, deserialisation returns [`Error::Cancelled`]. The token is
+    /// checked while parsing (by [`from_slice_with_options()`]), and between table entries while
+    /// deserialising.
+    ///
+    /// Defaults to [`None`].
+    pub cancellation: Option<CancellationToken>,
+
+    /// By default, [`u64`] and [`u128`] fields only accept integers from `0` to [`i64::MAX`], and
+    /// decimal integer literals greater than that are converted to floats (like Lua does).
+    ///
+    /// When this option is set to `true`:
+    ///
+    /// * decimal integer literals up to [`u64::MAX`] are [parsed as wrapping
+    ///   integers][crate::ParseOptions::unsigned_integers], keeping all 64 bits
+    /// * [`u64`] and [`u128`] fields reinterpret negative integers as [`u64`], so `-1` and
+    ///   `0xffffffffffffffff` are [`u64::MAX`]
+    ///
+    /// ```rust
+    /// use serde_luaq::{from_slice_with_options, DeserializeOptions, LuaFormat};
+    ///
+    /// let opts = DeserializeOptions::new().unsigned_integers(true);
+    /// let a: Vec<u64> = from_slice_with_options(
+    ///     b"{18446744073709551615, 0x8000000000000000, 1}",
+    ///     LuaFormat::Value,
+    ///     16,
+    ///     &opts,
+    /// ).unwrap();
+    /// assert_eq!(vec![u64::MAX, 1 << 63, 1], a);
+    /// ```
+    ///
+    /// Signed fields (like [`i64`]) see these values as negative numbers. Narrower unsigned fields
+    /// (like [`u32`]) still reject negative integers.
+    ///
+    /// To write a [`u64`] value back out so that both Lua and `serde_luaq` read the same 64 bits,
+    /// write values greater than [`i64::MAX`] as hexadecimal literals (like `0xffffffffffffffff`),
+    /// or as the equivalent negative [`i64`].
+    pub unsigned_integers: bool,
+
+    /// Parse empty or whitespace-only input as `nil` for [`LuaFormat::Value`] and
+    /// [`LuaFormat::Return`], rather than returning an error.
+    ///
+    /// See [`ParseOptions::empty_as_nil`][crate::ParseOptions::empty_as_nil] and
+    /// [`LuaFormat`'s empty input rules][LuaFormat#empty-input].
+    pub empty_as_nil: bool,
+
+    /// By default, struct fields must match table keys exactly.
+    ///
+    /// When this option is set to `true`, table keys which don't exactly match a struct field are
+    /// matched ignoring ASCII case, so `maxhealth` and `MAXHEALTH` both match a field named
+    /// `MaxHealth`. This saves adding `#[serde(alias)]` attributes for every field when a game
+    /// changes its key casing between versions:
+    ///
+    /// ```rust
+    /// use serde::Deserialize;
+    /// use serde_luaq::{from_slice_with_options, DeserializeOptions, LuaFormat};
+    ///
+    /// #[derive(Deserialize, Debug, PartialEq)]
+    /// #[serde(rename_all = "PascalCase")]
+    /// struct Player {
+    ///     max_health: i64,
+    /// }
+    ///
+    /// let opts = DeserializeOptions::new().case_insensitive_fields(true);
+    /// for input in [b"{MaxHealth = 10}".as_slice(), b"{maxhealth = 10}", b"{MAXHEALTH = 10}"] {
+    ///     let p: Player = from_slice_with_options(input, LuaFormat::Value, 16, &opts).unwrap();
+    ///     assert_eq!(Player { max_health: 10 }, p);
+    /// }
+    /// ```
+    ///
+    /// Exact matches are preferred, and only ASCII letters are case-folded. Keys which only differ
+    /// by case (like `{a = 1, A = 2}`) are [duplicate fields][Error::DuplicateField].
+    ///
+    /// This applies to structs and struct variants of enums, and does not apply to maps or fields
+    /// which Serde deserialises as an "any" type (such as [flattened fields][crate#flattening]).
+    ///
+    /// To also ignore the case of non-ASCII letters, enable `unicode_fields` (with the `unicode`
+    /// feature).
+    pub case_insensitive_fields: bool,
+
+    /// By default, struct fields must match table keys byte-for-byte.
+    ///
+    /// When this option is set to `true`, UTF-8 table keys which don't exactly match a struct
+    /// field are matched after [NFC normalisation][nfc], so a key written in decomposed form (NFD,
+    /// as some operating systems and editors do) still matches its field:
+    ///
+    /// ```rust
+    /// use serde::Deserialize;
+    /// use serde_luaq::{from_slice_with_options, DeserializeOptions, LuaFormat};
+    ///
+    /// #[derive(Deserialize, Debug, PartialEq)]
+    /// struct Greetings {
+    ///     #[serde(rename = "fran\u{e7}ais")]
+    ///     french: String,
+    /// }
+    ///
+    /// // "c" followed by U+0327 COMBINING CEDILLA
+    /// let lua = "{['franc\u{327}ais'] = 'Bonjour'}";
+    /// let opts = DeserializeOptions::new().unicode_fields(true);
+    /// let g: Greetings =
+    ///     from_slice_with_options(lua.as_bytes(), LuaFormat::Value, 16, &opts).unwrap();
+    /// assert_eq!("Bonjour", g.french);
+    /// ```
+    ///
+    /// When [`case_insensitive_fields`][Self::case_insensitive_fields] is also set, keys are
+    /// matched ignoring case using Unicode lowercase mappings, rather than only ASCII case.
+    ///
+    /// Exact matches are preferred, and keys which aren't valid UTF-8 are only matched exactly.
+    /// Keys which normalise to the same field (like `{["café"] = 1, ["cafe\u{301}"] = 2}`) are
+    /// [duplicate fields][Error::DuplicateField].
+    ///
+    /// This applies to structs and struct variants of enums, and does not apply to maps or fields
+    /// which Serde deserialises as an "any" type (such as [flattened fields][crate#flattening]).
+    ///
+    /// _Requires the `unicode` feature._
+    ///
+    /// [nfc]: https://www.unicode.org/reports/tr15/
+    #[cfg(feature = "unicode")]
+    pub unicode_fields: bool,
+
+    /// Transformations to apply to values before deserialising them, selected by field name or
+    /// path.
+    ///
+    /// See [`Transforms`] for details. Defaults to none.
+    pub transforms: Transforms,
+}
+
+impl DeserializeOptions {
+    /// Creates options with the default (strict) behaviour.
+    pub const fn new() -> Self {
+        Self {
+            lenient_bool: false,
+            lenient_bool_strings: false,
+            recursion_limit: None,
+            sequence_n_field: false,
+            max_sequence_padding: None,
+            integral_float_keys: false,
+            cancellation: None,
+            unsigned_integers: false,
+            empty_as_nil: false,
+            case_insensitive_fields: false,
+            #[cfg(feature = "unicode")]
+            unicode_fields: false,
+            transforms: Transforms::new(),
+        }
+    }
+
+    /// Sets [`lenient_bool`][Self::lenient_bool].
+    pub const fn lenient_bool(mut self, lenient_bool: bool) -> Self {
+        self.lenient_bool = lenient_bool;
+        self
+    }
+
+    /// Sets [`lenient_bool_strings`][Self::lenient_bool_strings].
+    pub const fn lenient_bool_strings(mut self, lenient_bool_strings: bool) -> Self {
+        self.lenient_bool_strings = lenient_bool_strings;
+        self
+    }
+
+    /// Sets [`recursion_limit`][Self::recursion_limit].
+    pub const fn recursion_limit(mut self, recursion_limit: Option<u16>) -> Self {
+        self.recursion_limit = recursion_limit;
+        self
+    }
+
+    /// Sets [`sequence_n_field`][Self::sequence_n_field].
+    pub const fn sequence_n_field(mut self, sequence_n_field: bool) -> Self {
+        self.sequence_n_field = sequence_n_field;
+        self
+    }
+
+    /// Sets [`max_sequence_padding`][Self::max_sequence_padding].
+    pub const fn max_sequence_padding(mut self, max_sequence_padding: Option<usize>) -> Self {
+        self.max_sequence_padding = max_sequence_padding;
+        self
+    }
+
+    /// Sets [`integral_float_keys`][Self::integral_float_keys].
+    pub const fn integral_float_keys(mut self, integral_float_keys: bool) -> Self {
+        self.integral_float_keys = integral_float_keys;
+        self
+    }
+
+    /// Sets [`unsigned_integers`][Self::unsigned_integers].
+    pub const fn unsigned_integers(mut self, unsigned_integers: bool) -> Self {
+        self.unsigned_integers = unsigned_integers;
+        self
+    }
+
+    /// Sets [`empty_as_nil`][Self::empty_as_nil].
+    pub const fn empty_as_nil(mut self, empty_as_nil: bool) -> Self {
+        self.empty_as_nil = empty_as_nil;
+        self
+    }
+
+    /// Sets [`case_insensitive_fields`][Self::case_insensitive_fields].
+    pub const fn case_insensitive_fields(mut self, case_insensitive_fields: bool) -> Self {
+        self.case_insensitive_fields = case_insensitive_fields;
+        self
+    }
+
+    /// Sets [`unicode_fields`][Self::unicode_fields].
+    #[cfg(feature = "unicode")]
+    pub const fn unicode_fields(mut self, unicode_fields: bool) -> Self {
+        self.unicode_fields = unicode_fields;
+        self
+    }
+
+    /// Returns `true` if table keys may match struct fields which aren't exactly the same.
+    const fn fuzzy_fields(&self) -> bool {
+        #[cfg(feature = "unicode")]
+        if self.unicode_fields {
+            return true;
+        }
+
+        self.case_insensitive_fields
+    }
+
+    /// Returns `true` if the table key `key` matches the struct field `field`, when it isn't
+    /// exactly the same.
+    fn field_matches(&self, field: &str, key: &[u8]) -> bool {
+        #[cfg(feature = "unicode")]
+        if self.unicode_fields {
+            return std::str::from_utf8(key).is_ok_and(|key| {
+                crate::unicode::fields_match(field, key, self.case_insensitive_fields)
+            });
+        }
+
+        self.case_insensitive_fields && field.as_bytes().eq_ignore_ascii_case(key)
+    }
+
+    /// Sets [`cancellation`][Self::cancellation].
+    pub fn cancellation(mut self, cancellation: Option<CancellationToken>) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Sets [`transforms`][Self::transforms].
+    pub fn transforms(mut self, transforms: Transforms) -> Self {
+        self.transforms = transforms;
+        self
+    }
+}
+
+impl Default for DeserializeOptions {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// Parses a byte slice containing a Lua expression in [`format`][LuaFormat].
@@ -992,13 +2439,62 @@ pub fn from_slice<'a, T>(b: &'a [u8], format: LuaFormat, max_depth: u16) -> Resu
 where
     T: de::Deserialize<'a>,
 {
+    from_slice_with_options(b, format, max_depth, &DEFAULT_OPTIONS)
+}
+
+/// Parses a byte slice containing a Lua expression in [`format`][LuaFormat], with
+/// [`DeserializeOptions`].
+///
+/// See [`from_slice()`] for more details.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use serde_luaq::{from_slice_with_options, DeserializeOptions, LuaFormat};
+///
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// struct Settings {
+///     fullscreen: bool,
+///     vsync: bool,
+/// }
+///
+/// let opts = DeserializeOptions::new().lenient_bool(true);
+///
+/// assert_eq!(
+///     Settings { fullscreen: true, vsync: false },
+///     from_slice_with_options(
+///         b"{fullscreen = 1, vsync = 0}",
+///         LuaFormat::Value,
+///         /* max table depth */ 16,
+///         &opts,
+///     ).unwrap(),
+/// );
+/// ```
+pub fn from_slice_with_options<'a, T>(
+    b: &'a [u8],
+    format: LuaFormat,
+    max_depth: u16,
+    opts: &DeserializeOptions,
+) -> Result<T, Error>
+where
+    T: de::Deserialize<'a>,
+{
+    let mut parse_opts = ParseOptions::new(max_depth);
+    parse_opts.unsigned_integers = opts.unsigned_integers;
+    parse_opts.empty_as_nil = opts.empty_as_nil;
+    let hooks = Hooks::new(opts.cancellation.as_ref());
     let v = match format {
-        LuaFormat::Value => lua_value(b, max_depth)?,
-        LuaFormat::Script => script(b, max_depth)?.into_iter().collect(),
-        LuaFormat::Return => return_statement(b, max_depth)?,
+        LuaFormat::Value => hooks.finish(lua::lua_value(b, &parse_opts, &hooks))?,
+        LuaFormat::Script => script_with_hooks(b, &parse_opts, &hooks)?
+            .into_iter()
+            .collect(),
+        LuaFormat::Return => hooks.finish(lua::return_statement(b, &parse_opts, &hooks))?,
+        LuaFormat::ScriptReturn => script_return_with_hooks(b, &parse_opts, &hooks)?.into_value(),
     };
+    let v = opts.transforms.apply(v)?;
 
-    Deserialize::deserialize(v)
+    Deserialize::deserialize(ValueDeserializer::new(v, DeContext::new(opts)))
 }
 
 /// Parses a [`str`] containing a Lua expression in [`format`][LuaFormat].
@@ -1020,3 +2516,85 @@ where
 {
     from_slice(b.as_bytes(), format, max_depth)
 }
+
+/// Parses a [`str`] containing a Lua expression in [`format`][LuaFormat], with
+/// [`DeserializeOptions`].
+///
+/// See [`from_slice_with_options()`] and [`from_str()`] for more details.
+#[inline]
+pub fn from_str_with_options<'a, T>(
+    b: &'a str,
+    format: LuaFormat,
+    max_depth: u16,
+    opts: &DeserializeOptions,
+) -> Result<T, Error>
+where
+    T: de::Deserialize<'a>,
+{
+    from_slice_with_options(b.as_bytes(), format, max_depth, opts)
+}
+
+/// Deserialises an already-parsed [`LuaValue`] into `T`, without consuming it.
+///
+/// Strings, sequences, maps and structs are borrowed from `v` rather than copied, so the same
+/// [`LuaValue`] can be deserialised many times, into different types, without parsing the Lua
+/// source again.
+///
+/// This is the same as calling `T::deserialize(&v)`.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use serde_luaq::{from_value, lua_value};
+///
+/// #[derive(Deserialize, Debug, PartialEq)]
+/// struct Version {
+///     version: u32,
+/// }
+///
+/// #[derive(Deserialize, Debug, PartialEq)]
+/// struct Player<'a> {
+///     name: &'a str,
+/// }
+///
+/// let v = lua_value(br#"{version = 2, name = "alice"}"#, 16).unwrap();
+/// let version: Version = from_value(&v).unwrap();
+/// assert_eq!(Version { version: 2 }, version);
+///
+/// let player: Player = from_value(&v).unwrap();
+/// assert_eq!(Player { name: "alice" }, player);
+/// ```
+#[inline]
+pub fn from_value<'a, T>(v: &'a LuaValue<'_>) -> Result<T, Error>
+where
+    T: de::Deserialize<'a>,
+{
+    from_value_with_options(v, &DEFAULT_OPTIONS)
+}
+
+/// Deserialises an already-parsed [`LuaValue`] into `T` with [`DeserializeOptions`], without
+/// consuming it.
+///
+/// Options which only affect parsing ([`unsigned_integers`][DeserializeOptions::unsigned_integers]
+/// and [`empty_as_nil`][DeserializeOptions::empty_as_nil]) have no effect; set them in the
+/// [`ParseOptions`][crate::ParseOptions] used to parse `v` instead.
+///
+/// See [`from_value()`] for more details.
+pub fn from_value_with_options<'a, T>(
+    v: &'a LuaValue<'_>,
+    opts: &DeserializeOptions,
+) -> Result<T, Error>
+where
+    T: de::Deserialize<'a>,
+{
+    if opts.transforms.is_empty() {
+        // Most of the time, nothing needs to change, so borrow the value rather than copying it.
+        return Deserialize::deserialize(BorrowedDeserializer::new(v, DeContext::new(opts)));
+    }
+
+    Deserialize::deserialize(ValueDeserializer::new(
+        opts.transforms.apply(v.as_borrowed())?,
+        DeContext::new(opts),
+    ))
+}
@@ -0,0 +1,301 @@
+//! Errors and warnings with source spans, for editors and linters.
+use crate::{
+    error::PathKey,
+    progress::Hooks,
+    salvage::repair,
+    script::{script_return_with_hooks, script_with_hooks},
+    table::HashKey,
+    Error, LuaFormat, LuaTableEntry, LuaValue, ParseOptions, Parser, ScriptStatement,
+};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    fmt::{Display, Formatter},
+    ops::Range,
+};
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The input is valid, but probably doesn't mean what its author intended.
+    Warning,
+
+    /// The input couldn't be parsed.
+    Error,
+}
+
+/// What a [`Diagnostic`] is about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DiagnosticKind {
+    /// The input couldn't be parsed.
+    Error(Error),
+
+    /// The input was truncated, and was repaired by discarding everything after the start of the
+    /// diagnostic's span, like [`Parser::salvage()`].
+    Truncated,
+
+    /// A table has more than one entry with the same key. Lua only keeps one of them.
+    ///
+    /// `key` is formatted like a [path component][Error::Path], like `foo` or `[1]`.
+    DuplicateKey {
+        /// The duplicated key.
+        key: String,
+    },
+
+    /// A script assigns a variable more than once. Only the last assignment is used.
+    DuplicateVariable {
+        /// Name of the variable.
+        name: String,
+    },
+
+    /// A table entry was dropped when [converting to JSON][crate::to_json_value_with_diagnostics],
+    /// because another entry in the same table had the same JSON object key.
+    #[cfg(feature = "serde_json")]
+    DuplicateJsonKey {
+        /// The JSON object key.
+        key: String,
+    },
+}
+
+impl DiagnosticKind {
+    /// Returns how serious this kind of diagnostic is.
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::Error(_) => Severity::Error,
+            Self::Truncated | Self::DuplicateKey { .. } | Self::DuplicateVariable { .. } => {
+                Severity::Warning
+            }
+            #[cfg(feature = "serde_json")]
+            Self::DuplicateJsonKey { .. } => Severity::Warning,
+        }
+    }
+}
+
+impl Display for DiagnosticKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error(e) => write!(f, "{e}"),
+            Self::Truncated => f.write_str("input is truncated; the rest of the input is ignored"),
+            Self::DuplicateKey { key } => {
+                write!(f, "duplicate table key {key}; only one entry is used")
+            }
+            Self::DuplicateVariable { name } => {
+                write!(
+                    f,
+                    "variable {name} is assigned more than once; only the last is used"
+                )
+            }
+            #[cfg(feature = "serde_json")]
+            Self::DuplicateJsonKey { key } => {
+                write!(f, "duplicate JSON object key {key:?}; an entry was dropped")
+            }
+        }
+    }
+}
+
+/// An error or warning about part of the input, returned by [`parse_with_diagnostics()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    /// What the diagnostic is about.
+    pub kind: DiagnosticKind,
+
+    /// Byte offsets of the part of the input which the diagnostic is about.
+    ///
+    /// Errors which aren't about a particular part of the input (like
+    /// [`Error::TooManyStatements`]) cover the whole input.
+    pub span: Range<usize>,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(kind: DiagnosticKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+
+    /// Returns how serious this diagnostic is.
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.kind.fmt(f)
+    }
+}
+
+/// Collects warnings while parsing.
+///
+/// Unless it was created with [`Collector::enabled()`], this does nothing.
+#[derive(Debug, Default)]
+pub(crate) struct Collector {
+    enabled: bool,
+    diagnostics: RefCell<Vec<Diagnostic>>,
+
+    /// Start positions of table entries which have already been warned about, because the grammar
+    /// may parse the same table more than once when backtracking.
+    seen: RefCell<HashSet<usize>>,
+}
+
+impl Collector {
+    pub(crate) fn enabled() -> Self {
+        Self {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    /// Returns `true` if the grammar should record table entry spans.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Called by the grammar with a table's `entries`, and their `spans` in the input.
+    pub(crate) fn table(&self, entries: &[LuaTableEntry<'_>], spans: &[Range<usize>]) {
+        let mut keys = HashSet::with_capacity(entries.len());
+        let mut index = 0;
+        for (entry, span) in entries.iter().zip(spans) {
+            let (key, path) = match entry {
+                LuaTableEntry::NameValue(kv) => (
+                    HashKey::String(kv.0.as_bytes()),
+                    PathKey::Name(kv.0.as_ref().into()),
+                ),
+                LuaTableEntry::KeyValue(kv) => match HashKey::new(&kv.0) {
+                    Some(key) => (key, PathKey::from(&kv.0)),
+                    None => continue,
+                },
+                LuaTableEntry::NilValue
+                | LuaTableEntry::BooleanValue(_)
+                | LuaTableEntry::NumberValue(_)
+                | LuaTableEntry::Value(_) => {
+                    index += 1;
+                    (HashKey::Integer(index), PathKey::Integer(index))
+                }
+            };
+
+            if !keys.insert(key) && self.seen.borrow_mut().insert(span.start) {
+                self.diagnostics.borrow_mut().push(Diagnostic::new(
+                    DiagnosticKind::DuplicateKey {
+                        key: path.to_string(),
+                    },
+                    span.clone(),
+                ));
+            }
+        }
+    }
+
+    /// Returns all warnings collected while parsing.
+    fn take(&self) -> Vec<Diagnostic> {
+        self.diagnostics.take()
+    }
+}
+
+/// Returns warnings for variables which are assigned more than once in a script.
+fn duplicate_variables(statements: &[ScriptStatement<'_>]) -> Vec<Diagnostic> {
+    let mut names = HashSet::with_capacity(statements.len());
+    statements
+        .iter()
+        .filter(|s| !names.insert(s.name))
+        .map(|s| {
+            Diagnostic::new(
+                DiagnosticKind::DuplicateVariable {
+                    name: s.name.to_string(),
+                },
+                s.span.clone(),
+            )
+        })
+        .collect()
+}
+
+/// Returns the span in `b` which `e` is about.
+fn error_span(b: &[u8], e: &Error) -> Range<usize> {
+    let position = match e {
+        Error::Peg(p) => p.location,
+        Error::IdentifierTooLong { position, .. } => *position,
+        _ => return 0..b.len(),
+    };
+    position..(position + 1).min(b.len()).max(position)
+}
+
+/// Parses `b` like [`Parser::parse_with_diagnostics()`].
+pub(crate) fn diagnose<'a>(
+    parser: &Parser,
+    b: &'a [u8],
+    format: LuaFormat,
+) -> (Option<LuaValue<'a>>, Vec<Diagnostic>) {
+    let opts = parser.options();
+    let hooks = Hooks::new(opts.cancellation.as_ref()).with_diagnostics();
+    let mut variables = Vec::new();
+    let r = match format {
+        LuaFormat::Script => script_with_hooks(b, opts, &hooks).map(|statements| {
+            variables = duplicate_variables(&statements);
+            statements.into_iter().collect()
+        }),
+        LuaFormat::ScriptReturn => script_return_with_hooks(b, opts, &hooks).map(|r| {
+            variables = duplicate_variables(&r.statements);
+            r.into_value()
+        }),
+        LuaFormat::Value | LuaFormat::Return => parser.parse_with_hooks(b, format, &hooks),
+    };
+
+    let e = match r {
+        Ok(value) => {
+            let mut diagnostics = hooks.diagnostics.take();
+            diagnostics.append(&mut variables);
+            diagnostics.sort_by_key(|d| (d.span.start, d.span.end));
+            return (Some(value), diagnostics);
+        }
+        Err(e) => e,
+    };
+
+    // Warnings from a failed parse may be about input which was never used, so only report the
+    // error, and whether the input could be salvaged.
+    let mut diagnostics = vec![Diagnostic::new(
+        DiagnosticKind::Error(e.clone()),
+        error_span(b, &e),
+    )];
+    let value = match repair(parser, b, format, e) {
+        Ok(s) => {
+            if let Some(truncated) = s.truncated {
+                diagnostics.push(Diagnostic::new(
+                    DiagnosticKind::Truncated,
+                    truncated..b.len(),
+                ));
+            }
+            Some(s.value)
+        }
+        Err(_) => None,
+    };
+
+    (value, diagnostics)
+}
+
+/// Parses `b` in any [`format`][LuaFormat] with [`ParseOptions`], returning the value (if any)
+/// and every error and warning about the input, with their spans.
+///
+/// This is intended for editors, linters and other tools which show problems to a person, rather
+/// than rejecting bad input. It's equivalent to [`Parser::parse_with_diagnostics()`].
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{lua_value, parse_with_diagnostics, DiagnosticKind, LuaFormat, ParseOptions};
+///
+/// let (value, diagnostics) = parse_with_diagnostics(
+///     b"{a = 1, b = 2, a = 3}",
+///     LuaFormat::Value,
+///     &ParseOptions::new(16),
+/// );
+/// assert_eq!(lua_value(b"{a = 1, b = 2, a = 3}", 16).ok(), value);
+/// assert_eq!(1, diagnostics.len());
+/// assert_eq!(DiagnosticKind::DuplicateKey { key: "a".to_string() }, diagnostics[0].kind);
+/// assert_eq!(15..20, diagnostics[0].span);
+/// ```
+pub fn parse_with_diagnostics<'a>(
+    b: &'a [u8],
+    format: LuaFormat,
+    opts: &ParseOptions,
+) -> (Option<LuaValue<'a>>, Vec<Diagnostic>) {
+    diagnose(&Parser::from(opts.clone()), b, format)
+}
@@ -0,0 +1,237 @@
+//! Decoding strings in legacy character encodings, using `encoding_rs`.
+use crate::{LuaTableEntry, LuaValue};
+use encoding_rs::Encoding;
+use serde::{de::Visitor, Deserializer};
+use std::{borrow::Cow, fmt::Formatter};
+
+impl<'a> LuaValue<'a> {
+    /// Decodes the value as a string in `encoding`, if it contains [a string][LuaValue::String].
+    ///
+    /// Many older games write strings in a single-byte encoding like
+    /// [Windows-1252][encoding_rs::WINDOWS_1252], rather than UTF-8. Byte order marks are not
+    /// handled, and malformed sequences are replaced with `U+FFFD REPLACEMENT CHARACTER`.
+    ///
+    /// The result is borrowed when the string is already valid in UTF-8 (like ASCII text).
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use encoding_rs::WINDOWS_1252;
+    /// use serde_luaq::LuaValue;
+    ///
+    /// let a = LuaValue::String(b"caf\xe9 \x80".into());
+    /// assert_eq!("café €", a.as_str_with_encoding(WINDOWS_1252).unwrap());
+    /// assert!(a.as_str().is_none());
+    ///
+    /// assert!(LuaValue::Boolean(true).as_str_with_encoding(WINDOWS_1252).is_none());
+    /// ```
+    pub fn as_str_with_encoding(&'a self, encoding: &'static Encoding) -> Option<Cow<'a, str>> {
+        match self {
+            Self::String(s) => Some(encoding.decode_without_bom_handling(s).0),
+            _ => None,
+        }
+    }
+}
+
+impl LuaTableEntry<'_> {
+    /// Decodes the entry's key as a string in `encoding`, if it has a string key.
+    ///
+    /// See [`LuaValue::as_str_with_encoding()`].
+    pub fn key_str_with_encoding(&self, encoding: &'static Encoding) -> Option<Cow<'_, str>> {
+        self.key_bytes()
+            .map(|k| encoding.decode_without_bom_handling(k).0)
+    }
+}
+
+/// Deserialises a Lua string in `encoding` as a [`String`].
+///
+/// This can be used to write a `deserialize_with` function for any encoding supported by
+/// `encoding_rs`. For Windows-1252 and Latin-1, use the [`windows_1252`] and [`latin1`] modules.
+///
+/// See [`LuaValue::as_str_with_encoding()`] for details.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde::{Deserialize, Deserializer};
+/// use serde_luaq::{decode_string, from_slice, LuaFormat};
+///
+/// fn shift_jis<'de, D: Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+///     decode_string(d, encoding_rs::SHIFT_JIS)
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct Item {
+///     #[serde(deserialize_with = "shift_jis")]
+///     name: String,
+/// }
+///
+/// let item: Item = from_slice(br#"{name = "\x83\x8a\x83\x93\x83\x53"}"#, LuaFormat::Value, 16)
+///     .unwrap();
+/// assert_eq!("リンゴ", item.name);
+/// ```
+pub fn decode_string<'de, D>(
+    deserializer: D,
+    encoding: &'static Encoding,
+) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_bytes(EncodedStringVisitor(Some(encoding)))
+}
+
+/// Decodes strings in an encoding, or Latin-1 if [`None`].
+struct EncodedStringVisitor(Option<&'static Encoding>);
+
+impl Visitor<'_> for EncodedStringVisitor {
+    type Value = String;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(match self.0 {
+            Some(encoding) => encoding.decode_without_bom_handling(v).0.into_owned(),
+            None => encoding_rs::mem::decode_latin1(v).into_owned(),
+        })
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(v.to_string())
+    }
+}
+
+/// Deserialises [Windows-1252][encoding_rs::WINDOWS_1252] encoded Lua strings into [`String`]
+/// fields, with `#[serde(deserialize_with = "serde_luaq::windows_1252::deserialize")]`.
+///
+/// Windows-1252 is a superset of Latin-1 (ISO-8859-1) which replaces most of the C1 control
+/// characters (`0x80` to `0x9F`) with printable characters like `€`. Web browsers treat Latin-1
+/// text as Windows-1252.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use serde_luaq::{from_slice, LuaFormat};
+///
+/// #[derive(Deserialize)]
+/// struct Item {
+///     #[serde(deserialize_with = "serde_luaq::windows_1252::deserialize")]
+///     name: String,
+/// }
+///
+/// let item: Item = from_slice(br#"{name = "Caf\xe9 \x80"}"#, LuaFormat::Value, 16).unwrap();
+/// assert_eq!("Café €", item.name);
+/// ```
+pub mod windows_1252 {
+    use serde::Deserializer;
+
+    /// Deserialises a Windows-1252 encoded Lua string as a [`String`].
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<String, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        super::decode_string(deserializer, encoding_rs::WINDOWS_1252)
+    }
+}
+
+/// Deserialises Latin-1 (ISO-8859-1) encoded Lua strings into [`String`] fields, with
+/// `#[serde(deserialize_with = "serde_luaq::latin1::deserialize")]`.
+///
+/// Each byte is decoded as the Unicode code point with the same value, so this never fails and
+/// keeps the C1 control characters (`0x80` to `0x9F`). Use [`windows_1252`]
+/// if the strings may contain characters like `€`.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use serde_luaq::{from_slice, LuaFormat};
+///
+/// #[derive(Deserialize)]
+/// struct Item {
+///     #[serde(deserialize_with = "serde_luaq::latin1::deserialize")]
+///     name: String,
+/// }
+///
+/// let item: Item = from_slice(br#"{name = "Caf\xe9 \x80"}"#, LuaFormat::Value, 16).unwrap();
+/// assert_eq!("Café \u{80}", item.name);
+/// ```
+pub mod latin1 {
+    use super::EncodedStringVisitor;
+    use serde::Deserializer;
+
+    /// Deserialises a Latin-1 encoded Lua string as a [`String`].
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<String, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_bytes(EncodedStringVisitor(None))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{from_slice, lua_value, LuaFormat};
+    use encoding_rs::WINDOWS_1252;
+    use serde::Deserialize;
+
+    #[test]
+    fn helpers() {
+        let v = lua_value(br#"{["na\xefve"] = "r\xe9sum\xe9", plain = 1}"#, 16).unwrap();
+        let LuaValue::Table(t) = &v else {
+            panic!("expected table");
+        };
+
+        assert_eq!(
+            Some(Cow::Owned("naïve".to_string())),
+            t[0].key_str_with_encoding(WINDOWS_1252)
+        );
+        assert_eq!(
+            Some("résumé".into()),
+            t[0].value()
+                .and_then(|v| v.as_str_with_encoding(WINDOWS_1252))
+        );
+
+        // ASCII is borrowed
+        assert!(matches!(
+            t[1].key_str_with_encoding(WINDOWS_1252),
+            Some(Cow::Borrowed("plain"))
+        ));
+        assert_eq!(
+            None,
+            t[1].value().unwrap().as_str_with_encoding(WINDOWS_1252)
+        );
+    }
+
+    #[test]
+    fn deserialize() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Item {
+            #[serde(deserialize_with = "crate::windows_1252::deserialize")]
+            a: String,
+            #[serde(deserialize_with = "crate::latin1::deserialize")]
+            b: String,
+        }
+
+        assert_eq!(
+            Item {
+                a: "\u{20ac}\u{e9}".to_string(),
+                b: "\u{80}\u{e9}".to_string(),
+            },
+            from_slice(br#"{a = "\x80\xe9", b = "\x80\xe9"}"#, LuaFormat::Value, 16).unwrap()
+        );
+
+        // Other types are still errors
+        assert!(from_slice::<Item>(b"{a = 1, b = ''}", LuaFormat::Value, 16).is_err());
+        assert!(from_slice::<Item>(b"{a = {}, b = ''}", LuaFormat::Value, 16).is_err());
+    }
+}
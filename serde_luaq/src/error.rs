@@ -1,19 +1,217 @@
+use crate::{valid_lua_identifier, LuaNumber, LuaValue};
 use serde::{de, ser};
-use std::fmt::Display;
 #[cfg(feature = "serde_json")]
 use std::str::Utf8Error;
+use std::{
+    borrow::Cow,
+    fmt::{Display, Formatter},
+};
 use thiserror::Error as ThisError;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-#[derive(Debug, ThisError, PartialEq, Eq)]
+#[derive(Debug, Clone, ThisError, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum Error {
     #[error("serde deserialize error: {0}")]
     SerdeDeserialize(String),
     #[error("serde serialize error: {0}")]
     SerdeSerialize(String),
-    #[error("peg parse error: {0:?}")]
+    /// The input isn't valid Lua, or uses syntax which isn't supported.
+    ///
+    /// This is displayed in the same format as [`SyntaxError`], which is stable across `peg`
+    /// versions.
+    #[error("{}", SyntaxError::from(.0))]
     Peg(#[from] peg::error::ParseError<usize>),
+    #[error("script has more than {limit} statements")]
+    TooManyStatements { limit: usize },
+    /// An identifier starting at byte offset `position` was longer than
+    /// [`ParseOptions::max_identifier_length`][crate::ParseOptions::max_identifier_length].
+    #[error("identifier at byte {position} is longer than {limit} bytes")]
+    IdentifierTooLong { limit: usize, position: usize },
+    /// Parsing or deserialisation was cancelled by a [`CancellationToken`][crate::CancellationToken]
+    /// or [progress hook][crate::Parser::value_with_progress].
+    #[error("cancelled")]
+    Cancelled,
+    /// Tables were nested more than `limit` deep while deserialising, with
+    /// [`DeserializeOptions::recursion_limit`][crate::DeserializeOptions::recursion_limit], or
+    /// while serialising, with [`Serializer::max_depth`][crate::Serializer::max_depth].
+    #[error("recursion limit of {limit} exceeded")]
+    RecursionLimit { limit: u16 },
+    /// A [field assignment][crate::ParseOptions::field_assignments] in a script tried to index
+    /// `path`, which is not a table (or was never assigned).
+    #[error("cannot assign to a field of {path}: not a table")]
+    IndexNotTable { path: String },
+    /// An error which occurred while deserialising a value nested inside a table.
+    ///
+    /// `path` is the location of the value, like `a.b[1]["c d"]`. Integer indexes start at `1`,
+    /// like Lua.
+    #[error("{path}: {source}")]
+    Path { path: String, source: Box<Error> },
+    /// A table was deserialised as a struct, but had more than one entry for the field `name`,
+    /// like `{a = 1, a = 2}`.
+    ///
+    /// `path` is the location of the table, in the same format as [`Error::Path`], or an empty
+    /// string for the root table.
+    #[error("{}duplicate field `{name}`", path_prefix(.path))]
+    DuplicateField { name: String, path: String },
+    /// Writing serialised output failed.
+    ///
+    /// The underlying [`std::io::Error`] is kept as its `kind` and message, so that this type can
+    /// still be compared.
+    #[error("I/O error: {message}")]
+    Io {
+        kind: std::io::ErrorKind,
+        message: String,
+    },
+    /// The input didn't end with a `--` comment line, but a
+    /// [footer check][crate::ParseOptions::footer] was set.
+    #[error("missing footer comment")]
+    MissingFooter,
+    /// A [footer check][crate::ParseOptions::footer] rejected the comment at the end of the input.
+    #[error("invalid footer: {message}")]
+    InvalidFooter { message: String },
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io {
+            kind: e.kind(),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl Error {
+    /// Returns the location of a deserialisation error within nested tables, if it has one.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Error::Path { path, .. } => Some(path),
+            Error::DuplicateField { path, .. } if !path.is_empty() => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Returns the underlying error, without any [path][Error::Path].
+    ///
+    /// [`Error::DuplicateField`] is returned as-is, because its path is part of the error.
+    pub fn without_path(&self) -> &Error {
+        match self {
+            Error::Path { source, .. } => source,
+            e => e,
+        }
+    }
+
+    /// Returns the details of a [syntax error][Error::Peg], if this is one.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::Parser;
+    ///
+    /// let err = Parser::default().value(b"{1 2}").unwrap_err();
+    /// let syntax = err.syntax_error().unwrap();
+    /// assert_eq!(3, syntax.position);
+    /// assert_eq!([r#"",""#, r#"";""#, r#""}""#, "whitespace"], syntax.expected.as_slice());
+    /// ```
+    pub fn syntax_error(&self) -> Option<SyntaxError> {
+        match self {
+            Error::Peg(e) => Some(e.into()),
+            _ => None,
+        }
+    }
+
+    /// Prepends a table key to the error's path.
+    ///
+    /// [`Error::Cancelled`] is returned as-is, because it doesn't relate to a particular value.
+    pub(crate) fn prepend_path(self, segment: impl Display) -> Self {
+        match self {
+            Error::Cancelled => Error::Cancelled,
+            Error::Path { path, source } => Error::Path {
+                path: join_path(segment, &path),
+                source,
+            },
+            Error::DuplicateField { name, path } => Error::DuplicateField {
+                name,
+                path: join_path(segment, &path),
+            },
+            source => Error::Path {
+                path: segment.to_string(),
+                source: Box::new(source),
+            },
+        }
+    }
+}
+
+/// Formats a (possibly empty) table `path` as an error message prefix.
+fn path_prefix(path: &str) -> String {
+    if path.is_empty() {
+        String::new()
+    } else {
+        format!("{path}: ")
+    }
+}
+
+/// Prepends `segment` to a (possibly empty) table `path`.
+fn join_path(segment: impl Display, path: &str) -> String {
+    if path.is_empty() || path.starts_with('[') {
+        format!("{segment}{path}")
+    } else {
+        format!("{segment}.{path}")
+    }
+}
+
+/// Appends a table key to a (possibly empty) table `path`.
+pub(crate) fn append_path(path: &str, key: &PathKey) -> String {
+    let key = key.to_string();
+    if path.is_empty() || key.starts_with('[') {
+        path.to_string() + &key
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// Key of a table entry, for [error paths][Error::Path].
+#[derive(Debug)]
+pub(crate) enum PathKey<'a> {
+    Name(Cow<'a, str>),
+    String(Cow<'a, [u8]>),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Nil,
+    Table,
+}
+
+impl<'a> From<&LuaValue<'a>> for PathKey<'a> {
+    fn from(key: &LuaValue<'a>) -> Self {
+        match key {
+            LuaValue::Nil => Self::Nil,
+            LuaValue::Boolean(b) => Self::Boolean(*b),
+            LuaValue::String(s) => Self::String(s.clone()),
+            LuaValue::Number(LuaNumber::Integer(i)) => Self::Integer(*i),
+            LuaValue::Number(LuaNumber::Float(f)) => Self::Float(*f),
+            LuaValue::Table(_) => Self::Table,
+        }
+    }
+}
+
+impl Display for PathKey<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Name(n) => write!(f, "{n}"),
+            Self::String(s) if valid_lua_identifier(s) => {
+                // Identifiers are always ASCII
+                write!(f, "{}", s.escape_ascii())
+            }
+            Self::String(s) => write!(f, "[\"{}\"]", s.escape_ascii()),
+            Self::Integer(i) => write!(f, "[{i}]"),
+            Self::Float(v) => write!(f, "[{v:?}]"),
+            Self::Boolean(b) => write!(f, "[{b}]"),
+            Self::Nil => write!(f, "[nil]"),
+            Self::Table => write!(f, "[{{...}}]"),
+        }
+    }
 }
 
 impl ser::Error for Error {
@@ -26,11 +224,226 @@ impl de::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
         Error::SerdeDeserialize(msg.to_string())
     }
+
+    fn duplicate_field(field: &'static str) -> Self {
+        Error::DuplicateField {
+            name: field.to_string(),
+            path: String::new(),
+        }
+    }
+}
+
+/// Details of a syntax error in Lua input, with a stable [`Display`] format.
+///
+/// The `peg` parser's own error type ([`peg::error::ParseError`]) may be displayed differently
+/// (or report its expected tokens differently) in future versions of `peg`. This type is
+/// `serde_luaq`'s own description of the error, so its output only changes when `serde_luaq`'s
+/// grammar does, and is suitable for snapshot tests.
+///
+/// It is displayed as:
+///
+/// ```text
+/// syntax error at byte {position}: expected {expected}
+/// ```
+///
+/// where `{expected}` is the single expected token, or `one of ` followed by a comma-separated
+/// list of tokens in byte order. Literal tokens are quoted as they are written in `serde_luaq`'s
+/// grammar (`"="`), and other tokens are descriptions (like `Lua value`). `peg`'s descriptions of byte patterns (like `[b',' | b';']`)
+/// are converted into quoted literals where possible, and otherwise omitted.
+///
+/// Changing this format is a breaking change.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{lua_value, SyntaxError};
+///
+/// let err = SyntaxError::from(lua_value(b"{a = true", 16).unwrap_err());
+/// assert_eq!(9, err.position);
+/// assert_eq!(
+///     r#"syntax error at byte 9: expected one of ",", ";", "}", whitespace"#,
+///     err.to_string(),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SyntaxError {
+    /// Byte offset in the input where parsing failed.
+    pub position: usize,
+
+    /// Tokens which the parser expected at [`position`][Self::position], sorted in byte order
+    /// without duplicates.
+    pub expected: Vec<String>,
+}
+
+/// Converts a `peg` byte pattern token (like `[b',' | b';']`) into quoted literals (`","` and
+/// `";"`), or [`None`] if it contains anything else.
+fn pattern_literals(token: &str) -> Option<Vec<String>> {
+    token
+        .strip_prefix('[')?
+        .strip_suffix(']')?
+        .split('|')
+        .map(|b| {
+            let c = b.trim().strip_prefix("b'")?.strip_suffix('\'')?;
+            (c.len() == 1 && c != "\\").then(|| format!("\"{c}\""))
+        })
+        .collect()
+}
+
+impl From<&peg::error::ParseError<usize>> for SyntaxError {
+    fn from(e: &peg::error::ParseError<usize>) -> Self {
+        let mut expected: Vec<String> = e
+            .expected
+            .tokens()
+            .flat_map(|token| {
+                if token.starts_with('[') {
+                    pattern_literals(token).unwrap_or_default()
+                } else {
+                    vec![token.to_string()]
+                }
+            })
+            .collect();
+        expected.sort_unstable();
+        expected.dedup();
+        Self {
+            position: e.location,
+            expected,
+        }
+    }
+}
+
+impl From<peg::error::ParseError<usize>> for SyntaxError {
+    fn from(e: peg::error::ParseError<usize>) -> Self {
+        Self::from(&e)
+    }
+}
+
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "syntax error at byte {}: expected ", self.position)?;
+        match self.expected.as_slice() {
+            [] => write!(f, "nothing"),
+            [token] => write!(f, "{token}"),
+            tokens => write!(f, "one of {}", tokens.join(", ")),
+        }
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// Error when converting a [`LuaValue`][crate::LuaValue] into another type with a checked
+/// conversion method, like [`LuaValue::try_into_i64()`][crate::LuaValue::try_into_i64].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConversionError {
+    /// The type the value was expected to be, like `integer` or `table`.
+    pub expected: &'static str,
+
+    /// The type the value actually was.
+    ///
+    /// This is like Lua's `type()` function, except numbers are described as `integer` or
+    /// `float`.
+    pub actual_type: &'static str,
+
+    /// The location of the value within nested tables, in the same format as [`Error::Path`], or
+    /// an empty string if unknown.
+    ///
+    /// Conversion methods don't know where a value came from, so use
+    /// [`prepend_path()`][Self::prepend_path] to add this while unwinding.
+    pub path: String,
+}
+
+impl ConversionError {
+    pub(crate) const fn new(expected: &'static str, actual_type: &'static str) -> Self {
+        Self {
+            expected,
+            actual_type,
+            path: String::new(),
+        }
+    }
+
+    /// Prepends a table key to the error's path.
+    ///
+    /// String keys should be passed as-is (`name`), and other keys in brackets (`[1]`).
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::LuaValue;
+    ///
+    /// let e = LuaValue::Nil.try_into_i64().unwrap_err();
+    /// let e = e.prepend_path("level").prepend_path("[1]").prepend_path("players");
+    /// assert_eq!("players[1].level: expected integer, found nil", e.to_string());
+    /// ```
+    pub fn prepend_path(mut self, segment: impl Display) -> Self {
+        self.path = join_path(segment, &self.path);
+        self
+    }
+}
+
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "expected {}, found {}", self.expected, self.actual_type)
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// Errors when applying [`Migrations`][crate::Migrations].
+#[derive(Debug, ThisError, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MigrationError {
+    #[error("version number is missing")]
+    MissingVersion,
+
+    #[error("version number is not an integer")]
+    InvalidVersion,
+
+    #[error("migration from version {version} failed: {message}")]
+    Step { version: i64, message: String },
+}
+
+/// Errors when decoding a value with [`from_binary()`][crate::from_binary].
+///
+/// Positions are byte offsets in the encoded input.
+#[derive(Debug, ThisError, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BinaryError {
+    #[error("input is not a serde_luaq binary value")]
+    InvalidHeader,
+
+    #[error("unsupported binary format version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("unexpected end of input")]
+    UnexpectedEof,
+
+    #[error("invalid tag {tag} at byte {position}")]
+    InvalidTag { tag: u8, position: usize },
+
+    #[error("invalid variable-length integer at byte {position}")]
+    InvalidVarint { position: usize },
+
+    #[error("length at byte {position} is longer than the input")]
+    InvalidLength { position: usize },
+
+    #[error("table key name at byte {position} is not valid UTF-8")]
+    InvalidUtf8 { position: usize },
+
+    #[error("table at byte {position} is too deeply nested")]
+    TooDeeplyNested { position: usize },
+
+    #[error("trailing bytes after value at byte {position}")]
+    TrailingBytes { position: usize },
 }
 
 #[cfg(feature = "serde_json")]
 /// Errors when converting Lua to JSON.
 #[derive(Debug, ThisError, PartialEq)]
+#[non_exhaustive]
 pub enum JsonConversionError {
     #[error("positive infinity cannot be represented in standard JSON")]
     PositiveInfinity,
@@ -49,12 +462,60 @@ pub enum JsonConversionError {
 
     #[error("Lua table contains a table as a key")]
     TableKeyedWithTable,
+
+    /// A table has more than one entry which converts to the JSON object key `key`, and
+    /// [`JsonConversionOptions::duplicate_keys`][crate::JsonConversionOptions::duplicate_keys] is
+    /// [`DuplicateKeys::Error`][crate::DuplicateKeys::Error].
+    #[error("Lua table contains more than one entry with the JSON object key {key:?}")]
+    DuplicateKey { key: String },
 }
 
 #[cfg(feature = "serde_json")]
 /// Errors when converting JSON to Lua.
 #[derive(Debug, ThisError, PartialEq)]
+#[non_exhaustive]
 pub enum LuaConversionError {
     #[error("Lua numbers must fit in `i64` or `f64`")]
     Number,
 }
+
+#[cfg(feature = "rayon")]
+/// Errors when deserialising a file with [`par_from_files()`][crate::par_from_files].
+#[derive(Debug, ThisError)]
+#[non_exhaustive]
+pub enum BulkError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Lua(#[from] Error),
+}
+
+#[cfg(feature = "csv")]
+/// Errors when exporting Lua tables to CSV.
+///
+/// Row numbers start at `1`, like Lua.
+#[derive(Debug, ThisError)]
+#[non_exhaustive]
+pub enum CsvExportError {
+    #[error("value is not a table")]
+    NotATable,
+
+    #[error("row {row} is not a table")]
+    RowNotATable { row: usize },
+
+    #[error("row {row} has a key which is not a string")]
+    InvalidKey { row: usize },
+
+    #[error("row {row} has column {column:?}, which is not in the first row")]
+    UnexpectedColumn { row: usize, column: String },
+
+    #[error("row {row} is missing column {column:?}")]
+    MissingColumn { row: usize, column: String },
+
+    #[error("row {row} column {column:?} contains a table")]
+    NestedTable { row: usize, column: String },
+
+    #[error("CSV error: {0}")]
+    Csv(#[from] ::csv::Error),
+}
@@ -0,0 +1,316 @@
+//! C ABI for using the parser from other languages.
+//!
+//! These functions are exported with C linkage when the `ffi` feature is enabled. To build a
+//! shared library, depend on `serde_luaq` with the `ffi` feature from a crate with
+//! `crate-type = ["cdylib"]`, and re-export this module's functions (eg: `pub use
+//! serde_luaq::ffi::*;`).
+//!
+//! The equivalent C declarations are:
+//!
+//! ```c
+//! #include <stddef.h>
+//! #include <stdint.h>
+//!
+//! int32_t serde_luaq_to_json(
+//!     const uint8_t *input, size_t len, uint32_t format, uint16_t max_depth,
+//!     char **out_json, char **out_error);
+//! int32_t serde_luaq_validate(
+//!     const uint8_t *input, size_t len, uint32_t format, uint16_t max_depth,
+//!     char **out_error);
+//! void serde_luaq_free_string(char *s);
+//! ```
+//!
+//! `format` is one of the `SERDE_LUAQ_FORMAT_*` constants, and functions return one of the
+//! `SERDE_LUAQ_*` status codes.
+//!
+//! Panics are caught at the boundary, and returned as [`SERDE_LUAQ_PANIC`].
+use crate::{to_json_value, Error, JsonConversionOptions, LuaValue, Parser};
+use std::{
+    ffi::{c_char, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr, slice,
+};
+
+/// `format`: [a bare Lua value][crate::LuaFormat::Value].
+pub const SERDE_LUAQ_FORMAT_VALUE: u32 = 0;
+
+/// `format`: [a `return` statement][crate::LuaFormat::Return].
+pub const SERDE_LUAQ_FORMAT_RETURN: u32 = 1;
+
+/// `format`: [a script with variable assignments][crate::LuaFormat::Script].
+pub const SERDE_LUAQ_FORMAT_SCRIPT: u32 = 2;
+
+/// `format`: [a script with a trailing `return` statement][crate::LuaFormat::ScriptReturn].
+pub const SERDE_LUAQ_FORMAT_SCRIPT_RETURN: u32 = 3;
+
+/// Status: success.
+pub const SERDE_LUAQ_OK: i32 = 0;
+
+/// Status: the input couldn't be parsed.
+pub const SERDE_LUAQ_PARSE_ERROR: i32 = 1;
+
+/// Status: the input was parsed, but couldn't be represented as JSON.
+pub const SERDE_LUAQ_JSON_ERROR: i32 = 2;
+
+/// Status: an argument was invalid, like a `NULL` output pointer or unknown `format`.
+pub const SERDE_LUAQ_INVALID_ARGUMENT: i32 = 3;
+
+/// Status: `serde_luaq` panicked. This is a bug.
+pub const SERDE_LUAQ_PANIC: i32 = 4;
+
+/// An error returned to C, with its status code and message.
+struct FfiError(i32, String);
+
+impl From<Error> for FfiError {
+    fn from(e: Error) -> Self {
+        Self(SERDE_LUAQ_PARSE_ERROR, e.to_string())
+    }
+}
+
+/// Parses `input` in `format`.
+fn parse(input: &[u8], format: u32, max_depth: u16) -> Result<LuaValue<'_>, FfiError> {
+    let p = Parser::builder().max_depth(max_depth).build();
+    Ok(match format {
+        SERDE_LUAQ_FORMAT_VALUE => p.value(input)?,
+        SERDE_LUAQ_FORMAT_RETURN => p.ret(input)?,
+        SERDE_LUAQ_FORMAT_SCRIPT => p.script(input)?.into_iter().collect(),
+        SERDE_LUAQ_FORMAT_SCRIPT_RETURN => p.script_return(input)?.into_value(),
+        _ => {
+            return Err(FfiError(
+                SERDE_LUAQ_INVALID_ARGUMENT,
+                format!("unknown format: {format}"),
+            ))
+        }
+    })
+}
+
+/// Converts a Rust string into a C string allocated by Rust, replacing any null bytes.
+fn to_c_string(s: String) -> *mut c_char {
+    CString::new(s.replace('\0', "\u{FFFD}"))
+        .unwrap_or_default()
+        .into_raw()
+}
+
+/// Runs `f` with the input slice, catching panics and writing any error to `out_error`.
+///
+/// ## Safety
+///
+/// See [`serde_luaq_to_json()`].
+unsafe fn call(
+    input: *const u8,
+    len: usize,
+    out_error: *mut *mut c_char,
+    f: impl FnOnce(&[u8]) -> Result<(), FfiError>,
+) -> i32 {
+    if !out_error.is_null() {
+        *out_error = ptr::null_mut();
+    }
+
+    let r = if input.is_null() && len != 0 {
+        Err(FfiError(
+            SERDE_LUAQ_INVALID_ARGUMENT,
+            "input is NULL".to_string(),
+        ))
+    } else {
+        let input = if len == 0 {
+            &[][..]
+        } else {
+            slice::from_raw_parts(input, len)
+        };
+
+        catch_unwind(AssertUnwindSafe(|| f(input))).unwrap_or_else(|_| {
+            Err(FfiError(
+                SERDE_LUAQ_PANIC,
+                "serde_luaq panicked".to_string(),
+            ))
+        })
+    };
+
+    match r {
+        Ok(()) => SERDE_LUAQ_OK,
+        Err(FfiError(code, message)) => {
+            if !out_error.is_null() {
+                *out_error = to_c_string(message);
+            }
+            code
+        }
+    }
+}
+
+/// Parses Lua `input` in `format`, and converts it to a JSON string with
+/// [`to_json_value()`][crate::to_json_value]'s default options.
+///
+/// On success, returns [`SERDE_LUAQ_OK`] and sets `*out_json` to a null-terminated UTF-8 string.
+///
+/// On failure, returns a status code, and sets `*out_error` to an error message (if `out_error`
+/// is not `NULL`).
+///
+/// Strings returned in `out_json` and `out_error` must be freed with
+/// [`serde_luaq_free_string()`].
+///
+/// ## Safety
+///
+/// * `input` must point to `len` readable bytes, or may be `NULL` if `len` is `0`.
+/// * `out_json` must be a valid pointer to write to.
+/// * `out_error` must be a valid pointer to write to, or `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn serde_luaq_to_json(
+    input: *const u8,
+    len: usize,
+    format: u32,
+    max_depth: u16,
+    out_json: *mut *mut c_char,
+    out_error: *mut *mut c_char,
+) -> i32 {
+    if out_json.is_null() {
+        return call(input, len, out_error, |_| {
+            Err(FfiError(
+                SERDE_LUAQ_INVALID_ARGUMENT,
+                "out_json is NULL".to_string(),
+            ))
+        });
+    }
+
+    *out_json = ptr::null_mut();
+    call(input, len, out_error, |input| {
+        let v = parse(input, format, max_depth)?;
+        let json = to_json_value(v, JsonConversionOptions::default())
+            .map_err(|e| FfiError(SERDE_LUAQ_JSON_ERROR, e.to_string()))?;
+        *out_json = to_c_string(json.to_string());
+        Ok(())
+    })
+}
+
+/// Checks that Lua `input` can be parsed in `format`.
+///
+/// Returns [`SERDE_LUAQ_OK`] if it can, or a status code and sets `*out_error` to an error
+/// message (if `out_error` is not `NULL`). The error message must be freed with
+/// [`serde_luaq_free_string()`].
+///
+/// ## Safety
+///
+/// * `input` must point to `len` readable bytes, or may be `NULL` if `len` is `0`.
+/// * `out_error` must be a valid pointer to write to, or `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn serde_luaq_validate(
+    input: *const u8,
+    len: usize,
+    format: u32,
+    max_depth: u16,
+    out_error: *mut *mut c_char,
+) -> i32 {
+    call(input, len, out_error, |input| {
+        parse(input, format, max_depth).map(drop)
+    })
+}
+
+/// Frees a string returned by another `serde_luaq_*` function. Does nothing if `s` is `NULL`.
+///
+/// ## Safety
+///
+/// `s` must be `NULL`, or a string returned by a `serde_luaq_*` function which hasn't already
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn serde_luaq_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::CStr;
+
+    /// Takes ownership of a string returned by the C ABI.
+    unsafe fn take(s: *mut c_char) -> Option<String> {
+        if s.is_null() {
+            return None;
+        }
+        let r = CStr::from_ptr(s).to_str().unwrap().to_string();
+        serde_luaq_free_string(s);
+        Some(r)
+    }
+
+    fn to_json(input: &[u8], format: u32) -> (i32, Option<String>, Option<String>) {
+        let mut json = ptr::null_mut();
+        let mut error = ptr::null_mut();
+        unsafe {
+            let r = serde_luaq_to_json(
+                input.as_ptr(),
+                input.len(),
+                format,
+                16,
+                &mut json,
+                &mut error,
+            );
+            (r, take(json), take(error))
+        }
+    }
+
+    #[test]
+    fn to_json_formats() {
+        assert_eq!(
+            (SERDE_LUAQ_OK, Some(r#"{"a":[1,2]}"#.to_string()), None),
+            to_json(b"{a = {1, 2}}", SERDE_LUAQ_FORMAT_VALUE)
+        );
+        assert_eq!(
+            (SERDE_LUAQ_OK, Some("true".to_string()), None),
+            to_json(b"return true", SERDE_LUAQ_FORMAT_RETURN)
+        );
+        assert_eq!(
+            (SERDE_LUAQ_OK, Some(r#"{"a":"b"}"#.to_string()), None),
+            to_json(b"a = 'b'", SERDE_LUAQ_FORMAT_SCRIPT)
+        );
+        assert_eq!(
+            (SERDE_LUAQ_OK, Some("[1]".to_string()), None),
+            to_json(b"a = {1}\nreturn a", SERDE_LUAQ_FORMAT_SCRIPT_RETURN)
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let (r, json, error) = to_json(b"{1 2}", SERDE_LUAQ_FORMAT_VALUE);
+        assert_eq!(SERDE_LUAQ_PARSE_ERROR, r);
+        assert_eq!(None, json);
+        assert!(error.unwrap().starts_with("syntax error at byte 3"));
+
+        let (r, _, error) = to_json(b"(0/0)", SERDE_LUAQ_FORMAT_VALUE);
+        assert_eq!(SERDE_LUAQ_JSON_ERROR, r);
+        assert!(error.is_some());
+
+        let (r, _, error) = to_json(b"1", 99);
+        assert_eq!(SERDE_LUAQ_INVALID_ARGUMENT, r);
+        assert_eq!(Some("unknown format: 99".to_string()), error);
+
+        unsafe {
+            // Missing output pointers
+            let mut error = ptr::null_mut();
+            let r = serde_luaq_to_json(ptr::null(), 0, 0, 16, ptr::null_mut(), &mut error);
+            assert_eq!(SERDE_LUAQ_INVALID_ARGUMENT, r);
+            assert_eq!(Some("out_json is NULL".to_string()), take(error));
+
+            // NULL input
+            let r = serde_luaq_validate(ptr::null(), 1, 0, 16, ptr::null_mut());
+            assert_eq!(SERDE_LUAQ_INVALID_ARGUMENT, r);
+
+            serde_luaq_free_string(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn validate() {
+        unsafe {
+            for (input, expected) in [
+                (&b"{1, 2}"[..], SERDE_LUAQ_OK),
+                (b"{1 2}", SERDE_LUAQ_PARSE_ERROR),
+                (b"(0/0)", SERDE_LUAQ_OK),
+            ] {
+                let mut error = ptr::null_mut();
+                let r = serde_luaq_validate(input.as_ptr(), input.len(), 0, 16, &mut error);
+                assert_eq!(expected, r);
+                assert_eq!(expected != SERDE_LUAQ_OK, take(error).is_some());
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! Validation of a trailing comment (like a checksum) at the end of the input.
+use crate::{Error, ParseOptions};
+use std::{fmt, sync::Arc};
+
+type FooterFn = dyn Fn(&[u8], &[u8]) -> Result<(), String> + Send + Sync;
+
+/// Callback to validate a `--` comment line at the end of the input, for
+/// [`ParseOptions::footer`].
+///
+/// Some games append a checksum or signature to their save files as a comment, like:
+///
+/// ```lua
+/// return {1, 2, 3}
+/// -- checksum: 04c1
+/// ```
+///
+/// The callback is passed:
+///
+/// 1. the body: all of the input before the comment's `--`, including the line break before it
+/// 2. the comment's text, after the `--` and with leading and trailing ASCII whitespace removed
+///
+/// It returns an error message if the footer doesn't match the body, which is returned as
+/// [`Error::InvalidFooter`].
+///
+/// The footer must be a line comment on its own line (after any trailing whitespace is removed).
+/// Long comments (like `--[[ ... ]]`) aren't footers.
+///
+/// Clones of a `FooterCheck` share the same callback, and compare equal.
+#[derive(Clone)]
+pub struct FooterCheck(Arc<FooterFn>);
+
+impl FooterCheck {
+    /// Creates a footer check which calls `check(body, footer)`.
+    pub fn new(check: impl Fn(&[u8], &[u8]) -> Result<(), String> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(check))
+    }
+}
+
+impl fmt::Debug for FooterCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FooterCheck(..)")
+    }
+}
+
+impl PartialEq for FooterCheck {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for FooterCheck {}
+
+/// Splits a trailing `--` line comment from `b`, returning the body and the comment's text.
+fn split_footer(b: &[u8]) -> Option<(&[u8], &[u8])> {
+    let end = b.trim_ascii_end();
+    let line_start = end.iter().rposition(|&c| c == b'\n').map_or(0, |i| i + 1);
+    let comment = end[line_start..].trim_ascii_start();
+    let text = comment.strip_prefix(b"--")?;
+
+    // `--[[`, `--[==[` etc. start a long comment.
+    if let Some(level) = text.strip_prefix(b"[") {
+        if level.iter().find(|&&c| c != b'=') == Some(&b'[') {
+            return None;
+        }
+    }
+
+    Some((&b[..end.len() - comment.len()], text.trim_ascii()))
+}
+
+/// Checks the footer of `b` with [`ParseOptions::footer`], returning the input to parse.
+///
+/// If no footer check is set, returns `b` unchanged.
+pub(crate) fn strip_footer<'a>(b: &'a [u8], opts: &ParseOptions) -> Result<&'a [u8], Error> {
+    let Some(check) = &opts.footer else {
+        return Ok(b);
+    };
+
+    let (body, footer) = split_footer(b).ok_or(Error::MissingFooter)?;
+    (check.0)(body, footer).map_err(|message| Error::InvalidFooter { message })?;
+    Ok(body)
+}
@@ -0,0 +1,614 @@
+//! Golden-file test harness for downstream crates.
+use crate::{
+    lua_value, return_statement, script, script_return_with_options, to_json_value, Error,
+    JsonConversionError, JsonConversionOptions, LuaFormat, LuaValue, ParseOptions,
+};
+use serde_json::Value as JsonValue;
+use std::{
+    fmt::{Display, Formatter},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// File extension for Lua fixtures.
+const LUA_EXTENSION: &str = "lua";
+
+/// File extension for JSON snapshots.
+const JSON_EXTENSION: &str = "json";
+
+/// File extension for [`Debug`][std::fmt::Debug] snapshots of [`LuaValue`].
+const DEBUG_EXTENSION: &str = "debug";
+
+/// [Golden-file test][check_golden_dir] options.
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub struct GoldenOptions {
+    /// The format of the `.lua` fixtures.
+    pub format: LuaFormat,
+
+    /// Maximum table depth when parsing fixtures.
+    pub max_depth: u16,
+
+    /// Options for converting fixtures to JSON, for comparison with `.json` snapshots.
+    pub json: JsonConversionOptions,
+
+    /// When `true`, snapshots are (re-)written with the _actual_ value of each fixture, rather
+    /// than compared.
+    ///
+    /// Existing snapshots are overwritten. Fixtures without any snapshot get a `.json` snapshot if
+    /// they can be [converted to JSON][to_json_value], or a `.debug` snapshot otherwise.
+    ///
+    /// This is typically controlled with an environment variable, eg:
+    ///
+    /// ```rust
+    /// # use serde_luaq::GoldenOptions;
+    /// let opts = GoldenOptions::new().bless(std::env::var_os("BLESS").is_some());
+    /// ```
+    pub bless: bool,
+}
+
+impl GoldenOptions {
+    /// Creates options for [`LuaFormat::Value`] fixtures, with a `max_depth` of `16`.
+    pub const fn new() -> Self {
+        Self {
+            format: LuaFormat::Value,
+            max_depth: 16,
+            json: JsonConversionOptions::new(),
+            bless: false,
+        }
+    }
+
+    /// Sets [`format`][Self::format].
+    pub const fn format(mut self, format: LuaFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets [`max_depth`][Self::max_depth].
+    pub const fn max_depth(mut self, max_depth: u16) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets [`json`][Self::json].
+    pub const fn json(mut self, json: JsonConversionOptions) -> Self {
+        self.json = json;
+        self
+    }
+
+    /// Sets [`bless`][Self::bless].
+    pub const fn bless(mut self, bless: bool) -> Self {
+        self.bless = bless;
+        self
+    }
+}
+
+impl Default for GoldenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Results of [a golden-file test run][check_golden_dir].
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct GoldenReport {
+    /// Fixtures which matched all of their snapshots.
+    pub passed: Vec<PathBuf>,
+
+    /// Snapshots which were written, when [`GoldenOptions::bless`] is `true`.
+    pub blessed: Vec<PathBuf>,
+
+    /// Fixtures which failed.
+    pub failures: Vec<GoldenFailure>,
+}
+
+impl GoldenReport {
+    /// Returns `true` if there were no failures.
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl Display for GoldenReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} passed, {} blessed, {} failed",
+            self.passed.len(),
+            self.blessed.len(),
+            self.failures.len()
+        )?;
+
+        for failure in &self.failures {
+            write!(f, "\n{failure}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A single fixture or snapshot which failed [a golden-file test][check_golden_dir].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct GoldenFailure {
+    /// Path to the `.lua` fixture.
+    pub fixture: PathBuf,
+
+    /// The reason the fixture failed.
+    pub kind: GoldenFailureKind,
+}
+
+impl Display for GoldenFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "--- {}", self.fixture.display())?;
+        match &self.kind {
+            GoldenFailureKind::Parse(e) => writeln!(f, "parse error: {e}"),
+            GoldenFailureKind::Json(e) => writeln!(f, "JSON conversion error: {e}"),
+            GoldenFailureKind::MissingSnapshot => {
+                writeln!(f, "no .{JSON_EXTENSION} or .{DEBUG_EXTENSION} snapshot")
+            }
+            GoldenFailureKind::InvalidSnapshot { snapshot, message } => {
+                writeln!(f, "invalid snapshot {}: {message}", snapshot.display())
+            }
+            GoldenFailureKind::JsonMismatch { snapshot, diffs } => {
+                writeln!(f, "does not match {}:", snapshot.display())?;
+                for diff in diffs {
+                    writeln!(f, "{diff}")?;
+                }
+                Ok(())
+            }
+            GoldenFailureKind::DebugMismatch { snapshot, diffs } => {
+                writeln!(f, "does not match {}:", snapshot.display())?;
+                for diff in diffs {
+                    writeln!(f, "{diff}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The reason a fixture failed [a golden-file test][check_golden_dir].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum GoldenFailureKind {
+    /// The fixture could not be parsed.
+    Parse(Error),
+
+    /// The fixture could not be converted to JSON, for comparison with a `.json` snapshot.
+    Json(JsonConversionError),
+
+    /// The fixture has no snapshots.
+    MissingSnapshot,
+
+    /// A snapshot could not be read or parsed.
+    InvalidSnapshot { snapshot: PathBuf, message: String },
+
+    /// The fixture does not match its `.json` snapshot.
+    JsonMismatch {
+        snapshot: PathBuf,
+        diffs: Vec<JsonDiff>,
+    },
+
+    /// The fixture does not match its `.debug` snapshot.
+    DebugMismatch {
+        snapshot: PathBuf,
+        diffs: Vec<LineDiff>,
+    },
+}
+
+/// A difference between an expected and actual [JSON value][JsonValue].
+#[derive(Debug, PartialEq)]
+pub struct JsonDiff {
+    /// [JSON Pointer][rfc6901] to the value which differs.
+    ///
+    /// [rfc6901]: https://www.rfc-editor.org/rfc/rfc6901
+    pub pointer: String,
+
+    /// The expected value, or [`None`] if the value was not expected.
+    pub expected: Option<JsonValue>,
+
+    /// The actual value, or [`None`] if the value was missing.
+    pub actual: Option<JsonValue>,
+}
+
+impl Display for JsonDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let pointer = if self.pointer.is_empty() {
+            "(root)"
+        } else {
+            &self.pointer
+        };
+
+        match (&self.expected, &self.actual) {
+            (Some(e), Some(a)) => write!(f, "  {pointer}: expected {e}, got {a}"),
+            (Some(e), None) => write!(f, "  {pointer}: missing, expected {e}"),
+            (None, Some(a)) => write!(f, "  {pointer}: unexpected {a}"),
+            (None, None) => write!(f, "  {pointer}"),
+        }
+    }
+}
+
+/// A difference between lines of an expected and actual [`Debug`][std::fmt::Debug] snapshot.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LineDiff {
+    /// Line number of the difference, starting at `1`.
+    pub line: usize,
+
+    /// The expected line, or [`None`] if the actual value has extra lines.
+    pub expected: Option<String>,
+
+    /// The actual line, or [`None`] if the actual value has fewer lines.
+    pub actual: Option<String>,
+}
+
+impl Display for LineDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let line = self.line;
+        match (&self.expected, &self.actual) {
+            (Some(e), Some(a)) => write!(f, "{line:>5} - {e}\n{line:>5} + {a}"),
+            (Some(e), None) => write!(f, "{line:>5} - {e}"),
+            (None, Some(a)) => write!(f, "{line:>5} + {a}"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Parses a fixture in [`format`][LuaFormat].
+fn parse<'a>(b: &'a [u8], opts: &GoldenOptions) -> Result<LuaValue<'a>, Error> {
+    Ok(match opts.format {
+        LuaFormat::Value => lua_value(b, opts.max_depth)?,
+        LuaFormat::Script => script(b, opts.max_depth)?.into_iter().collect(),
+        LuaFormat::Return => return_statement(b, opts.max_depth)?,
+        LuaFormat::ScriptReturn => {
+            script_return_with_options(b, &ParseOptions::new(opts.max_depth))?.into_value()
+        }
+    })
+}
+
+/// Compares two JSON values, appending differences to `diffs`.
+fn diff_json(
+    pointer: &mut String,
+    expected: &JsonValue,
+    actual: &JsonValue,
+    diffs: &mut Vec<JsonDiff>,
+) {
+    match (expected, actual) {
+        (JsonValue::Object(e), JsonValue::Object(a)) => {
+            for (k, ev) in e {
+                let len = pointer.len();
+                push_pointer(pointer, k);
+                match a.get(k) {
+                    Some(av) => diff_json(pointer, ev, av, diffs),
+                    None => diffs.push(JsonDiff {
+                        pointer: pointer.clone(),
+                        expected: Some(ev.clone()),
+                        actual: None,
+                    }),
+                }
+                pointer.truncate(len);
+            }
+
+            for (k, av) in a {
+                if !e.contains_key(k) {
+                    let len = pointer.len();
+                    push_pointer(pointer, k);
+                    diffs.push(JsonDiff {
+                        pointer: pointer.clone(),
+                        expected: None,
+                        actual: Some(av.clone()),
+                    });
+                    pointer.truncate(len);
+                }
+            }
+        }
+
+        (JsonValue::Array(e), JsonValue::Array(a)) => {
+            for i in 0..e.len().max(a.len()) {
+                let len = pointer.len();
+                push_pointer(pointer, &i.to_string());
+                match (e.get(i), a.get(i)) {
+                    (Some(ev), Some(av)) => diff_json(pointer, ev, av, diffs),
+                    (ev, av) => diffs.push(JsonDiff {
+                        pointer: pointer.clone(),
+                        expected: ev.cloned(),
+                        actual: av.cloned(),
+                    }),
+                }
+                pointer.truncate(len);
+            }
+        }
+
+        (e, a) => {
+            if e != a {
+                diffs.push(JsonDiff {
+                    pointer: pointer.clone(),
+                    expected: Some(e.clone()),
+                    actual: Some(a.clone()),
+                });
+            }
+        }
+    }
+}
+
+/// Appends an escaped reference token to a JSON Pointer.
+fn push_pointer(pointer: &mut String, token: &str) {
+    pointer.push('/');
+    pointer.push_str(&token.replace('~', "~0").replace('/', "~1"));
+}
+
+/// Compares two strings line-by-line.
+fn diff_lines(expected: &str, actual: &str) -> Vec<LineDiff> {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    (0..expected.len().max(actual.len()))
+        .filter_map(|i| {
+            let e = expected.get(i).copied();
+            let a = actual.get(i).copied();
+            (e != a).then(|| LineDiff {
+                line: i + 1,
+                expected: e.map(str::to_string),
+                actual: a.map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+/// Checks a single fixture against its snapshots.
+fn check_fixture(
+    fixture: &Path,
+    opts: &GoldenOptions,
+    report: &mut GoldenReport,
+) -> io::Result<()> {
+    let fail = |kind| GoldenFailure {
+        fixture: fixture.to_path_buf(),
+        kind,
+    };
+
+    let b = fs::read(fixture)?;
+    let value = match parse(&b, opts) {
+        Ok(v) => v,
+        Err(e) => {
+            report.failures.push(fail(GoldenFailureKind::Parse(e)));
+            return Ok(());
+        }
+    };
+
+    let json_path = fixture.with_extension(JSON_EXTENSION);
+    let debug_path = fixture.with_extension(DEBUG_EXTENSION);
+    let has_json = json_path.is_file();
+    let has_debug = debug_path.is_file();
+    let actual_debug = format!("{value:#?}\n");
+    let actual_json = to_json_value(value, &opts.json);
+
+    if opts.bless {
+        let write_json = has_json || (!has_debug && actual_json.is_ok());
+        if write_json {
+            match actual_json {
+                Ok(json) => {
+                    let mut s = serde_json::to_string_pretty(&json).map_err(io::Error::other)?;
+                    s.push('\n');
+                    fs::write(&json_path, s)?;
+                    report.blessed.push(json_path);
+                }
+                Err(e) => {
+                    report.failures.push(fail(GoldenFailureKind::Json(e)));
+                    return Ok(());
+                }
+            }
+        }
+
+        if has_debug || !write_json {
+            fs::write(&debug_path, actual_debug)?;
+            report.blessed.push(debug_path);
+        }
+
+        return Ok(());
+    }
+
+    if !has_json && !has_debug {
+        report
+            .failures
+            .push(fail(GoldenFailureKind::MissingSnapshot));
+        return Ok(());
+    }
+
+    let mut ok = true;
+    if has_json {
+        match actual_json {
+            Err(e) => {
+                report.failures.push(fail(GoldenFailureKind::Json(e)));
+                ok = false;
+            }
+            Ok(actual) => match serde_json::from_slice::<JsonValue>(&fs::read(&json_path)?) {
+                Err(e) => {
+                    report
+                        .failures
+                        .push(fail(GoldenFailureKind::InvalidSnapshot {
+                            snapshot: json_path,
+                            message: e.to_string(),
+                        }));
+                    ok = false;
+                }
+                Ok(expected) => {
+                    let mut diffs = Vec::new();
+                    diff_json(&mut String::new(), &expected, &actual, &mut diffs);
+                    if !diffs.is_empty() {
+                        report.failures.push(fail(GoldenFailureKind::JsonMismatch {
+                            snapshot: json_path,
+                            diffs,
+                        }));
+                        ok = false;
+                    }
+                }
+            },
+        }
+    }
+
+    if has_debug {
+        let expected = fs::read_to_string(&debug_path)?;
+        let diffs = diff_lines(&expected, &actual_debug);
+        if !diffs.is_empty() {
+            report.failures.push(fail(GoldenFailureKind::DebugMismatch {
+                snapshot: debug_path,
+                diffs,
+            }));
+            ok = false;
+        }
+    }
+
+    if ok {
+        report.passed.push(fixture.to_path_buf());
+    }
+
+    Ok(())
+}
+
+/// Checks every `.lua` fixture in `dir` against its snapshots.
+///
+/// Each fixture `name.lua` is parsed in [`GoldenOptions::format`], then compared with:
+///
+/// * `name.json`: a JSON snapshot, compared with the fixture [converted to JSON][to_json_value].
+///   Differences are reported as [JSON Pointers][JsonDiff::pointer].
+///
+/// * `name.debug`: a [`Debug`][std::fmt::Debug] snapshot (`{:#?}`) of the parsed [`LuaValue`],
+///   compared line-by-line. This can represent values that JSON cannot, like binary strings,
+///   `NaN`, and distinguish integers from floats.
+///
+/// Fixtures must have at least one snapshot. Fixtures are checked in file name order, and
+/// sub-directories are not searched.
+///
+/// This returns an [`io::Error`] only if `dir` or a file within it could not be read; all other
+/// problems are reported in the [`GoldenReport`]. Use [`assert_golden_dir()`] to panic on
+/// failure.
+///
+/// Requires the `golden` feature.
+pub fn check_golden_dir(dir: impl AsRef<Path>, opts: &GoldenOptions) -> io::Result<GoldenReport> {
+    let mut fixtures = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|e| e == LUA_EXTENSION) {
+            fixtures.push(path);
+        }
+    }
+    fixtures.sort();
+
+    let mut report = GoldenReport::default();
+    for fixture in fixtures {
+        check_fixture(&fixture, opts, &mut report)?;
+    }
+
+    Ok(report)
+}
+
+/// Checks every `.lua` fixture in `dir` against its snapshots, and panics with a report of all
+/// differences on failure.
+///
+/// This also panics if `dir` contains no fixtures, to catch mistyped paths.
+///
+/// See [`check_golden_dir()`] for details.
+///
+/// Requires the `golden` feature.
+///
+/// ## Example
+///
+/// ```rust,no_run
+/// use serde_luaq::{assert_golden_dir, GoldenOptions, LuaFormat};
+///
+/// #[test]
+/// fn save_files() {
+///     assert_golden_dir(
+///         concat!(env!("CARGO_MANIFEST_DIR"), "/tests/saves"),
+///         &GoldenOptions::new()
+///             .format(LuaFormat::Return)
+///             .bless(std::env::var_os("BLESS").is_some()),
+///     );
+/// }
+/// ```
+#[track_caller]
+pub fn assert_golden_dir(dir: impl AsRef<Path>, opts: &GoldenOptions) {
+    let dir = dir.as_ref();
+    let report = check_golden_dir(dir, opts)
+        .unwrap_or_else(|e| panic!("cannot read golden files in {}: {e}", dir.display()));
+
+    assert!(
+        !report.passed.is_empty() || !report.blessed.is_empty() || !report.failures.is_empty(),
+        "no .{LUA_EXTENSION} fixtures in {}",
+        dir.display()
+    );
+    assert!(
+        report.is_ok(),
+        "golden files in {} differ:\n{report}",
+        dir.display()
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn json_diffs() {
+        let mut diffs = Vec::new();
+        diff_json(
+            &mut String::new(),
+            &json!({"a": [1, 2, 3], "b/c": {"d": true}, "e": null}),
+            &json!({"a": [1, 5], "b/c": {"d": false}, "f": 1}),
+            &mut diffs,
+        );
+
+        assert_eq!(
+            vec![
+                JsonDiff {
+                    pointer: "/a/1".to_string(),
+                    expected: Some(json!(2)),
+                    actual: Some(json!(5)),
+                },
+                JsonDiff {
+                    pointer: "/a/2".to_string(),
+                    expected: Some(json!(3)),
+                    actual: None,
+                },
+                JsonDiff {
+                    pointer: "/b~1c/d".to_string(),
+                    expected: Some(json!(true)),
+                    actual: Some(json!(false)),
+                },
+                JsonDiff {
+                    pointer: "/e".to_string(),
+                    expected: Some(json!(null)),
+                    actual: None,
+                },
+                JsonDiff {
+                    pointer: "/f".to_string(),
+                    expected: None,
+                    actual: Some(json!(1)),
+                },
+            ],
+            diffs
+        );
+    }
+
+    #[test]
+    fn line_diffs() {
+        assert!(diff_lines("a\nb\n", "a\nb\n").is_empty());
+        assert_eq!(
+            vec![
+                LineDiff {
+                    line: 2,
+                    expected: Some("b".to_string()),
+                    actual: Some("c".to_string()),
+                },
+                LineDiff {
+                    line: 3,
+                    expected: None,
+                    actual: Some("d".to_string()),
+                },
+            ],
+            diff_lines("a\nb\n", "a\nc\nd\n")
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! Conversions between [`LuaValue`] tables and [`IndexMap`].
+use crate::{ConversionError, LuaMapKey, LuaTableEntry, LuaValue};
+use indexmap::IndexMap;
+
+/// A Lua table as an [`IndexMap`], for fast lookups by key.
+///
+/// [`LuaValue::Table`] is a [`Vec`] of entries in source order, which is compact and cheap to
+/// parse, but needs a linear scan to find a key. Converting a table into a `LuaIndexMap` (with
+/// [`LuaValue::try_into_index_map()`]) costs a little more memory, but gives `O(1)` lookups while
+/// still preserving the order of the entries.
+///
+/// Only the top level of the table is converted; nested tables are left as [`LuaValue`]s.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{lua_value, LuaIndexMap, LuaMapKey, LuaValue};
+///
+/// let m: LuaIndexMap = lua_value(br#"{"a", "b", name = "c", [2] = "d"}"#, 16)
+///     .unwrap()
+///     .try_into_index_map()
+///     .unwrap();
+///
+/// assert_eq!(Some(&LuaValue::from("c")), m.get(&LuaMapKey::from("name")));
+///
+/// // Later entries overwrite earlier entries, but keep their position.
+/// assert_eq!(
+///     vec![
+///         (LuaMapKey::Integer(1), LuaValue::from("a")),
+///         (LuaMapKey::Integer(2), LuaValue::from("d")),
+///         (LuaMapKey::from("name"), LuaValue::from("c")),
+///     ],
+///     m.clone().into_iter().collect::<Vec<_>>(),
+/// );
+///
+/// // Convert back into a LuaValue
+/// assert_eq!(
+///     lua_value(br#"{[1] = "a", [2] = "d", name = "c"}"#, 16).unwrap(),
+///     LuaValue::from(m),
+/// );
+/// ```
+pub type LuaIndexMap<'a> = IndexMap<LuaMapKey, LuaValue<'a>>;
+
+impl<'a> LuaValue<'a> {
+    /// Converts the value into a [`LuaIndexMap`], if it is [a table][LuaValue::Table].
+    ///
+    /// Keys are normalised like [`LuaMapKey`], so `[1.0]` and `[1]` are the same key. Implicit
+    /// keys are numbered from `1`, like Lua.
+    ///
+    /// [Like Serde][crate#duplicate-table-keys-in-serde], later entries overwrite earlier entries
+    /// with the same key, but the entry keeps the position of the first occurrence.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if the value is not a table, or the table has a `nil` or table key.
+    pub fn try_into_index_map(self) -> Result<LuaIndexMap<'a>, ConversionError> {
+        let entries = self.try_into_table()?;
+        let mut map = IndexMap::with_capacity(entries.len());
+        let mut next_index = 1;
+
+        for entry in entries {
+            let (key, value) = match entry {
+                LuaTableEntry::NameValue(b) => {
+                    let (k, v) = *b;
+                    (LuaMapKey::String(k.into_owned()), v)
+                }
+                LuaTableEntry::KeyValue(b) => {
+                    let (k, v) = *b;
+                    let k = LuaMapKey::try_from(k).map_err(|k| {
+                        ConversionError::new("boolean, number or string", k.conversion_type())
+                    })?;
+                    (k, v)
+                }
+                entry => {
+                    let i = next_index;
+                    next_index += 1;
+                    (LuaMapKey::Integer(i), entry.move_value())
+                }
+            };
+
+            map.insert(key, value);
+        }
+
+        Ok(map)
+    }
+}
+
+impl<'a> From<LuaIndexMap<'a>> for LuaValue<'a> {
+    /// Converts a [`LuaIndexMap`] into a [`LuaValue::Table`], with entries in the same order.
+    ///
+    /// String keys which are valid Lua identifiers are converted to
+    /// [`LuaTableEntry::NameValue`], and all other keys to [`LuaTableEntry::KeyValue`].
+    fn from(map: LuaIndexMap<'a>) -> Self {
+        map.into_iter()
+            .map(|(k, v)| match k {
+                LuaMapKey::String(k) => LuaTableEntry::from((k, v)),
+                k => LuaTableEntry::KeyValue(Box::new((k.into(), v))),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{lua_value, LuaNumber};
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+    fn index_map() {
+        let m = lua_value(
+            br#"{[true] = 1, 2, [1.0] = 3, [1.5] = 4, ["\xff"] = 5, ["a b"] = 6}"#,
+            16,
+        )
+        .unwrap()
+        .try_into_index_map()
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                (LuaMapKey::Bool(true), LuaValue::integer(1)),
+                (LuaMapKey::Integer(1), LuaValue::integer(3)),
+                (LuaMapKey::Float(1.5), LuaValue::integer(4)),
+                (LuaMapKey::Bytes(b"\xff".to_vec()), LuaValue::integer(5)),
+                (LuaMapKey::from("a b"), LuaValue::integer(6)),
+            ],
+            m.clone().into_iter().collect::<Vec<_>>(),
+        );
+
+        assert_eq!(
+            LuaValue::Table(vec![
+                LuaTableEntry::KeyValue(Box::new((true.into(), LuaValue::integer(1)))),
+                LuaTableEntry::KeyValue(Box::new((1.into(), LuaValue::integer(3)))),
+                LuaTableEntry::KeyValue(Box::new((
+                    LuaNumber::Float(1.5).into(),
+                    LuaValue::integer(4)
+                ))),
+                LuaTableEntry::KeyValue(Box::new((b"\xff".into(), LuaValue::integer(5)))),
+                LuaTableEntry::KeyValue(Box::new((b"a b".into(), LuaValue::integer(6)))),
+            ]),
+            LuaValue::from(m),
+        );
+    }
+
+    #[test]
+    #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+    fn errors() {
+        let e = LuaValue::integer(1).try_into_index_map().unwrap_err();
+        assert_eq!(ConversionError::new("table", "integer"), e);
+
+        let e = lua_value(b"{[{}] = 1}", 16)
+            .unwrap()
+            .try_into_index_map()
+            .unwrap_err();
+        assert_eq!(
+            ConversionError::new("boolean, number or string", "table"),
+            e
+        );
+    }
+}
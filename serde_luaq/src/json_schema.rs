@@ -0,0 +1,306 @@
+//! JSON Schema generation from observed [`LuaValue`] structure.
+use crate::{JsonConversionError, JsonConversionOptions, LuaNumber, LuaTableEntry, LuaValue};
+use serde_json::{json, Map as JsonMap, Value as JsonValue};
+use std::{borrow::Borrow, str::from_utf8};
+
+/// [JSON Schema dialect][0] used by [`json_schema()`].
+///
+/// [0]: https://json-schema.org/draft/2020-12/schema
+const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Generates a [JSON Schema][0] describing the structure of a [`LuaValue`], as it would be
+/// [converted to JSON][crate::to_json_value].
+///
+/// This can be used to derive a schema from a known-good file, and then validate other files
+/// (such as user uploads) with generic JSON Schema tooling.
+///
+/// Tables are described the same way that [`to_json_value()`][crate::to_json_value] converts
+/// them:
+///
+/// * A table containing _only_ implicitly-keyed entries is an `array`, and its `items` schema
+///   describes every entry.
+///
+/// * Any other table (including an empty table) is an `object`. Every key is listed in
+///   `properties` and `required`, with the same key conversion rules.
+///
+/// When an array's entries have different structures, their schemas are merged:
+///
+/// * integers and floats are a `number`
+/// * objects have the union of all `properties`, and only `required` the keys present in _every_
+///   entry
+/// * arrays have merged `items`
+/// * values which can't be merged (like a `string` and a `boolean`) are listed in `anyOf`
+///
+/// Integer values are `integer`, and floats are `number`.
+///
+/// ## Errors
+///
+/// This returns an error in the same cases as [`to_json_value()`][crate::to_json_value], except
+/// that non-finite floats are accepted (and described as `number`).
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_json::json;
+/// use serde_luaq::{json_schema, lua_value, JsonConversionOptions};
+///
+/// let v = lua_value(br#"{
+///     name = "alice",
+///     items = {{id = 1, count = 2}, {id = 2, note = "cursed"}},
+/// }"#, 16).unwrap();
+///
+/// assert_eq!(
+///     json!({
+///         "$schema": "https://json-schema.org/draft/2020-12/schema",
+///         "type": "object",
+///         "properties": {
+///             "name": {"type": "string"},
+///             "items": {
+///                 "type": "array",
+///                 "items": {
+///                     "type": "object",
+///                     "properties": {
+///                         "id": {"type": "integer"},
+///                         "count": {"type": "integer"},
+///                         "note": {"type": "string"},
+///                     },
+///                     "required": ["id"],
+///                 },
+///             },
+///         },
+///         "required": ["name", "items"],
+///     }),
+///     json_schema(&v, JsonConversionOptions::default()).unwrap(),
+/// );
+/// ```
+///
+/// [0]: https://json-schema.org/
+pub fn json_schema(
+    value: &LuaValue<'_>,
+    opts: impl Borrow<JsonConversionOptions>,
+) -> Result<JsonValue, JsonConversionError> {
+    let mut o = match Schema::of(value, opts.borrow())?.into_json() {
+        JsonValue::Object(o) => o,
+        _ => unreachable!(),
+    };
+
+    let mut schema = JsonMap::with_capacity(o.len() + 1);
+    schema.insert("$schema".to_string(), JsonValue::from(SCHEMA_DIALECT));
+    schema.append(&mut o);
+    Ok(JsonValue::Object(schema))
+}
+
+/// Observed structure of a value.
+#[derive(Debug, Clone, PartialEq)]
+enum Schema {
+    Null,
+    Boolean,
+    Integer,
+    Number,
+    String,
+    Array(Box<Schema>),
+    /// Object properties, in the order they were first seen, and whether they are required.
+    Object(Vec<(String, Schema, bool)>),
+    AnyOf(Vec<Schema>),
+}
+
+impl Schema {
+    fn of(value: &LuaValue<'_>, opts: &JsonConversionOptions) -> Result<Self, JsonConversionError> {
+        Ok(match value {
+            LuaValue::Nil => Self::Null,
+            LuaValue::Boolean(_) => Self::Boolean,
+            LuaValue::Number(n) => Self::of_number(n),
+            LuaValue::String(s) => {
+                if !opts.lossy_string {
+                    from_utf8(s)?;
+                }
+                Self::String
+            }
+            LuaValue::Table(entries) => Self::of_table(entries, opts)?,
+        })
+    }
+
+    const fn of_number(n: &LuaNumber) -> Self {
+        match n {
+            LuaNumber::Integer(_) => Self::Integer,
+            LuaNumber::Float(_) => Self::Number,
+        }
+    }
+
+    fn of_table(
+        entries: &[LuaTableEntry<'_>],
+        opts: &JsonConversionOptions,
+    ) -> Result<Self, JsonConversionError> {
+        let explicit = entries
+            .iter()
+            .any(|e| matches!(e, LuaTableEntry::KeyValue(_) | LuaTableEntry::NameValue(_)));
+
+        if !explicit && !entries.is_empty() {
+            let mut items: Option<Schema> = None;
+            for entry in entries {
+                let s = Self::of_entry_value(entry, opts)?;
+                items = Some(match items {
+                    None => s,
+                    Some(items) => items.merge(s),
+                });
+            }
+
+            return Ok(Self::Array(Box::new(items.unwrap_or(Self::Null))));
+        }
+
+        // Lua keys implicitly-keyed entries with consecutive integers starting at 1, and later
+        // entries replace earlier ones.
+        let mut properties: Vec<(String, Schema, bool)> = Vec::new();
+        let mut next_idx: i64 = 1;
+        for entry in entries {
+            let key = match entry {
+                LuaTableEntry::KeyValue(b) => match &b.0 {
+                    LuaValue::String(k) => {
+                        if opts.lossy_string {
+                            String::from_utf8_lossy(k).into_owned()
+                        } else {
+                            from_utf8(k)?.to_string()
+                        }
+                    }
+                    LuaValue::Nil => "nil".to_string(),
+                    LuaValue::Boolean(k) => k.to_string(),
+                    LuaValue::Number(k) => k.to_string(),
+                    LuaValue::Table(_) => return Err(JsonConversionError::TableKeyedWithTable),
+                },
+                LuaTableEntry::NameValue(b) => b.0.to_string(),
+                _ => {
+                    next_idx += 1;
+                    (next_idx - 1).to_string()
+                }
+            };
+
+            let value = Self::of_entry_value(entry, opts)?;
+            match properties.iter_mut().find(|(k, _, _)| *k == key) {
+                Some((_, v, _)) => *v = value,
+                None => properties.push((key, value, true)),
+            }
+        }
+
+        Ok(Self::Object(properties))
+    }
+
+    fn of_entry_value(
+        entry: &LuaTableEntry<'_>,
+        opts: &JsonConversionOptions,
+    ) -> Result<Self, JsonConversionError> {
+        match entry {
+            LuaTableEntry::KeyValue(b) => Self::of(&b.1, opts),
+            LuaTableEntry::NameValue(b) => Self::of(&b.1, opts),
+            LuaTableEntry::Value(v) => Self::of(v, opts),
+            LuaTableEntry::NumberValue(n) => Ok(Self::of_number(n)),
+            LuaTableEntry::BooleanValue(_) => Ok(Self::Boolean),
+            LuaTableEntry::NilValue => Ok(Self::Null),
+        }
+    }
+
+    /// Merges two schemas, such that the result describes values matching either of them.
+    fn merge(self, other: Self) -> Self {
+        let mut alternatives = match self {
+            Self::AnyOf(a) => a,
+            s => vec![s],
+        };
+        let others = match other {
+            Self::AnyOf(a) => a,
+            s => vec![s],
+        };
+
+        for other in others {
+            match alternatives.iter().position(|s| s.mergeable(&other)) {
+                Some(i) => {
+                    let s = std::mem::replace(&mut alternatives[i], Self::Null);
+                    alternatives[i] = s.merge_same(other);
+                }
+                None => alternatives.push(other),
+            }
+        }
+
+        if alternatives.len() == 1 {
+            alternatives.pop().unwrap()
+        } else {
+            Self::AnyOf(alternatives)
+        }
+    }
+
+    /// Returns `true` if `self` and `other` can be merged into a single (non-`anyOf`) schema.
+    fn mergeable(&self, other: &Self) -> bool {
+        use Schema::*;
+        matches!(
+            (self, other),
+            (Null, Null)
+                | (Boolean, Boolean)
+                | (Integer | Number, Integer | Number)
+                | (String, String)
+                | (Array(_), Array(_))
+                | (Object(_), Object(_))
+        )
+    }
+
+    /// Merges two [mergeable][Self::mergeable] schemas.
+    fn merge_same(self, other: Self) -> Self {
+        use Schema::*;
+        match (self, other) {
+            (Integer, Integer) => Integer,
+            (Integer | Number, Integer | Number) => Number,
+            (Array(a), Array(b)) => Array(Box::new(a.merge(*b))),
+            (Object(mut a), Object(b)) => {
+                // Keys are only required if they're required on both sides.
+                for (k, _, required) in a.iter_mut() {
+                    *required &= b.iter().any(|(bk, _, _)| bk == k);
+                }
+
+                for (k, v, required) in b {
+                    match a.iter_mut().find(|(ak, _, _)| *ak == k) {
+                        Some((_, av, a_required)) => {
+                            *av = std::mem::replace(av, Null).merge(v);
+                            *a_required &= required;
+                        }
+                        None => a.push((k, v, false)),
+                    }
+                }
+
+                Object(a)
+            }
+            (a, _) => a,
+        }
+    }
+
+    fn into_json(self) -> JsonValue {
+        match self {
+            Self::Null => json!({"type": "null"}),
+            Self::Boolean => json!({"type": "boolean"}),
+            Self::Integer => json!({"type": "integer"}),
+            Self::Number => json!({"type": "number"}),
+            Self::String => json!({"type": "string"}),
+            Self::Array(items) => json!({"type": "array", "items": items.into_json()}),
+            Self::Object(properties) => {
+                let required: Vec<JsonValue> = properties
+                    .iter()
+                    .filter(|(_, _, required)| *required)
+                    .map(|(k, _, _)| JsonValue::from(k.as_str()))
+                    .collect();
+                let properties: JsonMap<String, JsonValue> = properties
+                    .into_iter()
+                    .map(|(k, v, _)| (k, v.into_json()))
+                    .collect();
+
+                let mut o = JsonMap::new();
+                o.insert("type".to_string(), JsonValue::from("object"));
+                if !properties.is_empty() {
+                    o.insert("properties".to_string(), JsonValue::Object(properties));
+                }
+                if !required.is_empty() {
+                    o.insert("required".to_string(), JsonValue::Array(required));
+                }
+                JsonValue::Object(o)
+            }
+            Self::AnyOf(alternatives) => json!({
+                "anyOf": alternatives.into_iter().map(Self::into_json).collect::<Vec<_>>(),
+            }),
+        }
+    }
+}
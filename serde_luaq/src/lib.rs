@@ -1,13 +1,15 @@
-//! > **Note:** this library is still a work in progress, and there are no API stability guarantees.
+//! > **Note:** this library is still a work in progress, and there are no API stability guarantees
+//! > yet. See [API stability](#api-stability) for how the API is structured to minimise breaking
+//! > changes.
 //!
-//! `serde_luaq` is a library for deserialising (and eventually, serialising) simple, JSON-like data
-//! structures from Lua 5.4 source code, _without requiring Lua itself_.
+//! `serde_luaq` is a library for deserialising (and serialising) simple, JSON-like data structures
+//! from Lua 5.4 source code, _without requiring Lua itself_.
 //!
 //! The goal is to safely read state from software (mostly games) which is serialised using
 //! [Lua `%q` formatting][format] (and similar techniques)
 //! [_without_ allowing arbitrary code execution](#security).
 //!
-//! This library consists of four parts:
+//! This library consists of:
 //!
 //! - A [`LuaValue`] `enum`, which describes Lua's basic data types ([`nil`][LuaValue::Nil],
 //!   [boolean][LuaValue::Boolean], [string][LuaValue::String], [number][LuaValue::Number],
@@ -20,8 +22,53 @@
 //! - A [Serde]-based `Deserialize` implementation for
 //!   [converting a `LuaValue`][from_slice] into your own data types.
 //!
+//! - A [Serde]-based `Serializer` for [writing your own data types][to_string] (or a `LuaValue`)
+//!   as Lua source code, in the same form as Lua's `%q` formatting, or in your own style with a
+//!   [`Formatter`].
+//!
+//! - A [compact binary encoding][to_binary] of parsed values, for caching large files between
+//!   runs without re-parsing them.
+//!
 //! - _Optional_ lossy [converter to][to_json_value] and [from][from_json_value] `serde_json`'s
-//!   `Value` type.
+//!   `Value` type, and [JSON Schema generation][json_schema] from a value's structure.
+//!
+//! - _Optional_ [`bincode`](https://docs.rs/bincode/2) encoding of parsed values
+//!   (`to_bincode()` and `from_bincode()`, with the `bincode` feature), for caching large files.
+//!
+//! - _Optional_ CSV export of tables of records (`to_csv()`, with the `csv` feature), for
+//!   analysing game data in a spreadsheet.
+//!
+//! - _Optional_ atomic replacement of files with serialised values (`write_atomic()`, with the
+//!   `tempfile` feature), so a crash while saving doesn't corrupt the file.
+//!
+//! - _Optional_ conversion of tables to and from [`IndexMap`](https://docs.rs/indexmap/2)
+//!   (`LuaIndexMap`, with the `indexmap` feature), for fast lookups by key.
+//!
+//! - _Optional_ decoding of strings in legacy encodings like Windows-1252
+//!   (`LuaValue::as_str_with_encoding()` and `windows_1252::deserialize()`, with the `encoding`
+//!   feature), for older games which don't use UTF-8.
+//!
+//! - _Optional_ parallel deserialisation of many files (`par_from_files()`, with the `rayon`
+//!   feature), for analysing thousands of save files.
+//!
+//! - _Optional_ [`Arbitrary`](https://docs.rs/arbitrary/1) implementations for `LuaValue`,
+//!   `LuaTableEntry` and `LuaNumber` (with the `arbitrary` feature), for property testing.
+//!
+//! - _Optional_ C ABI for converting Lua to JSON (the `ffi` module, with the `ffi` feature), for
+//!   tools written in other languages.
+//!
+//! - _Optional_ Unicode normalisation and case folding of struct field names
+//!   (`DeserializeOptions::unicode_fields`, with the `unicode` feature), for files written by
+//!   different operating systems and editors.
+//!
+//! - _Optional_ generated corpus of valid and invalid Lua scripts (`corpus()`, with the `corpus`
+//!   feature), for seeding fuzzers.
+//!
+//! - _Optional_ golden-file test harness (`assert_golden_dir()`, with the `golden` feature), for
+//!   checking a directory of Lua fixtures against JSON or `Debug` snapshots in downstream crates.
+//!
+//! - _Optional_ parser backtracking and recursion counters (`Parser::parse_with_metrics()`, with
+//!   the `metrics` feature), for finding inputs which make the parser slow.
 //!
 //! ## Examples
 //!
@@ -34,8 +81,16 @@
 //! assert_eq!(LuaValue::Boolean(true), lua_value(b"true", /* max table depth */ 16).unwrap());
 //! ```
 //!
-//! There are similar deserialisers for [a `return` statement][return_statement] and
-//! [scripts with one or more variable assignments][script].
+//! There are similar deserialisers for [a `return` statement][return_statement] (which may have
+//! [more than one value][return_values]) and [scripts with one or more variable assignments][script].
+//! Large scripts can also be parsed [one statement at a time][script_entries], to skip over
+//! variables which aren't needed, and giant tables can be [sampled][Parser::sample] without keeping
+//! every entry in memory.
+//!
+//! Editors and linters can [parse with diagnostics][parse_with_diagnostics] to get every error
+//! and warning about the input with its position, and as much of the value as could be parsed.
+//!
+//! To reuse the same options across many calls, build a [`Parser`] instead.
 //!
 //! [Maximum table depth limits are described in their own section](#maximum-table-depth).
 //!
@@ -76,8 +131,13 @@
 //! );
 //! ```
 //!
-//! It can also deserialise from [a `return` statement][LuaFormat::Return] or
-//! [script with one or more variable assignments][LuaFormat::Script].
+//! It can also deserialise from [a `return` statement][LuaFormat::Return],
+//! [script with one or more variable assignments][LuaFormat::Script], or
+//! [script with assignments followed by a `return` statement][LuaFormat::ScriptReturn].
+//!
+//! To deserialise a [`LuaValue`] which has already been parsed, use [`from_value()`]. To go the
+//! other way, [`to_value()`] converts any `Serialize` type into a [`LuaValue`], and
+//! [`to_string()`] writes it as Lua source code.
 //!
 //! [Maximum table depth limits are described in their own section](#maximum-table-depth).
 //!
@@ -97,8 +157,8 @@
 //!
 //! The [`peg` deserialisers](#peg-deserialiser) will always produce a [`LuaValue`][].
 //!
-//! However, [`LuaValue` doesn't implement `Deserialize`][LuaValue#serde], so can't be used as a
-//! Serde field.
+//! [`LuaValue<'static>` can also be used as a Serde field][LuaValue#serde], to capture a value
+//! which doesn't have a fixed structure.
 //!
 //! Generally speaking, `serde_luaq` tries to do whatever a default build of Lua 5.4 does,
 //! **except for**:
@@ -110,6 +170,15 @@
 //! Unicode identifiers (`LUA_UCID`) and other locale-specific identifiers are not supported, even
 //! if they would be valid in Rust.
 //!
+//! ### Booleans
+//!
+//! By default, [`bool`][] fields only accept Lua's `true` and `false` literals.
+//!
+//! Some software serialises booleans as the integers `0` and `1`, or as the strings `"true"` and
+//! `"false"`. These can be accepted with [`from_slice_with_options()`][] and
+//! [`DeserializeOptions::lenient_bool`][] or [`DeserializeOptions::lenient_bool_strings`][], rather
+//! than needing a wrapper type.
+//!
 //! ### Numbers
 //!
 //! `serde_luaq` follows Lua 5.4's number handling semantics, but _doesn't_ implement
@@ -137,12 +206,17 @@
 //! * Decimal integer literals _outside_ of the [`i64`][] range are converted to [`f64`][], and will
 //!   lose precision. These cannot be used with [`i64`][] fields.
 //!
+//!   The only exception is [`ParseOptions::unsigned_integers`][], which wraps decimal literals
+//!   from 2<sup>63</sup> to [`u64::MAX`][] around to negative [`i64`][] values, like hexadecimal
+//!   literals.
+//!
 //! * Hexadecimal integer literals are always coerced to [`i64`][], and can _always_ be used with
 //!   [`i64`][] fields. Values _outside_ of the [`i64`][] range will _only_ under/overflow as
 //!   [`i64`][], regardless of the field type.
 //!
 //!   This means the literal `0xffffffffffffffff` is always treated as if it were written `-1`, even
-//!   for [`f64`][], [`i8`][], and [`u64`][] fields. This would be an error for unsigned types.
+//!   for [`f64`][], [`i8`][], and [`u64`][] fields. This would be an error for unsigned types,
+//!   unless [`DeserializeOptions::unsigned_integers`][] is set.
 //!
 //! * Hexadecimal float literals with more than 16 hex digits will not parse, due to a limitation of
 //!   the parsing library `serde_luaq` uses.
@@ -166,6 +240,11 @@
 //! * Wider integer fields like [`i128`][] and [`u64`][] apply the same limits as [`i64`][], even
 //!   with hexadecimal integer literals.
 //!
+//!   [`DeserializeOptions::unsigned_integers`] allows [`u64`][] fields to use the full range of
+//!   [`u64`][], by reading negative [`i64`][] values as the [`u64`][] with the same bits. Values
+//!   are still parsed as [`i64`][], so [`i64`][] fields read `0xffffffffffffffff` and
+//!   `18446744073709551615` as `-1`, and `0x8000000000000000` as [`i64::MIN`][].
+//!
 //! ### Strings
 //!
 //! Lua strings are "8-bit clean", and can contain *any* 8-bit value (ie: `[u8]`).
@@ -178,7 +257,8 @@
 //! Lua's `\u{...}` escapes follow [RFC 2279][] (1998) rather than [RFC 3629][] (2003). RFC 2279
 //! differs by allowing [surrogate code points][surrogate] and code points greater than
 //! `\u{10FFFF}`. `serde_luaq` will convert these escapes into bytes following RFC 2279, which might
-//! not be valid in RFC 3629.
+//! not be valid in RFC 3629. A [`Parser`] can instead
+//! [replace these escapes or reject them][InvalidUnicodeEscapes].
 //!
 //! Serde [`String`] fields can be used if the string literal evaluates to valid RFC 3629 UTF-8.
 //! This is not guaranteed even if [the input data is `&str`][self::from_str], as Lua string escapes
@@ -208,7 +288,7 @@
 //!
 //! Using duplicate table keys is undefined behaviour in Lua.
 //!
-//! However, when using `serde_luaq` with Serde, later entries always overwrite earlier entries,
+//! However, when using `serde_luaq` with Serde, later entries overwrite earlier entries,
 //! regardless of how they are defined, ie:
 //!
 //! ```lua
@@ -217,6 +297,27 @@
 //! { [1] = 1, 2 } == { 2 }
 //! ```
 //!
+//! The exceptions are deserialising a table as a [list of `(key, value)` pairs][pairs], which
+//! keeps every entry, and deserialising a table as a struct, where a duplicate field returns
+//! [`Error::DuplicateField`] with the location of the table:
+//!
+//! [pairs]: #tables-as-lists-of-key-value-pairs
+//!
+//! ```rust
+//! # use serde::Deserialize;
+//! # use serde_luaq::{Error, LuaFormat, from_slice};
+//! #[derive(Deserialize, Debug)]
+//! struct Point { x: i64 }
+//!
+//! #[derive(Deserialize, Debug)]
+//! struct Path { points: Vec<Point> }
+//!
+//! let e = from_slice::<Path>(b"{points = {{x = 1, ['x'] = 2}}}", LuaFormat::Value, 16)
+//!     .unwrap_err();
+//! assert_eq!("points[1]: duplicate field `x`", e.to_string());
+//! assert_eq!(Some("points[1]"), e.path());
+//! ```
+//!
 //! #### Tables as lists in Serde (Vec)
 //!
 //! Lua tables are 1-indexed, rather than 0-indexed. `serde_luaq` will handle these differences on
@@ -269,6 +370,17 @@
 //! # }
 //! ```
 //!
+//! Lua sequences start at index 1, so a table with a zero or negative integer key (such as
+//! `{[0] = "a", [1] = "b"}`) can't be deserialised as a [`Vec`] of values, and is treated as a map
+//! instead. This avoids silently re-numbering (or dropping) those entries.
+//!
+//! Tables written with `table.pack()`'s convention of an `n` field for the length
+//! (`{n = 3, "a", nil, nil}`) can be read with [`DeserializeOptions::sequence_n_field`][].
+//!
+//! Gaps in a sequence (and up to its `n` field) are filled with `nil`. To limit how much padding
+//! untrusted input can ask for, set [`DeserializeOptions::max_sequence_padding`][]; tables needing
+//! more padding are then an error.
+//!
 //! If you're working with a sparse table, it's probably better to handle it as a map (see below).
 //! This works everywhere but as a [flattened field's map value type](#flattening).
 //!
@@ -297,6 +409,9 @@
 //!
 //! Otherwise, all entries must be explicitly keyed.
 //!
+//! To accept keys of any type (including floats, booleans and a mix of types), use [`LuaMapKey`]
+//! as the map's key type.
+//!
 //! For maps, `serde_luaq` treats "entry present and set to `nil`" and "entry not present" as
 //! distinct states. This means unless a key or value uses an [`Option`][] type, it must not contain
 //! `nil`:
@@ -320,6 +435,35 @@
 //! # }
 //! ```
 //!
+//! Tables which are used as sets (`{apple = true, banana = true}`) can be read into a
+//! [`HashSet`][std::collections::HashSet] or [`BTreeSet`][std::collections::BTreeSet] field with
+//! [`#[serde(with = "serde_luaq::set_as_table")]`][set_as_table].
+//!
+//! #### Tables as lists of (key, value) pairs
+//!
+//! A table which isn't a sequence (because it has a key which isn't a positive integer) can be
+//! deserialised as a list of `(key, value)` pairs, like `Vec<(K, V)>`. Unlike a map, this keeps
+//! every entry in the order they were written, including duplicate keys:
+//!
+//! ```rust
+//! # use serde_luaq::{Error, LuaFormat, from_slice};
+//! # fn main() -> Result<(), Error> {
+//! let a: Vec<(String, i64)> = from_slice(b"{a = 1, b = 2, a = 3}", LuaFormat::Value, 16)?;
+//! assert_eq!(
+//!     vec![("a".to_string(), 1), ("b".to_string(), 2), ("a".to_string(), 3)],
+//!     a,
+//! );
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Implicit keys are numbered [like a map](#tables-as-maps-in-serde-btreemaphashmap). Each pair
+//! can be deserialised as anything which accepts a sequence of two elements, like a tuple or a
+//! struct with two fields.
+//!
+//! Tables which _are_ sequences are always deserialised as a list of values, so `{[1] = "a"}`
+//! can't be read this way.
+//!
 //! #### Tables as structs
 //!
 //! When deserialising a table as a `struct`, all keys must be written as valid
@@ -375,6 +519,22 @@
 //! This is because Serde tries to handle these as an "any" type, and this library forces anything
 //! that looks like an array or sparse array to be treated as an array.
 //!
+//! #### Unknown fields
+//!
+//! [`#[serde(deny_unknown_fields)]`][deny] makes unknown table keys an error.
+//!
+//! Errors in values nested inside tables are wrapped in [`Error::Path`], which describes where the
+//! error occurred (eg: `player.inventory[2]`).
+//!
+//! To preserve unknown fields instead, flatten them into an [`UnknownFields`][] field.
+//!
+//! [deny]: https://serde.rs/container-attrs.html#deny_unknown_fields
+//!
+//! #### Transforming values
+//!
+//! Values which need converting before Serde can deserialise them (like `"x,y,z"` strings) can be
+//! rewritten by field name or path with [`DeserializeOptions::transforms`][].
+//!
 //! ### Enums
 //!
 //! When deserialising, `enum`s may be represented multiple ways:
@@ -466,6 +626,27 @@
 //! read the same data structures, on a [`LuaValue`][] level (not Serde). If it doesn't, that's a
 //! bug. :)
 //!
+//! Scripts can be limited to a number of statements with [`ParseOptions::max_statements`][], and
+//! identifiers to a length with [`ParseOptions::max_identifier_length`][].
+//!
+//! Services which need to bound the time spent on each input can set a [`CancellationToken`][]
+//! with a deadline in [`ParseOptions::cancellation`][] or
+//! [`DeserializeOptions::cancellation`][].
+//!
+//! `serde_luaq` is written entirely in safe Rust, and doesn't call into C libraries to parse
+//! numbers. The only `unsafe` code is the optional C ABI in the `ffi` module, which must
+//! dereference the pointers it is given. Without the `ffi` feature, the crate is built with
+//! `#![forbid(unsafe_code)]`.
+//!
+//! Scripts can be parsed one statement at a time with [`script_iter()`][], so an async task can
+//! yield to its executor between statements. There's no async API, and parsing can't be suspended
+//! _within_ a statement: a bare value, a `return` statement, a single large assignment, and Serde
+//! deserialisation of a parsed value each run to completion once started.
+//!
+//! Async services should parse large inputs of those kinds on a thread which is allowed to block
+//! (like with `tokio::task::spawn_blocking()`), and use a [`CancellationToken`][] to stop parsing
+//! if the task is dropped or times out.
+//!
 //! ## Maximum table depth
 //!
 //! The `max_depth` argument controls how deeply nested a table can be before being rejected by
@@ -497,12 +678,23 @@
 //! **Warning:** setting `max_depth` too high allows a heavily-nested table to cause your program
 //! [to overflow its stack and crash][stackoverflow].
 //!
-//! What is "too high" depends on your platform and where you call `serde_luaq` in your program.
+//! What is "too high" depends on your platform, how your program is built, and where you call
+//! `serde_luaq` in your program.
 //!
 //! Setting `max_depth` to `0` disables support for tables, _even empty tables_.
 //!
 //! </div>
 //!
+//! [`RECOMMENDED_MAX_DEPTH_MAIN_THREAD`] and [`RECOMMENDED_MAX_DEPTH_SPAWNED_THREAD`] are safe
+//! values for a thread with the default stack size, on every supported platform, even in debug
+//! builds. They are tested to parse (and drop) a table nested that deeply with [`lua_value()`],
+//! [`return_statement()`] and [`script()`], on a thread with 1 MiB and 2 MiB of stack
+//! respectively.
+//!
+//! These only cover the parser itself, starting from a shallow call stack. Serde recurses through
+//! nested values with much more stack for each level, so use
+//! [`DeserializeOptions::recursion_limit`] to limit that separately.
+//!
 //! ## Memory usage
 //!
 //! Unless otherwise noted, all memory usage estimates assume a 64-bit target CPU.
@@ -518,6 +710,9 @@
 //! Heap-allocated variants of these `enum`s (those with [`Cow`][std::borrow::Cow] or [`Vec`][]
 //! fields) use more memory.
 //!
+//! [`LuaValue::heap_size()`][] returns the actual amount of heap memory used by a parsed value,
+//! which can be used to enforce a memory quota after parsing.
+//!
 //! ### Large data structures
 //!
 //! At present, the highest-known memory usage per byte of input Lua is a table of deeply-nested
@@ -529,6 +724,10 @@
 //!
 //! Lua uses similar amounts of memory for such data structures.
 //!
+//! By default, each table's [`Vec`][] grows as its entries are parsed, so may have up to twice as
+//! much capacity as it needs. [`TableCapacity::Count`][] counts the entries first, and reserves
+//! exactly the right amount.
+//!
 //! When deserialising into your own data structures with Serde, be mindful that some Rust data
 //! structures can use **significant amounts of memory** if you're not careful. Check out
 //! [the Rust performance book][rust-perf] for tips.
@@ -574,7 +773,8 @@
 //!
 //!   Lua 5.1 and earlier allows locale-dependent letters.
 //!
-//! * `serde_luaq` does not allow `goto` as an identifier name.
+//! * `serde_luaq` does not allow `goto` as an identifier name, unless parsing with
+//!   [`LuaDialect::Lua51`].
 //!
 //!   This is not a reserved keyword in Lua 5.1 and earlier.
 //!
@@ -591,11 +791,47 @@
 //!
 //! None of these features are supported by `serde_luaq`.
 //!
+//! ### Garry's Mod (GLua)
+//!
+//! **GLua** allows C-style `// line` and `/* block */` comments (as well as Lua's `--` comments),
+//! which can be parsed with [`LuaDialect::GLua`].
+//!
+//! Its other syntax extensions (like `!=`, `&&` and `continue`) aren't supported by
+//! `serde_luaq`.
+//!
 //! ### Ravi
 //!
 //! **Ravi** adds type annotations and some other language features, which aren't supported by
 //! `serde_luaq`.
 //!
+//! ## API stability
+//!
+//! This library is still working towards a 1.0 release, but its public types are structured so
+//! that new features can be added without breaking changes:
+//!
+//! - Error types ([`Error`], [`JsonConversionError`], [`MigrationError`]...) and option `enum`s
+//!   ([`LuaFormat`], [`InvalidUnicodeEscapes`], [`BareIdentifiers`], [`LuaDialect`]...) are
+//!   `#[non_exhaustive]`, so `match` statements need a wildcard arm.
+//!
+//! - Options `struct`s ([`ParseOptions`], [`DeserializeOptions`], [`JsonConversionOptions`]...)
+//!   are `#[non_exhaustive]`, so can't be created with a `struct` expression. Create them with
+//!   `new()` (or [`Default`]) and a chain of setters, or [`ParserBuilder`] for [`ParseOptions`]:
+//!
+//!   ```rust
+//!   use serde_luaq::{DeserializeOptions, Parser};
+//!
+//!   let opts = DeserializeOptions::new().lenient_bool(true);
+//!   let parse_opts = Parser::builder().max_depth(32).max_statements(100).build_options();
+//!   ```
+//!
+//!   New options always default to the existing behaviour.
+//!
+//! - Report `struct`s ([`DedupStats`], [`ConversionError`]...) are `#[non_exhaustive]`, so may
+//!   gain new fields.
+//!
+//! - The data model ([`LuaValue`], [`LuaNumber`], [`LuaTableEntry`], [`ScriptStatement`]) is
+//!   exhaustive, so it can be matched and constructed directly. Changing it is a breaking change.
+//!
 //! [comma]: https://github.com/lua/lua/blob/104b0fc7008b1f6b7d818985fbbad05cd37ee654/testes/literals.lua#L298-L300
 //! [CWE-95]: https://cwe.mitre.org/data/definitions/95.html
 //! [empty-statements]: https://www.lua.org/manual/5.1/manual.html#2.4.1
@@ -617,28 +853,147 @@
 //! [RFC 2279]: https://www.rfc-editor.org/rfc/rfc2279
 //! [RFC 3629]: https://www.rfc-editor.org/rfc/rfc3629
 //! [stackoverflow]: https://github.com/rust-lang/rust/issues/79935
+#![cfg_attr(not(feature = "ffi"), forbid(unsafe_code))]
+#![cfg_attr(feature = "ffi", deny(unsafe_code))]
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+#[cfg(feature = "tempfile")]
+mod atomic;
+mod binary;
+#[cfg(feature = "bincode")]
+mod bincode;
+#[cfg(feature = "rayon")]
+mod bulk;
+mod capture;
+#[cfg(feature = "corpus")]
+mod corpus;
+#[cfg(feature = "csv")]
+mod csv;
 mod de;
+mod diagnostics;
+#[cfg(feature = "encoding")]
+mod encoding;
 mod error;
+#[cfg(feature = "ffi")]
+#[allow(unsafe_code)]
+pub mod ffi;
+mod footer;
+#[cfg(feature = "golden")]
+mod golden;
+#[cfg(feature = "indexmap")]
+mod index_map;
+#[cfg(feature = "serde_json")]
+mod json_schema;
+mod lua_table;
+mod map_key;
+mod metrics;
+mod migrate;
 mod number;
+mod parser;
 mod peg_parser;
+mod progress;
+mod roundtrip;
+mod salvage;
+mod sample;
+mod script;
+mod ser;
 #[cfg(feature = "serde_json")]
 mod serde_json;
+mod set;
+mod table;
 mod table_entry;
+mod transform;
+#[cfg(feature = "unicode")]
+mod unicode;
 mod value;
 
 pub use crate::{
-    de::{from_slice, from_str, LuaFormat},
-    error::{Error, Result},
+    binary::{from_binary, to_binary, write_binary},
+    capture::UnknownFields,
+    de::{
+        from_slice, from_slice_with_options, from_str, from_str_with_options, from_value,
+        from_value_with_options, DeserializeOptions, LuaFormat,
+    },
+    diagnostics::{parse_with_diagnostics, Diagnostic, DiagnosticKind, Severity},
+    error::{BinaryError, ConversionError, Error, MigrationError, Result, SyntaxError},
+    footer::FooterCheck,
+    lua_table::LuaTable,
+    map_key::LuaMapKey,
+    migrate::Migrations,
     number::LuaNumber,
-    peg_parser::lua::{lua_value, return_statement, script},
+    parser::{
+        lua_value, lua_value_from_chunks, return_statement, return_values, script, script_entries,
+        unescape_long_string, unescape_short_string, BareIdentifiers, CallSubstitutions,
+        InvalidUnicodeEscapes, LuaDialect, ParseOptions, Parser, ParserBuilder, TableCapacity,
+        RECOMMENDED_MAX_DEPTH_MAIN_THREAD, RECOMMENDED_MAX_DEPTH_SPAWNED_THREAD,
+    },
+    progress::{CancellationToken, Progress},
+    roundtrip::{check_roundtrip, RoundTripDifference, RoundTripReason, RoundTripReport},
+    salvage::Salvaged,
+    sample::{Sample, Sampling},
+    script::{
+        script_iter, script_return_with_options, script_with_options, ScriptIter, ScriptReturn,
+        ScriptStatement,
+    },
+    ser::{
+        estimate_serialized_size, to_string, to_vec, to_writer, DefaultFormatter, FloatFormat,
+        Formatter, LineEnding, QuoteStyle, Serializer, DEFAULT_SERIALIZE_MAX_DEPTH,
+    },
+    set::set_as_table,
+    table::LuaTableExt,
     table_entry::LuaTableEntry,
-    value::LuaValue,
+    transform::Transforms,
+    value::{
+        to_value, DedupStats, LuaValue, LuaVisitor, OrdLuaValue, PathIter, SharedLuaValue,
+        SharedTableEntry, VisitAction,
+    },
 };
 
 #[cfg(feature = "serde_json")]
 pub use crate::{
     error::{JsonConversionError, LuaConversionError},
-    serde_json::{from_json_value, to_json_value, JsonConversionOptions},
+    json_schema::json_schema,
+    serde_json::{
+        from_json_value, to_json_value, to_json_value_with_diagnostics, DuplicateKeys,
+        JsonConversionOptions,
+    },
+};
+
+#[cfg(feature = "indexmap")]
+pub use crate::index_map::LuaIndexMap;
+
+#[cfg(feature = "metrics")]
+pub use crate::metrics::ParseMetrics;
+
+#[cfg(feature = "bincode")]
+pub use crate::bincode::{from_bincode, to_bincode};
+
+#[cfg(feature = "encoding")]
+pub use crate::encoding::{decode_string, latin1, windows_1252};
+
+#[cfg(feature = "rayon")]
+pub use crate::{
+    bulk::{par_from_files, par_from_slices},
+    error::BulkError,
+};
+
+#[cfg(feature = "corpus")]
+pub use crate::corpus::{corpus, CorpusEntry, CORPUS_MAX_DEPTH};
+
+#[cfg(feature = "csv")]
+pub use crate::{
+    csv::{csv_columns, to_csv, CsvOptions},
+    error::CsvExportError,
+};
+
+#[cfg(feature = "tempfile")]
+pub use crate::atomic::{write_atomic, Fsync, WriteAtomicOptions};
+
+#[cfg(feature = "golden")]
+pub use crate::golden::{
+    assert_golden_dir, check_golden_dir, GoldenFailure, GoldenFailureKind, GoldenOptions,
+    GoldenReport, JsonDiff, LineDiff,
 };
 
 /// Sorted list of Lua keywords which cannot be used as field names in scripts.
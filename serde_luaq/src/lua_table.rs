@@ -0,0 +1,282 @@
+//! Normalised [tables][LuaValue::Table], with Lua's key semantics.
+use crate::{
+    table::{key_eq, HashKey},
+    ConversionError, LuaNumber, LuaTableEntry, LuaValue,
+};
+use std::{borrow::Cow, collections::HashMap};
+
+/// Number of implicitly-keyed entries which Lua's compiler collects before assigning them to the
+/// table (`LFIELDS_PER_FLUSH`).
+const FIELDS_PER_FLUSH: usize = 50;
+
+/// A Lua table, normalised to have each key at most once, split into an array part and a map part.
+///
+/// [`LuaValue::Table`] is a list of entries exactly as they were written, which may have implicit
+/// keys, duplicate keys and `nil` values. Converting it into a `LuaTable` (with
+/// [`LuaValue::try_into_lua_table()`] or [`LuaTable::from_entries()`]) gives the table which Lua
+/// would build from the same table constructor:
+///
+/// * Implicitly-keyed entries are numbered from `1`.
+/// * Floats with an exact integer value are converted to integers, so `[1.0]` and `[1]` are the
+///   same key.
+/// * If a key is assigned more than once, only the last assignment is kept.
+/// * Keys assigned `nil` are removed.
+///
+/// Lua's manual leaves the order of the assignments in a table constructor undefined. This follows
+/// the reference implementation of Lua 5.4, which assigns implicitly-keyed entries in batches of
+/// 50, after any explicitly-keyed entries between them. So in `{"a", [1] = "b"}`, `t[1]` is `"a"`.
+///
+/// The [array part][Self::array_part] holds the values of the keys `1`, `2`, `3`, and so on, up
+/// to the first missing key. Every other key is in the [map part][Self::map_part], in the order
+/// it was first assigned.
+///
+/// Only the top level of the table is normalised; nested tables are left as [`LuaValue`]s.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{lua_value, LuaTable, LuaValue};
+///
+/// let t: LuaTable = lua_value(br#"{"a", "b", name = "c", [3.0] = "d", [5] = "e", name = nil}"#, 16)
+///     .unwrap()
+///     .try_into_lua_table()
+///     .unwrap();
+///
+/// assert_eq!(
+///     &[LuaValue::from("a"), LuaValue::from("b"), LuaValue::from("d")],
+///     t.array_part(),
+/// );
+/// assert_eq!(&[(LuaValue::integer(5), LuaValue::from("e"))], t.map_part());
+/// assert_eq!(Some(&LuaValue::from("b")), t.get(&LuaValue::float(2.0)));
+/// assert_eq!(None, t.get(&LuaValue::from("name")));
+///
+/// // Convert back into a LuaValue
+/// assert_eq!(
+///     lua_value(br#"{"a", "b", "d", [5] = "e"}"#, 16).unwrap(),
+///     LuaValue::from(t),
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LuaTable<'a> {
+    array: Vec<LuaValue<'a>>,
+    map: Vec<(LuaValue<'a>, LuaValue<'a>)>,
+}
+
+impl<'a> LuaTable<'a> {
+    /// Creates an empty table.
+    pub const fn new() -> Self {
+        Self {
+            array: Vec::new(),
+            map: Vec::new(),
+        }
+    }
+
+    /// Builds a table from a list of table entries, in the same way as Lua's table constructor.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if an entry has a `nil` or NaN key, which Lua doesn't allow.
+    pub fn from_entries(entries: Vec<LuaTableEntry<'a>>) -> Result<Self, ConversionError> {
+        let order = assignment_order(&entries);
+        let mut implicit = 0;
+        let mut pairs = entries
+            .into_iter()
+            .map(|entry| {
+                let (key, value) = match entry {
+                    LuaTableEntry::NameValue(b) => {
+                        let (k, v) = *b;
+                        let k = match k {
+                            Cow::Borrowed(k) => Cow::Borrowed(k.as_bytes()),
+                            Cow::Owned(k) => Cow::Owned(k.into_bytes()),
+                        };
+                        (LuaValue::String(k), v)
+                    }
+                    LuaTableEntry::KeyValue(b) => match *b {
+                        (LuaValue::Nil, _) => {
+                            return Err(ConversionError::new("non-nil table key", "nil"))
+                        }
+                        (LuaValue::Number(n), _) if n.is_nan() => {
+                            return Err(ConversionError::new("non-NaN table key", "float"))
+                        }
+                        (LuaValue::Number(n), v) => (LuaValue::Number(n.normalise_key()), v),
+                        kv => kv,
+                    },
+                    entry => {
+                        implicit += 1;
+                        (LuaValue::integer(implicit), entry.move_value())
+                    }
+                };
+                Ok(Some((key, value)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Find which slot each entry is assigned to, in the order the slots are first assigned.
+        let mut slot_of = vec![0; pairs.len()];
+        let mut slots = 0;
+        {
+            let mut index = HashMap::with_capacity(pairs.len());
+            for &i in &order {
+                let (key, _) = pairs[i].as_ref().expect("entries are only taken once");
+                let slot = match HashKey::new(key) {
+                    Some(key) => *index.entry(key).or_insert(slots),
+                    // Every table constructor is a different table, and so a different key.
+                    None => slots,
+                };
+                if slot == slots {
+                    slots += 1;
+                }
+                slot_of[i] = slot;
+            }
+        }
+
+        let mut assigned: Vec<Option<(LuaValue<'a>, LuaValue<'a>)>> = vec![None; slots];
+        for i in order {
+            let (key, value) = pairs[i].take().expect("entries are only taken once");
+            match &mut assigned[slot_of[i]] {
+                Some((_, v)) => *v = value,
+                slot => *slot = Some((key, value)),
+            }
+        }
+
+        // Split the remaining keys into the array part and map part.
+        let mut positions = HashMap::new();
+        let mut map = Vec::with_capacity(assigned.len());
+        for (key, value) in assigned.into_iter().flatten() {
+            if matches!(value, LuaValue::Nil) {
+                continue;
+            }
+
+            if let LuaValue::Number(LuaNumber::Integer(i @ 1..)) = key {
+                positions.insert(i, map.len());
+            }
+            map.push(Some((key, value)));
+        }
+
+        let mut array = Vec::new();
+        while let Some(p) = positions.get(&(array.len() as i64 + 1)) {
+            let (_, value) = map[*p].take().expect("keys are unique");
+            array.push(value);
+        }
+
+        Ok(Self {
+            array,
+            map: map.into_iter().flatten().collect(),
+        })
+    }
+
+    /// Values of the keys `1`, `2`, `3`, and so on, up to (but not including) the first missing
+    /// key.
+    ///
+    /// These values are at the positions of their keys minus one.
+    pub fn array_part(&self) -> &[LuaValue<'a>] {
+        &self.array
+    }
+
+    /// Keys and values which aren't in the [array part][Self::array_part], in the order each key
+    /// was first assigned.
+    pub fn map_part(&self) -> &[(LuaValue<'a>, LuaValue<'a>)] {
+        &self.map
+    }
+
+    /// Looks up `key` in the table, like Lua's `t[key]`.
+    ///
+    /// Keys in the map part are compared [like `LuaTableExt`][crate::LuaTableExt], with a linear
+    /// scan.
+    pub fn get(&self, key: &LuaValue<'_>) -> Option<&LuaValue<'a>> {
+        if let LuaValue::Number(n) = key {
+            if let LuaNumber::Integer(i @ 1..) = n.normalise_key() {
+                if let Some(v) = usize::try_from(i - 1).ok().and_then(|i| self.array.get(i)) {
+                    return Some(v);
+                }
+            }
+        }
+
+        self.map
+            .iter()
+            .find(|(k, _)| key_eq(k, key))
+            .map(|(_, v)| v)
+    }
+
+    /// Returns the number of keys in the table.
+    pub fn len(&self) -> usize {
+        self.array.len() + self.map.len()
+    }
+
+    /// Returns `true` if the table has no keys.
+    pub fn is_empty(&self) -> bool {
+        self.array.is_empty() && self.map.is_empty()
+    }
+
+    /// Splits the table into its [array part][Self::array_part] and [map part][Self::map_part].
+    pub fn into_parts(self) -> (Vec<LuaValue<'a>>, Vec<(LuaValue<'a>, LuaValue<'a>)>) {
+        (self.array, self.map)
+    }
+
+    /// Converts the table back into a list of table entries.
+    ///
+    /// The array part is converted to implicitly-keyed entries. In the map part, string keys
+    /// which are valid Lua identifiers are converted to [`LuaTableEntry::NameValue`], and all
+    /// other keys to [`LuaTableEntry::KeyValue`].
+    pub fn into_entries(self) -> Vec<LuaTableEntry<'a>> {
+        let mut entries = Vec::with_capacity(self.len());
+        entries.extend(self.array.into_iter().map(LuaTableEntry::from));
+        entries.extend(self.map.into_iter().map(|(k, v)| match k {
+            LuaValue::String(k) => LuaTableEntry::from((k, v)),
+            k => LuaTableEntry::KeyValue(Box::new((k, v))),
+        }));
+        entries
+    }
+}
+
+/// Returns the indexes of `entries`, in the order Lua's table constructor assigns them.
+///
+/// Explicitly-keyed entries are assigned immediately. Implicitly-keyed entries are collected, and
+/// assigned when the next entry is reached after collecting [`FIELDS_PER_FLUSH`] of them, or at
+/// the end of the table.
+fn assignment_order(entries: &[LuaTableEntry<'_>]) -> Vec<usize> {
+    let mut order = Vec::with_capacity(entries.len());
+    let mut pending = Vec::with_capacity(FIELDS_PER_FLUSH);
+    for (i, entry) in entries.iter().enumerate() {
+        if pending.len() == FIELDS_PER_FLUSH {
+            order.append(&mut pending);
+        }
+
+        if entry.implicit_key() {
+            pending.push(i);
+        } else {
+            order.push(i);
+        }
+    }
+
+    order.append(&mut pending);
+    order
+}
+
+impl<'a> LuaValue<'a> {
+    /// Converts the value into a normalised [`LuaTable`], if it is [a table][LuaValue::Table].
+    ///
+    /// See [`LuaTable::from_entries()`].
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if the value is not a table, or the table has a `nil` or NaN key.
+    pub fn try_into_lua_table(self) -> Result<LuaTable<'a>, ConversionError> {
+        LuaTable::from_entries(self.try_into_table()?)
+    }
+}
+
+impl<'a> TryFrom<LuaValue<'a>> for LuaTable<'a> {
+    type Error = ConversionError;
+
+    fn try_from(value: LuaValue<'a>) -> Result<Self, Self::Error> {
+        value.try_into_lua_table()
+    }
+}
+
+impl<'a> From<LuaTable<'a>> for LuaValue<'a> {
+    /// Converts a [`LuaTable`] into a [`LuaValue::Table`], with
+    /// [`LuaTable::into_entries()`].
+    fn from(table: LuaTable<'a>) -> Self {
+        LuaValue::Table(table.into_entries())
+    }
+}
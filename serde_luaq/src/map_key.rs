@@ -0,0 +1,385 @@
+//! Table keys of any Lua type, for use in Serde maps.
+use crate::{LuaNumber, LuaValue};
+use serde::{de::Visitor, Deserialize, Deserializer};
+use std::{
+    cmp::Ordering,
+    fmt::Formatter,
+    hash::{Hash, Hasher},
+    str::from_utf8,
+};
+
+/// A Lua table key of any (non-table) type.
+///
+/// Serde maps normally need a single key type, so a [`BTreeMap<String, T>`][std::collections::BTreeMap]
+/// can't hold integer keys, and a `BTreeMap<i64, T>` can't hold string keys. `LuaMapKey` accepts
+/// every kind of key Lua allows, so `BTreeMap<LuaMapKey, T>` (or
+/// [`HashMap`][std::collections::HashMap]) can capture any table:
+///
+/// ```rust
+/// use serde_luaq::{from_slice, LuaFormat, LuaMapKey};
+/// use std::collections::BTreeMap;
+///
+/// let m: BTreeMap<LuaMapKey, i64> = from_slice(
+///     br#"{10, [true] = 20, [1.5] = 30, name = 40, ["\xff"] = 50, [-1] = 60}"#,
+///     LuaFormat::Value,
+///     16,
+/// ).unwrap();
+///
+/// assert_eq!(
+///     vec![
+///         (LuaMapKey::Bool(true), 20),
+///         (LuaMapKey::Integer(-1), 60),
+///         (LuaMapKey::Integer(1), 10),
+///         (LuaMapKey::Float(1.5), 30),
+///         (LuaMapKey::from("name"), 40),
+///         (LuaMapKey::Bytes(b"\xff".to_vec()), 50),
+///     ],
+///     m.into_iter().collect::<Vec<_>>(),
+/// );
+/// ```
+///
+/// ## Normalisation
+///
+/// Like Lua, floats with an exact integer value are converted to [`LuaMapKey::Integer`], so
+/// `[1.0]` and `[1]` are the same key. [`LuaMapKey::from_f64()`] does the same.
+///
+/// Strings which are valid UTF-8 are [`LuaMapKey::String`], and all other strings are
+/// [`LuaMapKey::Bytes`].
+///
+/// ## Ordering
+///
+/// Keys are ordered by type, then by value:
+///
+/// 1. booleans (`false` before `true`)
+/// 2. numbers, in numeric order, whether they're integers or floats
+/// 3. UTF-8 strings, in byte order
+/// 4. non-UTF-8 strings, in byte order
+///
+/// Floats use [IEEE 754 total ordering][f64::total_cmp], so NaN keys (which Lua doesn't allow) are
+/// sorted after all other numbers, and equal to themselves.
+///
+/// Lua itself doesn't define an ordering between keys of different types, so this is only useful
+/// for consistent output.
+///
+/// ## Errors
+///
+/// `nil` keys (which Lua doesn't allow) and table keys fail to deserialise.
+#[derive(Debug, Clone)]
+pub enum LuaMapKey {
+    /// A boolean key: `[true]`
+    Bool(bool),
+
+    /// An integer key, or a float key with an exact integer value: `[1]`, `[1.0]`
+    Integer(i64),
+
+    /// A float key without an exact integer value: `[1.5]`
+    Float(f64),
+
+    /// A string key which is valid UTF-8: `name`, `["name"]`
+    String(String),
+
+    /// A string key which is not valid UTF-8: `["\xff"]`
+    Bytes(Vec<u8>),
+}
+
+impl LuaMapKey {
+    /// Creates a key from a float, converting it to [`LuaMapKey::Integer`] if it has an exact
+    /// integer value.
+    pub fn from_f64(v: f64) -> Self {
+        match LuaNumber::Float(v).normalise_key() {
+            LuaNumber::Integer(i) => Self::Integer(i),
+            LuaNumber::Float(f) => Self::Float(f),
+        }
+    }
+
+    /// Creates a key from bytes, using [`LuaMapKey::String`] if they are valid UTF-8.
+    pub fn from_bytes(v: Vec<u8>) -> Self {
+        match String::from_utf8(v) {
+            Ok(s) => Self::String(s),
+            Err(e) => Self::Bytes(e.into_bytes()),
+        }
+    }
+
+    /// Returns the bytes of a [`String`][Self::String] or [`Bytes`][Self::Bytes] key.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::String(s) => Some(s.as_bytes()),
+            Self::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Sort order of each type of key.
+    const fn type_order(&self) -> u8 {
+        match self {
+            Self::Bool(_) => 0,
+            Self::Integer(_) | Self::Float(_) => 1,
+            Self::String(_) => 2,
+            Self::Bytes(_) => 3,
+        }
+    }
+}
+
+impl PartialEq for LuaMapKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for LuaMapKey {}
+
+impl PartialOrd for LuaMapKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LuaMapKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Bool(a), Self::Bool(b)) => a.cmp(b),
+            (Self::Integer(a), Self::Integer(b)) => a.cmp(b),
+            (Self::Float(a), Self::Float(b)) => a.total_cmp(b),
+            // Floats are never integral values in the range of `i64`, so these can only be equal
+            // after rounding `a` to `f64`. Integers are sorted first in that case.
+            (Self::Integer(a), Self::Float(b)) => (*a as f64).total_cmp(b).then(Ordering::Less),
+            (Self::Float(a), Self::Integer(b)) => a.total_cmp(&(*b as f64)).then(Ordering::Greater),
+            (Self::String(a), Self::String(b)) => a.cmp(b),
+            (Self::Bytes(a), Self::Bytes(b)) => a.cmp(b),
+            (a, b) => a.type_order().cmp(&b.type_order()),
+        }
+    }
+}
+
+impl Hash for LuaMapKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Self::Bool(v) => v.hash(state),
+            Self::Integer(v) => v.hash(state),
+            Self::Float(v) => v.to_bits().hash(state),
+            Self::String(v) => v.hash(state),
+            Self::Bytes(v) => v.hash(state),
+        }
+    }
+}
+
+impl From<bool> for LuaMapKey {
+    fn from(v: bool) -> Self {
+        Self::Bool(v)
+    }
+}
+
+impl From<i64> for LuaMapKey {
+    fn from(v: i64) -> Self {
+        Self::Integer(v)
+    }
+}
+
+impl From<f64> for LuaMapKey {
+    fn from(v: f64) -> Self {
+        Self::from_f64(v)
+    }
+}
+
+impl From<&str> for LuaMapKey {
+    fn from(v: &str) -> Self {
+        Self::String(v.to_string())
+    }
+}
+
+impl From<String> for LuaMapKey {
+    fn from(v: String) -> Self {
+        Self::String(v)
+    }
+}
+
+impl From<LuaMapKey> for LuaValue<'static> {
+    fn from(v: LuaMapKey) -> Self {
+        match v {
+            LuaMapKey::Bool(b) => LuaValue::Boolean(b),
+            LuaMapKey::Integer(i) => LuaValue::integer(i),
+            LuaMapKey::Float(f) => LuaValue::float(f),
+            LuaMapKey::String(s) => LuaValue::String(s.into_bytes().into()),
+            LuaMapKey::Bytes(b) => LuaValue::String(b.into()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LuaMapKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(LuaMapKeyVisitor)
+    }
+}
+
+struct LuaMapKeyVisitor;
+
+impl<'de> Visitor<'de> for LuaMapKeyVisitor {
+    type Value = LuaMapKey;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("a boolean, number or string table key")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(LuaMapKey::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(LuaMapKey::Integer(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(match i64::try_from(v) {
+            Ok(v) => LuaMapKey::Integer(v),
+            Err(_) => LuaMapKey::Float(v as f64),
+        })
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(LuaMapKey::from_f64(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(LuaMapKey::from(v))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E> {
+        Ok(LuaMapKey::String(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(match from_utf8(v) {
+            Ok(s) => LuaMapKey::from(s),
+            Err(_) => LuaMapKey::Bytes(v.to_vec()),
+        })
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(LuaMapKey::from_bytes(v))
+    }
+}
+
+impl TryFrom<LuaValue<'_>> for LuaMapKey {
+    type Error = LuaValue<'static>;
+
+    /// Converts a [`LuaValue`] to a key, returning the value as an error if it is `nil` or a
+    /// table.
+    fn try_from(value: LuaValue<'_>) -> Result<Self, Self::Error> {
+        Ok(match value {
+            LuaValue::Boolean(b) => Self::Bool(b),
+            LuaValue::Number(LuaNumber::Integer(i)) => Self::Integer(i),
+            LuaValue::Number(LuaNumber::Float(f)) => Self::from_f64(f),
+            LuaValue::String(s) => Self::from_bytes(s.into_owned()),
+            v => return Err(v.into_owned()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{from_slice, LuaFormat};
+    use std::collections::{BTreeMap, HashSet};
+
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+    fn normalisation() {
+        assert_eq!(LuaMapKey::Integer(1), LuaMapKey::from(1.0));
+        assert_eq!(LuaMapKey::Integer(0), LuaMapKey::from(-0.0));
+        assert_eq!(LuaMapKey::Float(1.5), LuaMapKey::from(1.5));
+        assert_eq!(LuaMapKey::Float(9.3e18), LuaMapKey::from(9.3e18));
+        assert_eq!(
+            LuaMapKey::Integer(i64::MIN),
+            LuaMapKey::from(i64::MIN as f64)
+        );
+        assert_eq!(
+            LuaMapKey::Float(i64::MAX as f64),
+            LuaMapKey::from(i64::MAX as f64)
+        );
+        assert!(matches!(LuaMapKey::from(f64::NAN), LuaMapKey::Float(_)));
+        assert_eq!(LuaMapKey::from(f64::NAN), LuaMapKey::from(f64::NAN));
+
+        assert_eq!(LuaMapKey::from("a"), LuaMapKey::from_bytes(b"a".to_vec()));
+        assert_eq!(
+            LuaMapKey::Bytes(b"\xff".to_vec()),
+            LuaMapKey::from_bytes(b"\xff".to_vec())
+        );
+
+        assert_eq!(
+            Ok(LuaMapKey::Integer(2)),
+            LuaMapKey::try_from(LuaValue::float(2.0))
+        );
+        assert_eq!(Err(LuaValue::Nil), LuaMapKey::try_from(LuaValue::Nil));
+    }
+
+    #[test]
+    #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+    fn ordering() {
+        let keys = [
+            LuaMapKey::Bool(false),
+            LuaMapKey::Bool(true),
+            LuaMapKey::Float(f64::NEG_INFINITY),
+            LuaMapKey::Integer(i64::MIN),
+            LuaMapKey::Float(-0.5),
+            LuaMapKey::Integer(0),
+            LuaMapKey::Float(0.5),
+            LuaMapKey::Integer(1),
+            LuaMapKey::Integer(i64::MAX),
+            LuaMapKey::Float(i64::MAX as f64),
+            LuaMapKey::Float(f64::INFINITY),
+            LuaMapKey::Float(f64::NAN),
+            LuaMapKey::from(""),
+            LuaMapKey::from("a"),
+            LuaMapKey::from("b"),
+            LuaMapKey::Bytes(b"\x80".to_vec()),
+            LuaMapKey::Bytes(b"\xff".to_vec()),
+        ];
+
+        for (i, a) in keys.iter().enumerate() {
+            for (j, b) in keys.iter().enumerate() {
+                assert_eq!(i.cmp(&j), a.cmp(b), "{a:?} <=> {b:?}");
+            }
+        }
+
+        let set: HashSet<LuaMapKey> = keys.iter().cloned().chain(keys.iter().cloned()).collect();
+        assert_eq!(keys.len(), set.len());
+    }
+
+    #[test]
+    #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+    fn deserialize() {
+        let m: BTreeMap<LuaMapKey, i64> = from_slice(
+            b"{[1.0] = 1, 2, [false] = 3, ['1'] = 4, [2.5] = 5}",
+            LuaFormat::Value,
+            16,
+        )
+        .unwrap();
+
+        // `[1.0]` is replaced by the implicit `[1]`
+        assert_eq!(
+            BTreeMap::from([
+                (LuaMapKey::Bool(false), 3),
+                (LuaMapKey::Integer(1), 2),
+                (LuaMapKey::Float(2.5), 5),
+                (LuaMapKey::from("1"), 4),
+            ]),
+            m
+        );
+
+        assert!(
+            from_slice::<BTreeMap<LuaMapKey, i64>>(b"{[{}] = 1}", LuaFormat::Value, 16).is_err()
+        );
+        assert!(
+            from_slice::<BTreeMap<LuaMapKey, i64>>(b"{[nil] = 1}", LuaFormat::Value, 16).is_err()
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Counters describing how much work the parser did, for finding slow inputs.
+#[cfg(feature = "metrics")]
+use std::cell::Cell;
+
+/// Counters describing how much work the parser did, returned by
+/// [`Parser::parse_with_metrics()`][crate::Parser::parse_with_metrics].
+///
+/// The grammar is a [PEG][peg], which tries each alternative of a rule in order, and backtracks
+/// to try the next one when an alternative fails part-way through. This is usually cheap, but
+/// some inputs can make the parser re-scan the same bytes many times. These counters make that
+/// visible, so that tests can assert bounds on them.
+///
+/// Backtracking is measured at the level of values, table entries, identifiers and script
+/// statements, rather than individual tokens. When parsing fails, `peg` parses the input again
+/// from the start to collect details for the error, which counts as one more backtrack.
+///
+/// [peg]: https://en.wikipedia.org/wiki/Parsing_expression_grammar
+#[cfg(feature = "metrics")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ParseMetrics {
+    /// Deepest table nesting observed, where `1` is a table which is not inside of another table.
+    pub max_depth: u16,
+
+    /// Number of times the parser went back to an earlier position in the input, after parsing
+    /// something past it.
+    pub backtracks: usize,
+
+    /// Total number of bytes the parser went back over when backtracking.
+    pub rescanned: usize,
+}
+
+/// Records [`ParseMetrics`] while parsing.
+///
+/// Without the `metrics` feature, this does nothing.
+#[derive(Debug, Default)]
+pub(crate) struct Recorder {
+    #[cfg(feature = "metrics")]
+    metrics: Cell<ParseMetrics>,
+
+    /// Furthest position in the input which the parser has parsed up to.
+    #[cfg(feature = "metrics")]
+    furthest: Cell<usize>,
+}
+
+#[cfg(feature = "metrics")]
+impl Recorder {
+    /// Called by the grammar when it starts parsing something at `pos`.
+    pub(crate) fn enter(&self, pos: usize) {
+        let furthest = self.furthest.get();
+        if pos < furthest {
+            let mut m = self.metrics.get();
+            m.backtracks += 1;
+            m.rescanned += furthest - pos;
+            self.metrics.set(m);
+            self.furthest.set(pos);
+        }
+    }
+
+    /// Called by the grammar when it has parsed something up to `pos`.
+    pub(crate) fn leave(&self, pos: usize) {
+        if pos > self.furthest.get() {
+            self.furthest.set(pos);
+        }
+    }
+
+    /// Called by the grammar when it opens a table at `depth`.
+    pub(crate) fn depth(&self, depth: u16) {
+        let mut m = self.metrics.get();
+        m.max_depth = m.max_depth.max(depth);
+        self.metrics.set(m);
+    }
+
+    /// Returns the metrics recorded so far.
+    pub(crate) fn metrics(&self) -> ParseMetrics {
+        self.metrics.get()
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+impl Recorder {
+    #[inline(always)]
+    pub(crate) fn enter(&self, _pos: usize) {}
+
+    #[inline(always)]
+    pub(crate) fn leave(&self, _pos: usize) {}
+
+    #[inline(always)]
+    pub(crate) fn depth(&self, _depth: u16) {}
+}
@@ -0,0 +1,204 @@
+//! Versioned migrations for [`LuaValue`] trees.
+use crate::{LuaNumber, LuaTableEntry, LuaValue, MigrationError};
+use std::{collections::BTreeMap, fmt::Debug};
+
+type MigrationFn = dyn for<'a> Fn(LuaValue<'a>) -> Result<LuaValue<'a>, MigrationError>;
+
+/// A set of migrations which upgrade a [`LuaValue`] from older versions of a save format to the
+/// current version, before it is deserialised with Serde.
+///
+/// Many games store a version number somewhere in their save files, and bump it whenever the
+/// format changes. [`Migrations`] reads that version number from a [path of table keys][Self::new],
+/// then applies each registered migration step in order, updating the version number as it goes.
+///
+/// Each step upgrades from exactly one version to the next (ie: the step for version `3` upgrades
+/// to version `4`). Versions must be [integers][LuaNumber::Integer].
+///
+/// ## Example
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use serde_luaq::{lua_value, LuaNumber, LuaTableEntry, LuaValue, Migrations};
+///
+/// #[derive(Deserialize, Debug, PartialEq)]
+/// struct Save {
+///     version: i64,
+///     gold: i64,
+/// }
+///
+/// let migrations = Migrations::new(["version"])
+///     // Version 1 called the field `money`
+///     .step(1, |v| {
+///         let LuaValue::Table(entries) = v else {
+///             return Ok(v);
+///         };
+///         Ok(LuaValue::Table(
+///             entries
+///                 .into_iter()
+///                 .map(|e| match e {
+///                     LuaTableEntry::NameValue(b) if b.0 == "money" => {
+///                         LuaTableEntry::NameValue(Box::new(("gold".into(), b.1)))
+///                     }
+///                     e => e,
+///                 })
+///                 .collect(),
+///         ))
+///     })
+///     // Version 2 stored gold in hundreds
+///     .step(2, |mut v| {
+///         if let LuaValue::Table(entries) = &mut v {
+///             for e in entries.iter_mut() {
+///                 if let LuaTableEntry::NameValue(b) = e {
+///                     if let ("gold", LuaValue::Number(LuaNumber::Integer(g))) = (b.0.as_ref(), &b.1) {
+///                         b.1 = LuaValue::integer(g * 100);
+///                     }
+///                 }
+///             }
+///         }
+///         Ok(v)
+///     });
+///
+/// let old = lua_value(b"{version = 1, money = 5}", 16).unwrap();
+/// let new = migrations.migrate(old).unwrap();
+/// assert_eq!(Save { version: 3, gold: 500 }, Save::deserialize(new).unwrap());
+///
+/// // Current versions are left as-is
+/// let current = lua_value(b"{version = 3, gold = 123}", 16).unwrap();
+/// let new = migrations.migrate(current).unwrap();
+/// assert_eq!(Save { version: 3, gold: 123 }, Save::deserialize(new).unwrap());
+/// ```
+pub struct Migrations {
+    path: Vec<String>,
+    default_version: Option<i64>,
+    steps: BTreeMap<i64, Box<MigrationFn>>,
+}
+
+impl Debug for Migrations {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Migrations")
+            .field("path", &self.path)
+            .field("default_version", &self.default_version)
+            .field("steps", &self.steps.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Migrations {
+    /// Creates a new, empty set of migrations, where the version number is stored at `path`.
+    ///
+    /// `path` is a sequence of string table keys, starting from the root value. For example,
+    /// `["meta", "version"]` would read the version number `3` from
+    /// `{meta = {version = 3}}`.
+    ///
+    /// If a key is defined more than once, the last entry is used, [like with
+    /// Serde][crate#duplicate-table-keys-in-serde].
+    pub fn new<I, S>(path: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            path: path.into_iter().map(Into::into).collect(),
+            default_version: None,
+            steps: BTreeMap::new(),
+        }
+    }
+
+    /// Treat values with no version number at [`path`][Self::new] as if they were `version`.
+    ///
+    /// This is useful for save formats which only started recording a version number later on.
+    ///
+    /// If the version number is missing after migration, it will be added to the deepest table
+    /// in `path`. All other tables in `path` must already exist.
+    ///
+    /// By default, a missing version number returns [`MigrationError::MissingVersion`].
+    pub fn default_version(mut self, version: i64) -> Self {
+        self.default_version = Some(version);
+        self
+    }
+
+    /// Registers a migration step which upgrades a value from `version` to `version + 1`.
+    ///
+    /// After `f` returns, the version number at [`path`][Self::new] is set to `version + 1`.
+    ///
+    /// Registering a step for a `version` that already has a step replaces it.
+    pub fn step<F>(mut self, version: i64, f: F) -> Self
+    where
+        F: for<'a> Fn(LuaValue<'a>) -> Result<LuaValue<'a>, MigrationError> + 'static,
+    {
+        self.steps.insert(version, Box::new(f));
+        self
+    }
+
+    /// Returns the version that values will be upgraded to by [`migrate()`][Self::migrate], if
+    /// they are at or below the lowest registered step.
+    ///
+    /// Returns [`None`] if there are no steps registered.
+    pub fn latest_version(&self) -> Option<i64> {
+        self.steps
+            .last_key_value()
+            .map(|(v, _)| v.saturating_add(1))
+    }
+
+    /// Reads the version number of `value`.
+    pub fn version(&self, value: &LuaValue<'_>) -> Result<i64, MigrationError> {
+        match value
+            .get_path(self.path.iter().map(String::as_str))
+            .as_deref()
+        {
+            Some(LuaValue::Number(LuaNumber::Integer(v))) => Ok(*v),
+            Some(_) => Err(MigrationError::InvalidVersion),
+            None => self.default_version.ok_or(MigrationError::MissingVersion),
+        }
+    }
+
+    /// Applies all migration steps to `value`, starting from its current version.
+    ///
+    /// Steps are applied in order, until there is no step registered for the current version.
+    /// Values with a version number newer than all registered steps are returned as-is.
+    pub fn migrate<'a>(&self, mut value: LuaValue<'a>) -> Result<LuaValue<'a>, MigrationError> {
+        let mut version = self.version(&value)?;
+
+        while let Some(step) = self.steps.get(&version) {
+            value = step(value)?;
+            version = version
+                .checked_add(1)
+                .ok_or(MigrationError::InvalidVersion)?;
+            set_path(&mut value, &self.path, version)?;
+        }
+
+        Ok(value)
+    }
+}
+
+/// Sets the value at `path` to the integer `version`, using the last entry of any duplicate keys.
+///
+/// If the last key in `path` is missing, it is added to the end of the table.
+fn set_path(value: &mut LuaValue<'_>, path: &[String], version: i64) -> Result<(), MigrationError> {
+    let Some((last, parents)) = path.split_last() else {
+        // Replace the root value
+        *value = LuaValue::integer(version);
+        return Ok(());
+    };
+
+    let mut value = value;
+    for key in parents {
+        value = value
+            .get_mut(key.as_str())
+            .ok_or(MigrationError::MissingVersion)?;
+    }
+
+    if let Some(v) = value.get_mut(last.as_str()) {
+        *v = LuaValue::integer(version);
+        return Ok(());
+    }
+
+    let LuaValue::Table(entries) = value else {
+        return Err(MigrationError::MissingVersion);
+    };
+    entries.push(LuaTableEntry::from((
+        last.clone(),
+        LuaValue::integer(version),
+    )));
+    Ok(())
+}
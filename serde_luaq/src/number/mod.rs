@@ -7,7 +7,7 @@ use crate::LuaValue;
     target_arch = "wasm32"
 ))]
 use static_assertions::assert_eq_size;
-use std::{fmt::Display, ops::Neg};
+use std::{cmp::Ordering, fmt::Display, ops::Neg};
 
 /// Maximum integer value that can be represented in an [`f64`] without loss of precision.
 pub const MAX_F64_INTEGER: i64 = (1_i64 << f64::MANTISSA_DIGITS) - 1;
@@ -60,6 +60,26 @@ impl LuaNumber {
         matches!(self, LuaNumber::Float(f) if f.is_infinite())
     }
 
+    /// Returns the name of the number's subtype, as returned by Lua's
+    /// [`math.type()`][math.type] function: `integer` or `float`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::LuaNumber;
+    ///
+    /// assert_eq!("integer", LuaNumber::Integer(1).subtype_name());
+    /// assert_eq!("float", LuaNumber::Float(1.0).subtype_name());
+    /// ```
+    ///
+    /// [math.type]: https://www.lua.org/manual/5.4/manual.html#pdf-math.type
+    pub const fn subtype_name(&self) -> &'static str {
+        match self {
+            LuaNumber::Integer(_) => "integer",
+            LuaNumber::Float(_) => "float",
+        }
+    }
+
     /// Returns `true` if the number is represented using the `integer` subtype.
     pub const fn is_i64(&self) -> bool {
         matches!(self, Self::Integer(_))
@@ -124,6 +144,94 @@ impl LuaNumber {
             }
         }
     }
+
+    /// Converts a float with an exact integer value into an integer, like Lua does for table keys.
+    ///
+    /// In Lua, `t[2.0]` and `t[2]` refer to the same entry.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::LuaNumber;
+    ///
+    /// assert_eq!(LuaNumber::Integer(2), LuaNumber::Float(2.).normalise_key());
+    /// assert_eq!(LuaNumber::Float(2.5), LuaNumber::Float(2.5).normalise_key());
+    /// assert_eq!(LuaNumber::Integer(3), LuaNumber::Integer(3).normalise_key());
+    ///
+    /// // Out of the range of an integer
+    /// assert_eq!(LuaNumber::Float(1e100), LuaNumber::Float(1e100).normalise_key());
+    /// ```
+    pub fn normalise_key(self) -> Self {
+        match self {
+            // `i64::MAX as f64` rounds up to 2^63, which is out of range.
+            LuaNumber::Float(v)
+                if v.fract() == 0.0 && v >= i64::MIN as f64 && v < i64::MAX as f64 =>
+            {
+                LuaNumber::Integer(v as i64)
+            }
+            n => n,
+        }
+    }
+
+    /// Compares two numbers by their numeric value, with a total order.
+    ///
+    /// Floats are ordered like [`f64::total_cmp()`]: `-NaN < -inf < ... < -0.0 < +0.0 < ... < +inf
+    /// < +NaN`. Integers are compared with floats exactly, without converting either to the
+    /// other's type.
+    ///
+    /// An integer and a float with the same value (like `1` and `1.0`) aren't equal: the integer is
+    /// ordered first.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::LuaNumber;
+    /// use std::cmp::Ordering;
+    ///
+    /// assert_eq!(Ordering::Less, LuaNumber::Integer(1).total_cmp(&LuaNumber::Float(1.5)));
+    /// assert_eq!(Ordering::Less, LuaNumber::Integer(1).total_cmp(&LuaNumber::Float(1.0)));
+    /// assert_eq!(Ordering::Greater, LuaNumber::Float(f64::NAN).total_cmp(&LuaNumber::Integer(1)));
+    ///
+    /// // Integers beyond the precision of f64
+    /// assert_eq!(
+    ///     Ordering::Greater,
+    ///     LuaNumber::Integer((1 << 53) + 1).total_cmp(&LuaNumber::Float((1u64 << 53) as f64)),
+    /// );
+    /// ```
+    pub fn total_cmp(&self, other: &Self) -> Ordering {
+        match (*self, *other) {
+            (LuaNumber::Integer(a), LuaNumber::Integer(b)) => a.cmp(&b),
+            (LuaNumber::Float(a), LuaNumber::Float(b)) => a.total_cmp(&b),
+            (LuaNumber::Integer(a), LuaNumber::Float(b)) => {
+                cmp_integer_float(a, b).then(Ordering::Less)
+            }
+            (LuaNumber::Float(a), LuaNumber::Integer(b)) => {
+                cmp_integer_float(b, a).reverse().then(Ordering::Greater)
+            }
+        }
+    }
+}
+
+/// Compares the numeric value of an integer and a float exactly.
+fn cmp_integer_float(a: i64, b: f64) -> Ordering {
+    // 2^63, which is exactly representable as a float.
+    const LIMIT: f64 = 9223372036854775808.;
+
+    if b.is_nan() {
+        return if b.is_sign_negative() {
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        };
+    } else if b >= LIMIT {
+        return Ordering::Less;
+    } else if b < -LIMIT {
+        return Ordering::Greater;
+    }
+
+    // `t` is in the range of `i64`, so this conversion is exact.
+    let t = b.trunc();
+    a.cmp(&(t as i64)).then_with(|| t.total_cmp(&b))
 }
 
 impl From<f64> for LuaNumber {
@@ -209,6 +317,45 @@ mod test {
     #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
     wasm_bindgen_test_configure!(run_in_browser);
 
+    #[test]
+    #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+    fn total_cmp() {
+        use LuaNumber::{Float, Integer};
+
+        // Sorted in ascending order
+        let sorted = [
+            Float(-f64::NAN),
+            Float(f64::NEG_INFINITY),
+            Float(-9223372036854775808. * 2.),
+            Integer(i64::MIN),
+            Float(-9223372036854775808.),
+            Integer(i64::MIN + 1),
+            Integer(-1),
+            Float(-1.),
+            Float(-0.5),
+            Integer(0),
+            Float(-0.),
+            Float(0.),
+            Float(f64::MIN_POSITIVE),
+            Float(0.5),
+            Integer(1),
+            Float(1.),
+            Integer(1 << 53),
+            Float((1u64 << 53) as f64),
+            Integer((1 << 53) + 1),
+            Integer(i64::MAX),
+            Float(9223372036854775808.),
+            Float(f64::INFINITY),
+            Float(f64::NAN),
+        ];
+
+        for (i, a) in sorted.iter().enumerate() {
+            for (j, b) in sorted.iter().enumerate() {
+                assert_eq!(i.cmp(&j), a.total_cmp(b), "{a:?} <=> {b:?}");
+            }
+        }
+    }
+
     #[test]
     #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
     fn from_integer() {
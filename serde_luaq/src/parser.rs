@@ -0,0 +1,1362 @@
+//! Parser entry points and options.
+#[cfg(feature = "metrics")]
+use crate::ParseMetrics;
+use crate::{
+    diagnostics::{diagnose, Diagnostic},
+    footer::{strip_footer, FooterCheck},
+    peg_parser::lua,
+    progress::{CancellationToken, Hooks, Progress},
+    salvage::{salvage, Salvaged},
+    script::{script_return_with_hooks, script_with_hooks, Statement},
+    script_iter, script_return_with_options, script_with_options, Error, LuaFormat, LuaValue,
+    ScriptIter, ScriptReturn, ScriptStatement,
+};
+use peg::error::ParseError;
+use std::{borrow::Cow, collections::BTreeMap, iter::FusedIterator, ops::ControlFlow};
+
+/// A `max_depth` which is safe to use on a program's main thread, on every supported platform.
+///
+/// This is based on the smallest default main thread stack size of a supported platform (1 MiB,
+/// on Windows and `wasm32`), in an unoptimised build, with room to spare.
+///
+/// See [Maximum table depth][crate#maximum-table-depth] for what this covers.
+pub const RECOMMENDED_MAX_DEPTH_MAIN_THREAD: u16 = 128;
+
+/// A `max_depth` which is safe to use on a thread spawned with Rust's default stack size (2 MiB),
+/// on every supported platform.
+///
+/// This is based on an unoptimised build, with room to spare.
+///
+/// See [Maximum table depth][crate#maximum-table-depth] for what this covers.
+pub const RECOMMENDED_MAX_DEPTH_SPAWNED_THREAD: u16 = 256;
+
+/// Parse a bare Lua value expression as a [`LuaValue`].
+///
+/// The value _may_ be preceeded or followed by whitespace.
+///
+/// For more details about type mapping rules and parameters,
+/// [see the crate docs][crate#data-types].
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{lua_value, LuaValue};
+///
+/// assert_eq!(LuaValue::Boolean(true), lua_value(b"true", 16).unwrap());
+/// assert_eq!(LuaValue::Boolean(false), lua_value(b"  false\r\n  ", 16).unwrap());
+/// ```
+///
+/// For more information about Lua type conversion, see [`LuaValue`].
+pub fn lua_value(b: &[u8], max_depth: u16) -> Result<LuaValue<'_>, ParseError<usize>> {
+    lua::lua_value(b, &ParseOptions::new(max_depth), &Hooks::default())
+}
+
+/// Parse a Lua script containing variable assignments into a [`Vec`] of
+/// [`ScriptStatement`].
+///
+/// Each [`ScriptStatement`] can be converted into a `(&str, LuaValue)` tuple with
+/// [`Into`].
+///
+/// For more details about type mapping rules and parameters,
+/// [see the crate docs][crate#data-types].
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{script, LuaValue};
+///
+/// assert_eq!(
+///     vec![
+///         ("hello", LuaValue::Boolean(true)),
+///         ("goodbye", LuaValue::Boolean(false)),
+///     ],
+///     script(b"hello = true\ngoodbye = false", 16).unwrap()
+/// );
+/// ```
+///
+/// For more information about Lua type conversion, see [`LuaValue`].
+pub fn script(b: &[u8], max_depth: u16) -> Result<Vec<ScriptStatement<'_>>, ParseError<usize>> {
+    // Field assignments are disabled, so every statement is a global assignment.
+    lua::script(b, &ParseOptions::new(max_depth), &Hooks::default())
+        .map(|s| s.into_iter().filter_map(Statement::into_global).collect())
+}
+
+/// Parse a Lua script containing variable assignments lazily, one statement at a time, as
+/// `(name, value)` tuples.
+///
+/// Each call to [`Iterator::next()`] parses one more statement, so only the statements which are
+/// actually consumed are turned into [`LuaValue`]s. This is useful for large scripts where only
+/// one or two variables are needed. After returning an error, the iterator returns [`None`].
+///
+/// This is like [`script()`], except it stops at the first statement with a syntax error. Use
+/// [`script_iter()`] for more control over parsing, and to get each statement's
+/// [`span`][ScriptStatement::span].
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{script_entries, LuaValue};
+///
+/// let b = b"version = 3\ndata = {1, 2, 3}\nmore = {{{ invalid";
+/// let version = script_entries(b, 16)
+///     .find(|s| s.as_ref().map_or(true, |(name, _)| *name == "version"))
+///     .transpose()
+///     .unwrap();
+///
+/// assert_eq!(Some(("version", LuaValue::integer(3))), version);
+/// assert!(script_entries(b, 16).nth(2).unwrap().is_err());
+/// ```
+pub fn script_entries(
+    b: &[u8],
+    max_depth: u16,
+) -> impl FusedIterator<Item = Result<(&str, LuaValue<'_>), Error>> {
+    ScriptIter::new(b, Cow::Owned(ParseOptions::new(max_depth))).map(|s| s.map(Into::into))
+}
+
+/// Parse a Lua `return` stamement into a [`LuaValue`].
+///
+/// For more details about type mapping rules and parameters,
+/// [see the crate docs][crate#data-types].
+///
+/// ## Multiple values
+///
+/// If the statement returns more than one value (`return a, b, c`), they are returned as a
+/// sequence table, like `{a, b, c}`, so they can be deserialised as a tuple.
+///
+/// **Warning:** this means that `return a, b` and `return {a, b}` give the same [`LuaValue`].
+/// Use [`return_values()`] to tell them apart, which returns each value separately.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{return_statement, return_values, LuaValue};
+///
+/// assert_eq!(LuaValue::Boolean(true), return_statement(b"return true\n", 16).unwrap());
+///
+/// // Multiple values can't be told apart from a table
+/// assert_eq!(
+///     return_statement(b"return {1, 2}", 16).unwrap(),
+///     return_statement(b"return 1, 2", 16).unwrap(),
+/// );
+/// assert_eq!(1, return_values(b"return {1, 2}", 16).unwrap().len());
+/// assert_eq!(2, return_values(b"return 1, 2", 16).unwrap().len());
+/// ```
+///
+/// For more information about Lua type conversion, see [`LuaValue`].
+pub fn return_statement(b: &[u8], max_depth: u16) -> Result<LuaValue<'_>, ParseError<usize>> {
+    lua::return_statement(b, &ParseOptions::new(max_depth), &Hooks::default())
+}
+
+/// Parse a Lua `return` statement with one or more comma-separated values into a [`Vec`] of
+/// [`LuaValue`]s.
+///
+/// An empty input returns a single `nil`, like [`return_statement()`].
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{return_values, LuaValue};
+///
+/// assert_eq!(
+///     vec![LuaValue::Boolean(true), LuaValue::integer(2)],
+///     return_values(b"return true, 2\n", 16).unwrap(),
+/// );
+/// ```
+pub fn return_values(b: &[u8], max_depth: u16) -> Result<Vec<LuaValue<'_>>, ParseError<usize>> {
+    lua::return_values(b, &ParseOptions::new(max_depth), &Hooks::default())
+}
+
+/// Parse a bare Lua value expression as a [`LuaValue`], from input which has been split into
+/// chunks (for example, network frames or a ring buffer).
+///
+/// The parser needs contiguous input, so if there is more than one chunk, they are copied into a
+/// single buffer, and the returned value [owns all of its data][LuaValue::into_owned]. A single
+/// chunk is parsed in-place, like [`lua_value()`].
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{lua_value_from_chunks, lua_value, LuaValue};
+///
+/// let chunks: [&[u8]; 3] = [b"{'hel", b"lo', wor", b"ld = true}"];
+/// assert_eq!(
+///     lua_value(b"{'hello', world = true}", 16).unwrap(),
+///     lua_value_from_chunks(chunks, 16).unwrap(),
+/// );
+/// ```
+pub fn lua_value_from_chunks<'a, I>(
+    chunks: I,
+    max_depth: u16,
+) -> Result<LuaValue<'a>, ParseError<usize>>
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    let mut chunks = chunks.into_iter();
+    let first = chunks.next().unwrap_or_default();
+    let Some(second) = chunks.next() else {
+        return lua_value(first, max_depth);
+    };
+
+    let mut buf = Vec::with_capacity(first.len() + second.len());
+    buf.extend_from_slice(first);
+    buf.extend_from_slice(second);
+    for chunk in chunks {
+        buf.extend_from_slice(chunk);
+    }
+
+    Ok(lua_value(&buf, max_depth)?.into_owned())
+}
+
+/// Unescapes the body of a Lua short string literal, without its surrounding quotes.
+///
+/// This follows the same rules as the parser, and is useful for tools which find string literals
+/// by other means.
+///
+/// The body may contain unescaped quote characters of either type (`'` or `"`), but may not contain
+/// unescaped line breaks. `\u{XXX}` escapes which aren't Unicode scalar values are encoded with
+/// RFC 2279, [like Lua][InvalidUnicodeEscapes::Keep].
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::unescape_short_string;
+///
+/// assert_eq!(b"hello\n\"world\"\0", unescape_short_string(br#"hello\n\"world"\0"#).unwrap().as_ref());
+/// assert!(unescape_short_string(br"bad \q escape").is_err());
+/// ```
+pub fn unescape_short_string(b: &[u8]) -> Result<Cow<'_, [u8]>, ParseError<usize>> {
+    lua::short_string_body(b, &ParseOptions::new(0), &Hooks::default())
+}
+
+/// Unescapes the body of a Lua long string literal at `level`, without its surrounding brackets.
+///
+/// The level is the number of `=` characters in the brackets, so `[==[ ... ]==]` has a level of
+/// `2`.
+///
+/// Long strings have no escape sequences, but like Lua, a line break at the start of the body is
+/// skipped. The body may not contain a closing bracket of the same level.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::unescape_long_string;
+///
+/// assert_eq!(b"hello]]\\n", unescape_long_string(b"\nhello]]\\n", 1).unwrap().as_ref());
+/// assert!(unescape_long_string(b"hello]]", 0).is_err());
+/// ```
+pub fn unescape_long_string(b: &[u8], level: usize) -> Result<Cow<'_, [u8]>, ParseError<usize>> {
+    lua::long_string_body(b, &ParseOptions::new(0), &Hooks::default(), level)
+}
+
+/// Options for [`Parser`] and [`script_with_options()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ParseOptions {
+    /// Maximum table depth, as in [`lua_value()`].
+    pub max_depth: u16,
+
+    /// Maximum number of statements in a script, or [`None`] for no limit.
+    ///
+    /// The parser stops as soon as it reaches this limit, so a script with millions of
+    /// statements can't use up all memory with its results.
+    ///
+    /// If there are more statements than this, parsing returns [`Error::TooManyStatements`].
+    pub max_statements: Option<usize>,
+
+    /// Maximum length of an identifier (like a variable name or table key `name = ...`) in
+    /// bytes, or [`None`] for no limit.
+    ///
+    /// Identifiers are borrowed from the input, so they don't use any more memory, but a very
+    /// long identifier can slow down anything which compares or copies keys, and make output
+    /// (like JSON) very large.
+    ///
+    /// If an identifier is longer than this, parsing returns [`Error::IdentifierTooLong`].
+    ///
+    /// ```rust
+    /// use serde_luaq::{Error, Parser};
+    ///
+    /// let p = Parser::builder().max_identifier_length(8).build();
+    /// assert!(p.script(b"shortish = 1").is_ok());
+    /// assert_eq!(
+    ///     Error::IdentifierTooLong { limit: 8, position: 2 },
+    ///     p.value(b"{ too_long_name = 1 }").unwrap_err(),
+    /// );
+    ///
+    /// // Strings aren't identifiers
+    /// assert!(p.value(br#"{["too_long_name"] = 1}"#).is_ok());
+    /// ```
+    pub max_identifier_length: Option<usize>,
+
+    /// Lua language dialect to accept.
+    pub dialect: LuaDialect,
+
+    /// How to handle `\u{XXX}` escape sequences which aren't Unicode scalar values.
+    pub invalid_unicode_escapes: InvalidUnicodeEscapes,
+
+    /// How to handle bare identifiers used as values, like `RED` in `color = RED`.
+    pub bare_identifiers: BareIdentifiers,
+
+    /// Only accept strings and numbers in the forms written by Lua's `string.format()`, for
+    /// services which should only ever receive machine-generated data.
+    ///
+    /// When `true`:
+    ///
+    /// * strings must be double-quoted, in the form written by `%q`: control characters (`0x00`
+    ///   to `0x1F`, and `0x7F`) must be written with decimal escapes (`\13`), and the only other
+    ///   escape sequences allowed are `\"`, `\\`, `\` followed by a line feed, and (like Lua
+    ///   5.1) `\r`. Long strings (`[[...]]`) are rejected.
+    ///
+    /// * integers must be decimal (`%d`), or `0x8000000000000000` (`%q` of `math.mininteger`)
+    ///
+    /// * floats must be decimal (`%.17g`), lower-case hexadecimal with an exponent (`%a`), or
+    ///   `1e9999`, `-1e9999` or `(0/0)` (`%q` of infinities and NaN)
+    ///
+    /// Table syntax is unaffected.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{LuaValue, Parser};
+    ///
+    /// let p = Parser::builder().strict_q(true).build();
+    /// assert_eq!(LuaValue::from("a\r\"b\""), p.value(br#""a\13\"b\"""#).unwrap());
+    /// assert_eq!(LuaValue::float(1.5), p.value(b"0x1.8p+0").unwrap());
+    /// assert_eq!(LuaValue::float(0.1), p.value(b"0.10000000000000001").unwrap());
+    ///
+    /// assert!(p.value(b"'single quotes'").is_err());
+    /// assert!(p.value(b"[[long string]]").is_err());
+    /// assert!(p.value(br#""\x41""#).is_err());
+    /// assert!(p.value(b"0xff").is_err());
+    /// ```
+    pub strict_q: bool,
+
+    /// Allow scripts to assign to fields of tables stored in global variables, like
+    /// `Config.a = 1` or `Config["b"][1] = 2`.
+    ///
+    /// Each field assignment is merged into the value of the most recent assignment to the global,
+    /// and is not returned as a separate [`ScriptStatement`]. Like Lua, assigning to an existing
+    /// key replaces its value.
+    ///
+    /// If the global was never assigned, or a key along the way is not a table, parsing returns
+    /// [`Error::IndexNotTable`].
+    ///
+    /// Defaults to `false`, where field assignments are a syntax error.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{LuaTableEntry, LuaValue, Parser};
+    ///
+    /// let p = Parser::builder().field_assignments(true).build();
+    /// let s = p.script(b"Config = {} Config.a = 1 Config.b = {} Config.b[1] = 'x'").unwrap();
+    /// assert_eq!(
+    ///     vec![(
+    ///         "Config",
+    ///         LuaValue::from_iter([
+    ///             ("a", LuaValue::integer(1)),
+    ///             ("b", LuaValue::Table(vec![LuaTableEntry::KeyValue(Box::new((
+    ///                 LuaValue::integer(1),
+    ///                 LuaValue::from("x"),
+    ///             )))])),
+    ///         ]),
+    ///     )],
+    ///     s,
+    /// );
+    ///
+    /// assert!(p.script(b"Config.a = 1").is_err());
+    /// ```
+    pub field_assignments: bool,
+
+    /// Allow script assignments to be declared `local`, like `local t = {}`, as written by
+    /// exports which end with `return t`.
+    ///
+    /// Local variables are returned like any other assignment, with
+    /// [`ScriptStatement::local`] set. Attributes (like `local <const> a = 1`) and declarations
+    /// without a value (like `local a`) are still a syntax error.
+    ///
+    /// Defaults to `false`, where `local` is a syntax error.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{lua_value, Parser};
+    ///
+    /// let p = Parser::builder().local_assignments(true).build();
+    /// let s = p.script(b"local a = 1\nb = 2").unwrap();
+    /// assert_eq!(("a", true), (s[0].name, s[0].local));
+    /// assert_eq!(("b", false), (s[1].name, s[1].local));
+    ///
+    /// let r = p.script_return(b"local t = {1, 2}\nreturn t").unwrap();
+    /// assert_eq!(lua_value(b"{1, 2}", 16).ok(), r.value);
+    ///
+    /// assert!(Parser::default().script(b"local a = 1").is_err());
+    /// ```
+    pub local_assignments: bool,
+
+    /// Capture the block of comments before each script assignment in
+    /// [`ScriptStatement::comment`], so that they can be written back out with
+    /// [`Serializer::serialize_script()`][crate::Serializer::serialize_script].
+    ///
+    /// The comment block is everything between the end of the previous statement's line (or the
+    /// start of the script) and the start of the assignment, without surrounding whitespace. A
+    /// comment on the same line as the end of the previous statement (like `a = 1 -- note`)
+    /// isn't included.
+    ///
+    /// Comments before [field assignments][Self::field_assignments] and `return` statements aren't
+    /// captured.
+    ///
+    /// Defaults to `false`, where [`ScriptStatement::comment`] is always [`None`].
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::Parser;
+    ///
+    /// let p = Parser::builder().comments(true).build();
+    /// let s = p.script(b"-- player data\n-- (do not edit)\nplayer = {}\n\nlevel = 1 -- start").unwrap();
+    /// assert_eq!(Some(&b"-- player data\n-- (do not edit)"[..]), s[0].comment);
+    /// assert_eq!(None, s[1].comment);
+    /// ```
+    pub comments: bool,
+
+    /// Accept a comma as the decimal separator in floats (like `1,5`), as written by some
+    /// software using a comma locale.
+    ///
+    /// A comma also separates table entries, so `{1,5}` is always a table with two integers.
+    /// This option only applies to values which aren't inside a table: bare values, `return`
+    /// statements and script assignments.
+    ///
+    /// Defaults to `false`, where `1,5` is a syntax error.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{LuaNumber, LuaTableEntry, LuaValue, Parser};
+    ///
+    /// let p = Parser::builder().decimal_comma(true).build();
+    /// assert_eq!(LuaValue::float(-1.5), p.value(b"-1,5").unwrap());
+    /// assert_eq!(LuaValue::float(2.5e3), p.value(b"2,5e3").unwrap());
+    /// assert_eq!(
+    ///     LuaValue::Table(vec![
+    ///         LuaTableEntry::NumberValue(LuaNumber::Integer(1)),
+    ///         LuaTableEntry::NumberValue(LuaNumber::Integer(5)),
+    ///     ]),
+    ///     p.value(b"{1,5}").unwrap(),
+    /// );
+    ///
+    /// assert!(Parser::default().value(b"1,5").is_err());
+    /// ```
+    pub decimal_comma: bool,
+
+    /// Token to cancel parsing, either from another thread or after a deadline.
+    ///
+    /// When the token is cancelled, parsing returns [`Error::Cancelled`]. The token is checked
+    /// between table entries and script statements.
+    ///
+    /// Defaults to [`None`], where parsing can only be cancelled by a progress hook (like
+    /// [`Parser::value_with_progress()`]).
+    pub cancellation: Option<CancellationToken>,
+
+    /// Callback to validate a `--` comment line at the end of the input (like a checksum), before
+    /// parsing.
+    ///
+    /// When set, the input must end with a footer comment, or parsing returns
+    /// [`Error::MissingFooter`]. If the callback rejects the footer, parsing returns
+    /// [`Error::InvalidFooter`]. Otherwise, the input before the footer is parsed as usual.
+    ///
+    /// Defaults to [`None`], where footers aren't checked.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{Error, FooterCheck, LuaValue, Parser};
+    ///
+    /// let check = FooterCheck::new(|body, footer| {
+    ///     let sum: u32 = body.iter().map(|&c| u32::from(c)).sum();
+    ///     match footer.strip_prefix(b"checksum: ") {
+    ///         Some(c) if c == format!("{sum:04x}").as_bytes() => Ok(()),
+    ///         _ => Err(format!("expected checksum {sum:04x}")),
+    ///     }
+    /// });
+    /// let p = Parser::builder().footer(check).build();
+    ///
+    /// assert_eq!(LuaValue::integer(1), p.ret(b"return 1\n-- checksum: 02fb\n").unwrap());
+    /// assert_eq!(
+    ///     Error::InvalidFooter { message: "expected checksum 02fb".to_string() },
+    ///     p.ret(b"return 1\n-- checksum: 0000").unwrap_err(),
+    /// );
+    /// assert_eq!(Error::MissingFooter, p.ret(b"return 1").unwrap_err());
+    /// ```
+    pub footer: Option<FooterCheck>,
+
+    /// Allow string values to be concatenated from string literals with the `..` operator, like
+    /// `"abc" .. 'def' .. [[ghi]]`.
+    ///
+    /// This is commonly used to split long strings (like base64 or hex-encoded data) over multiple
+    /// lines. The result is assembled with a single allocation.
+    ///
+    /// Only string literals may be concatenated; Lua's conversion of numbers to strings (like
+    /// `"a" .. 1`) isn't supported.
+    ///
+    /// Defaults to `false`, where `..` is a syntax error.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{LuaValue, Parser};
+    ///
+    /// let p = Parser::builder().string_concatenation(true).build();
+    /// assert_eq!(
+    ///     LuaValue::from("aGVsbG8gd29ybGQ="),
+    ///     p.value(b"[[aGVsbG8g]] ..\n[[d29ybGQ=]]").unwrap(),
+    /// );
+    ///
+    /// assert!(Parser::default().value(b"'a' .. 'b'").is_err());
+    /// ```
+    pub string_concatenation: bool,
+
+    /// Parse decimal integer literals from 2<sup>63</sup> to [`u64::MAX`] as integers, instead of
+    /// converting them to floats.
+    ///
+    /// These values don't fit in an [`i64`], so they wrap around to negative numbers, the same way
+    /// hexadecimal integer literals do: `18446744073709551615` and `0xffffffffffffffff` are both
+    /// parsed as `-1`. This keeps all 64 bits of a `u64` value, which a float can't.
+    ///
+    /// Lua itself converts these literals to floats, so this is only useful for files written by
+    /// other software. Negative literals and literals greater than [`u64::MAX`] are still converted
+    /// to floats.
+    ///
+    /// Use [`DeserializeOptions::unsigned_integers`][crate::DeserializeOptions::unsigned_integers]
+    /// to deserialise these values into [`u64`] fields.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{LuaValue, Parser};
+    ///
+    /// let p = Parser::builder().unsigned_integers(true).build();
+    /// assert_eq!(LuaValue::integer(-1), p.value(b"18446744073709551615").unwrap());
+    /// assert_eq!(LuaValue::integer(i64::MIN), p.value(b"9223372036854775808").unwrap());
+    ///
+    /// assert_eq!(
+    ///     LuaValue::float(9223372036854775808.),
+    ///     Parser::default().value(b"9223372036854775808").unwrap(),
+    /// );
+    /// ```
+    pub unsigned_integers: bool,
+
+    /// Parse empty or whitespace-only input as `nil` when parsing a bare value or a `return`
+    /// statement.
+    ///
+    /// Defaults to `false`, where empty input is a syntax error for bare values and `return`
+    /// statements. Empty input is always a valid script with no statements. See
+    /// [`LuaFormat`][crate::LuaFormat#empty-input].
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{LuaValue, Parser};
+    ///
+    /// let p = Parser::builder().empty_as_nil(true).build();
+    /// assert_eq!(LuaValue::Nil, p.value(b"").unwrap());
+    /// assert_eq!(LuaValue::Nil, p.ret(b" \n").unwrap());
+    ///
+    /// assert!(Parser::default().value(b"").is_err());
+    /// assert!(Parser::default().ret(b"").is_err());
+    /// assert!(Parser::default().script(b"").unwrap().is_empty());
+    /// ```
+    pub empty_as_nil: bool,
+
+    /// Values to substitute for function calls without arguments, like `os.time()`.
+    ///
+    /// When this is not empty, a call to a function in this map is replaced with its value. A
+    /// call to any other function is a syntax error.
+    ///
+    /// Defaults to empty, where all function calls are syntax errors.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{CallSubstitutions, LuaValue, Parser};
+    ///
+    /// let p = Parser::builder()
+    ///     .calls(CallSubstitutions::from_iter([("os.time", LuaValue::integer(1700000000))]))
+    ///     .build();
+    /// assert_eq!(
+    ///     LuaValue::from_iter([("created", LuaValue::integer(1700000000))]),
+    ///     p.value(b"{created = os.time()}").unwrap(),
+    /// );
+    ///
+    /// assert!(p.value(b"os.clock()").is_err());
+    /// assert!(Parser::default().value(b"os.time()").is_err());
+    /// ```
+    pub calls: CallSubstitutions,
+
+    /// How to reserve capacity for each table's entries.
+    ///
+    /// Defaults to [`TableCapacity::Grow`].
+    pub table_capacity: TableCapacity,
+}
+
+impl ParseOptions {
+    /// Creates options with a given `max_depth`, no other limits, and default behaviour for
+    /// everything else.
+    pub const fn new(max_depth: u16) -> Self {
+        Self {
+            max_depth,
+            max_statements: None,
+            max_identifier_length: None,
+            dialect: LuaDialect::Lua54,
+            invalid_unicode_escapes: InvalidUnicodeEscapes::Keep,
+            bare_identifiers: BareIdentifiers::Error,
+            strict_q: false,
+            field_assignments: false,
+            local_assignments: false,
+            comments: false,
+            decimal_comma: false,
+            cancellation: None,
+            footer: None,
+            string_concatenation: false,
+            unsigned_integers: false,
+            empty_as_nil: false,
+            calls: CallSubstitutions::new(),
+            table_capacity: TableCapacity::Grow,
+        }
+    }
+}
+
+/// How to handle `\u{XXX}` escape sequences in strings which aren't
+/// [Unicode scalar values][scalar]: surrogates (`\u{D800}` to `\u{DFFF}`), or values greater than
+/// `\u{10FFFF}`.
+///
+/// Lua encodes these with [RFC 2279][], which is not valid UTF-8 (RFC 3629), so the string will
+/// fail to deserialise as a Rust [`String`] later on.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{InvalidUnicodeEscapes, LuaValue, Parser};
+///
+/// let keep = Parser::builder().build();
+/// assert_eq!(LuaValue::String(b"\xED\xA0\x80".into()), keep.value(br#""\u{D800}""#).unwrap());
+///
+/// let replace = Parser::builder()
+///     .invalid_unicode_escapes(InvalidUnicodeEscapes::Replace)
+///     .build();
+/// assert_eq!(LuaValue::from("\u{FFFD}"), replace.value(br#""\u{D800}""#).unwrap());
+///
+/// let error = Parser::builder()
+///     .invalid_unicode_escapes(InvalidUnicodeEscapes::Error)
+///     .build();
+/// assert!(error.value(br#""\u{D800}""#).is_err());
+/// ```
+///
+/// [RFC 2279]: https://www.rfc-editor.org/rfc/rfc2279
+/// [scalar]: https://www.unicode.org/glossary/#unicode_scalar_value
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum InvalidUnicodeEscapes {
+    /// Encode the value with RFC 2279, like Lua does.
+    #[default]
+    Keep,
+
+    /// Replace the escape sequence with U+FFFD REPLACEMENT CHARACTER.
+    Replace,
+
+    /// Fail parsing. The error's location is the end of the escape sequence.
+    Error,
+}
+
+/// How to handle bare identifiers used as values, like `RED` in `color = RED`.
+///
+/// In Lua, these are references to global variables, which this library can't evaluate. Some
+/// serialisers which dump enums write their values this way.
+///
+/// ## Round-tripping
+///
+/// [`BareIdentifiers::String`] parses the identifier as a string, so `color = RED` and
+/// `color = "RED"` parse to the same [`LuaValue`][crate::LuaValue]. The difference can't be
+/// recovered later, so writing the value back out produces a quoted string.
+///
+/// Keep the default ([`BareIdentifiers::Error`]) when the input must be valid, self-contained
+/// Lua data.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{BareIdentifiers, LuaValue, Parser};
+///
+/// let strict = Parser::builder().build();
+/// assert!(strict.value(b"{color = RED}").is_err());
+///
+/// let lenient = Parser::builder()
+///     .bare_identifiers(BareIdentifiers::String)
+///     .build();
+/// assert_eq!(
+///     lenient.value(b"{color = RED}").unwrap(),
+///     lenient.value(br#"{color = "RED"}"#).unwrap(),
+/// );
+///
+/// // Keywords are never identifiers.
+/// assert_eq!(LuaValue::Nil, lenient.value(b"nil").unwrap());
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum BareIdentifiers {
+    /// Fail parsing, like a missing value.
+    #[default]
+    Error,
+
+    /// Parse the identifier as a [`LuaValue::String`][crate::LuaValue::String] containing its
+    /// name.
+    String,
+}
+
+/// How to reserve capacity for a table's entries while parsing it.
+///
+/// This only affects performance: the parsed values are the same either way.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{LuaValue, Parser, TableCapacity};
+///
+/// let p = Parser::builder().table_capacity(TableCapacity::Count).build();
+/// let LuaValue::Table(t) = p.value(b"{1, 2, {3, 4, 5}, [[a,b]]}").unwrap() else {
+///     panic!("expected table");
+/// };
+/// assert_eq!(4, t.len());
+/// assert_eq!(4, t.capacity());
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TableCapacity {
+    /// Start with an empty `Vec`, and grow it as entries are parsed.
+    ///
+    /// A table with `n` entries is reallocated about `log2(n)` times, and may end up with up to
+    /// twice as much capacity as it needs.
+    #[default]
+    Grow,
+
+    /// Scan ahead to count a table's entries before parsing them, and reserve exactly that much
+    /// capacity.
+    ///
+    /// This avoids reallocating large tables, at the cost of reading each table's contents one
+    /// more time. Nested tables are scanned again when they are parsed, so deeply nested inputs
+    /// are scanned once for each level of nesting (up to
+    /// [`max_depth`][ParseOptions::max_depth] times).
+    ///
+    /// The scan doesn't understand [GLua][LuaDialect::GLua]'s C-style comments, so the count may
+    /// be wrong for tables which contain them. This only affects how much capacity is reserved, which is never more than one entry
+    /// for each byte of input.
+    Count,
+}
+
+/// Lua language dialect to accept when parsing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LuaDialect {
+    /// [Lua 5.4](https://www.lua.org/manual/5.4/manual.html).
+    #[default]
+    Lua54,
+
+    /// Lua 5.4, but with [Lua 5.1](https://www.lua.org/manual/5.1/manual.html)'s reserved words,
+    /// so `goto` can be used as a variable name or table key (`{goto = 1}`).
+    ///
+    /// Other differences between Lua 5.1 and 5.4 (like Lua 5.1 using [`f64`] for all numbers)
+    /// aren't affected by this option. See [the compatibility notes][crate#lua-51-and-earlier].
+    ///
+    /// ```rust
+    /// use serde_luaq::{LuaDialect, LuaValue, Parser};
+    ///
+    /// assert!(Parser::builder().build().script(b"goto = 1").is_err());
+    ///
+    /// let p = Parser::builder().dialect(LuaDialect::Lua51).build();
+    /// assert_eq!(("goto", LuaValue::integer(1)), p.script(b"goto = 1").unwrap()[0]);
+    /// ```
+    Lua51,
+
+    /// Lua 5.4 with Garry's Mod (GLua) C-style comments, which are treated as whitespace like
+    /// Lua's `--` comments:
+    ///
+    /// * `// line comments`, ending at the next line break
+    /// * `/* block comments */`, which can span multiple lines, but don't nest
+    ///
+    /// GLua's other extensions (like `!=`, `&&` and `continue`) are not supported.
+    ///
+    /// ```rust
+    /// use serde_luaq::{LuaDialect, LuaValue, Parser};
+    ///
+    /// let lua = b"{ /* colour */ r = 255, // red\n g = 0 }";
+    /// assert!(Parser::builder().build().value(lua).is_err());
+    ///
+    /// let v = Parser::builder().dialect(LuaDialect::GLua).build().value(lua).unwrap();
+    /// assert_eq!(Some(&LuaValue::integer(255)), v.pointer("/r").as_deref());
+    /// ```
+    GLua,
+}
+
+/// Values to substitute for function calls, for [`ParseOptions::calls`].
+///
+/// Functions are named by their dotted path, like `os.time` or `GetTime`. Only calls without
+/// arguments (like `os.time()`) are supported.
+#[derive(Debug, Clone, Default)]
+pub struct CallSubstitutions {
+    calls: BTreeMap<String, LuaValue<'static>>,
+}
+
+impl CallSubstitutions {
+    /// Creates an empty set of substitutions.
+    pub const fn new() -> Self {
+        Self {
+            calls: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the value to substitute for calls to the function `name`, returning the previous
+    /// value.
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        value: LuaValue<'static>,
+    ) -> Option<LuaValue<'static>> {
+        self.calls.insert(name.into(), value)
+    }
+
+    /// Gets the value to substitute for calls to the function `name`.
+    pub fn get(&self, name: &str) -> Option<&LuaValue<'static>> {
+        self.calls.get(name)
+    }
+
+    /// Returns `true` if there are no substitutions.
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+}
+
+impl<S: Into<String>> FromIterator<(S, LuaValue<'static>)> for CallSubstitutions {
+    fn from_iter<T: IntoIterator<Item = (S, LuaValue<'static>)>>(iter: T) -> Self {
+        Self {
+            calls: iter.into_iter().map(|(k, v)| (k.into(), v)).collect(),
+        }
+    }
+}
+
+/// Values are compared with [`LuaValue::total_cmp()`], so that this can implement [`Eq`].
+impl PartialEq for CallSubstitutions {
+    fn eq(&self, other: &Self) -> bool {
+        self.calls.len() == other.calls.len()
+            && self
+                .calls
+                .iter()
+                .zip(other.calls.iter())
+                .all(|(a, b)| a.0 == b.0 && a.1.total_cmp(b.1).is_eq())
+    }
+}
+
+impl Eq for CallSubstitutions {}
+
+/// A reusable Lua parser, with a fixed set of [`ParseOptions`].
+///
+/// This is an alternative to the free functions ([`lua_value()`], [`return_statement()`],
+/// [`script()`] and [`script_with_options()`]), which allows options to be set up
+/// once and reused across many calls.
+///
+/// Use [`Parser::builder()`] to create one.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{LuaDialect, LuaValue, Parser};
+///
+/// let parser = Parser::builder()
+///     .max_depth(64)
+///     .max_statements(1000)
+///     .dialect(LuaDialect::Lua54)
+///     .build();
+///
+/// assert_eq!(LuaValue::Boolean(true), parser.value(b"true").unwrap());
+/// assert_eq!(LuaValue::integer(1), parser.ret(b"return 1").unwrap());
+/// assert_eq!(("a", LuaValue::integer(1)), parser.script(b"a = 1").unwrap()[0]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parser {
+    opts: ParseOptions,
+}
+
+impl Parser {
+    /// Creates a builder for a [`Parser`].
+    pub const fn builder() -> ParserBuilder {
+        ParserBuilder::new()
+    }
+
+    /// Returns the options used by this parser.
+    pub const fn options(&self) -> &ParseOptions {
+        &self.opts
+    }
+
+    /// Parses a bare Lua value expression, like [`lua_value()`].
+    pub fn value<'a>(&self, b: &'a [u8]) -> Result<LuaValue<'a>, Error> {
+        let b = strip_footer(b, &self.opts)?;
+        let hooks = Hooks::new(self.opts.cancellation.as_ref());
+        hooks.finish(lua::lua_value(b, &self.opts, &hooks))
+    }
+
+    /// Parses a single `return` statement, like [`return_statement()`].
+    ///
+    /// Multiple values are returned as a sequence table, which can't be told apart from returning
+    /// a table. Use [`ret_values()`][Self::ret_values] to get each value separately.
+    pub fn ret<'a>(&self, b: &'a [u8]) -> Result<LuaValue<'a>, Error> {
+        let b = strip_footer(b, &self.opts)?;
+        let hooks = Hooks::new(self.opts.cancellation.as_ref());
+        hooks.finish(lua::return_statement(b, &self.opts, &hooks))
+    }
+
+    /// Parses a single `return` statement with one or more values, like [`return_values()`].
+    pub fn ret_values<'a>(&self, b: &'a [u8]) -> Result<Vec<LuaValue<'a>>, Error> {
+        let b = strip_footer(b, &self.opts)?;
+        let hooks = Hooks::new(self.opts.cancellation.as_ref());
+        hooks.finish(lua::return_values(b, &self.opts, &hooks))
+    }
+
+    /// Parses a Lua script containing variable assignments, like [`script_with_options()`].
+    pub fn script<'a>(&self, b: &'a [u8]) -> Result<Vec<ScriptStatement<'a>>, Error> {
+        script_with_options(b, &self.opts)
+    }
+
+    /// Parses a Lua script containing variable assignments lazily, one statement at a time, like
+    /// [`script_iter()`].
+    pub fn script_iter<'a, 'o>(&'o self, b: &'a [u8]) -> ScriptIter<'a, 'o> {
+        script_iter(b, &self.opts)
+    }
+
+    /// Parses a Lua script containing variable assignments, followed by an optional `return`
+    /// statement, like [`script_return_with_options()`].
+    pub fn script_return<'a>(&self, b: &'a [u8]) -> Result<ScriptReturn<'a>, Error> {
+        script_return_with_options(b, &self.opts)
+    }
+
+    /// Parses `b` in any [`format`][LuaFormat], salvaging as much data as possible if it was
+    /// truncated part-way through.
+    ///
+    /// Programs which crash while writing their state can leave a file which ends part-way
+    /// through a string or table. If `b` has a syntax error in its last token (or in a string or
+    /// comment which is never closed), this tries closing any strings, comments and tables which
+    /// are still open at the end of the input, then (if that fails) discarding incomplete table
+    /// entries and statements from the end of the input.
+    ///
+    /// Repaired input is parsed into an owned value, and [`Salvaged::truncated`] is set to the
+    /// byte offset where the salvaged data ends. Other errors are returned as-is.
+    ///
+    /// Truncated input won't have a [footer][ParseOptions::footer], so [`Error::MissingFooter`]
+    /// is treated as truncation. The footer isn't checked in repaired input.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{lua_value, LuaFormat, Parser};
+    ///
+    /// let p = Parser::default();
+    /// let s = p.salvage(b"{1, 2, {'abc', 'de", LuaFormat::Value).unwrap();
+    /// assert_eq!(lua_value(b"{1, 2, {'abc', 'de'}}", 16).unwrap(), s.value);
+    /// assert_eq!(Some(18), s.truncated);
+    ///
+    /// // Incomplete table entries are discarded
+    /// let s = p.salvage(b"return {a = 1, b = tr", LuaFormat::Return).unwrap();
+    /// assert_eq!(lua_value(b"{a = 1}", 16).unwrap(), s.value);
+    /// assert_eq!(Some(14), s.truncated);
+    ///
+    /// // Complete input is parsed as usual
+    /// let s = p.salvage(b"{1, 2}", LuaFormat::Value).unwrap();
+    /// assert!(!s.is_truncated());
+    ///
+    /// // Other syntax errors aren't repaired
+    /// assert!(p.salvage(b"{1 2}", LuaFormat::Value).is_err());
+    /// ```
+    pub fn salvage<'a>(&self, b: &'a [u8], format: LuaFormat) -> Result<Salvaged<'a>, Error> {
+        salvage(self, b, format)
+    }
+
+    /// Parses `b` in any [`format`][LuaFormat], returning the value (if any) and every error and
+    /// warning about the input, with their spans.
+    ///
+    /// This is the single entry point for editors, linters and other tools which show problems to
+    /// a person, rather than rejecting bad input:
+    ///
+    /// - If the input parses, this returns the value, and warnings about input which is valid but
+    ///   probably a mistake, like [duplicate table keys][crate::DiagnosticKind::DuplicateKey] and
+    ///   [variables assigned more than once][crate::DiagnosticKind::DuplicateVariable].
+    ///
+    /// - If the input doesn't parse, this returns [the error][crate::DiagnosticKind::Error]. If the input
+    ///   looks [truncated][crate::DiagnosticKind::Truncated], this also returns the value
+    ///   [salvaged][Self::salvage] from it.
+    ///
+    /// Diagnostics are sorted by their position in the input.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{lua_value, DiagnosticKind, LuaFormat, Parser, Severity};
+    ///
+    /// let p = Parser::default();
+    /// let (value, diagnostics) = p.parse_with_diagnostics(b"a = 1\nb = {2, [1] = 3}\na = 4", LuaFormat::Script);
+    /// assert!(value.is_some());
+    /// assert_eq!(2, diagnostics.len());
+    /// assert_eq!(DiagnosticKind::DuplicateKey { key: "[1]".to_string() }, diagnostics[0].kind);
+    /// assert_eq!(14..21, diagnostics[0].span);
+    /// assert_eq!(DiagnosticKind::DuplicateVariable { name: "a".to_string() }, diagnostics[1].kind);
+    /// assert_eq!(23..28, diagnostics[1].span);
+    /// assert!(diagnostics.iter().all(|d| d.severity() == Severity::Warning));
+    ///
+    /// // Truncated input is salvaged
+    /// let (value, diagnostics) = p.parse_with_diagnostics(b"{1, 2, {'abc', 'de", LuaFormat::Value);
+    /// assert_eq!(lua_value(b"{1, 2, {'abc', 'de'}}", 16).ok(), value);
+    /// assert_eq!(Severity::Error, diagnostics[0].severity());
+    /// assert_eq!(DiagnosticKind::Truncated, diagnostics[1].kind);
+    /// assert_eq!(18..18, diagnostics[1].span);
+    ///
+    /// // Other syntax errors aren't
+    /// let (value, diagnostics) = p.parse_with_diagnostics(b"{1 2}", LuaFormat::Value);
+    /// assert_eq!(None, value);
+    /// assert_eq!(1, diagnostics.len());
+    /// assert_eq!(3..4, diagnostics[0].span);
+    /// ```
+    pub fn parse_with_diagnostics<'a>(
+        &self,
+        b: &'a [u8],
+        format: LuaFormat,
+    ) -> (Option<LuaValue<'a>>, Vec<Diagnostic>) {
+        diagnose(self, b, format)
+    }
+
+    /// Parses `b` in any [`format`][LuaFormat].
+    pub(crate) fn parse<'a>(&self, b: &'a [u8], format: LuaFormat) -> Result<LuaValue<'a>, Error> {
+        self.parse_with_hooks(b, format, &Hooks::new(self.opts.cancellation.as_ref()))
+    }
+
+    /// Parses `b` in any [`format`][LuaFormat], with `hooks`.
+    pub(crate) fn parse_with_hooks<'a>(
+        &self,
+        b: &'a [u8],
+        format: LuaFormat,
+        hooks: &Hooks<'_>,
+    ) -> Result<LuaValue<'a>, Error> {
+        Ok(match format {
+            LuaFormat::Value => {
+                let b = strip_footer(b, &self.opts)?;
+                hooks.finish(lua::lua_value(b, &self.opts, hooks))?
+            }
+            LuaFormat::Script => script_with_hooks(b, &self.opts, hooks)?
+                .into_iter()
+                .collect(),
+            LuaFormat::Return => {
+                let b = strip_footer(b, &self.opts)?;
+                hooks.finish(lua::return_statement(b, &self.opts, hooks))?
+            }
+            LuaFormat::ScriptReturn => script_return_with_hooks(b, &self.opts, hooks)?.into_value(),
+        })
+    }
+
+    /// Parses `b` in any [`format`][LuaFormat], and returns [counters][ParseMetrics] describing
+    /// how much work the parser did, even if parsing failed.
+    ///
+    /// This is useful for testing that the parser doesn't take excessive time on adversarial
+    /// inputs.
+    ///
+    /// _Only available with the `metrics` feature._
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{LuaFormat, Parser};
+    ///
+    /// let p = Parser::default();
+    /// let (v, metrics) = p.parse_with_metrics(b"{1, {2, {3}}}", LuaFormat::Value);
+    /// assert!(v.is_ok());
+    /// assert_eq!(3, metrics.max_depth);
+    /// assert_eq!(0, metrics.backtracks);
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn parse_with_metrics<'a>(
+        &self,
+        b: &'a [u8],
+        format: LuaFormat,
+    ) -> (Result<LuaValue<'a>, Error>, ParseMetrics) {
+        let hooks = Hooks::new(self.opts.cancellation.as_ref());
+        let r = self.parse_with_hooks(b, format, &hooks);
+        (r, hooks.metrics.metrics())
+    }
+
+    /// Parses a bare Lua value expression like [`value()`][Self::value], calling `progress` about
+    /// every `interval` bytes of input.
+    ///
+    /// `progress` is called between table entries, so it may be called less often when parsing
+    /// large strings. If `progress` returns [`ControlFlow::Break`], parsing stops and returns
+    /// [`Error::Cancelled`].
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{Error, Parser};
+    /// use std::ops::ControlFlow;
+    ///
+    /// let p = Parser::default();
+    /// let lua = b"{1, 2, 3, 4, 5, 6, 7, 8, 9, 10}";
+    ///
+    /// let mut positions = Vec::new();
+    /// p.value_with_progress(lua, 10, |progress| {
+    ///     positions.push(progress.position);
+    ///     ControlFlow::Continue(())
+    /// })
+    /// .unwrap();
+    /// assert_eq!(vec![12, 24], positions);
+    ///
+    /// // Cancel after the first call
+    /// assert_eq!(
+    ///     Error::Cancelled,
+    ///     p.value_with_progress(lua, 10, |_| ControlFlow::Break(())).unwrap_err(),
+    /// );
+    /// ```
+    pub fn value_with_progress<'a>(
+        &self,
+        b: &'a [u8],
+        interval: usize,
+        mut progress: impl FnMut(Progress) -> ControlFlow<()>,
+    ) -> Result<LuaValue<'a>, Error> {
+        let b = strip_footer(b, &self.opts)?;
+        let hooks = Hooks::new(self.opts.cancellation.as_ref()).with_progress(
+            b.len(),
+            interval,
+            &mut progress,
+        );
+        hooks.finish(lua::lua_value(b, &self.opts, &hooks))
+    }
+
+    /// Parses a single `return` statement like [`ret()`][Self::ret], calling `progress` about
+    /// every `interval` bytes of input.
+    ///
+    /// See [`value_with_progress()`][Self::value_with_progress] for details.
+    pub fn ret_with_progress<'a>(
+        &self,
+        b: &'a [u8],
+        interval: usize,
+        mut progress: impl FnMut(Progress) -> ControlFlow<()>,
+    ) -> Result<LuaValue<'a>, Error> {
+        let b = strip_footer(b, &self.opts)?;
+        let hooks = Hooks::new(self.opts.cancellation.as_ref()).with_progress(
+            b.len(),
+            interval,
+            &mut progress,
+        );
+        hooks.finish(lua::return_statement(b, &self.opts, &hooks))
+    }
+
+    /// Parses a Lua script containing variable assignments like [`script()`][Self::script],
+    /// calling `progress` about every `interval` bytes of input.
+    ///
+    /// `progress` is called between table entries and after each statement, and
+    /// [`Progress::statements`] counts the statements parsed so far. See
+    /// [`value_with_progress()`][Self::value_with_progress] for details.
+    pub fn script_with_progress<'a>(
+        &self,
+        b: &'a [u8],
+        interval: usize,
+        mut progress: impl FnMut(Progress) -> ControlFlow<()>,
+    ) -> Result<Vec<ScriptStatement<'a>>, Error> {
+        let hooks = Hooks::new(self.opts.cancellation.as_ref()).with_progress(
+            b.len(),
+            interval,
+            &mut progress,
+        );
+        script_with_hooks(b, &self.opts, &hooks)
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        ParserBuilder::new().build()
+    }
+}
+
+impl From<ParseOptions> for Parser {
+    fn from(opts: ParseOptions) -> Self {
+        Self { opts }
+    }
+}
+
+/// Builder for a [`Parser`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParserBuilder {
+    opts: ParseOptions,
+}
+
+impl ParserBuilder {
+    /// Default maximum table depth.
+    pub const DEFAULT_MAX_DEPTH: u16 = 16;
+
+    /// Creates a builder with default options:
+    ///
+    /// * [`max_depth`][Self::max_depth]: [`DEFAULT_MAX_DEPTH`][Self::DEFAULT_MAX_DEPTH]
+    /// * [`max_statements`][Self::max_statements]: no limit
+    /// * [`max_identifier_length`][Self::max_identifier_length]: no limit
+    /// * [`dialect`][Self::dialect]: [`LuaDialect::Lua54`]
+    /// * [`invalid_unicode_escapes`][Self::invalid_unicode_escapes]:
+    ///   [`InvalidUnicodeEscapes::Keep`]
+    /// * [`bare_identifiers`][Self::bare_identifiers]: [`BareIdentifiers::Error`]
+    /// * [`strict_q`][Self::strict_q]: `false`
+    /// * [`field_assignments`][Self::field_assignments]: `false`
+    /// * [`local_assignments`][Self::local_assignments]: `false`
+    /// * [`comments`][Self::comments]: `false`
+    /// * [`decimal_comma`][Self::decimal_comma]: `false`
+    /// * [`cancellation`][Self::cancellation]: [`None`]
+    /// * [`footer`][Self::footer]: [`None`]
+    /// * [`string_concatenation`][Self::string_concatenation]: `false`
+    /// * [`unsigned_integers`][Self::unsigned_integers]: `false`
+    /// * [`empty_as_nil`][Self::empty_as_nil]: `false`
+    /// * [`calls`][Self::calls]: none
+    /// * [`table_capacity`][Self::table_capacity]: [`TableCapacity::Grow`]
+    pub const fn new() -> Self {
+        Self {
+            opts: ParseOptions::new(Self::DEFAULT_MAX_DEPTH),
+        }
+    }
+
+    /// Sets the maximum table depth. See [`ParseOptions::max_depth`].
+    pub const fn max_depth(mut self, max_depth: u16) -> Self {
+        self.opts.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the maximum number of statements in a script. See [`ParseOptions::max_statements`].
+    pub const fn max_statements(mut self, max_statements: usize) -> Self {
+        self.opts.max_statements = Some(max_statements);
+        self
+    }
+
+    /// Sets the maximum length of an identifier, in bytes. See
+    /// [`ParseOptions::max_identifier_length`].
+    pub const fn max_identifier_length(mut self, max_identifier_length: usize) -> Self {
+        self.opts.max_identifier_length = Some(max_identifier_length);
+        self
+    }
+
+    /// Sets the Lua dialect to accept. See [`ParseOptions::dialect`].
+    pub const fn dialect(mut self, dialect: LuaDialect) -> Self {
+        self.opts.dialect = dialect;
+        self
+    }
+
+    /// Sets how to handle `\u{XXX}` escape sequences which aren't Unicode scalar values. See
+    /// [`ParseOptions::invalid_unicode_escapes`].
+    pub const fn invalid_unicode_escapes(mut self, policy: InvalidUnicodeEscapes) -> Self {
+        self.opts.invalid_unicode_escapes = policy;
+        self
+    }
+
+    /// Sets how to handle bare identifiers used as values. See
+    /// [`ParseOptions::bare_identifiers`].
+    pub const fn bare_identifiers(mut self, policy: BareIdentifiers) -> Self {
+        self.opts.bare_identifiers = policy;
+        self
+    }
+
+    /// Sets whether to only accept strings and numbers in the forms written by
+    /// `string.format()`. See [`ParseOptions::strict_q`].
+    pub const fn strict_q(mut self, strict_q: bool) -> Self {
+        self.opts.strict_q = strict_q;
+        self
+    }
+
+    /// Sets whether scripts may assign to fields of tables stored in global variables. See
+    /// [`ParseOptions::field_assignments`].
+    pub const fn field_assignments(mut self, field_assignments: bool) -> Self {
+        self.opts.field_assignments = field_assignments;
+        self
+    }
+
+    /// Sets whether script assignments may be declared `local`. See
+    /// [`ParseOptions::local_assignments`].
+    pub const fn local_assignments(mut self, local_assignments: bool) -> Self {
+        self.opts.local_assignments = local_assignments;
+        self
+    }
+
+    /// Sets whether to capture the comments before script assignments. See
+    /// [`ParseOptions::comments`].
+    pub const fn comments(mut self, comments: bool) -> Self {
+        self.opts.comments = comments;
+        self
+    }
+
+    /// Sets whether to accept a comma as the decimal separator in floats outside of tables. See
+    /// [`ParseOptions::decimal_comma`].
+    pub const fn decimal_comma(mut self, decimal_comma: bool) -> Self {
+        self.opts.decimal_comma = decimal_comma;
+        self
+    }
+
+    /// Sets a token to cancel parsing. See [`ParseOptions::cancellation`].
+    pub fn cancellation(mut self, cancellation: Option<CancellationToken>) -> Self {
+        self.opts.cancellation = cancellation;
+        self
+    }
+
+    /// Sets a callback to validate a comment at the end of the input. See
+    /// [`ParseOptions::footer`].
+    pub fn footer(mut self, footer: FooterCheck) -> Self {
+        self.opts.footer = Some(footer);
+        self
+    }
+
+    /// Sets whether string values may be concatenated from string literals with `..`. See
+    /// [`ParseOptions::string_concatenation`].
+    pub const fn string_concatenation(mut self, string_concatenation: bool) -> Self {
+        self.opts.string_concatenation = string_concatenation;
+        self
+    }
+
+    /// Sets whether decimal integer literals up to [`u64::MAX`] are parsed as (wrapping) integers.
+    /// See [`ParseOptions::unsigned_integers`].
+    pub const fn unsigned_integers(mut self, unsigned_integers: bool) -> Self {
+        self.opts.unsigned_integers = unsigned_integers;
+        self
+    }
+
+    /// Sets whether empty input is parsed as `nil`. See [`ParseOptions::empty_as_nil`].
+    pub const fn empty_as_nil(mut self, empty_as_nil: bool) -> Self {
+        self.opts.empty_as_nil = empty_as_nil;
+        self
+    }
+
+    /// Sets values to substitute for function calls. See [`ParseOptions::calls`].
+    pub fn calls(mut self, calls: CallSubstitutions) -> Self {
+        self.opts.calls = calls;
+        self
+    }
+
+    /// Sets how to reserve capacity for each table's entries. See
+    /// [`ParseOptions::table_capacity`].
+    pub const fn table_capacity(mut self, table_capacity: TableCapacity) -> Self {
+        self.opts.table_capacity = table_capacity;
+        self
+    }
+
+    /// Builds the [`Parser`].
+    pub fn build(self) -> Parser {
+        Parser { opts: self.opts }
+    }
+
+    /// Builds the [`ParseOptions`], for functions which take them directly, like
+    /// [`script_with_options()`].
+    pub fn build_options(self) -> ParseOptions {
+        self.opts
+    }
+}
+
+impl Default for ParserBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
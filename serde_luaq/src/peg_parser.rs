@@ -1,7 +1,15 @@
 //! Peg-based Lua parser.
-use crate::{wrapping_parse_int, LuaNumber, LuaTableEntry, LuaValue, LUA_KEYWORDS};
+// peg adds its own arguments to rules which take arguments, so rules with more than one argument
+// are over clippy's limit.
+#![allow(clippy::too_many_arguments)]
+use crate::{
+    progress::Hooks,
+    script::{FieldAssignment, FieldKey, Returned, Statement},
+    wrapping_parse_int, BareIdentifiers, InvalidUnicodeEscapes, LuaDialect, LuaNumber,
+    LuaTableEntry, LuaValue, ParseOptions, ScriptStatement, TableCapacity, LUA_KEYWORDS,
+};
 use hexfloat2::parse as hexfloat_parse;
-use std::{borrow::Cow, str::from_utf8};
+use std::{borrow::Cow, cell::RefCell, ops::Range, str::from_utf8};
 
 const BELL: Cow<'static, [u8]> = Cow::Borrowed(b"\x07");
 const BACKSPACE: Cow<'static, [u8]> = Cow::Borrowed(b"\x08");
@@ -16,6 +24,7 @@ const BACKSLASH: Cow<'static, [u8]> = Cow::Borrowed(br"\");
 const QUOTATION_MARK: Cow<'static, [u8]> = Cow::Borrowed(b"\"");
 const APOSTROPHE: Cow<'static, [u8]> = Cow::Borrowed(b"'");
 const EMPTY: Cow<'static, [u8]> = Cow::Borrowed(b"");
+const REPLACEMENT_CHARACTER: Cow<'static, [u8]> = Cow::Borrowed("\u{FFFD}".as_bytes());
 
 /// Return a `'static` slice to a given byte.
 ///
@@ -104,13 +113,102 @@ fn merge_spans<'a>(s: Vec<Cow<'a, [u8]>>) -> Cow<'a, [u8]> {
     Cow::Owned(o)
 }
 
+/// If a long bracket (like `[[` or `[==[`) starts at `b[i]`, returns the position of the last byte
+/// of its closing bracket, or `b.len()` if it isn't closed.
+pub(crate) fn long_bracket_end(b: &[u8], i: usize) -> Option<usize> {
+    if b.get(i) != Some(&b'[') {
+        return None;
+    }
+    let level = b[i + 1..].iter().take_while(|&&c| c == b'=').count();
+    if b.get(i + 1 + level) != Some(&b'[') {
+        return None;
+    }
+
+    let mut i = i + 2 + level;
+    while i < b.len()
+        && !(b[i] == b']'
+            && b.get(i + 1 + level) == Some(&b']')
+            && b[i + 1..i + 1 + level].iter().all(|&c| c == b'='))
+    {
+        i += 1;
+    }
+    Some((i + 1 + level).min(b.len()))
+}
+
+/// Counts the entries in the table starting at `b` (just after its opening `{`), without parsing
+/// them, for [`TableCapacity::Count`].
+///
+/// This only looks for separators outside of nested tables, strings and Lua comments, so it can be
+/// confused by invalid input or C-style comments. The result is only used as a hint, and is never more than `b.len()`.
+fn count_table_entries(b: &[u8]) -> usize {
+    let mut count = 0;
+    let mut depth = 0usize;
+    // Whether anything other than whitespace has been seen since the last separator.
+    let mut entry = false;
+    let mut i = 0;
+
+    while i < b.len() {
+        match b[i] {
+            b'{' => {
+                depth += 1;
+                entry = true;
+            }
+            b'}' if depth == 0 => break,
+            b'}' => depth -= 1,
+            b',' | b';' if depth == 0 => {
+                count += usize::from(entry);
+                entry = false;
+            }
+            q @ (b'"' | b'\'') => {
+                i += 1;
+                while i < b.len() && b[i] != q {
+                    if b[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                entry = true;
+            }
+            b'[' => {
+                // Skip long strings: [[...]], [==[...]==]
+                if let Some(end) = long_bracket_end(b, i) {
+                    i = end;
+                }
+                entry = true;
+            }
+            b'-' if b.get(i + 1) == Some(&b'-') => {
+                // Skip comments: -- line, --[[ block ]]
+                i = long_bracket_end(b, i + 2).unwrap_or_else(|| {
+                    b[i..]
+                        .iter()
+                        .position(|&c| c == b'\r' || c == b'\n')
+                        .map_or(b.len(), |p| i + p)
+                });
+            }
+            c if c.is_ascii_whitespace() => (),
+            _ => entry = true,
+        }
+        i += 1;
+    }
+
+    count + usize::from(entry)
+}
+
 peg::parser! {
-    pub grammar lua() for [u8] {
+    pub grammar lua(opts: &ParseOptions, hooks: &Hooks<'_>) for [u8] {
         rule identifier() -> &'input str
             = (
+                enter() pos:position!()
                 i:$([ b'a'..=b'z' | b'A'..=b'Z' | b'_' ][ b'a'..=b'z' | b'A'..=b'Z' | b'_' | b'0'..=b'9' ]*)
+                leave()
                 {?
-                    if LUA_KEYWORDS.binary_search(&i).is_ok() {
+                    if let Some(limit) = opts.max_identifier_length.filter(|l| i.len() > *l) {
+                        hooks.identifier_too_long(limit, pos);
+                        Err("shorter identifier")
+                    } else if LUA_KEYWORDS.binary_search(&i).is_ok()
+                        // goto was added in Lua 5.2
+                        && !(opts.dialect == LuaDialect::Lua51 && i == b"goto")
+                    {
                         Err("identifier cannot be a reserved word")
                     } else {
                         // from_utf8 shouldn't error here
@@ -122,8 +220,28 @@ peg::parser! {
 
         rule whitespace()
             = quiet!{[ b' ' | b'\n' | b'\t' | b'\r' | b'\x0b' | b'\x0c' ]}
+            / quiet!{comment()}
+            / quiet!{c_comment()}
             / expected!("whitespace")
 
+        /// Matches a Lua comment: `-- line`, ending at the next line break, or `--[[ block ]]`,
+        /// with long brackets of any level (`--[==[ block ]==]`).
+        rule comment()
+            = "--" (
+                "[" level:$("="*) "["
+                (!("]" "="*<{level.len()}> "]") [_])*
+                "]" "="*<{level.len()}> "]"
+                / !("[" "="* "[") [^ b'\r' | b'\n']*
+            )
+
+        /// Matches a C-style comment (`// line` or `/* block */`), if allowed by the
+        /// [dialect][ParseOptions::dialect].
+        rule c_comment()
+            = &"/" allowed(opts.dialect == LuaDialect::GLua) (
+                "//" [^ b'\r' | b'\n']*
+                / "/*" (!"*/" [_])* "*/"
+            )
+
         /// Match any number of whitespace characters (including zero).
         rule _ = whitespace()*
 
@@ -149,8 +267,95 @@ peg::parser! {
             = $(quiet!{ hex_digit()+ })
             / expected!("hex digits")
 
-        /// Parse a numeric value.
+        /// Parse a numeric value, restricted to `%q` syntax in
+        /// [strict `%q` mode][ParseOptions::strict_q].
         rule numbers() -> LuaNumber
+            = foreign_numerals()
+            / allowed(opts.strict_q) n:q_numbers() { n }
+            / !allowed(opts.strict_q) n:any_numbers() { n }
+            / !allowed(opts.strict_q) n:negated_number() { n }
+
+        /// Recognises numeric literal syntax from other Lua dialects, to report what was found
+        /// rather than a generic syntax error. This never matches.
+        ///
+        /// None of these are accepted by any [`LuaDialect`][crate::LuaDialect].
+        rule foreign_numerals() -> LuaNumber
+            = quiet!{ [ b'-' ]? "0" [ b'b' | b'B' ] [ b'0' | b'1' | b'_' ]+ }
+              {? Err("Lua 5.4 number (binary literals like 0b101 are a Luau extension)") }
+            / quiet!{
+                [ b'-' ]?
+                ( "0" [ b'x' | b'X' ] [ b'0'..=b'9' | b'a'..=b'f' | b'A'..=b'F' ]+ / [ b'0'..=b'9' ]+ )
+                ( "ULL" / "ull" / "LL" / "ll" )
+              }
+              {? Err("Lua 5.4 number (LL and ULL suffixes are a LuaJIT extension)") }
+            / quiet!{
+                [ b'-' ]?
+                [ b'0'..=b'9' ]+
+                ( "." [ b'0'..=b'9' ]* )?
+                ( [ b'e' | b'E' ] [ b'+' | b'-' ]? [ b'0'..=b'9' ]+ )?
+                [ b'i' | b'I' ]
+              }
+              {? Err("Lua 5.4 number (imaginary numbers like 12.5i are a LuaJIT extension)") }
+            / quiet!{
+                [ b'-' ]?
+                !( "0" [ b'b' | b'B' ] )
+                ( "0" [ b'x' | b'X' ] / [ b'0'..=b'9' ] )
+                [ b'0'..=b'9' | b'a'..=b'f' | b'A'..=b'F' ]*
+                "_"
+                [ b'0'..=b'9' | b'a'..=b'f' | b'A'..=b'F' | b'_' ]*
+              }
+              {? Err("Lua 5.4 number (digit separators like 1_000 are a Luau extension)") }
+
+        /// Parse unary minus applied to a numeric literal, with optional whitespace (`- 5`) or
+        /// parentheses (`-(5)`).
+        ///
+        /// This only allows a single level of parentheses, and no other expressions. `--` starts a
+        /// comment, so isn't a double negation.
+        rule negated_number() -> LuaNumber
+            = "-" !"-" _ n:(
+                "(" _ n:any_numbers() _ ")" { n } /
+                any_numbers()
+            )
+            {
+                match n {
+                    // Like Lua, -math.mininteger == math.mininteger
+                    LuaNumber::Integer(i) => LuaNumber::Integer(i.wrapping_neg()),
+                    LuaNumber::Float(f) => LuaNumber::Float(-f),
+                }
+            }
+
+        /// Parse a numeric value in `string.format` output syntax: `%d` and `%q` for integers,
+        /// `%.17g`, `%a` and `%q` for floats.
+        rule q_numbers() -> LuaNumber
+            = &(
+                (
+                    "-1e9999" / "1e9999" / "(0/0)" /
+                    // %q of math.mininteger
+                    "0x8000000000000000" /
+                    // %a (lower case only)
+                    (
+                        [ b'-' ]?
+                        "0x"
+                        [ b'0'..=b'9' | b'a'..=b'f' ]
+                        ( "." [ b'0'..=b'9' | b'a'..=b'f' ]+ )?
+                        "p" [ b'+' | b'-' ] digit()+
+                    ) /
+                    // %d, %.17g
+                    (
+                        [ b'-' ]?
+                        digit()+
+                        ( "." digit()+ )?
+                        ( [ b'e' ] [ b'+' | b'-' ] digit()+ )?
+                    )
+                )
+                // Don't match a prefix of a longer literal.
+                ![ b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'_' | b'.' ]
+            )
+            n:any_numbers() { n }
+            / expected!("%q number")
+
+        /// Parse a numeric value in any syntax.
+        rule any_numbers() -> LuaNumber
             = (
                 "-1e9999" { LuaNumber::Float(f64::NEG_INFINITY) } /
                 "1e9999" { LuaNumber::Float(f64::INFINITY) } /
@@ -269,6 +474,13 @@ peg::parser! {
 
                         if let Ok(n) = src.parse() {
                             Ok(LuaNumber::Integer(n))
+                        } else if let Some(n) = opts
+                            .unsigned_integers
+                            .then(|| src.parse::<u64>().ok())
+                            .flatten()
+                        {
+                            // Wrap around, like hex literals
+                            Ok(LuaNumber::Integer(n as i64))
                         } else if let Ok(f) = src.parse() {
                             // Coerce to float
                             Ok(LuaNumber::Float(f))
@@ -346,6 +558,15 @@ peg::parser! {
                         _ => return Err("UTF-8 value too large"),
                     };
 
+                    if codepoint > 0x10FFFF || (0xD800..=0xDFFF).contains(&codepoint) {
+                        // Not a Unicode scalar value, so can't be represented in RFC 3629 UTF-8.
+                        match opts.invalid_unicode_escapes {
+                            InvalidUnicodeEscapes::Keep => (),
+                            InvalidUnicodeEscapes::Replace => return Ok(REPLACEMENT_CHARACTER),
+                            InvalidUnicodeEscapes::Error => return Err("Unicode scalar value"),
+                        }
+                    }
+
                     // Encode value as RFC 2279 UTF-8.
                     // https://github.com/lua/lua/blob/9a3940380a2a1540dc500593a6de0c1c5e6feb69/lobject.c#L392
                     let mut mfb = 0x3f;
@@ -391,6 +612,27 @@ peg::parser! {
                 merge_spans(s)
             }
 
+        /// Parses the body of a short string, without its delimiters.
+        pub rule short_string_body() -> Cow<'input, [u8]>
+            = s:(
+                c:$([^ b'\\' | b'\r' | b'\n' ]+) { c.into() }
+                / escaped_char()
+            )* {
+                merge_spans(s)
+            }
+
+        /// Parses the body of a long string at `level`, without its delimiters.
+        pub rule long_string_body(level: usize) -> Cow<'input, [u8]>
+            =
+                linebreak()?
+                v:$(
+                    (
+                        !("]" "="*<{level}> "]")
+                        [_]
+                    )+
+                )?
+                { v.map(Cow::Borrowed).unwrap_or(EMPTY) }
+
         // TODO: find a way to make this work with arbitrary levels.
         rule longer_string(level: usize) -> Cow<'input, [u8]>
             =
@@ -418,8 +660,44 @@ peg::parser! {
                 "]]"
                 { v.map(Cow::Borrowed).unwrap_or(EMPTY) }
 
-        /// Parses a string.
+        /// Parses a string, restricted to `%q` syntax in
+        /// [strict `%q` mode][ParseOptions::strict_q].
         rule string() -> Cow<'input, [u8]>
+            = allowed(opts.strict_q) s:q_string() { s }
+            / !allowed(opts.strict_q) s:any_string() { s }
+
+        /// Parses a decimal escape sequence, escaped quotation mark, backslash or line feed, or
+        /// (like Lua 5.1) carriage return, as written by `%q`.
+        rule q_escaped_char() -> Cow<'static, [u8]>
+            = (
+                r"\\" { BACKSLASH } /
+                "\\\"" { QUOTATION_MARK } /
+                "\\\n" { UNIX_LINEFEED } /
+                r"\r" { CARRIAGE_RETURN } /
+                r"\" x:$(digit()*<1,3>) {?
+                    // from_utf8 shouldn't error
+                    let x = from_utf8(x).unwrap();
+
+                    if let Ok(x) = x.parse() {
+                        Ok(slice_of_byte(x))
+                    } else {
+                        Err("decimal escape too large")
+                    }
+                } /
+                expected!("%q escape sequence")
+            )
+
+        /// Parses a double-quoted string written by `%q`, which escapes all control characters.
+        rule q_string() -> Cow<'input, [u8]>
+            = "\"" s:(
+                c:$([^ b'"' | b'\\' | b'\x00'..=b'\x1f' | b'\x7f' ]+) { c.into() }
+                / q_escaped_char()
+            )* "\"" {
+                merge_spans(s)
+            }
+
+        /// Parses a string in any syntax.
+        rule any_string() -> Cow<'input, [u8]>
             =
                 single_quoted_string() /
                 double_quoted_string() /
@@ -430,80 +708,193 @@ peg::parser! {
                 longer_string(4) /
                 longer_string(5)
 
+        /// Parses a string value, which may be a
+        /// [concatenation of strings][ParseOptions::string_concatenation].
+        rule string_value() -> Cow<'input, [u8]>
+            = allowed(opts.string_concatenation) s:(string() ++ (_ ".." _)) {
+                // Concatenated strings are never longer than the input, so this can't overflow.
+                merge_spans(s)
+            }
+            / string()
+
         rule boolean() -> bool
             = (
                 "true" { true } /
                 "false" { false }
             )
 
-        /// Parse a bare Lua value expression as a [`LuaValue`].
-        ///
-        /// The value _may_ be preceeded or followed by whitespace.
-        ///
-        /// For more details about type mapping rules and parameters,
-        /// [see the crate docs][crate#data-types].
-        ///
-        /// ## Example
-        ///
-        /// ```rust
-        /// use serde_luaq::{lua_value, LuaValue};
-        ///
-        /// assert_eq!(LuaValue::Boolean(true), lua_value(b"true", 16).unwrap());
-        /// assert_eq!(LuaValue::Boolean(false), lua_value(b"  false\r\n  ", 16).unwrap());
-        /// ```
-        ///
-        /// For more information about Lua type conversion, see [`LuaValue`].
-        pub rule lua_value(max_depth: u16) -> LuaValue<'input>
-            = _ v:(
-                "nil" { LuaValue::Nil } /
-                b:boolean() { LuaValue::Boolean(b) } /
-                n:numbers() { LuaValue::Number(n) } /
-                s:string() { LuaValue::String(s) } /
+        /// Parses a bare Lua value expression, which _may_ be surrounded by whitespace.
+        pub rule lua_value() -> LuaValue<'input>
+            = _ v:top_value(opts.max_depth) _ { v }
+            / allowed(opts.empty_as_nil) _ { LuaValue::Nil }
+
+        /// A Lua value which is not inside of a table, without any surrounding whitespace.
+        rule top_value(max_depth: u16) -> LuaValue<'input>
+            = allowed(opts.decimal_comma) n:comma_float() { LuaValue::Number(n) }
+            / bare_value(max_depth)
+
+        /// Parse a float with a comma decimal separator (`1,5`).
+        rule comma_float() -> LuaNumber
+            = n:$(
+                [ b'-' ]?
+                digit()+ "," digit()+
+                ( [ b'e' | b'E' ] [ b'+' | b'-' ]? digit()+ )?
+            )
+            {?
+                // from_utf8 shouldn't error
+                let src = from_utf8(n).unwrap().replacen(',', ".", 1);
+                if let Ok(f) = str::parse(&src) {
+                    Ok(LuaNumber::Float(f))
+                } else {
+                    Err("floating point parse error")
+                }
+            }
+
+        /// A Lua value, which _may_ be surrounded by whitespace.
+        rule value(max_depth: u16) -> LuaValue<'input>
+            = _ v:bare_value(max_depth) _ { v }
+
+        /// A function call without arguments, like `os.time()`, which is replaced with its
+        /// [substitution][ParseOptions::calls].
+        rule call() -> LuaValue<'input>
+            = names:(identifier() ++ (_ "." _)) _ "(" _ ")"
+            {?
+                match opts.calls.get(&names.join(".")) {
+                    Some(v) => Ok(v.clone()),
+                    None => Err("known function call"),
+                }
+            }
+
+        /// A Lua value without any surrounding whitespace.
+        rule bare_value(max_depth: u16) -> LuaValue<'input>
+            = enter() v:(
+                scalar_value() /
                 t:table(max_depth) { LuaValue::Table(t) } /
                 expected!("Lua value")
-            ) _ { v }
+            ) leave() { v }
 
-        rule table_entry(max_depth: u16) -> LuaTableEntry<'input>
-            = _ v:(
-                // foo = "bar"
-                key:identifier() _ "=" _ val:lua_value(max_depth)
-                {
-                    LuaTableEntry::NameValue(Box::new((Cow::Borrowed(key), val)))
-                } /
+        /// A Lua value which isn't a table.
+        rule scalar_value() -> LuaValue<'input>
+            = allowed(!opts.calls.is_empty()) v:call() { v }
+            / allowed(opts.bare_identifiers == BareIdentifiers::String) i:identifier() { LuaValue::String(Cow::Borrowed(i.as_bytes())) }
+            / "nil" { LuaValue::Nil }
+            / b:boolean() { LuaValue::Boolean(b) }
+            / n:numbers() { LuaValue::Number(n) }
+            / s:string_value() { LuaValue::String(s) }
 
-                // nil
-                "nil" {
-                    LuaTableEntry::NilValue
-                } /
+        /// Records that the parser is starting to parse something at this position, for
+        /// [metrics][crate::metrics::Recorder].
+        rule enter()
+            = #{|_, pos| {
+                hooks.metrics.enter(pos);
+                peg::RuleResult::Matched(pos, ())
+            }}
 
-                // true or false
-                val:boolean() {
-                    LuaTableEntry::BooleanValue(val)
-                } /
+        /// Records that the parser has parsed something up to this position, for
+        /// [metrics][crate::metrics::Recorder].
+        rule leave()
+            = #{|_, pos| {
+                hooks.metrics.leave(pos);
+                peg::RuleResult::Matched(pos, ())
+            }}
 
-                // 1234
-                val:numbers() {
-                    LuaTableEntry::NumberValue(val)
-                } /
+        /// Calls [progress hooks][Hooks], and fails if the parse has been cancelled.
+        rule checkpoint()
+            = #{|_, pos| {
+                if hooks.checkpoint(pos) {
+                    peg::RuleResult::Matched(pos, ())
+                } else {
+                    peg::RuleResult::Failed
+                }
+            }}
+
+        /// Like [`checkpoint`], after parsing a script statement.
+        rule statement_checkpoint()
+            = #{|_, pos| {
+                if hooks.statement(pos) {
+                    peg::RuleResult::Matched(pos, ())
+                } else {
+                    peg::RuleResult::Failed
+                }
+            }}
+
+        rule table_entry(max_depth: u16) -> (LuaTableEntry<'input>, Range<usize>)
+            = checkpoint() _ enter() start:position!() v:(
+                name_table_entry(max_depth) /
+                scalar_table_entry() /
 
                 // "foo"
-                val:lua_value(max_depth)
+                val:value(max_depth)
                 {
                     LuaTableEntry::Value(Box::new(val))
                 } /
 
-                // ["foo"]="bar"
-                // [1234]="bar"
-                "[" key:lua_value(max_depth) _ "]" _ "=" _ val:lua_value(max_depth)
-                {
-                    LuaTableEntry::KeyValue(Box::new((key, val)))
-                } /
-
+                key_table_entry(max_depth) /
                 expected!("Lua table entry")
-            ) _ { v }
+            ) end:position!() leave() _ { (v, start..end) }
+
+        /// A table entry with a name key, like `foo = "bar"`.
+        rule name_table_entry(max_depth: u16) -> LuaTableEntry<'input>
+            = key:identifier() _ "=" _ val:value(max_depth)
+            {
+                LuaTableEntry::NameValue(Box::new((Cow::Borrowed(key), val)))
+            }
+
+        /// A table entry with a key in brackets, like `["foo"] = "bar"` or `[1234] = "bar"`.
+        rule key_table_entry(max_depth: u16) -> LuaTableEntry<'input>
+            = "[" key:value(max_depth) _ "]" _ "=" _ val:value(max_depth)
+            {
+                LuaTableEntry::KeyValue(Box::new((key, val)))
+            }
+
+        /// A table entry with an implicit key, whose value is stored inline.
+        rule scalar_table_entry() -> LuaTableEntry<'input>
+            // nil
+            = "nil" {
+                LuaTableEntry::NilValue
+            }
+
+            // true or false
+            / val:boolean() {
+                LuaTableEntry::BooleanValue(val)
+            }
+
+            // 1234
+            / val:numbers() {
+                LuaTableEntry::NumberValue(val)
+            }
 
         rule table_entries(max_depth: u16) -> Vec<LuaTableEntry<'input>>
-            = entries:table_entry(max_depth) ** ([b',' | b';'])
+            = entries:table_vec() spans:span_vec()
+              (e:table_entry(max_depth) {
+                  entries.borrow_mut().push(e.0);
+                  if hooks.diagnostics.is_enabled() {
+                      spans.borrow_mut().push(e.1);
+                  }
+              }) ** ([b',' | b';'])
+              {
+                  let entries = entries.into_inner();
+                  if hooks.diagnostics.is_enabled() {
+                      hooks.diagnostics.table(&entries, &spans.into_inner());
+                  }
+                  entries
+              }
+
+        /// Creates an empty `Vec` for the spans of a table's entries, which are only recorded
+        /// when collecting diagnostics.
+        rule span_vec() -> RefCell<Vec<Range<usize>>>
+            = #{|_, pos| peg::RuleResult::Matched(pos, RefCell::default())}
+
+        /// Creates an empty `Vec` for a table's entries, with capacity reserved according to
+        /// [`ParseOptions::table_capacity`].
+        rule table_vec() -> RefCell<Vec<LuaTableEntry<'input>>>
+            = #{|input, pos| {
+                let v = match opts.table_capacity {
+                    TableCapacity::Grow => Vec::new(),
+                    TableCapacity::Count => Vec::with_capacity(count_table_entries(&input[pos..])),
+                };
+                peg::RuleResult::Matched(pos, RefCell::new(v))
+            }}
 
         rule table(max_depth: u16) -> Vec<LuaTableEntry<'input>>
             =
@@ -513,6 +904,7 @@ peg::parser! {
                     if max_depth == 0 {
                         Err("too deeply nested")
                     } else {
+                        hooks.metrics.depth(opts.max_depth.saturating_sub(max_depth) + 1);
                         Ok(())
                     }
                 })
@@ -525,48 +917,204 @@ peg::parser! {
                 _
                 "}" { e }
 
-        rule assignment(max_depth: u16) -> (&'input str, LuaValue<'input>)
-            = i:identifier() _ "=" _ v:lua_value(max_depth) { (i, v) }
+        rule assignment(max_depth: u16) -> ScriptStatement<'input>
+            = start:position!() local:$(local())? name:identifier() _ "=" _ value:top_value(max_depth)
+              end:position!()
+            {
+                ScriptStatement {
+                    name,
+                    value,
+                    span: start..end,
+                    index: 0,
+                    local: local.is_some(),
+                    comment: None,
+                }
+            }
+
+        /// Matches the `local` keyword before an assignment, if
+        /// [local assignments][ParseOptions::local_assignments] are allowed.
+        rule local()
+            = allowed(opts.local_assignments) "local" __
+
+        /// A field of a table being assigned to: `.name` or `[key]`.
+        rule field_key(max_depth: u16) -> FieldKey<'input>
+            = "." _ name:identifier() { FieldKey::Name(name) }
+            / "[" key:value(max_depth) "]" { FieldKey::Value(key) }
+
+        rule statement(max_depth: u16) -> Statement<'input>
+            = enter() s:(
+                a:assignment(max_depth) { Statement::Global(a) }
+                / allowed(opts.field_assignments)
+                  name:identifier() keys:(_ k:field_key(max_depth) { k })+
+                  _ "=" _ value:top_value(max_depth)
+                {
+                    Statement::Field(FieldAssignment { name, keys, value })
+                }
+            ) leave() { s }
+
+        /// Parses a Lua script containing variable assignments.
+        pub rule script() -> Vec<Statement<'input>>
+            = i:input() s:(_ a:statement(opts.max_depth) end:position!() _ (";" _)* statement_checkpoint() { (a, end) })* _
+            {
+                Statement::finish(i, opts, s)
+            }
+
+        /// Parse a Lua script like [`script`], stopping after `max_statements` statements.
+        ///
+        /// Returns `true` if there are more statements after the limit. The remainder of the input
+        /// is not parsed.
+        pub rule script_limited(max_statements: usize) -> (Vec<Statement<'input>>, bool)
+            = i:input() s:(_ a:statement(opts.max_depth) end:position!() _ (";" _)* statement_checkpoint() { (a, end) })*<,{max_statements}>
+              _ more:(&(local()? identifier() _ ("=" / "." / "[")) [_]*)?
+            {?
+                if more.is_some() && s.len() < max_statements {
+                    // Not at the limit yet, so this is a syntax error in the next statement.
+                    return Err("Lua value");
+                }
+
+                Ok((Statement::finish(i, opts, s), more.is_some()))
+            }
+
+        /// Parses the next global variable assignment in a script, starting at byte `start`, for
+        /// [`ScriptIter`][crate::ScriptIter].
+        ///
+        /// Returns the statement and the position of the next one, or [`None`] at the end of the
+        /// script.
+        pub rule next_assignment(start: usize) -> Option<(ScriptStatement<'input>, usize)>
+            = skip_to(start) _ a:assignment(opts.max_depth) _ (";" _)* statement_checkpoint()
+              next:position!() rest()
+            { Some((a, next)) }
+            / skip_to(start) _ { None }
+
+        /// Matches a comment at byte `start`, returning the position of its end.
+        pub rule comment_end(start: usize) -> usize
+            = skip_to(start) (comment() / c_comment()) p:position!() rest() { p }
+
+        /// Matches only whitespace from byte `start` to the end of the input.
+        pub rule end_of_script(start: usize)
+            = skip_to(start) _
+
+        /// Matches the name of the assignment at byte `start`, for [`Parser::sample()`].
+        ///
+        /// Returns the name and the position of its value, or [`None`] at the end of the script
+        /// (or at a `return` statement, if `returns` is set).
+        ///
+        /// [`Parser::sample()`]: crate::Parser::sample
+        pub rule sample_assignment(start: usize, returns: bool) -> Option<(&'input str, usize)>
+            = skip_to(start) _ local()? name:identifier() _ "=" p:position!() rest()
+            { Some((name, p)) }
+            / skip_to(start) _ allowed(returns) "return" __ rest() { None }
+            / skip_to(start) _ { None }
 
-        /// Parse a Lua script containing variable assignments into a [`Vec`] of
-        /// `(&str, LuaValue)`.
+        /// Parses the value of the assignment at byte `start`, for [`Parser::sample()`].
         ///
-        /// For more details about type mapping rules and parameters,
-        /// [see the crate docs][crate#data-types].
+        /// Returns the value and the position of the next statement.
         ///
-        /// ## Example
+        /// [`Parser::sample()`]: crate::Parser::sample
+        pub rule sample_assignment_value(start: usize) -> (LuaValue<'input>, usize)
+            = skip_to(start) _ v:top_value(opts.max_depth) _ (";" _)* statement_checkpoint()
+              p:position!() rest()
+            { (v, p) }
+
+        /// Matches a `return` statement, for [`Parser::sample()`].
+        ///
+        /// Returns the position of its value.
         ///
-        /// ```rust
-        /// use serde_luaq::{script, LuaValue};
+        /// [`Parser::sample()`]: crate::Parser::sample
+        pub rule sample_return() -> usize
+            = _ "return" __ p:position!() rest() { p }
+
+        /// Matches the start of a table at byte `start`, with `max_depth` levels of nesting
+        /// remaining, for [`Parser::sample()`].
         ///
-        /// assert_eq!(
-        ///     vec![
-        ///         ("hello", LuaValue::Boolean(true)),
-        ///         ("goodbye", LuaValue::Boolean(false)),
-        ///     ],
-        ///     script(b"hello = true\ngoodbye = false", 16).unwrap()
-        /// );
-        /// ```
+        /// Returns the position of its first entry, or [`None`] if the value isn't a table. Other
+        /// values aren't checked.
         ///
-        /// For more information about Lua type conversion, see [`LuaValue`].
-        pub rule script(max_depth: u16) -> Vec<(&'input str, LuaValue<'input>)>
-            = (_ a:assignment(max_depth) _ (";" _)* { a })*
+        /// [`Parser::sample()`]: crate::Parser::sample
+        pub rule sample_table(start: usize, max_depth: u16) -> Option<usize>
+            = skip_to(start) _
+              ("{" {? if max_depth == 0 { Err("too deeply nested") } else { Ok(()) } })
+              p:position!() rest()
+            { Some(p) }
+            / skip_to(start) _ !"{" rest() { None }
 
-        /// Parse a Lua `return` stamement into a [`LuaValue`].
+        /// Matches the key of the table entry at byte `start`, for [`Parser::sample()`].
         ///
-        /// For more details about type mapping rules and parameters,
-        /// [see the crate docs][crate#data-types].
+        /// Returns the key ([`None`] for implicit keys) and the position of the entry's value, or
+        /// [`None`] at the end of the table.
         ///
-        /// ## Example
+        /// [`Parser::sample()`]: crate::Parser::sample
+        pub rule sample_key(start: usize, max_depth: u16)
+            -> Option<(Option<FieldKey<'input>>, usize)>
+            = skip_to(start) _ "}" rest() { None }
+            / skip_to(start) _ checkpoint() k:identifier() _ "=" p:position!() rest()
+              { Some((Some(FieldKey::Name(k)), p)) }
+            / skip_to(start) _ checkpoint() "[" k:value(max_depth) "]" _ "=" p:position!() rest()
+              { Some((Some(FieldKey::Value(k)), p)) }
+            / skip_to(start) _ p:position!() rest() { Some((None, p)) }
+
+        /// Parses the value of the table entry at byte `start`, for [`Parser::sample()`].
         ///
-        /// ```rust
-        /// use serde_luaq::{return_statement, LuaValue};
+        /// Returns the value and the position of the next entry.
         ///
-        /// assert_eq!(LuaValue::Boolean(true), return_statement(b"return true\n", 16).unwrap());
-        /// ```
+        /// [`Parser::sample()`]: crate::Parser::sample
+        pub rule sample_value(start: usize, max_depth: u16) -> (LuaValue<'input>, usize)
+            = skip_to(start) v:value(max_depth) ([b',' | b';'] / &"}") p:position!() rest()
+            { (v, p) }
+
+        /// Matches nothing if `allowed` is set, without adding to the expected set when it isn't.
+        rule allowed(allowed: bool)
+            = #{|_, pos| {
+                if allowed {
+                    peg::RuleResult::Matched(pos, ())
+                } else {
+                    peg::RuleResult::Failed
+                }
+            }}
+
+        /// Skips to byte `pos` of the input, without checking what is before it.
+        rule skip_to(pos: usize)
+            = #{|input, _| peg::RuleResult::Matched(pos.min(input.len()), ())}
+
+        /// Matches nothing, returning the whole input.
+        rule input() -> &'input [u8]
+            = #{|input, pos| peg::RuleResult::Matched(pos, input)}
+
+        /// Skips the rest of the input, without checking it.
+        rule rest()
+            = #{|input, _| peg::RuleResult::Matched(input.len(), ())}
+
+        /// Parses a Lua script like [`script_limited`], followed by an optional `return`
+        /// statement.
+        pub rule script_return(max_statements: usize)
+            -> ((Vec<Statement<'input>>, bool), Option<Returned<'input>>)
+            = s:script_limited(max_statements)
+              r:("return" __ r:returned() _ (";" _)* { r })?
+            { (s, r) }
+
+        /// The value of a `return` statement at the end of a script.
+        rule returned() -> Returned<'input>
+            = name:identifier() _ &(";" / ![_]) { Returned::Name(name) }
+            / v:top_value(opts.max_depth) { Returned::Value(v) }
+
+        /// Parses a Lua `return` statement.
         ///
-        /// For more information about Lua type conversion, see [`LuaValue`].
-        pub rule return_statement(max_depth: u16) -> LuaValue<'input>
-            = _ "return" __ v:lua_value(max_depth) _ { v }
+        /// If it returns more than one value, they're returned as a sequence, which is the same as
+        /// returning a table.
+        pub rule return_statement() -> LuaValue<'input>
+            = v:return_values()
+            {
+                let mut v = v;
+                if v.len() == 1 {
+                    v.swap_remove(0)
+                } else {
+                    LuaValue::Table(v.into_iter().map(LuaTableEntry::from).collect())
+                }
+            }
+
+        /// Parses a Lua `return` statement with one or more comma-separated values.
+        pub rule return_values() -> Vec<LuaValue<'input>>
+            = _ "return" __ v:(top_value(opts.max_depth) ++ (_ "," _)) _ { v }
+            / allowed(opts.empty_as_nil) _ { vec![LuaValue::Nil] }
     }
 }
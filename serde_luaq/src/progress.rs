@@ -0,0 +1,267 @@
+//! Progress reporting and cancellation for long-running parses.
+use crate::{diagnostics::Collector, metrics::Recorder, Error};
+use peg::error::ParseError;
+use std::{
+    cell::{Cell, RefCell},
+    ops::ControlFlow,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// How many [`CancellationToken::check()`] calls to make between checking the deadline.
+const DEADLINE_CHECK_INTERVAL: u32 = 64;
+
+/// Progress of a parse, passed to a progress hook by [`Parser::value_with_progress()`] and
+/// friends.
+///
+/// [`Parser::value_with_progress()`]: crate::Parser::value_with_progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Progress {
+    /// Number of bytes of the input which have been parsed.
+    pub position: usize,
+
+    /// Total length of the input, in bytes.
+    pub len: usize,
+
+    /// Number of script statements which have been parsed.
+    ///
+    /// This is always `0` when not parsing a script.
+    pub statements: usize,
+}
+
+impl Progress {
+    /// Returns the fraction of the input which has been parsed, between `0.0` and `1.0`.
+    pub fn fraction(&self) -> f64 {
+        if self.len == 0 {
+            1.0
+        } else {
+            self.position as f64 / self.len as f64
+        }
+    }
+}
+
+/// A token for cancelling parsing and deserialisation, either from another thread or after a
+/// deadline.
+///
+/// When a token is set in [`ParseOptions::cancellation`][crate::ParseOptions::cancellation] or
+/// [`DeserializeOptions::cancellation`][crate::DeserializeOptions::cancellation], parsing and
+/// deserialisation periodically check the token, and return [`Error::Cancelled`] once it has been
+/// cancelled.
+///
+/// Clones of a token share the same state, and are equal to each other.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{CancellationToken, Error, Parser};
+/// use std::time::Duration;
+///
+/// let token = CancellationToken::with_timeout(Duration::from_millis(200));
+/// let p = Parser::builder().cancellation(Some(token.clone())).build();
+/// assert!(p.value(b"{1, 2, 3}").is_ok());
+///
+/// token.cancel();
+/// assert_eq!(Error::Cancelled, p.value(b"{1, 2, 3}").unwrap_err());
+/// ```
+///
+/// ## Platform support
+///
+/// Deadlines use [`Instant`], which is not available on `wasm32-unknown-unknown`. Only use
+/// [`CancellationToken::new()`] and [`CancellationToken::cancel()`] on that platform.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<TokenState>);
+
+#[derive(Debug, Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    deadline: Option<Instant>,
+    /// Number of calls to [`CancellationToken::check()`] since the deadline was last checked.
+    checks: AtomicU32,
+}
+
+impl CancellationToken {
+    /// Creates a token which is only cancelled by [`cancel()`][Self::cancel].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a token which is cancelled at `deadline`, or by [`cancel()`][Self::cancel].
+    pub fn with_deadline(deadline: Instant) -> Self {
+        Self(Arc::new(TokenState {
+            deadline: Some(deadline),
+            ..Default::default()
+        }))
+    }
+
+    /// Creates a token which is cancelled after `timeout` from now, or by
+    /// [`cancel()`][Self::cancel].
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self::with_deadline(Instant::now() + timeout)
+    }
+
+    /// Cancels the token, and all of its clones.
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if the token has been cancelled, or its deadline has passed.
+    pub fn is_cancelled(&self) -> bool {
+        if self.0.cancelled.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        if self.0.deadline.is_some_and(|d| Instant::now() >= d) {
+            self.cancel();
+            return true;
+        }
+
+        false
+    }
+
+    /// Like [`is_cancelled()`][Self::is_cancelled], but only checks the deadline every
+    /// [`DEADLINE_CHECK_INTERVAL`] calls, because getting the time is slow.
+    pub(crate) fn check(&self) -> bool {
+        if self.0.cancelled.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        if self.0.deadline.is_none()
+            || !self
+                .0
+                .checks
+                .fetch_add(1, Ordering::Relaxed)
+                .is_multiple_of(DEADLINE_CHECK_INTERVAL)
+        {
+            return false;
+        }
+
+        self.is_cancelled()
+    }
+}
+
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CancellationToken {}
+
+/// Parse-time callbacks, which are threaded through the `peg` grammar.
+///
+/// The grammar calls [`checkpoint()`][Self::checkpoint] before each table entry and after each
+/// script statement.
+#[derive(Default)]
+pub(crate) struct Hooks<'h> {
+    progress: Option<RefCell<&'h mut dyn FnMut(Progress) -> ControlFlow<()>>>,
+    cancellation: Option<&'h CancellationToken>,
+    interval: usize,
+    len: usize,
+    next: Cell<usize>,
+    statements: Cell<usize>,
+    cancelled: Cell<bool>,
+    long_identifier: Cell<Option<(usize, usize)>>,
+    pub(crate) metrics: Recorder,
+    pub(crate) diagnostics: Collector,
+}
+
+impl<'h> Hooks<'h> {
+    /// Checks `cancellation` while parsing.
+    pub(crate) fn new(cancellation: Option<&'h CancellationToken>) -> Self {
+        Self {
+            cancellation,
+            ..Default::default()
+        }
+    }
+
+    /// Collects warnings for [`parse_with_diagnostics()`][crate::parse_with_diagnostics].
+    pub(crate) fn with_diagnostics(self) -> Self {
+        Self {
+            diagnostics: Collector::enabled(),
+            ..self
+        }
+    }
+
+    /// Calls `progress` about every `interval` bytes of `len` bytes of input.
+    pub(crate) fn with_progress(
+        self,
+        len: usize,
+        interval: usize,
+        progress: &'h mut dyn FnMut(Progress) -> ControlFlow<()>,
+    ) -> Self {
+        let interval = interval.max(1);
+        Self {
+            progress: Some(RefCell::new(progress)),
+            interval,
+            len,
+            next: Cell::new(interval),
+            ..self
+        }
+    }
+
+    /// Called by the grammar at `pos`. Returns `false` if the parse has been cancelled, which must
+    /// fail the rule.
+    pub(crate) fn checkpoint(&self, pos: usize) -> bool {
+        if self.cancelled.get() {
+            return false;
+        }
+
+        if self.cancellation.is_some_and(CancellationToken::check) {
+            self.cancelled.set(true);
+            return false;
+        }
+
+        let Some(progress) = &self.progress else {
+            return true;
+        };
+
+        if pos < self.next.get() {
+            return true;
+        }
+
+        self.next.set(pos.saturating_add(self.interval));
+        let p = Progress {
+            position: pos,
+            len: self.len,
+            statements: self.statements.get(),
+        };
+
+        if (progress.borrow_mut())(p).is_break() {
+            self.cancelled.set(true);
+            return false;
+        }
+
+        true
+    }
+
+    /// Called by the grammar after each script statement at `pos`.
+    pub(crate) fn statement(&self, pos: usize) -> bool {
+        self.statements.set(self.statements.get() + 1);
+        self.checkpoint(pos)
+    }
+
+    /// Called by the grammar when an identifier at `position` is longer than `limit`.
+    pub(crate) fn identifier_too_long(&self, limit: usize, position: usize) {
+        if self.long_identifier.get().is_none() {
+            self.long_identifier.set(Some((limit, position)));
+        }
+    }
+
+    /// Converts a `peg` result into a [`Result`], returning [`Error::Cancelled`] if the parse was
+    /// cancelled by a progress hook or [`CancellationToken`], or [`Error::IdentifierTooLong`] if
+    /// it failed because of a long identifier.
+    pub(crate) fn finish<T>(&self, r: Result<T, ParseError<usize>>) -> Result<T, Error> {
+        if self.cancelled.get() {
+            return Err(Error::Cancelled);
+        }
+
+        match (r, self.long_identifier.get()) {
+            (Err(_), Some((limit, position))) => Err(Error::IdentifierTooLong { limit, position }),
+            (r, _) => Ok(r?),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Checking whether a script can be written back out exactly as it was read.
+use crate::{
+    script_with_options, to_vec, Error, LuaFormat, ParseOptions, ScriptStatement, Serializer,
+};
+use std::ops::Range;
+
+/// The result of [`check_roundtrip()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RoundTripReport {
+    /// The script written back out by [`Serializer::serialize_script()`].
+    pub output: Vec<u8>,
+
+    /// Parts of the input which aren't reproduced in [`output`][Self::output], in input order.
+    pub differences: Vec<RoundTripDifference>,
+}
+
+impl RoundTripReport {
+    /// Returns `true` if the output is byte-for-byte the same as the input.
+    pub fn is_exact(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+/// A part of the input which isn't reproduced when it is written back out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RoundTripDifference {
+    /// Byte offsets of the part of the input which is written differently (or not at all).
+    ///
+    /// This is empty if the output has something which isn't in the input, like a line break
+    /// after the last statement.
+    pub span: Range<usize>,
+
+    /// Why the input isn't reproduced.
+    pub reason: RoundTripReason,
+}
+
+/// Why part of the input isn't reproduced, in a [`RoundTripDifference`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RoundTripReason {
+    /// An assignment is written in a different style, like with other quotes, number formatting,
+    /// spacing or table separators.
+    Value,
+
+    /// The `local` keyword of an [assignment][crate::ParseOptions::local_assignments] isn't
+    /// written.
+    Local,
+
+    /// Whitespace or `;` between statements is written differently.
+    Whitespace,
+
+    /// Something between statements isn't written at all, like a comment on the same line as an
+    /// assignment, a [field assignment][crate::ParseOptions::field_assignments] (which is merged
+    /// into its table), or a [footer][crate::ParseOptions::footer].
+    Dropped,
+}
+
+/// Parses a Lua script with [`ParseOptions`], writes it back out with
+/// [`Serializer::serialize_script()`], and reports which parts of the input aren't reproduced
+/// exactly.
+///
+/// This checks whether editing a file by parsing and re-writing it would change anything other
+/// than the edited values. [Comments before assignments][ParseOptions::comments] are always
+/// captured and written back out.
+///
+/// Differences in assignments are narrowed down to the bytes between the first and last bytes
+/// which differ from the output.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{check_roundtrip, ParseOptions, RoundTripReason};
+///
+/// let opts = ParseOptions::new(16);
+/// let report = check_roundtrip(b"-- level\nlevel = 3\nname = \"a\"\n", &opts).unwrap();
+/// assert!(report.is_exact());
+///
+/// let lua = b"level = 3 -- start\nname = 'a'\n";
+/// let report = check_roundtrip(lua, &opts).unwrap();
+/// assert_eq!(b"level = 3\nname = \"a\"\n", report.output.as_slice());
+///
+/// assert_eq!(2, report.differences.len());
+/// assert_eq!(RoundTripReason::Dropped, report.differences[0].reason);
+/// assert_eq!(b" -- start", &lua[report.differences[0].span.clone()]);
+/// assert_eq!(RoundTripReason::Value, report.differences[1].reason);
+/// assert_eq!(b"'a'", &lua[report.differences[1].span.clone()]);
+/// ```
+pub fn check_roundtrip(b: &[u8], opts: &ParseOptions) -> Result<RoundTripReport, Error> {
+    let mut opts = opts.clone();
+    opts.comments = true;
+    let statements = script_with_options(b, &opts)?;
+
+    let mut ser = Serializer::with_format(Vec::new(), LuaFormat::Script);
+    ser.serialize_script(&statements)?;
+    let output = ser.into_inner();
+
+    let mut differences = Vec::new();
+    let mut prev_end = 0;
+    for (i, s) in statements.iter().enumerate() {
+        let mut expected = Vec::new();
+        if i > 0 {
+            expected.push(b'\n');
+        }
+        if let Some(comment) = s.comment {
+            expected.extend_from_slice(comment);
+            expected.push(b'\n');
+        }
+        gap(b, prev_end..s.span.start, &expected, &mut differences);
+        statement(b, s, &mut differences)?;
+        prev_end = s.span.end;
+    }
+
+    let expected: &[u8] = if statements.is_empty() { b"" } else { b"\n" };
+    gap(b, prev_end..b.len(), expected, &mut differences);
+
+    Ok(RoundTripReport {
+        output,
+        differences,
+    })
+}
+
+/// Compares an assignment with how it is written.
+fn statement(
+    b: &[u8],
+    s: &ScriptStatement<'_>,
+    differences: &mut Vec<RoundTripDifference>,
+) -> Result<(), Error> {
+    let mut start = s.span.start;
+    if s.local {
+        // `local` is followed by whitespace, up to the name.
+        let name = b[start..s.span.end]
+            .windows(s.name.len())
+            .skip(b"local".len())
+            .position(|w| w == s.name.as_bytes())
+            .map_or(s.span.end, |i| start + b"local".len() + i);
+        differences.push(RoundTripDifference {
+            span: start..name,
+            reason: RoundTripReason::Local,
+        });
+        start = name;
+    }
+
+    let mut expected = format!("{} = ", s.name).into_bytes();
+    expected.extend(to_vec(&s.value, LuaFormat::Value)?);
+    if let Some(span) = changed(b, start..s.span.end, &expected) {
+        differences.push(RoundTripDifference {
+            span,
+            reason: RoundTripReason::Value,
+        });
+    }
+    Ok(())
+}
+
+/// Compares the input between two statements with how it is written.
+fn gap(b: &[u8], range: Range<usize>, expected: &[u8], differences: &mut Vec<RoundTripDifference>) {
+    let Some(span) = changed(b, range, expected) else {
+        return;
+    };
+
+    // Anything other than whitespace and `;` is a comment or statement which isn't written.
+    let dropped = b[span.clone()]
+        .iter()
+        .any(|c| !c.is_ascii_whitespace() && *c != b';');
+    let reason = if dropped {
+        RoundTripReason::Dropped
+    } else {
+        RoundTripReason::Whitespace
+    };
+    differences.push(RoundTripDifference { span, reason });
+}
+
+/// Compares `b[range]` with `expected`, returning the part of `range` between the first and last
+/// bytes which are different, or [`None`] if they're the same.
+fn changed(b: &[u8], range: Range<usize>, expected: &[u8]) -> Option<Range<usize>> {
+    let actual = &b[range.clone()];
+    if actual == expected {
+        return None;
+    }
+
+    let prefix = actual
+        .iter()
+        .zip(expected)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = actual[prefix..]
+        .iter()
+        .rev()
+        .zip(expected[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    Some(range.start + prefix..range.end - suffix)
+}
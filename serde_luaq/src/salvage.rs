@@ -0,0 +1,274 @@
+//! Recovery of truncated input.
+use crate::{peg_parser::long_bracket_end, Error, LuaDialect, LuaFormat, LuaValue, Parser};
+
+/// Maximum number of repaired inputs to try parsing, so that salvaging a large input doesn't take
+/// quadratic time.
+const MAX_ATTEMPTS: usize = 8;
+
+/// The result of [`Parser::salvage()`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Salvaged<'a> {
+    /// The parsed value, which may be missing data from the end of the input.
+    pub value: LuaValue<'a>,
+
+    /// If the input was truncated, the byte offset in the input where the salvaged data ends.
+    ///
+    /// Any input after this offset was discarded, and any strings or tables which were still open
+    /// at this offset were closed.
+    ///
+    /// This is [`None`] if the input parsed without any repairs.
+    pub truncated: Option<usize>,
+}
+
+impl Salvaged<'_> {
+    /// Returns `true` if the input was truncated, and needed repairs to parse.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated.is_some()
+    }
+}
+
+/// A construct which is still open at the end of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Open {
+    /// A short string starting at byte `start`, delimited by `quote`.
+    Short { quote: u8, start: usize },
+
+    /// A long string or long comment at `level`, starting at byte `start`.
+    Long { level: usize, start: usize },
+
+    /// A C-style block comment starting at byte `start`, in [GLua][LuaDialect::GLua].
+    Comment { start: usize },
+}
+
+impl Open {
+    fn start(self) -> usize {
+        match self {
+            Open::Short { start, .. } | Open::Long { start, .. } | Open::Comment { start } => start,
+        }
+    }
+}
+
+/// Where the input could be cut off, and closed with `"}"` repeated `depth` times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Checkpoint {
+    pos: usize,
+    depth: usize,
+}
+
+/// The state of the input at its end.
+#[derive(Debug, Default)]
+struct Scan {
+    /// Number of open tables.
+    depth: usize,
+
+    /// String or comment which is open at the end of the input, inside of all open tables.
+    open: Option<Open>,
+
+    /// Positions after table separators, table delimiters and (outside of tables) statement
+    /// separators, in order.
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl Scan {
+    /// Scans `b` for open tables, strings and comments, without otherwise checking its syntax.
+    fn new(b: &[u8], c_comments: bool) -> Self {
+        let mut scan = Self::default();
+        let mut i = 0;
+        while i < b.len() {
+            match b[i] {
+                q @ (b'"' | b'\'') => {
+                    let start = i;
+                    i += 1;
+                    while i < b.len() && b[i] != q {
+                        if b[i] == b'\\' {
+                            i += 1;
+                        }
+                        i += 1;
+                    }
+                    if i >= b.len() {
+                        scan.open = Some(Open::Short { quote: q, start });
+                        break;
+                    }
+                }
+                b'[' => {
+                    let level = b[i + 1..].iter().take_while(|&&c| c == b'=').count();
+                    if b.get(i + 1 + level) == Some(&b'[') {
+                        let start = i;
+                        i += 2 + level;
+                        while i < b.len()
+                            && !(b[i] == b']'
+                                && b.get(i + 1 + level) == Some(&b']')
+                                && b[i + 1..i + 1 + level].iter().all(|&c| c == b'='))
+                        {
+                            i += 1;
+                        }
+                        if i >= b.len() {
+                            scan.open = Some(Open::Long { level, start });
+                            break;
+                        }
+                        i += 1 + level;
+                    }
+                }
+                b'-' if b.get(i + 1) == Some(&b'-') => match long_bracket_end(b, i + 2) {
+                    // A long comment is closed like a long string.
+                    Some(end) if end >= b.len() => {
+                        let level = b[i + 3..].iter().take_while(|&&c| c == b'=').count();
+                        scan.open = Some(Open::Long { level, start: i });
+                        break;
+                    }
+                    Some(end) => i = end,
+                    None => {
+                        while i + 1 < b.len() && b[i + 1] != b'\n' {
+                            i += 1;
+                        }
+                    }
+                },
+                b'/' if c_comments && b.get(i + 1) == Some(&b'/') => {
+                    while i + 1 < b.len() && b[i + 1] != b'\n' {
+                        i += 1;
+                    }
+                }
+                b'/' if c_comments && b.get(i + 1) == Some(&b'*') => {
+                    let start = i;
+                    i += 2;
+                    while i < b.len() && !b[i..].starts_with(b"*/") {
+                        i += 1;
+                    }
+                    if i >= b.len() {
+                        scan.open = Some(Open::Comment { start });
+                        break;
+                    }
+                    i += 1;
+                }
+                b'{' => {
+                    scan.depth += 1;
+                    scan.checkpoint(i + 1);
+                }
+                b'}' => {
+                    scan.depth = scan.depth.saturating_sub(1);
+                    scan.checkpoint(i + 1);
+                }
+                b',' | b';' => scan.checkpoint(i + 1),
+                b'\n' if scan.depth == 0 => scan.checkpoint(i + 1),
+                _ => (),
+            }
+            i += 1;
+        }
+
+        scan
+    }
+
+    fn checkpoint(&mut self, pos: usize) {
+        self.checkpoints.push(Checkpoint {
+            pos,
+            depth: self.depth,
+        });
+    }
+
+    /// Returns ways to repair the input, from the one which keeps the most data to the least, as
+    /// the length of the input to keep and the suffix to close it with.
+    fn repairs<'s>(&'s self, b: &'s [u8]) -> impl Iterator<Item = (usize, Vec<u8>)> + 's {
+        let mut at_end = Vec::new();
+        match self.open {
+            Some(Open::Short { quote, .. }) => at_end.push(quote),
+            Some(Open::Long { level, .. }) => {
+                at_end.push(b']');
+                at_end.extend(std::iter::repeat_n(b'=', level));
+                at_end.push(b']');
+            }
+            Some(Open::Comment { .. }) => at_end.extend_from_slice(b"*/"),
+            None => (),
+        }
+        at_end.resize(at_end.len() + self.depth, b'}');
+
+        // A short string may end part-way through an escape sequence.
+        let escape = match self.open {
+            Some(Open::Short { start, .. }) => b[start..]
+                .iter()
+                .rposition(|&c| c == b'\\')
+                .map(|i| (start + i, at_end.clone())),
+            _ => None,
+        };
+
+        let checkpoints = self
+            .checkpoints
+            .iter()
+            .rev()
+            .map(|c| (c.pos, vec![b'}'; c.depth]));
+
+        std::iter::once((b.len(), at_end))
+            .chain(escape)
+            .chain(checkpoints)
+            .take(MAX_ATTEMPTS)
+    }
+}
+
+/// Returns `true` if `pos` is in the last token of `b`, or at its end.
+fn in_last_token(b: &[u8], pos: usize) -> bool {
+    b.trim_ascii_end().get(pos..).is_none_or(|t| {
+        t.iter()
+            .all(|&c| c.is_ascii_alphanumeric() || b"_.+-".contains(&c))
+    })
+}
+
+/// Parses `b` like [`Parser::salvage()`].
+pub(crate) fn salvage<'a>(
+    parser: &Parser,
+    b: &'a [u8],
+    format: LuaFormat,
+) -> Result<Salvaged<'a>, Error> {
+    match parser.parse(b, format) {
+        Ok(value) => Ok(Salvaged {
+            value,
+            truncated: None,
+        }),
+        Err(e) => repair(parser, b, format, e),
+    }
+}
+
+/// Tries to repair `b` after parsing it failed with `e`.
+pub(crate) fn repair<'a>(
+    parser: &Parser,
+    b: &'a [u8],
+    format: LuaFormat,
+    e: Error,
+) -> Result<Salvaged<'a>, Error> {
+    if !matches!(e, Error::Peg(_) | Error::MissingFooter) {
+        return Err(e);
+    }
+
+    // Only syntax errors in the last token of the input, or in a string or comment which is still
+    // open at the end of the input, could be caused by truncation.
+    let scan = Scan::new(b, parser.options().dialect == LuaDialect::GLua);
+    if let Error::Peg(p) = &e {
+        if !in_last_token(b, p.location) && scan.open.is_none_or(|o| p.location < o.start()) {
+            return Err(e);
+        }
+    }
+
+    // Truncated input won't have a footer.
+    let mut opts = parser.options().clone();
+    opts.footer = None;
+    let parser = Parser::from(opts);
+
+    let mut buf = Vec::with_capacity(b.len() + scan.depth + 4);
+    for (len, suffix) in scan.repairs(b) {
+        buf.clear();
+        buf.extend_from_slice(&b[..len]);
+        buf.extend_from_slice(&suffix);
+
+        match parser.parse(&buf, format) {
+            Ok(value) => {
+                return Ok(Salvaged {
+                    value: value.into_owned(),
+                    truncated: Some(len),
+                })
+            }
+            Err(Error::Peg(_)) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(e)
+}
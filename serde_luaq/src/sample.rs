@@ -0,0 +1,469 @@
+//! Sampling entries from large tables.
+use crate::{
+    error::{append_path, PathKey},
+    footer::strip_footer,
+    peg_parser::lua,
+    progress::Hooks,
+    script::FieldKey,
+    Error, LuaFormat, LuaTableEntry, Parser,
+};
+use std::{cmp::Ordering, collections::BinaryHeap, fmt, sync::Arc};
+
+type WeightFn = dyn Fn(&LuaTableEntry<'_>) -> f64 + Send + Sync;
+
+/// How to choose entries with [`Parser::sample()`].
+///
+/// Random samples are reproducible: the same `seed` and input always give the same sample.
+///
+/// Clones of a `Sampling` share the same weight function, and compare equal.
+#[derive(Clone)]
+pub struct Sampling(Method);
+
+#[derive(Clone)]
+enum Method {
+    Every(usize),
+    Random {
+        count: usize,
+        seed: u64,
+    },
+    Weighted {
+        count: usize,
+        seed: u64,
+        weight: Arc<WeightFn>,
+    },
+}
+
+impl Sampling {
+    /// Samples every `n`th entry, starting with the first.
+    ///
+    /// `0` is treated as `1`, which samples every entry.
+    pub const fn every(n: usize) -> Self {
+        Self(Method::Every(n))
+    }
+
+    /// Samples up to `count` entries, chosen uniformly at random.
+    pub const fn random(count: usize, seed: u64) -> Self {
+        Self(Method::Random { count, seed })
+    }
+
+    /// Samples up to `count` entries, chosen at random in proportion to their `weight`.
+    ///
+    /// Entries with a weight which is not positive (or NaN) are never sampled.
+    pub fn weighted<F>(count: usize, seed: u64, weight: F) -> Self
+    where
+        F: Fn(&LuaTableEntry<'_>) -> f64 + Send + Sync + 'static,
+    {
+        Self(Method::Weighted {
+            count,
+            seed,
+            weight: Arc::new(weight),
+        })
+    }
+}
+
+impl fmt::Debug for Sampling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Method::Every(n) => f.debug_tuple("Every").field(n).finish(),
+            Method::Random { count, seed } => f
+                .debug_struct("Random")
+                .field("count", count)
+                .field("seed", seed)
+                .finish(),
+            Method::Weighted { count, seed, .. } => f
+                .debug_struct("Weighted")
+                .field("count", count)
+                .field("seed", seed)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+impl PartialEq for Sampling {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Method::Every(a), Method::Every(b)) => a == b,
+            (Method::Random { count, seed }, Method::Random { count: c, seed: s }) => {
+                count == c && seed == s
+            }
+            (
+                Method::Weighted {
+                    count,
+                    seed,
+                    weight,
+                },
+                Method::Weighted {
+                    count: c,
+                    seed: s,
+                    weight: w,
+                },
+            ) => count == c && seed == s && Arc::ptr_eq(weight, w),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Sampling {}
+
+/// The result of [`Parser::sample()`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Sample<'a> {
+    /// The sampled entries, in the order they appear in the table, with their position in the
+    /// table (starting at `0`).
+    pub entries: Vec<(usize, LuaTableEntry<'a>)>,
+
+    /// The total number of entries in the table.
+    pub len: usize,
+}
+
+/// Small, fast pseudo-random number generator ([SplitMix64][]).
+///
+/// [SplitMix64]: https://prng.di.unimi.it/splitmix64.c
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..n`.
+    fn below(&mut self, n: usize) -> usize {
+        ((u128::from(self.next_u64()) * n as u128) >> 64) as usize
+    }
+
+    /// Returns a number in `(0, 1]`.
+    fn unit(&mut self) -> f64 {
+        ((self.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// An entry in a weighted sample, ordered so that the entry with the smallest `key` is at the
+/// top of a [`BinaryHeap`].
+struct Keyed<'a> {
+    key: f64,
+    index: usize,
+    entry: LuaTableEntry<'a>,
+}
+
+impl PartialEq for Keyed<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Keyed<'_> {}
+
+impl PartialOrd for Keyed<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Keyed<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key.total_cmp(&self.key)
+    }
+}
+
+/// Collects a sample, one entry at a time.
+enum Sampler<'a> {
+    Every(usize, Vec<(usize, LuaTableEntry<'a>)>),
+    Random {
+        count: usize,
+        rng: SplitMix64,
+        entries: Vec<(usize, LuaTableEntry<'a>)>,
+    },
+    Weighted {
+        count: usize,
+        rng: SplitMix64,
+        weight: Arc<WeightFn>,
+        heap: BinaryHeap<Keyed<'a>>,
+    },
+}
+
+impl<'a> Sampler<'a> {
+    fn new(sampling: &Sampling) -> Self {
+        match &sampling.0 {
+            Method::Every(n) => Self::Every((*n).max(1), Vec::new()),
+            Method::Random { count, seed } => Self::Random {
+                count: *count,
+                rng: SplitMix64(*seed),
+                entries: Vec::new(),
+            },
+            Method::Weighted {
+                count,
+                seed,
+                weight,
+            } => Self::Weighted {
+                count: *count,
+                rng: SplitMix64(*seed),
+                weight: weight.clone(),
+                heap: BinaryHeap::new(),
+            },
+        }
+    }
+
+    /// Offers the entry at `index` for sampling.
+    fn push(&mut self, index: usize, entry: LuaTableEntry<'a>) {
+        match self {
+            Self::Every(n, entries) => {
+                if index.is_multiple_of(*n) {
+                    entries.push((index, entry));
+                }
+            }
+
+            // Algorithm R: https://en.wikipedia.org/wiki/Reservoir_sampling#Simple:_Algorithm_R
+            Self::Random {
+                count,
+                rng,
+                entries,
+            } => {
+                if entries.len() < *count {
+                    entries.push((index, entry));
+                } else {
+                    let i = rng.below(index + 1);
+                    if i < *count {
+                        entries[i] = (index, entry);
+                    }
+                }
+            }
+
+            // Algorithm A-Res: https://en.wikipedia.org/wiki/Reservoir_sampling#Algorithm_A-Res
+            Self::Weighted {
+                count,
+                rng,
+                weight,
+                heap,
+            } => {
+                let w = weight(&entry);
+                if *count == 0 || w.is_nan() || w <= 0. {
+                    return;
+                }
+
+                let key = rng.unit().powf(w.recip());
+                if heap.len() < *count {
+                    heap.push(Keyed { key, index, entry });
+                } else if heap.peek().is_some_and(|min| key > min.key) {
+                    heap.pop();
+                    heap.push(Keyed { key, index, entry });
+                }
+            }
+        }
+    }
+
+    fn finish(self, len: usize) -> Sample<'a> {
+        let mut entries = match self {
+            Self::Every(_, entries) | Self::Random { entries, .. } => entries,
+            Self::Weighted { heap, .. } => heap.into_iter().map(|k| (k.index, k.entry)).collect(),
+        };
+        entries.sort_unstable_by_key(|(index, _)| *index);
+        Sample { entries, len }
+    }
+}
+
+/// The result of searching for a path.
+enum Found {
+    /// The path refers to the value at this position, with this many levels of table nesting
+    /// remaining.
+    Value(usize, u16),
+
+    /// The path doesn't refer to anything.
+    Nothing,
+}
+
+/// Returns `true` if `path` is inside the value at `prefix`.
+fn is_inside(path: &str, prefix: &str) -> bool {
+    prefix.is_empty()
+        || path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with(['.', '[']))
+}
+
+impl Parser {
+    /// Parses a sample of the entries of the table at `path`, without keeping the rest of the
+    /// input in memory.
+    ///
+    /// `path` is in the same format as [`Error::path()`] (like `players[2].inventory` or
+    /// `["a b"].c`). For [`LuaFormat::Value`] and [`LuaFormat::Return`], the empty path is the
+    /// whole value. For [`LuaFormat::Script`] and [`LuaFormat::ScriptReturn`], paths start with a
+    /// variable name, and any `return` statement isn't searched.
+    ///
+    /// This is useful for estimating statistics about giant tables (like a game's save data),
+    /// where parsing the whole input into a [`LuaValue`][crate::LuaValue] would use too much memory. Entries are
+    /// parsed one at a time, and only the sampled entries are kept:
+    ///
+    /// * Entries in the sampled table are parsed, offered to the [`Sampling`], then dropped if
+    ///   they aren't sampled.
+    ///
+    /// * Values on the way to the table, which aren't part of `path`, are parsed and then dropped.
+    ///   Any of these values which are large will still use memory while they are parsed.
+    ///
+    /// * Parsing stops at the end of the sampled table, so input after it isn't checked. If the
+    ///   path appears more than once, only the first matching table is sampled.
+    ///
+    /// Returns [`None`] if there is no table at `path`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{LuaFormat, LuaTableEntry, LuaValue, Parser, Sampling};
+    ///
+    /// let p = Parser::default();
+    /// let b = b"
+    ///     version = 3
+    ///     save = {scores = {10, 20, 30, 40, 50, 60, 70}}
+    /// ";
+    ///
+    /// let s = p.sample(b, LuaFormat::Script, "save.scores", &Sampling::every(3))
+    ///     .unwrap()
+    ///     .unwrap();
+    /// assert_eq!(7, s.len);
+    /// assert_eq!(
+    ///     vec![
+    ///         (0, LuaTableEntry::from(LuaValue::integer(10))),
+    ///         (3, LuaTableEntry::from(LuaValue::integer(40))),
+    ///         (6, LuaTableEntry::from(LuaValue::integer(70))),
+    ///     ],
+    ///     s.entries,
+    /// );
+    ///
+    /// let s = p.sample(b, LuaFormat::Script, "save.scores", &Sampling::random(2, 1234))
+    ///     .unwrap()
+    ///     .unwrap();
+    /// assert_eq!(2, s.entries.len());
+    ///
+    /// assert!(p.sample(b, LuaFormat::Script, "version", &Sampling::every(1)).unwrap().is_none());
+    /// assert!(p.sample(b, LuaFormat::Script, "missing", &Sampling::every(1)).unwrap().is_none());
+    /// ```
+    pub fn sample<'a>(
+        &self,
+        b: &'a [u8],
+        format: LuaFormat,
+        path: &str,
+        sampling: &Sampling,
+    ) -> Result<Option<Sample<'a>>, Error> {
+        let b = strip_footer(b, self.options())?;
+        let found = match format {
+            LuaFormat::Value => self.find(b, 0, self.options().max_depth, "", path)?,
+            LuaFormat::Return => {
+                let pos = self.call(|h| lua::sample_return(b, self.options(), h))?;
+                self.find(b, pos, self.options().max_depth, "", path)?
+            }
+            LuaFormat::Script => self.find_global(b, path, false)?,
+            LuaFormat::ScriptReturn => self.find_global(b, path, true)?,
+        };
+
+        let Found::Value(pos, max_depth) = found else {
+            return Ok(None);
+        };
+        let Some(mut pos) = self.table(b, pos, max_depth)? else {
+            return Ok(None);
+        };
+        let max_depth = max_depth - 1;
+
+        let mut sampler = Sampler::new(sampling);
+        let mut len = 0;
+        while let Some((key, value_pos)) =
+            self.call(|h| lua::sample_key(b, self.options(), h, pos, max_depth))?
+        {
+            let (value, next) =
+                self.call(|h| lua::sample_value(b, self.options(), h, value_pos, max_depth))?;
+            let entry = match key {
+                None => LuaTableEntry::from(value),
+                Some(FieldKey::Name(k)) => LuaTableEntry::NameValue(Box::new((k.into(), value))),
+                Some(FieldKey::Value(k)) => LuaTableEntry::KeyValue(Box::new((k, value))),
+            };
+            sampler.push(len, entry);
+            len += 1;
+            pos = next;
+        }
+
+        Ok(Some(sampler.finish(len)))
+    }
+
+    /// Searches a script for the variable which `path` starts with.
+    fn find_global(&self, b: &[u8], path: &str, returns: bool) -> Result<Found, Error> {
+        let mut pos = 0;
+        let mut statements = 0;
+        while let Some((name, value_pos)) =
+            self.call(|h| lua::sample_assignment(b, self.options(), h, pos, returns))?
+        {
+            if let Some(limit) = self.options().max_statements.filter(|l| statements >= *l) {
+                return Err(Error::TooManyStatements { limit });
+            }
+            statements += 1;
+
+            if is_inside(path, name) || path == name {
+                return self.find(b, value_pos, self.options().max_depth, name, path);
+            }
+
+            // Skip over the value
+            pos = self
+                .call(|h| lua::sample_assignment_value(b, self.options(), h, value_pos))?
+                .1;
+        }
+
+        Ok(Found::Nothing)
+    }
+
+    /// Searches the value at `pos`, which is at `here`, for the table at `path`.
+    fn find(
+        &self,
+        b: &[u8],
+        pos: usize,
+        max_depth: u16,
+        here: &str,
+        path: &str,
+    ) -> Result<Found, Error> {
+        if here == path {
+            return Ok(Found::Value(pos, max_depth));
+        }
+
+        let Some(mut pos) = self.table(b, pos, max_depth)? else {
+            return Ok(Found::Nothing);
+        };
+        let max_depth = max_depth - 1;
+
+        let mut index = 0;
+        while let Some((key, value_pos)) =
+            self.call(|h| lua::sample_key(b, self.options(), h, pos, max_depth))?
+        {
+            let key = match &key {
+                Some(k) => k.path_key(),
+                None => {
+                    index += 1;
+                    PathKey::Integer(index)
+                }
+            };
+            let there = append_path(here, &key);
+            if there == path || is_inside(path, &there) {
+                return self.find(b, value_pos, max_depth, &there, path);
+            }
+
+            pos = self
+                .call(|h| lua::sample_value(b, self.options(), h, value_pos, max_depth))?
+                .1;
+        }
+
+        Ok(Found::Nothing)
+    }
+
+    /// If the value at `pos` is a table, returns the position of its first entry.
+    fn table(&self, b: &[u8], pos: usize, max_depth: u16) -> Result<Option<usize>, Error> {
+        self.call(|h| lua::sample_table(b, self.options(), h, pos, max_depth))
+    }
+
+    /// Calls a grammar rule with this parser's hooks.
+    fn call<T>(
+        &self,
+        rule: impl FnOnce(&Hooks<'_>) -> Result<T, peg::error::ParseError<usize>>,
+    ) -> Result<T, Error> {
+        let hooks = Hooks::new(self.options().cancellation.as_ref());
+        hooks.finish(rule(&hooks))
+    }
+}
@@ -0,0 +1,580 @@
+//! Lua script statements.
+use crate::{
+    error::{append_path, PathKey},
+    footer::strip_footer,
+    peg_parser::lua::{
+        comment_end, end_of_script, next_assignment, script as peg_script, script_limited,
+        script_return as peg_script_return,
+    },
+    progress::Hooks,
+    Error, LuaMapKey, LuaTableEntry, LuaValue, ParseOptions,
+};
+use std::{borrow::Cow, ops::Range};
+
+/// Parse a Lua script containing variable assignments into a [`Vec`] of [`ScriptStatement`], with
+/// [`ParseOptions`].
+///
+/// See [`script()`][crate::script()] for more details.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{script_with_options, Error, Parser};
+///
+/// let opts = Parser::builder().max_statements(2).build_options();
+///
+/// assert_eq!(2, script_with_options(b"a = 1\nb = 2", &opts).unwrap().len());
+/// assert_eq!(
+///     Error::TooManyStatements { limit: 2 },
+///     script_with_options(b"a = 1\nb = 2\nc = 3", &opts).unwrap_err(),
+/// );
+/// ```
+pub fn script_with_options<'a>(
+    b: &'a [u8],
+    opts: &ParseOptions,
+) -> Result<Vec<ScriptStatement<'a>>, Error> {
+    script_with_hooks(b, opts, &Hooks::new(opts.cancellation.as_ref()))
+}
+
+/// Parse a Lua script like [`script_with_options()`], calling `hooks` during parsing.
+pub(crate) fn script_with_hooks<'a>(
+    b: &'a [u8],
+    opts: &ParseOptions,
+    hooks: &Hooks<'_>,
+) -> Result<Vec<ScriptStatement<'a>>, Error> {
+    let b = strip_footer(b, opts)?;
+    let statements = match opts.max_statements {
+        None => hooks.finish(peg_script(b, opts, hooks))?,
+        Some(limit) => match hooks.finish(script_limited(b, opts, hooks, limit))? {
+            (_, true) => return Err(Error::TooManyStatements { limit }),
+            (s, false) => s,
+        },
+    };
+
+    merge_fields(statements)
+}
+
+/// Parse a Lua script containing variable assignments lazily, one statement at a time, with
+/// [`ParseOptions`].
+///
+/// Each call to [`Iterator::next()`] parses one more statement, so a caller which only needs the
+/// first few variables of a large script can stop early without parsing (or even validating) the
+/// rest of it.
+///
+/// After returning an error, the iterator returns [`None`].
+///
+/// [Field assignments][ParseOptions::field_assignments] aren't supported (they would change
+/// statements which have already been returned), and are always a syntax error. Use
+/// [`script_with_options()`] to parse them.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{script_iter, LuaValue, ParseOptions};
+///
+/// let opts = ParseOptions::new(16);
+/// let version = script_iter(b"version = 3\ndata = {1, 2, 3}\n{{{ invalid", &opts)
+///     .find_map(|s| match s {
+///         Ok(s) if s.name == "version" => Some(Ok(s.value)),
+///         Ok(_) => None,
+///         Err(e) => Some(Err(e)),
+///     });
+///
+/// assert_eq!(Some(LuaValue::integer(3)), version.transpose().unwrap());
+/// ```
+pub fn script_iter<'a, 'o>(b: &'a [u8], opts: &'o ParseOptions) -> ScriptIter<'a, 'o> {
+    ScriptIter::new(b, Cow::Borrowed(opts))
+}
+
+/// Iterator over the statements in a Lua script, returned by [`script_iter()`].
+#[derive(Debug)]
+pub struct ScriptIter<'a, 'o> {
+    b: &'a [u8],
+    opts: Cow<'o, ParseOptions>,
+
+    /// Position of the next statement.
+    pos: usize,
+
+    /// Index of the next statement.
+    index: usize,
+
+    /// End of the previous statement, for [`ParseOptions::comments`].
+    prev_end: Option<usize>,
+
+    /// Set once the end of the script or an error has been reached.
+    done: bool,
+}
+
+impl<'a, 'o> ScriptIter<'a, 'o> {
+    pub(crate) fn new(b: &'a [u8], opts: Cow<'o, ParseOptions>) -> Self {
+        Self {
+            b,
+            opts,
+            pos: 0,
+            index: 0,
+            prev_end: None,
+            done: false,
+        }
+    }
+}
+
+impl<'a> Iterator for ScriptIter<'a, '_> {
+    type Item = Result<ScriptStatement<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        if self.index == 0 && self.pos == 0 {
+            match strip_footer(self.b, &self.opts) {
+                Ok(b) => self.b = b,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        if self.opts.max_statements == Some(self.index) {
+            // Check that there are no more statements, without parsing them.
+            self.done = true;
+            return end_of_script(self.b, &self.opts, &Hooks::default(), self.pos)
+                .is_err()
+                .then_some(Err(Error::TooManyStatements { limit: self.index }));
+        }
+
+        let hooks = Hooks::new(self.opts.cancellation.as_ref());
+        match hooks.finish(next_assignment(self.b, &self.opts, &hooks, self.pos)) {
+            Ok(Some((mut s, next))) => {
+                s.index = self.index;
+                if self.opts.comments {
+                    s.comment = leading_comment(self.b, &self.opts, self.prev_end, s.span.start);
+                }
+                self.index += 1;
+                self.prev_end = Some(s.span.end);
+                self.pos = next;
+                Some(Ok(s))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl std::iter::FusedIterator for ScriptIter<'_, '_> {}
+
+/// Parse a Lua script containing variable assignments, followed by an optional `return`
+/// statement, with [`ParseOptions`].
+///
+/// Some software saves its state as a script which assigns some globals, then returns one of them
+/// (or another value):
+///
+/// ```lua
+/// seen = {"a", "b"}
+/// count = 2
+/// return seen
+/// ```
+///
+/// The `return` statement must be the last statement in the script. If it returns a global
+/// variable by name, its value is the most recent assignment to that variable, or `nil` if it was
+/// never assigned. This takes priority over
+/// [bare identifiers][ParseOptions::bare_identifiers].
+///
+/// The [`max_statements`][ParseOptions::max_statements] limit doesn't include the `return`
+/// statement.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{script_return_with_options, LuaValue, ParseOptions};
+///
+/// let opts = ParseOptions::new(16);
+/// let s = script_return_with_options(b"a = 1\nb = {1}\nreturn b", &opts).unwrap();
+/// assert_eq!(2, s.statements.len());
+/// assert_eq!(Some(LuaValue::Table(vec![1.into()])), s.value);
+///
+/// // The return statement is optional
+/// let s = script_return_with_options(b"a = 1", &opts).unwrap();
+/// assert_eq!(("a", LuaValue::integer(1)), s.statements[0]);
+/// assert_eq!(None, s.value);
+///
+/// // A return statement on its own is allowed too
+/// let s = script_return_with_options(b"return {}", &opts).unwrap();
+/// assert!(s.statements.is_empty());
+/// assert_eq!(Some(LuaValue::Table(vec![])), s.value);
+/// ```
+pub fn script_return_with_options<'a>(
+    b: &'a [u8],
+    opts: &ParseOptions,
+) -> Result<ScriptReturn<'a>, Error> {
+    script_return_with_hooks(b, opts, &Hooks::new(opts.cancellation.as_ref()))
+}
+
+/// Parse a Lua script like [`script_return_with_options()`], calling `hooks` during parsing.
+pub(crate) fn script_return_with_hooks<'a>(
+    b: &'a [u8],
+    opts: &ParseOptions,
+    hooks: &Hooks<'_>,
+) -> Result<ScriptReturn<'a>, Error> {
+    let b = strip_footer(b, opts)?;
+    let limit = opts.max_statements.unwrap_or(usize::MAX);
+    let (statements, returned) = match hooks.finish(peg_script_return(b, opts, hooks, limit))? {
+        ((_, true), _) => return Err(Error::TooManyStatements { limit }),
+        ((s, false), r) => (s, r),
+    };
+
+    let statements = merge_fields(statements)?;
+    let value = returned.map(|r| match r {
+        Returned::Value(v) => v,
+        Returned::Name(name) => statements
+            .iter()
+            .rev()
+            .find(|s| s.name == name)
+            .map_or(LuaValue::Nil, |s| s.value.clone()),
+    });
+
+    Ok(ScriptReturn { statements, value })
+}
+
+/// Returns the comments in `input` before a statement starting at `start`, where the previous
+/// statement ended at `prev_end`.
+///
+/// Everything between the statements is whitespace, comments or `;`. Comments which start on the
+/// same line as the previous statement belong to it, and a `;` separates comments from the next
+/// statement.
+fn leading_comment<'a>(
+    input: &'a [u8],
+    opts: &ParseOptions,
+    prev_end: Option<usize>,
+    start: usize,
+) -> Option<&'a [u8]> {
+    let input = &input[..start];
+    let mut pos = prev_end.unwrap_or(0);
+    let mut new_line = prev_end.is_none();
+    let mut first = None;
+    while let Some(&c) = input.get(pos) {
+        match c {
+            b'\n' | b'\r' => new_line = true,
+            b';' => first = None,
+            c if c.is_ascii_whitespace() => (),
+            _ => {
+                let end = comment_end(input, opts, &Hooks::default(), pos).ok()?;
+                if new_line && first.is_none() {
+                    first = Some(pos);
+                }
+                pos = end;
+                continue;
+            }
+        }
+        pos += 1;
+    }
+
+    first.map(|first| input[first..].trim_ascii_end())
+}
+
+/// The value of a `return` statement at the end of a script.
+pub(crate) enum Returned<'a> {
+    /// `return name`, referring to a global variable.
+    Name(&'a str),
+
+    /// `return value`
+    Value(LuaValue<'a>),
+}
+
+/// A statement in a script, before [field assignments][ParseOptions::field_assignments] are
+/// merged.
+pub(crate) enum Statement<'a> {
+    /// `name = value`
+    Global(ScriptStatement<'a>),
+
+    /// `name.field[key] = value`
+    Field(FieldAssignment<'a>),
+}
+
+impl<'a> Statement<'a> {
+    /// Numbers each statement in a script, and with [`ParseOptions::comments`], captures the
+    /// comments before each global assignment.
+    ///
+    /// `statements` are the statements and the position of their end, in `input`.
+    pub(crate) fn finish(
+        input: &'a [u8],
+        opts: &ParseOptions,
+        statements: Vec<(Self, usize)>,
+    ) -> Vec<Self> {
+        let mut prev_end = None;
+        statements
+            .into_iter()
+            .enumerate()
+            .map(|(index, (mut s, end))| {
+                if let Self::Global(s) = &mut s {
+                    s.index = index;
+                    if opts.comments {
+                        s.comment = leading_comment(input, opts, prev_end, s.span.start);
+                    }
+                }
+                prev_end = Some(end);
+                s
+            })
+            .collect()
+    }
+
+    /// Returns the statement if it is a [global assignment][Self::Global].
+    pub(crate) fn into_global(self) -> Option<ScriptStatement<'a>> {
+        match self {
+            Self::Global(s) => Some(s),
+            Self::Field(_) => None,
+        }
+    }
+}
+
+/// An assignment to a field of a table stored in a global variable, like `name.a[1] = value`.
+pub(crate) struct FieldAssignment<'a> {
+    pub name: &'a str,
+    pub keys: Vec<FieldKey<'a>>,
+    pub value: LuaValue<'a>,
+}
+
+/// A field of a table being assigned to.
+pub(crate) enum FieldKey<'a> {
+    /// `.name`
+    Name(&'a str),
+
+    /// `[key]`
+    Value(LuaValue<'a>),
+}
+
+impl<'a> FieldKey<'a> {
+    pub(crate) fn path_key(&self) -> PathKey<'a> {
+        match self {
+            Self::Name(n) => PathKey::Name(Cow::Borrowed(n)),
+            Self::Value(v) => PathKey::from(v),
+        }
+    }
+
+    /// Returns `true` if a table entry with key `k` (or implicit index `i`) has this key.
+    fn matches(&self, entry: &LuaTableEntry, i: i64) -> bool {
+        let k = match entry {
+            LuaTableEntry::NameValue(b) => return self.matches_name(&b.0),
+            LuaTableEntry::KeyValue(b) => &b.0,
+            _ => return matches!(self, Self::Value(v) if Self::same_key(v, &LuaValue::integer(i))),
+        };
+
+        match (self, k) {
+            (Self::Name(n), LuaValue::String(k)) => n.as_bytes() == k.as_ref(),
+            (Self::Name(_), _) => false,
+            (Self::Value(v), k) => Self::same_key(v, k),
+        }
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        match self {
+            Self::Name(n) => *n == name,
+            Self::Value(LuaValue::String(k)) => name.as_bytes() == k.as_ref(),
+            Self::Value(_) => false,
+        }
+    }
+
+    /// Compares keys like Lua, where `[1]` and `[1.0]` are the same key.
+    fn same_key(a: &LuaValue, b: &LuaValue) -> bool {
+        match (a, b) {
+            (LuaValue::Number(_), LuaValue::Number(_)) => {
+                LuaMapKey::try_from(a.clone()) == LuaMapKey::try_from(b.clone())
+            }
+            _ => a == b,
+        }
+    }
+
+    fn into_entry(self, value: LuaValue<'a>) -> LuaTableEntry<'a> {
+        match self {
+            Self::Name(n) => LuaTableEntry::NameValue(Box::new((Cow::Borrowed(n), value))),
+            Self::Value(k) => LuaTableEntry::KeyValue(Box::new((k, value))),
+        }
+    }
+}
+
+/// Merges [field assignments][ParseOptions::field_assignments] into the most recent assignment of
+/// each global.
+fn merge_fields(statements: Vec<Statement<'_>>) -> Result<Vec<ScriptStatement<'_>>, Error> {
+    let mut out: Vec<ScriptStatement> = Vec::with_capacity(statements.len());
+    for s in statements {
+        let f = match s {
+            Statement::Global(s) => {
+                out.push(s);
+                continue;
+            }
+            Statement::Field(f) => f,
+        };
+
+        let mut path = f.name.to_string();
+        let Some(global) = out.iter_mut().rev().find(|s| s.name == f.name) else {
+            return Err(Error::IndexNotTable { path });
+        };
+
+        let mut value = &mut global.value;
+        let mut keys = f.keys.into_iter().peekable();
+        while let Some(key) = keys.next() {
+            let LuaValue::Table(entries) = value else {
+                return Err(Error::IndexNotTable { path });
+            };
+
+            let mut i = 0;
+            let found = entries
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| {
+                    if matches!(e, LuaTableEntry::KeyValue(_) | LuaTableEntry::NameValue(_)) {
+                        key.matches(e, 0)
+                    } else {
+                        i += 1;
+                        key.matches(e, i)
+                    }
+                })
+                .map(|(j, _)| j)
+                .last();
+
+            if keys.peek().is_none() {
+                // Last key: replace or add the entry
+                match found {
+                    Some(j) => match &mut entries[j] {
+                        LuaTableEntry::KeyValue(b) => b.1 = f.value,
+                        LuaTableEntry::NameValue(b) => b.1 = f.value,
+                        e => *e = LuaTableEntry::Value(Box::new(f.value)),
+                    },
+                    None => entries.push(key.into_entry(f.value)),
+                }
+                break;
+            }
+
+            path = append_path(&path, &key.path_key());
+            value = match found.map(|j| &mut entries[j]) {
+                Some(LuaTableEntry::KeyValue(b)) => &mut b.1,
+                Some(LuaTableEntry::NameValue(b)) => &mut b.1,
+                Some(LuaTableEntry::Value(v)) => v,
+                _ => return Err(Error::IndexNotTable { path }),
+            };
+        }
+    }
+
+    Ok(out)
+}
+
+/// A single variable assignment statement in a [Lua script][crate::script()], `name = value`.
+///
+/// Scripts may assign to the same `name` more than once; each assignment is returned as a separate
+/// [`ScriptStatement`], in the order they appear in the script.
+///
+/// [`ScriptStatement`] can be converted into a `(&str, LuaValue)` tuple, and compared with one:
+///
+/// ```rust
+/// use serde_luaq::{script, LuaValue};
+///
+/// let s = script(b"hello = true\ngoodbye = false", 16).unwrap();
+/// assert_eq!(("hello", LuaValue::Boolean(true)), s[0]);
+/// assert_eq!(0..12, s[0].span);
+/// assert_eq!(13..28, s[1].span);
+/// assert_eq!(1, s[1].index);
+///
+/// let (name, value) = s[1].clone().into();
+/// assert_eq!("goodbye", name);
+/// assert_eq!(LuaValue::Boolean(false), value);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptStatement<'a> {
+    /// The name of the variable being assigned.
+    pub name: &'a str,
+
+    /// The value assigned to the variable.
+    pub value: LuaValue<'a>,
+
+    /// Byte offsets of the statement in the input script, from the start of `name` (or `local`)
+    /// to the end of `value`.
+    ///
+    /// This does not include any surrounding whitespace, or trailing semicolons (`;`).
+    pub span: Range<usize>,
+
+    /// Index of the statement in the script, starting at `0`.
+    pub index: usize,
+
+    /// `true` if the variable was declared `local`, with
+    /// [`ParseOptions::local_assignments`][crate::ParseOptions::local_assignments].
+    pub local: bool,
+
+    /// The block of comments before the statement, with
+    /// [`ParseOptions::comments`][crate::ParseOptions::comments].
+    pub comment: Option<&'a [u8]>,
+}
+
+impl<'a> From<ScriptStatement<'a>> for (&'a str, LuaValue<'a>) {
+    fn from(s: ScriptStatement<'a>) -> Self {
+        (s.name, s.value)
+    }
+}
+
+impl<'a> From<ScriptStatement<'a>> for LuaTableEntry<'a> {
+    /// Converts a [`ScriptStatement`] into a [`LuaTableEntry::NameValue`].
+    fn from(s: ScriptStatement<'a>) -> Self {
+        LuaTableEntry::from((s.name, s.value))
+    }
+}
+
+impl<'a> FromIterator<ScriptStatement<'a>> for LuaValue<'a> {
+    fn from_iter<T: IntoIterator<Item = ScriptStatement<'a>>>(iter: T) -> Self {
+        LuaValue::Table(iter.into_iter().map(From::from).collect())
+    }
+}
+
+impl<'a> PartialEq<(&'a str, LuaValue<'a>)> for ScriptStatement<'a> {
+    fn eq(&self, other: &(&'a str, LuaValue<'a>)) -> bool {
+        self.name == other.0 && self.value == other.1
+    }
+}
+
+impl<'a> PartialEq<ScriptStatement<'a>> for (&'a str, LuaValue<'a>) {
+    fn eq(&self, other: &ScriptStatement<'a>) -> bool {
+        other == self
+    }
+}
+
+/// A [Lua script][script_return_with_options] containing variable assignments, followed by an
+/// optional `return` statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptReturn<'a> {
+    /// Variable assignments in the script, like [`script_with_options()`].
+    pub statements: Vec<ScriptStatement<'a>>,
+
+    /// The value returned by the script, or [`None`] if it has no `return` statement.
+    pub value: Option<LuaValue<'a>>,
+}
+
+impl<'a> ScriptReturn<'a> {
+    /// Converts the script into a single [`LuaValue`]: the returned value, or if there was no
+    /// `return` statement, a table of all variable assignments.
+    ///
+    /// This is how [`LuaFormat::ScriptReturn`][crate::LuaFormat::ScriptReturn] is deserialised.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{script_return_with_options, LuaValue, ParseOptions};
+    ///
+    /// let opts = ParseOptions::new(16);
+    /// let v = script_return_with_options(b"a = 1; return 2", &opts).unwrap().into_value();
+    /// assert_eq!(LuaValue::integer(2), v);
+    ///
+    /// let v = script_return_with_options(b"a = 1", &opts).unwrap().into_value();
+    /// assert_eq!(LuaValue::Table(vec![("a", LuaValue::integer(1)).into()]), v);
+    /// ```
+    pub fn into_value(self) -> LuaValue<'a> {
+        match self.value {
+            Some(v) => v,
+            None => self.statements.into_iter().collect(),
+        }
+    }
+}
@@ -0,0 +1,1929 @@
+//! Serialising Rust types as Lua source code.
+use crate::{
+    error::PathKey, peg_parser::lua, progress::Hooks, valid_lua_identifier, Error, LuaFormat,
+    LuaNumber, LuaTableEntry, LuaValue, ParseOptions, ScriptStatement,
+};
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq};
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+    str::from_utf8,
+};
+
+/// Newtype struct name used by [`LuaValue`]'s [`Serialize`] implementation for the implicit keys
+/// of tables which also have explicit keys (like `{1, 2, a = 3}`).
+///
+/// [`Serializer`] writes these entries without a key. Other serializers see the key as an integer.
+const IMPLICIT_KEY: &str = "$serde_luaq::ImplicitKey";
+
+/// Serialises any [`Serialize`] type as Lua source code, in [`format`][LuaFormat], to `writer`.
+///
+/// Types are converted in the same way as [`to_value()`][crate::to_value], so the output is read
+/// back as the same [`LuaValue`] that `to_value()` returns:
+///
+/// * `()`, unit structs and [`None`] are written as `nil`.
+/// * Integers are written in decimal. Unsigned integers greater than [`i64::MAX`] are written in
+///   hexadecimal (`0xffffffffffffffff`), which Lua reads as the [`i64`] with the same bits; read
+///   them back with [`DeserializeOptions::unsigned_integers`][crate::DeserializeOptions::unsigned_integers].
+///   Integers outside the range of [`i64`] and [`u64`] return an error.
+///
+///   [`to_value()`][crate::to_value] can't represent unsigned integers greater than [`i64::MAX`],
+///   so returns an error for them.
+/// * Strings, [`char`]s and byte arrays (with [`serde_bytes`][serde_bytes]) are written as
+///   double-quoted strings.
+/// * Sequences and tuples are written as tables with implicit keys (`{1, 2, 3}`).
+/// * Maps and structs are written as tables with explicit keys. Keys which are valid Lua
+///   identifiers are written as names (`{a = 1}`), and other keys in brackets (`{["a b"] = 1}`).
+///   Keys which are `nil` or NaN return an error.
+/// * Unit enum variants are written as a string (`"Variant"`), and other enum variants as a table
+///   with a single entry (`{Variant = value}`).
+///
+/// Strings and numbers are written in the same form as Lua's [`string.format('%q')`][format]:
+///
+/// * strings are double-quoted. `"`, `\` and line feeds are escaped with a backslash, carriage
+///   returns are written as `\r`, and other control characters are written as decimal escapes
+///   (`\27`).
+///
+/// * [`i64::MIN`] is written as `0x8000000000000000`.
+///
+/// * infinities and NaN are written as `1e9999`, `-1e9999` and `(0/0)`.
+///
+/// Unlike `%q`, finite floats are written in decimal (`2.5`) rather than hexadecimal, with the
+/// shortest representation which reads back as the same value. Floats always contain a `.` or an
+/// exponent, so they are read back as floats rather than integers.
+///
+/// Unlike Lua, bytes in strings which aren't valid UTF-8 are written as decimal escapes, so the
+/// output is always valid UTF-8.
+///
+/// The output can be read in [strict `%q` mode][crate::ParseOptions::strict_q], unless it has
+/// unsigned integers greater than [`i64::MAX`].
+///
+/// ## Formats
+///
+/// * [`LuaFormat::Value`] writes a bare expression: `{a = 1}`
+/// * [`LuaFormat::Return`] writes a `return` statement: `return {a = 1}`
+/// * [`LuaFormat::Script`] and [`LuaFormat::ScriptReturn`] write each entry of a map or struct as
+///   a variable assignment on its own line: `a = 1`. Other types, and keys which aren't valid Lua
+///   identifiers, return an error.
+///
+/// ## `LuaValue`
+///
+/// [`LuaValue`] implements [`Serialize`], so parsed values can be written back out. Entries with
+/// implicit keys are written without a key (`{1, 2, a = 3}`), and other entries are written like
+/// map entries.
+///
+/// Strings and keys are escaped straight from the value into `writer`, and tables are written one
+/// entry at a time, so writing a `LuaValue` doesn't copy it or build the output in memory first.
+///
+/// This makes many small writes, so `writer` should be buffered (for example, with
+/// [`BufWriter`][std::io::BufWriter]).
+///
+/// Tables nested more than [`DEFAULT_SERIALIZE_MAX_DEPTH`] deep return an error. To change how
+/// the output is formatted, or the maximum depth, use a [`Serializer`].
+///
+/// [format]: https://www.lua.org/manual/5.4/manual.html#pdf-string.format
+/// [serde_bytes]: https://docs.rs/serde_bytes
+pub fn to_writer<W, T>(writer: W, value: &T, format: LuaFormat) -> Result<(), Error>
+where
+    W: Write,
+    T: Serialize + ?Sized,
+{
+    value.serialize(&mut Serializer::with_format(writer, format))
+}
+
+/// Serialises any [`Serialize`] type as Lua source code, in [`format`][LuaFormat], like
+/// [`to_writer()`], returning the output as bytes.
+pub fn to_vec<T>(value: &T, format: LuaFormat) -> Result<Vec<u8>, Error>
+where
+    T: Serialize + ?Sized,
+{
+    let mut out = Vec::new();
+    to_writer(&mut out, value, format)?;
+    Ok(out)
+}
+
+/// Serialises any [`Serialize`] type as Lua source code, in [`format`][LuaFormat], like
+/// [`to_writer()`], returning the output as a [`String`].
+///
+/// ## Example
+///
+/// ```rust
+/// use serde::Serialize;
+/// use serde_luaq::{lua_value, return_statement, to_string, LuaFormat, LuaValue};
+///
+/// #[derive(Serialize)]
+/// struct Player {
+///     name: &'static str,
+///     scores: Vec<u32>,
+///     best: Option<f64>,
+/// }
+///
+/// let player = Player { name: "alice", scores: vec![3, 5], best: Some(2.5) };
+/// assert_eq!(
+///     r#"{name = "alice", scores = {3, 5}, best = 2.5}"#,
+///     to_string(&player, LuaFormat::Value).unwrap(),
+/// );
+/// assert_eq!(
+///     "name = \"alice\"\nscores = {3, 5}\nbest = 2.5\n",
+///     to_string(&player, LuaFormat::Script).unwrap(),
+/// );
+///
+/// // Parsed values can be written back out
+/// let value = lua_value(br#"{hello = "world", 1, 2.5, ["a b"] = {true, nil}}"#, 16).unwrap();
+/// let lua = to_string(&value, LuaFormat::Return).unwrap();
+/// assert_eq!(r#"return {hello = "world", 1, 2.5, ["a b"] = {true, nil}}"#, lua);
+/// assert_eq!(value, return_statement(lua.as_bytes(), 16).unwrap());
+///
+/// assert_eq!(
+///     r#""line 1\
+/// line 2\r\0\"\\\255""#,
+///     to_string(&LuaValue::from(&b"line 1\nline 2\r\0\"\\\xff"[..]), LuaFormat::Value).unwrap(),
+/// );
+/// ```
+pub fn to_string<T>(value: &T, format: LuaFormat) -> Result<String, Error>
+where
+    T: Serialize + ?Sized,
+{
+    Ok(String::from_utf8(to_vec(value, format)?).expect("output is always valid UTF-8"))
+}
+
+/// Returns the number of bytes that [`to_writer()`] writes for `value` in [`LuaFormat::Value`],
+/// without writing or allocating the output.
+///
+/// This can be used to pre-allocate an output buffer, or to check that the output fits in a quota
+/// before writing it. [`LuaFormat::Return`] writes 7 more bytes (`return `).
+///
+/// Scalar values are measured with [`DefaultFormatter`], so this is exact, but only for the
+/// default style. If `value` can't be serialised (like a table with a `nil` key, or tables nested
+/// deeper than [`DEFAULT_SERIALIZE_MAX_DEPTH`]), this returns the size that it would have had.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{estimate_serialized_size, lua_value, to_vec, LuaFormat};
+///
+/// let value = lua_value(br#"{1, 2.5, name = "a\nb", ["a b"] = {true}}"#, 16).unwrap();
+/// let size = estimate_serialized_size(&value);
+///
+/// let mut out = Vec::with_capacity(size);
+/// serde_luaq::to_writer(&mut out, &value, LuaFormat::Value).unwrap();
+/// assert_eq!(size, out.len());
+/// ```
+pub fn estimate_serialized_size(value: &LuaValue<'_>) -> usize {
+    match value {
+        LuaValue::Table(entries) => {
+            // `{` and `}`, and `, ` between entries
+            let mut size = 2 + entries.len().saturating_sub(1) * 2;
+            for entry in entries {
+                size += match entry {
+                    LuaTableEntry::KeyValue(b) => key_size(&b.0) + estimate_serialized_size(&b.1),
+                    LuaTableEntry::NameValue(b) => {
+                        string_key_size(b.0.as_bytes()) + estimate_serialized_size(&b.1)
+                    }
+                    // Implicitly-keyed entries are written without a key.
+                    LuaTableEntry::Value(v) => estimate_serialized_size(v),
+                    LuaTableEntry::NumberValue(n) => scalar_size(&LuaValue::Number(*n)),
+                    LuaTableEntry::BooleanValue(b) => scalar_size(&LuaValue::Boolean(*b)),
+                    LuaTableEntry::NilValue => scalar_size(&LuaValue::Nil),
+                };
+            }
+            size
+        }
+        v => scalar_size(v),
+    }
+}
+
+/// Returns the size of a table key, including the ` = ` after it.
+fn key_size(key: &LuaValue<'_>) -> usize {
+    match key {
+        LuaValue::String(s) if from_utf8(s).is_ok() => string_key_size(s),
+        // `[` and `] = `
+        key => 5 + estimate_serialized_size(key),
+    }
+}
+
+/// Returns the size of a string table key, including the ` = ` after it.
+fn string_key_size(name: &[u8]) -> usize {
+    if valid_lua_identifier(name) {
+        name.len() + 3
+    } else {
+        5 + scalar_size(&LuaValue::String(Cow::Borrowed(name)))
+    }
+}
+
+/// Returns the size of a value which isn't a table, written by [`DefaultFormatter`].
+fn scalar_size(value: &LuaValue<'_>) -> usize {
+    let mut w = ByteCounter(0);
+    let mut f = DefaultFormatter::new();
+    let r = match value {
+        LuaValue::Nil => f.write_nil(&mut w),
+        LuaValue::Boolean(b) => f.write_bool(&mut w, *b),
+        LuaValue::Number(LuaNumber::Integer(i)) => f.write_integer(&mut w, *i),
+        LuaValue::Number(LuaNumber::Float(v)) => f.write_float(&mut w, *v),
+        LuaValue::String(s) => f.write_string(&mut w, s),
+        LuaValue::Table(_) => unreachable!("tables aren't scalars"),
+    };
+    r.expect("ByteCounter never fails");
+    w.0
+}
+
+/// [`Write`] implementation which only counts the bytes written to it.
+struct ByteCounter(usize);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// [`serde::Serializer`] which writes Lua source code. See [`to_writer()`] for details.
+///
+/// The output is formatted with a [`Formatter`], which defaults to [`DefaultFormatter`].
+///
+/// ## Maximum depth
+///
+/// Serde types can nest without limit, and a recursive [`Serialize`] implementation (like a
+/// cycle of [`Rc`][std::rc::Rc]s) never ends. To avoid overflowing the stack, tables can only be
+/// nested [`max_depth`][Self::max_depth] deep, which defaults to
+/// [`DEFAULT_SERIALIZE_MAX_DEPTH`]. Deeper tables return [`Error::RecursionLimit`].
+///
+/// This works like the parser's `max_depth`: the top-level table (or script) is at depth `1`,
+/// and every table written counts, including the `{Variant = ...}` tables around enum values.
+///
+/// ```rust
+/// use serde::Serialize;
+/// use serde_luaq::{Error, LuaFormat, Serializer};
+///
+/// let mut ser = Serializer::new(Vec::new()).max_depth(Some(2));
+/// vec![vec![1]].serialize(&mut ser).unwrap();
+/// assert_eq!(b"{{1}}", ser.into_inner().as_slice());
+///
+/// let mut ser = Serializer::new(Vec::new()).max_depth(Some(2));
+/// let err = vec![vec![vec![1]]].serialize(&mut ser).unwrap_err();
+/// assert_eq!(&Error::RecursionLimit { limit: 2 }, err.without_path());
+/// assert_eq!(Some("[1][1]"), err.path());
+/// ```
+#[derive(Debug)]
+pub struct Serializer<W, F = DefaultFormatter> {
+    writer: W,
+    formatter: F,
+
+    /// Format of the top-level value, which is reset to [`LuaFormat::Value`] once it is written.
+    format: LuaFormat,
+
+    /// Maximum number of nested tables, or [`None`] for no limit.
+    max_depth: Option<u16>,
+
+    /// Number of tables currently being written.
+    depth: u16,
+}
+
+/// Default [maximum depth][Serializer::max_depth] of tables written by a [`Serializer`].
+///
+/// This is the same as [`RECOMMENDED_MAX_DEPTH_MAIN_THREAD`][crate::RECOMMENDED_MAX_DEPTH_MAIN_THREAD],
+/// so anything written with the default can be parsed back on the main thread.
+pub const DEFAULT_SERIALIZE_MAX_DEPTH: u16 = crate::RECOMMENDED_MAX_DEPTH_MAIN_THREAD;
+
+impl<W: Write> Serializer<W> {
+    /// Creates a serializer which writes a bare Lua expression to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self::with_format(writer, LuaFormat::Value)
+    }
+
+    /// Creates a serializer which writes Lua in [`format`][LuaFormat] to `writer`.
+    pub fn with_format(writer: W, format: LuaFormat) -> Self {
+        Self::with_formatter(writer, format, DefaultFormatter::new())
+    }
+}
+
+impl<W: Write, F: Formatter> Serializer<W, F> {
+    /// Creates a serializer which writes Lua in [`format`][LuaFormat] to `writer`, formatted
+    /// with `formatter`.
+    pub fn with_formatter(writer: W, format: LuaFormat, formatter: F) -> Self {
+        Self {
+            writer,
+            formatter,
+            format,
+            max_depth: Some(DEFAULT_SERIALIZE_MAX_DEPTH),
+            depth: 0,
+        }
+    }
+
+    /// Sets the maximum number of nested tables to write, or [`None`] for no limit.
+    ///
+    /// Defaults to [`DEFAULT_SERIALIZE_MAX_DEPTH`]. See [above][Self#maximum-depth] for details.
+    pub fn max_depth(mut self, max_depth: Option<u16>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Writes parsed script statements as a script, with each statement's
+    /// [`comment`][ScriptStatement::comment] on the lines before it.
+    ///
+    /// This allows a script parsed with [`ParseOptions::comments`] to be written back out
+    /// without losing its comments. Statements are written like the entries of a map, so the
+    /// serializer must have been created with [`LuaFormat::Script`] or
+    /// [`LuaFormat::ScriptReturn`]. Statements are always written as global assignments, even if
+    /// they were declared [`local`][ScriptStatement::local].
+    ///
+    /// Comments are written with [`Formatter::write_comment()`]. Comments which aren't only Lua
+    /// comments and whitespace (like `"a = 1"`) return an error.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{LuaFormat, LuaValue, Parser, Serializer};
+    ///
+    /// let lua = b"-- player data\nplayer = {name = 'alice'}\n\n--[[ level ]]\nlevel = 3\n";
+    /// let mut statements = Parser::builder().comments(true).build().script(lua).unwrap();
+    /// statements[1].value = LuaValue::integer(4);
+    ///
+    /// let mut ser = Serializer::with_format(Vec::new(), LuaFormat::Script);
+    /// ser.serialize_script(&statements).unwrap();
+    /// assert_eq!(
+    ///     "-- player data\nplayer = {name = \"alice\"}\n--[[ level ]]\nlevel = 4\n",
+    ///     String::from_utf8(ser.into_inner()).unwrap(),
+    /// );
+    /// ```
+    pub fn serialize_script(&mut self, statements: &[ScriptStatement<'_>]) -> Result<(), Error> {
+        if !matches!(self.format, LuaFormat::Script | LuaFormat::ScriptReturn) {
+            return Err(ser::Error::custom(
+                "script statements can only be written as a script",
+            ));
+        }
+
+        let mut c = ser::Serializer::serialize_map(&mut *self, Some(statements.len()))?;
+        for s in statements {
+            if let Some(comment) = s.comment {
+                let opts = ParseOptions::new(0);
+                if lua::end_of_script(comment, &opts, &Hooks::default(), 0).is_err() {
+                    return Err(ser::Error::custom(format_args!(
+                        "comment before {:?} is not a Lua comment",
+                        s.name
+                    )));
+                }
+                c.ser.formatter.write_comment(&mut c.ser.writer, comment)?;
+            }
+            c.key(s.name)?;
+            c.value(&s.value, || PathKey::Name(s.name.to_string().into()))?;
+        }
+        c.finish()
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Starts writing a value which isn't a map or struct, writing any `return` keyword first.
+    fn begin_value(&mut self) -> Result<(), Error> {
+        match std::mem::take(&mut self.format) {
+            LuaFormat::Value => Ok(()),
+            LuaFormat::Return => Ok(self.formatter.write_return(&mut self.writer)?),
+            LuaFormat::Script | LuaFormat::ScriptReturn => Err(ser::Error::custom(
+                "scripts can only be written from a map or struct",
+            )),
+        }
+    }
+
+    /// Enters a nested table, checking [`max_depth`][Self::max_depth].
+    fn enter_table(&mut self) -> Result<(), Error> {
+        let depth = self.depth.saturating_add(1);
+        if let Some(limit) = self.max_depth.filter(|&limit| depth > limit) {
+            return Err(Error::RecursionLimit { limit });
+        }
+        self.depth = depth;
+        Ok(())
+    }
+
+    /// Leaves a table entered with [`enter_table()`][Self::enter_table].
+    fn leave_table(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Starts writing a map or struct, returning `true` if it should be written as a script.
+    fn begin_table(&mut self) -> Result<bool, Error> {
+        match std::mem::take(&mut self.format) {
+            LuaFormat::Value => Ok(false),
+            LuaFormat::Return => self.formatter.write_return(&mut self.writer).map(|_| false),
+            LuaFormat::Script | LuaFormat::ScriptReturn => Ok(true),
+        }
+        .map_err(Error::from)
+    }
+
+    fn write_integer<T>(&mut self, v: T) -> Result<(), Error>
+    where
+        T: TryInto<i64> + std::fmt::Display + Copy,
+    {
+        let i = integer(v)?;
+        self.begin_value()?;
+        Ok(self.formatter.write_integer(&mut self.writer, i)?)
+    }
+
+    /// Writes an unsigned integer, which may be greater than [`i64::MAX`].
+    fn write_unsigned<T>(&mut self, v: T) -> Result<(), Error>
+    where
+        T: TryInto<u64> + std::fmt::Display + Copy,
+    {
+        let u = unsigned(v)?;
+        self.begin_value()?;
+        Ok(self.formatter.write_unsigned_integer(&mut self.writer, u)?)
+    }
+
+    /// Writes a string table key followed by ` = `: as a name if it is a valid identifier, or in
+    /// brackets otherwise.
+    fn write_key(&mut self, name: &str) -> io::Result<()> {
+        let (w, f) = (&mut self.writer, &mut self.formatter);
+        if valid_lua_identifier(name.as_bytes()) {
+            f.write_name(w, name)?;
+        } else {
+            f.begin_key(w)?;
+            f.write_string(w, name.as_bytes())?;
+            f.end_key(w)?;
+        }
+        f.begin_value(w)
+    }
+
+    /// Writes the start of a script statement assigning to `name`.
+    fn begin_statement(&mut self, name: &str) -> Result<(), Error> {
+        if !valid_lua_identifier(name.as_bytes()) {
+            return Err(ser::Error::custom(format_args!(
+                "script variable name {name:?} is not a valid Lua identifier"
+            )));
+        }
+        Ok(self.formatter.begin_statement(&mut self.writer, name)?)
+    }
+
+    /// Writes the start of a `{Variant = ...}` table.
+    fn begin_variant(&mut self, variant: &'static str) -> Result<(), Error> {
+        self.enter_table()?;
+        self.formatter.begin_table(&mut self.writer)?;
+        self.formatter.begin_entry(&mut self.writer, true)?;
+        Ok(self.write_key(variant)?)
+    }
+
+    /// Writes the end of a `{Variant = ...}` table.
+    fn end_variant(&mut self) -> Result<(), Error> {
+        self.leave_table();
+        self.formatter.end_entry(&mut self.writer)?;
+        Ok(self.formatter.end_table(&mut self.writer)?)
+    }
+
+    fn compound(&mut self, script: bool, variant: Option<&'static str>) -> Compound<'_, W, F> {
+        Compound {
+            ser: self,
+            len: 0,
+            script,
+            variant,
+            key: false,
+            path: None,
+        }
+    }
+}
+
+/// Converts a map key into a path segment for errors.
+///
+/// This copies the key, so is only used when there is an error, or when the key is serialised
+/// separately from its value.
+fn key_path<K: Serialize + ?Sized>(key: &K) -> PathKey<'static> {
+    match crate::to_value(key) {
+        Ok(LuaValue::String(s)) => match String::from_utf8(s.into_owned()) {
+            Ok(s) => PathKey::Name(Cow::Owned(s)),
+            Err(e) => PathKey::String(Cow::Owned(e.into_bytes())),
+        },
+        Ok(k) => PathKey::from(&k),
+        Err(_) => PathKey::Table,
+    }
+}
+
+/// Converts an integer which may not fit in an [`i64`].
+fn integer<T>(v: T) -> Result<i64, Error>
+where
+    T: TryInto<i64> + std::fmt::Display + Copy,
+{
+    v.try_into().map_err(|_| {
+        ser::Error::custom(format_args!(
+            "integer {v} is out of range for a Lua integer"
+        ))
+    })
+}
+
+/// Converts an unsigned integer which may not fit in a [`u64`].
+fn unsigned<T>(v: T) -> Result<u64, Error>
+where
+    T: TryInto<u64> + std::fmt::Display + Copy,
+{
+    v.try_into().map_err(|_| {
+        ser::Error::custom(format_args!(
+            "integer {v} is out of range for a Lua integer"
+        ))
+    })
+}
+
+impl<'a, W: Write, F: Formatter> ser::Serializer for &'a mut Serializer<W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a, W, F>;
+    type SerializeTuple = Compound<'a, W, F>;
+    type SerializeTupleStruct = Compound<'a, W, F>;
+    type SerializeTupleVariant = Compound<'a, W, F>;
+    type SerializeMap = Compound<'a, W, F>;
+    type SerializeStruct = Compound<'a, W, F>;
+    type SerializeStructVariant = Compound<'a, W, F>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.begin_value()?;
+        Ok(self.formatter.write_bool(&mut self.writer, v)?)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.write_integer(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.write_integer(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.write_integer(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.write_integer(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), Error> {
+        self.write_integer(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.write_integer(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.write_integer(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.write_integer(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.write_unsigned(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), Error> {
+        self.write_unsigned(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.begin_value()?;
+        Ok(self.formatter.write_float(&mut self.writer, v)?)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.begin_value()?;
+        Ok(self.formatter.write_string(&mut self.writer, v)?)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.begin_value()?;
+        Ok(self.formatter.write_nil(&mut self.writer)?)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.begin_value()?;
+        self.begin_variant(variant)?;
+        value
+            .serialize(&mut *self)
+            .map_err(|e| e.prepend_path(PathKey::Name(variant.into())))?;
+        self.end_variant()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a, W, F>, Error> {
+        self.begin_value()?;
+        self.enter_table()?;
+        self.formatter.begin_table(&mut self.writer)?;
+        Ok(self.compound(false, None))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'a, W, F>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a, W, F>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a, W, F>, Error> {
+        self.begin_value()?;
+        self.begin_variant(variant)?;
+        self.enter_table()?;
+        self.formatter.begin_table(&mut self.writer)?;
+        Ok(self.compound(false, Some(variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a, W, F>, Error> {
+        let script = self.begin_table()?;
+        self.enter_table()?;
+        if !script {
+            self.formatter.begin_table(&mut self.writer)?;
+        }
+        Ok(self.compound(script, None))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a, W, F>, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a, W, F>, Error> {
+        self.begin_value()?;
+        self.begin_variant(variant)?;
+        self.enter_table()?;
+        self.formatter.begin_table(&mut self.writer)?;
+        Ok(self.compound(false, Some(variant)))
+    }
+}
+
+/// Writes the entries of a table, or the statements of a script, for [`Serializer`].
+#[derive(Debug)]
+pub struct Compound<'a, W, F = DefaultFormatter> {
+    ser: &'a mut Serializer<W, F>,
+
+    /// Number of entries written so far.
+    len: i64,
+
+    /// Write entries as script statements, rather than table entries.
+    script: bool,
+
+    /// Name of the enum variant this table is the value of, which needs another `}` at the end.
+    variant: Option<&'static str>,
+
+    /// This table is a key of another table, which needs `] = ` at the end.
+    key: bool,
+
+    /// Key of the map entry whose value is about to be written, for error paths.
+    path: Option<PathKey<'static>>,
+}
+
+impl<W: Write, F: Formatter> Compound<'_, W, F> {
+    /// Writes the separator before a table entry.
+    fn begin_entry(&mut self) -> Result<(), Error> {
+        self.len += 1;
+        if !self.script {
+            let first = self.len == 1;
+            self.ser
+                .formatter
+                .begin_entry(&mut self.ser.writer, first)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a value, adding the path from `path` to any error.
+    ///
+    /// `path` is only called if there is an error, so map keys don't need to be copied otherwise.
+    fn value<T, P>(&mut self, value: &T, path: impl FnOnce() -> P) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+        P: std::fmt::Display,
+    {
+        let variant = self.variant;
+        value.serialize(&mut *self.ser).map_err(|e| {
+            let e = e.prepend_path(path());
+            match variant {
+                Some(variant) => e.prepend_path(PathKey::Name(variant.into())),
+                None => e,
+            }
+        })?;
+        let (w, f) = (&mut self.ser.writer, &mut self.ser.formatter);
+        if self.script {
+            f.end_statement(w)?;
+        } else {
+            f.end_entry(w)?;
+        }
+        Ok(())
+    }
+
+    fn element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.begin_entry()?;
+        let index = self.len;
+        self.value(value, || PathKey::Integer(index))
+    }
+
+    fn field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.begin_entry()?;
+        if self.script {
+            self.ser.begin_statement(key)?;
+        } else {
+            self.ser.write_key(key)?;
+        }
+        self.value(value, || PathKey::Name(key.into()))
+    }
+
+    /// Writes the key of a map entry, followed by ` = `.
+    fn key<K>(&mut self, key: &K) -> Result<(), Error>
+    where
+        K: Serialize + ?Sized,
+    {
+        self.begin_entry()?;
+        key.serialize(KeySerializer {
+            ser: &mut *self.ser,
+            script: self.script,
+            implicit: false,
+        })
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        self.ser.leave_table();
+        let (w, f) = (&mut self.ser.writer, &mut self.ser.formatter);
+        if !self.script {
+            f.end_table(w)?;
+        }
+        if self.variant.is_some() {
+            self.ser.end_variant()?;
+        }
+        if self.key {
+            let (w, f) = (&mut self.ser.writer, &mut self.ser.formatter);
+            f.end_key(w)?;
+            f.begin_value(w)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write, F: Formatter> ser::SerializeSeq for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<W: Write, F: Formatter> ser::SerializeTuple for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<W: Write, F: Formatter> ser::SerializeTupleStruct for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<W: Write, F: Formatter> ser::SerializeTupleVariant for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<W: Write, F: Formatter> ser::SerializeMap for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.key(key)?;
+        self.path = Some(key_path(key));
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let path = self.path.take().ok_or_else(|| {
+            <Error as ser::Error>::custom("serialize_value called before serialize_key")
+        })?;
+        self.value(value, || path)
+    }
+
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<(), Error>
+    where
+        K: Serialize + ?Sized,
+        V: Serialize + ?Sized,
+    {
+        self.key(key)?;
+        self.value(value, || key_path(key))
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<W: Write, F: Formatter> ser::SerializeStruct for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<W: Write, F: Formatter> ser::SerializeStructVariant for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+/// Writes the key of a map entry followed by ` = `.
+struct KeySerializer<'a, W, F> {
+    ser: &'a mut Serializer<W, F>,
+
+    /// The key is a script variable name, which must be an identifier.
+    script: bool,
+
+    /// The key is an [implicit key][IMPLICIT_KEY], which isn't written.
+    implicit: bool,
+}
+
+impl<'a, W: Write, F: Formatter> KeySerializer<'a, W, F> {
+    /// Writes a key which isn't a string, in brackets.
+    fn bracketed(
+        self,
+        f: impl FnOnce(&mut Serializer<W, F>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        if self.script {
+            return Err(ser::Error::custom("script variable names must be strings"));
+        }
+        self.ser.formatter.begin_key(&mut self.ser.writer)?;
+        f(self.ser)?;
+        self.ser.formatter.end_key(&mut self.ser.writer)?;
+        Ok(self.ser.formatter.begin_value(&mut self.ser.writer)?)
+    }
+
+    fn integer<T>(self, v: T) -> Result<(), Error>
+    where
+        T: TryInto<i64> + std::fmt::Display + Copy,
+    {
+        let i = integer(v)?;
+        if self.implicit && !self.script {
+            return Ok(());
+        }
+        self.bracketed(|s| Ok(s.formatter.write_integer(&mut s.writer, i)?))
+    }
+
+    /// Writes an unsigned integer key, which may be greater than [`i64::MAX`].
+    fn unsigned<T>(self, v: T) -> Result<(), Error>
+    where
+        T: TryInto<u64> + std::fmt::Display + Copy,
+    {
+        let u = unsigned(v)?;
+        self.bracketed(|s| Ok(s.formatter.write_unsigned_integer(&mut s.writer, u)?))
+    }
+
+    /// Starts writing a table as a key.
+    fn table(self, variant: Option<&'static str>) -> Result<KeyCompound<'a, W, F>, Error> {
+        if self.script {
+            return Err(ser::Error::custom("script variable names must be strings"));
+        }
+        self.ser.formatter.begin_key(&mut self.ser.writer)?;
+        if let Some(variant) = variant {
+            self.ser.begin_variant(variant)?;
+        }
+        self.ser.enter_table()?;
+        self.ser.formatter.begin_table(&mut self.ser.writer)?;
+        let mut c = self.ser.compound(false, variant);
+        c.key = true;
+        Ok(KeyCompound(c))
+    }
+}
+
+impl<'a, W: Write, F: Formatter> ser::Serializer for KeySerializer<'a, W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = KeyCompound<'a, W, F>;
+    type SerializeTuple = KeyCompound<'a, W, F>;
+    type SerializeTupleStruct = KeyCompound<'a, W, F>;
+    type SerializeTupleVariant = KeyCompound<'a, W, F>;
+    type SerializeMap = KeyCompound<'a, W, F>;
+    type SerializeStruct = KeyCompound<'a, W, F>;
+    type SerializeStructVariant = KeyCompound<'a, W, F>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Error> {
+        self.bracketed(|s| Ok(s.formatter.write_bool(&mut s.writer, v)?))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Error> {
+        self.integer(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Error> {
+        self.integer(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Error> {
+        self.integer(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Error> {
+        self.integer(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Error> {
+        self.integer(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Error> {
+        self.integer(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Error> {
+        self.integer(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Error> {
+        self.integer(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Error> {
+        self.unsigned(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Error> {
+        self.unsigned(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Error> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Error> {
+        if v.is_nan() {
+            return Err(ser::Error::custom("table key can't be NaN"));
+        }
+        self.bracketed(|s| Ok(s.formatter.write_float(&mut s.writer, v)?))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Error> {
+        if self.script {
+            self.ser.begin_statement(v)
+        } else {
+            Ok(self.ser.write_key(v)?)
+        }
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Error> {
+        match from_utf8(v) {
+            Ok(v) => self.serialize_str(v),
+            Err(_) => self.bracketed(|s| Ok(s.formatter.write_string(&mut s.writer, v)?)),
+        }
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Error> {
+        Err(ser::Error::custom("table key can't be nil"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Self::Ok, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(KeySerializer {
+            implicit: name == IMPLICIT_KEY,
+            ..self
+        })
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.bracketed(|s| {
+            ser::Serializer::serialize_newtype_variant(s, name, variant_index, variant, value)
+        })
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<KeyCompound<'a, W, F>, Error> {
+        self.table(None)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<KeyCompound<'a, W, F>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<KeyCompound<'a, W, F>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<KeyCompound<'a, W, F>, Error> {
+        self.table(Some(variant))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<KeyCompound<'a, W, F>, Error> {
+        self.table(None)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<KeyCompound<'a, W, F>, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<KeyCompound<'a, W, F>, Error> {
+        self.table(Some(variant))
+    }
+}
+
+/// Writes a table which is the key of another table.
+struct KeyCompound<'a, W, F>(Compound<'a, W, F>);
+
+macro_rules! key_compound_elements {
+    ($($trait:ident::$method:ident),+) => {$(
+        impl<W: Write, F: Formatter> ser::$trait for KeyCompound<'_, W, F> {
+            type Ok = ();
+            type Error = Error;
+
+            fn $method<T>(&mut self, value: &T) -> Result<(), Error>
+            where
+                T: Serialize + ?Sized,
+            {
+                self.0.element(value)
+            }
+
+            fn end(self) -> Result<Self::Ok, Error> {
+                self.0.finish()
+            }
+        }
+    )+};
+}
+
+key_compound_elements! {
+    SerializeSeq::serialize_element,
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field,
+    SerializeTupleVariant::serialize_field
+}
+
+macro_rules! key_compound_fields {
+    ($($trait:ident),+) => {$(
+        impl<W: Write, F: Formatter> ser::$trait for KeyCompound<'_, W, F> {
+            type Ok = ();
+            type Error = Error;
+
+            fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+            where
+                T: Serialize + ?Sized,
+            {
+                self.0.field(key, value)
+            }
+
+            fn end(self) -> Result<Self::Ok, Error> {
+                self.0.finish()
+            }
+        }
+    )+};
+}
+
+key_compound_fields! { SerializeStruct, SerializeStructVariant }
+
+impl<W: Write, F: Formatter> ser::SerializeMap for KeyCompound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.0.serialize_key(key)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.0.serialize_value(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        self.0.finish()
+    }
+}
+
+impl Serialize for LuaValue<'_> {
+    /// Serialises a value as the equivalent Serde type.
+    ///
+    /// Tables with only implicit keys (`{1, 2, 3}`) are serialised as sequences, and other tables
+    /// as maps. Strings which aren't valid UTF-8 are serialised as bytes.
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            LuaValue::Nil => serializer.serialize_unit(),
+            LuaValue::Boolean(b) => serializer.serialize_bool(*b),
+            LuaValue::Number(n) => n.serialize(serializer),
+            LuaValue::String(s) => match from_utf8(s) {
+                Ok(s) => serializer.serialize_str(s),
+                Err(_) => serializer.serialize_bytes(s),
+            },
+            LuaValue::Table(t) if t.iter().all(|e| e.key().is_none()) => {
+                let mut seq = serializer.serialize_seq(Some(t.len()))?;
+                for entry in t {
+                    seq.serialize_element(&EntryValue(entry))?;
+                }
+                seq.end()
+            }
+            LuaValue::Table(t) => {
+                let mut map = serializer.serialize_map(Some(t.len()))?;
+                let mut index = 0;
+                for entry in t {
+                    match entry {
+                        LuaTableEntry::KeyValue(b) => map.serialize_entry(&b.0, &b.1)?,
+                        LuaTableEntry::NameValue(b) => map.serialize_entry(&b.0, &b.1)?,
+                        _ => {
+                            index += 1;
+                            map.serialize_entry(&ImplicitKey(index), &EntryValue(entry))?;
+                        }
+                    }
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl Serialize for LuaNumber {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            LuaNumber::Integer(i) => serializer.serialize_i64(*i),
+            LuaNumber::Float(f) => serializer.serialize_f64(*f),
+        }
+    }
+}
+
+/// The value of a table entry, without its key.
+struct EntryValue<'a, 'b>(&'a LuaTableEntry<'b>);
+
+impl Serialize for EntryValue<'_, '_> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            LuaTableEntry::KeyValue(b) => b.1.serialize(serializer),
+            LuaTableEntry::NameValue(b) => b.1.serialize(serializer),
+            LuaTableEntry::Value(v) => v.serialize(serializer),
+            LuaTableEntry::NumberValue(n) => n.serialize(serializer),
+            LuaTableEntry::BooleanValue(b) => serializer.serialize_bool(*b),
+            LuaTableEntry::NilValue => serializer.serialize_unit(),
+        }
+    }
+}
+
+/// The implicit key of a table entry, in a table which also has explicit keys.
+struct ImplicitKey(i64);
+
+impl Serialize for ImplicitKey {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(IMPLICIT_KEY, &self.0)
+    }
+}
+
+/// Controls how a [`Serializer`] writes Lua source code.
+///
+/// Each method has a default implementation, which writes the same output as
+/// [`DefaultFormatter`]. Implementations can override some of them to change the style of the
+/// output, like to match the output of a particular game engine.
+///
+/// The [`Serializer`] checks that script variable names are valid identifiers, and only calls
+/// [`write_name()`][Self::write_name] for table keys which are valid identifiers. Other keys are
+/// written with [`begin_key()`][Self::begin_key] and [`end_key()`][Self::end_key].
+///
+/// The output must still be valid Lua which reads back as the same value.
+///
+/// ## Example
+///
+/// Write each table entry on its own line, with single-quoted strings:
+///
+/// ```rust
+/// use serde_luaq::{lua_value, Formatter, LuaFormat, QuoteStyle, Serializer};
+/// use serde::Serialize;
+/// use std::io::{self, Write};
+///
+/// #[derive(Default)]
+/// struct Pretty {
+///     depth: usize,
+/// }
+///
+/// impl Formatter for Pretty {
+///     fn write_string<W: ?Sized + Write>(&mut self, w: &mut W, v: &[u8]) -> io::Result<()> {
+///         QuoteStyle::Single.write_string(w, v)
+///     }
+///
+///     fn begin_table<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+///         self.depth += 1;
+///         w.write_all(b"{")
+///     }
+///
+///     fn end_table<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+///         self.depth -= 1;
+///         write!(w, "\n{}}}", "  ".repeat(self.depth))
+///     }
+///
+///     fn begin_entry<W: ?Sized + Write>(&mut self, w: &mut W, _first: bool) -> io::Result<()> {
+///         write!(w, "\n{}", "  ".repeat(self.depth))
+///     }
+///
+///     fn end_entry<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+///         w.write_all(b",")
+///     }
+/// }
+///
+/// let value = lua_value(br#"{a = "b", c = {1, 2}}"#, 16).unwrap();
+/// let mut ser = Serializer::with_formatter(Vec::new(), LuaFormat::Return, Pretty::default());
+/// value.serialize(&mut ser).unwrap();
+/// assert_eq!(
+///     "return {\n  a = 'b',\n  c = {\n    1,\n    2,\n  },\n}",
+///     String::from_utf8(ser.into_inner()).unwrap(),
+/// );
+/// ```
+pub trait Formatter {
+    /// Writes `nil`.
+    fn write_nil<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        w.write_all(b"nil")
+    }
+
+    /// Writes a boolean: `true` or `false`.
+    fn write_bool<W: ?Sized + Write>(&mut self, w: &mut W, v: bool) -> io::Result<()> {
+        w.write_all(if v { b"true" } else { b"false" })
+    }
+
+    /// Writes an integer in decimal, or [`i64::MIN`] as `0x8000000000000000`.
+    fn write_integer<W: ?Sized + Write>(&mut self, w: &mut W, v: i64) -> io::Result<()> {
+        match v {
+            // -9223372036854775808 would be read as a float, because the literal is positive.
+            i64::MIN => w.write_all(b"0x8000000000000000"),
+            v => write!(w, "{v}"),
+        }
+    }
+
+    /// Writes an unsigned integer. Values up to [`i64::MAX`] are written with
+    /// [`write_integer()`][Self::write_integer], and greater values in hexadecimal
+    /// (`0xffffffffffffffff`), which Lua reads as the [`i64`] with the same bits.
+    fn write_unsigned_integer<W: ?Sized + Write>(&mut self, w: &mut W, v: u64) -> io::Result<()> {
+        match i64::try_from(v) {
+            Ok(v) => self.write_integer(w, v),
+            Err(_) => write!(w, "{v:#x}"),
+        }
+    }
+
+    /// Writes a float with [`FloatFormat::Shortest`].
+    fn write_float<W: ?Sized + Write>(&mut self, w: &mut W, v: f64) -> io::Result<()> {
+        FloatFormat::Shortest.write_float(w, v)
+    }
+
+    /// Writes a string, with [`QuoteStyle::Double`].
+    fn write_string<W: ?Sized + Write>(&mut self, w: &mut W, v: &[u8]) -> io::Result<()> {
+        QuoteStyle::Double.write_string(w, v)
+    }
+
+    /// Writes the `return` keyword and a space, before the value in [`LuaFormat::Return`].
+    fn write_return<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        w.write_all(b"return ")
+    }
+
+    /// Writes the start of a table: `{`.
+    fn begin_table<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        w.write_all(b"{")
+    }
+
+    /// Writes the end of a table: `}`.
+    fn end_table<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        w.write_all(b"}")
+    }
+
+    /// Called before each table entry, where `first` is `true` for the first entry in the table.
+    ///
+    /// Writes a `, ` separator before all but the first entry.
+    fn begin_entry<W: ?Sized + Write>(&mut self, w: &mut W, first: bool) -> io::Result<()> {
+        if first {
+            Ok(())
+        } else {
+            w.write_all(b", ")
+        }
+    }
+
+    /// Called after each table entry. Writes nothing.
+    fn end_entry<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        let _ = w;
+        Ok(())
+    }
+
+    /// Writes a string table key which is a valid Lua identifier, like `name`.
+    fn write_name<W: ?Sized + Write>(&mut self, w: &mut W, name: &str) -> io::Result<()> {
+        w.write_all(name.as_bytes())
+    }
+
+    /// Writes the start of a table key which isn't written as a name: `[`.
+    ///
+    /// If keys may be [long bracket strings][QuoteStyle::LongBracket], this needs to be followed
+    /// by a space, because `[[[` starts a long bracket string.
+    fn begin_key<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        w.write_all(b"[")
+    }
+
+    /// Writes the end of a table key which isn't written as a name: `]`.
+    fn end_key<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        w.write_all(b"]")
+    }
+
+    /// Writes the separator between a table key and its value: ` = `.
+    fn begin_value<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        w.write_all(b" = ")
+    }
+
+    /// Writes the start of a script statement which assigns to the variable `name`: `name = `.
+    fn begin_statement<W: ?Sized + Write>(&mut self, w: &mut W, name: &str) -> io::Result<()> {
+        w.write_all(name.as_bytes())?;
+        w.write_all(b" = ")
+    }
+
+    /// Writes the end of a script statement: a line feed ([`LineEnding::Lf`]).
+    fn end_statement<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        w.write_all(b"\n")
+    }
+
+    /// Writes a block of comments before a script statement, for
+    /// [`Serializer::serialize_script()`]: the comments as-is, followed by a line feed
+    /// ([`LineEnding::Lf`]).
+    fn write_comment<W: ?Sized + Write>(&mut self, w: &mut W, comment: &[u8]) -> io::Result<()> {
+        w.write_all(comment)?;
+        w.write_all(b"\n")
+    }
+}
+
+/// [`Formatter`] which writes tables on a single line, like `{1, 2, a = "b"}`, and strings and
+/// numbers like `string.format('%q')`. See [`to_writer()`] for details.
+///
+/// ## Example
+///
+/// Write floats like Lua 5.3's `tostring()`:
+///
+/// ```rust
+/// use serde::Serialize;
+/// use serde_luaq::{DefaultFormatter, FloatFormat, LuaFormat, Serializer};
+///
+/// let f = DefaultFormatter::new().float_format(FloatFormat::Precision(14));
+/// let mut ser = Serializer::with_formatter(Vec::new(), LuaFormat::Value, f);
+/// vec![0.1 + 0.2, 100.0, 1e100].serialize(&mut ser).unwrap();
+/// assert_eq!(b"{0.3, 100.0, 1e+100}", ser.into_inner().as_slice());
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct DefaultFormatter {
+    /// How to write floats. Defaults to [`FloatFormat::Shortest`].
+    pub float_format: FloatFormat,
+
+    /// Line break written after each script statement. Defaults to [`LineEnding::Lf`].
+    ///
+    /// With any other line ending, line feeds in strings are written as decimal escapes (`\10`),
+    /// so that the output has no other line breaks.
+    pub line_ending: LineEnding,
+}
+
+impl DefaultFormatter {
+    /// Creates a formatter with the default style.
+    pub const fn new() -> Self {
+        Self {
+            float_format: FloatFormat::Shortest,
+            line_ending: LineEnding::Lf,
+        }
+    }
+
+    /// Sets [`float_format`][Self::float_format].
+    pub const fn float_format(mut self, float_format: FloatFormat) -> Self {
+        self.float_format = float_format;
+        self
+    }
+
+    /// Sets [`line_ending`][Self::line_ending].
+    pub const fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+}
+
+impl Formatter for DefaultFormatter {
+    fn write_float<W: ?Sized + Write>(&mut self, w: &mut W, v: f64) -> io::Result<()> {
+        self.float_format.write_float(w, v)
+    }
+
+    fn write_string<W: ?Sized + Write>(&mut self, w: &mut W, v: &[u8]) -> io::Result<()> {
+        write_quoted(w, v, b'"', self.line_ending == LineEnding::Lf)
+    }
+
+    fn end_statement<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        w.write_all(self.line_ending.as_bytes())
+    }
+
+    fn write_comment<W: ?Sized + Write>(&mut self, w: &mut W, comment: &[u8]) -> io::Result<()> {
+        w.write_all(comment)?;
+        w.write_all(self.line_ending.as_bytes())
+    }
+}
+
+/// Line break to write between script statements, for [`DefaultFormatter`].
+///
+/// The parser accepts all of these, and Lua reads them all as a single line break.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde::Serialize;
+/// use serde_luaq::{DefaultFormatter, LineEnding, LuaFormat, Serializer};
+/// use std::collections::BTreeMap;
+///
+/// let f = DefaultFormatter::new().line_ending(LineEnding::CrLf);
+/// let mut ser = Serializer::with_formatter(Vec::new(), LuaFormat::Script, f);
+/// BTreeMap::from([("a", "b\nc"), ("d", "e")]).serialize(&mut ser).unwrap();
+/// assert_eq!(b"a = \"b\\10c\"\r\nd = \"e\"\r\n", ser.into_inner().as_slice());
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LineEnding {
+    /// A line feed (`\n`), as used on Unix-like systems.
+    #[default]
+    Lf,
+
+    /// A carriage return followed by a line feed (`\r\n`), as used on Windows.
+    CrLf,
+
+    /// A carriage return (`\r`), as used on classic Mac OS.
+    Cr,
+}
+
+impl LineEnding {
+    /// Returns the line break as bytes.
+    pub const fn as_bytes(self) -> &'static [u8] {
+        match self {
+            LineEnding::Lf => b"\n",
+            LineEnding::CrLf => b"\r\n",
+            LineEnding::Cr => b"\r",
+        }
+    }
+}
+
+/// How to write floats, for [`Formatter::write_float()`].
+///
+/// Infinities and NaN are always written as `1e9999`, `-1e9999` and `(0/0)`, and finite floats
+/// always contain a `.` or an exponent, so they are read back as floats rather than integers.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::FloatFormat;
+///
+/// let mut out = Vec::new();
+/// for format in [
+///     FloatFormat::Shortest,
+///     FloatFormat::Precision(14),
+///     FloatFormat::Precision(17),
+/// ] {
+///     for v in [0.1, 2.0, 1e21, 1.5e-7] {
+///         format.write_float(&mut out, v).unwrap();
+///         out.push(b' ');
+///     }
+///     out.push(b'\n');
+/// }
+///
+/// assert_eq!(
+///     concat!(
+///         "0.1 2.0 1e+21 1.5e-7 \n",
+///         "0.1 2.0 1e+21 1.5e-07 \n",
+///         "0.10000000000000001 2.0 1e+21 1.4999999999999999e-07 \n",
+///     ),
+///     String::from_utf8(out).unwrap(),
+/// );
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FloatFormat {
+    /// The shortest decimal representation which reads back as the same value: `0.1`
+    #[default]
+    Shortest,
+
+    /// Rounded to this many significant digits, like C's `printf("%.14g")`: `0.1`
+    ///
+    /// Lua 5.3 and earlier write floats with `%.14g`, which doesn't always read back as the same
+    /// value. `%.17g` always does, but often has more digits than needed
+    /// (`0.10000000000000001`).
+    ///
+    /// Like Lua 5.3's `tostring()`, `.0` is added to floats which would otherwise look like
+    /// integers (`2.0`). Precisions less than `1` or more than `17` are treated as `1` or `17`.
+    Precision(u8),
+}
+
+impl FloatFormat {
+    /// Writes `v` as a Lua number, in this format.
+    pub fn write_float<W: ?Sized + Write>(self, w: &mut W, v: f64) -> io::Result<()> {
+        match v {
+            v if v.is_nan() => return w.write_all(b"(0/0)"),
+            f64::INFINITY => return w.write_all(b"1e9999"),
+            f64::NEG_INFINITY => return w.write_all(b"-1e9999"),
+            _ => (),
+        }
+
+        // The longest is 24 bytes, like `-2.2250738585072014e-308` or
+        // `-0.00012345678901234567`.
+        let mut buf = [0; 32];
+        let mut cursor = io::Cursor::new(&mut buf[..]);
+        let precision = match self {
+            FloatFormat::Shortest => {
+                // Debug always includes a `.` or exponent, but `%q` syntax needs a sign on the
+                // exponent.
+                write!(cursor, "{v:?}")?;
+                let len = cursor.position() as usize;
+                let s = &buf[..len];
+                return match s.iter().position(|&c| c == b'e') {
+                    Some(e) if s[e + 1] != b'-' => {
+                        w.write_all(&s[..=e])?;
+                        w.write_all(b"+")?;
+                        w.write_all(&s[e + 1..])
+                    }
+                    _ => w.write_all(s),
+                };
+            }
+            FloatFormat::Precision(p) => usize::from(p.clamp(1, 17)),
+        };
+
+        // Round to the precision first, because that can change the exponent (`9.99` -> `1e1`).
+        write!(cursor, "{v:.*e}", precision - 1)?;
+        let len = cursor.position() as usize;
+        let e = buf[..len]
+            .iter()
+            .position(|&c| c == b'e')
+            .expect("float has an exponent");
+        let exp: i32 = from_utf8(&buf[e + 1..len])
+            .ok()
+            .and_then(|exp| exp.parse().ok())
+            .expect("float exponent is an integer");
+
+        if exp < -4 || exp >= precision as i32 {
+            // Like C, the exponent has a sign and at least 2 digits.
+            w.write_all(trim_fraction(&buf[..e]))?;
+            let sign = if exp < 0 { '-' } else { '+' };
+            return write!(w, "e{sign}{:02}", exp.unsigned_abs());
+        }
+
+        let mut cursor = io::Cursor::new(&mut buf[..]);
+        write!(cursor, "{v:.*}", (precision as i32 - 1 - exp) as usize)?;
+        let len = cursor.position() as usize;
+        let s = trim_fraction(&buf[..len]);
+        w.write_all(s)?;
+        if !s.contains(&b'.') {
+            w.write_all(b".0")?;
+        }
+        Ok(())
+    }
+}
+
+/// Removes trailing zeros after the decimal point of a number, and the point itself if there's
+/// nothing after it, like `%g`.
+fn trim_fraction(s: &[u8]) -> &[u8] {
+    if !s.contains(&b'.') {
+        return s;
+    }
+    let end = s.iter().rposition(|&c| c != b'0').map_or(0, |i| i + 1);
+    let s = &s[..end];
+    s.strip_suffix(b".").unwrap_or(s)
+}
+
+/// How to quote strings, for [`Formatter::write_string()`].
+///
+/// Only [`Double`][Self::Double] can be read in [strict `%q` mode][crate::ParseOptions::strict_q].
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::QuoteStyle;
+///
+/// let mut out = Vec::new();
+/// for style in [QuoteStyle::Double, QuoteStyle::Single, QuoteStyle::LongBracket] {
+///     style.write_string(&mut out, b"it's \"here\"]]\n").unwrap();
+///     out.push(b'\n');
+/// }
+///
+/// assert_eq!(
+///     concat!(
+///         "\"it's \\\"here\\\"]]\\\n\"\n",
+///         "'it\\'s \"here\"]]\\\n'\n",
+///         "[=[it's \"here\"]]\n]=]\n",
+///     ),
+///     String::from_utf8(out).unwrap(),
+/// );
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum QuoteStyle {
+    /// A double-quoted string, like `string.format('%q')`: `"it's"`
+    ///
+    /// `"`, `\` and line feeds are escaped with a backslash, carriage returns are written as
+    /// `\r`, and other control characters and bytes which aren't valid UTF-8 are written as
+    /// decimal escapes (`\27`).
+    #[default]
+    Double,
+
+    /// A single-quoted string: `'it\'s'`
+    ///
+    /// This is escaped like [`Double`][Self::Double], but escapes `'` rather than `"`.
+    Single,
+
+    /// A long bracket string, at the lowest level which doesn't appear in the string:
+    /// `[[it's]]` or `[=[a]]b]=]`
+    ///
+    /// Long bracket strings can't contain escape sequences, and Lua converts any line break in
+    /// them to a line feed. Strings which contain carriage returns, other control characters
+    /// (except tabs and line feeds), bytes which aren't valid UTF-8, or the closing brackets of
+    /// all levels up to 5 are written as [`Double`][Self::Double] instead.
+    ///
+    /// Table keys written in this style need spaces inside of their brackets (`[ [[key]] ]`); see
+    /// [`Formatter::begin_key()`].
+    LongBracket,
+}
+
+impl QuoteStyle {
+    /// Writes `s` as a quoted Lua string, in this style.
+    pub fn write_string<W: ?Sized + Write>(self, w: &mut W, s: &[u8]) -> io::Result<()> {
+        match self {
+            QuoteStyle::Double => write_quoted(w, s, b'"', true),
+            QuoteStyle::Single => write_quoted(w, s, b'\'', true),
+            QuoteStyle::LongBracket => match long_bracket_level(s) {
+                Some(level) => {
+                    let eq = &"====="[..level];
+                    // A line feed straight after the opening bracket is skipped.
+                    let nl = if s.first() == Some(&b'\n') { "\n" } else { "" };
+                    write!(w, "[{eq}[{nl}")?;
+                    w.write_all(s)?;
+                    write!(w, "]{eq}]")
+                }
+                None => write_quoted(w, s, b'"', true),
+            },
+        }
+    }
+}
+
+/// Returns the lowest level of long bracket which can contain `s`, or [`None`] if `s` can't be
+/// written as a long bracket string.
+fn long_bracket_level(s: &[u8]) -> Option<usize> {
+    let valid = from_utf8(s).is_ok_and(|s| {
+        !s.bytes()
+            .any(|c| (c.is_ascii_control() && c != b'\t' && c != b'\n') || c == 0x7f)
+    });
+    if !valid {
+        return None;
+    }
+
+    // `parser` supports levels up to 5.
+    (0..=5).find(|&level| {
+        let mut close = [b'='; 7];
+        (close[0], close[level + 1]) = (b']', b']');
+        let close = &close[..level + 2];
+        !s.windows(close.len()).any(|w| w == close) && !s.ends_with(&close[..=level])
+    })
+}
+
+/// Writes a short string delimited by `quote`, escaped like `string.format('%q')`.
+///
+/// If `escaped_line_feeds` is `false`, line feeds are written as decimal escapes (`\10`) rather
+/// than a backslash followed by a line feed.
+fn write_quoted<W: ?Sized + Write>(
+    w: &mut W,
+    s: &[u8],
+    quote: u8,
+    escaped_line_feeds: bool,
+) -> io::Result<()> {
+    w.write_all(&[quote])?;
+
+    // Writes a decimal escape for byte `c` at position `i`, padded if it's followed by a digit.
+    let escape = |w: &mut W, i: usize, c: u8| {
+        if s.get(i + 1).is_some_and(u8::is_ascii_digit) {
+            write!(w, "\\{c:03}")
+        } else {
+            write!(w, "\\{c}")
+        }
+    };
+
+    let mut pos = 0;
+    for chunk in s.utf8_chunks() {
+        let valid = chunk.valid().as_bytes();
+
+        // Write runs of characters which don't need escaping directly from the input.
+        let mut start = 0;
+        for (i, &c) in valid.iter().enumerate() {
+            let escaped: &[u8] = match c {
+                c if c == quote => &[b'\\', quote],
+                b'\\' => b"\\\\",
+                b'\n' if escaped_line_feeds => b"\\\n",
+                b'\r' => b"\\r",
+                0..=0x1f | 0x7f => b"",
+                _ => continue,
+            };
+
+            w.write_all(&valid[start..i])?;
+            if escaped.is_empty() {
+                escape(w, pos + i, c)?;
+            } else {
+                w.write_all(escaped)?;
+            }
+            start = i + 1;
+        }
+        w.write_all(&valid[start..])?;
+        pos += valid.len();
+
+        for &c in chunk.invalid() {
+            escape(w, pos, c)?;
+            pos += 1;
+        }
+    }
+
+    w.write_all(&[quote])
+}
@@ -2,9 +2,9 @@
 use crate::{
     error::LuaConversionError,
     value::{from_utf8_cow, from_utf8_cow_lossy},
-    JsonConversionError, LuaNumber, LuaTableEntry, LuaValue,
+    Diagnostic, DiagnosticKind, JsonConversionError, LuaNumber, LuaTableEntry, LuaValue,
 };
-use serde_json::{Map as JsonMap, Number as JsonNumber, Value as JsonValue};
+use serde_json::{map::Entry, Map as JsonMap, Number as JsonNumber, Value as JsonValue};
 use std::borrow::Borrow;
 
 impl<'a> TryFrom<LuaValue<'a>> for JsonValue {
@@ -15,8 +15,28 @@ impl<'a> TryFrom<LuaValue<'a>> for JsonValue {
     }
 }
 
+impl TryFrom<JsonValue> for LuaValue<'static> {
+    type Error = LuaConversionError;
+
+    /// Converts a JSON value to a Lua value, like [`from_json_value()`].
+    ///
+    /// This is handy for building test fixtures with [`serde_json::json!`]:
+    ///
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_luaq::{lua_value, LuaValue};
+    ///
+    /// let v = LuaValue::try_from(json!({"name": "Alice", "scores": [1, 2.5]})).unwrap();
+    /// assert_eq!(lua_value(b"{name = 'Alice', scores = {1, 2.5}}", 16).unwrap(), v);
+    /// ```
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        from_json_value(value)
+    }
+}
+
 /// [Lua to JSON][to_json_value] conversion options.
 #[derive(Default, Debug, PartialEq)]
+#[non_exhaustive]
 pub struct JsonConversionOptions {
     /// By default, [`to_json_value()`] returns [`JsonConversionError::Utf8Error`] on invalid UTF-8
     /// sequences.
@@ -24,6 +44,68 @@ pub struct JsonConversionOptions {
     /// When this option is set to `true`, it uses
     /// [lossy string conversion][String::from_utf8_lossy] instead. This can result in data loss.
     pub lossy_string: bool,
+
+    /// What to do when a table has more than one entry which converts to the same JSON object
+    /// key, like `{a = 1, a = 2}` or `{[1] = 1, ["1"] = 2}`.
+    ///
+    /// By default, [later entries take precedence][DuplicateKeys::LastWins].
+    pub duplicate_keys: DuplicateKeys,
+}
+
+impl JsonConversionOptions {
+    /// Creates options with the default behaviour.
+    pub const fn new() -> Self {
+        Self {
+            lossy_string: false,
+            duplicate_keys: DuplicateKeys::LastWins,
+        }
+    }
+
+    /// Sets [`lossy_string`][Self::lossy_string].
+    pub const fn lossy_string(mut self, lossy_string: bool) -> Self {
+        self.lossy_string = lossy_string;
+        self
+    }
+
+    /// Sets [`duplicate_keys`][Self::duplicate_keys].
+    pub const fn duplicate_keys(mut self, duplicate_keys: DuplicateKeys) -> Self {
+        self.duplicate_keys = duplicate_keys;
+        self
+    }
+}
+
+/// Policy for table entries which convert to the same JSON object key, for
+/// [`JsonConversionOptions::duplicate_keys`].
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_json::json;
+/// use serde_luaq::{lua_value, to_json_value, DuplicateKeys, JsonConversionOptions};
+///
+/// let v = lua_value(b"{a = 1, b = 2, a = 3}", 16).unwrap();
+///
+/// let opts = JsonConversionOptions::new().duplicate_keys(DuplicateKeys::LastWins);
+/// assert_eq!(json!({"a": 3, "b": 2}), to_json_value(v.clone(), opts).unwrap());
+///
+/// let opts = JsonConversionOptions::new().duplicate_keys(DuplicateKeys::FirstWins);
+/// assert_eq!(json!({"a": 1, "b": 2}), to_json_value(v.clone(), opts).unwrap());
+///
+/// let opts = JsonConversionOptions::new().duplicate_keys(DuplicateKeys::Error);
+/// assert!(to_json_value(v, opts).is_err());
+/// ```
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DuplicateKeys {
+    /// Later entries overwrite earlier entries with the same key.
+    #[default]
+    LastWins,
+
+    /// Later entries with the same key as an earlier entry are dropped.
+    FirstWins,
+
+    /// Return [`JsonConversionError::DuplicateKey`].
+    Error,
 }
 
 /// Converts a [`LuaValue`] into a [`serde_json::Value`].
@@ -87,8 +169,9 @@ pub struct JsonConversionOptions {
 ///     [converted to strings with _Rust_ formatting conventions][ToString::to_string]
 ///   * Tables keyed with a table will return [`JsonConversionError::TableKeyedWithTable`]
 ///
-/// * Entries of tables with the same key defined multiple times will be
-///   silently overwritten (later entries take precedence).
+/// * Entries of tables with the same key defined multiple times are handled according to
+///   [`JsonConversionOptions::duplicate_keys`]. By default, later entries take precedence.
+///   [`to_json_value_with_diagnostics()`] also reports each entry which was dropped.
 ///
 /// **Note:** `serde_json` may not preserve the order of keys in [an object][JsonValue::Object].
 ///
@@ -98,8 +181,54 @@ pub fn to_json_value(
     value: LuaValue<'_>,
     opts: impl Borrow<JsonConversionOptions>,
 ) -> Result<JsonValue, JsonConversionError> {
-    let opts = opts.borrow();
+    convert(value, opts.borrow(), &mut Vec::new())
+}
 
+/// Converts a [`LuaValue`] into a [`serde_json::Value`] like [`to_json_value()`], and returns a
+/// [`DiagnosticKind::DuplicateJsonKey`] warning for each table entry which was dropped because it
+/// had the same JSON object key as another entry.
+///
+/// This is useful for monitoring the quality of converted data.
+///
+/// Values don't record where they were in the input, so these diagnostics have an empty span.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_json::json;
+/// use serde_luaq::{
+///     lua_value, to_json_value_with_diagnostics, DiagnosticKind, JsonConversionOptions,
+/// };
+///
+/// let v = lua_value(b"{a = 1, b = {[1] = 2, ['1'] = 3}, a = 4}", 16).unwrap();
+/// let (json, diagnostics) =
+///     to_json_value_with_diagnostics(v, JsonConversionOptions::new()).unwrap();
+///
+/// assert_eq!(json!({"a": 4, "b": {"1": 3}}), json);
+/// assert_eq!(
+///     vec![
+///         DiagnosticKind::DuplicateJsonKey { key: "1".to_string() },
+///         DiagnosticKind::DuplicateJsonKey { key: "a".to_string() },
+///     ],
+///     diagnostics.into_iter().map(|d| d.kind).collect::<Vec<_>>(),
+/// );
+/// ```
+pub fn to_json_value_with_diagnostics(
+    value: LuaValue<'_>,
+    opts: impl Borrow<JsonConversionOptions>,
+) -> Result<(JsonValue, Vec<Diagnostic>), JsonConversionError> {
+    let mut diagnostics = Vec::new();
+    let value = convert(value, opts.borrow(), &mut diagnostics)?;
+    Ok((value, diagnostics))
+}
+
+/// Converts `value` like [`to_json_value()`], adding a warning to `diagnostics` for each dropped
+/// table entry.
+fn convert(
+    value: LuaValue<'_>,
+    opts: &JsonConversionOptions,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<JsonValue, JsonConversionError> {
     match value {
         LuaValue::Nil => Ok(JsonValue::Null),
 
@@ -153,24 +282,32 @@ pub fn to_json_value(
                             }
                         };
 
-                        object.insert(k, to_json_value(b.1, opts)?);
+                        let v = convert(b.1, opts, diagnostics)?;
+                        insert(&mut object, k, v, opts, diagnostics)?;
                     }
 
                     LuaTableEntry::NameValue(b) => {
                         // Switched to an object, move any existing entries from the array.
                         move_array_to_object(&mut array, &mut array_next_idx, &mut object);
 
-                        object.insert(b.0.to_string(), to_json_value(b.1, opts)?);
+                        let v = convert(b.1, opts, diagnostics)?;
+                        insert(&mut object, b.0.to_string(), v, opts, diagnostics)?;
                     }
 
                     LuaTableEntry::Value(v) => {
-                        let v = to_json_value(*v, opts)?;
+                        let v = convert(*v, opts, diagnostics)?;
                         if object.is_empty() {
                             // We have no object yet, push into array
                             array.push(v);
                         } else {
                             // We have an object, use the next key
-                            object.insert(array_next_idx.to_string(), v);
+                            insert(
+                                &mut object,
+                                array_next_idx.to_string(),
+                                v,
+                                opts,
+                                diagnostics,
+                            )?;
                             array_next_idx += 1;
                         }
                     }
@@ -182,7 +319,13 @@ pub fn to_json_value(
                             array.push(v);
                         } else {
                             // We have an object, use the next key
-                            object.insert(array_next_idx.to_string(), v);
+                            insert(
+                                &mut object,
+                                array_next_idx.to_string(),
+                                v,
+                                opts,
+                                diagnostics,
+                            )?;
                             array_next_idx += 1;
                         }
                     }
@@ -194,7 +337,13 @@ pub fn to_json_value(
                             array.push(v);
                         } else {
                             // We have an object, use the next key
-                            object.insert(array_next_idx.to_string(), v);
+                            insert(
+                                &mut object,
+                                array_next_idx.to_string(),
+                                v,
+                                opts,
+                                diagnostics,
+                            )?;
                             array_next_idx += 1;
                         }
                     }
@@ -206,7 +355,13 @@ pub fn to_json_value(
                             array.push(v);
                         } else {
                             // We have an object, use the next key
-                            object.insert(array_next_idx.to_string(), v);
+                            insert(
+                                &mut object,
+                                array_next_idx.to_string(),
+                                v,
+                                opts,
+                                diagnostics,
+                            )?;
                             array_next_idx += 1;
                         }
                     }
@@ -263,15 +418,7 @@ pub fn from_json_value(value: JsonValue) -> Result<LuaValue<'static>, LuaConvers
     match value {
         JsonValue::Null => Ok(LuaValue::Nil),
         JsonValue::Bool(b) => Ok(LuaValue::Boolean(b)),
-        JsonValue::Number(n) => {
-            if let Some(v) = n.as_i64() {
-                Ok(LuaValue::integer(v))
-            } else if let Some(v) = n.as_f64() {
-                Ok(LuaValue::float(v))
-            } else {
-                Err(LuaConversionError::Number)
-            }
-        }
+        JsonValue::Number(n) => Ok(LuaValue::Number(n.try_into()?)),
         JsonValue::String(s) => Ok(LuaValue::String(s.into_bytes().into())),
         JsonValue::Array(a) => {
             let r: Result<Vec<LuaTableEntry<'static>>, LuaConversionError> = a
@@ -292,6 +439,44 @@ pub fn from_json_value(value: JsonValue) -> Result<LuaValue<'static>, LuaConvers
     }
 }
 
+/// Inserts `k` into `object`, following [`JsonConversionOptions::duplicate_keys`] if it is
+/// already there.
+fn insert(
+    object: &mut JsonMap<String, JsonValue>,
+    k: String,
+    v: JsonValue,
+    opts: &JsonConversionOptions,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<(), JsonConversionError> {
+    let mut e = match object.entry(k) {
+        Entry::Vacant(e) => {
+            e.insert(v);
+            return Ok(());
+        }
+        Entry::Occupied(e) => e,
+    };
+
+    match opts.duplicate_keys {
+        DuplicateKeys::LastWins => {
+            e.insert(v);
+        }
+        DuplicateKeys::FirstWins => (),
+        DuplicateKeys::Error => {
+            return Err(JsonConversionError::DuplicateKey {
+                key: e.key().clone(),
+            })
+        }
+    }
+
+    diagnostics.push(Diagnostic::new(
+        DiagnosticKind::DuplicateJsonKey {
+            key: e.key().clone(),
+        },
+        0..0,
+    ));
+    Ok(())
+}
+
 #[inline]
 fn move_array_to_object(
     array: &mut Vec<JsonValue>,
@@ -307,6 +492,28 @@ fn move_array_to_object(
     }
 }
 
+impl TryFrom<JsonNumber> for LuaNumber {
+    type Error = LuaConversionError;
+
+    /// Converts a JSON number to a [`LuaNumber`].
+    ///
+    /// Integers which fit in an [`i64`] are always converted to [`LuaNumber::Integer`] without
+    /// any loss of precision, and numbers written with a fraction or exponent (like `1.0`) are
+    /// always converted to [`LuaNumber::Float`].
+    ///
+    /// Integers greater than [`i64::MAX`] are converted to [`LuaNumber::Float`], which loses
+    /// precision.
+    fn try_from(value: JsonNumber) -> Result<Self, Self::Error> {
+        if let Some(v) = value.as_i64() {
+            Ok(LuaNumber::Integer(v))
+        } else if let Some(v) = value.as_f64() {
+            Ok(LuaNumber::Float(v))
+        } else {
+            Err(LuaConversionError::Number)
+        }
+    }
+}
+
 impl TryFrom<LuaNumber> for JsonNumber {
     type Error = JsonConversionError;
 
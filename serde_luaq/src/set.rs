@@ -0,0 +1,109 @@
+//! Serde adapter for Lua's set idiom.
+
+/// Serialises and deserialises sets (like [`HashSet`][std::collections::HashSet] and
+/// [`BTreeSet`][std::collections::BTreeSet]) as tables whose values are `true`, with
+/// `#[serde(with = "serde_luaq::set_as_table")]`.
+///
+/// Lua doesn't have a set type, so sets are commonly written as a table whose keys are the set's
+/// members:
+///
+/// ```lua
+/// fruit = {apple = true, banana = true}
+/// ```
+///
+/// When deserialising, entries whose value is `false` or `nil` are skipped, because Lua treats
+/// them as not being in the set. Other values are an error, unless they are accepted as a
+/// [`bool`] by [`DeserializeOptions::lenient_bool`][crate::DeserializeOptions::lenient_bool].
+///
+/// When serialising, every member has the value `true`, in the set's iteration order.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use serde_luaq::{from_slice, to_string, LuaFormat};
+/// use std::collections::BTreeSet;
+///
+/// #[derive(Deserialize, Serialize, Debug, PartialEq)]
+/// struct Basket {
+///     #[serde(with = "serde_luaq::set_as_table")]
+///     fruit: BTreeSet<String>,
+/// }
+///
+/// let basket: Basket = from_slice(
+///     b"{fruit = {apple = true, ['dragon fruit'] = true, banana = false}}",
+///     LuaFormat::Value,
+///     16,
+/// ).unwrap();
+/// assert_eq!(BTreeSet::from(["apple".to_string(), "dragon fruit".to_string()]), basket.fruit);
+///
+/// assert_eq!(
+///     r#"{fruit = {apple = true, ["dragon fruit"] = true}}"#,
+///     to_string(&basket, LuaFormat::Value).unwrap(),
+/// );
+/// ```
+pub mod set_as_table {
+    use serde::{
+        de::{MapAccess, Visitor},
+        Deserialize, Deserializer, Serialize, Serializer,
+    };
+    use std::{fmt::Formatter, marker::PhantomData};
+
+    /// Serialises a set as a table whose values are `true`.
+    pub fn serialize<S, C, T>(set: &C, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        for<'a> &'a C: IntoIterator<Item = &'a T>,
+        T: Serialize,
+    {
+        serializer.collect_map(set.into_iter().map(|k| (k, true)))
+    }
+
+    /// Deserialises a table whose values are `true` as a set.
+    pub fn deserialize<'de, D, C, T>(deserializer: D) -> Result<C, D::Error>
+    where
+        D: Deserializer<'de>,
+        C: FromIterator<T>,
+        T: Deserialize<'de>,
+    {
+        deserializer.deserialize_map(SetVisitor(PhantomData))
+    }
+
+    struct SetVisitor<C, T>(PhantomData<(C, T)>);
+
+    impl<'de, C, T> Visitor<'de> for SetVisitor<C, T>
+    where
+        C: FromIterator<T>,
+        T: Deserialize<'de>,
+    {
+        type Value = C;
+
+        fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+            formatter.write_str("a table whose values are true")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let mut err = None;
+            let set = std::iter::from_fn(|| loop {
+                match map.next_entry::<T, Option<bool>>() {
+                    Ok(Some((k, Some(true)))) => return Some(k),
+                    Ok(Some(_)) => continue,
+                    Ok(None) => return None,
+                    Err(e) => {
+                        err = Some(e);
+                        return None;
+                    }
+                }
+            })
+            .collect();
+
+            match err {
+                Some(e) => Err(e),
+                None => Ok(set),
+            }
+        }
+    }
+}
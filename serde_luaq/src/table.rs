@@ -0,0 +1,140 @@
+//! Helpers for editing [tables][LuaValue::Table] by key.
+use crate::{LuaNumber, LuaTableEntry, LuaValue};
+use std::borrow::Cow;
+
+/// Extension methods for finding and removing [table][LuaValue::Table] entries by key, using
+/// Lua's rules for key equality.
+///
+/// These follow the same rules as a Lua table lookup:
+///
+/// * [`NameValue`][LuaTableEntry::NameValue] and [`KeyValue`][LuaTableEntry::KeyValue] entries
+///   with the same string key are the same key (`{a = 1}` and `{["a"] = 1}`).
+/// * Floats with an exact integer value are the same key as that integer (`[1.0]` and `[1]`).
+/// * Implicitly-keyed entries (like `{"a", "b"}`) have consecutive integer keys starting at `1`.
+/// * `nil`, NaN and table keys never match anything.
+///
+/// Tables parsed from Lua may contain more than one entry with the same key, like
+/// `{a = 1, a = 2}`.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{lua_value, LuaTableExt, LuaValue};
+///
+/// let mut t = lua_value(br#"{"a", "b", name = "x", [3.0] = "c"}"#, 16)
+///     .unwrap()
+///     .try_into_table()
+///     .unwrap();
+///
+/// assert_eq!(Some(1), t.position_by_key(&LuaValue::integer(2)));
+/// assert_eq!(Some(3), t.position_by_key(&LuaValue::integer(3)));
+///
+/// let removed = t.remove_by_key(&LuaValue::String(b"name".into())).unwrap();
+/// assert_eq!(Some(&LuaValue::String(b"x".into())), removed.value());
+/// assert_eq!(3, t.len());
+/// ```
+pub trait LuaTableExt<'a> {
+    /// Returns the index of the first entry with the key `key`.
+    fn position_by_key(&self, key: &LuaValue<'_>) -> Option<usize>;
+
+    /// Retains only the entries whose key matches the predicate `f`.
+    ///
+    /// Implicitly-keyed entries are passed their key _before_ any entries are removed.
+    ///
+    /// **Note:** removing an implicitly-keyed entry changes the keys of any implicitly-keyed
+    /// entries after it, in the same way as [`Vec::remove()`].
+    fn retain_by_key<F>(&mut self, f: F)
+    where
+        F: FnMut(&LuaValue<'_>) -> bool;
+
+    /// Removes and returns the first entry with the key `key`.
+    ///
+    /// **Note:** removing an implicitly-keyed entry changes the keys of any implicitly-keyed
+    /// entries after it, in the same way as [`Vec::remove()`].
+    fn remove_by_key(&mut self, key: &LuaValue<'_>) -> Option<LuaTableEntry<'a>>;
+}
+
+impl<'a> LuaTableExt<'a> for Vec<LuaTableEntry<'a>> {
+    fn position_by_key(&self, key: &LuaValue<'_>) -> Option<usize> {
+        let mut implicit = 0;
+        self.iter()
+            .position(|entry| key_eq(&entry_key(entry, &mut implicit), key))
+    }
+
+    fn retain_by_key<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&LuaValue<'_>) -> bool,
+    {
+        let mut implicit = 0;
+        self.retain(|entry| f(&entry_key(entry, &mut implicit)));
+    }
+
+    fn remove_by_key(&mut self, key: &LuaValue<'_>) -> Option<LuaTableEntry<'a>> {
+        self.position_by_key(key).map(|i| self.remove(i))
+    }
+}
+
+/// Gets the key of a table entry, without copying any strings.
+///
+/// `implicit` is the number of implicitly-keyed entries seen so far, and is incremented if `entry`
+/// is implicitly-keyed.
+pub(crate) fn entry_key<'k>(
+    entry: &'k LuaTableEntry<'_>,
+    implicit: &mut i64,
+) -> Cow<'k, LuaValue<'k>> {
+    match entry {
+        LuaTableEntry::KeyValue(b) => Cow::Borrowed(&b.0),
+        LuaTableEntry::NameValue(b) => Cow::Owned(LuaValue::String(Cow::Borrowed(b.0.as_bytes()))),
+        LuaTableEntry::Value(_)
+        | LuaTableEntry::NumberValue(_)
+        | LuaTableEntry::BooleanValue(_)
+        | LuaTableEntry::NilValue => {
+            *implicit += 1;
+            Cow::Owned(LuaValue::integer(*implicit))
+        }
+    }
+}
+
+/// Compares two table keys, using Lua's rules for key equality.
+pub(crate) fn key_eq(a: &LuaValue<'_>, b: &LuaValue<'_>) -> bool {
+    match (a, b) {
+        (LuaValue::Boolean(a), LuaValue::Boolean(b)) => a == b,
+        (LuaValue::String(a), LuaValue::String(b)) => a == b,
+        (LuaValue::Number(a), LuaValue::Number(b)) => {
+            match (a.normalise_key(), b.normalise_key()) {
+                (LuaNumber::Integer(a), LuaNumber::Integer(b)) => a == b,
+                // NaN is never equal to itself.
+                (LuaNumber::Float(a), LuaNumber::Float(b)) => a == b,
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// A table key which can be hashed, normalised so that keys which Lua considers equal are equal.
+#[derive(PartialEq, Eq, Hash)]
+pub(crate) enum HashKey<'k> {
+    String(&'k [u8]),
+    Integer(i64),
+    Float(u64),
+    Boolean(bool),
+}
+
+impl<'k> HashKey<'k> {
+    /// Normalises `key`, returning [`None`] for keys which never match anything (`nil`, NaN and
+    /// tables).
+    pub(crate) fn new(key: &'k LuaValue<'_>) -> Option<Self> {
+        Some(match key {
+            LuaValue::String(s) => Self::String(s),
+            LuaValue::Number(n) => match n.normalise_key() {
+                LuaNumber::Integer(i) => Self::Integer(i),
+                LuaNumber::Float(f) if f.is_nan() => return None,
+                // -0.0 is normalised to an integer, so every other float has one bit pattern.
+                LuaNumber::Float(f) => Self::Float(f.to_bits()),
+            },
+            LuaValue::Boolean(b) => Self::Boolean(*b),
+            LuaValue::Nil | LuaValue::Table(_) => return None,
+        })
+    }
+}
@@ -256,6 +256,69 @@ impl<'a> LuaTableEntry<'a> {
         }
     }
 
+    /// Get the key of the table entry as a byte slice, if it is a string.
+    ///
+    /// Unlike [`key()`][Self::key], this never allocates or clones.
+    ///
+    /// Returns [`None`] for non-string keys, and entries with implicit keys.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use serde_luaq::{LuaValue, LuaTableEntry};
+    /// assert_eq!(
+    ///     Some(&b"foo"[..]),
+    ///     LuaTableEntry::NameValue(Box::new(("foo".into(), LuaValue::Boolean(true)))).key_bytes()
+    /// );
+    /// assert_eq!(
+    ///     Some(&b"\xff"[..]),
+    ///     LuaTableEntry::from((&b"\xff"[..], LuaValue::Boolean(true))).key_bytes()
+    /// );
+    /// assert_eq!(None, LuaTableEntry::from((1, LuaValue::Boolean(true))).key_bytes());
+    /// assert_eq!(None, LuaTableEntry::from(true).key_bytes());
+    /// ```
+    pub fn key_bytes(&self) -> Option<&[u8]> {
+        match self {
+            LuaTableEntry::NameValue(b) => Some(b.0.as_bytes()),
+            LuaTableEntry::KeyValue(b) => match &b.0 {
+                LuaValue::String(k) => Some(k),
+                _ => None,
+            },
+            LuaTableEntry::Value(_)
+            | LuaTableEntry::NumberValue(_)
+            | LuaTableEntry::NilValue
+            | LuaTableEntry::BooleanValue(_) => None,
+        }
+    }
+
+    /// Get the key of the table entry, if it is an explicit
+    /// [integer][crate::LuaNumber::Integer] (like `[1] = true`).
+    ///
+    /// Returns [`None`] for other keys (including floats), and entries with implicit keys.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use serde_luaq::{LuaValue, LuaTableEntry};
+    /// assert_eq!(Some(1), LuaTableEntry::from((1, LuaValue::Boolean(true))).key_integer());
+    /// assert_eq!(
+    ///     None,
+    ///     LuaTableEntry::KeyValue(Box::new((LuaValue::float(1.), LuaValue::Boolean(true))))
+    ///         .key_integer()
+    /// );
+    /// assert_eq!(None, LuaTableEntry::from(("1", LuaValue::Boolean(true))).key_integer());
+    /// assert_eq!(None, LuaTableEntry::from(true).key_integer());
+    /// ```
+    pub fn key_integer(&self) -> Option<i64> {
+        match self {
+            LuaTableEntry::KeyValue(b) => match b.0 {
+                LuaValue::Number(LuaNumber::Integer(k)) => Some(k),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     /// Get a reference to the value of the table entry, as a [`LuaValue`][].
     ///
     /// Returns [`None`][] for [`BooleanValue`][LuaTableEntry::BooleanValue],
@@ -313,6 +376,99 @@ impl<'a> LuaTableEntry<'a> {
         }
     }
 
+    /// Gets the value of the table entry, borrowing it if possible.
+    ///
+    /// Like [`move_value()`][Self::move_value], compact entries are wrapped in a [`LuaValue`].
+    pub(crate) fn value_cow(&self) -> Cow<'_, LuaValue<'a>> {
+        match self {
+            LuaTableEntry::KeyValue(b) => Cow::Borrowed(&b.1),
+            LuaTableEntry::NameValue(b) => Cow::Borrowed(&b.1),
+            LuaTableEntry::Value(v) => Cow::Borrowed(v.as_ref()),
+            LuaTableEntry::NumberValue(n) => Cow::Owned(LuaValue::Number(*n)),
+            LuaTableEntry::BooleanValue(b) => Cow::Owned(LuaValue::Boolean(*b)),
+            LuaTableEntry::NilValue => Cow::Owned(LuaValue::Nil),
+        }
+    }
+
+    /// Converts the entry into one which owns all of its data, copying any borrowed strings.
+    ///
+    /// See [`LuaValue::into_owned()`].
+    pub fn into_owned(self) -> LuaTableEntry<'static> {
+        match self {
+            LuaTableEntry::KeyValue(b) => {
+                let (k, v) = *b;
+                LuaTableEntry::KeyValue(Box::new((k.into_owned(), v.into_owned())))
+            }
+            LuaTableEntry::NameValue(b) => {
+                let (k, v) = *b;
+                LuaTableEntry::NameValue(Box::new((Cow::Owned(k.into_owned()), v.into_owned())))
+            }
+            LuaTableEntry::Value(v) => LuaTableEntry::Value(Box::new(v.into_owned())),
+            LuaTableEntry::NumberValue(n) => LuaTableEntry::NumberValue(n),
+            LuaTableEntry::BooleanValue(b) => LuaTableEntry::BooleanValue(b),
+            LuaTableEntry::NilValue => LuaTableEntry::NilValue,
+        }
+    }
+
+    /// Makes a copy of the entry which owns all of its data, without consuming `self`.
+    ///
+    /// See [`LuaValue::to_static()`].
+    pub fn to_static(&self) -> LuaTableEntry<'static> {
+        match self {
+            LuaTableEntry::KeyValue(b) => {
+                LuaTableEntry::KeyValue(Box::new((b.0.to_static(), b.1.to_static())))
+            }
+            LuaTableEntry::NameValue(b) => {
+                LuaTableEntry::NameValue(Box::new((Cow::Owned(b.0.to_string()), b.1.to_static())))
+            }
+            LuaTableEntry::Value(v) => LuaTableEntry::Value(Box::new(v.to_static())),
+            LuaTableEntry::NumberValue(n) => LuaTableEntry::NumberValue(*n),
+            LuaTableEntry::BooleanValue(b) => LuaTableEntry::BooleanValue(*b),
+            LuaTableEntry::NilValue => LuaTableEntry::NilValue,
+        }
+    }
+
+    /// Makes a copy of the entry which [borrows][Cow::Borrowed] all of its strings from `self`.
+    ///
+    /// See [`LuaValue::as_borrowed()`].
+    pub(crate) fn as_borrowed(&self) -> LuaTableEntry<'_> {
+        match self {
+            LuaTableEntry::KeyValue(b) => {
+                LuaTableEntry::KeyValue(Box::new((b.0.as_borrowed(), b.1.as_borrowed())))
+            }
+            LuaTableEntry::NameValue(b) => {
+                LuaTableEntry::NameValue(Box::new((Cow::Borrowed(&b.0), b.1.as_borrowed())))
+            }
+            LuaTableEntry::Value(v) => LuaTableEntry::Value(Box::new(v.as_borrowed())),
+            LuaTableEntry::NumberValue(n) => LuaTableEntry::NumberValue(*n),
+            LuaTableEntry::BooleanValue(b) => LuaTableEntry::BooleanValue(*b),
+            LuaTableEntry::NilValue => LuaTableEntry::NilValue,
+        }
+    }
+
+    /// Returns the number of bytes of heap memory allocated by this entry, including its key and
+    /// value.
+    ///
+    /// See [`LuaValue::heap_size()`].
+    pub fn heap_size(&self) -> usize {
+        match self {
+            LuaTableEntry::KeyValue(b) => {
+                size_of::<(LuaValue, LuaValue)>() + b.0.heap_size() + b.1.heap_size()
+            }
+            LuaTableEntry::NameValue(b) => {
+                let name = match &b.0 {
+                    Cow::Borrowed(_) => 0,
+                    Cow::Owned(n) => n.capacity(),
+                };
+                size_of::<(Cow<str>, LuaValue)>() + name + b.1.heap_size()
+            }
+            LuaTableEntry::Value(v) => size_of::<LuaValue>() + v.heap_size(),
+            LuaTableEntry::NumberValue(_)
+            | LuaTableEntry::BooleanValue(_)
+            | LuaTableEntry::NilValue => 0,
+        }
+    }
+
     /// Moves a [`LuaNumber`][] value out of the table entry.
     ///
     /// Returns [`None`][] if the contained value is not a [`LuaNumber`][].
@@ -0,0 +1,257 @@
+//! Transformations of [`LuaValue`]s before deserialisation.
+use crate::{
+    error::{append_path, PathKey},
+    Error, LuaTableEntry, LuaValue,
+};
+use serde::de;
+use std::{fmt, mem, str::from_utf8, sync::Arc};
+
+type TransformFn = dyn for<'a> Fn(LuaValue<'a>) -> Result<LuaValue<'a>, String> + Send + Sync;
+
+/// Which values a transformation applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    /// Values of table entries with a string key, at any depth.
+    Field(String),
+
+    /// The value at a path, in the same format as [`Error::path()`].
+    Path(String),
+}
+
+/// Transformations which are applied to [`LuaValue`]s before they are deserialised, for
+/// [`DeserializeOptions::transforms`][crate::DeserializeOptions::transforms].
+///
+/// Some software stores structured data in strings (like `"1,2,3"`, or base64-encoded blobs).
+/// Rather than writing custom [`Deserialize`][serde::Deserialize] implementations for every field
+/// which does this, a transformation can convert the value into a form that Serde can deserialise
+/// directly.
+///
+/// A transformation is a function which takes a [`LuaValue`] and returns a new one, or an error
+/// message. Transformations apply to:
+///
+/// * [`field()`][Self::field]: the values of all table entries with a particular string key, at
+///   any depth (including script variables)
+/// * [`path()`][Self::path]: the value at a particular path, in the same format as
+///   [`Error::path()`] (like `player.pos`, `items[2]` or `["a b"].c`). The empty path is the root
+///   value.
+///
+/// Values inside of a table are transformed before the table itself. When more than one
+/// transformation applies to a value, they are applied in the order they were added.
+///
+/// If a transformation returns an error, deserialisation fails with that error message, and the
+/// [path][Error::path] of the value.
+///
+/// Transformations are only applied by [`from_slice_with_options()`][crate::from_slice_with_options]
+/// and [`from_value_with_options()`][crate::from_value_with_options]. They walk the entire
+/// parsed value, and [`from_value_with_options()`][crate::from_value_with_options] copies the
+/// value's tables to transform them, so they are slower than deserialising without them.
+///
+/// Clones of a `Transforms` share the same functions, and compare equal.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use serde_luaq::{
+///     from_slice_with_options, DeserializeOptions, LuaFormat, LuaTableEntry, LuaValue, Transforms,
+/// };
+///
+/// #[derive(Deserialize, Debug, PartialEq)]
+/// struct Unit {
+///     name: String,
+///     pos: (f64, f64, f64),
+/// }
+///
+/// // Split "x,y,z" strings into tables of numbers
+/// let transforms = Transforms::new().field("pos", |v| {
+///     let LuaValue::String(s) = &v else {
+///         return Ok(v);
+///     };
+///
+///     std::str::from_utf8(s)
+///         .map_err(|e| e.to_string())?
+///         .split(',')
+///         .map(|n| n.trim().parse::<f64>().map(LuaTableEntry::from))
+///         .collect::<Result<_, _>>()
+///         .map(LuaValue::Table)
+///         .map_err(|e| e.to_string())
+/// });
+/// let opts = DeserializeOptions::new().transforms(transforms);
+///
+/// let units: Vec<Unit> = from_slice_with_options(
+///     br#"{{name = "tank", pos = "1, 2.5, 0"}, {name = "scout", pos = {3, 4, 5}}}"#,
+///     LuaFormat::Value,
+///     16,
+///     &opts,
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     vec![
+///         Unit { name: "tank".to_string(), pos: (1., 2.5, 0.) },
+///         Unit { name: "scout".to_string(), pos: (3., 4., 5.) },
+///     ],
+///     units,
+/// );
+///
+/// let e = from_slice_with_options::<Vec<Unit>>(
+///     br#"{{name = "tank", pos = "1, x, 0"}}"#,
+///     LuaFormat::Value,
+///     16,
+///     &opts,
+/// )
+/// .unwrap_err();
+/// assert_eq!("[1].pos: serde deserialize error: invalid float literal", e.to_string());
+/// ```
+#[derive(Default, Clone)]
+pub struct Transforms {
+    rules: Vec<(Target, Arc<TransformFn>)>,
+
+    /// Whether any rules are [`Target::Path`], so paths need to be tracked.
+    paths: bool,
+}
+
+impl Transforms {
+    /// Creates an empty set of transformations.
+    pub const fn new() -> Self {
+        Self {
+            rules: Vec::new(),
+            paths: false,
+        }
+    }
+
+    /// Adds a transformation for the values of all table entries with the string key `name`, at
+    /// any depth.
+    pub fn field<F>(mut self, name: impl Into<String>, f: F) -> Self
+    where
+        F: for<'a> Fn(LuaValue<'a>) -> Result<LuaValue<'a>, String> + Send + Sync + 'static,
+    {
+        self.rules.push((Target::Field(name.into()), Arc::new(f)));
+        self
+    }
+
+    /// Adds a transformation for the value at `path`, in the same format as [`Error::path()`].
+    pub fn path<F>(mut self, path: impl Into<String>, f: F) -> Self
+    where
+        F: for<'a> Fn(LuaValue<'a>) -> Result<LuaValue<'a>, String> + Send + Sync + 'static,
+    {
+        self.rules.push((Target::Path(path.into()), Arc::new(f)));
+        self.paths = true;
+        self
+    }
+
+    /// Returns `true` if there are no transformations.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns `true` if there is a transformation for `path`.
+    fn has_path(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .any(|(target, _)| matches!(target, Target::Path(p) if p == path))
+    }
+
+    /// Applies the transformations to `v` and all of the values inside of it.
+    pub(crate) fn apply<'a>(&self, v: LuaValue<'a>) -> Result<LuaValue<'a>, Error> {
+        if self.is_empty() {
+            return Ok(v);
+        }
+        self.walk(v, None, self.paths.then_some(""))
+    }
+
+    /// Transforms `v`, which is the value of a table entry with the string key `field`, at
+    /// `path` (if paths are tracked).
+    fn walk<'a>(
+        &self,
+        v: LuaValue<'a>,
+        field: Option<&str>,
+        path: Option<&str>,
+    ) -> Result<LuaValue<'a>, Error> {
+        let v = match v {
+            LuaValue::Table(entries) => {
+                let mut index = 0;
+                let entries = entries
+                    .into_iter()
+                    .map(|entry| {
+                        let key = match &entry {
+                            LuaTableEntry::KeyValue(b) => PathKey::from(&b.0),
+                            LuaTableEntry::NameValue(b) => PathKey::Name(b.0.clone()),
+                            _ => {
+                                index += 1;
+                                PathKey::Integer(index)
+                            }
+                        };
+                        self.entry(entry, &key, path)
+                            .map_err(|e| e.prepend_path(&key))
+                    })
+                    .collect::<Result<_, _>>()?;
+                LuaValue::Table(entries)
+            }
+            v => v,
+        };
+
+        self.rules
+            .iter()
+            .filter(|(target, _)| match target {
+                Target::Field(name) => field == Some(name),
+                Target::Path(p) => path == Some(p),
+            })
+            .try_fold(v, |v, (_, f)| f(v).map_err(de::Error::custom))
+    }
+
+    /// Transforms the value of a table entry with `key`, in a table at `path`.
+    fn entry<'a>(
+        &self,
+        mut entry: LuaTableEntry<'a>,
+        key: &PathKey<'_>,
+        path: Option<&str>,
+    ) -> Result<LuaTableEntry<'a>, Error> {
+        let path = path.map(|p| append_path(p, key));
+        let path = path.as_deref();
+
+        match &mut entry {
+            LuaTableEntry::KeyValue(b) => {
+                let (k, v) = &mut **b;
+                let field = match k {
+                    LuaValue::String(s) => from_utf8(s).ok(),
+                    _ => None,
+                };
+                *v = self.walk(mem::replace(v, LuaValue::Nil), field, path)?;
+            }
+            LuaTableEntry::NameValue(b) => {
+                let (k, v) = &mut **b;
+                *v = self.walk(mem::replace(v, LuaValue::Nil), Some(k), path)?;
+            }
+            LuaTableEntry::Value(v) => {
+                **v = self.walk(mem::replace(&mut **v, LuaValue::Nil), None, path)?;
+            }
+            // Other values without keys can only be matched by path.
+            _ if path.is_some_and(|p| self.has_path(p)) => {
+                return Ok(self.walk(entry.move_value(), None, path)?.into());
+            }
+            _ => (),
+        }
+        Ok(entry)
+    }
+}
+
+impl fmt::Debug for Transforms {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.rules.iter().map(|(target, _)| target))
+            .finish()
+    }
+}
+
+impl PartialEq for Transforms {
+    fn eq(&self, other: &Self) -> bool {
+        self.rules.len() == other.rules.len()
+            && self
+                .rules
+                .iter()
+                .zip(&other.rules)
+                .all(|((a, f), (b, g))| a == b && Arc::ptr_eq(f, g))
+    }
+}
+
+impl Eq for Transforms {}
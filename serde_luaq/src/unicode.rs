@@ -0,0 +1,45 @@
+//! Unicode normalisation of struct field names, for
+//! [`DeserializeOptions::unicode_fields`][crate::DeserializeOptions::unicode_fields].
+use unicode_normalization::UnicodeNormalization;
+
+/// Returns `true` if `field` and `key` are canonically equivalent (have the same NFC form), and
+/// optionally ignoring case.
+///
+/// This compares characters as they are normalised, without allocating.
+pub(crate) fn fields_match(field: &str, key: &str, case_insensitive: bool) -> bool {
+    if case_insensitive {
+        // Lowercasing can produce characters which need to be recomposed, so decompose first.
+        field
+            .nfd()
+            .flat_map(char::to_lowercase)
+            .nfc()
+            .eq(key.nfd().flat_map(char::to_lowercase).nfc())
+    } else {
+        field.nfc().eq(key.nfc())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn normalisation() {
+        // "français", composed (NFC) and decomposed (NFD)
+        let nfc = "fran\u{e7}ais";
+        let nfd = "franc\u{327}ais";
+
+        assert!(fields_match(nfc, nfd, false));
+        assert!(fields_match(nfd, nfc, false));
+        assert!(!fields_match(nfc, "francais", false));
+
+        // Case folding
+        assert!(!fields_match(nfc, "FRAN\u{c7}AIS", false));
+        assert!(fields_match(nfc, "FRAN\u{c7}AIS", true));
+        assert!(fields_match(nfc, "FRANC\u{327}AIS", true));
+        assert!(fields_match("\u{3a3}\u{3a9}", "\u{3c3}\u{3c9}", true));
+
+        // Compatibility equivalents are different
+        assert!(!fields_match("fi", "\u{fb01}", true));
+    }
+}
@@ -0,0 +1,257 @@
+//! Checked conversions from [`LuaValue`] into Rust types.
+use super::from_utf8_cow;
+use crate::{ConversionError, LuaNumber, LuaTableEntry, LuaValue};
+use std::borrow::Cow;
+
+impl<'a> LuaValue<'a> {
+    /// Describes the type of this value for [`ConversionError::actual_type`].
+    ///
+    /// This is like Lua's `type()` function, except numbers are described as `integer` or `float`.
+    pub(crate) const fn conversion_type(&self) -> &'static str {
+        match self {
+            LuaValue::Number(n) => n.subtype_name(),
+            _ => self.type_name(),
+        }
+    }
+
+    fn conversion_error(&self, expected: &'static str) -> ConversionError {
+        ConversionError::new(expected, self.conversion_type())
+    }
+
+    /// Converts the value into a `bool`, if it is [a boolean][LuaValue::Boolean].
+    ///
+    /// Unlike Lua, this does not treat other values as "truthy".
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::LuaValue;
+    ///
+    /// assert!(LuaValue::Boolean(true).try_into_bool().unwrap());
+    ///
+    /// let e = LuaValue::Nil.try_into_bool().unwrap_err();
+    /// assert_eq!("expected boolean, found nil", e.to_string());
+    /// ```
+    pub fn try_into_bool(self) -> Result<bool, ConversionError> {
+        self.as_bool()
+            .ok_or_else(|| self.conversion_error("boolean"))
+    }
+
+    /// Converts the value into an `i64`, if it is [an integer number][LuaNumber::Integer].
+    ///
+    /// Like [`as_i64()`][LuaValue::as_i64], floating point values are never converted.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::LuaValue;
+    ///
+    /// assert_eq!(123, LuaValue::integer(123).try_into_i64().unwrap());
+    ///
+    /// let e = LuaValue::float(3.).try_into_i64().unwrap_err();
+    /// assert_eq!("expected integer, found float", e.to_string());
+    /// ```
+    pub fn try_into_i64(self) -> Result<i64, ConversionError> {
+        self.as_i64()
+            .ok_or_else(|| self.conversion_error("integer"))
+    }
+
+    /// Converts the value into an `f64`, if it is [a number][LuaValue::Number].
+    ///
+    /// Like [`as_f64()`][LuaValue::as_f64], integers are only converted if they can be represented
+    /// without a loss of precision.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::LuaValue;
+    ///
+    /// assert_eq!(3.5, LuaValue::float(3.5).try_into_f64().unwrap());
+    /// assert_eq!(3., LuaValue::integer(3).try_into_f64().unwrap());
+    ///
+    /// let e = LuaValue::integer(i64::MAX).try_into_f64().unwrap_err();
+    /// assert_eq!("expected float, found integer", e.to_string());
+    /// ```
+    pub fn try_into_f64(self) -> Result<f64, ConversionError> {
+        self.as_f64().ok_or_else(|| self.conversion_error("float"))
+    }
+
+    /// Converts the value into a [`LuaNumber`], if it is [a number][LuaValue::Number].
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{LuaNumber, LuaValue};
+    ///
+    /// assert_eq!(LuaNumber::Integer(1), LuaValue::integer(1).try_into_number().unwrap());
+    /// assert!(LuaValue::from("1").try_into_number().is_err());
+    /// ```
+    pub fn try_into_number(self) -> Result<LuaNumber, ConversionError> {
+        match self {
+            LuaValue::Number(n) => Ok(n),
+            v => Err(v.conversion_error("number")),
+        }
+    }
+
+    /// Converts the value into its raw bytes, if it is [a string][LuaValue::String].
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::LuaValue;
+    ///
+    /// let a = LuaValue::String(b"\0\xC0".into());
+    /// assert_eq!(b"\0\xC0", a.try_into_bytes().unwrap().as_ref());
+    ///
+    /// let e = LuaValue::integer(1).try_into_bytes().unwrap_err();
+    /// assert_eq!("expected string, found integer", e.to_string());
+    /// ```
+    pub fn try_into_bytes(self) -> Result<Cow<'a, [u8]>, ConversionError> {
+        match self {
+            LuaValue::String(s) => Ok(s),
+            v => Err(v.conversion_error("string")),
+        }
+    }
+
+    /// Converts the value into a string, if it is [a UTF-8-encoded string][LuaValue::String].
+    ///
+    /// Strings which are not valid UTF-8 are reported with an
+    /// [`actual_type`][ConversionError::actual_type] of `non-UTF-8 string`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::LuaValue;
+    ///
+    /// assert_eq!("hello", LuaValue::from("hello").try_into_string().unwrap());
+    ///
+    /// let e = LuaValue::String(b"\0\xC0".into()).try_into_string().unwrap_err();
+    /// assert_eq!("expected UTF-8 string, found non-UTF-8 string", e.to_string());
+    /// ```
+    pub fn try_into_string(self) -> Result<Cow<'a, str>, ConversionError> {
+        const EXPECTED: &str = "UTF-8 string";
+        match self {
+            LuaValue::String(s) => {
+                from_utf8_cow(s).map_err(|_| ConversionError::new(EXPECTED, "non-UTF-8 string"))
+            }
+            v => Err(v.conversion_error(EXPECTED)),
+        }
+    }
+
+    /// Converts the value into its entries, if it is [a table][LuaValue::Table].
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{lua_value, LuaValue};
+    ///
+    /// assert_eq!(2, lua_value(b"{1, 2}", 16).unwrap().try_into_table().unwrap().len());
+    ///
+    /// let e = LuaValue::Boolean(false).try_into_table().unwrap_err();
+    /// assert_eq!("expected table, found boolean", e.to_string());
+    /// ```
+    pub fn try_into_table(self) -> Result<Vec<LuaTableEntry<'a>>, ConversionError> {
+        match self {
+            LuaValue::Table(t) => Ok(t),
+            v => Err(v.conversion_error("table")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+    fn conversions() {
+        assert!(!LuaValue::Boolean(false).try_into_bool().unwrap());
+        assert_eq!(
+            i64::MIN,
+            LuaValue::integer(i64::MIN).try_into_i64().unwrap()
+        );
+        assert_eq!(0.5, LuaValue::float(0.5).try_into_f64().unwrap());
+        assert_eq!(
+            LuaNumber::Float(0.5),
+            LuaValue::float(0.5).try_into_number().unwrap()
+        );
+
+        // Borrowed strings should stay borrowed
+        let s = LuaValue::String(Cow::Borrowed(b"hello"));
+        assert!(matches!(
+            s.clone().try_into_bytes(),
+            Ok(Cow::Borrowed(b"hello"))
+        ));
+        assert!(matches!(s.try_into_string(), Ok(Cow::Borrowed("hello"))));
+
+        assert_eq!(
+            vec![LuaTableEntry::NumberValue(LuaNumber::Integer(1))],
+            LuaValue::Table(vec![LuaTableEntry::NumberValue(LuaNumber::Integer(1))])
+                .try_into_table()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+    fn errors() {
+        fn check(expected: &'static str, actual_type: &'static str, e: ConversionError) {
+            assert_eq!(ConversionError::new(expected, actual_type), e);
+        }
+
+        check("boolean", "nil", LuaValue::Nil.try_into_bool().unwrap_err());
+        check(
+            "integer",
+            "float",
+            LuaValue::float(1.).try_into_i64().unwrap_err(),
+        );
+        check(
+            "float",
+            "string",
+            LuaValue::from("1").try_into_f64().unwrap_err(),
+        );
+        check(
+            "number",
+            "boolean",
+            LuaValue::Boolean(true).try_into_number().unwrap_err(),
+        );
+        check(
+            "string",
+            "table",
+            LuaValue::Table(vec![]).try_into_bytes().unwrap_err(),
+        );
+        check(
+            "UTF-8 string",
+            "table",
+            LuaValue::Table(vec![]).try_into_string().unwrap_err(),
+        );
+        check(
+            "table",
+            "integer",
+            LuaValue::integer(1).try_into_table().unwrap_err(),
+        );
+    }
+
+    #[test]
+    #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+    fn paths() {
+        let e = LuaValue::from("3")
+            .try_into_i64()
+            .map_err(|e| {
+                e.prepend_path("level")
+                    .prepend_path("[1]")
+                    .prepend_path("players")
+            })
+            .unwrap_err();
+
+        assert_eq!("players[1].level", e.path);
+        assert_eq!(
+            "players[1].level: expected integer, found string",
+            e.to_string()
+        );
+    }
+}
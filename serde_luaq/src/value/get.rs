@@ -0,0 +1,144 @@
+//! Looking up values in [tables][LuaValue::Table] by key.
+use crate::{
+    table::{entry_key, key_eq},
+    LuaTableEntry, LuaValue,
+};
+use std::borrow::Cow;
+
+impl<'a> LuaValue<'a> {
+    /// Looks up `key` in a [table][LuaValue::Table], like Lua's `t[key]`.
+    ///
+    /// Keys are compared using [Lua's rules for key equality][crate::LuaTableExt], so `"a"` finds
+    /// both `a = 1` and `["a"] = 1`, `1` finds both `[1.0] = "x"` and the first implicitly-keyed
+    /// entry, and `nil`, NaN and table keys never find anything.
+    ///
+    /// If the table contains more than one entry with the same key (like `{a = 1, a = 2}`), the
+    /// last one is used.
+    ///
+    /// Returns [`None`] if `self` isn't a table, or the table doesn't contain `key`. An entry whose
+    /// value is `nil` is returned as [`LuaValue::Nil`].
+    ///
+    /// Values are returned as a [`Cow`], because [compact table entries][LuaTableEntry::NumberValue]
+    /// don't contain a [`LuaValue`] which can be borrowed.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{lua_value, LuaValue};
+    ///
+    /// let v = lua_value(br#"{"a", "b", name = "x", name = "y", [2.5] = true}"#, 16).unwrap();
+    ///
+    /// assert_eq!(Some(&LuaValue::from("b")), v.get(2).as_deref());
+    /// assert_eq!(Some(&LuaValue::from("y")), v.get("name").as_deref());
+    /// assert_eq!(Some(&LuaValue::Boolean(true)), v.get(2.5).as_deref());
+    /// assert_eq!(None, v.get(3));
+    /// assert_eq!(None, LuaValue::from("a").get(1));
+    /// ```
+    pub fn get<'k>(&self, key: impl Into<LuaValue<'k>>) -> Option<Cow<'_, LuaValue<'a>>> {
+        let LuaValue::Table(entries) = self else {
+            return None;
+        };
+
+        let key = key.into();
+        let mut implicit = 0;
+        entries
+            .iter()
+            .filter(|entry| key_eq(&entry_key(entry, &mut implicit), &key))
+            .last()
+            .map(LuaTableEntry::value_cow)
+    }
+
+    /// Looks up `key` in a [table][LuaValue::Table] like [`get()`][Self::get], returning a mutable
+    /// reference to its value.
+    ///
+    /// If the entry is a [compact table entry][LuaTableEntry::NumberValue], it is replaced with an
+    /// equivalent [`Value`][LuaTableEntry::Value] entry which can be borrowed.
+    ///
+    /// Returns [`None`] if `self` isn't a table, or the table doesn't contain `key`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{lua_value, LuaValue};
+    ///
+    /// let mut v = lua_value(br#"{1, 2, name = "x"}"#, 16).unwrap();
+    ///
+    /// *v.get_mut(2).unwrap() = LuaValue::from("b");
+    /// *v.get_mut("name").unwrap() = LuaValue::from("y");
+    /// assert_eq!(None, v.get_mut(3));
+    ///
+    /// assert_eq!(lua_value(br#"{1, "b", name = "y"}"#, 16).unwrap(), v);
+    /// ```
+    pub fn get_mut<'k>(&mut self, key: impl Into<LuaValue<'k>>) -> Option<&mut LuaValue<'a>> {
+        let LuaValue::Table(entries) = self else {
+            return None;
+        };
+
+        let key = key.into();
+        let mut implicit = 0;
+        let i = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| key_eq(&entry_key(entry, &mut implicit), &key))
+            .last()?
+            .0;
+
+        let entry = &mut entries[i];
+        if entry.value().is_none() {
+            let value = std::mem::replace(entry, LuaTableEntry::NilValue).move_value();
+            *entry = LuaTableEntry::Value(Box::new(value));
+        }
+
+        match entry {
+            LuaTableEntry::KeyValue(b) => Some(&mut b.1),
+            LuaTableEntry::NameValue(b) => Some(&mut b.1),
+            LuaTableEntry::Value(value) => Some(value),
+            LuaTableEntry::NumberValue(_)
+            | LuaTableEntry::BooleanValue(_)
+            | LuaTableEntry::NilValue => unreachable!(),
+        }
+    }
+
+    /// Looks up a value in nested [tables][LuaValue::Table] by a `path` of keys, like Lua's
+    /// `t[a][b][c]`.
+    ///
+    /// Each key is looked up with [`get()`][Self::get]. An empty path refers to the whole value.
+    ///
+    /// Returns [`None`] if any key along the path is missing, or refers to a value which isn't a
+    /// table.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{lua_value, LuaValue};
+    ///
+    /// let v = lua_value(br#"{players = {{name = "alice"}, {name = "bob"}}}"#, 16).unwrap();
+    ///
+    /// // Keys of different types need to be converted to a LuaValue first
+    /// assert_eq!(
+    ///     Some(&LuaValue::from("bob")),
+    ///     v.get_path([LuaValue::from("players"), 2.into(), "name".into()]).as_deref(),
+    /// );
+    ///
+    /// assert_eq!(None, v.get_path(["players", "name"]));
+    /// assert_eq!(Some(&v), v.get_path::<LuaValue>([]).as_deref());
+    /// ```
+    pub fn get_path<'k, K>(
+        &self,
+        path: impl IntoIterator<Item = K>,
+    ) -> Option<Cow<'_, LuaValue<'a>>>
+    where
+        K: Into<LuaValue<'k>>,
+    {
+        let mut value = Cow::Borrowed(self);
+        for key in path {
+            // Only compact table entries are owned, and they aren't tables.
+            let Cow::Borrowed(v) = value else {
+                return None;
+            };
+            value = v.get(key)?;
+        }
+
+        Some(value)
+    }
+}
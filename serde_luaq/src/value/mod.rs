@@ -11,6 +11,20 @@ use std::{
     str::{from_utf8, Utf8Error},
 };
 
+mod convert;
+mod get;
+mod ord;
+mod paths;
+mod pointer;
+mod ser;
+mod shared;
+mod walk;
+pub use ord::OrdLuaValue;
+pub use paths::PathIter;
+pub use ser::to_value;
+pub use shared::{DedupStats, SharedLuaValue, SharedTableEntry};
+pub use walk::{LuaVisitor, VisitAction};
+
 /// Basic Lua 5.4 data types that are equivalent to those available in JSON, similar to
 /// `serde_json::Value`.
 ///
@@ -20,21 +34,57 @@ use std::{
 ///
 /// ## Serde
 ///
-/// **Unlike** `serde_json::Value`, [`LuaValue`][] does not implement
-/// [`Deserialize`][serde::Deserialize]. This is due to two problems:
+/// Like `serde_json::Value`, [`LuaValue`][] implements [`Deserialize`][serde::Deserialize], so it
+/// can be used as a catch-all field type for data which doesn't have a fixed structure:
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use serde_luaq::{from_slice, LuaFormat, LuaTableEntry, LuaValue};
+///
+/// #[derive(Deserialize, Debug)]
+/// struct Item {
+///     name: String,
+///     data: LuaValue<'static>,
+/// }
+///
+/// let item: Item = from_slice(
+///     b"{name = 'sword', data = {damage = 5, ['tags'] = {'sharp', 'shiny'}}}",
+///     LuaFormat::Value,
+///     16,
+/// ).unwrap();
+///
+/// assert_eq!("sword", item.name);
+/// assert_eq!(
+///     LuaValue::from_iter([
+///         ("damage", LuaValue::integer(5)),
+///         ("tags", LuaValue::Table(vec![
+///             LuaTableEntry::from(LuaValue::from("sharp")),
+///             LuaTableEntry::from(LuaValue::from("shiny")),
+///         ])),
+///     ]),
+///     item.data,
+/// );
+/// ```
 ///
-/// * [`LuaValue`][] tries to avoid owning data, so any fields would need a lifetime of `'de`. This
-///   doesn't work with the `derive` macros.
+/// However, this has some limitations:
+///
+/// * Deserialised values always [own all of their data][LuaValue::into_owned], because a
+///   [`LuaValue`][] which borrows from the input would need a lifetime of `'de`, which doesn't work
+///   with the `derive` macros. This copies every string.
 ///
 /// * Lua tables' [multiple key types][LuaTableEntry] are a hybrid of Serde's map and sequence
-///   types, which are converted to one or the other depending on the destination field type. Serde
-///   also does not allow us to make a distinction between [a regular key][LuaTableEntry::KeyValue]
-///   and [an identifier key][LuaTableEntry::NameValue], so the table might change.
+///   types, and Serde doesn't make a distinction between [a regular key][LuaTableEntry::KeyValue]
+///   and [an identifier key][LuaTableEntry::NameValue], so the table might change:
+///
+///   * tables which look like [sequences][crate#tables-as-lists-in-serde-vec] have
+///     [implicit keys][LuaTableEntry::Value]
 ///
-/// As a result, you can't use [`LuaValue`][] as a field type or deserialise to it.
+///   * string keys which are valid Lua identifiers become [`LuaTableEntry::NameValue`], and all
+///     other keys become [`LuaTableEntry::KeyValue`]
+///
+/// If you want to deserialise Lua to a [`LuaValue`][] exactly as it was written (and without
+/// copying), use one of [the `peg` deserialisers][crate#peg-deserialiser].
 ///
-/// If you want to deserialise Lua to a [`LuaValue`][], use one of
-/// [the `peg` deserialisers][crate#peg-deserialiser].
 #[derive(Clone, PartialEq)]
 pub enum LuaValue<'a> {
     /// Nil value.
@@ -124,6 +174,9 @@ pub enum LuaValue<'a> {
     ///
     /// * Table values may be set to `nil`.
     ///
+    /// To get the table Lua would build, with each key at most once, use
+    /// [`LuaValue::try_into_lua_table()`].
+    ///
     /// When using `serde`, you can still use a table to populate a [`BTreeMap`] or [`Vec`].
     ///
     /// [`BTreeMap`]: std::collections::BTreeMap
@@ -167,6 +220,34 @@ impl<'a> LuaValue<'a> {
         Self::Number(LuaNumber::Float(v))
     }
 
+    /// Returns the name of the value's type, as returned by Lua's [`type()`][type] function:
+    /// `nil`, `boolean`, `number`, `string` or `table`.
+    ///
+    /// This is useful for error messages which are consistent with Lua. To tell integers and
+    /// floats apart, use [`LuaNumber::subtype_name()`].
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{lua_value, LuaValue};
+    ///
+    /// assert_eq!("nil", LuaValue::Nil.type_name());
+    /// assert_eq!("number", LuaValue::integer(1).type_name());
+    /// assert_eq!("number", LuaValue::float(1.5).type_name());
+    /// assert_eq!("table", lua_value(b"{}", 16).unwrap().type_name());
+    /// ```
+    ///
+    /// [type]: https://www.lua.org/manual/5.4/manual.html#pdf-type
+    pub const fn type_name(&self) -> &'static str {
+        match self {
+            LuaValue::Nil => "nil",
+            LuaValue::Boolean(_) => "boolean",
+            LuaValue::Number(_) => "number",
+            LuaValue::String(_) => "string",
+            LuaValue::Table(_) => "table",
+        }
+    }
+
     /// Returns `true` for a [`LuaValue::Number`] that is not a number.
     ///
     /// Other types return `false`, even if they are not numbers.
@@ -199,6 +280,117 @@ impl<'a> LuaValue<'a> {
         matches!(self, LuaValue::String(Cow::Borrowed(_)))
     }
 
+    /// Converts the value into one which owns all of its data, copying any borrowed strings.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{lua_value, LuaValue};
+    ///
+    /// let owned: LuaValue<'static> = {
+    ///     let input = b"{'hello'}".to_vec();
+    ///     lua_value(&input, 16).unwrap().into_owned()
+    /// };
+    /// assert_eq!(lua_value(b"{'hello'}", 16).unwrap(), owned);
+    /// ```
+    pub fn into_owned(self) -> LuaValue<'static> {
+        match self {
+            LuaValue::Nil => LuaValue::Nil,
+            LuaValue::Boolean(b) => LuaValue::Boolean(b),
+            LuaValue::Number(n) => LuaValue::Number(n),
+            LuaValue::String(s) => LuaValue::String(Cow::Owned(s.into_owned())),
+            LuaValue::Table(t) => {
+                LuaValue::Table(t.into_iter().map(LuaTableEntry::into_owned).collect())
+            }
+        }
+    }
+
+    /// Makes a copy of the value which owns all of its data, without consuming `self`.
+    ///
+    /// This is like `self.clone().into_owned()`, but only copies each string once.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{lua_value, LuaValue};
+    /// use std::collections::HashMap;
+    ///
+    /// let mut cache: HashMap<String, LuaValue<'static>> = HashMap::new();
+    /// let input = b"{name = 'hello'}".to_vec();
+    /// let value = lua_value(&input, 16).unwrap();
+    /// cache.insert("greeting".to_string(), value.to_static());
+    /// drop(input);
+    ///
+    /// assert_eq!(
+    ///     LuaValue::from_iter([("name", LuaValue::from("hello"))]),
+    ///     cache["greeting"],
+    /// );
+    /// ```
+    pub fn to_static(&self) -> LuaValue<'static> {
+        match self {
+            LuaValue::Nil => LuaValue::Nil,
+            LuaValue::Boolean(b) => LuaValue::Boolean(*b),
+            LuaValue::Number(n) => LuaValue::Number(*n),
+            LuaValue::String(s) => LuaValue::String(Cow::Owned(s.to_vec())),
+            LuaValue::Table(t) => LuaValue::Table(t.iter().map(LuaTableEntry::to_static).collect()),
+        }
+    }
+
+    /// Makes a copy of the value which [borrows][Cow::Borrowed] all of its strings from `self`.
+    ///
+    /// Table structure is copied, but string contents aren't.
+    pub(crate) fn as_borrowed(&self) -> LuaValue<'_> {
+        match self {
+            LuaValue::Nil => LuaValue::Nil,
+            LuaValue::Boolean(b) => LuaValue::Boolean(*b),
+            LuaValue::Number(n) => LuaValue::Number(*n),
+            LuaValue::String(s) => LuaValue::String(Cow::Borrowed(s)),
+            LuaValue::Table(t) => {
+                LuaValue::Table(t.iter().map(LuaTableEntry::as_borrowed).collect())
+            }
+        }
+    }
+
+    /// Returns the number of bytes of heap memory allocated by this value, including all nested
+    /// tables.
+    ///
+    /// This counts owned strings, table [`Vec`]s (by capacity) and the [`Box`]es of
+    /// [`LuaTableEntry`] variants. It doesn't count
+    /// [`size_of::<LuaValue>()`][std::mem::size_of] for the value itself, strings which are
+    /// [borrowed][Cow::Borrowed] from the input, or the allocator's own overheads.
+    ///
+    /// This can be used to enforce a memory quota for each document after parsing.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{lua_value, LuaTableEntry, LuaValue};
+    /// use std::mem::size_of;
+    ///
+    /// // Strings without escape sequences are borrowed from the input.
+    /// assert_eq!(0, lua_value(b"'hello'", 16).unwrap().heap_size());
+    /// assert_eq!(5, lua_value(br"'hell\x6f'", 16).unwrap().heap_size());
+    ///
+    /// // Integers are stored inline in table entries, so only the table's entries are allocated.
+    /// let v = lua_value(b"{1, 2, 3}", 16).unwrap();
+    /// assert!(v.heap_size() >= 3 * size_of::<LuaTableEntry>());
+    ///
+    /// // Enforce a quota
+    /// const QUOTA: usize = 1024 * 1024;
+    /// assert!(v.heap_size() <= QUOTA);
+    /// ```
+    pub fn heap_size(&self) -> usize {
+        match self {
+            LuaValue::Nil | LuaValue::Boolean(_) | LuaValue::Number(_) => 0,
+            LuaValue::String(Cow::Borrowed(_)) => 0,
+            LuaValue::String(Cow::Owned(s)) => s.capacity(),
+            LuaValue::Table(t) => {
+                t.capacity() * size_of::<LuaTableEntry>()
+                    + t.iter().map(LuaTableEntry::heap_size).sum::<usize>()
+            }
+        }
+    }
+
     /// Returns the value as a byte array, if it contains [a string][LuaValue::String].
     ///
     /// Lua strings may contain arbitrary binary data, with no defined encoding. This may not decode
@@ -677,4 +869,44 @@ mod test {
         let f = LuaValue::from(f32::NAN);
         assert!(matches!(f, LuaValue::Number(LuaNumber::Float(x)) if x.is_nan()));
     }
+
+    #[test]
+    #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+    fn heap_size() {
+        const ENTRY: usize = size_of::<LuaTableEntry>();
+        const VALUE: usize = size_of::<LuaValue>();
+
+        assert_eq!(0, LuaValue::Nil.heap_size());
+        assert_eq!(0, LuaValue::from("hello").heap_size());
+        assert_eq!(5, LuaValue::from("hello".to_string()).heap_size());
+        assert_eq!(0, LuaValue::Table(vec![]).heap_size());
+
+        let v =
+            crate::lua_value(br#"{{}, "a", name = 1, ["a\x41"] = 2, [{}] = true}"#, 16).unwrap();
+        let LuaValue::Table(t) = &v else {
+            panic!("expected table");
+        };
+        // The parser may allocate more capacity than it uses
+        let expected = t.capacity() * ENTRY
+            // {}
+            + VALUE
+            // "a"
+            + VALUE
+            // name = 1
+            + size_of::<(Cow<str>, LuaValue)>()
+            // ["a\x41"] = 2
+            + 2 * VALUE + 2
+            // [{}] = true
+            + 2 * VALUE;
+        assert_eq!(expected, v.heap_size());
+
+        // Owned names are counted
+        let e = LuaTableEntry::NameValue(Box::new((Cow::Owned("name".into()), LuaValue::Nil)));
+        assert_eq!(size_of::<(Cow<str>, LuaValue)>() + 4, e.heap_size());
+
+        // Unused capacity is counted
+        let mut t = Vec::with_capacity(8);
+        t.push(LuaTableEntry::NilValue);
+        assert_eq!(8 * ENTRY, LuaValue::Table(t).heap_size());
+    }
 }
@@ -0,0 +1,186 @@
+//! Total ordering of [`LuaValue`]s.
+use crate::{LuaTableEntry, LuaValue};
+use std::{borrow::Cow, cmp::Ordering};
+
+impl LuaValue<'_> {
+    /// Compares two values with a total order, for sorting values deterministically.
+    ///
+    /// Values are ordered by type, then by value:
+    ///
+    /// 1. `nil`
+    /// 2. booleans (`false` before `true`)
+    /// 3. numbers, in [numeric order][crate::LuaNumber::total_cmp], including NaN
+    /// 4. strings, in byte order
+    /// 5. tables, comparing their entries [in order][LuaTableEntry::total_cmp], then their length
+    ///
+    /// Lua itself doesn't define an ordering between values of different types (or tables), so
+    /// this is only useful for consistent output.
+    ///
+    /// This order is stable, and won't change in future versions.
+    ///
+    /// Unlike [`PartialEq`], this treats all NaN values with the same bits as equal, and `-0.0` as
+    /// less than `+0.0`. To use values in a [`BTreeSet`][std::collections::BTreeSet] or as
+    /// [`BTreeMap`][std::collections::BTreeMap] keys, wrap them in [`OrdLuaValue`].
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{lua_value, LuaValue};
+    ///
+    /// let mut values = vec![
+    ///     lua_value(b"{1, 2}", 16).unwrap(),
+    ///     lua_value(b"'b'", 16).unwrap(),
+    ///     lua_value(b"2.5", 16).unwrap(),
+    ///     lua_value(b"nil", 16).unwrap(),
+    ///     lua_value(b"{1}", 16).unwrap(),
+    ///     lua_value(b"true", 16).unwrap(),
+    ///     LuaValue::float(f64::NAN),
+    ///     lua_value(b"'a'", 16).unwrap(),
+    ///     lua_value(b"3", 16).unwrap(),
+    /// ];
+    ///
+    /// values.sort_by(LuaValue::total_cmp);
+    /// assert_eq!(
+    ///     values.into_iter().map(|v| format!("{v:?}")).collect::<Vec<_>>(),
+    ///     [
+    ///         "Nil",
+    ///         "Boolean(true)",
+    ///         "Number(Float(2.5))",
+    ///         "Number(Integer(3))",
+    ///         "Number(Float(NaN))",
+    ///         "String(\"a\")",
+    ///         "String(\"b\")",
+    ///         "Table([NumberValue(Integer(1))])",
+    ///         "Table([NumberValue(Integer(1)), NumberValue(Integer(2))])",
+    ///     ],
+    /// );
+    /// ```
+    pub fn total_cmp(&self, other: &LuaValue<'_>) -> Ordering {
+        match (self, other) {
+            (LuaValue::Nil, LuaValue::Nil) => Ordering::Equal,
+            (LuaValue::Boolean(a), LuaValue::Boolean(b)) => a.cmp(b),
+            (LuaValue::Number(a), LuaValue::Number(b)) => a.total_cmp(b),
+            (LuaValue::String(a), LuaValue::String(b)) => a.as_ref().cmp(b.as_ref()),
+            (LuaValue::Table(a), LuaValue::Table(b)) => {
+                for (a, b) in a.iter().zip(b.iter()) {
+                    match a.total_cmp(b) {
+                        Ordering::Equal => (),
+                        o => return o,
+                    }
+                }
+                a.len().cmp(&b.len())
+            }
+            (a, b) => a.type_order().cmp(&b.type_order()),
+        }
+    }
+
+    /// Sort order of each type of value.
+    const fn type_order(&self) -> u8 {
+        match self {
+            LuaValue::Nil => 0,
+            LuaValue::Boolean(_) => 1,
+            LuaValue::Number(_) => 2,
+            LuaValue::String(_) => 3,
+            LuaValue::Table(_) => 4,
+        }
+    }
+}
+
+impl LuaTableEntry<'_> {
+    /// Compares two table entries with a total order.
+    ///
+    /// Implicitly-keyed entries are ordered before entries with explicit keys. Entries with
+    /// explicit keys are compared by their key, then their value, using [`LuaValue::total_cmp()`].
+    ///
+    /// Like [`PartialEq`], [`NameValue`][LuaTableEntry::NameValue] and
+    /// [`KeyValue`][LuaTableEntry::KeyValue] entries with the same string key are equal, as are
+    /// the compact implicitly-keyed variants and their [`Value`][LuaTableEntry::Value]
+    /// equivalents.
+    pub fn total_cmp(&self, other: &LuaTableEntry<'_>) -> Ordering {
+        let (ak, av) = self.key_value();
+        let (bk, bv) = other.key_value();
+        match (ak, bk) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(a), Some(b)) => a.total_cmp(&b),
+        }
+        .then_with(|| av.total_cmp(&bv))
+    }
+
+    /// Gets the explicit key and value of an entry, without copying any strings.
+    fn key_value(&self) -> (Option<Cow<'_, LuaValue<'_>>>, Cow<'_, LuaValue<'_>>) {
+        match self {
+            LuaTableEntry::KeyValue(b) => (Some(Cow::Borrowed(&b.0)), Cow::Borrowed(&b.1)),
+            LuaTableEntry::NameValue(b) => (
+                Some(Cow::Owned(LuaValue::String(Cow::Borrowed(b.0.as_bytes())))),
+                Cow::Borrowed(&b.1),
+            ),
+            LuaTableEntry::Value(v) => (None, Cow::Borrowed(v)),
+            LuaTableEntry::NumberValue(n) => (None, Cow::Owned(LuaValue::Number(*n))),
+            LuaTableEntry::BooleanValue(b) => (None, Cow::Owned(LuaValue::Boolean(*b))),
+            LuaTableEntry::NilValue => (None, Cow::Owned(LuaValue::Nil)),
+        }
+    }
+}
+
+/// A [`LuaValue`] which implements [`Ord`] using [`LuaValue::total_cmp()`], so it can be used in a
+/// [`BTreeSet`][std::collections::BTreeSet] or as a [`BTreeMap`][std::collections::BTreeMap] key.
+///
+/// [`LuaValue`] doesn't implement [`Ord`] itself, because its [`PartialEq`] implementation treats
+/// NaN as unequal to itself.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde_luaq::{LuaValue, OrdLuaValue};
+/// use std::collections::BTreeSet;
+///
+/// let set: BTreeSet<_> = [
+///     LuaValue::String(b"a".into()),
+///     LuaValue::integer(1),
+///     LuaValue::float(f64::NAN),
+///     LuaValue::float(f64::NAN),
+///     LuaValue::integer(1),
+/// ]
+/// .into_iter()
+/// .map(OrdLuaValue)
+/// .collect();
+///
+/// assert_eq!(3, set.len());
+/// assert_eq!(&LuaValue::integer(1), &set.first().unwrap().0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct OrdLuaValue<'a>(pub LuaValue<'a>);
+
+impl PartialEq for OrdLuaValue<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OrdLuaValue<'_> {}
+
+impl PartialOrd for OrdLuaValue<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrdLuaValue<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl<'a> From<LuaValue<'a>> for OrdLuaValue<'a> {
+    fn from(value: LuaValue<'a>) -> Self {
+        Self(value)
+    }
+}
+
+impl<'a> From<OrdLuaValue<'a>> for LuaValue<'a> {
+    fn from(value: OrdLuaValue<'a>) -> Self {
+        value.0
+    }
+}
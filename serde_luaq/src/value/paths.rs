@@ -0,0 +1,164 @@
+//! Flattening [`LuaValue`] trees into paths.
+use crate::{
+    error::{append_path, PathKey},
+    LuaTableEntry, LuaValue,
+};
+use std::{borrow::Cow, slice};
+
+impl<'a> LuaValue<'a> {
+    /// Iterates over all leaf (non-table) values in this value, along with their path.
+    ///
+    /// Paths are in the same format as [`Error::Path`][crate::Error::Path], like
+    /// `a.b[1]["c d"]`. Implicitly-keyed entries (`{"a", "b"}`) have integer indexes starting at
+    /// `1`, like Lua.
+    ///
+    /// If this value is not a table, the iterator yields only this value, with an empty path.
+    ///
+    /// Values are visited depth-first, in the order entries were defined. Empty tables have no
+    /// leaf values, so are skipped.
+    ///
+    /// Values are yielded as a [`Cow`], because [compact table entries][LuaTableEntry::NumberValue]
+    /// don't contain a [`LuaValue`] which can be borrowed. Strings are always borrowed.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{lua_value, LuaValue};
+    ///
+    /// let v = lua_value(br#"{name = "alice", stats = {3, 4}, ["max hp"] = 10}"#, 16).unwrap();
+    /// let flat: Vec<String> = v.iter_paths().map(|(path, _)| path).collect();
+    /// assert_eq!(vec!["name", "stats[1]", "stats[2]", r#"["max hp"]"#], flat);
+    ///
+    /// let (path, value) = v.iter_paths().nth(2).unwrap();
+    /// assert_eq!("stats[2]", path);
+    /// assert_eq!(LuaValue::integer(4), *value);
+    /// ```
+    pub fn iter_paths(&self) -> PathIter<'_, 'a> {
+        PathIter {
+            root: Some(self),
+            stack: Vec::new(),
+        }
+    }
+}
+
+/// Iterator over the leaf values of a [`LuaValue`] and their paths.
+///
+/// This is returned by [`LuaValue::iter_paths()`].
+#[derive(Debug, Clone)]
+pub struct PathIter<'v, 'a> {
+    root: Option<&'v LuaValue<'a>>,
+    stack: Vec<Frame<'v, 'a>>,
+}
+
+/// A table which [`PathIter`] is part way through.
+#[derive(Debug, Clone)]
+struct Frame<'v, 'a> {
+    path: String,
+    entries: slice::Iter<'v, LuaTableEntry<'a>>,
+    next_index: i64,
+}
+
+impl<'v, 'a> Iterator for PathIter<'v, 'a> {
+    type Item = (String, Cow<'v, LuaValue<'a>>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take() {
+            match root {
+                LuaValue::Table(entries) => self.stack.push(Frame {
+                    path: String::new(),
+                    entries: entries.iter(),
+                    next_index: 1,
+                }),
+                v => return Some((String::new(), Cow::Borrowed(v))),
+            }
+        }
+
+        loop {
+            let frame = self.stack.last_mut()?;
+            let Some(entry) = frame.entries.next() else {
+                self.stack.pop();
+                continue;
+            };
+
+            let (key, value) = match entry {
+                LuaTableEntry::KeyValue(b) => (PathKey::from(&b.0), Cow::Borrowed(&b.1)),
+                LuaTableEntry::NameValue(b) => (PathKey::Name(b.0.clone()), Cow::Borrowed(&b.1)),
+                LuaTableEntry::Value(v) => {
+                    frame.next_index += 1;
+                    (PathKey::Integer(frame.next_index - 1), Cow::Borrowed(&**v))
+                }
+                entry => {
+                    frame.next_index += 1;
+                    (
+                        PathKey::Integer(frame.next_index - 1),
+                        Cow::Owned(entry.clone().move_value()),
+                    )
+                }
+            };
+
+            let path = append_path(&frame.path, &key);
+            match value {
+                Cow::Borrowed(LuaValue::Table(entries)) => self.stack.push(Frame {
+                    path,
+                    entries: entries.iter(),
+                    next_index: 1,
+                }),
+                value => return Some((path, value)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lua_value;
+
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn paths<'a>(v: &LuaValue<'a>) -> Vec<(String, LuaValue<'a>)> {
+        v.iter_paths().map(|(p, v)| (p, v.into_owned())).collect()
+    }
+
+    #[test]
+    #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+    fn scalar() {
+        assert_eq!(
+            vec![(String::new(), LuaValue::integer(1))],
+            paths(&LuaValue::integer(1))
+        );
+        assert!(paths(&LuaValue::Table(vec![])).is_empty());
+    }
+
+    #[test]
+    #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+    fn nested() {
+        let v = lua_value(
+            br#"{
+                true, "a",
+                b = {c = {}, d = {nil, 2.5}},
+                [3] = 1,
+                ["e f"] = {g = "h"},
+                [false] = 0,
+                {{1}},
+            }"#,
+            16,
+        )
+        .unwrap();
+
+        let expected: Vec<(String, LuaValue)> = vec![
+            ("[1]".into(), LuaValue::Boolean(true)),
+            ("[2]".into(), LuaValue::from("a")),
+            ("b.d[1]".into(), LuaValue::Nil),
+            ("b.d[2]".into(), LuaValue::float(2.5)),
+            ("[3]".into(), LuaValue::integer(1)),
+            (r#"["e f"].g"#.into(), LuaValue::from("h")),
+            ("[false]".into(), LuaValue::integer(0)),
+            ("[3][1][1]".into(), LuaValue::integer(1)),
+        ];
+        assert_eq!(expected, paths(&v));
+    }
+}
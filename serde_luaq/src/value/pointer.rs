@@ -0,0 +1,112 @@
+//! Addressing [`LuaValue`]s with JSON Pointers.
+use crate::{LuaTableEntry, LuaValue};
+use std::borrow::Cow;
+
+impl<'a> LuaValue<'a> {
+    /// Looks up a value by a [JSON Pointer][rfc6901], like `/players/0/name`.
+    ///
+    /// The pointer is resolved the same way it would be against the JSON value from
+    /// [`to_json_value()`][crate::to_json_value], so pointers from JSON-based tooling can be
+    /// reused on the original [`LuaValue`]:
+    ///
+    /// * A table containing _only_ implicitly-keyed entries (like `{"a", "b"}`) is a JSON array,
+    ///   and is indexed from `0`: `/0` is Lua's `[1]`.
+    ///
+    /// * Any other table is a JSON object, and keys are matched as strings: `/1` is Lua's `[1]`
+    ///   (or `["1"]`), `/true` is Lua's `[true]`, and `/1.5` is Lua's `[1.5]`. Implicitly-keyed
+    ///   entries are numbered from `1`, like Lua. If a key appears more than once, the last entry
+    ///   is used.
+    ///
+    /// An empty pointer (`""`) refers to the whole value.
+    ///
+    /// Returns [`None`] if the pointer is invalid, or doesn't refer to a value.
+    ///
+    /// Values are returned as a [`Cow`], because [compact table entries][LuaTableEntry::NumberValue]
+    /// don't contain a [`LuaValue`] which can be borrowed.
+    ///
+    /// This doesn't need the `serde_json` feature.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{lua_value, LuaValue};
+    ///
+    /// let v = lua_value(br#"{players = {{name = "alice"}, {name = "bob"}}, [5] = "x"}"#, 16)
+    ///     .unwrap();
+    ///
+    /// // Arrays are indexed from 0
+    /// assert_eq!(
+    ///     Some(&LuaValue::from("bob")),
+    ///     v.pointer("/players/1/name").as_deref(),
+    /// );
+    ///
+    /// // Integer keys in objects are strings
+    /// assert_eq!(Some(&LuaValue::from("x")), v.pointer("/5").as_deref());
+    ///
+    /// assert_eq!(None, v.pointer("/players/2"));
+    /// assert_eq!(Some(&v), v.pointer("").as_deref());
+    /// ```
+    ///
+    /// [rfc6901]: https://www.rfc-editor.org/rfc/rfc6901
+    pub fn pointer<'v>(&'v self, pointer: &str) -> Option<Cow<'v, LuaValue<'a>>> {
+        if pointer.is_empty() {
+            return Some(Cow::Borrowed(self));
+        }
+
+        let mut value = Cow::Borrowed(self);
+        for token in pointer.strip_prefix('/')?.split('/') {
+            let token = token.replace("~1", "/").replace("~0", "~");
+            let Cow::Borrowed(LuaValue::Table(entries)) = value else {
+                return None;
+            };
+
+            let entry = if !entries.is_empty() && entries.iter().all(LuaTableEntry::implicit_key) {
+                entries.get(parse_index(&token)?)?
+            } else {
+                let mut implicit = 0;
+                entries
+                    .iter()
+                    .filter(|entry| {
+                        if entry.implicit_key() {
+                            implicit += 1;
+                            implicit.to_string() == token
+                        } else {
+                            key_matches(entry, &token)
+                        }
+                    })
+                    .last()?
+            };
+
+            value = entry.value_cow();
+        }
+
+        Some(value)
+    }
+}
+
+/// Parses a JSON Pointer array index, which can't have leading zeros.
+fn parse_index(token: &str) -> Option<usize> {
+    if token.is_empty()
+        || !token.bytes().all(|b| b.is_ascii_digit())
+        || (token.len() > 1 && token.starts_with('0'))
+    {
+        return None;
+    }
+
+    token.parse().ok()
+}
+
+/// Returns `true` if an explicitly-keyed entry's key is converted to the JSON object key `token`.
+fn key_matches(entry: &LuaTableEntry<'_>, token: &str) -> bool {
+    match entry {
+        LuaTableEntry::NameValue(b) => b.0 == token,
+        LuaTableEntry::KeyValue(b) => match &b.0 {
+            LuaValue::String(k) => k.as_ref() == token.as_bytes(),
+            LuaValue::Nil => token == "nil",
+            LuaValue::Boolean(k) => k.to_string() == token,
+            LuaValue::Number(k) => k.to_string() == token,
+            LuaValue::Table(_) => false,
+        },
+        _ => false,
+    }
+}
@@ -0,0 +1,446 @@
+//! Serialising Rust types into [`LuaValue`]s.
+use crate::{error::PathKey, Error, LuaTableEntry, LuaValue};
+use serde::ser::{self, Serialize};
+use std::borrow::Cow;
+
+/// Converts any [`Serialize`] type into a [`LuaValue`].
+///
+/// This is the inverse of [`from_value()`][crate::from_value], and can be used to build or edit a
+/// [`LuaValue`] tree (for example, to merge it with a parsed value) from your own data types.
+///
+/// Types are converted like this:
+///
+/// * `()`, unit structs and [`None`] become `nil`.
+/// * Integers become [`LuaNumber::Integer`][crate::LuaNumber::Integer]. Integers outside the range of [`i64`] return an error.
+/// * Floats become [`LuaNumber::Float`][crate::LuaNumber::Float].
+/// * Strings, [`char`]s and byte arrays (with [`serde_bytes`][serde_bytes]) become
+///   [`LuaValue::String`].
+/// * Sequences and tuples become tables with implicit keys (`{1, 2, 3}`).
+/// * Maps and structs become tables with explicit keys. Keys which are valid Lua identifiers become
+///   [`LuaTableEntry::NameValue`] entries. Keys which are `nil` or NaN return an error.
+/// * Unit enum variants become a string (`"Variant"`), and other enum variants become a table with
+///   a single entry (`{Variant = value}`), matching what the deserialiser accepts.
+///
+/// Errors inside tables include the [path][Error::path] to the value.
+///
+/// ## Example
+///
+/// ```rust
+/// use serde::Serialize;
+/// use serde_luaq::{lua_value, to_value};
+///
+/// #[derive(Serialize)]
+/// struct Player {
+///     name: &'static str,
+///     scores: Vec<u32>,
+///     best: Option<u32>,
+/// }
+///
+/// let player = Player { name: "alice", scores: vec![3, 5], best: None };
+/// assert_eq!(
+///     lua_value(br#"{name = "alice", scores = {3, 5}, best = nil}"#, 16).unwrap(),
+///     to_value(&player).unwrap(),
+/// );
+///
+/// let err = to_value(&[u64::MAX]).unwrap_err();
+/// assert_eq!(Some("[1]"), err.path());
+/// ```
+///
+/// [serde_bytes]: https://docs.rs/serde_bytes
+pub fn to_value<T>(value: &T) -> Result<LuaValue<'static>, Error>
+where
+    T: Serialize + ?Sized,
+{
+    value.serialize(ValueSerializer)
+}
+
+/// [`serde::Serializer`] which builds a [`LuaValue`].
+pub(crate) struct ValueSerializer;
+
+/// Converts an integer which may not fit in an [`i64`].
+fn integer<T>(v: T) -> Result<LuaValue<'static>, Error>
+where
+    T: TryInto<i64> + std::fmt::Display + Copy,
+{
+    v.try_into().map(LuaValue::integer).map_err(|_| {
+        ser::Error::custom(format_args!(
+            "integer {v} is out of range for a Lua integer"
+        ))
+    })
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = LuaValue<'static>;
+    type Error = Error;
+
+    type SerializeSeq = SerializeTable;
+    type SerializeTuple = SerializeTable;
+    type SerializeTupleStruct = SerializeTable;
+    type SerializeTupleVariant = SerializeVariant;
+    type SerializeMap = SerializeTable;
+    type SerializeStruct = SerializeTable;
+    type SerializeStructVariant = SerializeVariant;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Error> {
+        Ok(LuaValue::Boolean(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Error> {
+        Ok(LuaValue::integer(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Error> {
+        Ok(LuaValue::integer(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Error> {
+        Ok(LuaValue::integer(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Error> {
+        Ok(LuaValue::integer(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Error> {
+        integer(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Error> {
+        Ok(LuaValue::integer(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Error> {
+        Ok(LuaValue::integer(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Error> {
+        Ok(LuaValue::integer(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Error> {
+        integer(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Error> {
+        integer(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Error> {
+        Ok(LuaValue::float(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Error> {
+        Ok(LuaValue::float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Error> {
+        Ok(LuaValue::String(Cow::Owned(v.to_string().into_bytes())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Error> {
+        Ok(LuaValue::String(Cow::Owned(v.as_bytes().to_vec())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Error> {
+        Ok(LuaValue::String(Cow::Owned(v.to_vec())))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Error> {
+        Ok(LuaValue::Nil)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Error> {
+        Ok(LuaValue::Nil)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Error> {
+        Ok(LuaValue::Nil)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = to_value(value).map_err(|e| e.prepend_path(PathKey::Name(variant.into())))?;
+        Ok(LuaValue::Table(vec![LuaTableEntry::from((variant, value))]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Ok(SerializeTable::new(len.unwrap_or_default()))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        Ok(SerializeTable::new(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Ok(SerializeTable::new(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Ok(SerializeVariant {
+            variant,
+            table: SerializeTable::new(len),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Ok(SerializeTable::new(len.unwrap_or_default()))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Ok(SerializeTable::new(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Ok(SerializeVariant {
+            variant,
+            table: SerializeTable::new(len),
+        })
+    }
+}
+
+/// Builds a [`LuaValue::Table`] from a sequence, map or struct.
+pub(crate) struct SerializeTable {
+    entries: Vec<LuaTableEntry<'static>>,
+
+    /// Key passed to [`SerializeMap::serialize_key()`][ser::SerializeMap::serialize_key], waiting
+    /// for its value.
+    key: Option<LuaValue<'static>>,
+}
+
+impl SerializeTable {
+    fn new(len: usize) -> Self {
+        Self {
+            // Don't trust size hints too much
+            entries: Vec::with_capacity(len.min(1024)),
+            key: None,
+        }
+    }
+
+    /// Adds an implicitly-keyed entry.
+    fn push_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = to_value(value)
+            .map_err(|e| e.prepend_path(PathKey::Integer(self.entries.len() as i64 + 1)))?;
+        self.entries.push(value.into());
+        Ok(())
+    }
+
+    /// Adds a struct field.
+    fn push_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = to_value(value).map_err(|e| e.prepend_path(PathKey::Name(key.into())))?;
+        self.entries.push(LuaTableEntry::from((key, value)));
+        Ok(())
+    }
+
+    fn end(self) -> LuaValue<'static> {
+        LuaValue::Table(self.entries)
+    }
+}
+
+impl ser::SerializeSeq for SerializeTable {
+    type Ok = LuaValue<'static>;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push_value(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(SerializeTable::end(self))
+    }
+}
+
+impl ser::SerializeTuple for SerializeTable {
+    type Ok = LuaValue<'static>;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push_value(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(SerializeTable::end(self))
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeTable {
+    type Ok = LuaValue<'static>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push_value(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(SerializeTable::end(self))
+    }
+}
+
+impl ser::SerializeMap for SerializeTable {
+    type Ok = LuaValue<'static>;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = to_value(key)?;
+        if matches!(key, LuaValue::Nil) {
+            return Err(<Error as ser::Error>::custom("table key can't be nil"));
+        } else if key.is_nan() {
+            return Err(<Error as ser::Error>::custom("table key can't be NaN"));
+        }
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = self.key.take().ok_or_else(|| {
+            <Error as ser::Error>::custom("serialize_value called before serialize_key")
+        })?;
+        let value = to_value(value).map_err(|e| e.prepend_path(PathKey::from(&key)))?;
+        self.entries.push(match key {
+            LuaValue::String(k) => LuaTableEntry::from((k, value)),
+            k => LuaTableEntry::KeyValue(Box::new((k, value))),
+        });
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(SerializeTable::end(self))
+    }
+}
+
+impl ser::SerializeStruct for SerializeTable {
+    type Ok = LuaValue<'static>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push_field(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(SerializeTable::end(self))
+    }
+}
+
+/// Builds a `{Variant = ...}` table for a tuple or struct enum variant.
+pub(crate) struct SerializeVariant {
+    variant: &'static str,
+    table: SerializeTable,
+}
+
+impl SerializeVariant {
+    fn end(self) -> LuaValue<'static> {
+        LuaValue::Table(vec![LuaTableEntry::from((self.variant, self.table.end()))])
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant {
+    type Ok = LuaValue<'static>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let variant = self.variant;
+        self.table
+            .push_value(value)
+            .map_err(|e| e.prepend_path(PathKey::Name(variant.into())))
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(SerializeVariant::end(self))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant {
+    type Ok = LuaValue<'static>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let variant = self.variant;
+        self.table
+            .push_field(key, value)
+            .map_err(|e| e.prepend_path(PathKey::Name(variant.into())))
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(SerializeVariant::end(self))
+    }
+}
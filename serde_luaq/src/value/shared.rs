@@ -0,0 +1,551 @@
+//! De-duplicated, [`Arc`]-shared [`LuaValue`] trees.
+use crate::{
+    error::{append_path, PathKey},
+    LuaNumber, LuaTableEntry, LuaValue,
+};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    hash::{Hash, Hasher},
+    mem::{discriminant, size_of},
+    sync::Arc,
+};
+
+/// An owned, immutable Lua value where identical strings and tables are shared.
+///
+/// This is returned by [`LuaValue::dedup_shared()`]. Cloning a [`SharedLuaValue`] is cheap.
+///
+/// Unlike [`LuaValue`], there are no compact table entry types: implicitly-keyed entries are
+/// always [`SharedTableEntry::Value`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SharedLuaValue {
+    /// `nil`
+    Nil,
+    /// Boolean
+    Boolean(bool),
+    /// Number
+    Number(LuaNumber),
+    /// String, which may contain arbitrary binary data.
+    String(Arc<[u8]>),
+    /// Table
+    Table(Arc<[SharedTableEntry]>),
+}
+
+/// Entry in a [`SharedLuaValue::Table`]. See [`LuaTableEntry`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SharedTableEntry {
+    /// Table entry in the form `[key] = value`.
+    KeyValue(SharedLuaValue, SharedLuaValue),
+    /// Table entry in the form `name = value`.
+    NameValue(Arc<str>, SharedLuaValue),
+    /// Table entry with an implicit key.
+    Value(SharedLuaValue),
+}
+
+/// Counts of values seen by [`LuaValue::dedup_shared_with_stats()`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DedupStats {
+    /// Total number of tables, including the root value.
+    pub tables: usize,
+    /// Number of distinct tables, after de-duplication.
+    pub unique_tables: usize,
+    /// Total number of strings, including [`NameValue`][LuaTableEntry::NameValue] keys.
+    pub strings: usize,
+    /// Number of distinct strings, after de-duplication.
+    pub unique_strings: usize,
+    /// Approximate heap memory used by all strings and tables, in bytes, without de-duplication.
+    ///
+    /// This counts the contents of strings, and the entries of tables (as [`SharedTableEntry`]),
+    /// but not allocator overheads.
+    pub bytes: usize,
+    /// Approximate heap memory used by distinct strings and tables, in bytes, after
+    /// de-duplication. See [`bytes`][Self::bytes].
+    pub unique_bytes: usize,
+}
+
+impl DedupStats {
+    /// Returns the approximate number of bytes of heap memory saved by de-duplication.
+    pub const fn saved_bytes(&self) -> usize {
+        self.bytes.saturating_sub(self.unique_bytes)
+    }
+}
+
+impl LuaValue<'_> {
+    /// Copies this value into a [`SharedLuaValue`], where identical strings and tables are stored
+    /// once and shared with an [`Arc`].
+    ///
+    /// This can significantly reduce memory usage for inputs which repeat the same large tables
+    /// many times.
+    ///
+    /// Values are identical if they have the same type and contents, and tables are identical if
+    /// they have identical entries in the same order, with the same entry types. Floats are
+    /// compared by their bit pattern.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::{lua_value, SharedLuaValue};
+    ///
+    /// let v = lua_value(b"{{hp = 10}, {hp = 10}, {hp = 11}}", 16).unwrap();
+    /// let (shared, stats) = v.dedup_shared_with_stats();
+    /// assert_eq!(v, shared.to_lua_value());
+    /// assert_eq!(4, stats.tables);
+    /// assert_eq!(3, stats.unique_tables);
+    ///
+    /// let SharedLuaValue::Table(rows) = shared else { unreachable!() };
+    /// let [a, b, c] = [0, 1, 2].map(|i| match rows[i].value() {
+    ///     SharedLuaValue::Table(t) => t.clone(),
+    ///     _ => unreachable!(),
+    /// });
+    /// assert!(std::sync::Arc::ptr_eq(&a, &b));
+    /// assert!(!std::sync::Arc::ptr_eq(&a, &c));
+    /// ```
+    pub fn dedup_shared(&self) -> SharedLuaValue {
+        self.dedup_shared_with_stats().0
+    }
+
+    /// Like [`dedup_shared()`][Self::dedup_shared], but also returns [`DedupStats`] describing
+    /// how much was shared.
+    pub fn dedup_shared_with_stats(&self) -> (SharedLuaValue, DedupStats) {
+        let mut interner = Interner::default();
+        let v = interner.value(self);
+        (v, interner.stats)
+    }
+
+    /// Finds tables which are identical to another table in this value, without copying it.
+    ///
+    /// Returns groups of paths (like [`iter_paths()`][Self::iter_paths]) to identical tables.
+    /// Each group has at least two paths. Tables are compared like
+    /// [`dedup_shared()`][Self::dedup_shared], and tables used as keys are not reported.
+    ///
+    /// Only the outermost duplicates are reported: if two tables are identical, the tables inside
+    /// of them are not reported separately.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use serde_luaq::lua_value;
+    ///
+    /// let v = lua_value(
+    ///     b"{slots = {{item = {id = 1}}, {item = {id = 1}}, {item = {id = 2}}}, default = {id = 2}}",
+    ///     16,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(
+    ///     vec![
+    ///         vec!["slots[1]".to_string(), "slots[2]".to_string()],
+    ///         vec!["slots[3].item".to_string(), "default".to_string()],
+    ///     ],
+    ///     v.duplicate_tables(),
+    /// );
+    /// ```
+    pub fn duplicate_tables(&self) -> Vec<Vec<String>> {
+        let mut interner = Interner {
+            paths: Some(TablePaths::default()),
+            ..Default::default()
+        };
+        interner.value(self);
+        let Some(TablePaths {
+            order, mut paths, ..
+        }) = interner.paths
+        else {
+            unreachable!();
+        };
+
+        // Paths of every duplicated table, to find nested duplicates.
+        let duplicated: HashSet<String> = paths
+            .values()
+            .filter(|p| p.len() > 1)
+            .flatten()
+            .cloned()
+            .collect();
+        let nested = |path: &str| {
+            path.char_indices()
+                .filter(|(_, c)| *c == '.' || *c == '[')
+                .any(|(i, _)| duplicated.contains(&path[..i]))
+        };
+
+        order
+            .into_iter()
+            .filter_map(|t| paths.remove(&t))
+            .map(|mut g| {
+                g.retain(|p| !nested(p));
+                g
+            })
+            .filter(|g| g.len() > 1)
+            .collect()
+    }
+}
+
+impl SharedLuaValue {
+    /// Copies this value into a [`LuaValue`].
+    ///
+    /// Implicitly-keyed entries are converted to the most compact [`LuaTableEntry`] type.
+    pub fn to_lua_value(&self) -> LuaValue<'static> {
+        match self {
+            Self::Nil => LuaValue::Nil,
+            Self::Boolean(b) => LuaValue::Boolean(*b),
+            Self::Number(n) => LuaValue::Number(*n),
+            Self::String(s) => LuaValue::String(Cow::Owned(s.to_vec())),
+            Self::Table(t) => LuaValue::Table(t.iter().map(SharedTableEntry::to_entry).collect()),
+        }
+    }
+}
+
+impl SharedTableEntry {
+    /// Returns the value of the entry.
+    pub const fn value(&self) -> &SharedLuaValue {
+        match self {
+            Self::KeyValue(_, v) | Self::NameValue(_, v) | Self::Value(v) => v,
+        }
+    }
+
+    fn to_entry(&self) -> LuaTableEntry<'static> {
+        match self {
+            Self::KeyValue(k, v) => {
+                LuaTableEntry::KeyValue(Box::new((k.to_lua_value(), v.to_lua_value())))
+            }
+            Self::NameValue(k, v) => {
+                LuaTableEntry::NameValue(Box::new((Cow::Owned(k.to_string()), v.to_lua_value())))
+            }
+            Self::Value(v) => LuaTableEntry::from(v.to_lua_value()),
+        }
+    }
+}
+
+/// Interns strings and tables while building a [`SharedLuaValue`].
+#[derive(Default)]
+struct Interner {
+    strings: HashSet<Arc<[u8]>>,
+    names: HashSet<Arc<str>>,
+    tables: HashSet<InternedTable>,
+    stats: DedupStats,
+    /// Records the paths of tables, for [`LuaValue::duplicate_tables()`].
+    paths: Option<TablePaths>,
+}
+
+/// Paths of interned tables.
+#[derive(Default)]
+struct TablePaths {
+    /// Path of the value currently being interned.
+    current: String,
+    /// Paths of each distinct table, keyed by its address.
+    paths: HashMap<usize, Vec<String>>,
+    /// Distinct tables, in the order they were first interned.
+    order: Vec<usize>,
+}
+
+impl Interner {
+    fn value(&mut self, v: &LuaValue) -> SharedLuaValue {
+        match v {
+            LuaValue::Nil => SharedLuaValue::Nil,
+            LuaValue::Boolean(b) => SharedLuaValue::Boolean(*b),
+            LuaValue::Number(n) => SharedLuaValue::Number(*n),
+            LuaValue::String(s) => SharedLuaValue::String(self.string(s)),
+            LuaValue::Table(t) => SharedLuaValue::Table(self.table(t)),
+        }
+    }
+
+    fn string(&mut self, s: &[u8]) -> Arc<[u8]> {
+        self.stats.strings += 1;
+        self.stats.bytes += s.len();
+        if let Some(s) = self.strings.get(s) {
+            return s.clone();
+        }
+
+        let s: Arc<[u8]> = Arc::from(s);
+        self.strings.insert(s.clone());
+        self.stats.unique_strings += 1;
+        self.stats.unique_bytes += s.len();
+        s
+    }
+
+    fn name(&mut self, s: &str) -> Arc<str> {
+        self.stats.strings += 1;
+        self.stats.bytes += s.len();
+        if let Some(s) = self.names.get(s) {
+            return s.clone();
+        }
+
+        let s: Arc<str> = Arc::from(s);
+        self.names.insert(s.clone());
+        self.stats.unique_strings += 1;
+        self.stats.unique_bytes += s.len();
+        s
+    }
+
+    /// Interns the value of a table entry with key `key`.
+    fn entry_value(&mut self, key: PathKey, v: &LuaValue) -> SharedLuaValue {
+        let Some(paths) = &mut self.paths else {
+            return self.value(v);
+        };
+
+        let path = append_path(&paths.current, &key);
+        let parent = std::mem::replace(&mut paths.current, path);
+        let v = self.value(v);
+        if let Some(paths) = &mut self.paths {
+            paths.current = parent;
+        }
+        v
+    }
+
+    fn table(&mut self, entries: &[LuaTableEntry]) -> Arc<[SharedTableEntry]> {
+        // Children are interned first, so they can be compared by pointer.
+        let mut index = 0;
+        let entries: Arc<[SharedTableEntry]> = entries
+            .iter()
+            .map(|entry| match entry {
+                LuaTableEntry::KeyValue(b) => {
+                    // Keys don't have paths.
+                    let paths = self.paths.take();
+                    let k = self.value(&b.0);
+                    self.paths = paths;
+                    SharedTableEntry::KeyValue(k, self.entry_value(PathKey::from(&b.0), &b.1))
+                }
+                LuaTableEntry::NameValue(b) => {
+                    let key = PathKey::Name(Cow::Borrowed(&b.0));
+                    SharedTableEntry::NameValue(self.name(&b.0), self.entry_value(key, &b.1))
+                }
+                LuaTableEntry::Value(v) => {
+                    index += 1;
+                    SharedTableEntry::Value(self.entry_value(PathKey::Integer(index), v))
+                }
+                LuaTableEntry::NumberValue(n) => {
+                    index += 1;
+                    SharedTableEntry::Value(SharedLuaValue::Number(*n))
+                }
+                LuaTableEntry::BooleanValue(b) => {
+                    index += 1;
+                    SharedTableEntry::Value(SharedLuaValue::Boolean(*b))
+                }
+                LuaTableEntry::NilValue => {
+                    index += 1;
+                    SharedTableEntry::Value(SharedLuaValue::Nil)
+                }
+            })
+            .collect();
+
+        self.stats.tables += 1;
+        let size = entries.len() * size_of::<SharedTableEntry>();
+        self.stats.bytes += size;
+        let entries = match self.tables.get(&InternedTable(entries.clone())) {
+            Some(t) => t.0.clone(),
+            None => {
+                self.tables.insert(InternedTable(entries.clone()));
+                self.stats.unique_tables += 1;
+                self.stats.unique_bytes += size;
+                entries
+            }
+        };
+
+        if let Some(paths) = &mut self.paths {
+            let ptr = Arc::as_ptr(&entries).cast::<u8>() as usize;
+            paths
+                .paths
+                .entry(ptr)
+                .or_insert_with(|| {
+                    paths.order.push(ptr);
+                    Vec::new()
+                })
+                .push(paths.current.clone());
+        }
+
+        entries
+    }
+}
+
+/// A table whose strings and sub-tables have already been interned, so that they can be hashed
+/// and compared by pointer, rather than by walking the whole tree.
+struct InternedTable(Arc<[SharedTableEntry]>);
+
+impl PartialEq for InternedTable {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .zip(other.0.iter())
+                .all(|(a, b)| match (a, b) {
+                    (SharedTableEntry::KeyValue(ak, av), SharedTableEntry::KeyValue(bk, bv)) => {
+                        interned_eq(ak, bk) && interned_eq(av, bv)
+                    }
+                    (SharedTableEntry::NameValue(ak, av), SharedTableEntry::NameValue(bk, bv)) => {
+                        Arc::ptr_eq(ak, bk) && interned_eq(av, bv)
+                    }
+                    (SharedTableEntry::Value(a), SharedTableEntry::Value(b)) => interned_eq(a, b),
+                    _ => false,
+                })
+    }
+}
+
+impl Eq for InternedTable {}
+
+impl Hash for InternedTable {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.len().hash(state);
+        for entry in self.0.iter() {
+            discriminant(entry).hash(state);
+            match entry {
+                SharedTableEntry::KeyValue(k, v) => {
+                    interned_hash(k, state);
+                    interned_hash(v, state);
+                }
+                SharedTableEntry::NameValue(k, v) => {
+                    Arc::as_ptr(k).cast::<u8>().hash(state);
+                    interned_hash(v, state);
+                }
+                SharedTableEntry::Value(v) => interned_hash(v, state),
+            }
+        }
+    }
+}
+
+fn interned_eq(a: &SharedLuaValue, b: &SharedLuaValue) -> bool {
+    match (a, b) {
+        (SharedLuaValue::Nil, SharedLuaValue::Nil) => true,
+        (SharedLuaValue::Boolean(a), SharedLuaValue::Boolean(b)) => a == b,
+        (
+            SharedLuaValue::Number(LuaNumber::Integer(a)),
+            SharedLuaValue::Number(LuaNumber::Integer(b)),
+        ) => a == b,
+        (
+            SharedLuaValue::Number(LuaNumber::Float(a)),
+            SharedLuaValue::Number(LuaNumber::Float(b)),
+        ) => a.to_bits() == b.to_bits(),
+        (SharedLuaValue::String(a), SharedLuaValue::String(b)) => Arc::ptr_eq(a, b),
+        (SharedLuaValue::Table(a), SharedLuaValue::Table(b)) => Arc::ptr_eq(a, b),
+        _ => false,
+    }
+}
+
+fn interned_hash<H: Hasher>(v: &SharedLuaValue, state: &mut H) {
+    discriminant(v).hash(state);
+    match v {
+        SharedLuaValue::Nil => (),
+        SharedLuaValue::Boolean(b) => b.hash(state),
+        SharedLuaValue::Number(LuaNumber::Integer(i)) => i.hash(state),
+        SharedLuaValue::Number(LuaNumber::Float(f)) => f.to_bits().hash(state),
+        SharedLuaValue::String(s) => Arc::as_ptr(s).cast::<u8>().hash(state),
+        SharedLuaValue::Table(t) => Arc::as_ptr(t).cast::<u8>().hash(state),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lua_value;
+
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn table(v: &SharedLuaValue) -> &Arc<[SharedTableEntry]> {
+        match v {
+            SharedLuaValue::Table(t) => t,
+            _ => panic!("expected table, got {v:?}"),
+        }
+    }
+
+    #[test]
+    #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+    fn round_trip() {
+        let v = lua_value(
+            br#"{1, true, nil, "a", 1.5, a = {b = "c"}, [{}] = {}, ["a"] = "a"}"#,
+            16,
+        )
+        .unwrap();
+        assert_eq!(v, v.dedup_shared().to_lua_value());
+
+        assert_eq!(
+            LuaValue::integer(1),
+            LuaValue::integer(1).dedup_shared().to_lua_value()
+        );
+    }
+
+    #[test]
+    #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+    fn shared() {
+        let v = lua_value(
+            br#"{
+                {x = {1, 2}, y = "hello"},
+                {x = {1, 2}, y = "hello"},
+                {x = {1, 2}, y = "world"},
+                {x = {1, 2.0}, y = "hello"},
+                {{1, 2}, "hello"},
+            }"#,
+            16,
+        )
+        .unwrap();
+        let (shared, stats) = v.dedup_shared_with_stats();
+        assert_eq!(v, shared.to_lua_value());
+
+        let rows = table(&shared);
+        let rows: Vec<_> = rows.iter().map(|r| table(r.value())).collect();
+        assert!(Arc::ptr_eq(rows[0], rows[1]));
+        assert!(!Arc::ptr_eq(rows[0], rows[2]));
+        assert!(!Arc::ptr_eq(rows[0], rows[3]));
+        assert!(!Arc::ptr_eq(rows[0], rows[4]));
+
+        // Nested tables are shared between different parents
+        assert!(Arc::ptr_eq(
+            table(rows[0][0].value()),
+            table(rows[2][0].value())
+        ));
+        assert!(Arc::ptr_eq(
+            table(rows[0][0].value()),
+            table(rows[4][0].value())
+        ));
+
+        assert_eq!(
+            DedupStats {
+                tables: 11,
+                unique_tables: 7,
+                strings: 13,
+                unique_strings: 4,
+                bytes: 33 + 25 * size_of::<SharedTableEntry>(),
+                unique_bytes: 12 + 17 * size_of::<SharedTableEntry>(),
+            },
+            stats
+        );
+        assert_eq!(21 + 8 * size_of::<SharedTableEntry>(), stats.saved_bytes());
+    }
+
+    #[test]
+    #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+    fn duplicate_tables() {
+        let v = lua_value(
+            br#"{
+                {x = {1, 2}, y = "hello"},
+                {x = {1, 2}, y = "hello"},
+                {x = {1, 2}, y = "world"},
+                {x = {1, 2.0}, y = "hello"},
+                {{1, 2}, "hello", [{}] = {}, [true] = {1, 2}},
+                a = {b = {{1, 2}}},
+            }"#,
+            16,
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                vec!["[3].x", "[5][1]", "[5][true]", "a.b[1]"],
+                vec!["[1]", "[2]"],
+            ],
+            v.duplicate_tables()
+        );
+
+        // Tables used as keys are compared, but don't have paths
+        assert_eq!(
+            Vec::<Vec<String>>::new(),
+            lua_value(b"{[{}] = 1, {}}", 16).unwrap().duplicate_tables()
+        );
+        assert_eq!(
+            Vec::<Vec<String>>::new(),
+            lua_value(b"{{1}, {2}, 'a', 'a'}", 16)
+                .unwrap()
+                .duplicate_tables()
+        );
+    }
+}
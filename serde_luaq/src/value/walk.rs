@@ -0,0 +1,305 @@
+//! Mutable traversal of [`LuaValue`] trees.
+use crate::{LuaTableEntry, LuaValue};
+
+/// What [`LuaValue::walk()`] should do after visiting a value or entry.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum VisitAction {
+    /// Continue walking, including any children.
+    #[default]
+    Continue,
+
+    /// Continue walking, but skip any children of this value or entry.
+    ///
+    /// This has no effect when returned from an `exit_*` method.
+    SkipChildren,
+
+    /// Remove this value or entry.
+    ///
+    /// Removing a value removes the [`LuaTableEntry`] which contains it. Removing the root value
+    /// replaces it with [`LuaValue::Nil`].
+    ///
+    /// **Note:** removing an [implicitly-keyed entry][LuaTableEntry::Value] changes the implicit
+    /// keys of any implicitly-keyed entries after it, in the same way as [`Vec::remove()`].
+    Remove,
+}
+
+/// Visitor for [`LuaValue::walk()`].
+///
+/// All methods default to [`VisitAction::Continue`] without modifying anything, so visitors only
+/// need to implement the methods they care about.
+///
+/// Each method may modify or replace the value or entry it is given. Changes made in an `enter_*`
+/// method are visible when walking its children.
+pub trait LuaVisitor<'a> {
+    /// Called for every value, before its children (pre-order).
+    ///
+    /// Values include the root value, and the value of every [`LuaTableEntry`] (but not its key).
+    ///
+    /// Compact entries ([`LuaTableEntry::NumberValue`], [`LuaTableEntry::BooleanValue`] and
+    /// [`LuaTableEntry::NilValue`]) are visited as a [`LuaValue`], and converted back to the
+    /// most compact entry type afterwards.
+    fn enter_value(&mut self, value: &mut LuaValue<'a>) -> VisitAction {
+        let _ = value;
+        VisitAction::Continue
+    }
+
+    /// Called for every value, after its children (post-order).
+    fn exit_value(&mut self, value: &mut LuaValue<'a>) -> VisitAction {
+        let _ = value;
+        VisitAction::Continue
+    }
+
+    /// Called for every table entry, before its value is visited (pre-order).
+    ///
+    /// This can be used to inspect or modify keys.
+    fn enter_entry(&mut self, entry: &mut LuaTableEntry<'a>) -> VisitAction {
+        let _ = entry;
+        VisitAction::Continue
+    }
+
+    /// Called for every table entry, after its value is visited (post-order).
+    fn exit_entry(&mut self, entry: &mut LuaTableEntry<'a>) -> VisitAction {
+        let _ = entry;
+        VisitAction::Continue
+    }
+}
+
+impl<'a> LuaValue<'a> {
+    /// Walks this value and all of its children with a [`LuaVisitor`], which may modify, replace
+    /// or [remove][VisitAction::Remove] values and table entries as it goes.
+    ///
+    /// Values and entries are visited depth-first, in the order entries were defined.
+    ///
+    /// ## Example
+    ///
+    /// Redact all `password` fields, and upper-case all other strings:
+    ///
+    /// ```rust
+    /// use serde_luaq::{lua_value, LuaTableEntry, LuaValue, LuaVisitor, VisitAction};
+    ///
+    /// struct Redact;
+    ///
+    /// impl<'a> LuaVisitor<'a> for Redact {
+    ///     fn enter_entry(&mut self, entry: &mut LuaTableEntry<'a>) -> VisitAction {
+    ///         match entry {
+    ///             LuaTableEntry::NameValue(b) if b.0 == "password" => VisitAction::Remove,
+    ///             _ => VisitAction::Continue,
+    ///         }
+    ///     }
+    ///
+    ///     fn enter_value(&mut self, value: &mut LuaValue<'a>) -> VisitAction {
+    ///         if let LuaValue::String(s) = value {
+    ///             s.to_mut().make_ascii_uppercase();
+    ///         }
+    ///         VisitAction::Continue
+    ///     }
+    /// }
+    ///
+    /// let mut v = lua_value(br#"{user = {name = "alice", password = "hunter2"}}"#, 16).unwrap();
+    /// v.walk(&mut Redact);
+    /// assert_eq!(lua_value(br#"{user = {name = "ALICE"}}"#, 16).unwrap(), v);
+    /// ```
+    pub fn walk<V: LuaVisitor<'a> + ?Sized>(&mut self, visitor: &mut V) {
+        if !walk_value(self, visitor) {
+            *self = LuaValue::Nil;
+        }
+    }
+}
+
+/// Walks a value, returning `false` if it should be removed.
+fn walk_value<'a, V: LuaVisitor<'a> + ?Sized>(value: &mut LuaValue<'a>, visitor: &mut V) -> bool {
+    match visitor.enter_value(value) {
+        VisitAction::Remove => return false,
+        VisitAction::SkipChildren => (),
+        VisitAction::Continue => {
+            if let LuaValue::Table(entries) = value {
+                entries.retain_mut(|entry| walk_entry(entry, visitor));
+            }
+        }
+    }
+
+    visitor.exit_value(value) != VisitAction::Remove
+}
+
+/// Walks a table entry, returning `false` if it should be removed.
+fn walk_entry<'a, V: LuaVisitor<'a> + ?Sized>(
+    entry: &mut LuaTableEntry<'a>,
+    visitor: &mut V,
+) -> bool {
+    match visitor.enter_entry(entry) {
+        VisitAction::Remove => return false,
+        VisitAction::SkipChildren => (),
+        VisitAction::Continue => {
+            let keep = match entry {
+                LuaTableEntry::KeyValue(b) => walk_value(&mut b.1, visitor),
+                LuaTableEntry::NameValue(b) => walk_value(&mut b.1, visitor),
+                LuaTableEntry::Value(b) => {
+                    let keep = walk_value(b, visitor);
+                    if keep && !matches!(**b, LuaValue::String(_) | LuaValue::Table(_)) {
+                        // Value was replaced with a compact type
+                        *entry = LuaTableEntry::from(std::mem::replace(&mut **b, LuaValue::Nil));
+                    }
+                    keep
+                }
+                LuaTableEntry::NumberValue(n) => {
+                    let mut v = LuaValue::Number(*n);
+                    let keep = walk_value(&mut v, visitor);
+                    *entry = LuaTableEntry::from(v);
+                    keep
+                }
+                LuaTableEntry::BooleanValue(b) => {
+                    let mut v = LuaValue::Boolean(*b);
+                    let keep = walk_value(&mut v, visitor);
+                    *entry = LuaTableEntry::from(v);
+                    keep
+                }
+                LuaTableEntry::NilValue => {
+                    let mut v = LuaValue::Nil;
+                    let keep = walk_value(&mut v, visitor);
+                    *entry = LuaTableEntry::from(v);
+                    keep
+                }
+            };
+
+            if !keep {
+                return false;
+            }
+        }
+    }
+
+    visitor.exit_entry(entry) != VisitAction::Remove
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{lua_value, LuaNumber};
+
+    /// Records the order values and entries are visited in.
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl<'a> LuaVisitor<'a> for Recorder {
+        fn enter_value(&mut self, value: &mut LuaValue<'a>) -> VisitAction {
+            self.0.push(format!("enter {}", short(value)));
+            VisitAction::Continue
+        }
+
+        fn exit_value(&mut self, value: &mut LuaValue<'a>) -> VisitAction {
+            self.0.push(format!("exit {}", short(value)));
+            VisitAction::Continue
+        }
+
+        fn enter_entry(&mut self, _: &mut LuaTableEntry<'a>) -> VisitAction {
+            self.0.push("enter entry".to_string());
+            VisitAction::Continue
+        }
+
+        fn exit_entry(&mut self, _: &mut LuaTableEntry<'a>) -> VisitAction {
+            self.0.push("exit entry".to_string());
+            VisitAction::Continue
+        }
+    }
+
+    fn short(v: &LuaValue) -> String {
+        match v {
+            LuaValue::Table(_) => "table".to_string(),
+            LuaValue::Number(LuaNumber::Integer(i)) => i.to_string(),
+            LuaValue::String(s) => String::from_utf8_lossy(s).into_owned(),
+            v => format!("{v:?}"),
+        }
+    }
+
+    #[test]
+    fn order() {
+        let mut v = lua_value(b"{1, a = {'b'}}", 16).unwrap();
+        let expected = v.clone();
+        let mut r = Recorder::default();
+        v.walk(&mut r);
+
+        assert_eq!(expected, v);
+        assert_eq!(
+            vec![
+                "enter table",
+                "enter entry",
+                "enter 1",
+                "exit 1",
+                "exit entry",
+                "enter entry",
+                "enter table",
+                "enter entry",
+                "enter b",
+                "exit b",
+                "exit entry",
+                "exit table",
+                "exit entry",
+                "exit table",
+            ],
+            r.0
+        );
+    }
+
+    /// Replaces and removes values.
+    struct Mutator;
+
+    impl<'a> LuaVisitor<'a> for Mutator {
+        fn enter_value(&mut self, value: &mut LuaValue<'a>) -> VisitAction {
+            match value {
+                // Remove nils
+                LuaValue::Nil => VisitAction::Remove,
+                // Replace strings with numbers
+                LuaValue::String(s) if s.as_ref() == b"one" => {
+                    *value = LuaValue::integer(1);
+                    VisitAction::Continue
+                }
+                // Replace numbers with strings
+                LuaValue::Number(LuaNumber::Integer(2)) => {
+                    *value = LuaValue::String(b"two".into());
+                    VisitAction::Continue
+                }
+                // Don't look inside secret tables
+                LuaValue::Table(t) if t.first() == Some(&LuaTableEntry::BooleanValue(true)) => {
+                    VisitAction::SkipChildren
+                }
+                _ => VisitAction::Continue,
+            }
+        }
+
+        fn exit_value(&mut self, value: &mut LuaValue<'a>) -> VisitAction {
+            match value {
+                // Remove tables emptied by walking
+                LuaValue::Table(t) if t.is_empty() => VisitAction::Remove,
+                _ => VisitAction::Continue,
+            }
+        }
+    }
+
+    #[test]
+    fn mutate() {
+        let mut v = lua_value(
+            b"{'one', 2, nil, x = nil, {nil}, {true, nil, 2}, [2] = 'one'}",
+            16,
+        )
+        .unwrap();
+        v.walk(&mut Mutator);
+
+        assert_eq!(
+            LuaValue::Table(vec![
+                LuaTableEntry::NumberValue(LuaNumber::Integer(1)),
+                LuaTableEntry::Value(Box::new(LuaValue::String(b"two".into()))),
+                LuaTableEntry::Value(Box::new(LuaValue::Table(vec![
+                    LuaTableEntry::BooleanValue(true),
+                    LuaTableEntry::NilValue,
+                    LuaTableEntry::NumberValue(LuaNumber::Integer(2)),
+                ]))),
+                LuaTableEntry::KeyValue(Box::new((LuaValue::integer(2), LuaValue::integer(1)))),
+            ]),
+            v
+        );
+
+        // Removing the root
+        let mut v = lua_value(b"{nil}", 16).unwrap();
+        v.walk(&mut Mutator);
+        assert_eq!(LuaValue::Nil, v);
+    }
+}
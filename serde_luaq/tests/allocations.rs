@@ -0,0 +1,309 @@
+//! Allocation counting tests, which check that the parser borrows strings from the input where it
+//! can, and doesn't allocate excess capacity.
+//!
+//! These use a counting global allocator, so live in their own test binary.
+use serde_luaq::{
+    lua_value, script, to_writer, LuaFormat, LuaTableEntry, LuaValue, Parser, TableCapacity,
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    borrow::Cow,
+    cell::Cell,
+    io::sink,
+};
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+wasm_bindgen_test_configure!(run_in_browser);
+
+const MAX_DEPTH: u16 = 16;
+
+type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+thread_local! {
+    /// Number of allocations made by this thread.
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+
+    /// Number of bytes currently allocated by this thread.
+    static LIVE_BYTES: Cell<isize> = const { Cell::new(0) };
+}
+
+/// Counts allocations made by each thread, so that tests can run in parallel.
+struct Counter;
+
+unsafe impl GlobalAlloc for Counter {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ret = unsafe { System.alloc(layout) };
+        if !ret.is_null() {
+            // Ignore allocations during thread teardown, when the counters may be gone.
+            let _ = ALLOCATIONS.try_with(|c| c.set(c.get() + 1));
+            let _ = LIVE_BYTES.try_with(|c| c.set(c.get() + layout.size() as isize));
+        }
+        ret
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        let _ = LIVE_BYTES.try_with(|c| c.set(c.get() - layout.size() as isize));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let ret = unsafe { System.realloc(ptr, layout, new_size) };
+        if !ret.is_null() {
+            let _ = ALLOCATIONS.try_with(|c| c.set(c.get() + 1));
+            let _ = LIVE_BYTES
+                .try_with(|c| c.set(c.get() + new_size as isize - layout.size() as isize));
+        }
+        ret
+    }
+}
+
+#[global_allocator]
+static A: Counter = Counter;
+
+/// Allocations made while running a function.
+#[derive(Debug)]
+struct Stats {
+    /// Number of allocations (and reallocations).
+    allocations: usize,
+
+    /// Number of bytes still allocated when the function returned.
+    live_bytes: usize,
+}
+
+/// Runs `f`, counting the allocations it makes on this thread.
+fn measure<T>(f: impl FnOnce() -> T) -> (T, Stats) {
+    let allocations = ALLOCATIONS.get();
+    let live_bytes = LIVE_BYTES.get();
+    let r = f();
+    let stats = Stats {
+        allocations: ALLOCATIONS.get() - allocations,
+        live_bytes: (LIVE_BYTES.get() - live_bytes) as usize,
+    };
+    (r, stats)
+}
+
+/// Checks that no owned string in `v` has excess capacity, and returns the number of heap
+/// allocations `v` owns.
+///
+/// Tables are built by pushing to a [`Vec`], so may have up to twice as much capacity as they
+/// need.
+fn check_exact(v: &LuaValue<'_>) -> usize {
+    match v {
+        LuaValue::String(Cow::Owned(s)) => {
+            assert_eq!(s.len(), s.capacity(), "excess capacity: {v:?}");
+            usize::from(s.capacity() > 0)
+        }
+        LuaValue::Table(t) => {
+            assert!(
+                t.capacity() <= (t.len() * 2).max(4),
+                "excess capacity: {v:?}"
+            );
+            usize::from(t.capacity() > 0)
+                + t.iter()
+                    .map(|e| match e {
+                        LuaTableEntry::KeyValue(b) => 1 + check_exact(&b.0) + check_exact(&b.1),
+                        LuaTableEntry::NameValue(b) => 1 + check_exact(&b.1),
+                        LuaTableEntry::Value(v) => 1 + check_exact(v),
+                        _ => 0,
+                    })
+                    .sum::<usize>()
+        }
+        _ => 0,
+    }
+}
+
+/// Strings without escape sequences are borrowed from the input, so the only allocations are for
+/// table entries.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn borrowed_strings() -> Result {
+    let entries: Vec<String> = (0..1000)
+        .map(|i| match i % 4 {
+            0 => format!("'string {i}'"),
+            1 => format!("\"string {i}\""),
+            2 => format!("[[long string {i}]]"),
+            _ => format!("k{i} = 'string {i}'"),
+        })
+        .collect();
+    let lua = format!("{{{}}}", entries.join(", "));
+
+    let (v, stats) = measure(|| lua_value(lua.as_bytes(), MAX_DEPTH));
+    let v = v?;
+    let owned = check_exact(&v);
+
+    // One Vec for the table, and a Box for each entry.
+    assert_eq!(1001, owned);
+    assert_eq!(v.heap_size(), stats.live_bytes, "{stats:?}");
+
+    // Short strings use a temporary allocation to collect their parts, and growing the table's Vec
+    // costs a few more, but strings must not be copied.
+    assert!(stats.allocations <= owned + 750 + 32, "{stats:?}");
+
+    let LuaValue::Table(t) = &v else {
+        panic!("expected table");
+    };
+    for e in t {
+        let s = e.value().unwrap();
+        assert!(
+            matches!(s, LuaValue::String(Cow::Borrowed(_))),
+            "not borrowed: {s:?}"
+        );
+    }
+
+    Ok(())
+}
+
+/// Strings with escape sequences need to be copied, but only once, and without excess capacity.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn escaped_strings() -> Result {
+    let entries: Vec<String> = (0..1000)
+        .map(|i| format!(r#""line {i}\n\t\x41\u{{1F600}}\65\z   end""#))
+        .collect();
+    let lua = format!("{{{}}}", entries.join(", "));
+
+    let (v, stats) = measure(|| lua_value(lua.as_bytes(), MAX_DEPTH));
+    let v = v?;
+    let owned = check_exact(&v);
+
+    // One Vec for the table, and a Box and owned string for each entry.
+    assert_eq!(2001, owned);
+    assert_eq!(v.heap_size(), stats.live_bytes, "{stats:?}");
+
+    // Unescaping uses up to 3 temporary allocations per string, to collect its parts.
+    assert!(stats.allocations <= owned + 3 * 1000 + 32, "{stats:?}");
+
+    Ok(())
+}
+
+/// Numbers, booleans and `nil` are stored inline in table entries.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn inline_values() -> Result {
+    let entries: Vec<String> = (0..1000)
+        .map(|i| match i % 4 {
+            0 => format!("{i}"),
+            1 => format!("{i}.5"),
+            2 => "true".to_string(),
+            _ => "nil".to_string(),
+        })
+        .collect();
+    let lua = format!("{{{}}}", entries.join(", "));
+
+    let (v, stats) = measure(|| lua_value(lua.as_bytes(), MAX_DEPTH));
+    let v = v?;
+    assert_eq!(1, check_exact(&v));
+    assert_eq!(v.heap_size(), stats.live_bytes, "{stats:?}");
+    assert!(stats.allocations <= 32, "{stats:?}");
+
+    Ok(())
+}
+
+/// Script statements borrow their names from the input.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn script_statements() -> Result {
+    let lua: String = (0..1000)
+        .map(|i| format!("var{i} = 'value {i}'\n"))
+        .collect();
+
+    let (s, stats) = measure(|| script(lua.as_bytes(), MAX_DEPTH));
+    let s = s?;
+    assert_eq!(1000, s.len());
+    for statement in &s {
+        assert!(matches!(
+            statement.value,
+            LuaValue::String(Cow::Borrowed(_))
+        ));
+    }
+
+    // Only the Vec of statements, and a temporary allocation for each short string.
+    assert!(stats.allocations <= 1000 + 32, "{stats:?}");
+    Ok(())
+}
+
+/// Counting table entries before parsing them avoids reallocating the table.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn table_capacity() -> Result {
+    let entries: Vec<String> = (0..10000)
+        .map(|i| match i % 5 {
+            0 => format!("{i} --[=[ ]] {i}, }} ]=]"),
+            1 => format!("'a, {i}; \\' .. \\'}}'"),
+            2 => format!("[==[ ]] {i}, }}]==]"),
+            3 => format!("{{{i}, {{{i}}}, ',{i}'}}"),
+            _ => format!("k{i} = true -- {i}, }}\n"),
+        })
+        .collect();
+    let lua = format!("{{{};}}", entries.join(", "));
+
+    let grow = Parser::builder().max_depth(MAX_DEPTH).build();
+    let (v, grow_stats) = measure(|| grow.value(lua.as_bytes()));
+    let v = v?;
+
+    let count = Parser::builder()
+        .max_depth(MAX_DEPTH)
+        .table_capacity(TableCapacity::Count)
+        .build();
+    let (counted, count_stats) = measure(|| count.value(lua.as_bytes()));
+    let counted = counted?;
+    assert_eq!(v, counted);
+    assert_eq!(
+        counted.heap_size(),
+        count_stats.live_bytes,
+        "{count_stats:?}"
+    );
+
+    // Every table has exactly the capacity it needs.
+    let LuaValue::Table(t) = &counted else {
+        panic!("expected table");
+    };
+    assert_eq!(10000, t.len());
+    assert_eq!(t.len(), t.capacity());
+    for e in t {
+        if let Some(LuaValue::Table(inner)) = e.value() {
+            assert_eq!(3, inner.capacity());
+            let Some(LuaValue::Table(innermost)) = inner[1].value() else {
+                panic!("expected table");
+            };
+            assert_eq!(1, innermost.capacity());
+        }
+    }
+
+    // Each table is allocated once.
+    assert!(
+        count_stats.allocations < grow_stats.allocations,
+        "{count_stats:?} {grow_stats:?}"
+    );
+    assert!(count_stats.live_bytes < grow_stats.live_bytes);
+
+    Ok(())
+}
+
+/// Writing a value streams it straight to the writer, without copying strings or keys.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn serialize_without_copies() -> Result {
+    let entries: Vec<String> = (0..1000)
+        .map(|i| match i % 6 {
+            0 => format!("'string {i}'"),
+            1 => format!("'line\\n{i}\\0\\255'"),
+            2 => format!("{i}.5e100"),
+            3 => format!("k{i} = {{{i}, true, nil}}"),
+            4 => format!("['key {i}'] = '{i}'"),
+            _ => format!("[{i}.5] = -{i}"),
+        })
+        .collect();
+    let lua = format!("{{{}}}", entries.join(", "));
+    let v = lua_value(lua.as_bytes(), MAX_DEPTH)?;
+
+    for format in [LuaFormat::Value, LuaFormat::Return] {
+        let (r, stats) = measure(|| to_writer(sink(), &v, format));
+        r?;
+        assert_eq!(0, stats.allocations, "{format:?} {stats:?}");
+    }
+
+    Ok(())
+}
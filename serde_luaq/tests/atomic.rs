@@ -0,0 +1,116 @@
+//! Atomic file replacement tests
+mod common;
+use crate::common::MAX_DEPTH;
+use serde::Serialize;
+use serde_luaq::{
+    lua_value, return_statement, write_atomic, Error, Fsync, LuaFormat, WriteAtomicOptions,
+};
+use std::{collections::BTreeMap, fs, path::Path};
+
+type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Serialize)]
+struct Save {
+    name: &'static str,
+    level: i64,
+}
+
+/// Lists the names of files in a directory.
+fn files(dir: &Path) -> Result<Vec<String>> {
+    let mut names = fs::read_dir(dir)?
+        .map(|e| Ok(e?.file_name().to_string_lossy().into_owned()))
+        .collect::<Result<Vec<_>>>()?;
+    names.sort();
+    Ok(names)
+}
+
+/// A new file is created, and an existing file is replaced.
+#[test]
+fn create_and_replace() -> Result {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("save.lua");
+
+    for fsync in [Fsync::None, Fsync::File, Fsync::FileAndDirectory] {
+        for level in 1..3 {
+            let save = Save {
+                name: "alice",
+                level,
+            };
+            write_atomic(&path, &save, &WriteAtomicOptions::new().fsync(fsync))?;
+            assert_eq!(
+                lua_value(
+                    format!("{{name = 'alice', level = {level}}}").as_bytes(),
+                    MAX_DEPTH
+                )?,
+                return_statement(&fs::read(&path)?, MAX_DEPTH)?,
+            );
+        }
+    }
+
+    // No temporary files are left behind
+    assert_eq!(vec!["save.lua"], files(dir.path())?);
+    Ok(())
+}
+
+/// The output is written in the given format.
+#[test]
+fn format() -> Result {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("config.lua");
+    let value = BTreeMap::from([("a", 1), ("b", 2)]);
+
+    write_atomic(&path, &value, &WriteAtomicOptions::new())?;
+    assert_eq!(b"return {a = 1, b = 2}", fs::read(&path)?.as_slice());
+
+    let opts = WriteAtomicOptions::new().format(LuaFormat::Script);
+    write_atomic(&path, &value, &opts)?;
+    assert_eq!(b"a = 1\nb = 2\n", fs::read(&path)?.as_slice());
+    Ok(())
+}
+
+/// A value which can't be serialised leaves the file as it was.
+#[test]
+fn error_keeps_file() -> Result {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("save.lua");
+    fs::write(&path, b"return 1")?;
+
+    // Scripts can only be written from maps
+    let opts = WriteAtomicOptions::new().format(LuaFormat::Script);
+    let err = write_atomic(&path, &[1, 2, 3], &opts).unwrap_err();
+    assert!(!matches!(err, Error::Io { .. }), "{err:?}");
+
+    assert_eq!(b"return 1", fs::read(&path)?.as_slice());
+    assert_eq!(vec!["save.lua"], files(dir.path())?);
+    Ok(())
+}
+
+/// Writing into a directory which doesn't exist is an I/O error.
+#[test]
+fn missing_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("missing").join("save.lua");
+
+    let err = write_atomic(&path, &1, &WriteAtomicOptions::new()).unwrap_err();
+    assert!(
+        matches!(err, Error::Io { kind, .. } if kind == std::io::ErrorKind::NotFound),
+        "{err:?}"
+    );
+}
+
+/// Replacing a file keeps its permissions.
+#[cfg(unix)]
+#[test]
+fn permissions() -> Result {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("save.lua");
+    fs::write(&path, b"return 1")?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o640))?;
+
+    write_atomic(&path, &2, &WriteAtomicOptions::new())?;
+    assert_eq!(b"return 2", fs::read(&path)?.as_slice());
+    assert_eq!(0o640, fs::metadata(&path)?.permissions().mode() & 0o777);
+    Ok(())
+}
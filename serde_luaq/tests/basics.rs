@@ -1,6 +1,11 @@
 mod common;
 use crate::common::{check, MAX_DEPTH};
-use serde_luaq::{script, LuaValue};
+use serde_luaq::{
+    from_slice, lua_value, lua_value_from_chunks, return_statement, return_values, script,
+    script_with_options, CallSubstitutions, Error, FooterCheck, LuaDialect, LuaFormat, LuaNumber,
+    LuaTableEntry, LuaValue, ParseOptions, Parser, ParserBuilder, ScriptStatement, SyntaxError,
+};
+use std::collections::BTreeMap;
 
 #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
 use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
@@ -69,3 +74,1324 @@ fn script_expressions() -> Result {
 
     Ok(())
 }
+
+/// Every kind of line ending (including old Mac OS `\r`) separates statements and table entries.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn line_endings() -> Result {
+    let table = LuaValue::Table(vec![
+        LuaTableEntry::NumberValue(LuaNumber::Integer(1)),
+        LuaTableEntry::NameValue(Box::new(("a".into(), LuaValue::from("b")))),
+    ]);
+    let expected = vec![
+        ("x", LuaValue::integer(4)),
+        ("y", table.clone()),
+        ("z", LuaValue::Boolean(true)),
+    ];
+
+    for eol in ["\n", "\r", "\r\n", "\n\r"] {
+        let s = format!("x = 4{eol}y = {{{eol}  1,{eol}  a = 'b'{eol}}}{eol}z = true{eol}");
+        assert_eq!(expected, script(s.as_bytes(), MAX_DEPTH)?, "{eol:?}");
+
+        // Leading blank lines and semicolons at the end of lines
+        let s = format!("{eol}{eol}x = 4;{eol}y = {{1, a = 'b'}};{eol}z = true;");
+        assert_eq!(expected, script(s.as_bytes(), MAX_DEPTH)?, "{eol:?}");
+
+        let s = format!("{eol}return{eol}{{{eol}  1,{eol}  a = 'b'{eol}}}{eol}");
+        assert_eq!(table, return_statement(s.as_bytes(), MAX_DEPTH)?, "{eol:?}");
+
+        // The first line break in a long string is skipped, and the rest are kept as-is.
+        let s = format!("[[{eol}a{eol}b]]");
+        assert_eq!(
+            LuaValue::from(format!("a{eol}b")),
+            lua_value(s.as_bytes(), MAX_DEPTH)?,
+            "{eol:?}"
+        );
+
+        // Line breaks can't appear in short strings
+        let s = format!("x = 'a{eol}b'");
+        assert!(script(s.as_bytes(), MAX_DEPTH).is_err(), "{eol:?}");
+    }
+
+    // Mixed line endings
+    assert_eq!(
+        expected,
+        script(b"x = 4\ry = {1,\r\na = 'b'}\n\rz = true\n", MAX_DEPTH)?
+    );
+
+    Ok(())
+}
+
+/// Empty and whitespace-only input in each format
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn empty_input() -> Result {
+    let default = Parser::default();
+    let nil = Parser::builder().empty_as_nil(true).build();
+    let limited = Parser::builder().max_statements(1).build();
+
+    for input in [b"".as_slice(), b" ", b"\n", b"\r\n\t \x0b\x0c"] {
+        let msg = input.escape_ascii().to_string();
+        assert!(default.value(input).is_err(), "{msg}");
+        assert!(default.ret(input).is_err(), "{msg}");
+        assert_eq!(
+            Vec::<ScriptStatement>::new(),
+            default.script(input)?,
+            "{msg}"
+        );
+        assert_eq!(
+            Vec::<ScriptStatement>::new(),
+            limited.script(input)?,
+            "{msg}"
+        );
+
+        assert_eq!(LuaValue::Nil, nil.value(input)?, "{msg}");
+        assert_eq!(LuaValue::Nil, nil.ret(input)?, "{msg}");
+        assert_eq!(Vec::<ScriptStatement>::new(), nil.script(input)?, "{msg}");
+    }
+
+    // Only empty input is nil: a bare `return` or `;` is still an error.
+    assert!(nil.ret(b"return").is_err());
+    assert!(nil.value(b";").is_err());
+
+    // `nil` itself is always fine
+    assert_eq!(LuaValue::Nil, default.value(b" nil ")?);
+    assert_eq!(LuaValue::Nil, default.ret(b"return nil")?);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn multiple_return_values() -> Result {
+    assert_eq!(
+        vec![LuaValue::integer(1)],
+        return_values(b"return 1", MAX_DEPTH)?
+    );
+    assert_eq!(
+        vec![
+            LuaValue::integer(1),
+            LuaValue::from("two"),
+            LuaValue::Nil,
+            lua_value(b"{3}", MAX_DEPTH)?,
+        ],
+        return_values(b"return 1, 'two' ,nil,\n{3}\n", MAX_DEPTH)?
+    );
+
+    // A single value is returned as-is, and multiple values as a sequence.
+    assert_eq!(
+        LuaValue::integer(1),
+        return_statement(b"return 1", MAX_DEPTH)?
+    );
+    assert_eq!(
+        lua_value(b"{1, 'two', {3}}", MAX_DEPTH)?,
+        return_statement(b"return 1, 'two', {3}", MAX_DEPTH)?
+    );
+
+    // Multiple values are the same as returning a sequence table, but `return_values()` can tell
+    // them apart.
+    assert_eq!(
+        return_statement(b"return {1, 2}", MAX_DEPTH)?,
+        return_statement(b"return 1, 2", MAX_DEPTH)?
+    );
+    assert_eq!(
+        vec![lua_value(b"{1, 2}", MAX_DEPTH)?],
+        return_values(b"return {1, 2}", MAX_DEPTH)?
+    );
+    assert_eq!(
+        vec![LuaValue::integer(1), LuaValue::integer(2)],
+        return_values(b"return 1, 2", MAX_DEPTH)?
+    );
+    assert_eq!(
+        from_slice::<(i64, i64)>(b"return {1, 2}", LuaFormat::Return, MAX_DEPTH)?,
+        from_slice::<(i64, i64)>(b"return 1, 2", LuaFormat::Return, MAX_DEPTH)?
+    );
+
+    let p = Parser::default();
+    assert_eq!(
+        vec![LuaValue::Boolean(true), LuaValue::Boolean(false)],
+        p.ret_values(b"return true, false")?
+    );
+    assert_eq!(
+        lua_value(b"{true, false}", MAX_DEPTH)?,
+        p.ret(b"return true, false")?
+    );
+
+    let (a, b, c): (i64, String, Option<bool>) =
+        from_slice(b"return 1, 'two', nil", LuaFormat::Return, MAX_DEPTH)?;
+    assert_eq!((1, "two".to_string(), None), (a, b, c));
+
+    // Empty input is still only allowed with `empty_as_nil`.
+    assert!(p.ret_values(b"").is_err());
+    assert_eq!(
+        vec![LuaValue::Nil],
+        Parser::builder()
+            .empty_as_nil(true)
+            .build()
+            .ret_values(b"")?
+    );
+
+    for invalid in [
+        b"return 1,".as_slice(),
+        b"return ,1",
+        b"return 1,,2",
+        b"return 1 2",
+        b"return",
+    ] {
+        assert!(
+            return_values(invalid, MAX_DEPTH).is_err(),
+            "{}",
+            invalid.escape_ascii()
+        );
+        assert!(
+            return_statement(invalid, MAX_DEPTH).is_err(),
+            "{}",
+            invalid.escape_ascii()
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn script_statements() -> Result {
+    let s = script(b"  x = 4 ;\n  y = {1, 2}  ;; x = 'a'\n", MAX_DEPTH)?;
+    assert_eq!(
+        vec![
+            ScriptStatement {
+                name: "x",
+                value: LuaValue::integer(4),
+                span: 2..7,
+                index: 0,
+                local: false,
+                comment: None,
+            },
+            ScriptStatement {
+                name: "y",
+                value: LuaValue::Table(vec![1.into(), 2.into()]),
+                span: 12..22,
+                index: 1,
+                local: false,
+                comment: None,
+            },
+            ScriptStatement {
+                name: "x",
+                value: LuaValue::String(b"a".into()),
+                span: 27..34,
+                index: 2,
+                local: false,
+                comment: None,
+            },
+        ],
+        s
+    );
+
+    // Converting to a table keeps every assignment, in order
+    let v: LuaValue = s.into_iter().collect();
+    assert_eq!(
+        LuaValue::from_iter([
+            ("x", LuaValue::integer(4)),
+            ("y", LuaValue::Table(vec![1.into(), 2.into()])),
+            ("x", LuaValue::String(b"a".into())),
+        ]),
+        v
+    );
+
+    Ok(())
+}
+
+/// Comments before script statements, with `ParseOptions::comments`
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn script_comments() -> Result {
+    let lua = b"-- header\n\n-- a\na = {} -- after a\nb = 2; -- after b\n;\n--[[ c\n]] -- c2\r\n c = 3\nd = 4 --[[ d\n]] e = 5\n-- f\nf = 7\n-- end\n";
+    let expected = vec![
+        ("a", Some(&b"-- header\n\n-- a"[..])),
+        // Comments on the previous statement's line belong to it
+        ("b", None),
+        ("c", Some(b"--[[ c\n]] -- c2")),
+        ("d", None),
+        ("e", None),
+        ("f", Some(b"-- f")),
+    ];
+
+    fn comments<'a>(s: &[ScriptStatement<'a>]) -> Vec<(&'a str, Option<&'a [u8]>)> {
+        s.iter().map(|s| (s.name, s.comment)).collect()
+    }
+
+    let p = Parser::builder().comments(true).build();
+    assert_eq!(expected, comments(&p.script(lua)?));
+    assert_eq!(expected, comments(&p.script_return(lua)?.statements));
+    assert_eq!(
+        expected,
+        comments(
+            &p.script_iter(lua)
+                .collect::<std::result::Result<Vec<_>, _>>()?
+        )
+    );
+
+    // Comments before field assignments aren't captured
+    let p = Parser::builder()
+        .comments(true)
+        .field_assignments(true)
+        .build();
+    let s = p.script(b"a = {}\n-- x\na.x = 1\n-- b\nb = 2")?;
+    assert_eq!(vec![("a", None), ("b", Some(&b"-- b"[..]))], comments(&s));
+
+    // Disabled by default
+    assert!(Parser::default()
+        .script(lua)?
+        .iter()
+        .all(|s| s.comment.is_none()));
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn script_max_statements() -> Result {
+    let lua = b"\na = 1; b = 2\n\nc = 3;\n";
+    let expected = vec![
+        ("a", LuaValue::integer(1)),
+        ("b", LuaValue::integer(2)),
+        ("c", LuaValue::integer(3)),
+    ];
+
+    for max_statements in [None, Some(3), Some(4), Some(usize::MAX)] {
+        let mut opts = ParseOptions::new(MAX_DEPTH);
+        opts.max_statements = max_statements;
+        assert_eq!(expected, script_with_options(lua, &opts)?);
+    }
+
+    for limit in [0, 1, 2] {
+        let opts = Parser::builder()
+            .max_depth(MAX_DEPTH)
+            .max_statements(limit)
+            .build_options();
+        assert_eq!(
+            Err(Error::TooManyStatements { limit }),
+            script_with_options(lua, &opts)
+        );
+    }
+
+    let opts = Parser::builder().max_statements(0).build_options();
+    assert!(script_with_options(b"", &opts)?.is_empty());
+    assert!(script_with_options(b"  \n ;", &opts).is_err());
+
+    // Syntax errors before the limit are still errors
+    let opts = Parser::builder().max_statements(2).build_options();
+    assert!(matches!(
+        script_with_options(b"a = 1 b = ", &opts),
+        Err(Error::Peg(_))
+    ));
+    assert!(matches!(
+        script_with_options(b"a = 1 b", &opts),
+        Err(Error::Peg(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn parser_builder() -> Result {
+    let parser = Parser::builder().max_depth(1).max_statements(2).build();
+    assert_eq!(1, parser.options().max_depth);
+    assert_eq!(Some(2), parser.options().max_statements);
+    assert_eq!(LuaDialect::Lua54, parser.options().dialect);
+
+    // Options are reused across calls
+    for _ in 0..2 {
+        assert_eq!(LuaValue::Table(vec![]), parser.value(b"{}")?);
+        assert!(parser.value(b"{{}}").is_err());
+        assert_eq!(LuaValue::Table(vec![]), parser.ret(b"return {}")?);
+        assert!(parser.ret(b"return {{}}").is_err());
+        assert_eq!(2, parser.script(b"a = 1\nb = 2")?.len());
+        assert_eq!(
+            Error::TooManyStatements { limit: 2 },
+            parser.script(b"a = 1\nb = 2\nc = 3").unwrap_err()
+        );
+    }
+
+    assert_eq!(
+        Parser::from(ParseOptions::new(MAX_DEPTH)),
+        Parser::builder().max_depth(MAX_DEPTH).build()
+    );
+    assert_eq!(Parser::default(), ParserBuilder::default().build());
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn script_iter() -> Result {
+    let lua = b"a = 1\n\nb = {2}; ;\n  c = 'three'  \n";
+    let opts = ParseOptions::new(MAX_DEPTH);
+    let expected = script(lua, MAX_DEPTH)?;
+    let actual = serde_luaq::script_iter(lua, &opts).collect::<std::result::Result<Vec<_>, _>>()?;
+    assert_eq!(expected, actual);
+    assert_eq!(
+        vec![0, 1, 2],
+        actual.iter().map(|s| s.index).collect::<Vec<_>>()
+    );
+
+    // Statements are parsed lazily, so stopping early never sees the syntax error
+    let lua = b"a = 1\nb = 2\nc = {{{";
+    let mut i = serde_luaq::script_iter(lua, &opts);
+    assert_eq!(("a", LuaValue::integer(1)), i.next().unwrap()?);
+    assert_eq!(("b", LuaValue::integer(2)), i.next().unwrap()?);
+    let Some(Err(Error::Peg(_))) = i.next() else {
+        panic!("expected syntax error");
+    };
+    assert!(i.next().is_none());
+
+    // Empty scripts
+    assert!(serde_luaq::script_iter(b"", &opts).next().is_none());
+    assert!(serde_luaq::script_iter(b" \n ", &opts).next().is_none());
+
+    // Statement limits
+    let parser = Parser::builder()
+        .max_depth(MAX_DEPTH)
+        .max_statements(2)
+        .build();
+    assert_eq!(2, parser.script_iter(b"a = 1 b = 2  ").count());
+    let mut i = parser.script_iter(b"a = 1 b = 2 c = {{{");
+    assert!(i.next().unwrap().is_ok());
+    assert!(i.next().unwrap().is_ok());
+    assert_eq!(
+        Error::TooManyStatements { limit: 2 },
+        i.next().unwrap().unwrap_err()
+    );
+    assert!(i.next().is_none());
+
+    // Field assignments aren't supported
+    let parser = Parser::builder()
+        .max_depth(MAX_DEPTH)
+        .field_assignments(true)
+        .build();
+    let mut i = parser.script_iter(b"a = {} a.b = 1");
+    assert!(i.next().unwrap().is_ok());
+    assert!(i.next().unwrap().is_err());
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn owned_values() -> Result {
+    const LUA: &[u8] = b"{'a', b = 'c', ['d\\n'] = {1.5, true, nil, [{}] = 'e'}}";
+    let expected = lua_value(LUA, MAX_DEPTH)?;
+
+    let (copied, owned) = {
+        let input = LUA.to_vec();
+        let v = lua_value(&input, MAX_DEPTH)?;
+        (v.to_static(), v.into_owned())
+    };
+    assert_eq!(expected, copied);
+    assert_eq!(expected, owned);
+
+    let LuaValue::Table(t) = &expected else {
+        panic!("expected table");
+    };
+    for entry in t {
+        assert_eq!(*entry, entry.to_static());
+    }
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn script_entries() -> Result {
+    let lua = b"a = 1\n\nb = {2}; ;\n  c = 'three'  \n";
+    let expected = script(lua, MAX_DEPTH)?;
+    let actual =
+        serde_luaq::script_entries(lua, MAX_DEPTH).collect::<std::result::Result<Vec<_>, _>>()?;
+    assert_eq!(expected, actual);
+
+    // Find one variable, without parsing the rest of the script
+    let lua = b"a = {1, 2}\nb = 'x'\nc = {{{";
+    let b = serde_luaq::script_entries(lua, MAX_DEPTH)
+        .find_map(|s| match s {
+            Ok(("b", v)) => Some(Ok(v)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+        .transpose()?;
+    assert_eq!(Some(LuaValue::from("x")), b);
+
+    let mut i = serde_luaq::script_entries(lua, MAX_DEPTH);
+    assert_eq!(2, i.by_ref().take(2).count());
+    let Some(Err(Error::Peg(_))) = i.next() else {
+        panic!("expected syntax error");
+    };
+    assert!(i.next().is_none());
+
+    // Table depth limits
+    let mut i = serde_luaq::script_entries(b"a = 1 b = {{{}}}", 2);
+    assert_eq!(("a", LuaValue::integer(1)), i.next().unwrap()?);
+    assert!(i.next().unwrap().is_err());
+    assert!(serde_luaq::script_entries(b"", MAX_DEPTH).next().is_none());
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn comments() -> Result {
+    let expected = LuaValue::Table(vec![
+        LuaTableEntry::NameValue(Box::new(("a".into(), LuaValue::integer(1)))),
+        LuaTableEntry::Value(Box::new(LuaValue::from("-- b"))),
+    ]);
+    for lua in [
+        &b"{a = 1, -- comment
+'-- b'}"[..],
+        b"-- first
+-- second
+{a = 1, '-- b' -- trailing
+}",
+        b"{--[[ key ]]a--[[]]=--[[
+value
+]]1,--[==[ ]] ]=] ]==]'-- b'}",
+        b"{a = 1, [[-- b]]} -- no line break",
+        b"{a = 1, '-- b'}--[[ trailing ]]",
+        b"{a = 1, --[ not a long comment ]] '
+'-- b'}",
+        b"{a = 1, ---[[ line comment
+'-- b'}",
+    ] {
+        assert_eq!(
+            expected,
+            lua_value(lua, MAX_DEPTH)?,
+            "{}",
+            lua.escape_ascii()
+        );
+    }
+
+    // Comments are whitespace in scripts and return statements too
+    let s = script(
+        b"-- line comment
+a = 1 --[[ long comment ]]b = 2
+--[==[
+longer
+]==]
+",
+        MAX_DEPTH,
+    )?;
+    assert_eq!(
+        vec![("a", LuaValue::integer(1)), ("b", LuaValue::integer(2))],
+        s
+    );
+    assert_eq!(
+        LuaValue::integer(1),
+        return_statement(
+            b"-- header
+return--[[ one ]]1 -- one",
+            MAX_DEPTH
+        )?
+    );
+    assert_eq!(
+        LuaValue::integer(1),
+        Parser::builder()
+            .strict_q(true)
+            .build()
+            .value(b"1 -- one")?
+    );
+
+    assert_eq!(
+        LuaValue::integer(-1),
+        lua_value(b"- -- comment\n1", MAX_DEPTH)?
+    );
+
+    // Comments aren't allowed inside tokens, and long comments must be closed at the same level
+    for lua in [
+        &b"1--[[ x ]]2"[..],
+        b"{1, --[[ unterminated }",
+        b"{1, --[==[ mismatched ]=] }",
+        b"{1, -- comment }",
+        b"-- only a comment",
+    ] {
+        assert!(lua_value(lua, MAX_DEPTH).is_err(), "{}", lua.escape_ascii());
+    }
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn glua_comments() -> Result {
+    let lua54 = Parser::builder().max_depth(MAX_DEPTH).build();
+    let glua = Parser::builder()
+        .max_depth(MAX_DEPTH)
+        .dialect(LuaDialect::GLua)
+        .build();
+
+    let expected = LuaValue::Table(vec![
+        LuaTableEntry::NameValue(Box::new(("a".into(), LuaValue::integer(1)))),
+        LuaTableEntry::NumberValue(LuaNumber::Integer(2)),
+    ]);
+    for lua in [
+        &b"{a = 1, // comment\n2}"[..],
+        b"{a = 1, // comment\r\n2 // trailing\n}",
+        b"// first\n// second\n{a = 1, 2}",
+        b"{/**/a/* key */=/*\nvalue\n*/1,/*/ still a comment */2}",
+        b"{a = 1, 2}/* trailing */",
+    ] {
+        assert_eq!(expected, glua.value(lua)?, "{}", lua.escape_ascii());
+        assert!(lua54.value(lua).is_err(), "{}", lua.escape_ascii());
+    }
+
+    // Comments are whitespace in scripts and return statements too
+    assert_eq!(
+        vec![("a", LuaValue::integer(1)), ("b", LuaValue::integer(2))],
+        glua.script(b"a = 1 // one\n/* two */ b = 2")?
+    );
+    assert_eq!(LuaValue::integer(1), glua.ret(b"return/* one */1")?);
+
+    // Comments aren't allowed inside tokens, and block comments don't nest
+    assert!(glua.value(b"1/* x */2").is_err());
+    assert!(glua.value(b"{/* unterminated }").is_err());
+    assert!(glua.value(b"{/* /* nested */ */}").is_err());
+
+    // ...and don't start inside strings or numbers
+    assert_eq!(LuaValue::from("a // b"), glua.value(b"'a // b'")?);
+    assert!(glua.value(b"(0/0)")?.is_nan());
+
+    // Lua comments are supported too
+    assert_eq!(
+        LuaValue::Table(vec![LuaTableEntry::NumberValue(LuaNumber::Integer(1))]),
+        glua.value(b"{1, -- comment\n}")?
+    );
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn chunks() -> Result {
+    let lua = b"{1, 'two', three = {[4] = 'five'}}";
+    let expected = lua_value(lua, MAX_DEPTH)?;
+
+    // Every possible split point
+    for i in 0..=lua.len() {
+        let (a, b) = lua.split_at(i);
+        assert_eq!(expected, lua_value_from_chunks([a, b], MAX_DEPTH)?);
+    }
+
+    // One byte at a time
+    assert_eq!(expected, lua_value_from_chunks(lua.chunks(1), MAX_DEPTH)?);
+
+    // Single chunks are borrowed
+    let v = lua_value_from_chunks([b"'hello'".as_slice()], MAX_DEPTH)?;
+    assert!(v.is_borrowed());
+
+    // No chunks is the same as empty input
+    assert!(lua_value_from_chunks([], MAX_DEPTH).is_err());
+    assert!(lua_value_from_chunks([b"{".as_slice(), b"{}"], MAX_DEPTH).is_err());
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn strict_q() -> Result {
+    let strict = Parser::builder().strict_q(true).build();
+    let lenient = Parser::default();
+
+    // Output of Lua 5.4's string.format("%q", ...), and %d / %.17g
+    for lua in [
+        &br#""hello""#[..],
+        br#""""#,
+        b"\"\\0\\1\\0012\\127\\\n\\\\\\\"\xff\"",
+        br#""\r""#,
+        b"123",
+        b"-9223372036854775808",
+        b"0x8000000000000000",
+        b"0x1p+0",
+        b"-0x1.999999999999ap-4",
+        b"0x0p+0",
+        b"1e9999",
+        b"-1e9999",
+        b"0.10000000000000001",
+        b"1.0000000000000001e+20",
+        b"-5e-324",
+        b"{1, -2.5, [\"a\\10\"] = 0x1.8p+1, b = {\"c\"}}",
+    ] {
+        let expected = lenient.value(lua)?;
+        assert_eq!(expected, strict.value(lua)?, "{}", lua.escape_ascii());
+    }
+    assert!(strict.value(b"(0/0)")?.as_f64().unwrap().is_nan());
+
+    for lua in [
+        &b"'single'"[..],
+        b"[[long]]",
+        b"[==[long]==]",
+        br#""\n""#,
+        br#""\t""#,
+        br#""\x41""#,
+        br#""\u{41}""#,
+        br#""\z  ""#,
+        br#""\'""#,
+        b"\"\t\"",
+        b"\"\x7f\"",
+        b"\"\\\r\"",
+        b"0xff",
+        b"0X1p+0",
+        b"0x1P+0",
+        b"0x1.8",
+        b"0x1p0",
+        b"0xAp+0",
+        b"1.",
+        b".5",
+        b"1E+5",
+        b"1e5",
+        b"{'a'}",
+        b"{[0xff] = 1}",
+    ] {
+        assert!(lenient.value(lua).is_ok(), "{}", lua.escape_ascii());
+        assert!(strict.value(lua).is_err(), "{}", lua.escape_ascii());
+    }
+
+    assert!(strict.value(br#""\256""#).is_err());
+
+    assert_eq!(
+        vec![("a", LuaValue::from("b"))],
+        strict.script(b"a = \"b\"")?
+    );
+    assert!(strict.script(b"a = 'b'").is_err());
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn field_assignments() -> Result {
+    let p = Parser::builder().field_assignments(true).build();
+    let lua = b"
+        Config = {}
+        Config.a = 1
+        Config.b = {}
+        Config.b.c = { 'x', 'y' }
+        Config.b.c[2] = 'z'
+        Config.b.c[3.0] = 'w'
+        Config['d'] = true
+        Config.a = 2
+        Other = 3
+        Config[1] = { [true] = 1 }
+        Config[1][true] = 4
+    ";
+
+    let s = p.script(lua)?;
+    assert_eq!(2, s.len());
+    assert_eq!("Config", s[0].name);
+    assert_eq!(0, s[0].index);
+    assert_eq!("Other", s[1].name);
+    assert_eq!(8, s[1].index);
+
+    let expected = lua_value(
+        b"{
+            a = 2,
+            b = { c = { 'x', 'z', [3.0] = 'w' } },
+            d = true,
+            [1] = { [true] = 4 },
+        }",
+        MAX_DEPTH,
+    )?;
+    assert_eq!(expected, s[0].value);
+
+    // Later assignments replace the whole table
+    let s = p.script(b"a = { b = 1 } a.c = 2 a = {} a.d = 3")?;
+    assert_eq!(2, s.len());
+    assert_eq!(lua_value(b"{ b = 1, c = 2 }", MAX_DEPTH)?, s[0].value);
+    assert_eq!(lua_value(b"{ d = 3 }", MAX_DEPTH)?, s[1].value);
+
+    for (lua, path) in [
+        (&b"a.b = 1"[..], "a"),
+        (b"a = 1 a.b = 1", "a"),
+        (b"a = {} a.b.c = 1", "a.b"),
+        (b"a = { b = 'x' } a.b[1] = 1", "a.b"),
+        (b"a = { {} } a[1][2][3] = 1", "a[1][2]"),
+    ] {
+        let Err(Error::IndexNotTable { path: actual }) = p.script(lua) else {
+            panic!("expected error for {}", lua.escape_ascii());
+        };
+        assert_eq!(path, actual, "{}", lua.escape_ascii());
+    }
+
+    // Disabled by default
+    assert!(script(b"a = {} a.b = 1", MAX_DEPTH).is_err());
+    assert!(Parser::default().script(b"a = {} a.b = 1").is_err());
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn local_assignments() -> Result {
+    let p = Parser::builder()
+        .local_assignments(true)
+        .field_assignments(true)
+        .build();
+    let s = p.script(b"local a = 1\nb = 2;local\tc = {}\nc.d = 3")?;
+    assert_eq!(
+        vec![
+            ("a", true, 0..11),
+            ("b", false, 12..17),
+            ("c", true, 18..30)
+        ],
+        s.iter()
+            .map(|s| (s.name, s.local, s.span.clone()))
+            .collect::<Vec<_>>(),
+    );
+    assert_eq!(lua_value(b"{d = 3}", MAX_DEPTH)?, s[2].value);
+
+    // Returning a local variable
+    let r = p.script_return(b"local t = {1, 2}\nreturn t")?;
+    assert_eq!(Some(lua_value(b"{1, 2}", MAX_DEPTH)?), r.value);
+    assert!(r.statements[0].local);
+
+    // Iterating over statements
+    let s = p
+        .script_iter(b"local a = 1 local b = 2")
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    assert_eq!(vec!["a", "b"], s.iter().map(|s| s.name).collect::<Vec<_>>());
+    assert!(s.iter().all(|s| s.local));
+
+    // Statement limits
+    let limited = Parser::builder()
+        .local_assignments(true)
+        .max_statements(1)
+        .build();
+    assert_eq!(
+        Err(Error::TooManyStatements { limit: 1 }),
+        limited.script(b"local a = 1 local b = 2")
+    );
+
+    // Names starting with "local"
+    let s = p.script(b"locala = 1")?;
+    assert_eq!(("locala", false), (s[0].name, s[0].local));
+
+    // Still unsupported
+    for lua in [
+        &b"local a"[..],
+        b"local <const> a = 1",
+        b"local a, b = 1, 2",
+        b"local local = 1",
+        b"local = 1",
+    ] {
+        assert!(p.script(lua).is_err(), "{}", lua.escape_ascii());
+    }
+
+    // Disabled by default
+    assert!(script(b"local a = 1", MAX_DEPTH).is_err());
+    assert!(Parser::default().script(b"local a = 1").is_err());
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn decimal_comma() -> Result {
+    let p = Parser::builder().decimal_comma(true).build();
+
+    for (lua, expected) in [
+        (&b"1,5"[..], 1.5),
+        (b" -1,5 ", -1.5),
+        (b"0,25", 0.25),
+        (b"1,5e3", 1500.),
+        (b"1,5E-1", 0.15),
+    ] {
+        assert_eq!(
+            LuaValue::float(expected),
+            p.value(lua)?,
+            "{}",
+            lua.escape_ascii()
+        );
+        assert!(Parser::default().value(lua).is_err());
+    }
+
+    // Regular numbers are unaffected
+    assert_eq!(LuaValue::integer(1), p.value(b"1")?);
+    assert_eq!(LuaValue::float(1.5), p.value(b"1.5")?);
+
+    // Table entry separators take precedence
+    assert_eq!(lua_value(b"{1, 5}", MAX_DEPTH)?, p.value(b"{1,5}")?);
+    assert_eq!(lua_value(b"{a = 1, 5}", MAX_DEPTH)?, p.value(b"{a = 1,5}")?);
+
+    assert_eq!(LuaValue::float(2.5), p.ret(b"return 2,5")?);
+    assert_eq!(
+        vec![
+            ScriptStatement {
+                name: "a",
+                value: LuaValue::float(1.5),
+                span: 0..7,
+                index: 0,
+                local: false,
+                comment: None,
+            },
+            ScriptStatement {
+                name: "b",
+                value: LuaValue::integer(2),
+                span: 8..13,
+                index: 1,
+                local: false,
+                comment: None,
+            },
+        ],
+        p.script(b"a = 1,5 b = 2")?
+    );
+
+    for lua in [
+        &b"1,"[..],
+        b",5",
+        b"1,,5",
+        b"1, 5",
+        b"1,5,5",
+        b"1,5.5",
+        b"0x1,5",
+    ] {
+        assert!(p.value(lua).is_err(), "{}", lua.escape_ascii());
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn progress() -> Result {
+    use std::ops::ControlFlow;
+
+    let p = Parser::default();
+    let lua: Vec<u8> = (0..1000)
+        .flat_map(|i| format!("a{i} = {{ {i}, 'x' }}\n").into_bytes())
+        .collect();
+
+    let mut calls = Vec::new();
+    let s = p.script_with_progress(&lua, 1000, |progress| {
+        calls.push(progress);
+        ControlFlow::Continue(())
+    })?;
+    assert_eq!(1000, s.len());
+    assert!(calls.len() > 10);
+    assert!(calls
+        .windows(2)
+        .all(|w| w[0].position + 1000 <= w[1].position && w[0].statements <= w[1].statements));
+    assert!(calls
+        .iter()
+        .all(|c| c.len == lua.len() && c.fraction() > 0. && c.fraction() <= 1.));
+    assert!(calls.last().unwrap().statements > 900);
+
+    // Cancellation
+    let mut count = 0;
+    let err = p
+        .script_with_progress(&lua, 1000, |progress| {
+            count += 1;
+            if progress.statements > 100 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .unwrap_err();
+    assert_eq!(Error::Cancelled, err);
+    assert!(count < calls.len());
+
+    // Errors are unaffected
+    let err = p
+        .script_with_progress(b"a = {1, 2", 1, |_| ControlFlow::Continue(()))
+        .unwrap_err();
+    assert!(matches!(err, Error::Peg(_)), "{err:?}");
+
+    let lua = b"return {1, {2, {3}}, 4}";
+    let mut positions = Vec::new();
+    assert_eq!(
+        p.ret(lua)?,
+        p.ret_with_progress(lua, 1, |progress| {
+            positions.push(progress.position);
+            assert_eq!(0, progress.statements);
+            ControlFlow::Continue(())
+        })?
+    );
+    assert_eq!(vec![8, 10, 12, 14, 16, 20], positions);
+    assert_eq!(
+        Error::Cancelled,
+        p.ret_with_progress(lua, 1, |_| ControlFlow::Break(()))
+            .unwrap_err()
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn string_concatenation() -> Result {
+    let p = Parser::builder().string_concatenation(true).build();
+
+    for (lua, expected) in [
+        (&b"'a' .. 'b'"[..], &b"ab"[..]),
+        (b"'a'..\"b\"..[[c]]..[==[d]==]", b"abcd"),
+        (b"'a'\r\n  ..\t'b'", b"ab"),
+        (b"'' .. '\\x41' .. ''", b"A"),
+        (b"'\\n' .. '\\xff'", b"\n\xff"),
+        (b"'' .. ''", b""),
+    ] {
+        assert_eq!(
+            LuaValue::String(expected.into()),
+            p.value(lua)?,
+            "{}",
+            lua.escape_ascii()
+        );
+        assert!(Parser::default().value(lua).is_err());
+    }
+
+    // Large blobs split over many lines
+    let line = "QUJDREVGR0hJSktMTU5PUFFSU1RVVldYWVo=";
+    let lua = vec![format!("[[{line}]]"); 10000].join(" ..\n");
+    let LuaValue::String(s) = p.value(lua.as_bytes())? else {
+        panic!("expected string");
+    };
+    assert_eq!(line.repeat(10000).as_bytes(), s.as_ref());
+
+    // Also in tables, keys and scripts
+    assert_eq!(
+        lua_value(b"{['ab'] = {'cd', x = 'ef'}}", MAX_DEPTH)?,
+        p.value(b"{['a' .. 'b'] = {'c' .. 'd', x = 'e' .. 'f'}}")?
+    );
+    assert_eq!(
+        vec![("a", LuaValue::from("bc"))],
+        p.script(b"a = 'b' .. 'c'")?
+    );
+
+    for lua in [
+        &b"'a' .."[..],
+        b".. 'a'",
+        b"'a' .. 1",
+        b"1 .. 'a'",
+        b"'a' ... 'b'",
+        b"'a' . . 'b'",
+    ] {
+        assert!(p.value(lua).is_err(), "{}", lua.escape_ascii());
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn call_substitutions() -> Result {
+    let mut calls = CallSubstitutions::new();
+    calls.insert("os.time", LuaValue::integer(1700000000));
+    calls.insert("GetVersion", LuaValue::from("1.2.3"));
+    calls.insert("empty_table", LuaValue::Table(vec![]));
+    let p = Parser::builder().calls(calls).build();
+
+    for (lua, expected) in [
+        (&b"os.time()"[..], LuaValue::integer(1700000000)),
+        (b"os . time ( )", LuaValue::integer(1700000000)),
+        (b"GetVersion()", LuaValue::from("1.2.3")),
+        (b"empty_table()", LuaValue::Table(vec![])),
+    ] {
+        assert_eq!(expected, p.value(lua)?, "{}", lua.escape_ascii());
+        assert!(Parser::default().value(lua).is_err());
+    }
+
+    // Also in tables, keys, return statements and scripts
+    assert_eq!(
+        lua_value(b"{[1700000000] = {'1.2.3', x = 1700000000}}", MAX_DEPTH)?,
+        p.value(b"{[os.time()] = {GetVersion(), x = os.time()}}")?
+    );
+    assert_eq!(LuaValue::integer(1700000000), p.ret(b"return os.time()")?);
+    assert_eq!(
+        vec![("a", LuaValue::integer(1700000000))],
+        p.script(b"a = os.time()")?
+    );
+
+    // Unknown calls, arguments and other call syntaxes
+    for lua in [
+        &b"os.clock()"[..],
+        b"time()",
+        b"os.time",
+        b"os.time(1)",
+        b"os.time('a')",
+        b"os.time{}",
+        b"os:time()",
+        b"os.time()()",
+        b"(os.time)()",
+    ] {
+        assert!(p.value(lua).is_err(), "{}", lua.escape_ascii());
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn script_return() -> Result {
+    let p = Parser::default();
+
+    let s = p.script_return(b"seen = {'a', 'b'}\ncount = 2\nreturn seen\n")?;
+    assert_eq!(
+        vec![
+            ("seen", lua_value(b"{'a', 'b'}", MAX_DEPTH)?),
+            ("count", LuaValue::integer(2)),
+        ],
+        s.statements
+    );
+    assert_eq!(Some(lua_value(b"{'a', 'b'}", MAX_DEPTH)?), s.value);
+
+    for (lua, expected) in [
+        // Most recent assignment
+        (&b"a = 1; a = 2; return a;"[..], Some(LuaValue::integer(2))),
+        // Unassigned globals are nil
+        (b"a = 1 return b", Some(LuaValue::Nil)),
+        (
+            b"a = 1 return {x = 1}",
+            Some(lua_value(b"{x = 1}", MAX_DEPTH)?),
+        ),
+        (b"return 'a'", Some(LuaValue::from("a"))),
+        (b"a = 1", None),
+        (b"  ", None),
+    ] {
+        assert_eq!(
+            expected,
+            p.script_return(lua)?.value,
+            "{}",
+            lua.escape_ascii()
+        );
+    }
+
+    // The return statement must be last, and only return one value
+    for lua in [
+        &b"return 1\na = 1"[..],
+        b"return 1; return 2",
+        b"return",
+        b"return a, b",
+        b"a = 1 return a b",
+    ] {
+        assert!(p.script_return(lua).is_err(), "{}", lua.escape_ascii());
+    }
+
+    // Statement limits don't include the return statement
+    let p = Parser::builder().max_statements(1).build();
+    assert_eq!(
+        Some(LuaValue::integer(1)),
+        p.script_return(b"a = 1\nreturn a")?.value
+    );
+    assert_eq!(
+        Error::TooManyStatements { limit: 1 },
+        p.script_return(b"a = 1\nb = 2\nreturn a").unwrap_err()
+    );
+
+    // Serde deserialises the returned value, or all globals
+    assert_eq!(
+        vec!["a".to_string(), "b".to_string()],
+        from_slice::<Vec<String>>(
+            b"seen = {'a', 'b'}\nreturn seen",
+            LuaFormat::ScriptReturn,
+            MAX_DEPTH
+        )?
+    );
+    assert_eq!(
+        BTreeMap::from([("a".to_string(), 1)]),
+        from_slice::<BTreeMap<String, i64>>(b"a = 1", LuaFormat::ScriptReturn, MAX_DEPTH)?
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn footer() -> Result {
+    // Footer is the length of the body
+    let check = FooterCheck::new(|body, footer| {
+        if footer == format!("length: {}", body.len()).as_bytes() {
+            Ok(())
+        } else {
+            Err(format!("length is {}", body.len()))
+        }
+    });
+    let p = Parser::builder().footer(check).build();
+
+    assert_eq!(LuaValue::integer(1), p.value(b"1\n-- length: 2")?);
+    assert_eq!(
+        LuaValue::integer(1),
+        p.ret(b"return 1\r\n-- length: 10\r\n\n")?
+    );
+    assert_eq!(
+        vec![("a", LuaValue::integer(1))],
+        p.script(b"a = 1\n  --length: 8  \n")?
+    );
+    assert_eq!(
+        Some(LuaValue::integer(1)),
+        p.script_return(b"a = 1\nreturn a\n-- length: 15")?.value
+    );
+    assert_eq!(
+        vec![("a", LuaValue::integer(1))],
+        p.script_iter(b"a = 1\n-- length: 6")
+            .collect::<std::result::Result<Vec<_>, _>>()?
+    );
+
+    for (lua, message) in [
+        (&b"1\n-- length: 3"[..], "length is 2"),
+        (b"1\n-- checksum: abcd", "length is 2"),
+    ] {
+        assert_eq!(
+            Error::InvalidFooter {
+                message: message.to_string()
+            },
+            p.value(lua).unwrap_err(),
+            "{}",
+            lua.escape_ascii()
+        );
+    }
+
+    // Only line comments at the end of the input are footers
+    for lua in [
+        &b"1"[..],
+        b"1\n",
+        b"-- length: 0\n1",
+        b"1\n--[[ length: 2 ]]",
+        b"1\n--[==[ length: 2 ]==]",
+        b"1 - - length: 2",
+        b"return 1 -- length: 9",
+    ] {
+        assert_eq!(
+            Error::MissingFooter,
+            p.value(lua).unwrap_err(),
+            "{}",
+            lua.escape_ascii()
+        );
+    }
+    assert_eq!(
+        Error::MissingFooter,
+        p.script_iter(b"a = 1").next().unwrap().unwrap_err()
+    );
+
+    // The footer isn't checked by default, so is only a comment
+    assert_eq!(
+        LuaValue::integer(1),
+        Parser::default().value(b"1\n-- length: 3")?
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn salvage() -> Result {
+    let p = Parser::default();
+
+    for (lua, expected, truncated) in [
+        // Unterminated strings and tables are closed
+        (&b"{1, 2"[..], &b"{1, 2}"[..], 5),
+        (b"{'abc", b"{'abc'}", 5),
+        (b"{{\"a\", {[[b\n", b"{{'a', {'b\\n'}}}", 12),
+        (b"{[==[a]]", b"{'a]]'}", 8),
+        (b"{a = {b = {}}", b"{a = {b = {}}}", 13),
+        // Partial escape sequences are removed
+        (b"{'ab\\", b"{'ab'}", 4),
+        (b"{'ab\\x4", b"{'ab'}", 4),
+        (b"{'ab\\u{10", b"{'ab'}", 4),
+        // Incomplete entries are discarded
+        (b"{1, 2, tr", b"{1, 2}", 6),
+        (b"{1, 2, 3.5e", b"{1, 2}", 6),
+        (b"{1, 2; [", b"{1, 2}", 6),
+        (b"{a = 1, b =", b"{a = 1}", 7),
+        (b"{a = {1, 2}, [\"b", b"{a = {1, 2}}", 12),
+        (b"{a = {1, 2} b", b"{a = {1, 2}}", 11),
+    ] {
+        let s = p.salvage(lua, LuaFormat::Value)?;
+        assert_eq!(
+            lua_value(expected, MAX_DEPTH)?,
+            s.value,
+            "{}",
+            lua.escape_ascii()
+        );
+        assert_eq!(Some(truncated), s.truncated, "{}", lua.escape_ascii());
+    }
+
+    // Scripts discard incomplete statements
+    let s = p.salvage(b"a = 1\nb = {1, 2}\nc =", LuaFormat::Script)?;
+    assert_eq!(lua_value(b"{a = 1, b = {1, 2}}", MAX_DEPTH)?, s.value);
+    assert_eq!(Some(17), s.truncated);
+
+    let s = p.salvage(b"a = 1\nb = {1, 'x", LuaFormat::ScriptReturn)?;
+    assert_eq!(lua_value(b"{a = 1, b = {1, 'x'}}", MAX_DEPTH)?, s.value);
+    assert_eq!(Some(16), s.truncated);
+
+    let s = p.salvage(b"return {1, {2", LuaFormat::Return)?;
+    assert_eq!(lua_value(b"{1, {2}}", MAX_DEPTH)?, s.value);
+
+    // Long comments are closed, and line comments are discarded
+    let s = p.salvage(b"{1, 2 --[==[ three", LuaFormat::Value)?;
+    assert_eq!(lua_value(b"{1, 2}", MAX_DEPTH)?, s.value);
+    assert_eq!(Some(18), s.truncated);
+    let s = p.salvage(b"{1, 2, -- three, four", LuaFormat::Value)?;
+    assert_eq!(lua_value(b"{1, 2}", MAX_DEPTH)?, s.value);
+    assert_eq!(Some(6), s.truncated);
+
+    // GLua comments are closed
+    let p = Parser::builder().dialect(LuaDialect::GLua).build();
+    let s = p.salvage(b"{1, 2 /* three", LuaFormat::Value)?;
+    assert_eq!(lua_value(b"{1, 2}", MAX_DEPTH)?, s.value);
+    assert_eq!(Some(14), s.truncated);
+
+    // Complete input isn't changed
+    let p = Parser::default();
+    let s = p.salvage(b"{1, 2}\n", LuaFormat::Value)?;
+    assert_eq!(lua_value(b"{1, 2}", MAX_DEPTH)?, s.value);
+    assert_eq!(None, s.truncated);
+    assert!(!s.is_truncated());
+
+    // Other errors aren't repaired
+    for lua in [&b"{1 2, 3"[..], b"{1, 2}}", b"", b"{1, }, 2"] {
+        assert!(
+            p.salvage(lua, LuaFormat::Value).is_err(),
+            "{}",
+            lua.escape_ascii()
+        );
+    }
+    let p = Parser::builder().max_depth(1).build();
+    assert!(p.salvage(b"{{1}, 2", LuaFormat::Value).is_err());
+
+    // Truncated input has no footer
+    let p = Parser::builder()
+        .footer(FooterCheck::new(|_, _| Err("bad".to_string())))
+        .build();
+    let s = p.salvage(b"{1, 2", LuaFormat::Value)?;
+    assert_eq!(lua_value(b"{1, 2}", MAX_DEPTH)?, s.value);
+    assert_eq!(
+        Error::InvalidFooter {
+            message: "bad".to_string()
+        },
+        p.salvage(b"{1, 2}\n-- abc", LuaFormat::Value).unwrap_err()
+    );
+
+    Ok(())
+}
+
+/// Syntax errors are displayed in a stable format
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn syntax_errors() -> Result {
+    let p = Parser::default();
+    for (lua, expected) in [
+        (
+            &b"{a = 1"[..],
+            r#"syntax error at byte 6: expected one of ",", ".", ";", "E", "e", "}", digit, whitespace"#,
+        ),
+        (
+            b"{1 2}",
+            r#"syntax error at byte 3: expected one of ",", ";", "}", whitespace"#,
+        ),
+        (
+            b"nil nil",
+            "syntax error at byte 4: expected one of EOF, whitespace",
+        ),
+    ] {
+        let err = p.value(lua).unwrap_err();
+        assert_eq!(expected, err.to_string(), "{}", lua.escape_ascii());
+
+        let syntax = err.syntax_error().unwrap();
+        assert_eq!(expected, syntax.to_string());
+        assert_eq!(
+            syntax,
+            SyntaxError::from(lua_value(lua, MAX_DEPTH).unwrap_err())
+        );
+    }
+
+    assert_eq!(None, Error::Cancelled.syntax_error());
+    Ok(())
+}
@@ -0,0 +1,179 @@
+//! Tests for the compact binary encoding.
+mod common;
+
+use crate::common::MAX_DEPTH;
+use serde_luaq::{
+    from_binary, lua_value, script, to_binary, write_binary, BinaryError, LuaNumber, LuaTableEntry,
+    LuaValue,
+};
+use std::borrow::Cow;
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+wasm_bindgen_test_configure!(run_in_browser);
+
+type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn round_trip() -> Result {
+    for lua in [
+        &b"nil"[..],
+        b"true",
+        b"false",
+        b"0",
+        b"-12345678901",
+        b"0x7fffffffffffffff",
+        b"1.5",
+        b"-0.0",
+        b"1e308",
+        b"(0/0)",
+        b"''",
+        b"'hello\\0\\xff'",
+        b"{}",
+        b"{1, 2.5, true, nil, 'a', {}}",
+        b"{a = 1, ['b c'] = 2, [3] = {x = {y = {}}}, [false] = nil, [1.5] = 'z'}",
+    ] {
+        let value = lua_value(lua, MAX_DEPTH)?;
+        let encoded = to_binary(&value);
+        let decoded = from_binary(&encoded, MAX_DEPTH)?;
+
+        // Compare the encodings, so that NaN and -0.0 round-trip exactly, as do compact entries.
+        assert_eq!(encoded, to_binary(&decoded), "{}", lua.escape_ascii());
+        assert_eq!(format!("{value:?}"), format!("{decoded:?}"));
+
+        let mut written = Vec::new();
+        write_binary(&value, &mut written)?;
+        assert_eq!(encoded, written);
+    }
+
+    // Scripts, converted to a table
+    let value: LuaValue = script(b"a = 1\nb = {'c'}", MAX_DEPTH)?
+        .into_iter()
+        .collect();
+    assert_eq!(value, from_binary(&to_binary(&value), MAX_DEPTH)?);
+
+    Ok(())
+}
+
+/// Strings and names are borrowed from the encoded input.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn borrowed() -> Result {
+    let value = lua_value(b"{name = 'a\\tb', [ [[key]] ] = 'value'}", MAX_DEPTH)?;
+    let encoded = to_binary(&value);
+    let LuaValue::Table(t) = from_binary(&encoded, MAX_DEPTH)? else {
+        panic!("expected table");
+    };
+
+    let LuaTableEntry::NameValue(b) = &t[0] else {
+        panic!("expected NameValue");
+    };
+    assert!(matches!(&b.0, Cow::Borrowed("name")));
+    assert!(matches!(&b.1, LuaValue::String(Cow::Borrowed(b"a\tb"))));
+
+    let LuaTableEntry::KeyValue(b) = &t[1] else {
+        panic!("expected KeyValue");
+    };
+    assert!(matches!(&b.0, LuaValue::String(Cow::Borrowed(b"key"))));
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn format() {
+    let value = LuaValue::Table(vec![
+        LuaTableEntry::NumberValue(LuaNumber::Integer(-2)),
+        LuaTableEntry::NameValue(Box::new(("a".into(), LuaValue::String(b"bc".into())))),
+        LuaTableEntry::BooleanValue(true),
+        LuaTableEntry::NilValue,
+    ]);
+
+    assert_eq!(
+        b"LUAQ\x01\x06\x04\x03\x03\x03\x01\x01a\x05\x02bc\x04\x02\x05".as_slice(),
+        to_binary(&value)
+    );
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn errors() {
+    for (b, expected) in [
+        (&b""[..], BinaryError::UnexpectedEof),
+        (b"LUA", BinaryError::UnexpectedEof),
+        (b"LUAX\x01\x00", BinaryError::InvalidHeader),
+        (b"LUAQ\x02\x00", BinaryError::UnsupportedVersion(2)),
+        (b"LUAQ\x01", BinaryError::UnexpectedEof),
+        (
+            b"LUAQ\x01\x07",
+            BinaryError::InvalidTag {
+                tag: 7,
+                position: 5,
+            },
+        ),
+        (
+            b"LUAQ\x01\x00\x00",
+            BinaryError::TrailingBytes { position: 6 },
+        ),
+        (b"LUAQ\x01\x04\x00\x00", BinaryError::UnexpectedEof),
+        (
+            b"LUAQ\x01\x05\x03ab",
+            BinaryError::InvalidLength { position: 6 },
+        ),
+        (
+            b"LUAQ\x01\x05\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01",
+            BinaryError::InvalidVarint { position: 6 },
+        ),
+        // A table claiming to have a huge number of entries
+        (
+            b"LUAQ\x01\x06\xff\xff\xff\xff\x0f\x00",
+            BinaryError::InvalidLength { position: 6 },
+        ),
+        (
+            b"LUAQ\x01\x06\x01\x06",
+            BinaryError::InvalidTag {
+                tag: 6,
+                position: 7,
+            },
+        ),
+        (
+            b"LUAQ\x01\x06\x01\x01\x01\xff\x00",
+            BinaryError::InvalidUtf8 { position: 9 },
+        ),
+        // NumberValue containing a string
+        (
+            b"LUAQ\x01\x06\x01\x03\x05\x00",
+            BinaryError::InvalidTag {
+                tag: 3,
+                position: 7,
+            },
+        ),
+    ] {
+        assert_eq!(
+            Err(expected),
+            from_binary(b, MAX_DEPTH),
+            "{}",
+            b.escape_ascii()
+        );
+    }
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn max_depth() -> Result {
+    let value = lua_value(b"{{{}}}", MAX_DEPTH)?;
+    let encoded = to_binary(&value);
+    assert_eq!(value, from_binary(&encoded, 3)?);
+    assert_eq!(
+        Err(BinaryError::TooDeeplyNested { position: 11 }),
+        from_binary(&encoded, 2)
+    );
+
+    // Tables in keys count too
+    let value = lua_value(b"{[{}] = 1}", MAX_DEPTH)?;
+    let encoded = to_binary(&value);
+    assert_eq!(value, from_binary(&encoded, 2)?);
+    assert!(from_binary(&encoded, 1).is_err());
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Parallel deserialisation tests
+mod common;
+use crate::common::MAX_DEPTH;
+use serde::Deserialize;
+use serde_luaq::{
+    par_from_files, par_from_slices, BulkError, DeserializeOptions, Error, LuaFormat,
+};
+use std::fs;
+
+type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
+const DEFAULT_OPTS: DeserializeOptions = DeserializeOptions::new();
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Save {
+    name: String,
+    level: i64,
+}
+
+#[test]
+fn slices() {
+    let inputs: Vec<Vec<u8>> = (0..100)
+        .map(|i| format!("return {{name = 'p{i}', level = {i}}}").into_bytes())
+        .chain([b"return {name = 'bad'}".to_vec(), b"return {".to_vec()])
+        .collect();
+
+    let saves = par_from_slices::<_, Save>(&inputs, LuaFormat::Return, MAX_DEPTH, &DEFAULT_OPTS);
+    assert_eq!(102, saves.len());
+
+    // Results are in the same order as the inputs
+    for (i, save) in saves[..100].iter().enumerate() {
+        assert_eq!(
+            &Save {
+                name: format!("p{i}"),
+                level: i as i64,
+            },
+            save.as_ref().unwrap()
+        );
+    }
+
+    assert!(matches!(saves[100], Err(Error::SerdeDeserialize(_))));
+    assert!(matches!(saves[101], Err(Error::Peg(_))));
+}
+
+#[test]
+fn files() -> Result {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("bulk_files");
+    fs::create_dir_all(&dir)?;
+
+    let mut paths = Vec::new();
+    for i in 0..20 {
+        let path = dir.join(format!("{i}.lua"));
+        fs::write(&path, format!("name = 'p{i}'\nlevel = {i}\n"))?;
+        paths.push(path);
+    }
+    paths.push(dir.join("missing.lua"));
+
+    let saves = par_from_files::<_, Save>(&paths, LuaFormat::Script, MAX_DEPTH, &DEFAULT_OPTS);
+    assert_eq!(21, saves.len());
+    for (i, save) in saves[..20].iter().enumerate() {
+        assert_eq!(
+            &Save {
+                name: format!("p{i}"),
+                level: i as i64,
+            },
+            save.as_ref().unwrap()
+        );
+    }
+
+    assert!(matches!(saves[20], Err(BulkError::Io(_))));
+    Ok(())
+}
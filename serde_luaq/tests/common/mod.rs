@@ -1,4 +1,4 @@
-#![allow(dead_code)]
+#![allow(dead_code, clippy::expect_fun_call)]
 
 use serde_luaq::{lua_value, return_statement, script, LuaValue};
 use std::borrow::Borrow;
@@ -25,11 +25,14 @@ pub fn check<'a>(lua: &'_ [u8], expected: impl Borrow<LuaValue<'a>>) {
     s.extend_from_slice(b"a = ");
     s.extend_from_slice(lua);
 
-    let (n, actual) = script(&s, MAX_DEPTH)
+    let statement = script(&s, MAX_DEPTH)
         .expect(&format!("parse error for Lua script: {}", s.escape_ascii()))
         .pop()
         .unwrap();
-    assert_eq!("a", n);
+    assert_eq!("a", statement.name);
+    assert_eq!(0, statement.index);
+    assert_eq!(0..s.len(), statement.span);
+    let actual = statement.value;
 
     if expected.is_nan() {
         assert!(actual.is_nan(), "lua: {}", s.escape_ascii());
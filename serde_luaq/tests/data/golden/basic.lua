@@ -0,0 +1,5 @@
+{
+    name = "Alice",
+    items = { "sword", "shield" },
+    ["gold"] = 123,
+}
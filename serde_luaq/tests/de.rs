@@ -1,9 +1,14 @@
 //! Serde deserialisation tests.
+#![allow(clippy::redundant_static_lifetimes)]
 mod common;
 use crate::common::{check, MAX_DEPTH};
-use serde::Deserialize;
-use serde_luaq::{from_slice, LuaFormat, LuaNumber, LuaTableEntry, LuaValue};
-use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use serde_luaq::{
+    from_slice, from_slice_with_options, from_value, from_value_with_options, lua_value, to_string,
+    CancellationToken, DeserializeOptions, Error, LuaFormat, LuaNumber, LuaTableEntry, LuaValue,
+    Parser, Transforms, UnknownFields,
+};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 
 #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
 use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
@@ -95,6 +100,40 @@ fn btreemap_field() {
     );
 }
 
+/// Deserialise a script which builds a table with field assignments
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn script_field_assignments() -> Result {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Config {
+        foo: i64,
+        bar: Vec<String>,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    struct Saved {
+        config: Config,
+    }
+
+    let lua = b"Config = {}\nConfig.foo = 1\nConfig[\"bar\"] = {'a', 'b'}\n";
+    let p = Parser::builder().field_assignments(true).build();
+    let v: LuaValue = p.script(lua)?.into_iter().collect();
+    assert_eq!(
+        Saved {
+            config: Config {
+                foo: 1,
+                bar: vec!["a".to_string(), "b".to_string()],
+            },
+        },
+        from_value(&v)?
+    );
+
+    // Field assignments need to be enabled
+    assert!(from_slice::<Saved>(lua, LuaFormat::Script, MAX_DEPTH).is_err());
+    Ok(())
+}
+
 /// Deseralise a [`BTreeMap`] directly
 #[test]
 #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
@@ -701,188 +740,1360 @@ fn booleans() -> Result {
     Ok(())
 }
 
-/// Tests for Serde's field naming
+/// Booleans written as integers or strings, with [`DeserializeOptions`].
 #[test]
 #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
-fn field_naming() -> Result {
+fn booleans_lenient() -> Result {
     #[derive(Deserialize, PartialEq, Debug)]
-    struct FieldNaming {
-        foo: i64,
-
-        #[serde(rename = "1")]
-        one: i64,
-
-        snake_case: i64,
+    struct Booleans {
+        a: bool,
+        b: Option<bool>,
+        c: Vec<bool>,
     }
 
-    let expected = FieldNaming {
-        foo: 1,
-        one: 2,
-        snake_case: 3,
+    let int_opts = DeserializeOptions::new().lenient_bool(true);
+    let str_opts = DeserializeOptions::new().lenient_bool_strings(true);
+    let both_opts = DeserializeOptions::new()
+        .lenient_bool(true)
+        .lenient_bool_strings(true);
+
+    let expected = Booleans {
+        a: true,
+        b: Some(false),
+        c: vec![true, false, true],
     };
 
+    // Regular booleans still work
+    let lua = b"{a = true, b = false, c = {true, false, true}}";
+    assert_eq!(expected, from_slice(lua, LuaFormat::Value, MAX_DEPTH)?);
+    for opts in [&int_opts, &str_opts, &both_opts] {
+        assert_eq!(
+            expected,
+            from_slice_with_options(lua, LuaFormat::Value, MAX_DEPTH, opts)?
+        );
+    }
+
+    // Integers
+    let lua = b"{a = 1, b = 0, c = {1, 0, true}}";
+    assert!(from_slice::<Booleans>(lua, LuaFormat::Value, MAX_DEPTH).is_err());
+    assert!(
+        from_slice_with_options::<Booleans>(lua, LuaFormat::Value, MAX_DEPTH, &str_opts).is_err()
+    );
     assert_eq!(
         expected,
-        from_slice(
-            b"{['foo'] = 1, ['1'] = 2, snake_case = 3}",
-            LuaFormat::Value,
+        from_slice_with_options(lua, LuaFormat::Value, MAX_DEPTH, &int_opts)?
+    );
+    assert_eq!(
+        expected,
+        from_slice_with_options(lua, LuaFormat::Value, MAX_DEPTH, &both_opts)?
+    );
+    assert_eq!(
+        expected,
+        from_slice_with_options(
+            b"a = 1\nb = 0\nc = {[1] = 1, [3] = 1, [2] = 0}\n",
+            LuaFormat::Script,
             MAX_DEPTH,
+            &int_opts
         )?
     );
 
-    #[derive(Deserialize, PartialEq, Debug)]
-    struct UnicodeFields {
-        français: i16,
-        español: i16,
-        māori: i16,
-    }
+    // Strings
+    let lua = b"{a = 'true', b = 'false', c = {'true', false, \"true\"}}";
+    assert!(from_slice::<Booleans>(lua, LuaFormat::Value, MAX_DEPTH).is_err());
+    assert!(
+        from_slice_with_options::<Booleans>(lua, LuaFormat::Value, MAX_DEPTH, &int_opts).is_err()
+    );
+    assert_eq!(
+        expected,
+        from_slice_with_options(lua, LuaFormat::Value, MAX_DEPTH, &str_opts)?
+    );
+    assert_eq!(
+        expected,
+        from_slice_with_options(lua, LuaFormat::Value, MAX_DEPTH, &both_opts)?
+    );
 
-    let expected = UnicodeFields {
-        français: 1,
-        español: 2,
-        māori: 3,
-    };
+    // Mixed
+    let lua = b"{a = 'true', b = 0, c = {1, 'false', true}}";
+    assert!(
+        from_slice_with_options::<Booleans>(lua, LuaFormat::Value, MAX_DEPTH, &int_opts).is_err()
+    );
+    assert!(
+        from_slice_with_options::<Booleans>(lua, LuaFormat::Value, MAX_DEPTH, &str_opts).is_err()
+    );
+    assert_eq!(
+        expected,
+        from_slice_with_options(lua, LuaFormat::Value, MAX_DEPTH, &both_opts)?
+    );
+
+    // Other values are still rejected
+    for lua in [
+        b"{a = 2, c = {}}".as_slice(),
+        b"{a = -1, c = {}}",
+        b"{a = 1.0, c = {}}",
+        b"{a = 0x1p0, c = {}}",
+        b"{a = 'TRUE', c = {}}",
+        b"{a = '1', c = {}}",
+        b"{a = '', c = {}}",
+        b"{a = {}, c = {}}",
+        b"{a = true, c = {2}}",
+    ] {
+        assert!(
+            from_slice_with_options::<Booleans>(lua, LuaFormat::Value, MAX_DEPTH, &both_opts)
+                .is_err(),
+            "{}",
+            lua.escape_ascii()
+        );
+    }
 
+    // Map keys
+    let expected = BTreeMap::from([(true, 1), (false, 2)]);
     assert_eq!(
         expected,
-        from_slice(
-            "{['français'] = 1, [\"español\"] = 2, [ [[māori]]] = 3}".as_bytes(),
+        from_slice_with_options(
+            b"{[1] = 1, ['false'] = 2}",
             LuaFormat::Value,
             MAX_DEPTH,
+            &both_opts
         )?
     );
 
-    assert!(from_slice::<UnicodeFields>(
-        "{français=1, español=2, māori=3}".as_bytes(),
-        LuaFormat::Value,
-        MAX_DEPTH,
-    )
-    .is_err());
-
-    assert!(from_slice::<UnicodeFields>(
-        "français=1\nespañol=2\nmāori=3\n".as_bytes(),
-        LuaFormat::Script,
-        MAX_DEPTH,
-    )
-    .is_err());
-
-    // Numeric key support blocked on https://github.com/serde-rs/serde/issues/2358
-    // This also means we can't use a table with implicitly-keyed entries and map it to a numeric
-    // key.
     Ok(())
 }
 
+/// Deserialisation recursion limit
 #[test]
-fn strings() -> Result {
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn recursion_limit() -> Result {
     #[derive(Deserialize, PartialEq, Debug)]
-    struct Strings {
-        a: u8,
-        #[serde(with = "serde_bytes")]
-        b: Vec<u8>,
-        c: char,
-        d: String,
+    struct Node {
+        children: Vec<Node>,
     }
 
-    let expected = Strings {
-        a: 64,
-        b: b"hello".to_vec(),
-        c: '@',
-        d: "world".to_string(),
-    };
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(untagged)]
+    enum Any {
+        Table(BTreeMap<String, Any>),
+        Seq(Vec<Any>),
+        Int(i64),
+    }
+
+    let opts = DeserializeOptions::new().recursion_limit(Some(4));
 
+    // Each `Node` is two tables deep
+    let lua = b"{children = {{children = {}}}}";
+    let expected = Node {
+        children: vec![Node { children: vec![] }],
+    };
     assert_eq!(
         expected,
-        from_slice(
-            b"{a = 64, b = 'hello', c = '@', d = 'world'}",
-            LuaFormat::Value,
-            MAX_DEPTH,
-        )?
+        from_slice_with_options(lua, LuaFormat::Value, MAX_DEPTH, &opts)?
     );
+
+    let lua = b"{children = {{children = {{children = {}}}}}}";
+    assert!(from_slice::<Node>(lua, LuaFormat::Value, MAX_DEPTH).is_ok());
+    let err = from_slice_with_options::<Node>(lua, LuaFormat::Value, MAX_DEPTH, &opts).unwrap_err();
     assert_eq!(
-        expected,
-        from_slice(
-            br"{a = 0x40, b = {104, 101, 108, 108, 111}, c = '\u{40}', d = '\119\111\114\108\100'}",
-            LuaFormat::Value,
-            MAX_DEPTH,
-        )?
+        &Error::RecursionLimit { limit: 4 },
+        err.without_path(),
+        "{err}"
     );
+    assert_eq!(Some("children[1].children[1]"), err.path());
 
-    let expected = Strings {
-        a: 32,
-        b: vec![0, 1, 2, 3, 4, 5, 0xC0, 0xE0, 0],
-        c: '#',
-        d: "\u{65E5}\u{672C}\u{8A9E}".to_string(),
-    };
+    // Also applies to "any" types
+    let lua = b"{{{{1}}}}";
+    assert!(from_slice_with_options::<Any>(lua, LuaFormat::Value, MAX_DEPTH, &opts).is_ok());
+    let lua = b"{{{{{1}}}}}";
+    assert!(from_slice::<Any>(lua, LuaFormat::Value, MAX_DEPTH).is_ok());
+    let err = from_slice_with_options::<Any>(lua, LuaFormat::Value, MAX_DEPTH, &opts).unwrap_err();
     assert_eq!(
-        expected,
-        from_slice(
-            b"{a = 32, b = '\0\\1\\2\\3\\004\\5\\xC0\\xE0\\0', c = '#', d = '\\u{65E5}\\u{672C}\\u{8A9E}'}",
-            LuaFormat::Value, MAX_DEPTH,
-        )?
+        &Error::RecursionLimit { limit: 4 },
+        err.without_path(),
+        "{err}"
     );
+
+    // Scripts are a table
+    let lua = b"a = {{{1}}}";
+    assert!(from_slice_with_options::<Any>(lua, LuaFormat::Script, MAX_DEPTH, &opts).is_ok());
+    let lua = b"a = {{{{1}}}}";
+    assert!(from_slice_with_options::<Any>(lua, LuaFormat::Script, MAX_DEPTH, &opts).is_err());
+
+    Ok(())
+}
+
+/// `table.pack()`-style sequences with an `n` field
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn sequence_n_field() -> Result {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Test {
+        a: Vec<Option<i64>>,
+        n: i64,
+    }
+
+    let opts = DeserializeOptions::new().sequence_n_field(true);
+
+    for (lua, expected) in [
+        (&b"{n = 3, 1, nil, 3}"[..], vec![Some(1), None, Some(3)]),
+        (b"{n = 3, 1}", vec![Some(1), None, None]),
+        (b"{1, [3] = 3, n = 4}", vec![Some(1), None, Some(3), None]),
+        (b"{['n'] = 2, nil, 2}", vec![None, Some(2)]),
+        (b"{n = 0}", vec![]),
+        (b"{1, 2}", vec![Some(1), Some(2)]),
+    ] {
+        assert_eq!(
+            expected,
+            from_slice_with_options::<Vec<Option<i64>>>(lua, LuaFormat::Value, MAX_DEPTH, &opts)?,
+            "{}",
+            lua.escape_ascii(),
+        );
+        if lua.contains(&b'n') {
+            assert!(from_slice::<Vec<Option<i64>>>(lua, LuaFormat::Value, MAX_DEPTH).is_err());
+        }
+    }
+
+    for lua in [
+        &b"{n = 1, 1, 2}"[..],
+        b"{n = -1}",
+        b"{n = 1.0, 1}",
+        b"{n = '1', 1}",
+        b"{n = 1, x = 1}",
+    ] {
+        assert!(
+            from_slice_with_options::<Vec<Option<i64>>>(lua, LuaFormat::Value, MAX_DEPTH, &opts)
+                .is_err(),
+            "{}",
+            lua.escape_ascii(),
+        );
+    }
+
+    let err = from_slice_with_options::<Test>(
+        b"{a = {n = true}, n = 1}",
+        LuaFormat::Value,
+        MAX_DEPTH,
+        &opts,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("a.n"), "{err}");
+
+    // Structs and maps keep their `n` field
     assert_eq!(
-        expected,
-        from_slice(
-            b"{a = 0x20, b = '\0\x01\x02\x03\x04\x05\xC0\xE0\0', c = '\\35', d = '\xe6\x97\xa5\xe6\x9c\xac\xe8\xaa\x9e'}",
-            LuaFormat::Value, MAX_DEPTH,
+        Test {
+            a: vec![Some(1), None],
+            n: 1
+        },
+        from_slice_with_options(
+            b"{a = {n = 2, 1}, n = 1}",
+            LuaFormat::Value,
+            MAX_DEPTH,
+            &opts
         )?
     );
 
-    // Serde handling byte strings as String
-    let expected = serde_luaq::Error::SerdeDeserialize(
-        "invalid value: byte array, expected UTF8 string".to_string(),
-    );
+    Ok(())
+}
 
-    // RFC 2279 escapes
+/// Huge `n` fields and sparse keys are limited by `max_sequence_padding`, rather than allocating
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn max_sequence_padding() -> Result {
+    // No limit by default
+    assert_eq!(None, DeserializeOptions::new().max_sequence_padding);
+
+    let opts = DeserializeOptions::new()
+        .sequence_n_field(true)
+        .max_sequence_padding(Some(65536));
+
+    for lua in [
+        &b"{n = 100000000000}"[..],
+        b"{n = 100000000000, 1, 2}",
+        b"{[100000000000] = 1}",
+        b"{1, [0x7fffffffffffffff] = 1}",
+    ] {
+        let err =
+            from_slice_with_options::<Vec<Option<i64>>>(lua, LuaFormat::Value, MAX_DEPTH, &opts)
+                .unwrap_err();
+        assert!(
+            err.to_string().contains("max_sequence_padding"),
+            "{}: {err}",
+            lua.escape_ascii(),
+        );
+    }
+
+    let err = from_slice_with_options::<Vec<Option<i64>>>(
+        b"{n = 100000000000}",
+        LuaFormat::Value,
+        MAX_DEPTH,
+        &opts,
+    )
+    .unwrap_err();
+    assert!(err.to_string().starts_with("n:"), "{err}");
+
+    // Padding up to the limit is fine
+    let opts = opts.max_sequence_padding(Some(2));
     assert_eq!(
-        expected,
-        from_slice::<String>(b"'\\u{d800}'", LuaFormat::Value, MAX_DEPTH).unwrap_err(),
+        vec![Some(1), None, None],
+        from_slice_with_options::<Vec<Option<i64>>>(
+            b"{n = 3, 1}",
+            LuaFormat::Value,
+            MAX_DEPTH,
+            &opts
+        )?,
     );
     assert_eq!(
-        expected,
-        from_slice::<String>(b"'\\u{7FFFFFFF}'", LuaFormat::Value, MAX_DEPTH).unwrap_err(),
+        vec![Some(1), None, None, Some(4)],
+        from_slice_with_options::<Vec<Option<i64>>>(
+            b"{1, [4] = 4}",
+            LuaFormat::Value,
+            MAX_DEPTH,
+            &opts
+        )?,
     );
+    for lua in [&b"{n = 4, 1}"[..], b"{1, [5] = 5}", b"{n = 4, [4] = 4}"] {
+        assert!(
+            from_slice_with_options::<Vec<Option<i64>>>(lua, LuaFormat::Value, MAX_DEPTH, &opts)
+                .is_err(),
+            "{}",
+            lua.escape_ascii(),
+        );
+    }
 
-    // Binary data
+    // No limit
+    let opts = opts.max_sequence_padding(None);
     assert_eq!(
-        expected,
-        from_slice::<String>(b"'\xC0\xE0'", LuaFormat::Value, MAX_DEPTH).unwrap_err(),
+        1000,
+        from_slice_with_options::<Vec<Option<i64>>>(
+            b"{n = 1000}",
+            LuaFormat::Value,
+            MAX_DEPTH,
+            &opts
+        )?
+        .len(),
     );
 
-    // Escaped binary data
+    Ok(())
+}
+
+/// Integral float table keys
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn integral_float_keys() -> Result {
+    let opts = DeserializeOptions::new().integral_float_keys(true);
+
+    for (lua, expected) in [
+        (
+            &b"{[1.0] = 'a', [2.0] = 'b'}"[..],
+            vec![Some("a"), Some("b")],
+        ),
+        (b"{[2.0] = 'b', 'a'}", vec![Some("a"), Some("b")]),
+        (b"{'a', [3.0] = 'c'}", vec![Some("a"), None, Some("c")]),
+        (b"{[1] = 'a', [2.0] = 'b'}", vec![Some("a"), Some("b")]),
+        (b"{[0x1p1] = 'b'}", vec![None, Some("b")]),
+    ] {
+        assert_eq!(
+            expected,
+            from_slice_with_options::<Vec<Option<&str>>>(lua, LuaFormat::Value, MAX_DEPTH, &opts)?,
+            "{}",
+            lua.escape_ascii(),
+        );
+        assert!(from_slice::<Vec<Option<&str>>>(lua, LuaFormat::Value, MAX_DEPTH).is_err());
+    }
+
+    for lua in [
+        &b"{[1.5] = 'a'}"[..],
+        b"{[1e100] = 'a'}",
+        b"{[(0/0)] = 'a'}",
+    ] {
+        assert!(
+            from_slice_with_options::<Vec<Option<&str>>>(lua, LuaFormat::Value, MAX_DEPTH, &opts)
+                .is_err(),
+            "{}",
+            lua.escape_ascii(),
+        );
+    }
+
+    // Map keys
+    let lua = b"{[1.0] = 'a', [-2.0] = 'b', [3] = 'c'}";
+    let expected = BTreeMap::from([(1, "a"), (-2, "b"), (3, "c")]);
     assert_eq!(
         expected,
-        from_slice::<String>(b"'\\xC0\\xE0'", LuaFormat::Value, MAX_DEPTH).unwrap_err(),
+        from_slice_with_options::<BTreeMap<i64, &str>>(lua, LuaFormat::Value, MAX_DEPTH, &opts)?
     );
+    assert!(from_slice::<BTreeMap<i64, &str>>(lua, LuaFormat::Value, MAX_DEPTH).is_err());
+
+    // Error paths use the normalised key
+    let err = from_slice_with_options::<BTreeMap<i64, i64>>(
+        b"{[2.0] = 'x'}",
+        LuaFormat::Value,
+        MAX_DEPTH,
+        &opts,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("[2]"), "{err}");
 
     Ok(())
 }
 
+/// Tables with zero or negative integer keys aren't sequences
 #[test]
 #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
-fn arrays() -> Result {
-    let expected = ("hello", "world");
-    let expected_a = ["hello", "world"];
-    let expected_v = vec!["hello", "world"];
-    let expected_m = BTreeMap::from([(1, "hello"), (2, "world")]);
-    for b in [
-        b"{'hello', 'world'}".as_slice(),
-        b"{[1] = 'hello', [2] = 'world'}",
+fn non_positive_keys() -> Result {
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(untagged)]
+    enum Any {
+        Seq(Vec<Option<String>>),
+        Map(BTreeMap<i64, String>),
+    }
+
+    for (lua, expected) in [
+        (
+            &b"{[-1] = 'a', [0] = 'b', [1] = 'c'}"[..],
+            BTreeMap::from([(-1, "a"), (0, "b"), (1, "c")]),
+        ),
+        (b"{[-1] = 'a', 'b'}", BTreeMap::from([(-1, "a"), (1, "b")])),
+        (b"{[0] = 'a'}", BTreeMap::from([(0, "a")])),
+        (b"{[-0x1] = 'a'}", BTreeMap::from([(-1, "a")])),
+        (
+            b"{[-9223372036854775808] = 'a'}",
+            BTreeMap::from([(i64::MIN, "a")]),
+        ),
     ] {
         assert_eq!(
             expected,
-            from_slice(b, LuaFormat::Value, MAX_DEPTH)?,
+            from_slice::<BTreeMap<i64, &str>>(lua, LuaFormat::Value, MAX_DEPTH)?,
             "{}",
-            b.escape_ascii()
+            lua.escape_ascii(),
         );
         assert_eq!(
-            expected_a,
-            from_slice::<[&str; 2]>(b, LuaFormat::Value, MAX_DEPTH)?,
+            Any::Map(expected.iter().map(|(k, v)| (*k, v.to_string())).collect()),
+            from_slice::<Any>(lua, LuaFormat::Value, MAX_DEPTH)?,
             "{}",
-            b.escape_ascii()
+            lua.escape_ascii(),
+        );
+
+        // As a sequence, they're (key, value) pairs
+        let err = from_slice::<Vec<Option<&str>>>(lua, LuaFormat::Value, MAX_DEPTH).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("(key, value) pair of a table which isn't a sequence"),
+            "{}: {err}",
+            lua.escape_ascii(),
         );
         assert_eq!(
-            expected_v,
+            expected.into_iter().collect::<Vec<_>>(),
+            from_slice::<Vec<(i64, &str)>>(lua, LuaFormat::Value, MAX_DEPTH)?,
+        );
+    }
+
+    // Positive keys are still a sequence
+    assert_eq!(
+        Any::Seq(vec![Some("a".into()), None, Some("c".into())]),
+        from_slice::<Any>(b"{'a', [3] = 'c'}", LuaFormat::Value, MAX_DEPTH)?,
+    );
+
+    // Integral float keys are normalised before checking
+    let opts = DeserializeOptions::new().integral_float_keys(true);
+    assert!(from_slice_with_options::<Vec<Option<&str>>>(
+        b"{[-0.0] = 'a'}",
+        LuaFormat::Value,
+        MAX_DEPTH,
+        &opts
+    )
+    .is_err());
+    assert_eq!(
+        BTreeMap::from([(0, "a")]),
+        from_slice_with_options::<BTreeMap<i64, &str>>(
+            b"{[-0.0] = 'a'}",
+            LuaFormat::Value,
+            MAX_DEPTH,
+            &opts
+        )?,
+    );
+
+    Ok(())
+}
+
+/// Deserialise the same [`LuaValue`] many times by reference
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn borrowed_value() -> Result {
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Names<'a> {
+        #[serde(borrow)]
+        names: Vec<&'a str>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Count {
+        count: u8,
+    }
+
+    let v = lua_value(br"{names = {'a', 'b\x63'}, count = 2}", MAX_DEPTH)?;
+    let names = Names::deserialize(&v)?;
+    let count = Count::deserialize(&v)?;
+    assert_eq!(vec!["a", "bc"], names.names);
+    assert_eq!(Count { count: 2 }, count);
+
+    // The value is unchanged
+    assert_eq!(
+        lua_value(br"{names = {'a', 'b\x63'}, count = 2}", MAX_DEPTH)?,
+        v
+    );
+
+    // Errors
+    let err = Count::deserialize(&lua_value(b"{count = 256}", MAX_DEPTH)?).unwrap_err();
+    assert_eq!(Some("count"), err.path());
+
+    Ok(())
+}
+
+/// Deserialise an already-parsed [`LuaValue`] with `from_value()`
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn from_value_ref() -> Result {
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Flags<'a> {
+        enabled: bool,
+        name: &'a str,
+    }
+
+    let v = lua_value(b"{enabled = 1, name = 'x'}", MAX_DEPTH)?;
+    assert!(from_value::<Flags>(&v).is_err());
+
+    let opts = DeserializeOptions::new().lenient_bool(true);
+    assert_eq!(
+        Flags {
+            enabled: true,
+            name: "x"
+        },
+        from_value_with_options(&v, &opts)?
+    );
+
+    // Same result as parsing the text
+    let v = lua_value(b"{a = 1, b = 2}", MAX_DEPTH)?;
+    let from_text: BTreeMap<String, i64> =
+        from_slice(b"{a = 1, b = 2}", LuaFormat::Value, MAX_DEPTH)?;
+    assert_eq!(from_text, from_value::<BTreeMap<String, i64>>(&v)?);
+
+    // Errors include the path
+    let err = from_value::<BTreeMap<String, u8>>(&lua_value(b"{a = 256}", MAX_DEPTH)?).unwrap_err();
+    assert_eq!(Some("a"), err.path());
+
+    Ok(())
+}
+
+/// `from_value()` borrows common tables rather than copying them, and gives the same results as
+/// deserialising an owned value.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn from_value_same_as_owned() -> Result {
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Item {
+        name: String,
+        count: Option<u8>,
+        tags: Vec<String>,
+    }
+
+    fn check_same<T>(lua: &[u8], opts: &DeserializeOptions) -> Result
+    where
+        T: for<'de> Deserialize<'de> + std::fmt::Debug + PartialEq,
+    {
+        let owned = from_slice_with_options::<T>(lua, LuaFormat::Value, MAX_DEPTH, opts);
+        let borrowed = from_value_with_options::<T>(&lua_value(lua, MAX_DEPTH)?, opts);
+        assert_eq!(owned, borrowed, "lua: {}", lua.escape_ascii());
+        Ok(())
+    }
+
+    let opts = DeserializeOptions::new();
+    for lua in [
+        &b"{1, 2, 3}"[..],
+        b"{1, nil, 3}",
+        b"{[2] = 2, 1}",
+        b"{1, 'x'}",
+        b"{}",
+        b"{a = 1}",
+    ] {
+        check_same::<Vec<Option<i64>>>(lua, &opts)?;
+        check_same::<(i64, i64)>(lua, &opts)?;
+        check_same::<BTreeMap<i64, i64>>(lua, &opts)?;
+    }
+
+    for lua in [
+        &b"{a = 1, ['b'] = 2}"[..],
+        b"{a = 'x'}",
+        b"{1, 2}",
+        b"{[true] = 1}",
+    ] {
+        check_same::<BTreeMap<String, i64>>(lua, &opts)?;
+    }
+
+    for lua in [
+        &b"{{name = 'a', count = 1, tags = {'x', 'y'}}, {name = 'b', tags = {}}}"[..],
+        b"{{name = 'a', count = 256, tags = {}}}",
+        b"{{name = 'a', tags = {[1] = 'x', [3] = 'z'}}}",
+        b"{{name = 'a', tags = {1}}}",
+        b"{{NAME = 'a', tags = {}}}",
+    ] {
+        check_same::<Vec<Item>>(lua, &opts)?;
+        check_same::<Vec<Item>>(
+            lua,
+            &DeserializeOptions::new().case_insensitive_fields(true),
+        )?;
+        check_same::<Vec<Item>>(lua, &DeserializeOptions::new().recursion_limit(Some(2)))?;
+    }
+
+    let opts = DeserializeOptions::new().sequence_n_field(true);
+    check_same::<Vec<Option<i64>>>(b"{1, n = 3}", &opts)?;
+    let opts = DeserializeOptions::new().integral_float_keys(true);
+    check_same::<BTreeMap<i64, String>>(b"{[1.0] = 'a', [2] = 'b'}", &opts)?;
+    Ok(())
+}
+
+/// `u64` fields with values greater than `i64::MAX`
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn unsigned_integers() -> Result {
+    let opts = DeserializeOptions::new().unsigned_integers(true);
+
+    for (input, expected) in [
+        (b"18446744073709551615".as_slice(), u64::MAX),
+        (b"18446744073709551614", u64::MAX - 1),
+        (b"9223372036854775808", 1 << 63),
+        (b"9223372036854775807", i64::MAX as u64),
+        (b"0xffffffffffffffff", u64::MAX),
+        (b"-1", u64::MAX),
+        (b"0", 0),
+    ] {
+        assert_eq!(
+            expected,
+            from_slice_with_options::<u64>(input, LuaFormat::Value, MAX_DEPTH, &opts)?,
+            "{}",
+            input.escape_ascii()
+        );
+        assert_eq!(
+            expected as u128,
+            from_slice_with_options::<u128>(input, LuaFormat::Value, MAX_DEPTH, &opts)?,
+            "{}",
+            input.escape_ascii()
+        );
+    }
+
+    // Past u64::MAX, literals are still converted to floats.
+    assert!(from_slice_with_options::<u64>(
+        b"18446744073709551616",
+        LuaFormat::Value,
+        MAX_DEPTH,
+        &opts
+    )
+    .is_err());
+
+    // Signed fields see the wrapped value
+    assert_eq!(
+        -1,
+        from_slice_with_options::<i64>(
+            b"18446744073709551615",
+            LuaFormat::Value,
+            MAX_DEPTH,
+            &opts
+        )?
+    );
+
+    // Hex literals still wrap, so math.mininteger (as written by %q) is unchanged
+    assert_eq!(
+        i64::MIN,
+        from_slice_with_options::<i64>(b"0x8000000000000000", LuaFormat::Value, MAX_DEPTH, &opts)?
+    );
+    assert_eq!(
+        1 << 63,
+        from_slice_with_options::<u64>(b"0x8000000000000000", LuaFormat::Value, MAX_DEPTH, &opts)?
+    );
+
+    // Narrower fields still reject negative numbers
+    assert!(from_slice_with_options::<u32>(b"-1", LuaFormat::Value, MAX_DEPTH, &opts).is_err());
+
+    // Without the option, these are floats or negative
+    assert!(from_slice::<u64>(b"18446744073709551615", LuaFormat::Value, MAX_DEPTH).is_err());
+    assert!(from_slice::<u64>(b"0xffffffffffffffff", LuaFormat::Value, MAX_DEPTH).is_err());
+    assert_eq!(
+        i64::MAX as u64,
+        from_slice::<u64>(b"9223372036854775807", LuaFormat::Value, MAX_DEPTH)?
+    );
+
+    Ok(())
+}
+
+/// Cancelling deserialisation
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn cancellation() -> Result {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Test {
+        a: Vec<BTreeMap<String, i64>>,
+    }
+
+    let lua = b"{a = {{b = 1}, {c = 2}}}";
+    let token = CancellationToken::new();
+    let opts = DeserializeOptions::new().cancellation(Some(token.clone()));
+    assert!(from_slice_with_options::<Test>(lua, LuaFormat::Value, MAX_DEPTH, &opts).is_ok());
+    assert!(!token.is_cancelled());
+
+    token.cancel();
+    assert!(token.is_cancelled());
+    for (lua, format) in [
+        (&b"{a = {1, 2}}"[..], LuaFormat::Value),
+        (b"return {a = {1, 2}}", LuaFormat::Return),
+        (b"a = {1, 2}", LuaFormat::Script),
+    ] {
+        assert_eq!(
+            Error::Cancelled,
+            from_slice_with_options::<BTreeMap<String, Vec<i64>>>(lua, format, MAX_DEPTH, &opts)
+                .unwrap_err(),
+        );
+    }
+
+    // Cancelling from another type's Deserialize impl, to test the deserialiser without parsing
+    let token = CancellationToken::new();
+    let opts = DeserializeOptions::new().cancellation(Some(token.clone()));
+    #[derive(Debug)]
+    struct Cancel;
+    impl<'de> Deserialize<'de> for Cancel {
+        fn deserialize<D: serde::Deserializer<'de>>(d: D) -> std::result::Result<Self, D::Error> {
+            CANCEL.with(|t| t.borrow().as_ref().unwrap().cancel());
+            i64::deserialize(d).map(|_| Cancel)
+        }
+    }
+    thread_local! {
+        static CANCEL: std::cell::RefCell<Option<CancellationToken>> = const {
+            std::cell::RefCell::new(None)
+        };
+    }
+    CANCEL.with(|t| *t.borrow_mut() = Some(token.clone()));
+
+    let err = from_slice_with_options::<BTreeMap<String, Vec<(i64, Cancel)>>>(
+        b"{a = {{1, 1}, {2, 2}}}",
+        LuaFormat::Value,
+        MAX_DEPTH,
+        &opts,
+    )
+    .unwrap_err();
+    // Not wrapped in Error::Path
+    assert_eq!(Error::Cancelled, err);
+
+    // Tokens are only equal to their clones
+    assert_eq!(token, token.clone());
+    assert_ne!(token, CancellationToken::new());
+
+    Ok(())
+}
+
+/// Cancelling deserialisation after a deadline
+#[test]
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn cancellation_deadline() -> Result {
+    use std::time::{Duration, Instant};
+
+    let lua = b"{1, 2, 3}";
+    let opts = DeserializeOptions::new().cancellation(Some(CancellationToken::with_timeout(
+        Duration::from_secs(3600),
+    )));
+    assert_eq!(
+        vec![1, 2, 3],
+        from_slice_with_options::<Vec<i64>>(lua, LuaFormat::Value, MAX_DEPTH, &opts)?
+    );
+
+    let token = CancellationToken::with_deadline(Instant::now());
+    assert!(token.is_cancelled());
+    let opts = DeserializeOptions::new().cancellation(Some(token));
+    assert_eq!(
+        Error::Cancelled,
+        from_slice_with_options::<Vec<i64>>(lua, LuaFormat::Value, MAX_DEPTH, &opts).unwrap_err()
+    );
+
+    Ok(())
+}
+
+/// Tests for Serde's field naming
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn field_naming() -> Result {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct FieldNaming {
+        foo: i64,
+
+        #[serde(rename = "1")]
+        one: i64,
+
+        snake_case: i64,
+    }
+
+    let expected = FieldNaming {
+        foo: 1,
+        one: 2,
+        snake_case: 3,
+    };
+
+    assert_eq!(
+        expected,
+        from_slice(
+            b"{['foo'] = 1, ['1'] = 2, snake_case = 3}",
+            LuaFormat::Value,
+            MAX_DEPTH,
+        )?
+    );
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct UnicodeFields {
+        français: i16,
+        español: i16,
+        māori: i16,
+    }
+
+    let expected = UnicodeFields {
+        français: 1,
+        español: 2,
+        māori: 3,
+    };
+
+    assert_eq!(
+        expected,
+        from_slice(
+            "{['français'] = 1, [\"español\"] = 2, [ [[māori]]] = 3}".as_bytes(),
+            LuaFormat::Value,
+            MAX_DEPTH,
+        )?
+    );
+
+    assert!(from_slice::<UnicodeFields>(
+        "{français=1, español=2, māori=3}".as_bytes(),
+        LuaFormat::Value,
+        MAX_DEPTH,
+    )
+    .is_err());
+
+    assert!(from_slice::<UnicodeFields>(
+        "français=1\nespañol=2\nmāori=3\n".as_bytes(),
+        LuaFormat::Script,
+        MAX_DEPTH,
+    )
+    .is_err());
+
+    // Numeric key support blocked on https://github.com/serde-rs/serde/issues/2358
+    // This also means we can't use a table with implicitly-keyed entries and map it to a numeric
+    // key.
+    Ok(())
+}
+
+/// Tests for `#[serde(rename_all)]`, including names which aren't valid Lua identifiers
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn rename_all() -> Result {
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(rename_all = "camelCase")]
+    struct Camel {
+        max_hp: i64,
+        is_alive: bool,
+    }
+
+    assert_eq!(
+        Camel {
+            max_hp: 10,
+            is_alive: true
+        },
+        from_slice(
+            b"{maxHp = 10, ['isAlive'] = true}",
+            LuaFormat::Value,
+            MAX_DEPTH
+        )?
+    );
+    assert!(from_slice::<Camel>(
+        b"{max_hp = 10, is_alive = true}",
+        LuaFormat::Value,
+        MAX_DEPTH
+    )
+    .is_err());
+
+    // Not valid identifiers, so must use bracketed keys
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(rename_all = "kebab-case")]
+    struct Kebab {
+        max_hp: i64,
+        #[serde(rename = "end")]
+        end_: i64,
+    }
+
+    assert_eq!(
+        Kebab {
+            max_hp: 10,
+            end_: 2
+        },
+        from_slice(
+            b"{['max-hp'] = 10, ['end'] = 2}",
+            LuaFormat::Value,
+            MAX_DEPTH
+        )?
+    );
+    assert!(from_slice::<Kebab>(b"{max-hp = 10, end = 2}", LuaFormat::Value, MAX_DEPTH).is_err());
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+    enum Colour {
+        DarkRed,
+        LightBlue { shade: i64 },
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    struct Paint {
+        colours: Vec<Colour>,
+    }
+
+    assert_eq!(
+        Paint {
+            colours: vec![Colour::DarkRed, Colour::LightBlue { shade: 3 }]
+        },
+        from_slice(
+            b"Colours = {'DARK_RED', {LIGHT_BLUE = {shade = 3}}}",
+            LuaFormat::Script,
+            MAX_DEPTH
+        )?
+    );
+
+    Ok(())
+}
+
+/// Duplicate struct fields are an error, but duplicate map keys are not.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn duplicate_field() -> Result {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Inner {
+        a: i64,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Outer {
+        inner: Vec<Inner>,
+    }
+
+    assert_eq!(
+        Error::DuplicateField {
+            name: "a".to_string(),
+            path: String::new(),
+        },
+        from_slice::<Inner>(b"{a = 1, a = 2}", LuaFormat::Value, MAX_DEPTH).unwrap_err()
+    );
+
+    let e = from_slice::<Outer>(
+        b"{inner = {{a = 1}, {a = 1, ['a'] = 2}}}",
+        LuaFormat::Value,
+        MAX_DEPTH,
+    )
+    .unwrap_err();
+    assert_eq!(
+        Error::DuplicateField {
+            name: "a".to_string(),
+            path: "inner[2]".to_string(),
+        },
+        e
+    );
+    assert_eq!(Some("inner[2]"), e.path());
+    assert_eq!("inner[2]: duplicate field `a`", e.to_string());
+
+    // Maps use the last entry
+    assert_eq!(
+        BTreeMap::from([("a".to_string(), 2)]),
+        from_slice::<BTreeMap<String, i64>>(b"{a = 1, a = 2}", LuaFormat::Value, MAX_DEPTH)?
+    );
+
+    Ok(())
+}
+
+/// Matching struct fields ignoring ASCII case
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn case_insensitive_fields() -> Result {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Stats {
+        #[serde(rename = "MaxHealth")]
+        max_health: i64,
+        #[serde(rename = "maxhealth_bonus", default)]
+        bonus: i64,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    enum Entity {
+        Player { name: String },
+    }
+
+    let opts = DeserializeOptions::new().case_insensitive_fields(true);
+    let expected = Stats {
+        max_health: 10,
+        bonus: 0,
+    };
+
+    for input in [
+        b"{MaxHealth = 10}".as_slice(),
+        b"{maxhealth = 10}",
+        b"{MAXHEALTH = 10}",
+        b"{['maxHEALTH'] = 10}",
+    ] {
+        assert_eq!(
+            expected,
+            from_slice_with_options(input, LuaFormat::Value, MAX_DEPTH, &opts)?,
+            "{}",
+            input.escape_ascii()
+        );
+    }
+
+    // Without the option, only the exact name works.
+    assert!(from_slice::<Stats>(b"{maxhealth = 10}", LuaFormat::Value, MAX_DEPTH).is_err());
+
+    // Exact matches are preferred
+    assert_eq!(
+        Stats {
+            max_health: 10,
+            bonus: 5,
+        },
+        from_slice_with_options(
+            b"{MaxHealth = 10, MAXHEALTH_BONUS = 5}",
+            LuaFormat::Value,
+            MAX_DEPTH,
+            &opts
+        )?
+    );
+
+    // Keys which differ only by case are duplicates
+    assert_eq!(
+        Error::DuplicateField {
+            name: "MaxHealth".to_string(),
+            path: String::new(),
+        },
+        from_slice_with_options::<Stats>(
+            b"{MaxHealth = 10, maxhealth = 11}",
+            LuaFormat::Value,
+            MAX_DEPTH,
+            &opts
+        )
+        .unwrap_err()
+    );
+
+    // Struct variants
+    assert_eq!(
+        Entity::Player {
+            name: "a".to_string()
+        },
+        from_slice_with_options(
+            b"{Player = {NAME = 'a'}}",
+            LuaFormat::Value,
+            MAX_DEPTH,
+            &opts
+        )?
+    );
+
+    // Maps are unaffected
+    assert_eq!(
+        BTreeMap::from([("MAXHEALTH".to_string(), 10)]),
+        from_slice_with_options::<BTreeMap<String, i64>>(
+            b"{MAXHEALTH = 10}",
+            LuaFormat::Value,
+            MAX_DEPTH,
+            &opts
+        )?
+    );
+
+    Ok(())
+}
+
+/// Matching struct fields after Unicode normalisation
+#[cfg(feature = "unicode")]
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn unicode_fields() -> Result {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Names {
+        #[serde(rename = "fran\u{e7}ais")]
+        french: String,
+        #[serde(default)]
+        cafe: i64,
+    }
+
+    let opts = DeserializeOptions::new().unicode_fields(true);
+    let nfc = "{['fran\u{e7}ais'] = 'Bonjour'}";
+    let nfd = "{['franc\u{327}ais'] = 'Bonjour'}";
+    let expected = Names {
+        french: "Bonjour".to_string(),
+        cafe: 0,
+    };
+
+    for input in [nfc, nfd] {
+        assert_eq!(
+            expected,
+            from_slice_with_options(input.as_bytes(), LuaFormat::Value, MAX_DEPTH, &opts)?,
+            "{input}"
+        );
+    }
+
+    // Without the option, only the exact name works.
+    assert!(from_slice::<Names>(nfd.as_bytes(), LuaFormat::Value, MAX_DEPTH).is_err());
+
+    // Case is only ignored with case_insensitive_fields
+    let upper = "{['FRANC\u{327}AIS'] = 'Bonjour'}";
+    assert!(
+        from_slice_with_options::<Names>(upper.as_bytes(), LuaFormat::Value, MAX_DEPTH, &opts)
+            .is_err()
+    );
+    let opts = opts.case_insensitive_fields(true);
+    assert_eq!(
+        expected,
+        from_slice_with_options(upper.as_bytes(), LuaFormat::Value, MAX_DEPTH, &opts)?
+    );
+    assert_eq!(
+        Names {
+            french: "Bonjour".to_string(),
+            cafe: 1,
+        },
+        from_slice_with_options(
+            b"{CAFE = 1, [ [[fran\xc3\xa7ais]] ] = 'Bonjour'}",
+            LuaFormat::Value,
+            MAX_DEPTH,
+            &opts
+        )?
+    );
+
+    // Keys which normalise to the same field are duplicates
+    let both = "{['fran\u{e7}ais'] = 'a', ['franc\u{327}ais'] = 'b'}";
+    assert_eq!(
+        Error::DuplicateField {
+            name: "fran\u{e7}ais".to_string(),
+            path: String::new(),
+        },
+        from_slice_with_options::<Names>(both.as_bytes(), LuaFormat::Value, MAX_DEPTH, &opts)
+            .unwrap_err()
+    );
+
+    // Keys which aren't valid UTF-8 never match
+    assert!(from_slice_with_options::<Names>(
+        b"{['fran\\xe7ais'] = 'Bonjour'}",
+        LuaFormat::Value,
+        MAX_DEPTH,
+        &opts
+    )
+    .is_err());
+
+    Ok(())
+}
+
+/// `#[serde(alias)]` matches keys however they're written
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn alias() -> Result {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Item {
+        #[serde(alias = "hp", alias = "hit-points")]
+        health: i64,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Flattened {
+        #[serde(flatten)]
+        item: Item,
+        name: Option<String>,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(untagged)]
+    enum Untagged {
+        Item(Item),
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(tag = "type")]
+    enum Tagged {
+        Item {
+            #[serde(alias = "hp", alias = "hit-points")]
+            health: i64,
+        },
+    }
+
+    for input in [
+        // Field name
+        b"{health = 1}".as_slice(),
+        br#"{["health"] = 1}"#,
+        // Alias
+        b"{hp = 1}",
+        br#"{["hp"] = 1}"#,
+        br#"{['hp'] = 1}"#,
+        br#"{["h\x70"] = 1}"#,
+        br#"{["\104\112"] = 1}"#,
+        br#"{["\u{68}p"] = 1}"#,
+        b"{[ [[hp]] ] = 1}",
+        b"{[ [==[hp]==] ] = 1}",
+        // Alias which isn't a Lua identifier
+        br#"{["hit-points"] = 1}"#,
+        br#"{["hit\x2dpoints"] = 1}"#,
+        b"{[ [[hit-points]] ] = 1}",
+    ] {
+        let msg = input.escape_ascii().to_string();
+        let item = Item { health: 1 };
+
+        assert_eq!(
+            item,
+            from_slice::<Item>(input, LuaFormat::Value, MAX_DEPTH)?,
+            "{msg}"
+        );
+        assert_eq!(
+            Flattened {
+                item: Item { health: 1 },
+                name: None,
+            },
+            from_slice(input, LuaFormat::Value, MAX_DEPTH)?,
+            "{msg}"
+        );
+        assert_eq!(
+            Untagged::Item(Item { health: 1 }),
+            from_slice(input, LuaFormat::Value, MAX_DEPTH)?,
+            "{msg}"
+        );
+
+        let mut tagged = b"{type = 'Item', ".to_vec();
+        tagged.extend_from_slice(&input[1..]);
+        assert_eq!(
+            Tagged::Item { health: 1 },
+            from_slice(&tagged, LuaFormat::Value, MAX_DEPTH)?,
+            "{msg}"
+        );
+    }
+
+    // Non-UTF-8 keys never match
+    assert!(from_slice::<Item>(br#"{["h\xf0"] = 1}"#, LuaFormat::Value, MAX_DEPTH).is_err());
+
+    // Using both a name and its alias is a duplicate field, rather than dropping one.
+    assert_eq!(
+        Error::DuplicateField {
+            name: "health".to_string(),
+            path: String::new(),
+        },
+        from_slice::<Item>(
+            br#"{health = 1, [ [[hp]] ] = 2}"#,
+            LuaFormat::Value,
+            MAX_DEPTH
+        )
+        .unwrap_err()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn strings() -> Result {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Strings {
+        a: u8,
+        #[serde(with = "serde_bytes")]
+        b: Vec<u8>,
+        c: char,
+        d: String,
+    }
+
+    let expected = Strings {
+        a: 64,
+        b: b"hello".to_vec(),
+        c: '@',
+        d: "world".to_string(),
+    };
+
+    assert_eq!(
+        expected,
+        from_slice(
+            b"{a = 64, b = 'hello', c = '@', d = 'world'}",
+            LuaFormat::Value,
+            MAX_DEPTH,
+        )?
+    );
+    assert_eq!(
+        expected,
+        from_slice(
+            br"{a = 0x40, b = {104, 101, 108, 108, 111}, c = '\u{40}', d = '\119\111\114\108\100'}",
+            LuaFormat::Value,
+            MAX_DEPTH,
+        )?
+    );
+
+    let expected = Strings {
+        a: 32,
+        b: vec![0, 1, 2, 3, 4, 5, 0xC0, 0xE0, 0],
+        c: '#',
+        d: "\u{65E5}\u{672C}\u{8A9E}".to_string(),
+    };
+    assert_eq!(
+        expected,
+        from_slice(
+            b"{a = 32, b = '\0\\1\\2\\3\\004\\5\\xC0\\xE0\\0', c = '#', d = '\\u{65E5}\\u{672C}\\u{8A9E}'}",
+            LuaFormat::Value, MAX_DEPTH,
+        )?
+    );
+    assert_eq!(
+        expected,
+        from_slice(
+            b"{a = 0x20, b = '\0\x01\x02\x03\x04\x05\xC0\xE0\0', c = '\\35', d = '\xe6\x97\xa5\xe6\x9c\xac\xe8\xaa\x9e'}",
+            LuaFormat::Value, MAX_DEPTH,
+        )?
+    );
+
+    // Serde handling byte strings as String
+    let expected = serde_luaq::Error::SerdeDeserialize(
+        "invalid value: byte array, expected UTF8 string".to_string(),
+    );
+
+    // RFC 2279 escapes
+    assert_eq!(
+        expected,
+        from_slice::<String>(b"'\\u{d800}'", LuaFormat::Value, MAX_DEPTH).unwrap_err(),
+    );
+    assert_eq!(
+        expected,
+        from_slice::<String>(b"'\\u{7FFFFFFF}'", LuaFormat::Value, MAX_DEPTH).unwrap_err(),
+    );
+
+    // Binary data
+    assert_eq!(
+        expected,
+        from_slice::<String>(b"'\xC0\xE0'", LuaFormat::Value, MAX_DEPTH).unwrap_err(),
+    );
+
+    // Escaped binary data
+    assert_eq!(
+        expected,
+        from_slice::<String>(b"'\\xC0\\xE0'", LuaFormat::Value, MAX_DEPTH).unwrap_err(),
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn arrays() -> Result {
+    let expected = ("hello", "world");
+    let expected_a = ["hello", "world"];
+    let expected_v = vec!["hello", "world"];
+    let expected_m = BTreeMap::from([(1, "hello"), (2, "world")]);
+    for b in [
+        b"{'hello', 'world'}".as_slice(),
+        b"{[1] = 'hello', [2] = 'world'}",
+    ] {
+        assert_eq!(
+            expected,
+            from_slice(b, LuaFormat::Value, MAX_DEPTH)?,
+            "{}",
+            b.escape_ascii()
+        );
+        assert_eq!(
+            expected_a,
+            from_slice::<[&str; 2]>(b, LuaFormat::Value, MAX_DEPTH)?,
+            "{}",
+            b.escape_ascii()
+        );
+        assert_eq!(
+            expected_v,
             from_slice::<Vec<&str>>(b, LuaFormat::Value, MAX_DEPTH)?,
             "{}",
             b.escape_ascii()
@@ -1091,6 +2302,104 @@ fn arrays() -> Result {
     Ok(())
 }
 
+/// Tables which aren't sequences, as a list of `(key, value)` pairs
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn table_pairs() -> Result {
+    // Every entry is kept in order, including duplicate keys
+    let lua = b"{a = 1, ['b'] = 2, a = 3}";
+    assert_eq!(
+        vec![("a", 1), ("b", 2), ("a", 3)],
+        from_slice::<Vec<(&str, i64)>>(lua, LuaFormat::Value, MAX_DEPTH)?,
+    );
+    assert_eq!(
+        BTreeMap::from([("a", 3), ("b", 2)]),
+        from_slice::<BTreeMap<&str, i64>>(lua, LuaFormat::Value, MAX_DEPTH)?,
+    );
+    assert_eq!(
+        vec![
+            ("a".to_string(), 1),
+            ("b".to_string(), 2),
+            ("a".to_string(), 3)
+        ],
+        from_value::<Vec<(String, i64)>>(&lua_value(lua, MAX_DEPTH)?)?,
+    );
+
+    // Implicit keys are numbered like a map, and explicit keys are kept as they were written
+    assert_eq!(
+        vec![
+            (LuaValue::integer(1), LuaValue::from("x")),
+            (LuaValue::from("a"), LuaValue::Boolean(true)),
+            (LuaValue::integer(2), LuaValue::from("y")),
+            (LuaValue::float(1.0), LuaValue::Nil),
+        ],
+        from_slice::<Vec<(LuaValue, LuaValue)>>(
+            b"{'x', a = true, 'y', [1.0] = nil}",
+            LuaFormat::Value,
+            MAX_DEPTH
+        )?,
+    );
+
+    // Pairs can also be structs or Vecs
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Pair<'a> {
+        key: &'a str,
+        value: Option<i64>,
+    }
+    assert_eq!(
+        vec![
+            Pair {
+                key: "a",
+                value: Some(1)
+            },
+            Pair {
+                key: "b",
+                value: None
+            },
+        ],
+        from_slice::<Vec<Pair>>(b"{a = 1, b = nil}", LuaFormat::Value, MAX_DEPTH)?,
+    );
+    assert_eq!(
+        vec![vec![LuaValue::from("a"), LuaValue::integer(1)]],
+        from_slice::<Vec<Vec<LuaValue>>>(b"{a = 1}", LuaFormat::Value, MAX_DEPTH)?,
+    );
+
+    // Sequences are still values
+    assert_eq!(
+        vec![(1, "a"), (2, "b")],
+        from_slice::<Vec<(i64, &str)>>(b"{{1, 'a'}, {2, 'b'}}", LuaFormat::Value, MAX_DEPTH)?,
+    );
+    assert!(
+        from_slice::<Vec<(i64, &str)>>(b"{'a', [3] = 'c'}", LuaFormat::Value, MAX_DEPTH).is_err()
+    );
+
+    // Errors
+    let err = from_slice::<Vec<(&str, u8)>>(b"{a = 1, b = 256}", LuaFormat::Value, MAX_DEPTH)
+        .unwrap_err();
+    assert_eq!(Some("b"), err.path());
+    let err = from_slice::<Vec<(&str,)>>(b"{a = 1}", LuaFormat::Value, MAX_DEPTH).unwrap_err();
+    assert!(err.to_string().contains("(key, value) pair"), "{err}");
+    let err = from_slice::<Vec<&str>>(b"{a = 'b'}", LuaFormat::Value, MAX_DEPTH).unwrap_err();
+    assert_eq!(Some("a"), err.path());
+    assert!(
+        err.to_string()
+            .contains("(key, value) pair of a table which isn't a sequence"),
+        "{err}"
+    );
+
+    // A table.pack()-style length doesn't apply
+    let opts = DeserializeOptions::new().sequence_n_field(true);
+    assert!(from_slice_with_options::<Vec<(&str, i64)>>(
+        b"{a = 1, n = 1}",
+        LuaFormat::Value,
+        MAX_DEPTH,
+        &opts
+    )
+    .is_err());
+
+    Ok(())
+}
+
 /// Tests for `#[serde(flatten)]`
 ///
 /// `#[serde(flatten)]` forces serde into the `deserialize_any` path, even if it should use
@@ -1329,3 +2638,395 @@ fn enum_parse_quirks() -> Result {
     assert!(from_slice::<Choice>(c, LuaFormat::Value, MAX_DEPTH).is_err());
     Ok(())
 }
+
+/// `#[serde(deny_unknown_fields)]` and error paths
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn deny_unknown_fields() -> Result {
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(deny_unknown_fields)]
+    struct Item {
+        name: String,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(deny_unknown_fields)]
+    struct Player {
+        items: Vec<Item>,
+        stats: BTreeMap<String, Item>,
+    }
+
+    let expected = Player {
+        items: vec![Item {
+            name: "sword".to_string(),
+        }],
+        stats: BTreeMap::new(),
+    };
+    assert_eq!(
+        expected,
+        from_slice(
+            b"{items = {{name = 'sword'}}, stats = {}}",
+            LuaFormat::Value,
+            MAX_DEPTH
+        )?
+    );
+
+    // Top level
+    let err = from_slice::<Player>(
+        b"{items = {}, stats = {}, extra = 1}",
+        LuaFormat::Value,
+        MAX_DEPTH,
+    )
+    .unwrap_err();
+    assert_eq!(None, err.path());
+    assert!(err.to_string().contains("unknown field `extra`"), "{err}");
+
+    for (lua, path) in [
+        (
+            b"{items = {{name = 'sword'}, {name = 'shield', weight = 3}}, stats = {}}".as_slice(),
+            "items[2]",
+        ),
+        (
+            b"{items = {}, stats = {['x y'] = {name = 'a', weight = 3}}}",
+            r#"stats["x y"]"#,
+        ),
+        (
+            b"{items = {}, stats = {['x'] = {name = 'a', weight = 3}}}",
+            "stats.x",
+        ),
+        (
+            b"{['items'] = {[1] = {name = 'a', weight = 3}}, stats = {}}",
+            "items[1]",
+        ),
+    ] {
+        let err = from_slice::<Player>(lua, LuaFormat::Value, MAX_DEPTH).unwrap_err();
+        assert_eq!(Some(path), err.path(), "{}", lua.escape_ascii());
+        assert!(
+            matches!(err.without_path(), Error::SerdeDeserialize(m) if m.starts_with("unknown field `weight`")),
+            "{err}"
+        );
+        assert!(
+            err.to_string().starts_with(&format!(
+                "{path}: serde deserialize error: unknown field `weight`"
+            )),
+            "{err}"
+        );
+    }
+
+    // Other errors get paths too
+    let err = from_slice::<Player>(
+        b"{items = {{name = 1}}, stats = {}}",
+        LuaFormat::Value,
+        MAX_DEPTH,
+    )
+    .unwrap_err();
+    assert_eq!(Some("items[1].name"), err.path());
+
+    Ok(())
+}
+
+/// Capturing unknown fields
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn unknown_fields() -> Result {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Player {
+        name: String,
+        #[serde(flatten)]
+        unknown: UnknownFields,
+    }
+
+    let player: Player = from_slice(b"{name = 'Alice'}", LuaFormat::Value, MAX_DEPTH)?;
+    assert_eq!("Alice", player.name);
+    assert!(player.unknown.is_empty());
+
+    let player: Player = from_slice(
+        b"{name = 'Alice', level = 3, ['x y'] = 1.5, [3] = true, b = '\\xff', s = {1, nil, {a = 'b'}}}",
+        LuaFormat::Value,
+        MAX_DEPTH,
+    )?;
+    assert_eq!("Alice", player.name);
+    assert_eq!(
+        UnknownFields(vec![
+            LuaTableEntry::from(("level", LuaValue::integer(3))),
+            LuaTableEntry::KeyValue(Box::new((LuaValue::from("x y"), LuaValue::float(1.5)))),
+            LuaTableEntry::KeyValue(Box::new((LuaValue::integer(3), LuaValue::Boolean(true)))),
+            LuaTableEntry::from(("b", LuaValue::String(b"\xff".into()))),
+            LuaTableEntry::from((
+                "s",
+                LuaValue::Table(vec![
+                    LuaTableEntry::from(LuaValue::integer(1)),
+                    LuaTableEntry::NilValue,
+                    LuaTableEntry::from(LuaValue::from_iter([("a", LuaValue::from("b"))])),
+                ])
+            )),
+        ]),
+        player.unknown
+    );
+
+    Ok(())
+}
+
+/// `LuaValue` as a catch-all field
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn lua_value_field() -> Result {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Item {
+        name: String,
+        data: LuaValue<'static>,
+        #[serde(default)]
+        extra: Option<LuaValue<'static>>,
+    }
+
+    let item: Item = from_slice(
+        b"{name = 'sword', data = {1, nil, {a = 'b', ['x y'] = 1.5, [3] = true}}}",
+        LuaFormat::Value,
+        MAX_DEPTH,
+    )?;
+    assert_eq!(
+        Item {
+            name: "sword".to_string(),
+            data: LuaValue::Table(vec![
+                LuaTableEntry::from(LuaValue::integer(1)),
+                LuaTableEntry::NilValue,
+                LuaTableEntry::from(LuaValue::Table(vec![
+                    LuaTableEntry::from(("a", LuaValue::from("b"))),
+                    LuaTableEntry::KeyValue(Box::new((
+                        LuaValue::from("x y"),
+                        LuaValue::float(1.5)
+                    ))),
+                    LuaTableEntry::KeyValue(Box::new((
+                        LuaValue::integer(3),
+                        LuaValue::Boolean(true)
+                    ))),
+                ])),
+            ]),
+            extra: None,
+        },
+        item
+    );
+
+    // Scalars, and strings which aren't valid UTF-8
+    for (lua, expected) in [
+        (&b"nil"[..], LuaValue::Nil),
+        (b"false", LuaValue::Boolean(false)),
+        (b"0x7fffffffffffffff", LuaValue::integer(i64::MAX)),
+        (b"-1.5", LuaValue::float(-1.5)),
+        (b"'\\xff\\0'", LuaValue::String(b"\xff\0".into())),
+        (b"{}", LuaValue::Table(vec![])),
+    ] {
+        assert_eq!(
+            expected,
+            from_slice::<LuaValue>(lua, LuaFormat::Value, MAX_DEPTH)?
+        );
+    }
+
+    let item: Item = from_slice(
+        b"{name = 'shield', data = 'wood', extra = false}",
+        LuaFormat::Value,
+        MAX_DEPTH,
+    )?;
+    assert_eq!(LuaValue::from("wood"), item.data);
+    assert_eq!(Some(LuaValue::Boolean(false)), item.extra);
+
+    // Values which are already parsed are copied
+    let v = lua_value(b"{a = {'b', 'c'}, ['d e'] = {f = 1}}", MAX_DEPTH)?;
+    let copy: LuaValue = from_value(&v)?;
+    assert_eq!(v, copy);
+
+    // ...but implicit keys in a mixed table become explicit
+    let copy: LuaValue = from_value(&lua_value(b"{'a', b = 2}", MAX_DEPTH)?)?;
+    assert_eq!(lua_value(b"{[1] = 'a', b = 2}", MAX_DEPTH)?, copy);
+    let copy: BTreeMap<String, LuaValue> = from_value(&lua_value(b"{b = {c = 1}}", MAX_DEPTH)?)?;
+    assert_eq!(
+        LuaValue::from_iter([("c", LuaValue::integer(1))]),
+        copy["b"]
+    );
+
+    Ok(())
+}
+
+/// Sets as tables whose values are `true`
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn set_as_table() -> Result {
+    #[derive(Deserialize, Serialize, PartialEq, Debug)]
+    struct Sets {
+        #[serde(with = "serde_luaq::set_as_table")]
+        names: HashSet<String>,
+        #[serde(with = "serde_luaq::set_as_table", default)]
+        ids: BTreeSet<i64>,
+    }
+
+    let sets: Sets = from_slice(
+        b"{names = {a = true, ['b c'] = true, d = false, e = nil}, ids = {[3] = true, [1] = true}}",
+        LuaFormat::Value,
+        MAX_DEPTH,
+    )?;
+    assert_eq!(
+        Sets {
+            names: HashSet::from(["a".to_string(), "b c".to_string()]),
+            ids: BTreeSet::from([1, 3]),
+        },
+        sets
+    );
+
+    let sets: Sets = from_slice(b"{names = {}}", LuaFormat::Value, MAX_DEPTH)?;
+    assert!(sets.names.is_empty());
+    assert!(sets.ids.is_empty());
+
+    // Round trip
+    let lua = to_string(
+        &Sets {
+            names: HashSet::from(["x".to_string()]),
+            ids: BTreeSet::from([2, 1]),
+        },
+        LuaFormat::Value,
+    )?;
+    assert_eq!("{names = {x = true}, ids = {[1] = true, [2] = true}}", lua);
+    assert_eq!(
+        BTreeSet::from([1, 2]),
+        from_slice::<Sets>(lua.as_bytes(), LuaFormat::Value, MAX_DEPTH)?.ids
+    );
+
+    // Values must be booleans, unless lenient
+    let lua = b"{names = {a = 1, b = 0}}";
+    assert!(from_slice::<Sets>(lua, LuaFormat::Value, MAX_DEPTH).is_err());
+    assert!(from_slice::<Sets>(b"{names = {'a'}}", LuaFormat::Value, MAX_DEPTH).is_err());
+    assert!(from_slice::<Sets>(b"{names = true}", LuaFormat::Value, MAX_DEPTH).is_err());
+    let opts = DeserializeOptions::new().lenient_bool(true);
+    let sets: Sets = from_slice_with_options(lua, LuaFormat::Value, MAX_DEPTH, &opts)?;
+    assert_eq!(HashSet::from(["a".to_string()]), sets.names);
+
+    Ok(())
+}
+
+/// Transforming values before deserialisation
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn transforms() -> Result {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Item {
+        id: i64,
+        tags: Vec<String>,
+    }
+
+    fn split(v: LuaValue<'_>) -> std::result::Result<LuaValue<'_>, String> {
+        match v {
+            LuaValue::String(s) => Ok(LuaValue::Table(
+                String::from_utf8_lossy(&s)
+                    .split(',')
+                    .map(|t| LuaTableEntry::from(LuaValue::from(t.to_string())))
+                    .collect(),
+            )),
+            v => Ok(v),
+        }
+    }
+
+    fn double(v: LuaValue<'_>) -> std::result::Result<LuaValue<'_>, String> {
+        match v {
+            LuaValue::Number(LuaNumber::Integer(i)) => Ok(LuaValue::integer(i * 2)),
+            _ => Err("expected integer".to_string()),
+        }
+    }
+
+    // Field transformations apply at any depth
+    let opts = DeserializeOptions::new().transforms(Transforms::new().field("tags", split));
+    let items: Vec<Item> = from_slice_with_options(
+        b"{{id = 1, tags = 'a,b'}, {id = 2, tags = {'c'}}, {id = 3, ['tags'] = 'd'}}",
+        LuaFormat::Value,
+        MAX_DEPTH,
+        &opts,
+    )?;
+    assert_eq!(
+        vec![
+            Item {
+                id: 1,
+                tags: vec!["a".into(), "b".into()]
+            },
+            Item {
+                id: 2,
+                tags: vec!["c".into()]
+            },
+            Item {
+                id: 3,
+                tags: vec!["d".into()]
+            },
+        ],
+        items
+    );
+
+    // Script variables are fields
+    let item: BTreeMap<String, Item> = from_slice_with_options(
+        b"item = {id = 1, tags = 'x'}",
+        LuaFormat::Script,
+        MAX_DEPTH,
+        &opts,
+    )?;
+    assert_eq!(vec!["x".to_string()], item["item"].tags);
+
+    // Path transformations only apply to one value, including implicit keys and the root
+    let opts = DeserializeOptions::new().transforms(Transforms::new().path("[2].id", double));
+    let v: Vec<BTreeMap<String, i64>> =
+        from_slice_with_options(b"{{id = 1}, {id = 2}}", LuaFormat::Value, MAX_DEPTH, &opts)?;
+    assert_eq!(vec![1, 4], v.iter().map(|m| m["id"]).collect::<Vec<_>>());
+    let opts =
+        DeserializeOptions::new().transforms(Transforms::new().path("[3]", double).path("", |v| {
+            match v {
+                LuaValue::Table(mut t) => {
+                    t.push(LuaTableEntry::from(LuaValue::integer(100)));
+                    Ok(LuaValue::Table(t))
+                }
+                v => Ok(v),
+            }
+        }));
+    let v: Vec<i64> = from_slice_with_options(b"{1, 2, 3, 4}", LuaFormat::Value, MAX_DEPTH, &opts)?;
+    assert_eq!(vec![1, 2, 6, 4, 100], v);
+
+    // Transformations apply in order, inside out
+    let opts = DeserializeOptions::new().transforms(
+        Transforms::new()
+            .field("n", double)
+            .path("a.n", double)
+            .field("a", |v| {
+                let LuaValue::Table(t) = v else {
+                    return Err("expected table".to_string());
+                };
+                Ok(t.into_iter()
+                    .next()
+                    .map_or(LuaValue::Nil, |e| e.move_value()))
+            }),
+    );
+    let v: BTreeMap<String, i64> =
+        from_slice_with_options(b"{a = {n = 1}}", LuaFormat::Value, MAX_DEPTH, &opts)?;
+    assert_eq!(4, v["a"]);
+
+    // Works with from_value_with_options, without changing the original value
+    let value = lua_value(b"{a = {n = 3}}", MAX_DEPTH)?;
+    let v: BTreeMap<String, i64> = from_value_with_options(&value, &opts)?;
+    assert_eq!(12, v["a"]);
+    assert_eq!(lua_value(b"{a = {n = 3}}", MAX_DEPTH)?, value);
+
+    // Errors include the path of the value
+    let e = from_slice_with_options::<BTreeMap<String, i64>>(
+        b"{a = {n = 'x'}}",
+        LuaFormat::Value,
+        MAX_DEPTH,
+        &opts,
+    )
+    .unwrap_err();
+    assert_eq!(Some("a.n"), e.path());
+    assert_eq!(
+        "a.n: serde deserialize error: expected integer",
+        e.to_string()
+    );
+
+    // Transforms compare equal if they share the same functions
+    let transforms = Transforms::new().field("n", double);
+    assert_eq!(transforms, transforms.clone());
+    assert_ne!(transforms, Transforms::new().field("n", double));
+    assert!(Transforms::new().is_empty());
+
+    Ok(())
+}
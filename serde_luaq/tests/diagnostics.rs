@@ -0,0 +1,231 @@
+//! Parsing with diagnostics.
+mod common;
+use crate::common::MAX_DEPTH;
+use serde_luaq::{
+    lua_value, parse_with_diagnostics, script, Diagnostic, DiagnosticKind, Error, LuaFormat,
+    Parser, Severity,
+};
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+wasm_bindgen_test_configure!(run_in_browser);
+
+type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+fn parser() -> Parser {
+    Parser::builder().max_depth(MAX_DEPTH).build()
+}
+
+/// Returns the duplicate keys reported in `lua`, and the input they cover.
+fn duplicate_keys(lua: &[u8]) -> Vec<(String, String)> {
+    let (value, diagnostics) = parser().parse_with_diagnostics(lua, LuaFormat::Value);
+    assert!(value.is_some(), "lua: {}", lua.escape_ascii());
+    diagnostics
+        .into_iter()
+        .map(|d| match d.kind {
+            DiagnosticKind::DuplicateKey { key } => {
+                (key, String::from_utf8_lossy(&lua[d.span]).into_owned())
+            }
+            k => panic!("unexpected diagnostic {k:?}"),
+        })
+        .collect()
+}
+
+fn key(key: &str, entry: &str) -> (String, String) {
+    (key.to_string(), entry.to_string())
+}
+
+/// Valid input without mistakes has no diagnostics.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn clean() -> Result {
+    let p = parser();
+    for (lua, format) in [
+        (
+            &b"{1, 2, a = 3, ['b c'] = 4, [4] = 5, {1, 2}}"[..],
+            LuaFormat::Value,
+        ),
+        (b"return {1, 2, 3}", LuaFormat::Return),
+        (b"a = 1\nb = {a = 1}", LuaFormat::Script),
+        (b"a = 1\nreturn a", LuaFormat::ScriptReturn),
+    ] {
+        let (value, diagnostics) = p.parse_with_diagnostics(lua, format);
+        assert!(value.is_some());
+        assert_eq!(Vec::<Diagnostic>::new(), diagnostics);
+    }
+
+    let (value, diagnostics) = parse_with_diagnostics(
+        b"{[1] = 1, [1.5] = 2, [true] = 3}",
+        LuaFormat::Value,
+        p.options(),
+    );
+    assert_eq!(
+        Some(lua_value(b"{[1] = 1, [1.5] = 2, [true] = 3}", MAX_DEPTH)?),
+        value
+    );
+    assert!(diagnostics.is_empty());
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn duplicate_key() {
+    assert_eq!(vec![key("a", "a = 2")], duplicate_keys(b"{a = 1, a = 2}"));
+    assert_eq!(
+        vec![key("a", "['a'] = 2")],
+        duplicate_keys(b"{a = 1, ['a'] = 2}")
+    );
+    assert_eq!(
+        vec![key("[\"b c\"]", "['b c'] = 2")],
+        duplicate_keys(b"{['b c'] = 1, ['b c'] = 2}")
+    );
+
+    // Implicit keys
+    assert_eq!(
+        vec![key("[1]", "[1] = 3")],
+        duplicate_keys(b"{1, 2, [1] = 3}")
+    );
+    assert_eq!(
+        vec![key("[2]", "'b'")],
+        duplicate_keys(b"{[2] = 'a', 1, 'b'}")
+    );
+
+    // Floats with integer values are integer keys
+    assert_eq!(
+        vec![key("[2.0]", "[2.0] = 3")],
+        duplicate_keys(b"{[2] = 1, [2.0] = 3}")
+    );
+    assert_eq!(
+        vec![key("[true]", "[true] = 2")],
+        duplicate_keys(b"{[true] = 1, [true] = 2}")
+    );
+
+    // Every entry after the first is reported
+    assert_eq!(
+        vec![key("a", "a = 2"), key("a", "a = {}")],
+        duplicate_keys(b"{a = 1, a = 2, a = {}}")
+    );
+
+    // Nested tables are checked separately
+    assert_eq!(
+        vec![key("b", "b = 2"), key("a", "a = {}")],
+        duplicate_keys(b"{a = {b = 1, b = 2}, a = {}, b = 3}")
+    );
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn duplicate_variable() -> Result {
+    let p = Parser::builder()
+        .max_depth(MAX_DEPTH)
+        .field_assignments(true)
+        .build();
+    let lua = b"a = 1\nb = 2\na = {c = 1}\na.c = 2\nb = 3";
+    let (value, diagnostics) = p.parse_with_diagnostics(lua, LuaFormat::Script);
+    assert_eq!(
+        Some(
+            script(b"a = 1\nb = 2\na = {c = 2}\nb = 3", MAX_DEPTH)?
+                .into_iter()
+                .collect()
+        ),
+        value
+    );
+
+    let expected = [("a", "a = {c = 1}"), ("b", "b = 3")];
+    assert_eq!(expected.len(), diagnostics.len());
+    for (d, (name, statement)) in diagnostics.iter().zip(expected) {
+        assert_eq!(
+            DiagnosticKind::DuplicateVariable {
+                name: name.to_string()
+            },
+            d.kind
+        );
+        assert_eq!(statement.as_bytes(), &lua[d.span.clone()]);
+        assert_eq!(Severity::Warning, d.severity());
+    }
+
+    let (value, diagnostics) = p.parse_with_diagnostics(
+        b"a = {1, [1] = 2}\na = 3\nreturn a",
+        LuaFormat::ScriptReturn,
+    );
+    assert_eq!(Some(lua_value(b"3", MAX_DEPTH)?), value);
+    assert_eq!(
+        vec![
+            DiagnosticKind::DuplicateKey {
+                key: "[1]".to_string()
+            },
+            DiagnosticKind::DuplicateVariable {
+                name: "a".to_string()
+            },
+        ],
+        diagnostics.into_iter().map(|d| d.kind).collect::<Vec<_>>(),
+    );
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn errors() {
+    let p = parser();
+    let (value, diagnostics) = p.parse_with_diagnostics(b"{a = 1, a = 2, 3 4}", LuaFormat::Value);
+    assert_eq!(None, value);
+    assert_eq!(1, diagnostics.len());
+    assert!(matches!(
+        diagnostics[0].kind,
+        DiagnosticKind::Error(Error::Peg(_))
+    ));
+    assert_eq!(Severity::Error, diagnostics[0].severity());
+    assert_eq!(17..18, diagnostics[0].span);
+
+    let p = Parser::builder()
+        .max_depth(MAX_DEPTH)
+        .max_identifier_length(4)
+        .build();
+    let (value, diagnostics) = p.parse_with_diagnostics(b"a = 1\nlonger = 2", LuaFormat::Script);
+    assert_eq!(None, value);
+    assert_eq!(
+        vec![DiagnosticKind::Error(Error::IdentifierTooLong {
+            limit: 4,
+            position: 6
+        })],
+        diagnostics
+            .iter()
+            .map(|d| d.kind.clone())
+            .collect::<Vec<_>>(),
+    );
+    assert_eq!(6..7, diagnostics[0].span);
+
+    // Errors without a position cover the whole input
+    let p = Parser::builder()
+        .max_depth(MAX_DEPTH)
+        .max_statements(1)
+        .build();
+    let (value, diagnostics) = p.parse_with_diagnostics(b"a = 1\nb = 2", LuaFormat::Script);
+    assert_eq!(None, value);
+    assert_eq!(
+        DiagnosticKind::Error(Error::TooManyStatements { limit: 1 }),
+        diagnostics[0].kind
+    );
+    assert_eq!(0..11, diagnostics[0].span);
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn truncated() -> Result {
+    let p = parser();
+    let lua = b"return {a = 1, a = 2, b = tr";
+    let (value, diagnostics) = p.parse_with_diagnostics(lua, LuaFormat::Return);
+    assert_eq!(Some(lua_value(b"{a = 1, a = 2}", MAX_DEPTH)?), value);
+
+    // Warnings from the failed parse aren't reported
+    assert_eq!(2, diagnostics.len());
+    assert!(matches!(
+        diagnostics[0].kind,
+        DiagnosticKind::Error(Error::Peg(_))
+    ));
+    assert_eq!(DiagnosticKind::Truncated, diagnostics[1].kind);
+    assert_eq!(Severity::Warning, diagnostics[1].severity());
+    assert_eq!(21..lua.len(), diagnostics[1].span);
+    Ok(())
+}
@@ -0,0 +1,143 @@
+//! Golden-file test harness tests.
+#![cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use serde_json::json;
+use serde_luaq::{
+    assert_golden_dir, check_golden_dir, GoldenFailureKind, GoldenOptions, JsonDiff, LineDiff,
+};
+use std::{fs, path::PathBuf};
+
+type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/golden");
+
+/// Makes an empty temporary directory for a test.
+fn temp_dir(name: &str) -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("serde_luaq-{name}-{}", std::process::id()));
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+#[test]
+fn golden() {
+    assert_golden_dir(GOLDEN_DIR, &GoldenOptions::default());
+}
+
+#[test]
+#[should_panic(expected = "no .lua fixtures")]
+fn no_fixtures() {
+    let dir = temp_dir("no_fixtures").unwrap();
+    assert_golden_dir(dir, &GoldenOptions::default());
+}
+
+#[test]
+fn bless() -> Result {
+    let dir = temp_dir("bless")?;
+    for f in ["basic.lua", "binary.lua"] {
+        fs::copy(PathBuf::from(GOLDEN_DIR).join(f), dir.join(f))?;
+    }
+
+    let report = check_golden_dir(&dir, &GoldenOptions::default())?;
+    assert_eq!(2, report.failures.len());
+    assert!(report
+        .failures
+        .iter()
+        .all(|f| matches!(f.kind, GoldenFailureKind::MissingSnapshot)));
+
+    let report = check_golden_dir(&dir, &GoldenOptions::new().bless(true))?;
+    assert!(report.is_ok(), "{report}");
+    assert_eq!(
+        vec![dir.join("basic.json"), dir.join("binary.debug")],
+        report.blessed
+    );
+
+    // Blessed snapshots should match the originals
+    for f in ["basic.json", "binary.debug"] {
+        assert_eq!(
+            fs::read(PathBuf::from(GOLDEN_DIR).join(f))?,
+            fs::read(dir.join(f))?,
+            "{f}"
+        );
+    }
+
+    assert_golden_dir(&dir, &GoldenOptions::default());
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn mismatch() -> Result {
+    let dir = temp_dir("mismatch")?;
+    fs::write(dir.join("a.lua"), b"{ a = 1, b = { 2, 3 }, c = (0/0) }")?;
+    fs::write(dir.join("a.json"), br#"{"a": 1, "b": [2, 4], "d": true}"#)?;
+    fs::write(dir.join("b.lua"), b"{ 1 }")?;
+    fs::write(
+        dir.join("b.debug"),
+        b"Table(\n    [\n        NumberValue(\n            Integer(\n                2,\n            ),\n        ),\n    ],\n)\n",
+    )?;
+    fs::write(dir.join("c.lua"), b"{")?;
+    fs::write(dir.join("c.json"), b"{}")?;
+    fs::write(dir.join("d.lua"), b"1")?;
+    fs::write(dir.join("d.json"), b"{")?;
+
+    let report = check_golden_dir(&dir, &GoldenOptions::default())?;
+    assert!(report.passed.is_empty());
+    assert_eq!(4, report.failures.len());
+
+    let GoldenFailureKind::Json(_) = &report.failures[0].kind else {
+        panic!("expected JSON conversion error: {}", report.failures[0]);
+    };
+    let GoldenFailureKind::DebugMismatch { diffs, .. } = &report.failures[1].kind else {
+        panic!("expected debug mismatch: {}", report.failures[1]);
+    };
+    assert_eq!(
+        &vec![LineDiff {
+            line: 5,
+            expected: Some("                2,".to_string()),
+            actual: Some("                1,".to_string()),
+        }],
+        diffs
+    );
+    let GoldenFailureKind::Parse(_) = &report.failures[2].kind else {
+        panic!("expected parse error: {}", report.failures[2]);
+    };
+    let GoldenFailureKind::InvalidSnapshot { .. } = &report.failures[3].kind else {
+        panic!("expected invalid snapshot: {}", report.failures[3]);
+    };
+
+    // Replace the NaN
+    fs::write(dir.join("a.lua"), b"{ a = 1, b = { 2, 3 }, c = 0 }")?;
+    let report = check_golden_dir(&dir, &GoldenOptions::default())?;
+    let GoldenFailureKind::JsonMismatch { diffs, .. } = &report.failures[0].kind else {
+        panic!("expected JSON mismatch: {}", report.failures[0]);
+    };
+    assert_eq!(
+        &vec![
+            JsonDiff {
+                pointer: "/b/1".to_string(),
+                expected: Some(json!(4)),
+                actual: Some(json!(3)),
+            },
+            JsonDiff {
+                pointer: "/d".to_string(),
+                expected: Some(json!(true)),
+                actual: None,
+            },
+            JsonDiff {
+                pointer: "/c".to_string(),
+                expected: None,
+                actual: Some(json!(0)),
+            },
+        ],
+        diffs
+    );
+
+    let s = report.to_string();
+    assert!(s.starts_with("0 passed, 0 blessed, 4 failed\n"), "{s}");
+    assert!(s.contains("  /b/1: expected 4, got 3\n"), "{s}");
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
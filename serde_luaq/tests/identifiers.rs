@@ -1,7 +1,10 @@
 mod common;
 use crate::common::MAX_DEPTH;
 
-use serde_luaq::{lua_value, return_statement, script, LuaTableEntry, LuaValue};
+use serde_luaq::{
+    lua_value, return_statement, script, BareIdentifiers, Error, LuaDialect, LuaTableEntry,
+    LuaValue, Parser,
+};
 
 #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
 use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
@@ -655,3 +658,143 @@ fn invalid() {
     assert!(script(b"{'foo' = true}\n", MAX_DEPTH).is_err());
     assert!(script(b"{\"foo\" = true}\n", MAX_DEPTH).is_err());
 }
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn bare_identifiers() -> Result {
+    let strict = Parser::default();
+    let lenient = Parser::builder()
+        .bare_identifiers(BareIdentifiers::String)
+        .build();
+
+    for input in [
+        &b"RED"[..],
+        b"{color = RED}",
+        b"{RED, GREEN}",
+        b"{[RED] = 1}",
+        b"nilly",
+    ] {
+        assert!(strict.value(input).is_err(), "{}", input.escape_ascii());
+    }
+
+    assert_eq!(LuaValue::from("RED"), lenient.value(b"RED")?);
+    assert_eq!(LuaValue::from("nilly"), lenient.value(b" nilly ")?);
+    assert_eq!(LuaValue::from("true_"), lenient.value(b"true_")?);
+    assert_eq!(
+        LuaValue::Table(vec![
+            LuaTableEntry::NameValue(Box::new(("color".into(), LuaValue::from("RED")))),
+            LuaTableEntry::Value(Box::new(LuaValue::from("GREEN"))),
+            LuaTableEntry::KeyValue(Box::new((LuaValue::from("BLUE"), LuaValue::Boolean(true)))),
+        ]),
+        lenient.value(b"{color = RED, GREEN, [BLUE] = true}")?
+    );
+    assert_eq!(
+        vec![("a", LuaValue::from("b")), ("c", LuaValue::from("d"))],
+        lenient.script(b"a = b\nc = d")?
+    );
+    assert_eq!(LuaValue::from("X"), lenient.ret(b"return X")?);
+
+    // Keywords still have their usual meaning
+    assert_eq!(LuaValue::Nil, lenient.value(b"nil")?);
+    assert_eq!(LuaValue::Boolean(false), lenient.value(b"false")?);
+    assert!(lenient.value(b"end").is_err());
+    assert!(lenient.value(b"{a = end}").is_err());
+
+    // Only simple names
+    assert!(lenient.value(b"Color.RED").is_err());
+    assert!(lenient.value(b"RED()").is_err());
+
+    Ok(())
+}
+
+/// Identifier length limits
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn max_identifier_length() -> Result {
+    let long = "a".repeat(100_000);
+    let p = Parser::builder().max_identifier_length(4).build();
+
+    // At the limit
+    assert_eq!(
+        lua_value(b"{abcd = 1}", MAX_DEPTH)?,
+        p.value(b"{abcd = 1}")?
+    );
+    assert_eq!(("abcd", LuaValue::integer(1)), p.script(b"abcd = 1")?[0]);
+
+    let too_long = |position| Error::IdentifierTooLong { limit: 4, position };
+    for (lua, position) in [
+        (format!("{{{long} = 1}}"), 1),
+        (format!("{{1, 2, {long} = 1}}"), 7),
+        (format!("{{b = {{c = {{{long} = 1}}}}}}"), 11),
+    ] {
+        assert_eq!(too_long(position), p.value(lua.as_bytes()).unwrap_err());
+
+        // No limit by default
+        assert!(Parser::default().value(lua.as_bytes()).is_ok());
+    }
+
+    for (lua, position) in [
+        (format!("{long} = 1"), 0),
+        (format!("a = 1\n{long} = 1"), 6),
+        (format!("a = {{{long} = 1}}"), 5),
+    ] {
+        assert_eq!(too_long(position), p.script(lua.as_bytes()).unwrap_err());
+        assert!(Parser::default().script(lua.as_bytes()).is_ok());
+    }
+
+    // Also applies to bare identifiers
+    let p = Parser::builder()
+        .max_identifier_length(4)
+        .bare_identifiers(BareIdentifiers::String)
+        .build();
+    assert_eq!(LuaValue::from("RED"), p.value(b"RED")?);
+    assert_eq!(too_long(0), p.value(b"GREEN").unwrap_err());
+
+    // Other syntax errors are unaffected
+    let p = Parser::builder().max_identifier_length(4).build();
+    assert!(matches!(p.value(b"{a = }").unwrap_err(), Error::Peg(_)));
+    Ok(())
+}
+
+/// `goto` isn't reserved in Lua 5.1
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn lua51_goto() -> Result {
+    let p = Parser::builder()
+        .max_depth(MAX_DEPTH)
+        .dialect(LuaDialect::Lua51)
+        .build();
+
+    assert_eq!(
+        vec![("goto", LuaValue::Boolean(true))],
+        p.script(b"goto = true\n")?
+    );
+    assert_eq!(
+        LuaValue::Table(vec![LuaTableEntry::NameValue(Box::new((
+            "goto".into(),
+            LuaValue::Boolean(true)
+        )))]),
+        p.value(b"{goto = true}")?
+    );
+    assert_eq!(p.value(b"{goto = true}")?, p.ret(b"return {goto = true}")?);
+
+    // Bare identifiers
+    let p = Parser::builder()
+        .dialect(LuaDialect::Lua51)
+        .bare_identifiers(BareIdentifiers::String)
+        .build();
+    assert_eq!(LuaValue::from("goto"), p.value(b"goto")?);
+
+    // Other keywords are still reserved
+    for lua in [&b"break = true"[..], b"end = true", b"nil = true"] {
+        assert!(p.script(lua).is_err(), "{}", lua.escape_ascii());
+    }
+
+    // ...and goto is reserved in other dialects
+    for dialect in [LuaDialect::Lua54, LuaDialect::GLua] {
+        let p = Parser::builder().dialect(dialect).build();
+        assert!(p.script(b"goto = true").is_err(), "{dialect:?}");
+        assert!(p.value(b"{goto = true}").is_err(), "{dialect:?}");
+    }
+    Ok(())
+}
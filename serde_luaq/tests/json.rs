@@ -3,8 +3,9 @@ mod common;
 use crate::common::MAX_DEPTH;
 use serde_json::json;
 use serde_luaq::{
-    from_json_value, lua_value, to_json_value, JsonConversionError, JsonConversionOptions,
-    LuaNumber, LuaTableEntry, LuaValue,
+    from_json_value, json_schema, lua_value, to_json_value, to_json_value_with_diagnostics,
+    DiagnosticKind, DuplicateKeys, JsonConversionError, JsonConversionOptions, LuaNumber,
+    LuaTableEntry, LuaValue, Severity,
 };
 
 #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
@@ -13,9 +14,7 @@ use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
 wasm_bindgen_test_configure!(run_in_browser);
 
 type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
-const DEFAULT_OPTS: JsonConversionOptions = JsonConversionOptions {
-    lossy_string: false,
-};
+const DEFAULT_OPTS: JsonConversionOptions = JsonConversionOptions::new();
 
 #[test]
 #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
@@ -44,6 +43,61 @@ fn table_precedence() -> Result {
     Ok(())
 }
 
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn duplicate_keys() -> Result {
+    let first_wins = JsonConversionOptions::new().duplicate_keys(DuplicateKeys::FirstWins);
+    let error = JsonConversionOptions::new().duplicate_keys(DuplicateKeys::Error);
+
+    for (input, first, dropped) in [
+        (&b"{[1] = 1, 2, [2] = 3, 4}"[..], json!({"1": 1, "2": 3}), 2),
+        (b"{1, 3, [1] = 2, [2] = 4}", json!({"1": 1, "2": 3}), 2),
+        (
+            b"{['1'] = 1, [1] = 2, [2] = 3, ['2'] = 4}",
+            json!({"1": 1, "2": 3}),
+            2,
+        ),
+        (b"{a = {b = 1, b = 2}, a = 3}", json!({"a": {"b": 1}}), 2),
+        (b"{1, 2, a = 3}", json!({"1": 1, "2": 2, "a": 3}), 0),
+    ] {
+        let v = lua_value(input, MAX_DEPTH)?;
+        assert_eq!(
+            first,
+            to_json_value(v.clone(), &first_wins)?,
+            "for input: {}",
+            input.escape_ascii(),
+        );
+
+        for opts in [&DEFAULT_OPTS, &first_wins] {
+            let (json, diagnostics) = to_json_value_with_diagnostics(v.clone(), opts)?;
+            assert_eq!(to_json_value(v.clone(), opts)?, json);
+            assert_eq!(dropped, diagnostics.len());
+            for d in diagnostics {
+                assert!(matches!(d.kind, DiagnosticKind::DuplicateJsonKey { .. }));
+                assert_eq!(Severity::Warning, d.severity());
+                assert_eq!(0..0, d.span);
+            }
+        }
+
+        if dropped > 0 {
+            assert!(matches!(
+                to_json_value(v, &error),
+                Err(JsonConversionError::DuplicateKey { .. }),
+            ));
+        } else {
+            assert_eq!(first, to_json_value(v, &error)?);
+        }
+    }
+
+    assert_eq!(
+        JsonConversionError::DuplicateKey {
+            key: "b".to_string()
+        },
+        to_json_value(lua_value(b"{a = {b = 1, b = 2}}", MAX_DEPTH)?, &error).unwrap_err(),
+    );
+    Ok(())
+}
+
 #[test]
 #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
 fn table_coersion() -> Result {
@@ -245,3 +299,186 @@ fn ints() -> Result {
 
     Ok(())
 }
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn try_from_json() -> Result {
+    // serde_json sorts object keys by default
+    assert_eq!(
+        lua_value(
+            br#"{empty = {}, list = {1, 2.5, nil}, name = "a", ["not a name"] = true}"#,
+            MAX_DEPTH
+        )?,
+        LuaValue::try_from(json!({
+            "name": "a",
+            "not a name": true,
+            "list": [1, 2.5, null],
+            "empty": [],
+        }))?
+    );
+
+    // Integers in the i64 range are exact, and floats stay floats
+    assert_eq!(
+        LuaNumber::Integer(i64::MIN),
+        LuaNumber::try_from(serde_json::Number::from(i64::MIN))?
+    );
+    assert_eq!(
+        LuaNumber::Integer(i64::MAX),
+        LuaNumber::try_from(serde_json::Number::from(i64::MAX))?
+    );
+    assert_eq!(LuaValue::float(1.), LuaValue::try_from(json!(1.0))?);
+    assert_eq!(
+        LuaValue::float(u64::MAX as f64),
+        LuaValue::try_from(json!(u64::MAX))?
+    );
+
+    Ok(())
+}
+
+/// `LuaValue::pointer()` resolves pointers the same way as on the converted JSON value
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn pointer() -> Result {
+    let inputs: [&[u8]; 7] = [
+        br#"{players = {{name = "alice", hp = 3}, {name = "bob", [true] = false}}}"#,
+        b"{[1] = 1, 2, [2] = 3, 4}",
+        b"{['1'] = 1, [1] = 2, [2] = 3, ['2'] = 4}",
+        b"{1, {2, {3, nil}}, x = 1.5, [1.5] = 'y'}",
+        br#"{["a/b"] = 1, ["m~n"] = 2, [""] = 3, [" "] = 4}"#,
+        b"{}",
+        b"'hello'",
+    ];
+    let pointers = [
+        "",
+        "/",
+        "/0",
+        "/1",
+        "/2",
+        "/01",
+        "/-1",
+        "/true",
+        "/x",
+        "/1.5",
+        "/1/0",
+        "/1/1",
+        "/1/1/0",
+        "/1/1/1",
+        "/2/1/0",
+        "/a~1b",
+        "/m~0n",
+        "/ ",
+        "/players",
+        "/players/0/name",
+        "/players/1/name",
+        "/players/1/true",
+        "/players/2",
+        "/players/name",
+        "players",
+    ];
+
+    for lua in inputs {
+        let v = lua_value(lua, MAX_DEPTH)?;
+        let json = to_json_value(v.clone(), &DEFAULT_OPTS)?;
+        for p in pointers {
+            let actual = v
+                .pointer(p)
+                .map(|v| to_json_value(v.into_owned(), &DEFAULT_OPTS))
+                .transpose()?;
+            assert_eq!(
+                json.pointer(p),
+                actual.as_ref(),
+                "{} {p:?}",
+                lua.escape_ascii()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn schema() -> Result {
+    let schema = |lua: &[u8]| -> Result<serde_json::Value> {
+        let mut s = json_schema(&lua_value(lua, MAX_DEPTH)?, &DEFAULT_OPTS)?;
+        assert_eq!(
+            Some(json!("https://json-schema.org/draft/2020-12/schema")),
+            s.as_object_mut().unwrap().remove("$schema")
+        );
+        Ok(s)
+    };
+
+    assert_eq!(json!({"type": "null"}), schema(b"nil")?);
+    assert_eq!(json!({"type": "boolean"}), schema(b"true")?);
+    assert_eq!(json!({"type": "integer"}), schema(b"1")?);
+    assert_eq!(json!({"type": "number"}), schema(b"1.5")?);
+    assert_eq!(json!({"type": "string"}), schema(b"'hi'")?);
+    assert_eq!(json!({"type": "object"}), schema(b"{}")?);
+
+    // Integers and floats merge into numbers
+    assert_eq!(
+        json!({"type": "array", "items": {"type": "number"}}),
+        schema(b"{1, 2.5, 3}")?
+    );
+    assert_eq!(
+        json!({"type": "array", "items": {"type": "integer"}}),
+        schema(b"{1, 2, 3}")?
+    );
+
+    // Unmergeable types
+    assert_eq!(
+        json!({"type": "array", "items": {"anyOf": [
+            {"type": "string"},
+            {"type": "boolean"},
+            {"type": "array", "items": {"type": "integer"}},
+        ]}}),
+        schema(b"{'a', true, 'b', {1}, false, {2}}")?
+    );
+
+    // Mixed keys follow Lua's rules, and later entries take precedence
+    assert_eq!(
+        json!({
+            "type": "object",
+            "properties": {
+                "1": {"type": "string"},
+                "2": {"type": "boolean"},
+                "a": {"type": "integer"},
+            },
+            "required": ["1", "a", "2"],
+        }),
+        schema(b"{[1] = 1, a = 2, 'x', true}")?
+    );
+
+    // Only keys in every object are required
+    assert_eq!(
+        json!({"type": "array", "items": {
+            "type": "object",
+            "properties": {
+                "a": {"type": "integer"},
+                "b": {"anyOf": [{"type": "integer"}, {"type": "null"}]},
+                "c": {"type": "object"},
+            },
+            "required": ["a"],
+        }}),
+        schema(b"{{a = 1, b = 2}, {c = {}, a = 3}, {a = 4, b = nil}}")?
+    );
+
+    // Non-finite floats can't be converted, but still have a schema
+    assert_eq!(json!({"type": "number"}), schema(b"1e999")?);
+
+    assert_eq!(
+        JsonConversionError::TableKeyedWithTable,
+        json_schema(&lua_value(b"{[{}] = 1}", MAX_DEPTH)?, &DEFAULT_OPTS).unwrap_err()
+    );
+    assert!(matches!(
+        json_schema(&lua_value(b"{'\\xff'}", MAX_DEPTH)?, &DEFAULT_OPTS),
+        Err(JsonConversionError::Utf8Error(_))
+    ));
+    assert!(json_schema(
+        &lua_value(b"{['\\xff'] = 1}", MAX_DEPTH)?,
+        JsonConversionOptions::new().lossy_string(true)
+    )
+    .is_ok());
+
+    Ok(())
+}
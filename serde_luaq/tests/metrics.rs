@@ -0,0 +1,172 @@
+//! Parser metrics tests, which check that adversarial inputs don't make the parser do excessive
+//! work.
+use serde_luaq::{BareIdentifiers, LuaFormat, ParseMetrics, Parser, ParserBuilder};
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+wasm_bindgen_test_configure!(run_in_browser);
+
+/// Maximum table depth used by the `fuzz-target` crate.
+const FUZZ_MAX_DEPTH: u16 = 200;
+
+/// Inputs from the `fuzz-target` crate's corpus.
+const FUZZ_CORPUS: &[(&str, &[u8])] = &[
+    (
+        "braces_8k",
+        include_bytes!("../../fuzz-target/in/braces_8k.lua"),
+    ),
+    (
+        "dental_plan",
+        include_bytes!("../../fuzz-target/in/dental_plan.lua"),
+    ),
+    ("hello", include_bytes!("../../fuzz-target/in/hello.lua")),
+    (
+        "integers_dec",
+        include_bytes!("../../fuzz-target/in/integers_dec.lua"),
+    ),
+    (
+        "integers_hex",
+        include_bytes!("../../fuzz-target/in/integers_hex.lua"),
+    ),
+    ("logo", include_bytes!("../../fuzz-target/in/logo.lua")),
+    (
+        "numbers",
+        include_bytes!("../../fuzz-target/in/numbers.lua"),
+    ),
+];
+
+/// Parses inputs of `n` and `2 * n` units from `make`, and checks that the parser does at most
+/// twice as much backtracking for the larger input (plus a little slack), so that the work it does
+/// grows linearly with the size of the input.
+fn check_linear(p: &Parser, format: LuaFormat, n: usize, make: impl Fn(usize) -> Vec<u8>) {
+    let small = make(n);
+    let large = make(n * 2);
+    let (_, s) = p.parse_with_metrics(&small, format);
+    let (_, l) = p.parse_with_metrics(&large, format);
+    let input = large.escape_ascii();
+
+    assert!(
+        l.backtracks <= s.backtracks * 2 + 2,
+        "{s:?} then {l:?} for {input}"
+    );
+    assert!(
+        l.rescanned <= s.rescanned * 2 + 16,
+        "{s:?} then {l:?} for {input}"
+    );
+    assert!(l.rescanned <= large.len() * 4, "{l:?} for {input}");
+    assert!(l.max_depth <= p.options().max_depth, "{l:?} for {input}");
+}
+
+/// Inputs from the fuzzing corpus parse without backtracking, except to collect error details.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn fuzz_corpus() {
+    let p = Parser::builder().max_depth(FUZZ_MAX_DEPTH).build();
+    for (name, b) in FUZZ_CORPUS {
+        let (r, m) = p.parse_with_metrics(b, LuaFormat::Script);
+        if r.is_ok() {
+            assert_eq!(0, m.backtracks, "{name}: {m:?}");
+            assert_eq!(0, m.rescanned, "{name}: {m:?}");
+        } else {
+            // peg parses the input again to collect the expected tokens.
+            assert!(m.backtracks <= 1, "{name}: {m:?}");
+            assert!(m.rescanned <= b.len(), "{name}: {m:?}");
+        }
+    }
+
+    let (r, m) = p.parse_with_metrics(FUZZ_CORPUS[0].1, LuaFormat::Script);
+    assert!(r.is_err());
+    assert_eq!(FUZZ_MAX_DEPTH, m.max_depth);
+
+    let (r, m) = p.parse_with_metrics(FUZZ_CORPUS[1].1, LuaFormat::Script);
+    assert!(r.is_ok());
+    assert_eq!(119, m.max_depth);
+}
+
+/// Basic counting of depth and backtracking.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn counters() {
+    let p = Parser::default();
+    let (r, m) = p.parse_with_metrics(b"{1, {2, {3}}, {4}}", LuaFormat::Value);
+    assert!(r.is_ok());
+    assert_eq!(3, m.max_depth);
+    assert_eq!(0, m.backtracks);
+    assert_eq!(0, m.rescanned);
+
+    let (r, m) = p.parse_with_metrics(b"a = 1\nb = 'x'", LuaFormat::Script);
+    assert!(r.is_ok());
+    assert_eq!(ParseMetrics::default(), m);
+
+    // A bare identifier is parsed as a key first, then again as a value.
+    let p = Parser::builder()
+        .bare_identifiers(BareIdentifiers::String)
+        .build();
+    let (r, m) = p.parse_with_metrics(b"{foo, bar = 1}", LuaFormat::Value);
+    assert!(r.is_ok());
+    assert_eq!(1, m.max_depth);
+    assert_eq!(1, m.backtracks);
+    assert_eq!(3, m.rescanned);
+
+    // A global assignment is parsed again as a field assignment.
+    let p = Parser::builder().field_assignments(true).build();
+    let (r, m) = p.parse_with_metrics(b"a = {}\na.b = 1", LuaFormat::Script);
+    assert!(r.is_ok());
+    assert_eq!(1, m.backtracks);
+    assert_eq!(1, m.rescanned);
+
+    // A failed parse is parsed again to collect error details.
+    let (r, m) = Parser::default().parse_with_metrics(b"x = 1 y = {", LuaFormat::Script);
+    assert!(r.is_err());
+    assert_eq!(1, m.backtracks);
+    assert_eq!(7, m.rescanned);
+}
+
+/// Adversarial inputs don't make the parser backtrack more than linearly.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn adversarial() {
+    let deep = ParserBuilder::new().max_depth(FUZZ_MAX_DEPTH).build();
+    let bare = Parser::builder()
+        .bare_identifiers(BareIdentifiers::String)
+        .max_depth(FUZZ_MAX_DEPTH)
+        .build();
+    let fields = Parser::builder()
+        .field_assignments(true)
+        .string_concatenation(true)
+        .max_depth(FUZZ_MAX_DEPTH)
+        .build();
+
+    // Deeply nested tables which are never closed
+    let open = |n| b"{".repeat(n);
+    check_linear(&deep, LuaFormat::Value, 50, open);
+
+    // Deeply nested tables which are closed one short
+    let short = |n| [b"{".repeat(n), b"1".to_vec(), b"}".repeat(n - 1)].concat();
+    check_linear(&deep, LuaFormat::Value, 50, short);
+    check_linear(&bare, LuaFormat::Value, 50, short);
+
+    // Nested tables with keys, where the innermost value is invalid. Each key is parsed again as
+    // a value.
+    let keyed = |n| [b"{a = ".repeat(n), b"?".to_vec(), b"}".repeat(n)].concat();
+    let (_, m) = deep.parse_with_metrics(&keyed(100), LuaFormat::Value);
+    assert_eq!(100, m.max_depth);
+    assert_eq!(201, m.backtracks);
+    check_linear(&deep, LuaFormat::Value, 50, keyed);
+    check_linear(&bare, LuaFormat::Value, 50, keyed);
+
+    // Long lists of bare identifiers, ending with a dotted name
+    let idents = |n| [b"{".to_vec(), b"abc, ".repeat(n), b"x.y}".to_vec()].concat();
+    check_linear(&bare, LuaFormat::Value, 500, idents);
+
+    // Many statements, ending with an unterminated one
+    let script = |n| [b"a = 1; ".repeat(n), b"b.c.d = 'x' .. ".to_vec()].concat();
+    check_linear(&deep, LuaFormat::Script, 500, script);
+    check_linear(&fields, LuaFormat::Script, 500, script);
+    check_linear(&fields, LuaFormat::ScriptReturn, 500, script);
+
+    // Many field assignments
+    let assign = |n| [b"t = {}\n".to_vec(), b"t.a.b = t.c\n".repeat(n)].concat();
+    check_linear(&fields, LuaFormat::Script, 500, assign);
+}
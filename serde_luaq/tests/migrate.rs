@@ -0,0 +1,173 @@
+//! Versioned migration tests.
+mod common;
+use crate::common::MAX_DEPTH;
+use serde_luaq::{
+    lua_value, script, LuaNumber, LuaTableEntry, LuaValue, MigrationError, Migrations,
+};
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+wasm_bindgen_test_configure!(run_in_browser);
+
+type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Appends a `v{version} = true` entry to a table.
+fn mark(
+    version: i64,
+) -> impl for<'a> Fn(LuaValue<'a>) -> std::result::Result<LuaValue<'a>, MigrationError> {
+    move |v| {
+        let LuaValue::Table(mut entries) = v else {
+            return Err(MigrationError::Step {
+                version,
+                message: "not a table".to_string(),
+            });
+        };
+        entries.push(LuaTableEntry::from((format!("v{version}"), true.into())));
+        Ok(LuaValue::Table(entries))
+    }
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn steps_in_order() -> Result {
+    // Register out of order
+    let migrations = Migrations::new(["version"])
+        .step(2, mark(2))
+        .step(1, mark(1))
+        .step(3, mark(3));
+    assert_eq!(Some(4), migrations.latest_version());
+
+    let v = migrations.migrate(lua_value(b"{version = 1}", MAX_DEPTH)?)?;
+    assert_eq!(
+        lua_value(b"{version = 4, v1 = true, v2 = true, v3 = true}", MAX_DEPTH)?,
+        v
+    );
+
+    let v = migrations.migrate(lua_value(b"{version = 3}", MAX_DEPTH)?)?;
+    assert_eq!(lua_value(b"{version = 4, v3 = true}", MAX_DEPTH)?, v);
+
+    // Already current, or newer
+    for version in [4, 100] {
+        let expected = LuaValue::from_iter([("version", LuaValue::integer(version))]);
+        assert_eq!(expected, migrations.migrate(expected.clone())?);
+    }
+
+    // Older than the oldest migration
+    let v = migrations.migrate(lua_value(b"{version = 0}", MAX_DEPTH)?)?;
+    assert_eq!(lua_value(b"{version = 0}", MAX_DEPTH)?, v);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn nested_path() -> Result {
+    let migrations = Migrations::new(["meta", "version"]).step(1, mark(1));
+
+    let v = migrations.migrate(lua_value(
+        b"{meta = {version = 1}, ['meta'] = {['version'] = 1}}",
+        MAX_DEPTH,
+    )?)?;
+
+    // Only the last entry is updated
+    assert_eq!(
+        lua_value(
+            b"{meta = {version = 1}, ['meta'] = {['version'] = 2}, v1 = true}",
+            MAX_DEPTH
+        )?,
+        v
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn script_values() -> Result {
+    let migrations = Migrations::new(["version"]).step(1, mark(1));
+    let s = script(b"version = 1\nname = 'test'", MAX_DEPTH)?;
+    let v = migrations.migrate(LuaValue::Table(
+        s.into_iter().map(LuaTableEntry::from).collect(),
+    ))?;
+
+    assert_eq!(
+        lua_value(b"{version = 2, name = 'test', v1 = true}", MAX_DEPTH)?,
+        v
+    );
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn missing_version() -> Result {
+    let migrations = Migrations::new(["version"]).step(0, mark(0));
+    assert_eq!(
+        Err(MigrationError::MissingVersion),
+        migrations.migrate(lua_value(b"{}", MAX_DEPTH)?)
+    );
+
+    let migrations = migrations.default_version(0);
+    let v = migrations.migrate(lua_value(b"{}", MAX_DEPTH)?)?;
+    assert_eq!(lua_value(b"{v0 = true, version = 1}", MAX_DEPTH)?, v);
+
+    // Parent tables must exist
+    let migrations = Migrations::new(["meta", "version"])
+        .default_version(0)
+        .step(0, mark(0));
+    assert_eq!(
+        Err(MigrationError::MissingVersion),
+        migrations.migrate(lua_value(b"{}", MAX_DEPTH)?)
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn invalid_version() -> Result {
+    let migrations = Migrations::new(["version"]).step(1, mark(1));
+    for b in [
+        b"{version = 1.0}".as_slice(),
+        b"{version = '1'}",
+        b"{version = {}}",
+    ] {
+        assert_eq!(
+            Err(MigrationError::InvalidVersion),
+            migrations.migrate(lua_value(b, MAX_DEPTH)?)
+        );
+    }
+
+    let migrations = Migrations::new(["version"]).step(i64::MAX, mark(0));
+    assert_eq!(
+        Err(MigrationError::InvalidVersion),
+        migrations.migrate(LuaValue::from_iter([(
+            "version",
+            LuaValue::Number(LuaNumber::Integer(i64::MAX))
+        )]))
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn step_error() -> Result {
+    // Empty path means the version is the value itself
+    let migrations = Migrations::new(Vec::<String>::new())
+        .step(1, |v| Ok(v))
+        .step(2, mark(2));
+    assert_eq!(
+        Err(MigrationError::Step {
+            version: 2,
+            message: "not a table".to_string()
+        }),
+        migrations.migrate(LuaValue::integer(1))
+    );
+    assert_eq!(
+        LuaValue::integer(3),
+        migrations.migrate(LuaValue::integer(3))?
+    );
+
+    Ok(())
+}
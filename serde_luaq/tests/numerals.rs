@@ -1,8 +1,11 @@
 //! Numeral literal tests
+// Tests use literals which look like (but aren't) well-known constants.
+#![allow(clippy::approx_constant)]
 mod common;
 
+use crate::common::MAX_DEPTH;
 use crate::common::{check, should_error};
-use serde_luaq::LuaValue;
+use serde_luaq::{lua_value, LuaNumber, LuaTableEntry, LuaValue, SyntaxError};
 
 #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
 use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
@@ -252,3 +255,122 @@ fn special_floats() {
     check(b"1e9999", LuaValue::float(f64::INFINITY));
     check(b"-1e9999", LuaValue::float(f64::NEG_INFINITY));
 }
+
+/// Unary minus with whitespace or parentheses
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn unary_minus() {
+    check(b"- 5", LuaValue::integer(-5));
+    check(b"-\t\n5", LuaValue::integer(-5));
+    check(b"-(5)", LuaValue::integer(-5));
+    check(b"- ( 5 )", LuaValue::integer(-5));
+    check(b"-(-5)", LuaValue::integer(5));
+    check(b"- -5", LuaValue::integer(5));
+    check(b"- 0xff", LuaValue::integer(-0xff));
+    check(b"-(1.5)", LuaValue::float(-1.5));
+    check(b"- 1e9999", LuaValue::float(f64::NEG_INFINITY));
+    check(b"-(0/0)", LuaValue::float(f64::NAN));
+
+    // Wraps around like Lua
+    check(b"-(-9223372036854775808)", LuaValue::integer(i64::MIN));
+    check(b"-(0x8000000000000000)", LuaValue::integer(i64::MIN));
+
+    // Doesn't fit in an integer, so is a float
+    check(
+        b"- 9223372036854775808",
+        LuaValue::float(-9223372036854776000.),
+    );
+
+    // In tables
+    check(
+        b"{- 5, [-(1)] = 2}",
+        LuaValue::Table(vec![
+            LuaTableEntry::NumberValue(LuaNumber::Integer(-5)),
+            LuaTableEntry::KeyValue(Box::new((LuaValue::integer(-1), LuaValue::integer(2)))),
+        ]),
+    );
+
+    // `--` starts a comment
+    assert!(lua_value(b"--5", MAX_DEPTH).is_err());
+    assert!(lua_value(b"{--5}", MAX_DEPTH).is_err());
+    check(b"- --5\n5", LuaValue::integer(-5));
+    should_error(b"-(--5)");
+
+    // Only numeric literals, with one level of parentheses
+    should_error(b"-");
+    should_error(b"-()");
+    should_error(b"-(5");
+    should_error(b"-((5))");
+    should_error(b"-(- 5)");
+    should_error(b"- - 5");
+    should_error(b"-(5 + 1)");
+    should_error(b"-'5'");
+    should_error(b"-true");
+    should_error(b"(5)");
+    should_error(b"+(5)");
+}
+
+/// Numerals from other Lua dialects give errors which say what was found
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn foreign_numerals() {
+    for (lua, position, found) in [
+        (
+            &b"0b101"[..],
+            5,
+            "binary literals like 0b101 are a Luau extension",
+        ),
+        (
+            b"-0B1_0",
+            6,
+            "binary literals like 0b101 are a Luau extension",
+        ),
+        (b"1LL", 3, "LL and ULL suffixes are a LuaJIT extension"),
+        (b"0x10ULL", 7, "LL and ULL suffixes are a LuaJIT extension"),
+        (b"{-5ll}", 5, "LL and ULL suffixes are a LuaJIT extension"),
+        (
+            b"12.5i",
+            5,
+            "imaginary numbers like 12.5i are a LuaJIT extension",
+        ),
+        (
+            b"1e3I",
+            4,
+            "imaginary numbers like 12.5i are a LuaJIT extension",
+        ),
+        (
+            b"1_000",
+            5,
+            "digit separators like 1_000 are a Luau extension",
+        ),
+        (
+            b"0x_ff",
+            5,
+            "digit separators like 1_000 are a Luau extension",
+        ),
+        (
+            b"{a = 1_0}",
+            8,
+            "digit separators like 1_000 are a Luau extension",
+        ),
+    ] {
+        let err = SyntaxError::from(lua_value(lua, MAX_DEPTH).unwrap_err());
+        assert_eq!(position, err.position, "{}", lua.escape_ascii());
+        assert_eq!(
+            vec![format!("Lua 5.4 number ({found})")],
+            err.expected,
+            "{}",
+            lua.escape_ascii()
+        );
+    }
+
+    // Other invalid numerals still give a generic error
+    for lua in [&b"1a"[..], b"0x", b"1.2.3", b"0b"] {
+        let err = SyntaxError::from(lua_value(lua, MAX_DEPTH).unwrap_err());
+        assert!(
+            !err.expected.iter().any(|e| e.starts_with("Lua 5.4 number")),
+            "{}: {err}",
+            lua.escape_ascii()
+        );
+    }
+}
@@ -0,0 +1,335 @@
+//! Sampling entries from large tables.
+mod common;
+use crate::common::MAX_DEPTH;
+use serde_luaq::{
+    lua_value, CancellationToken, Error, LuaFormat, LuaNumber, LuaTableEntry, LuaValue, Parser,
+    Sample, Sampling,
+};
+use std::{collections::BTreeSet, fmt::Write as _};
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+wasm_bindgen_test_configure!(run_in_browser);
+
+type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Returns the positions of the entries in a sample.
+fn indexes(s: &Sample) -> Vec<usize> {
+    s.entries.iter().map(|(i, _)| *i).collect()
+}
+
+/// Builds a table of the integers `1..=n`.
+fn sequence(n: usize) -> Vec<u8> {
+    let mut b = String::from("{");
+    for i in 1..=n {
+        write!(b, "{i}, ").unwrap();
+    }
+    b.push('}');
+    b.into_bytes()
+}
+
+fn parser() -> Parser {
+    Parser::builder().max_depth(MAX_DEPTH).build()
+}
+
+/// Sampling every entry gives the same entries as parsing the whole table.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn every_entry() -> Result {
+    let p = parser();
+    let b = br#"{1, "two", a = {3}, ["b c"] = nil, [4.5] = true; false, nil, [[long]], {x = 1},}"#;
+    let LuaValue::Table(expected) = lua_value(b, MAX_DEPTH)? else {
+        panic!("expected table");
+    };
+
+    for n in [0, 1] {
+        let s = p
+            .sample(b, LuaFormat::Value, "", &Sampling::every(n))?
+            .unwrap();
+        assert_eq!(expected.len(), s.len);
+        assert_eq!(
+            expected,
+            s.entries.into_iter().map(|(_, e)| e).collect::<Vec<_>>()
+        );
+    }
+
+    let s = p
+        .sample(b"{}", LuaFormat::Value, "", &Sampling::every(1))?
+        .unwrap();
+    assert_eq!(0, s.len);
+    assert!(s.entries.is_empty());
+    Ok(())
+}
+
+/// Finding a table by its path.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn paths() -> Result {
+    let p = parser();
+    let every = Sampling::every(1);
+    let b = br#"return {
+        "first",
+        {1, 2},
+        players = {
+            {name = "alice", inventory = {"sword"}},
+            {name = "bob", inventory = {"shield", "potion"}},
+        },
+        ["a b"] = {c = {true}},
+        [5] = {"five"},
+    }"#;
+
+    let sampled = |path| -> Result<Option<Vec<LuaTableEntry>>> {
+        Ok(p.sample(b, LuaFormat::Return, path, &every)?
+            .map(|s| s.entries.into_iter().map(|(_, e)| e).collect()))
+    };
+
+    assert_eq!(
+        Some(vec![
+            LuaTableEntry::from(LuaValue::integer(1)),
+            LuaTableEntry::from(LuaValue::integer(2)),
+        ]),
+        sampled("[2]")?
+    );
+    assert_eq!(
+        Some(vec![
+            LuaTableEntry::from(LuaValue::from("shield")),
+            LuaTableEntry::from(LuaValue::from("potion")),
+        ]),
+        sampled("players[2].inventory")?
+    );
+    assert_eq!(
+        Some(vec![LuaTableEntry::from(LuaValue::Boolean(true))]),
+        sampled(r#"["a b"].c"#)?
+    );
+    assert_eq!(
+        Some(vec![LuaTableEntry::from(LuaValue::from("five"))]),
+        sampled("[5]")?
+    );
+    assert_eq!(Some(2), sampled("players")?.map(|e| e.len()));
+    assert_eq!(Some(5), sampled("")?.map(|e| e.len()));
+
+    // Not tables
+    assert_eq!(None, sampled("[1]")?);
+    assert_eq!(None, sampled("players[1].name")?);
+
+    // Not found
+    assert_eq!(None, sampled("[3]")?);
+    assert_eq!(None, sampled("player")?);
+    assert_eq!(None, sampled("players[3]")?);
+    assert_eq!(None, sampled("players[1].name.x")?);
+    assert_eq!(None, sampled("a")?);
+    Ok(())
+}
+
+/// Sampling variables in scripts.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn scripts() -> Result {
+    let p = parser();
+    let every = Sampling::every(2);
+    let b = b"a = {1, 2, 3}\nab = 'x'; ; b = {c = {4, 5, 6}}\n";
+
+    let s = p.sample(b, LuaFormat::Script, "a", &every)?.unwrap();
+    assert_eq!(vec![0, 2], indexes(&s));
+    let s = p.sample(b, LuaFormat::Script, "b.c", &every)?.unwrap();
+    assert_eq!(
+        vec![
+            (0, LuaTableEntry::from(LuaValue::integer(4))),
+            (2, LuaTableEntry::from(LuaValue::integer(6))),
+        ],
+        s.entries
+    );
+    assert_eq!(None, p.sample(b, LuaFormat::Script, "ab", &every)?);
+    assert_eq!(None, p.sample(b, LuaFormat::Script, "c", &every)?);
+    assert_eq!(None, p.sample(b, LuaFormat::Script, "", &every)?);
+
+    // The return statement isn't searched
+    let b = b"a = {1}\nreturn {a = {2}}";
+    assert!(p.sample(b, LuaFormat::Script, "a", &every).is_ok());
+    assert!(p.sample(b, LuaFormat::Script, "b", &every).is_err());
+    assert_eq!(None, p.sample(b, LuaFormat::ScriptReturn, "b", &every)?);
+
+    // Statement limits
+    let p = Parser::builder().max_statements(1).build();
+    let b = b"a = 1\nb = {1}";
+    assert_eq!(
+        Error::TooManyStatements { limit: 1 },
+        p.sample(b, LuaFormat::Script, "b", &every).unwrap_err()
+    );
+    assert!(p.sample(b, LuaFormat::Script, "a", &every).is_ok());
+    Ok(())
+}
+
+/// Random samples are reproducible, and roughly uniform.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn random() -> Result {
+    let p = parser();
+    let b = sequence(1000);
+
+    let s = p
+        .sample(&b, LuaFormat::Value, "", &Sampling::random(10, 1))?
+        .unwrap();
+    assert_eq!(1000, s.len);
+    assert_eq!(10, s.entries.len());
+    let i = indexes(&s);
+    assert!(i.windows(2).all(|w| w[0] < w[1]), "{i:?}");
+    for (i, e) in &s.entries {
+        assert_eq!(
+            LuaTableEntry::NumberValue(LuaNumber::Integer(*i as i64 + 1)),
+            *e
+        );
+    }
+
+    // Same seed, same sample
+    let again = p
+        .sample(&b, LuaFormat::Value, "", &Sampling::random(10, 1))?
+        .unwrap();
+    assert_eq!(s, again);
+    let other = p
+        .sample(&b, LuaFormat::Value, "", &Sampling::random(10, 2))?
+        .unwrap();
+    assert_ne!(s, other);
+
+    // Every entry is chosen, and from all parts of the table
+    let mut seen = BTreeSet::new();
+    for seed in 0..100 {
+        let s = p
+            .sample(&b, LuaFormat::Value, "", &Sampling::random(10, seed))?
+            .unwrap();
+        seen.extend(indexes(&s).into_iter().map(|i| i / 100));
+    }
+    assert_eq!((0..10).collect::<BTreeSet<_>>(), seen);
+
+    // Small tables
+    let s = p
+        .sample(b"{1, 2}", LuaFormat::Value, "", &Sampling::random(10, 1))?
+        .unwrap();
+    assert_eq!(vec![0, 1], indexes(&s));
+    let s = p
+        .sample(b"{1, 2}", LuaFormat::Value, "", &Sampling::random(0, 1))?
+        .unwrap();
+    assert!(s.entries.is_empty());
+    Ok(())
+}
+
+/// Weighted samples prefer entries with higher weights.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn weighted() -> Result {
+    let p = parser();
+    let b = br#"{
+        {name = "common", weight = 100},
+        {name = "rare", weight = 1},
+        {name = "never", weight = 0},
+        {name = "broken"},
+    }"#;
+    let weight = |e: &LuaTableEntry| match e.value() {
+        Some(LuaValue::Table(t)) => t
+            .iter()
+            .find(|e| e.key() == Some(LuaValue::from("weight")))
+            .and_then(|e| e.value()?.as_f64())
+            .unwrap_or(f64::NAN),
+        _ => f64::NAN,
+    };
+    let sampling = Sampling::weighted(1, 0, weight);
+    assert_eq!(sampling, sampling.clone());
+    assert_ne!(sampling, Sampling::weighted(1, 0, weight));
+
+    let mut counts = [0; 4];
+    for seed in 0..200 {
+        let s = p
+            .sample(
+                b,
+                LuaFormat::Value,
+                "",
+                &Sampling::weighted(1, seed, weight),
+            )?
+            .unwrap();
+        assert_eq!(4, s.len);
+        counts[indexes(&s)[0]] += 1;
+    }
+    assert_eq!(0, counts[2]);
+    assert_eq!(0, counts[3]);
+    assert!(counts[0] > counts[1] * 10, "{counts:?}");
+
+    // Only entries with a positive weight are sampled
+    let s = p
+        .sample(b, LuaFormat::Value, "", &Sampling::weighted(4, 0, weight))?
+        .unwrap();
+    assert_eq!(vec![0, 1], indexes(&s));
+    Ok(())
+}
+
+/// Sampling a table in a large input.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn large() -> Result {
+    const N: usize = 100_000;
+    let p = parser();
+    let b = [
+        b"data = {skip = ".to_vec(),
+        sequence(N),
+        b", keep = ".to_vec(),
+        sequence(N),
+        b"}".to_vec(),
+    ]
+    .concat();
+
+    let s = p
+        .sample(&b, LuaFormat::Script, "data.keep", &Sampling::every(N / 4))?
+        .unwrap();
+    assert_eq!(N, s.len);
+    assert_eq!(vec![0, N / 4, N / 2, N * 3 / 4], indexes(&s));
+    Ok(())
+}
+
+/// Syntax errors, depth limits and cancellation.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn errors() -> Result {
+    let p = parser();
+    let every = Sampling::every(1);
+
+    // Errors before and inside the sampled table
+    assert!(p
+        .sample(b"{a = {1 2}, b = {}}", LuaFormat::Value, "b", &every)
+        .is_err());
+    assert!(p
+        .sample(b"{a = {1 2}, b = {}}", LuaFormat::Value, "a", &every)
+        .is_err());
+    assert!(p
+        .sample(b"{a = {1, 2,,}}", LuaFormat::Value, "a", &every)
+        .is_err());
+    assert!(p.sample(b"{a = 1", LuaFormat::Value, "b", &every).is_err());
+    assert!(p.sample(b"{1, 2}", LuaFormat::Return, "", &every).is_err());
+
+    // Input after the sampled table isn't checked
+    let s = p.sample(b"{a = {1, 2}, b = {{{", LuaFormat::Value, "a", &every)?;
+    assert_eq!(Some(2), s.map(|s| s.len));
+
+    // Depth limits are the same as parsing the whole input
+    let p = Parser::builder().max_depth(3).build();
+    let b = b"{a = {b = {1}}}";
+    assert!(p.value(b).is_ok());
+    assert!(p.sample(b, LuaFormat::Value, "a", &every).is_ok());
+    assert!(p.sample(b, LuaFormat::Value, "a.b", &every).is_ok());
+    let b = b"{a = {b = {{1}}}}";
+    assert!(p.value(b).is_err());
+    assert!(p.sample(b, LuaFormat::Value, "a", &every).is_err());
+    assert!(p.sample(b, LuaFormat::Value, "a.b", &every).is_err());
+    let b = b"{a = {b = {c = {}}}}";
+    assert!(p.sample(b, LuaFormat::Value, "a.b.c", &every).is_err());
+
+    // Cancellation
+    let token = CancellationToken::new();
+    token.cancel();
+    let p = Parser::builder().cancellation(Some(token)).build();
+    assert_eq!(
+        Error::Cancelled,
+        p.sample(&sequence(100), LuaFormat::Value, "", &every)
+            .unwrap_err()
+    );
+    Ok(())
+}
@@ -0,0 +1,878 @@
+//! Serde serialisation tests.
+mod common;
+use crate::common::MAX_DEPTH;
+use serde::{Deserialize, Serialize};
+use serde_luaq::{
+    check_roundtrip, estimate_serialized_size, from_slice, from_slice_with_options, from_value,
+    lua_value, to_string, to_value, to_vec, to_writer, DefaultFormatter, DeserializeOptions, Error,
+    FloatFormat, Formatter, LineEnding, LuaFormat, LuaNumber, LuaTableEntry, LuaValue,
+    ParseOptions, Parser, QuoteStyle, RoundTripReason, Serializer, DEFAULT_SERIALIZE_MAX_DEPTH,
+};
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+wasm_bindgen_test_configure!(run_in_browser);
+
+type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+enum Shape {
+    Empty,
+    Circle(f64),
+    Point(i64, i64),
+    Rect { w: u32, h: u32 },
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Save {
+    name: String,
+    level: u8,
+    alive: bool,
+    pet: Option<String>,
+    scores: Vec<i64>,
+    #[serde(with = "serde_bytes")]
+    raw: Vec<u8>,
+    shapes: Vec<Shape>,
+    flags: BTreeMap<String, bool>,
+    ids: BTreeMap<i64, char>,
+}
+
+fn example_save() -> Save {
+    Save {
+        name: "alice".to_string(),
+        level: 3,
+        alive: true,
+        pet: None,
+        scores: vec![10, -2],
+        raw: b"\xff\x00".to_vec(),
+        shapes: vec![
+            Shape::Empty,
+            Shape::Circle(1.5),
+            Shape::Point(1, 2),
+            Shape::Rect { w: 3, h: 4 },
+        ],
+        flags: BTreeMap::from([("a".to_string(), true), ("b c".to_string(), false)]),
+        ids: BTreeMap::from([(1, 'x'), (-5, 'y')]),
+    }
+}
+
+/// Convert structs to [`LuaValue`], and back again
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn round_trip() -> Result {
+    let save = example_save();
+    let v = to_value(&save)?;
+    assert_eq!(
+        lua_value(
+            br#"{
+                name = "alice",
+                level = 3,
+                alive = true,
+                pet = nil,
+                scores = {10, -2},
+                raw = "\xff\0",
+                shapes = {"Empty", {Circle = 1.5}, {Point = {1, 2}}, {Rect = {w = 3, h = 4}}},
+                flags = {a = true, ["b c"] = false},
+                ids = {[-5] = "y", [1] = "x"},
+            }"#,
+            MAX_DEPTH
+        )?,
+        v
+    );
+
+    // Identifiers become NameValue entries
+    let LuaValue::Table(entries) = &v else {
+        panic!("expected table: {v:?}");
+    };
+    assert!(matches!(entries[0], LuaTableEntry::NameValue(_)));
+
+    assert_eq!(save, from_value(&v)?);
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn errors() -> Result {
+    assert_eq!(LuaValue::integer(i64::MAX), to_value(&(i64::MAX as u64))?);
+    assert_eq!(
+        LuaValue::integer(i64::MIN),
+        to_value(&i128::from(i64::MIN))?
+    );
+    assert!(to_value(&u64::MAX).is_err());
+    assert!(to_value(&i128::MIN).is_err());
+
+    let err = to_value(&BTreeMap::from([("a", vec![0, u64::MAX])])).unwrap_err();
+    assert_eq!(Some("a[2]"), err.path());
+
+    // Lua doesn't allow nil or NaN keys
+    assert!(to_value(&BTreeMap::from([(None::<i64>, 1)])).is_err());
+
+    #[derive(Serialize)]
+    struct NanKey(#[serde(serialize_with = "nan_key")] ());
+    fn nan_key<S: serde::Serializer>(_: &(), s: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let mut m = s.serialize_map(Some(1))?;
+        m.serialize_entry(&f64::NAN, &1)?;
+        m.end()
+    }
+    assert!(to_value(&NanKey(())).is_err());
+
+    Ok(())
+}
+
+/// Write [`LuaValue`]s as Lua source, and read them back again
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn lua_source() -> Result {
+    let strict = Parser::builder().strict_q(true).build();
+    let check = |expected: &str, value: LuaValue<'_>| -> Result {
+        let lua = to_string(&value, LuaFormat::Value)?;
+        assert_eq!(expected, lua);
+        assert_eq!(lua.as_bytes(), to_vec(&value, LuaFormat::Value)?);
+        let mut out = Vec::new();
+        to_writer(&mut out, &value, LuaFormat::Value)?;
+        assert_eq!(lua.as_bytes(), out);
+
+        // NaN is never equal to itself
+        if !matches!(value, LuaValue::Number(LuaNumber::Float(f)) if f.is_nan()) {
+            assert_eq!(value, lua_value(lua.as_bytes(), MAX_DEPTH)?, "{lua}");
+            assert_eq!(value, strict.value(lua.as_bytes())?, "{lua}");
+        }
+        Ok(())
+    };
+
+    check("nil", LuaValue::Nil)?;
+    check("true", LuaValue::Boolean(true))?;
+    check("false", LuaValue::Boolean(false))?;
+
+    check("0", LuaValue::integer(0))?;
+    check("-12", LuaValue::integer(-12))?;
+    check("9223372036854775807", LuaValue::integer(i64::MAX))?;
+    check("0x8000000000000000", LuaValue::integer(i64::MIN))?;
+
+    check("0.0", LuaValue::float(0.))?;
+    check("-0.0", LuaValue::float(-0.))?;
+    check("2.5", LuaValue::float(2.5))?;
+    check("0.1", LuaValue::float(0.1))?;
+    check("1e+16", LuaValue::float(1e16))?;
+    check("1.5e+300", LuaValue::float(1.5e300))?;
+    check("-1e-300", LuaValue::float(-1e-300))?;
+    check("5e-324", LuaValue::float(f64::from_bits(1)))?;
+    check("1e9999", LuaValue::float(f64::INFINITY))?;
+    check("-1e9999", LuaValue::float(f64::NEG_INFINITY))?;
+    check("(0/0)", LuaValue::float(f64::NAN))?;
+
+    check(r#""""#, LuaValue::from(""))?;
+    check(r#""hello, world""#, LuaValue::from("hello, world"))?;
+    check(r#""\"\\""#, LuaValue::from(r#""\"#))?;
+    check("\"a\\\nb\"", LuaValue::from("a\nb"))?;
+    check(
+        r#""\r\0\1\27\127""#,
+        LuaValue::from(&b"\r\0\x01\x1b\x7f"[..]),
+    )?;
+    // Escapes followed by digits are padded
+    check(r#""\0001\0019""#, LuaValue::from(&b"\x001\x019"[..]))?;
+    check(
+        "\"caf\u{e9} \u{1F600}\"",
+        LuaValue::from("caf\u{e9} \u{1F600}"),
+    )?;
+    // Invalid UTF-8
+    check(r#""\255\1921\195""#, LuaValue::from(&b"\xff\xc01\xc3"[..]))?;
+
+    check("{}", LuaValue::Table(vec![]))?;
+    check(
+        r#"{1, 2.5, true, nil, "a", {}, a = 1, ["b c"] = 2, [3] = 4, [{}] = {x = {}}}"#,
+        lua_value(
+            br#"{1, 2.5, true, nil, "a", {}, a = 1, ["b c"] = 2, [3] = 4, [{}] = {x = {}}}"#,
+            MAX_DEPTH,
+        )?,
+    )?;
+
+    // Names which aren't valid identifiers are written as strings
+    assert_eq!(
+        r#"{["end"] = 1, ["a b"] = 2}"#,
+        to_string(
+            &LuaValue::Table(vec![
+                LuaTableEntry::NameValue(Box::new(("end".into(), LuaValue::integer(1)))),
+                LuaTableEntry::NameValue(Box::new(("a b".into(), LuaValue::integer(2)))),
+            ]),
+            LuaFormat::Value
+        )?,
+    );
+
+    Ok(())
+}
+
+/// Write structs as Lua source, and read them back again
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn write_lua() -> Result {
+    let save = example_save();
+    let lua = to_string(&save, LuaFormat::Value)?;
+    assert_eq!(
+        concat!(
+            r#"{name = "alice", level = 3, alive = true, pet = nil, scores = {10, -2}, "#,
+            r#"raw = "\255\0", "#,
+            r#"shapes = {"Empty", {Circle = 1.5}, {Point = {1, 2}}, {Rect = {w = 3, h = 4}}}, "#,
+            r#"flags = {a = true, ["b c"] = false}, ids = {[-5] = "y", [1] = "x"}}"#,
+        ),
+        lua,
+    );
+    assert_eq!(to_value(&save)?, lua_value(lua.as_bytes(), MAX_DEPTH)?);
+    assert_eq!(
+        save,
+        from_slice(lua.as_bytes(), LuaFormat::Value, MAX_DEPTH)?
+    );
+
+    let lua = to_string(&save, LuaFormat::Return)?;
+    assert!(lua.starts_with("return {name = "));
+    assert_eq!(
+        save,
+        from_slice(lua.as_bytes(), LuaFormat::Return, MAX_DEPTH)?
+    );
+
+    for format in [LuaFormat::Script, LuaFormat::ScriptReturn] {
+        let lua = to_string(&save, format)?;
+        assert!(lua.starts_with("name = \"alice\"\nlevel = 3\n"), "{lua}");
+        assert!(
+            lua.ends_with("\nids = {[-5] = \"y\", [1] = \"x\"}\n"),
+            "{lua}"
+        );
+        assert_eq!(save, from_slice(lua.as_bytes(), format, MAX_DEPTH)?);
+    }
+
+    // Tables as keys
+    let m = BTreeMap::from([((1, 2), "a"), ((3, 4), "b")]);
+    let lua = to_string(&m, LuaFormat::Value)?;
+    assert_eq!(r#"{[{1, 2}] = "a", [{3, 4}] = "b"}"#, lua);
+    assert_eq!(to_value(&m)?, lua_value(lua.as_bytes(), MAX_DEPTH)?);
+
+    #[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+    enum Key {
+        Empty,
+        Point(i64, i64),
+    }
+    let m = BTreeMap::from([(Key::Point(1, 2), 1), (Key::Empty, 2)]);
+    let lua = to_string(&m, LuaFormat::Value)?;
+    assert_eq!(r#"{Empty = 2, [{Point = {1, 2}}] = 1}"#, lua);
+    assert_eq!(to_value(&m)?, lua_value(lua.as_bytes(), MAX_DEPTH)?);
+
+    // Parsed values keep their implicit keys
+    let v = lua_value(
+        br#"{1, x = {}, nil, [true] = "t", [2.5] = {[{}] = 1}, [10] = false, "z"}"#,
+        MAX_DEPTH,
+    )?;
+    let lua = to_string(&v, LuaFormat::Value)?;
+    assert_eq!(
+        r#"{1, x = {}, nil, [true] = "t", [2.5] = {[{}] = 1}, [10] = false, "z"}"#,
+        lua
+    );
+    assert_eq!(v, lua_value(lua.as_bytes(), MAX_DEPTH)?);
+
+    // Errors
+    assert!(to_string(&(u128::from(u64::MAX) + 1), LuaFormat::Value).is_err());
+    assert!(to_string(&(i128::from(i64::MIN) - 1), LuaFormat::Value).is_err());
+    let err = to_string(
+        &BTreeMap::from([("a", vec![0, u128::MAX])]),
+        LuaFormat::Value,
+    )
+    .unwrap_err();
+    assert_eq!(Some("a[2]"), err.path());
+    let err = to_string(&Shape::Rect { w: 1, h: 2 }, LuaFormat::Value)?;
+    assert_eq!("{Rect = {w = 1, h = 2}}", err);
+    assert!(to_string(&BTreeMap::from([(None::<i64>, 1)]), LuaFormat::Value).is_err());
+    let nan_key = LuaValue::Table(vec![LuaTableEntry::KeyValue(Box::new((
+        LuaValue::float(f64::NAN),
+        LuaValue::integer(1),
+    )))]);
+    assert!(to_string(&nan_key, LuaFormat::Value).is_err());
+
+    // Scripts need a map or struct with identifier keys
+    assert!(to_string(&1, LuaFormat::Script).is_err());
+    assert!(to_string(&vec![1], LuaFormat::Script).is_err());
+    assert!(to_string(&BTreeMap::from([("a b", 1)]), LuaFormat::Script).is_err());
+    assert!(to_string(&BTreeMap::from([(1, 1)]), LuaFormat::Script).is_err());
+    assert!(to_string(&lua_value(b"{1, a = 2}", MAX_DEPTH)?, LuaFormat::Script).is_err());
+    assert_eq!(
+        "a = 1\nb = {c = 2}\n",
+        to_string(
+            &lua_value(b"{a = 1, b = {c = 2}}", MAX_DEPTH)?,
+            LuaFormat::Script
+        )?,
+    );
+
+    Ok(())
+}
+
+/// Formatter which writes strings in long brackets, and always writes keys in brackets.
+struct Bracketed;
+
+impl Formatter for Bracketed {
+    fn write_string<W: ?Sized + Write>(&mut self, w: &mut W, v: &[u8]) -> io::Result<()> {
+        QuoteStyle::LongBracket.write_string(w, v)
+    }
+
+    fn write_name<W: ?Sized + Write>(&mut self, w: &mut W, name: &str) -> io::Result<()> {
+        self.begin_key(w)?;
+        self.write_string(w, name.as_bytes())?;
+        self.end_key(w)
+    }
+
+    fn begin_key<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        w.write_all(b"[ ")
+    }
+
+    fn end_key<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        w.write_all(b" ]")
+    }
+
+    fn begin_entry<W: ?Sized + Write>(&mut self, w: &mut W, _first: bool) -> io::Result<()> {
+        w.write_all(b" ")
+    }
+
+    fn end_entry<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        w.write_all(b";")
+    }
+
+    fn begin_value<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        w.write_all(b"=")
+    }
+
+    fn write_float<W: ?Sized + Write>(&mut self, w: &mut W, v: f64) -> io::Result<()> {
+        write!(w, "{v:.3}")
+    }
+
+    fn end_statement<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        w.write_all(b";\r\n")
+    }
+}
+
+fn to_string_with<F: Formatter>(value: &impl Serialize, format: LuaFormat, f: F) -> Result<String> {
+    let mut ser = Serializer::with_formatter(Vec::new(), format, f);
+    value.serialize(&mut ser)?;
+    Ok(String::from_utf8(ser.into_inner())?)
+}
+
+/// Custom formatters
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn formatter() -> Result {
+    let save = example_save();
+    let lua = to_string_with(&save, LuaFormat::Value, Bracketed)?;
+    assert_eq!(
+        concat!(
+            "{ [ [[name]] ]=[[alice]]; [ [[level]] ]=3; [ [[alive]] ]=true; [ [[pet]] ]=nil; ",
+            "[ [[scores]] ]={ 10; -2;}; [ [[raw]] ]=\"\\255\\0\"; ",
+            "[ [[shapes]] ]={ [[Empty]]; { [ [[Circle]] ]=1.500;}; ",
+            "{ [ [[Point]] ]={ 1; 2;};}; { [ [[Rect]] ]={ [ [[w]] ]=3; [ [[h]] ]=4;};};}; ",
+            "[ [[flags]] ]={ [ [[a]] ]=true; [ [[b c]] ]=false;}; ",
+            "[ [[ids]] ]={ [ -5 ]=[[y]]; [ 1 ]=[[x]];};}",
+        ),
+        lua,
+    );
+    assert_eq!(
+        save,
+        from_slice(lua.as_bytes(), LuaFormat::Value, MAX_DEPTH)?
+    );
+
+    let lua = to_string_with(&save, LuaFormat::Script, Bracketed)?;
+    assert!(
+        lua.starts_with("name = [[alice]];\r\nlevel = 3;\r\n"),
+        "{lua}"
+    );
+    assert_eq!(
+        save,
+        from_slice(lua.as_bytes(), LuaFormat::Script, MAX_DEPTH)?
+    );
+
+    // Long bracket strings pick a level which doesn't appear in the string
+    for (s, expected) in [
+        ("", "[[]]"),
+        ("a]]b", "[=[a]]b]=]"),
+        ("a]", "[=[a]]=]"),
+        ("]]]=]", "[==[]]]=]]==]"),
+        ("\nabc\n", "[[\n\nabc\n]]"),
+        ("tab\there", "[[tab\there]]"),
+        ("cr\r", "\"cr\\r\""),
+        ("nul\0", "\"nul\\0\""),
+        (
+            "]]]=]]==]]===]]====]]=====]",
+            "\"]]]=]]==]]===]]====]]=====]\"",
+        ),
+    ] {
+        let lua = to_string_with(&s, LuaFormat::Value, Bracketed)?;
+        assert_eq!(expected, lua, "{s:?}");
+        assert_eq!(
+            LuaValue::from(s),
+            lua_value(lua.as_bytes(), MAX_DEPTH)?,
+            "{s:?}"
+        );
+    }
+
+    // Single quotes
+    let mut out = Vec::new();
+    QuoteStyle::Single.write_string(&mut out, b"'\"\\\n\x80")?;
+    assert_eq!(&b"'\\'\"\\\\\\\n\\128'"[..], out);
+    assert_eq!(
+        LuaValue::from(&b"'\"\\\n\x80"[..]),
+        lua_value(&out, MAX_DEPTH)?
+    );
+
+    Ok(())
+}
+
+/// Serialising deeply-nested or endlessly recursive types returns an error
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn max_depth() -> Result {
+    /// Contains itself, like a cycle of `Rc`s.
+    struct Cycle;
+
+    impl Serialize for Cycle {
+        fn serialize<S: serde::Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+            use serde::ser::SerializeStruct;
+            let mut t = s.serialize_struct("Cycle", 1)?;
+            t.serialize_field("next", self)?;
+            t.end()
+        }
+    }
+
+    let err = to_vec(&Cycle, LuaFormat::Value).unwrap_err();
+    assert_eq!(
+        &Error::RecursionLimit {
+            limit: DEFAULT_SERIALIZE_MAX_DEPTH
+        },
+        err.without_path(),
+    );
+    assert_eq!(
+        Some(
+            vec!["next"; usize::from(DEFAULT_SERIALIZE_MAX_DEPTH)]
+                .join(".")
+                .as_str()
+        ),
+        err.path(),
+    );
+
+    let err = to_vec(&Cycle, LuaFormat::Script).unwrap_err();
+    assert_eq!(
+        &Error::RecursionLimit {
+            limit: DEFAULT_SERIALIZE_MAX_DEPTH
+        },
+        err.without_path(),
+    );
+
+    // Every table counts, including enum variants and table keys
+    let ser = || Serializer::new(Vec::new()).max_depth(Some(2));
+    for (value, ok) in [
+        (to_value(&vec![vec![1]])?, true),
+        (to_value(&vec![vec![vec![1]]])?, false),
+        (to_value(&vec![Shape::Circle(1.)])?, true),
+        (to_value(&vec![Shape::Point(1, 2)])?, false),
+        (to_value(&Shape::Point(1, 2))?, true),
+        (lua_value(b"{[{1}] = 1}", MAX_DEPTH)?, true),
+        (lua_value(b"{[{{1}}] = 1}", MAX_DEPTH)?, false),
+    ] {
+        let mut s = ser();
+        let r = value.serialize(&mut s);
+        if ok {
+            r?;
+            let lua = s.into_inner();
+            assert_eq!(value, lua_value(&lua, 2)?);
+        } else {
+            assert_eq!(
+                &Error::RecursionLimit { limit: 2 },
+                r.unwrap_err().without_path(),
+                "{value:?}",
+            );
+        }
+    }
+
+    let mut s = Serializer::with_format(Vec::new(), LuaFormat::Script).max_depth(Some(1));
+    assert!(BTreeMap::from([("a", vec![1])]).serialize(&mut s).is_err());
+
+    // Disable the limit
+    let mut value = LuaValue::integer(1);
+    for _ in 0..DEFAULT_SERIALIZE_MAX_DEPTH + 1 {
+        value = LuaValue::Table(vec![LuaTableEntry::from(value)]);
+    }
+    assert!(to_vec(&value, LuaFormat::Value).is_err());
+    let mut s = Serializer::new(Vec::new()).max_depth(None);
+    value.serialize(&mut s)?;
+    let lua = s.into_inner();
+    assert_eq!(value, lua_value(&lua, DEFAULT_SERIALIZE_MAX_DEPTH + 1)?);
+
+    Ok(())
+}
+
+/// Floats with a fixed precision, like `%.14g`
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn float_precision() -> Result {
+    // Expected output is from C's printf(), with `.0` added to integral values
+    for (v, p14, p17, p1) in [
+        (0.1, "0.1", "0.10000000000000001", "0.1"),
+        (0.5, "0.5", "0.5", "0.5"),
+        (1.0, "1.0", "1.0", "1.0"),
+        (-1.0, "-1.0", "-1.0", "-1.0"),
+        (100.0, "100.0", "100.0", "1e+02"),
+        (1e15, "1e+15", "1000000000000000.0", "1e+15"),
+        (1e16, "1e+16", "10000000000000000.0", "1e+16"),
+        (1e17, "1e+17", "1e+17", "1e+17"),
+        (
+            123456789012345678.0,
+            "1.2345678901235e+17",
+            "1.2345678901234568e+17",
+            "1e+17",
+        ),
+        (0.0001, "0.0001", "0.0001", "0.0001"),
+        (0.00001, "1e-05", "1.0000000000000001e-05", "1e-05"),
+        (1.5e-7, "1.5e-07", "1.4999999999999999e-07", "1e-07"),
+        (-0.0, "-0.0", "-0.0", "-0.0"),
+        (
+            std::f64::consts::PI,
+            "3.1415926535898",
+            "3.1415926535897931",
+            "3.0",
+        ),
+        (2.5e-308, "2.5e-308", "2.4999999999999998e-308", "2e-308"),
+        (
+            1.7976931348623157e308,
+            "1.7976931348623e+308",
+            "1.7976931348623157e+308",
+            "2e+308",
+        ),
+        (9.9999999999999999, "10.0", "10.0", "1e+01"),
+        (0.30000000000000004, "0.3", "0.30000000000000004", "0.3"),
+        (
+            5e-324,
+            "4.9406564584125e-324",
+            "4.9406564584124654e-324",
+            "5e-324",
+        ),
+        (99999999999999.5, "1e+14", "99999999999999.5", "1e+14"),
+        (f64::INFINITY, "1e9999", "1e9999", "1e9999"),
+        (f64::NEG_INFINITY, "-1e9999", "-1e9999", "-1e9999"),
+    ] {
+        for (precision, expected) in [(14, p14), (17, p17), (1, p1), (0, p1), (255, p17)] {
+            let f = DefaultFormatter::new().float_format(FloatFormat::Precision(precision));
+            let lua = to_string_with(&v, LuaFormat::Value, f)?;
+            assert_eq!(expected, lua, "{v:?} with precision {precision}");
+
+            // Always read back as a float, and exactly with 17 digits
+            let LuaValue::Number(LuaNumber::Float(f)) = lua_value(lua.as_bytes(), MAX_DEPTH)?
+            else {
+                panic!("{lua} is not a float");
+            };
+            if expected == p17 {
+                assert_eq!(v.to_bits(), f.to_bits(), "{lua}");
+            }
+        }
+    }
+
+    let f = DefaultFormatter::new().float_format(FloatFormat::Precision(14));
+    assert_eq!("(0/0)", to_string_with(&f64::NAN, LuaFormat::Value, f)?);
+
+    // Shortest is the default
+    for v in [0.1, 1.0, 1e100, 1.5e-7, 5e-324] {
+        assert_eq!(
+            to_string(&v, LuaFormat::Value)?,
+            to_string_with(
+                &v,
+                LuaFormat::Value,
+                DefaultFormatter::new().float_format(FloatFormat::Shortest)
+            )?,
+        );
+    }
+
+    Ok(())
+}
+
+/// Scripts written with each line ending, read back again
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn line_endings() -> Result {
+    let save = example_save();
+    let strings = BTreeMap::from([("a", "line 1\nline 2\r\n"), ("b", "\n\r"), ("c", "")]);
+    let owned: BTreeMap<String, String> = strings
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let strict = Parser::builder().strict_q(true).build();
+
+    for (line_ending, eol) in [
+        (LineEnding::Lf, "\n"),
+        (LineEnding::CrLf, "\r\n"),
+        (LineEnding::Cr, "\r"),
+    ] {
+        assert_eq!(eol.as_bytes(), line_ending.as_bytes());
+        let f = DefaultFormatter::new().line_ending(line_ending);
+
+        for format in [
+            LuaFormat::Value,
+            LuaFormat::Return,
+            LuaFormat::Script,
+            LuaFormat::ScriptReturn,
+        ] {
+            let lua = to_string_with(&save, format, f)?;
+            assert_eq!(save, from_slice(lua.as_bytes(), format, MAX_DEPTH)?);
+
+            let lua = to_string_with(&strings, format, f)?;
+            assert_eq!(
+                owned,
+                from_slice::<BTreeMap<String, String>>(lua.as_bytes(), format, MAX_DEPTH)?,
+                "{line_ending:?} {format:?}: {lua:?}",
+            );
+
+            // The only line breaks are the line endings. With `Lf`, strings also contain escaped
+            // line feeds.
+            if line_ending != LineEnding::Lf {
+                let statements = if matches!(format, LuaFormat::Script | LuaFormat::ScriptReturn) {
+                    strings.len()
+                } else {
+                    0
+                };
+                assert_eq!(statements, lua.matches(eol).count(), "{lua:?}");
+                assert_eq!(lua.replace(eol, "").find(['\r', '\n']), None, "{lua:?}");
+            }
+        }
+
+        // Also readable in strict `%q` mode
+        let lua = to_string_with(&strings, LuaFormat::Script, f)?;
+        assert_eq!(strings.len(), strict.script(lua.as_bytes())?.len());
+    }
+
+    assert_eq!(
+        "a = \"line 1\\10line 2\\r\\10\"\rb = \"\\10\\r\"\rc = \"\"\r",
+        to_string_with(
+            &strings,
+            LuaFormat::Script,
+            DefaultFormatter::new().line_ending(LineEnding::Cr)
+        )?,
+    );
+
+    Ok(())
+}
+
+/// Unsigned integers greater than `i64::MAX` are written in hexadecimal
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn unsigned_integers() -> Result {
+    let opts = DeserializeOptions::new().unsigned_integers(true);
+    for (v, expected) in [
+        (0, "0"),
+        (i64::MAX as u64, "9223372036854775807"),
+        (1 << 63, "0x8000000000000000"),
+        (u64::MAX - 1, "0xfffffffffffffffe"),
+        (u64::MAX, "0xffffffffffffffff"),
+    ] {
+        let lua = to_string(&v, LuaFormat::Value)?;
+        assert_eq!(expected, lua);
+        assert_eq!(
+            v,
+            from_slice_with_options::<u64>(lua.as_bytes(), LuaFormat::Value, MAX_DEPTH, &opts)?
+        );
+        assert_eq!(
+            expected,
+            to_string(&u128::from(v), LuaFormat::Value)?,
+            "u128 {v}"
+        );
+
+        // Lua reads these as the i64 with the same bits
+        assert_eq!(
+            LuaValue::integer(v as i64),
+            lua_value(lua.as_bytes(), MAX_DEPTH)?
+        );
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Ids {
+        id: u64,
+        ids: Vec<u64>,
+        names: BTreeMap<u64, String>,
+    }
+
+    let ids = Ids {
+        id: u64::MAX,
+        ids: vec![1, u64::MAX, 1 << 63],
+        names: BTreeMap::from([(2, "two".to_string()), (u64::MAX, "max".to_string())]),
+    };
+    let lua = to_string(&ids, LuaFormat::Script)?;
+    assert_eq!(
+        concat!(
+            "id = 0xffffffffffffffff\n",
+            "ids = {1, 0xffffffffffffffff, 0x8000000000000000}\n",
+            "names = {[2] = \"two\", [0xffffffffffffffff] = \"max\"}\n",
+        ),
+        lua
+    );
+    assert_eq!(
+        ids,
+        from_slice_with_options(lua.as_bytes(), LuaFormat::Script, MAX_DEPTH, &opts)?
+    );
+
+    Ok(())
+}
+
+/// `estimate_serialized_size()` matches the size of the output
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn estimate_size() -> Result {
+    for lua in [
+        b"nil".as_slice(),
+        b"true",
+        b"false",
+        b"0",
+        b"-12345",
+        b"-0x8000000000000000",
+        b"0.1",
+        b"-1e300",
+        b"1e-300",
+        b"(0/0)",
+        b"1e9999",
+        b"''",
+        b"'hello'",
+        b"'\\0\\1\\\"\\\\\\n\\r\\t\\1272'",
+        b"'\\xff\\xfe1 \\xe2\\x82'",
+        b"{}",
+        b"{1, 2.5, true, nil, 'a', {}}",
+        b"{a = 1, ['b c'] = 2, [3] = {x = {y = {}}}, [false] = 4, [1.5] = 'z', ['and'] = 5}",
+        b"{1, a = 2, 3, ['\\xff'] = 4, [{1, b = 2}] = 5, [-0x8000000000000000] = 6}",
+    ] {
+        let value = lua_value(lua, MAX_DEPTH)?;
+        assert_eq!(
+            to_vec(&value, LuaFormat::Value)?.len(),
+            estimate_serialized_size(&value),
+            "{}",
+            lua.escape_ascii()
+        );
+        assert_eq!(
+            to_vec(&value, LuaFormat::Return)?.len(),
+            estimate_serialized_size(&value) + 7,
+            "{}",
+            lua.escape_ascii()
+        );
+    }
+
+    // Compact entries
+    let value = LuaValue::Table(vec![
+        LuaTableEntry::NumberValue(LuaNumber::Integer(i64::MIN)),
+        LuaTableEntry::NumberValue(LuaNumber::Float(2.)),
+        LuaTableEntry::BooleanValue(false),
+        LuaTableEntry::NilValue,
+        LuaTableEntry::KeyValue(Box::new((
+            LuaValue::Number(LuaNumber::Integer(-1)),
+            LuaValue::from("x"),
+        ))),
+    ]);
+    assert_eq!(
+        to_vec(&value, LuaFormat::Value)?.len(),
+        estimate_serialized_size(&value)
+    );
+
+    Ok(())
+}
+
+/// Comments captured by the parser are written back out by `serialize_script()`
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn script_comments() -> Result {
+    let lua = b"-- header\n--[[ player\n data ]]\nplayer = {name = \"alice\"}\nlevel = 3 -- start\n\n-- gold\ngold = 10\n";
+    let p = Parser::builder().comments(true).build();
+    let statements = p.script(lua)?;
+
+    let mut ser = Serializer::with_format(Vec::new(), LuaFormat::Script);
+    ser.serialize_script(&statements)?;
+    let out = ser.into_inner();
+    assert_eq!(
+        "-- header\n--[[ player\n data ]]\nplayer = {name = \"alice\"}\nlevel = 3\n-- gold\ngold = 10\n",
+        String::from_utf8(out.clone())?
+    );
+    assert_eq!(
+        statements,
+        p.script(&out)?
+            .into_iter()
+            .map(|mut s| {
+                // Spans and indexes are relative to the new output
+                let old = &statements[s.index];
+                s.span = old.span.clone();
+                s
+            })
+            .collect::<Vec<_>>()
+    );
+
+    // Other line endings
+    let f = DefaultFormatter::new().line_ending(LineEnding::CrLf);
+    let mut ser = Serializer::with_formatter(Vec::new(), LuaFormat::Script, f);
+    ser.serialize_script(&statements[2..])?;
+    assert_eq!(b"-- gold\r\ngold = 10\r\n", ser.into_inner().as_slice());
+
+    // Comments must only contain comments
+    let mut bad = statements.clone();
+    bad[1].comment = Some(b"x = 1");
+    let mut ser = Serializer::with_format(Vec::new(), LuaFormat::Script);
+    assert!(ser.serialize_script(&bad).is_err());
+    bad[1].comment = Some(b"--[[ unclosed");
+    let mut ser = Serializer::with_format(Vec::new(), LuaFormat::Script);
+    assert!(ser.serialize_script(&bad).is_err());
+
+    // Only scripts can be written
+    let mut ser = Serializer::with_format(Vec::new(), LuaFormat::Value);
+    assert!(ser.serialize_script(&statements).is_err());
+
+    Ok(())
+}
+
+/// `check_roundtrip()` reports what isn't written back out exactly
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn roundtrip_report() -> Result {
+    let opts = ParseOptions::new(MAX_DEPTH);
+
+    for lua in [
+        b"".as_slice(),
+        b"a = 1\n",
+        b"-- header\n\n-- a\na = {1, 2.5, b = \"c\"}\n--[[ d ]]\nd = nil\n",
+    ] {
+        let report = check_roundtrip(lua, &opts)?;
+        assert!(report.is_exact(), "{}: {report:?}", lua.escape_ascii());
+        assert_eq!(lua, report.output.as_slice());
+    }
+
+    let opts = Parser::builder()
+        .max_depth(MAX_DEPTH)
+        .local_assignments(true)
+        .field_assignments(true)
+        .build_options();
+    let lua = b"local a = {x = 1}\na.y = 2\nb = 0x10;\n\n\nc = 'c' -- c\nd = 1";
+    let report = check_roundtrip(lua, &opts)?;
+    assert_eq!(
+        b"a = {x = 1, y = 2}\nb = 16\nc = \"c\"\nd = 1\n".as_slice(),
+        report.output.as_slice()
+    );
+    let differences = report
+        .differences
+        .iter()
+        .map(|d| (d.reason, lua[d.span.clone()].escape_ascii().to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            (RoundTripReason::Local, "local ".to_string()),
+            (RoundTripReason::Value, "".to_string()),
+            (RoundTripReason::Dropped, "a.y = 2\\n".to_string()),
+            (RoundTripReason::Value, "0x10".to_string()),
+            (RoundTripReason::Whitespace, ";\\n\\n".to_string()),
+            (RoundTripReason::Value, "\\'c\\'".to_string()),
+            (RoundTripReason::Dropped, " -- c".to_string()),
+            // The missing line break at the end
+            (RoundTripReason::Whitespace, "".to_string()),
+        ],
+        differences
+    );
+
+    Ok(())
+}
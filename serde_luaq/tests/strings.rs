@@ -1,8 +1,14 @@
 //! String literal tests
+// Tests use `\0` followed by digits, which Rust doesn't read as an octal escape.
+#![allow(clippy::octal_escapes)]
 mod common;
 
 use crate::common::{check, should_error, MAX_DEPTH};
-use serde_luaq::{lua_value, LuaTableEntry, LuaValue};
+use serde_luaq::{
+    lua_value, unescape_long_string, unescape_short_string, Error, InvalidUnicodeEscapes,
+    LuaTableEntry, LuaValue, Parser,
+};
+use std::borrow::Cow;
 
 #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
 use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
@@ -782,3 +788,88 @@ fn borrows() -> Result {
 
     Ok(())
 }
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn invalid_unicode_escapes() -> Result {
+    let replace = Parser::builder()
+        .max_depth(MAX_DEPTH)
+        .invalid_unicode_escapes(InvalidUnicodeEscapes::Replace)
+        .build();
+    let error = Parser::builder()
+        .max_depth(MAX_DEPTH)
+        .invalid_unicode_escapes(InvalidUnicodeEscapes::Error)
+        .build();
+
+    for escape in [
+        br"\u{d800}".as_slice(),
+        br"\u{dfff}",
+        br"\u{110000}",
+        br"\u{7fffffff}",
+    ] {
+        let lua = [b"{'a", escape, b"b'}"].concat();
+        assert_eq!(
+            LuaValue::Table(vec![LuaTableEntry::from(LuaValue::from("a\u{FFFD}b"))]),
+            replace.value(&lua)?,
+        );
+
+        let Error::Peg(e) = error.value(&lua).unwrap_err() else {
+            panic!("expected peg error");
+        };
+        // Error is reported at the end of the escape sequence
+        assert_eq!(lua.len() - 3, e.location);
+    }
+
+    // Valid escapes are unaffected
+    for parser in [&replace, &error] {
+        assert_eq!(
+            LuaValue::from("\u{d7ff}\u{e000}\u{10ffff}"),
+            parser.value(br"'\u{d7ff}\u{e000}\u{10ffff}'")?
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn unescape() -> Result {
+    // Should match the parser
+    for body in [
+        br"".as_slice(),
+        br"hello",
+        br"\a\b\f\n\r\t\v\\\'",
+        br#"\"\'"#,
+        b"line\\\nbreak",
+        b"skip \\z  \n  whitespace",
+        br"\x41\65\u{42}\u{d800}",
+    ] {
+        for quote in [b"'", b"\""] {
+            let lua = [quote.as_slice(), body, quote].concat();
+            assert_eq!(
+                lua_value(&lua, MAX_DEPTH)?,
+                LuaValue::String(unescape_short_string(body)?)
+            );
+        }
+    }
+
+    // Either type of quote is allowed
+    assert_eq!(br#"'""#, unescape_short_string(br#"'""#)?.as_ref());
+
+    // Strings without escapes are borrowed
+    assert!(matches!(
+        unescape_short_string(b"hello")?,
+        Cow::Borrowed(b"hello")
+    ));
+    assert!(unescape_short_string(b"\\").is_err());
+    assert!(unescape_short_string(b"a\nb").is_err());
+    assert!(unescape_short_string(br"\xZZ").is_err());
+
+    assert_eq!(b"", unescape_long_string(b"", 0)?.as_ref());
+    assert_eq!(b"", unescape_long_string(b"\r\n", 0)?.as_ref());
+    assert_eq!(b"\\n\n", unescape_long_string(b"\n\\n\n", 0)?.as_ref());
+    assert_eq!(b"]]]=]", unescape_long_string(b"]]]=]", 7)?.as_ref());
+    assert!(unescape_long_string(b"]=======]", 7).is_err());
+    assert!(unescape_long_string(b"]]", 0).is_err());
+    Ok(())
+}
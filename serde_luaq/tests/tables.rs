@@ -1,7 +1,12 @@
+// Tests use literals which look like (but aren't) well-known constants.
+#![allow(clippy::approx_constant)]
 mod common;
 
 use crate::common::{check, should_error, MAX_DEPTH};
-use serde_luaq::{lua_value, script, LuaNumber, LuaTableEntry, LuaValue};
+use serde_luaq::{
+    lua_value, return_statement, script, LuaNumber, LuaTable, LuaTableEntry, LuaTableExt, LuaValue,
+    RECOMMENDED_MAX_DEPTH_MAIN_THREAD, RECOMMENDED_MAX_DEPTH_SPAWNED_THREAD,
+};
 
 #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
 use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
@@ -194,6 +199,51 @@ fn recursion() -> Result {
     Ok(())
 }
 
+/// Parses tables nested exactly `depth` levels deep in every entry point, with implicit, name and
+/// bracketed keys, and drops them.
+fn parse_nested(depth: u16) {
+    for (open, close) in [("{", "}"), ("{a = ", "}"), ("{[", "] = 1}")] {
+        let n = usize::from(depth) - 1;
+        let lua = format!("{}{{}}{}", open.repeat(n), close.repeat(n));
+
+        assert!(lua_value(lua.as_bytes(), depth).is_ok(), "{open}");
+        assert!(lua_value(lua.as_bytes(), depth - 1).is_err(), "{open}");
+        assert!(return_statement(format!("return {lua}").as_bytes(), depth).is_ok());
+        assert!(script(format!("a = {lua}").as_bytes(), depth).is_ok());
+    }
+}
+
+/// [`RECOMMENDED_MAX_DEPTH_MAIN_THREAD`] fits in the smallest main thread stack of any supported
+/// platform.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn recommended_max_depth_main_thread() {
+    // wasm32 runs tests on its main thread, which has a 1 MiB stack.
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    parse_nested(RECOMMENDED_MAX_DEPTH_MAIN_THREAD);
+
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    std::thread::Builder::new()
+        .stack_size(1 << 20)
+        .spawn(|| parse_nested(RECOMMENDED_MAX_DEPTH_MAIN_THREAD))
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+/// [`RECOMMENDED_MAX_DEPTH_SPAWNED_THREAD`] fits in Rust's default stack size for spawned threads.
+#[test]
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn recommended_max_depth_spawned_thread() {
+    // Rust's default, unless it's changed with `RUST_MIN_STACK`.
+    std::thread::Builder::new()
+        .stack_size(2 << 20)
+        .spawn(|| parse_nested(RECOMMENDED_MAX_DEPTH_SPAWNED_THREAD))
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
 /// Tests for handling long strings in tables.
 #[test]
 #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
@@ -237,3 +287,222 @@ fn long_string_tables() -> Result {
 
     Ok(())
 }
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn edit_by_key() -> Result {
+    let t = lua_value(
+        br#"{"a", name = "b", nil, ["key"] = "c", [2.5] = "d", [true] = "e", 3, [-1] = "f"}"#,
+        MAX_DEPTH,
+    )?
+    .try_into_table()?;
+
+    for (key, expected) in [
+        (LuaValue::integer(1), Some(0)),
+        (LuaValue::float(1.0), Some(0)),
+        (LuaValue::integer(2), Some(2)),
+        (LuaValue::integer(3), Some(6)),
+        (LuaValue::integer(4), None),
+        (LuaValue::integer(-1), Some(7)),
+        (LuaValue::float(-1.0), Some(7)),
+        (LuaValue::String(b"name".into()), Some(1)),
+        (LuaValue::String(b"key".into()), Some(3)),
+        (LuaValue::String(b"nam".into()), None),
+        (LuaValue::float(2.5), Some(4)),
+        (LuaValue::Boolean(true), Some(5)),
+        (LuaValue::Boolean(false), None),
+        (LuaValue::Nil, None),
+        (LuaValue::float(f64::NAN), None),
+        (LuaValue::Table(vec![]), None),
+    ] {
+        assert_eq!(expected, t.position_by_key(&key), "{key:?}");
+    }
+
+    // Implicit keys are counted before anything is removed
+    let mut u = t.clone();
+    let mut keys = vec![];
+    u.retain_by_key(|k| {
+        keys.push(k.clone().into_owned());
+        !matches!(k, LuaValue::Number(LuaNumber::Integer(i)) if *i >= 2)
+    });
+    assert_eq!(t.len(), keys.len());
+    assert_eq!(LuaValue::integer(3), keys[6]);
+    assert_eq!(
+        lua_value(
+            br#"{"a", name = "b", ["key"] = "c", [2.5] = "d", [true] = "e", [-1] = "f"}"#,
+            MAX_DEPTH,
+        )?,
+        LuaValue::Table(u),
+    );
+
+    // Remove the first matching entry only
+    let mut u = lua_value(br#"{a = 1, ["a"] = 2}"#, MAX_DEPTH)?.try_into_table()?;
+    let removed = u.remove_by_key(&LuaValue::String(b"a".into()));
+    assert_eq!(
+        Some(&LuaValue::integer(1)),
+        removed.as_ref().and_then(|e| e.value())
+    );
+    assert_eq!(Some(0), u.position_by_key(&LuaValue::String(b"a".into())));
+    assert!(u.remove_by_key(&LuaValue::String(b"a".into())).is_some());
+    assert!(u.remove_by_key(&LuaValue::String(b"a".into())).is_none());
+    assert!(u.is_empty());
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn get_by_key() -> Result {
+    let v = lua_value(
+        br#"{"a", name = "b", nil, ["key"] = "c", [2.5] = {x = {1, 2}}, [true] = "e", 3, [3] = "g", key = "h"}"#,
+        MAX_DEPTH,
+    )?;
+
+    for (key, expected) in [
+        (LuaValue::integer(1), Some(LuaValue::from("a"))),
+        (LuaValue::float(1.0), Some(LuaValue::from("a"))),
+        (LuaValue::integer(2), Some(LuaValue::Nil)),
+        // Later entries win
+        (LuaValue::integer(3), Some(LuaValue::from("g"))),
+        (LuaValue::from("key"), Some(LuaValue::from("h"))),
+        (LuaValue::integer(4), None),
+        (LuaValue::from("name"), Some(LuaValue::from("b"))),
+        (LuaValue::Boolean(true), Some(LuaValue::from("e"))),
+        (LuaValue::Boolean(false), None),
+        (LuaValue::Nil, None),
+        (LuaValue::float(f64::NAN), None),
+        (LuaValue::Table(vec![]), None),
+    ] {
+        assert_eq!(expected.as_ref(), v.get(key.clone()).as_deref(), "{key:?}");
+    }
+
+    assert_eq!(
+        Some(&LuaValue::integer(2)),
+        v.get_path([LuaValue::float(2.5), "x".into(), 2.into()])
+            .as_deref()
+    );
+    assert_eq!(
+        None,
+        v.get_path([LuaValue::float(2.5), "x".into(), 3.into()])
+    );
+    assert_eq!(None, v.get_path(["name", "x"]));
+    assert_eq!(None, v.get_path([1, 1]));
+    assert_eq!(None, LuaValue::integer(1).get(1));
+
+    // Mutable lookups use the same entries, expanding compact entries
+    let mut u = v.clone();
+    *u.get_mut(3).ok_or("missing 3")? = LuaValue::from("x");
+    *u.get_mut("key").ok_or("missing key")? = LuaValue::from("y");
+    *u.get_mut(1).ok_or("missing 1")? = LuaValue::integer(1);
+    *u.get_mut(2).ok_or("missing 2")? = LuaValue::Boolean(false);
+    assert!(u.get_mut(4).is_none());
+    assert!(u.get_mut(LuaValue::Nil).is_none());
+    assert!(LuaValue::integer(1).get_mut(1).is_none());
+    assert_eq!(
+        lua_value(
+            br#"{1, name = "b", false, ["key"] = "c", [2.5] = {x = {1, 2}}, [true] = "e", 3, [3] = "x", key = "y"}"#,
+            MAX_DEPTH,
+        )?,
+        u,
+    );
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn normalised_table() -> Result {
+    let t = lua_value(
+        br#"{"a", name = "b", nil, ["name"] = "c", [2.5] = "d", [true] = "e", [2] = "f", [4] = "g", x = 1, x = nil}"#,
+        MAX_DEPTH,
+    )?
+    .try_into_lua_table()?;
+
+    // [2] = "f" is assigned before the implicitly-keyed nil
+    assert_eq!(&[LuaValue::from("a")], t.array_part());
+    assert_eq!(
+        &[
+            (LuaValue::from("name"), LuaValue::from("c")),
+            (LuaValue::float(2.5), LuaValue::from("d")),
+            (LuaValue::Boolean(true), LuaValue::from("e")),
+            (LuaValue::integer(4), LuaValue::from("g")),
+        ],
+        t.map_part(),
+    );
+    assert_eq!(5, t.len());
+    assert_eq!(Some(&LuaValue::from("a")), t.get(&LuaValue::float(1.0)));
+    assert_eq!(Some(&LuaValue::from("g")), t.get(&LuaValue::integer(4)));
+    assert_eq!(Some(&LuaValue::from("c")), t.get(&LuaValue::from("name")));
+    assert_eq!(None, t.get(&LuaValue::integer(2)));
+    assert_eq!(None, t.get(&LuaValue::from("x")));
+    assert_eq!(None, t.get(&LuaValue::Nil));
+
+    assert_eq!(
+        lua_value(
+            br#"{"a", name = "c", [2.5] = "d", [true] = "e", [4] = "g"}"#,
+            MAX_DEPTH
+        )?,
+        LuaValue::from(t.clone()),
+    );
+    let (array, map) = t.into_parts();
+    assert_eq!(1, array.len());
+    assert_eq!(4, map.len());
+
+    // Keys in the map part can fill the array part
+    let t = LuaTable::try_from(lua_value(b"{[3] = 3, [2.0] = 2, 1, {}}", MAX_DEPTH)?)?;
+    assert_eq!(
+        &[
+            LuaValue::integer(1),
+            LuaValue::Table(vec![]),
+            LuaValue::integer(3)
+        ],
+        t.array_part(),
+    );
+    assert!(t.map_part().is_empty());
+
+    // Table keys are never equal
+    let t = lua_value(b"{[{}] = 1, [{}] = 2}", MAX_DEPTH)?.try_into_lua_table()?;
+    assert_eq!(2, t.map_part().len());
+
+    assert!(LuaTable::new().is_empty());
+    assert_eq!(LuaTable::new(), LuaTable::from_entries(vec![])?);
+    Ok(())
+}
+
+/// Implicitly-keyed entries are assigned in batches, like Lua's table constructor.
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn normalised_table_batches() -> Result {
+    for (implicit, expected) in [
+        (1, LuaValue::integer(1)),
+        (49, LuaValue::integer(1)),
+        (50, LuaValue::from("x")),
+        (51, LuaValue::from("x")),
+    ] {
+        let mut entries: Vec<LuaTableEntry> = (1..=implicit).map(LuaTableEntry::from).collect();
+        entries.push(LuaTableEntry::KeyValue(Box::new((
+            LuaValue::integer(1),
+            LuaValue::from("x"),
+        ))));
+        let t = LuaTable::from_entries(entries)?;
+        assert_eq!(implicit as usize, t.array_part().len());
+        assert_eq!(expected, t.array_part()[0], "{implicit} entries");
+    }
+
+    // An implicitly-keyed nil removes an explicit key
+    let t = lua_value(b"{[1] = 1, nil}", MAX_DEPTH)?.try_into_lua_table()?;
+    assert!(t.is_empty());
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
+fn normalised_table_errors() -> Result {
+    let e = LuaValue::integer(1).try_into_lua_table().unwrap_err();
+    assert_eq!(("table", "integer"), (e.expected, e.actual_type));
+
+    for key in [LuaValue::Nil, LuaValue::float(f64::NAN)] {
+        let t = LuaValue::Table(vec![LuaTableEntry::KeyValue(Box::new((key, 1.into())))]);
+        assert!(t.try_into_lua_table().is_err());
+    }
+    Ok(())
+}
@@ -121,15 +121,6 @@ fn block_do() {
     assert!(script(b"a = 3\ndo\n  a = 4\nend\n", MAX_DEPTH).is_err());
 }
 
-#[test]
-#[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
-fn comments() {
-    assert!(script(b"-- line comment\na = 3\n", MAX_DEPTH).is_err());
-    assert!(script(b"--[[ long comment ]]a = 3\n", MAX_DEPTH).is_err());
-    assert!(script(b"--[[\nlong comment\n]]\na = 3\n", MAX_DEPTH).is_err());
-    assert!(script(b"--[==[\nlonger comment\n]==]\na = 3\n", MAX_DEPTH).is_err());
-}
-
 #[test]
 #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
 fn coroutine() {
@@ -163,6 +154,7 @@ fn length_operator() {
 #[test]
 #[cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), wasm_bindgen_test)]
 fn local() {
+    // Unless ParseOptions::local_assignments is set
     assert!(script(b"local a = 3\n", MAX_DEPTH).is_err());
 }
 